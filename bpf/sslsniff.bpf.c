@@ -15,6 +15,22 @@ struct {
     __uint(max_entries, RING_BUFFER_SIZE);
 } rb SEC(".maps");
 
+/* events lost because the ring buffer was full, summed by userspace */
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, __u64);
+} rb_drops SEC(".maps");
+
+static __always_inline void count_rb_drop(void)
+{
+    __u32 zero = 0;
+    __u64 *drops = bpf_map_lookup_elem(&rb_drops, &zero);
+    if (drops)
+        (*drops)++;
+}
+
 struct {
     __uint(type, BPF_MAP_TYPE_HASH);
     __uint(max_entries, 10240);
@@ -48,13 +64,22 @@ struct {
     __type(value, __u64);
 } bufs SEC(".maps");
 
-const volatile pid_t targ_pid = 0;
+/* processes traced when filter_pids is set; userspace adds and removes
+ * entries while running */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, MAX_TARGET_PIDS);
+    __type(key, __u32);
+    __type(value, __u8);
+} target_pids SEC(".maps");
+
+const volatile bool filter_pids = false;
 const volatile uid_t targ_uid = -1;
 
 static __always_inline bool trace_allowed(u32 uid, u32 pid)
 {
     /* filters */
-    if (targ_pid && targ_pid != pid)
+    if (filter_pids && !bpf_map_lookup_elem(&target_pids, &pid))
         return false;
     if (targ_uid != -1) {
         if (targ_uid != uid) {
@@ -82,8 +107,61 @@ int BPF_UPROBE(probe_SSL_rw_enter, void *ssl, void *buf, int num) {
     return 0;
 }
 
+/* Copy `len` bytes of SSL data at `buf` into ring buffer events of at most
+ * MAX_BUF_SIZE bytes each. Every fragment carries the call's timestamp and
+ * its offset into the data, and the final one is marked `last`, so
+ * userspace can put the whole read or write back together. */
+static __always_inline void submit_ssl_data(u64 buf, u32 len, int rw, u64 ts, u64 delta_ns,
+                                            u32 pid, u32 tid, u32 uid)
+{
+    u32 offset = 0;
+
+    for (int i = 0; i < MAX_FRAGMENTS; i++) {
+        struct probe_SSL_data_t *data = bpf_ringbuf_reserve(&rb, sizeof(*data), 0);
+        if (!data) {
+            count_rb_drop();
+            return;
+        }
+
+        data->timestamp_ns = ts;
+        data->delta_ns = delta_ns;
+        data->pid = pid;
+        data->tid = tid;
+        data->uid = uid;
+        data->len = len;
+        data->offset = offset;
+        data->buf_filled = 0;
+        data->buf_size = 0;
+        data->rw = rw;
+        data->is_handshake = false;
+        bpf_get_current_comm(&data->comm, sizeof(data->comm));
+
+        /* Explicit bounds clamping to satisfy eBPF verifier
+         * Clamp to the buffer first, then mask so the range is known */
+        u32 buf_copy_size = len - offset;
+        if (buf_copy_size > MAX_BUF_SIZE)
+            buf_copy_size = MAX_BUF_SIZE;
+        buf_copy_size &= 0xFFFFF;  /* Mask to 20 bits (1MB-1) */
+
+        if (!bpf_probe_read_user(&data->buf, buf_copy_size, (char *)(buf + offset))) {
+            data->buf_filled = 1;
+            data->buf_size = buf_copy_size;
+            offset += buf_copy_size;
+            data->last = offset >= len || i == MAX_FRAGMENTS - 1;
+        } else {
+            /* the rest of the data is unreadable too */
+            data->last = 1;
+        }
+
+        bool last = data->last;
+        /* submit to ring buffer */
+        bpf_ringbuf_submit(data, 0);
+        if (last)
+            return;
+    }
+}
+
 static int SSL_exit(struct pt_regs *ctx, int rw) {
-    int ret = 0;
     u64 pid_tgid = bpf_get_current_pid_tgid();
     u32 pid = pid_tgid >> 32;
     u32 tid = (u32)pid_tgid;
@@ -108,41 +186,11 @@ static int SSL_exit(struct pt_regs *ctx, int rw) {
     if (len <= 0)  // no data
         return 0;
 
-    /* reserve space in ring buffer */
-    struct probe_SSL_data_t *data = bpf_ringbuf_reserve(&rb, sizeof(*data), 0);
-    if (!data)
-        return 0;
-
-    data->timestamp_ns = ts;
-    data->delta_ns = delta_ns;
-    data->pid = pid;
-    data->tid = tid;
-    data->uid = uid;
-    data->len = (u32)len;
-    data->buf_filled = 0;
-    data->buf_size = 0;
-    data->rw = rw;
-    data->is_handshake = false;
-    u32 buf_copy_size = min((size_t)MAX_BUF_SIZE, (size_t)len);
-
-    bpf_get_current_comm(&data->comm, sizeof(data->comm));
-
-    if (bufp != 0)
-        ret = bpf_probe_read_user(&data->buf, buf_copy_size, (char *)*bufp);
-
+    u64 buf = *bufp;
     bpf_map_delete_elem(&bufs, &tid);
     bpf_map_delete_elem(&start_ns, &tid);
 
-    if (!ret) {
-        data->buf_filled = 1;
-        data->buf_size = buf_copy_size;
-    } else {
-        data->buf_filled = 0;
-        data->buf_size = 0;
-    }
-
-    /* submit to ring buffer */
-    bpf_ringbuf_submit(data, 0);
+    submit_ssl_data(buf, (u32)len, rw, ts, delta_ns, pid, tid, uid);
     return 0;
 }
 
@@ -197,7 +245,6 @@ int BPF_UPROBE(probe_SSL_read_ex_enter, void *ssl, void *buf, size_t num, size_t
 }
 
 static int ex_SSL_exit(struct pt_regs *ctx, int rw, int len) {
-    int ret = 0;
     u64 pid_tgid = bpf_get_current_pid_tgid();
     u32 pid = pid_tgid >> 32;
     u32 tid = (u32)pid_tgid;
@@ -218,50 +265,17 @@ static int ex_SSL_exit(struct pt_regs *ctx, int rw, int len) {
         return 0;
     u64 delta_ns = ts - *tsp;
 
-    if (len <= 0)  // no data
-        return 0;
-
-    /* reserve space in ring buffer */
-    struct probe_SSL_data_t *data = bpf_ringbuf_reserve(&rb, sizeof(*data), 0);
-    if (!data)
+    if (len <= 0) {  // failed call or no data
+        bpf_map_delete_elem(&bufs, &tid);
+        bpf_map_delete_elem(&start_ns, &tid);
         return 0;
+    }
 
-    data->timestamp_ns = ts;
-    data->delta_ns = delta_ns;
-    data->pid = pid;
-    data->tid = tid;
-    data->uid = uid;
-    data->len = (u32)len;
-    data->buf_filled = 0;
-    data->buf_size = 0;
-    data->rw = rw;
-    data->is_handshake = false;
-    
-    /* Explicit bounds clamping to satisfy eBPF verifier
-     * Use bitmask first to ensure value range, then clamp to actual max */
-    u32 buf_copy_size = (u32)len & 0xFFFFF;  /* Mask to 20 bits (1MB-1) */
-    if (buf_copy_size > MAX_BUF_SIZE)
-        buf_copy_size = MAX_BUF_SIZE;
-
-    bpf_get_current_comm(&data->comm, sizeof(data->comm));
-
-    if (bufp != 0)
-        ret = bpf_probe_read_user(&data->buf, buf_copy_size, (char *)*bufp);
-
+    u64 buf = *bufp;
     bpf_map_delete_elem(&bufs, &tid);
     bpf_map_delete_elem(&start_ns, &tid);
 
-    if (!ret) {
-        data->buf_filled = 1;
-        data->buf_size = buf_copy_size;
-    } else {
-        data->buf_filled = 0;
-        data->buf_size = 0;
-    }
-
-    /* submit to ring buffer */
-    bpf_ringbuf_submit(data, 0);
-    
+    submit_ssl_data(buf, (u32)len, rw, ts, delta_ns, pid, tid, uid);
     return 0;
 }
 
@@ -273,11 +287,13 @@ int BPF_URETPROBE(probe_SSL_write_ex_exit)
     if (!readbytes_ptr)
         return 0;
 
+    /* *readbytes is only set when the call succeeds (returns 1) */
     size_t written = 0;
-    bpf_probe_read_user(&written, sizeof(written), *readbytes_ptr);
+    int ret = PT_REGS_RC(ctx);
+    if (ret == 1)
+        bpf_probe_read_user(&written, sizeof(written), *readbytes_ptr);
     bpf_map_delete_elem(&readbytes_ptrs, &tid);
 
-    int ret = PT_REGS_RC(ctx);
     int len = (ret == 1) ? written : 0;
 
     return ex_SSL_exit(ctx, 1, len);
@@ -291,11 +307,13 @@ int BPF_URETPROBE(probe_SSL_read_ex_exit)
     if (!readbytes_ptr)
         return 0;
 
+    /* *readbytes is only set when the call succeeds (returns 1) */
     size_t written = 0;
-    bpf_probe_read_user(&written, sizeof(written), *readbytes_ptr);
+    int ret = PT_REGS_RC(ctx);
+    if (ret == 1)
+        bpf_probe_read_user(&written, sizeof(written), *readbytes_ptr);
     bpf_map_delete_elem(&readbytes_ptrs, &tid);
 
-    int ret = PT_REGS_RC(ctx);
     int len = (ret == 1) ? written : 0;
 
     return ex_SSL_exit(ctx, 0, len);
@@ -345,8 +363,10 @@ int BPF_URETPROBE(probe_SSL_do_handshake_exit) {
 
     /* reserve space in ring buffer */
     struct probe_SSL_data_t *data = bpf_ringbuf_reserve(&rb, sizeof(*data), 0);
-    if (!data)
+    if (!data) {
+        count_rb_drop();
         return 0;
+    }
 
     data->timestamp_ns = ts;
     data->delta_ns = ts - *tsp;
@@ -354,6 +374,8 @@ int BPF_URETPROBE(probe_SSL_do_handshake_exit) {
     data->tid = tid;
     data->uid = uid;
     data->len = ret;
+    data->offset = 0;
+    data->last = 1;
     data->buf_filled = 0;
     data->buf_size = 0;
     data->rw = 2;
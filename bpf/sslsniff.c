@@ -8,6 +8,7 @@
 #include <bpf/libbpf.h>
 #include <ctype.h>
 #include <errno.h>
+#include <fcntl.h>
 #include <signal.h>
 #include <stdio.h>
 #include <stdlib.h>
@@ -16,6 +17,7 @@
 #include <locale.h>
 #include <wchar.h>
 #include <string.h>
+#include <sys/stat.h>
 
 #include "sslsniff.skel.h"
 #include "sslsniff.h"
@@ -29,7 +31,7 @@
 	  LIBBPF_OPTS(bpf_uprobe_opts, uprobe_opts, .func_name = #sym_name,        \
 				  .retprobe = is_retprobe);                                    \
 	  skel->links.prog_name = bpf_program__attach_uprobe_opts(                 \
-		  skel->progs.prog_name, env.pid, binary_path, 0, &uprobe_opts);       \
+		  skel->progs.prog_name, uprobe_pid(), binary_path, 0, &uprobe_opts);  \
 	} while (false)
 
 #define __CHECK_PROGRAM(skel, prog_name)               \
@@ -47,10 +49,25 @@
 	  __CHECK_PROGRAM(skel, prog_name);                                     \
 	} while (false)
 
+/* Attach only if the library exports the symbol (e.g. SSL_read_ex is
+ * missing before OpenSSL 1.1.1); a missing symbol is not an error. */
+#define __ATTACH_UPROBE_OPTIONAL(skel, binary_path, sym_name, prog_name,    \
+								 is_retprobe)                               \
+	do {                                                                    \
+	  __ATTACH_UPROBE(skel, binary_path, sym_name, prog_name, is_retprobe); \
+	  if (!skel->links.prog_name && verbose)                                \
+		fprintf(stderr, "%s: " #sym_name " not found, skipping\n",          \
+				binary_path);                                               \
+	} while (false)
+
 #define ATTACH_UPROBE_CHECKED(skel, binary_path, sym_name, prog_name)     \
 	__ATTACH_UPROBE_CHECKED(skel, binary_path, sym_name, prog_name, false)
 #define ATTACH_URETPROBE_CHECKED(skel, binary_path, sym_name, prog_name)  \
 	__ATTACH_UPROBE_CHECKED(skel, binary_path, sym_name, prog_name, true)
+#define ATTACH_UPROBE_OPTIONAL(skel, binary_path, sym_name, prog_name)    \
+	__ATTACH_UPROBE_OPTIONAL(skel, binary_path, sym_name, prog_name, false)
+#define ATTACH_URETPROBE_OPTIONAL(skel, binary_path, sym_name, prog_name) \
+	__ATTACH_UPROBE_OPTIONAL(skel, binary_path, sym_name, prog_name, true)
 
 volatile sig_atomic_t exiting = 0;
 
@@ -73,7 +90,14 @@ const char argp_program_doc[] =
 	"    ./sslsniff --no-gnutls  # don't show GnuTLS calls\n"
 	"    ./sslsniff --no-nss     # don't show NSS calls\n"
 	"    ./sslsniff --handshake # show handshake events\n"
-	"    ./sslsniff --binary-path ~/.nvm/versions/node/v20.0.0/bin/node # attach to Node.js binary\n";
+	"    ./sslsniff --binary-path ~/.nvm/versions/node/v20.0.0/bin/node # attach to Node.js binary\n"
+	"    ./sslsniff -p 181 --lib /proc/181/root/opt/app/libssl.so.1.1 # also probe a second libssl for PID 181\n"
+	"    ./sslsniff --load-only  # load and unload the BPF programs, then exit\n"
+	"    ./sslsniff --ringbuf-size 16777216 # 16MB ring buffer for bursty traffic\n"
+	"    ./sslsniff --control    # accept attach/detach commands on stdin\n";
+
+/* --lib may be given this many times */
+#define MAX_PID_LIBS 8
 
 struct env {
 	pid_t pid;
@@ -83,7 +107,12 @@ struct env {
 	bool gnutls;
 	bool nss;
 	bool handshake;
+	bool load_only;
+	bool control;
 	char *extra_lib;
+	char *pid_libs[MAX_PID_LIBS];
+	int nr_pid_libs;
+	unsigned long ringbuf_size;
 } env = {
 	.uid = INVALID_UID,
 	.pid = INVALID_PID,
@@ -91,10 +120,22 @@ struct env {
 	.gnutls = false,
 	.nss = false,
 	.handshake = false,
+	.load_only = false,
+	.control = false,
 	.comm = NULL,
 };
 
+/* With --control, PIDs are filtered in the kernel through target_pids, so
+ * library probes must fire in every process, not only the -p one */
+static pid_t uprobe_pid(void) {
+	return env.control ? -1 : env.pid;
+}
+
 #define EXTRA_LIB_KEY 1003
+#define LOAD_ONLY_KEY 1004
+#define RINGBUF_SIZE_KEY 1005
+#define CONTROL_KEY 1006
+#define PID_LIB_KEY 1007
 
 static const struct argp_option opts[] = {
 	{"pid", 'p', "PID", 0, "Sniff this PID only."},
@@ -106,6 +147,10 @@ static const struct argp_option opts[] = {
 	{"handshake", 'h', NULL, 0, "Show handshake events."},
 	{"verbose", 'v', NULL, 0, "Verbose debug output"},
 	{"binary-path", EXTRA_LIB_KEY, "PATH", 0, "Attach to specific binary (e.g., ~/.nvm/versions/node/v20.0.0/bin/node)."},
+	{"load-only", LOAD_ONLY_KEY, NULL, 0, "Load the BPF programs, unload them and exit (self-test)."},
+	{"ringbuf-size", RINGBUF_SIZE_KEY, "BYTES", 0, "Ring buffer size; a power of two multiple of the page size."},
+	{"control", CONTROL_KEY, NULL, 0, "Read attach/detach commands on stdin."},
+	{"lib", PID_LIB_KEY, "PATH", 0, "Also probe this OpenSSL library for the -p process (repeatable)."},
 	{},
 };
 
@@ -140,13 +185,43 @@ static error_t parse_arg(int key, char *arg, struct argp_state *state) {
 	case EXTRA_LIB_KEY:
 		env.extra_lib = strdup(arg);
 		break;
+	case LOAD_ONLY_KEY:
+		env.load_only = true;
+		break;
+	case CONTROL_KEY:
+		env.control = true;
+		break;
+	case PID_LIB_KEY:
+		if (env.nr_pid_libs == MAX_PID_LIBS)
+			argp_error(state, "--lib may be given at most %d times", MAX_PID_LIBS);
+		env.pid_libs[env.nr_pid_libs++] = strdup(arg);
+		break;
+	case RINGBUF_SIZE_KEY: {
+		/* The kernel rejects ring buffers that are not a power of two
+		 * multiple of the page size */
+		unsigned long page_size = sysconf(_SC_PAGESIZE);
+		char *end;
+		env.ringbuf_size = strtoul(arg, &end, 10);
+		if (*end || env.ringbuf_size < page_size ||
+		    (env.ringbuf_size & (env.ringbuf_size - 1)))
+			argp_error(state, "--ringbuf-size must be a power of two of at least %lu bytes",
+				   page_size);
+		break;
+	}
 	default:
 		return ARGP_ERR_UNKNOWN;
 	}
 	return 0;
 }
 
-#define PERF_POLL_TIMEOUT_MS 100
+/*
+ * ring_buffer__poll() sleeps in epoll and wakes as soon as a record is
+ * submitted, so the timeout does not add latency; it only bounds how long
+ * an idle sensor sleeps between ring buffer drop checks.
+ */
+#define PERF_POLL_TIMEOUT_MS 1000
+#define DROP_REPORT_INTERVAL_MS 1000
+#define HEARTBEAT_INTERVAL_MS 5000
 #define warn(...) fprintf(stderr, __VA_ARGS__)
 
 static struct argp argp = {
@@ -175,10 +250,12 @@ int attach_openssl(struct sslsniff_bpf *skel, const char *lib) {
 	ATTACH_UPROBE_CHECKED(skel, lib, SSL_read, probe_SSL_rw_enter);
 	ATTACH_URETPROBE_CHECKED(skel, lib, SSL_read, probe_SSL_read_exit);
 
-	ATTACH_UPROBE_CHECKED(skel, lib, SSL_write_ex, probe_SSL_write_ex_enter);
-	ATTACH_URETPROBE_CHECKED(skel, lib, SSL_write_ex, probe_SSL_write_ex_exit);
-	ATTACH_UPROBE_CHECKED(skel, lib, SSL_read_ex, probe_SSL_read_ex_enter);
-	ATTACH_URETPROBE_CHECKED(skel, lib, SSL_read_ex, probe_SSL_read_ex_exit);
+	/* OpenSSL 1.1.1+ callers such as curl and Python's ssl
+	 * module use the _ex variants, which do not go through SSL_read/SSL_write */
+	ATTACH_UPROBE_OPTIONAL(skel, lib, SSL_write_ex, probe_SSL_write_ex_enter);
+	ATTACH_URETPROBE_OPTIONAL(skel, lib, SSL_write_ex, probe_SSL_write_ex_exit);
+	ATTACH_UPROBE_OPTIONAL(skel, lib, SSL_read_ex, probe_SSL_read_ex_enter);
+	ATTACH_URETPROBE_OPTIONAL(skel, lib, SSL_read_ex, probe_SSL_read_ex_exit);
 
 	ATTACH_UPROBE_CHECKED(skel, lib, SSL_do_handshake,
 							probe_SSL_do_handshake_enter);
@@ -210,6 +287,272 @@ int attach_nss(struct sslsniff_bpf *skel, const char *lib) {
 	return 0;
 }
 
+/*
+ * Runtime attach/detach (--control)
+ *
+ * Commands arrive on stdin, one per line:
+ *   attach <pid> <lib>  trace <pid>, probing <lib> for it unless it is the
+ *                       library already probed for every process; repeat
+ *                       for each libssl a process has loaded
+ *   detach <pid>        stop tracing <pid> and remove all its probes
+ * Each is answered on stdout with {"control":...,"pid":N,"ok":...}, plus
+ * "lib" for attach.
+ * Commands are read between ring buffer polls, so an idle sensor applies
+ * them within PERF_POLL_TIMEOUT_MS.
+ */
+#define MAX_ATTACHED_PIDS 64
+#define MAX_PID_LINKS 10
+
+/* probes on one library for one process */
+struct pid_probes {
+	pid_t pid;
+	dev_t dev;
+	ino_t ino;
+	int nr_links;
+	struct bpf_link *links[MAX_PID_LINKS];
+};
+
+/* Symbol versions to try when the unversioned name does not resolve, e.g.
+ * in a library exporting SSL_read under more than one version */
+static const char *openssl_versions[] = {
+	"OPENSSL_3.0.0",
+	"OPENSSL_1_1_1",
+	"OPENSSL_1_1_0",
+	"OPENSSL_1.0.0",
+};
+
+/* free slots have pid 0 */
+static struct pid_probes attached[MAX_ATTACHED_PIDS];
+
+/* OpenSSL library probed for every process at startup */
+static struct stat global_ssl;
+static bool have_global_ssl = false;
+
+static char control_buf[1024];
+static size_t control_len;
+static bool control_open = true;
+
+static void print_json_string(const char *s) {
+	putchar('"');
+	for (; *s; s++) {
+		if (*s == '"' || *s == '\\')
+			putchar('\\');
+		if ((unsigned char)*s >= 0x20)
+			putchar(*s);
+	}
+	putchar('"');
+}
+
+static void print_control_result(const char *cmd, int pid, const char *lib,
+								 const char *error) {
+	printf("{\"control\":\"%s\",\"pid\":%d", cmd, pid);
+	if (lib) {
+		printf(",\"lib\":");
+		print_json_string(lib);
+	}
+	if (error)
+		printf(",\"ok\":false,\"error\":\"%s\"}\n", error);
+	else
+		printf(",\"ok\":true}\n");
+	fflush(stdout);
+}
+
+static struct pid_probes *find_pid_probes(pid_t pid) {
+	for (int i = 0; i < MAX_ATTACHED_PIDS; i++) {
+		if (attached[i].pid == pid)
+			return &attached[i];
+	}
+	return NULL;
+}
+
+static struct pid_probes *find_lib_probes(pid_t pid, const struct stat *st) {
+	for (int i = 0; i < MAX_ATTACHED_PIDS; i++) {
+		if (attached[i].pid == pid && attached[i].dev == st->st_dev &&
+			attached[i].ino == st->st_ino)
+			return &attached[i];
+	}
+	return NULL;
+}
+
+/* Attach one probe, falling back to versioned symbol names */
+static struct bpf_link *attach_versioned(struct bpf_program *prog, pid_t pid,
+										 const char *lib, const char *sym,
+										 bool retprobe) {
+	LIBBPF_OPTS(bpf_uprobe_opts, opts, .func_name = sym, .retprobe = retprobe);
+	struct bpf_link *link = bpf_program__attach_uprobe_opts(prog, pid, lib, 0, &opts);
+	char versioned[64];
+
+	for (size_t i = 0; !link && i < sizeof(openssl_versions) / sizeof(*openssl_versions); i++) {
+		snprintf(versioned, sizeof(versioned), "%s@@%s", sym, openssl_versions[i]);
+		opts.func_name = versioned;
+		link = bpf_program__attach_uprobe_opts(prog, pid, lib, 0, &opts);
+	}
+	return link;
+}
+
+static void detach_pid_probes(struct pid_probes *p) {
+	for (int i = 0; i < p->nr_links; i++)
+		bpf_link__destroy(p->links[i]);
+	memset(p, 0, sizeof(*p));
+}
+
+/* Attach the OpenSSL probes to lib for one process; returns an error or NULL */
+static const char *attach_pid_probes(struct sslsniff_bpf *skel, pid_t pid, const char *lib,
+									 const struct stat *st) {
+	struct {
+		struct bpf_program *prog;
+		const char *sym;
+		bool retprobe;
+		bool optional;
+	} probes[MAX_PID_LINKS] = {
+		{skel->progs.probe_SSL_rw_enter, "SSL_write", false, false},
+		{skel->progs.probe_SSL_write_exit, "SSL_write", true, false},
+		{skel->progs.probe_SSL_rw_enter, "SSL_read", false, false},
+		{skel->progs.probe_SSL_read_exit, "SSL_read", true, false},
+		{skel->progs.probe_SSL_write_ex_enter, "SSL_write_ex", false, true},
+		{skel->progs.probe_SSL_write_ex_exit, "SSL_write_ex", true, true},
+		{skel->progs.probe_SSL_read_ex_enter, "SSL_read_ex", false, true},
+		{skel->progs.probe_SSL_read_ex_exit, "SSL_read_ex", true, true},
+		{skel->progs.probe_SSL_do_handshake_enter, "SSL_do_handshake", false, false},
+		{skel->progs.probe_SSL_do_handshake_exit, "SSL_do_handshake", true, false},
+	};
+	struct pid_probes *p = find_pid_probes(0);
+
+	if (!p)
+		return "too many attached processes";
+	p->pid = pid;
+	p->dev = st->st_dev;
+	p->ino = st->st_ino;
+	for (int i = 0; i < MAX_PID_LINKS; i++) {
+		struct bpf_link *link = attach_versioned(probes[i].prog, pid, lib, probes[i].sym,
+												 probes[i].retprobe);
+		if (!link) {
+			if (probes[i].optional)
+				continue;
+			detach_pid_probes(p);
+			return "failed to attach OpenSSL uprobes";
+		}
+		p->links[p->nr_links++] = link;
+	}
+	return NULL;
+}
+
+/* Probe each --lib for the -p process, skipping the library already probed
+ * for every process and any library given twice under another path; the
+ * results are reported like attach commands */
+static void attach_startup_pid_libs(struct sslsniff_bpf *obj) {
+	for (int i = 0; i < env.nr_pid_libs; i++) {
+		const char *lib = env.pid_libs[i];
+		const char *error = NULL;
+		struct stat st;
+
+		if (stat(lib, &st)) {
+			print_control_result("attach", env.pid, lib, "library not found");
+			continue;
+		}
+		bool covered = have_global_ssl && st.st_dev == global_ssl.st_dev &&
+					   st.st_ino == global_ssl.st_ino;
+		if (!covered && !find_lib_probes(env.pid, &st))
+			error = attach_pid_probes(obj, env.pid, lib, &st);
+		print_control_result("attach", env.pid, lib, error);
+	}
+}
+
+static void control_attach(struct sslsniff_bpf *obj, int pid, const char *lib) {
+	int fd = bpf_map__fd(obj->maps.target_pids);
+	__u32 key = pid;
+	__u8 one = 1, value;
+	const char *error = NULL;
+	struct stat st;
+
+	if (pid <= 0 || !*lib) {
+		print_control_result("attach", pid, NULL, "usage: attach <pid> <lib>");
+		return;
+	}
+	if (stat(lib, &st)) {
+		print_control_result("attach", pid, lib, "library not found");
+		return;
+	}
+
+	bool was_target = bpf_map_lookup_elem(fd, &key, &value) == 0;
+	if (!was_target && bpf_map_update_elem(fd, &key, &one, BPF_ANY)) {
+		print_control_result("attach", pid, lib, "too many target processes");
+		return;
+	}
+
+	/* probes on the startup library already fire in every process */
+	bool covered = have_global_ssl && st.st_dev == global_ssl.st_dev &&
+				   st.st_ino == global_ssl.st_ino;
+	/* the same library under another path (a symlink, another
+	 * /proc/<pid>/root) is probed only once */
+	if (!covered && !find_lib_probes(pid, &st))
+		error = attach_pid_probes(obj, pid, lib, &st);
+	if (error && !was_target && !find_pid_probes(pid))
+		bpf_map_delete_elem(fd, &key);
+	print_control_result("attach", pid, lib, error);
+}
+
+static void control_detach(struct sslsniff_bpf *obj, int pid) {
+	__u32 key = pid;
+	struct pid_probes *p;
+	bool had_probes = false;
+	int err = bpf_map_delete_elem(bpf_map__fd(obj->maps.target_pids), &key);
+
+	while (pid > 0 && (p = find_pid_probes(pid))) {
+		detach_pid_probes(p);
+		had_probes = true;
+	}
+	print_control_result("detach", pid, NULL, err && !had_probes ? "not attached" : NULL);
+}
+
+static void handle_control_line(struct sslsniff_bpf *obj, char *line) {
+	char cmd[16];
+	int pid, lib_offset = 0;
+
+	if (sscanf(line, "%15s %d %n", cmd, &pid, &lib_offset) < 2) {
+		fprintf(stderr, "invalid control command: %s\n", line);
+		return;
+	}
+	if (!strcmp(cmd, "attach"))
+		control_attach(obj, pid, lib_offset ? line + lib_offset : "");
+	else if (!strcmp(cmd, "detach"))
+		control_detach(obj, pid);
+	else
+		fprintf(stderr, "unknown control command: %s\n", cmd);
+}
+
+/* Run the complete commands waiting on stdin without blocking */
+static void read_control(struct sslsniff_bpf *obj) {
+	while (control_open) {
+		ssize_t n = read(STDIN_FILENO, control_buf + control_len,
+						 sizeof(control_buf) - 1 - control_len);
+		if (n == 0) {
+			/* the sensor closed stdin; keep capturing with the current targets */
+			control_open = false;
+			break;
+		}
+		if (n < 0) {
+			if (errno != EAGAIN && errno != EINTR)
+				control_open = false;
+			break;
+		}
+		control_len += n;
+		control_buf[control_len] = '\0';
+
+		char *line = control_buf, *nl;
+		while ((nl = strchr(line, '\n'))) {
+			*nl = '\0';
+			handle_control_line(obj, line);
+			line = nl + 1;
+		}
+		control_len -= line - control_buf;
+		memmove(control_buf, line, control_len);
+		/* a line longer than the buffer can never complete */
+		if (control_len == sizeof(control_buf) - 1)
+			control_len = 0;
+	}
+}
+
 /*
  * Find the path of a library using ldconfig.
  */
@@ -356,6 +699,8 @@ void print_event(struct probe_SSL_data_t *event, const char *evt) {
 	printf("\"pid\":%d,", event->pid);
 	printf("\"len\":%d,", event->len);
 	printf("\"buf_size\":%u,", event->buf_size);
+	printf("\"offset\":%u,", event->offset);
+	printf("\"last\":%s,", event->last ? "true" : "false");
 
 	// Always include extra fields (UID, TID)
 	printf("\"uid\":%d,", event->uid);
@@ -414,12 +759,15 @@ void print_event(struct probe_SSL_data_t *event, const char *evt) {
 		printf("\",");
 		
 		
-		// Add truncated info if data was truncated
-		if (buf_size < event->len) {
-			printf("\"truncated\":true,\"bytes_lost\":%d", event->len - buf_size);
+		// Add truncated info if the final fragment does not reach the end
+		if (event->last && event->offset + buf_size < event->len) {
+			printf("\"truncated\":true,\"bytes_lost\":%u", event->len - event->offset - buf_size);
 		} else {
 			printf("\"truncated\":false");
 		}
+	} else if (event->offset > 0) {
+		// A later fragment could not be read
+		printf("\"data\":null,\"truncated\":true,\"bytes_lost\":%u", event->len - event->offset);
 	} else {
 		printf("\"data\":null,\"truncated\":false");
 	}
@@ -429,6 +777,35 @@ void print_event(struct probe_SSL_data_t *event, const char *evt) {
 	fflush(stdout);
 }
 
+/* Milliseconds on CLOCK_MONOTONIC */
+static unsigned long long monotonic_ms(void) {
+	struct timespec ts;
+
+	clock_gettime(CLOCK_MONOTONIC, &ts);
+	return (unsigned long long)ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+/* Print the ring buffer drop total when it has changed since the last call */
+static void report_rb_drops(struct sslsniff_bpf *obj) {
+	static __u64 reported;
+	int ncpus = libbpf_num_possible_cpus();
+	__u32 zero = 0;
+	__u64 total = 0;
+
+	if (ncpus <= 0)
+		return;
+	__u64 values[ncpus];
+	if (bpf_map_lookup_elem(bpf_map__fd(obj->maps.rb_drops), &zero, values))
+		return;
+	for (int i = 0; i < ncpus; i++)
+		total += values[i];
+	if (total == reported)
+		return;
+	reported = total;
+	printf("{\"ringbuf_dropped\":%llu}\n", (unsigned long long)total);
+	fflush(stdout);
+}
+
 static int handle_event(void *ctx, void *data, size_t data_sz) {
 	struct probe_SSL_data_t *e = data;
 	if (e->is_handshake) {
@@ -458,19 +835,47 @@ int main(int argc, char **argv) {
 
 	obj = sslsniff_bpf__open_opts(&open_opts);
 	if (!obj) {
-		warn("failed to open BPF object\n");
+		err = -errno;
+		warn("failed to open BPF object: %d (%s)\n", err, strerror(-err));
 		goto cleanup;
 	}
 
 	obj->rodata->targ_uid = env.uid;
-	obj->rodata->targ_pid = env.pid == INVALID_PID ? 0 : env.pid;
+	obj->rodata->filter_pids = env.pid != INVALID_PID;
+
+	if (env.ringbuf_size) {
+		err = bpf_map__set_max_entries(obj->maps.rb, env.ringbuf_size);
+		if (err) {
+			warn("failed to set ring buffer size: %d (%s)\n", err, strerror(-err));
+			goto cleanup;
+		}
+	}
+	if (verbose)
+		fprintf(stderr, "Ring buffer size: %u bytes\n", bpf_map__max_entries(obj->maps.rb));
 
 	err = sslsniff_bpf__load(obj);
 	if (err) {
-		warn("failed to load BPF object: %d\n", err);
+		warn("failed to load BPF object: %d (%s)\n", err, strerror(-err));
+		goto cleanup;
+	}
+
+	if (env.load_only) {
+		if (verbose)
+			fprintf(stderr, "BPF object loaded\n");
 		goto cleanup;
 	}
 
+	if (env.pid != INVALID_PID) {
+		__u32 key = env.pid;
+		__u8 one = 1;
+
+		err = bpf_map_update_elem(bpf_map__fd(obj->maps.target_pids), &key, &one, BPF_ANY);
+		if (err) {
+			warn("failed to add target pid: %d (%s)\n", err, strerror(errno));
+			goto cleanup;
+		}
+	}
+
 	// Allocate global buffer once
 	event_buf = malloc(MAX_BUF_SIZE + 1);
 	if (!event_buf) {
@@ -485,7 +890,8 @@ int main(int argc, char **argv) {
 			fprintf(stderr, "OpenSSL path: %s\n", openssl_path ? openssl_path : "not found");
 		}
 		if (openssl_path) {
-			attach_openssl(obj, openssl_path);
+			have_global_ssl = attach_openssl(obj, openssl_path) == 0 &&
+							  stat(openssl_path, &global_ssl) == 0;
 		} else {
 			warn("OpenSSL library not found\n");
 		}
@@ -513,6 +919,13 @@ int main(int argc, char **argv) {
 		}
 	}
 
+	if (env.openssl && env.nr_pid_libs > 0) {
+		if (env.pid == INVALID_PID)
+			warn("--lib needs -p, ignoring it\n");
+		else
+			attach_startup_pid_libs(obj);
+	}
+
 	// Handle custom binary path for statically-linked SSL (e.g., NVM Node.js)
 	if (env.extra_lib) {
 		if (verbose) {
@@ -535,6 +948,18 @@ int main(int argc, char **argv) {
 		goto cleanup;
 	}
 
+	if (env.control &&
+	    fcntl(STDIN_FILENO, F_SETFL, fcntl(STDIN_FILENO, F_GETFL) | O_NONBLOCK)) {
+		warn("can't make stdin non-blocking: %s\n", strerror(errno));
+		err = 1;
+		goto cleanup;
+	}
+
+	/* drops are reported about once a second, by wall time rather than
+	 * poll count since busy polls return early */
+	unsigned long long last_report = monotonic_ms();
+	/* heartbeats tell the sensor an idle poll loop apart from a stalled one */
+	unsigned long long last_heartbeat = last_report;
 	while (!exiting) {
 		err = ring_buffer__poll(rb, PERF_POLL_TIMEOUT_MS);
 		if (err < 0 && err != -EINTR) {
@@ -542,7 +967,20 @@ int main(int argc, char **argv) {
 			goto cleanup;
 		}
 		err = 0;
+		if (env.control)
+			read_control(obj);
+		unsigned long long now = monotonic_ms();
+		if (now - last_report >= DROP_REPORT_INTERVAL_MS) {
+			report_rb_drops(obj);
+			last_report = now;
+		}
+		if (now - last_heartbeat >= HEARTBEAT_INTERVAL_MS) {
+			printf("{\"heartbeat\":%llu}\n", now);
+			fflush(stdout);
+			last_heartbeat = now;
+		}
 	}
+	report_rb_drops(obj);
 
 cleanup:
 	if (event_buf) {
@@ -557,6 +995,10 @@ cleanup:
 		free(env.comm);
 		env.comm = NULL;
 	}
+	for (int i = 0; i < MAX_ATTACHED_PIDS; i++) {
+		if (attached[i].pid)
+			detach_pid_probes(&attached[i]);
+	}
 	ring_buffer__free(rb);
 	sslsniff_bpf__destroy(obj);
 	return err != 0;
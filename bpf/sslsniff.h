@@ -9,6 +9,8 @@
 #define MAX_BUF_SIZE (512 * 1024)  // 512KB eBPF buffer size (kernel limit)
 #define RING_BUFFER_SIZE (2 * 1024 * 1024)  // 2MB ring buffer
 #define TASK_COMM_LEN 16
+#define MAX_TARGET_PIDS 1024  // processes traced when filtering by PID
+#define MAX_FRAGMENTS 8  // events per SSL call, so up to 4MB is captured
 
 struct probe_SSL_data_t {
     __u64 timestamp_ns;
@@ -18,6 +20,8 @@ struct probe_SSL_data_t {
     __u32 uid;
     __u32 len;
     __u32 buf_size;         // Actual bytes copied to buf
+    __u32 offset;           // Position of buf in the SSL call's data
+    int last;               // No more fragments follow for this call
     int buf_filled;
     int rw;
     char comm[TASK_COMM_LEN];
@@ -12,10 +12,16 @@ mod sslsniff_runner;
 mod linux_proc;
 
 #[cfg(target_os = "linux")]
-pub use sslsniff_runner::{SslsniffCapture, SslsniffConfig};
+mod target_pids;
 
 #[cfg(target_os = "linux")]
-pub use linux_proc::{ProcInfo, ProcInfoCache, SocketToPidMap, TcpConnection};
+pub use sslsniff_runner::{ringbuf_size_bytes, SslsniffCapture, SslsniffConfig};
+
+#[cfg(target_os = "linux")]
+pub use linux_proc::{ProcInfo, ProcInfoCache, SocketSeed, SocketToPidMap, TcpConnection};
+
+#[cfg(target_os = "linux")]
+pub use target_pids::SslsniffTargets;
 
 // Re-export as the main capture type for backwards compatibility
 #[cfg(target_os = "linux")]
@@ -53,4 +59,6 @@ pub struct EbpfCaptureConfig {
     pub comm_filter: Vec<String>,
     pub pid_filter: Option<u32>,
     pub ebpf_bytecode_path: Option<String>,
+    pub ringbuf_size: Option<usize>,
+    pub runtime_attach: bool,
 }
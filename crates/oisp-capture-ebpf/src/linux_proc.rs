@@ -3,9 +3,11 @@
 //! Provides process attribution and socket-to-process mapping for Linux.
 //! Used to enrich events captured by sslsniff with full process info.
 
-use std::collections::HashMap;
+use oisp_core::plugins::{ProcessTargetError, RawCaptureEvent};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
 use tracing::{trace, warn};
 
 /// Process information read from /proc
@@ -109,6 +111,56 @@ fn parse_stat_ppid(stat: &str) -> Option<u32> {
     ppid_str.parse::<u32>().ok()
 }
 
+/// OpenSSL libraries mapped by a process, as paths usable from the sensor
+///
+/// The paths go through `/proc/{pid}/root`, so libraries inside containers
+/// resolve to the process's own copy rather than the host's. A process can
+/// load several versions at once, e.g. libssl.so.3 for itself and
+/// libssl.so.1.1 for a plugin; each is returned once.
+pub fn openssl_libraries(pid: u32) -> Result<Vec<PathBuf>, ProcessTargetError> {
+    let proc_path = format!("/proc/{}", pid);
+    if !Path::new(&proc_path).exists() {
+        return Err(ProcessTargetError::NoSuchProcess(pid));
+    }
+    let maps = fs::read_to_string(format!("{}/maps", proc_path)).map_err(|e| {
+        ProcessTargetError::Failed(format!("Cannot read memory maps of process {}: {}", pid, e))
+    })?;
+    let libs = parse_maps_libssl(&maps);
+    if libs.is_empty() {
+        return Err(ProcessTargetError::NoTlsLibrary(pid));
+    }
+    Ok(libs
+        .into_iter()
+        .map(|lib| PathBuf::from(format!("{}/root{}", proc_path, lib)))
+        .collect())
+}
+
+/// Paths of the distinct libssl mappings in /proc/{pid}/maps, in load order
+/// Format: address perms offset dev inode pathname
+///
+/// Each library is mapped once per segment, and the same file can appear
+/// under several names (hard links, bind mounts), so mappings are
+/// deduplicated by device and inode.
+fn parse_maps_libssl(maps: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut libs = Vec::new();
+    for line in maps.lines() {
+        let fields: Vec<&str> = line.splitn(6, char::is_whitespace).collect();
+        let [_, _, _, dev, inode, path] = fields[..] else {
+            continue;
+        };
+        let path = path.trim();
+        let is_libssl = path
+            .rsplit('/')
+            .next()
+            .is_some_and(|name| name.starts_with("libssl.so"));
+        if is_libssl && seen.insert((dev, inode)) {
+            libs.push(path);
+        }
+    }
+    libs
+}
+
 /// Socket inode to PID mapping
 /// Built by scanning /proc/*/fd/* for socket inodes
 #[derive(Debug, Default)]
@@ -189,17 +241,22 @@ impl SocketToPidMap {
     }
 }
 
-/// TCP connection info from /proc/net/tcp
+/// TCP connection info from /proc/net/tcp or /proc/net/tcp6
 #[derive(Debug, Clone)]
 pub struct TcpConnection {
-    pub local_addr: std::net::Ipv4Addr,
+    pub local_addr: IpAddr,
     pub local_port: u16,
-    pub remote_addr: std::net::Ipv4Addr,
+    pub remote_addr: IpAddr,
     pub remote_port: u16,
+    /// Kernel TCP state (`TCP_ESTABLISHED` is 1)
+    pub state: u8,
     pub inode: u64,
     pub uid: u32,
 }
 
+/// `st` of an established connection in /proc/net/tcp
+const TCP_ESTABLISHED: u8 = 0x01;
+
 /// Parse /proc/net/tcp to get TCP connection info
 /// Returns a map from (local_port, remote_addr, remote_port) to inode
 #[allow(dead_code)]
@@ -208,21 +265,21 @@ pub fn parse_proc_net_tcp() -> Vec<TcpConnection> {
 
     for path in &["/proc/net/tcp", "/proc/net/tcp6"] {
         if let Ok(content) = fs::read_to_string(path) {
-            for line in content.lines().skip(1) {
-                // Skip header
-                if let Some(conn) = parse_tcp_line(line) {
-                    connections.push(conn);
-                }
-            }
+            connections.extend(parse_net_tcp(&content));
         }
     }
 
     connections
 }
 
+/// Parse the contents of /proc/net/tcp or /proc/net/tcp6
+fn parse_net_tcp(content: &str) -> impl Iterator<Item = TcpConnection> + '_ {
+    // Skip header
+    content.lines().skip(1).filter_map(parse_tcp_line)
+}
+
 /// Parse a single line from /proc/net/tcp
 /// Format: sl local_address rem_address st tx_queue rx_queue tr tm->when retrnsmt uid timeout inode ...
-#[allow(dead_code)]
 fn parse_tcp_line(line: &str) -> Option<TcpConnection> {
     let parts: Vec<&str> = line.split_whitespace().collect();
     if parts.len() < 10 {
@@ -235,6 +292,8 @@ fn parse_tcp_line(line: &str) -> Option<TcpConnection> {
     // Remote address
     let (remote_addr, remote_port) = parse_hex_addr(parts[2])?;
 
+    let state = u8::from_str_radix(parts[3], 16).ok()?;
+
     // UID is at index 7
     let uid: u32 = parts[7].parse().ok()?;
 
@@ -246,34 +305,164 @@ fn parse_tcp_line(line: &str) -> Option<TcpConnection> {
         local_port,
         remote_addr,
         remote_port,
+        state,
         inode,
         uid,
     })
 }
 
-/// Parse hex address format: AABBCCDD:PORT (in little-endian for IPv4)
-#[allow(dead_code)]
-fn parse_hex_addr(hex: &str) -> Option<(std::net::Ipv4Addr, u16)> {
-    let parts: Vec<&str> = hex.split(':').collect();
-    if parts.len() != 2 {
-        return None;
+/// Parse hex address format: ADDR:PORT
+///
+/// The address is 8 (IPv4) or 32 (IPv6) hex digits, printed as 32-bit words
+/// in host byte order, so each word's native-endian bytes are the address
+/// bytes in network order.
+fn parse_hex_addr(hex: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = hex.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for word in addr.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(word).ok()?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
     }
+    let ip = match addr.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        32 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
 
-    // IP is in little-endian hex
-    let ip_hex = u32::from_str_radix(parts[0], 16).ok()?;
-    // Convert from network byte order (which /proc shows in little-endian on little-endian systems)
-    let ip = std::net::Ipv4Addr::from(ip_hex.to_be());
+    Some((ip, port))
+}
 
-    let port = u16::from_str_radix(parts[1], 16).ok()?;
+/// Socket inodes behind the `socket:[inode]` links in a /proc/{pid}/fd directory
+fn socket_inodes(fd_dir: &Path) -> HashSet<u64> {
+    let Ok(entries) = fs::read_dir(fd_dir) else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| fs::read_link(entry.path()).ok())
+        .filter_map(|target| {
+            target
+                .to_str()?
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
 
-    Some((ip, port))
+/// Established TCP connections of processes that already had OpenSSL
+/// loaded when capture started
+///
+/// sslsniff reports SSL reads and writes without the socket they went
+/// through. For connections opened before the sensor started there is no
+/// other record of where they go, so /proc is scanned once at startup: each
+/// libssl process's socket inodes from /proc/{pid}/fd are matched against
+/// /proc/{pid}/net/tcp{,6} (the process's own network namespace).
+#[derive(Debug, Default)]
+pub struct SocketSeed {
+    connections: HashMap<u32, Vec<TcpConnection>>,
+}
+
+impl SocketSeed {
+    /// Scan processes under `proc_root`, only `pids` when given
+    pub fn scan(proc_root: &Path, pids: Option<&[u32]>) -> Self {
+        let candidates: Vec<u32> = match pids {
+            Some(pids) => pids.to_vec(),
+            None => match fs::read_dir(proc_root) {
+                Ok(dir) => dir
+                    .flatten()
+                    .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to read {}: {}", proc_root.display(), e);
+                    return Self::default();
+                }
+            },
+        };
+
+        // Processes sharing a network namespace share its tables
+        let mut tables: HashMap<PathBuf, Vec<TcpConnection>> = HashMap::new();
+        let mut seed = Self::default();
+        for pid in candidates {
+            let proc_path = proc_root.join(pid.to_string());
+            let uses_libssl = fs::read_to_string(proc_path.join("maps"))
+                .is_ok_and(|maps| !parse_maps_libssl(&maps).is_empty());
+            if !uses_libssl {
+                continue;
+            }
+            let inodes = socket_inodes(&proc_path.join("fd"));
+            if inodes.is_empty() {
+                continue;
+            }
+
+            let netns =
+                fs::read_link(proc_path.join("ns/net")).unwrap_or_else(|_| proc_path.clone());
+            let table = tables.entry(netns).or_insert_with(|| {
+                ["tcp", "tcp6"]
+                    .iter()
+                    .filter_map(|name| fs::read_to_string(proc_path.join("net").join(name)).ok())
+                    .flat_map(|content| parse_net_tcp(&content).collect::<Vec<_>>())
+                    .filter(|conn| conn.state == TCP_ESTABLISHED)
+                    .collect()
+            });
+            let connections: Vec<TcpConnection> = table
+                .iter()
+                .filter(|conn| inodes.contains(&conn.inode))
+                .cloned()
+                .collect();
+            if !connections.is_empty() {
+                trace!(
+                    "pid {} has {} established connections",
+                    pid,
+                    connections.len()
+                );
+                seed.connections.insert(pid, connections);
+            }
+        }
+        seed
+    }
+
+    /// Number of processes with seeded connections
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Established connections of `pid` at startup
+    pub fn connections(&self, pid: u32) -> &[TcpConnection] {
+        self.connections.get(&pid).map_or(&[], Vec::as_slice)
+    }
+
+    /// Fill in the addresses of an SSL event whose process had exactly one
+    /// established connection at startup
+    ///
+    /// With several connections the event cannot be tied to one of them, so
+    /// it is left alone, as is an event whose addresses are already known.
+    pub fn attribute(&self, event: &mut RawCaptureEvent) {
+        if event.metadata.remote_addr.is_some() {
+            return;
+        }
+        let [conn] = self.connections(event.pid) else {
+            return;
+        };
+        event.metadata.remote_addr = Some(conn.remote_addr.to_string());
+        event.metadata.remote_port = Some(conn.remote_port);
+        event.metadata.local_addr = Some(conn.local_addr.to_string());
+        event.metadata.local_port = Some(conn.local_port);
+    }
 }
 
 /// Find the PID that owns a TCP connection
 #[allow(dead_code)]
 pub fn find_pid_for_connection(
     local_port: u16,
-    remote_addr: std::net::Ipv4Addr,
+    remote_addr: Ipv4Addr,
     remote_port: u16,
     socket_map: &SocketToPidMap,
 ) -> Option<(u32, i32)> {
@@ -340,6 +529,58 @@ mod tests {
         assert_eq!(parse_stat_ppid(stat), Some(100));
     }
 
+    #[test]
+    fn test_parse_maps_libssl() {
+        let maps = "\
+55d4c8a00000-55d4c8a02000 r--p 00000000 08:01 1048 /usr/bin/curl
+7f1e2c000000-7f1e2c021000 rw-p 00000000 00:00 0
+7f1e2d200000-7f1e2d23a000 r--p 00000000 08:01 2231 /usr/lib/x86_64-linux-gnu/libcrypto.so.3
+7f1e2d600000-7f1e2d61c000 r--p 00000000 08:01 2240 /usr/lib/x86_64-linux-gnu/libssl.so.3
+7f1e2d61c000-7f1e2d677000 r-xp 0001c000 08:01 2240 /usr/lib/x86_64-linux-gnu/libssl.so.3
+7ffd3b1fe000-7ffd3b21f000 rw-p 00000000 00:00 0 [stack]";
+        assert_eq!(
+            parse_maps_libssl(maps),
+            vec!["/usr/lib/x86_64-linux-gnu/libssl.so.3"]
+        );
+
+        // Only libcrypto, or a library that merely mentions libssl
+        let maps = "\
+7f1e2d200000-7f1e2d23a000 r--p 00000000 08:01 2231 /usr/lib/libcrypto.so.3
+7f1e2d600000-7f1e2d61c000 r--p 00000000 08:01 2240 /opt/libssl.so.3-tools/libfoo.so";
+        assert!(parse_maps_libssl(maps).is_empty());
+    }
+
+    #[test]
+    fn test_parse_maps_two_libssl_versions() {
+        // Python on libssl.so.3 with a plugin bundling libssl.so.1.1; the
+        // bundled copy is also reachable through a bind mount
+        let maps = "\
+55d4c8a00000-55d4c8a02000 r--p 00000000 08:01 1048                       /usr/bin/python3.11
+7f1e2d600000-7f1e2d61c000 r--p 00000000 08:01 2240                       /usr/lib/x86_64-linux-gnu/libssl.so.3
+7f1e2d61c000-7f1e2d677000 r-xp 0001c000 08:01 2240                       /usr/lib/x86_64-linux-gnu/libssl.so.3
+7f1e2e000000-7f1e2e020000 r--p 00000000 08:01 9911                       /opt/plugin/lib/libssl.so.1.1
+7f1e2e020000-7f1e2e070000 r-xp 00020000 08:01 9911                       /opt/plugin/lib/libssl.so.1.1
+7f1e2e400000-7f1e2e420000 r--p 00000000 08:01 9911                       /mnt/plugin/lib/libssl.so.1.1
+7f1e2e800000-7f1e2e820000 r--p 00000000 08:02 9911                       /srv/other/libssl.so.1.1";
+        assert_eq!(
+            parse_maps_libssl(maps),
+            vec![
+                "/usr/lib/x86_64-linux-gnu/libssl.so.3",
+                "/opt/plugin/lib/libssl.so.1.1",
+                "/srv/other/libssl.so.1.1",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_openssl_libraries_missing_process() {
+        assert_eq!(
+            openssl_libraries(u32::MAX),
+            Err(ProcessTargetError::NoSuchProcess(u32::MAX))
+        );
+    }
+
     #[test]
     fn test_parse_hex_addr() {
         // 127.0.0.1:8080
@@ -349,6 +590,122 @@ mod tests {
         // Note: The IP parsing depends on system endianness, so we only check port
     }
 
+    // 10.0.0.5:51324 -> 104.18.7.192:443 established (inode 4001), a
+    // listener on 127.0.0.1:8080 (inode 4002) and an established connection
+    // of another process (inode 4003)
+    const NET_TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4002 1 0000000000000000 100 0 0 10 0
+   1: 0500000A:C87C C0071268:01BB 01 00000000:00000000 02:000A7D2A 00000000  1000        0 4001 2 0000000000000000 20 4 30 10 -1
+   2: 0500000A:C87D C0071268:01BB 01 00000000:00000000 02:000A7D2A 00000000  1000        0 4003 2 0000000000000000 20 4 30 10 -1
+";
+
+    // [2606:4700::6812:7c0]:443 from [fd00::5]:40000
+    const NET_TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 000000FD000000000000000005000000:9C40 004706260000000000000000C0071268:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 4004 1 0000000000000000 20 4 30 10 -1
+";
+
+    const LIBSSL_MAPS: &str =
+        "7f1e2d600000-7f1e2d61c000 r--p 00000000 08:01 2240 /usr/lib/libssl.so.3\n";
+
+    fn write_process(root: &Path, pid: u32, maps: &str, sockets: &[u64], tcp6: bool) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::create_dir_all(dir.join("net")).unwrap();
+        fs::write(dir.join("maps"), maps).unwrap();
+        fs::write(dir.join("net/tcp"), NET_TCP).unwrap();
+        if tcp6 {
+            fs::write(dir.join("net/tcp6"), NET_TCP6).unwrap();
+        }
+        std::os::unix::fs::symlink("/dev/null", dir.join("fd/0")).unwrap();
+        for (fd, inode) in sockets.iter().enumerate() {
+            std::os::unix::fs::symlink(
+                format!("socket:[{}]", inode),
+                dir.join(format!("fd/{}", fd + 3)),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_net_tcp() {
+        let conns: Vec<_> = parse_net_tcp(NET_TCP).collect();
+        assert_eq!(conns.len(), 3);
+        assert_eq!(conns[0].state, 0x0A);
+        let conn = &conns[1];
+        assert_eq!(conn.state, TCP_ESTABLISHED);
+        assert_eq!(conn.inode, 4001);
+        assert_eq!(conn.uid, 1000);
+        #[cfg(target_endian = "little")]
+        {
+            assert_eq!(conn.local_addr, IpAddr::from([10, 0, 0, 5]));
+            assert_eq!(conn.remote_addr, IpAddr::from([104, 18, 7, 192]));
+
+            let conn6 = parse_net_tcp(NET_TCP6).next().unwrap();
+            assert_eq!(
+                conn6.remote_addr,
+                "2606:4700::6812:7c0".parse::<IpAddr>().unwrap()
+            );
+            assert_eq!(conn6.local_addr, "fd00::5".parse::<IpAddr>().unwrap());
+            assert_eq!(conn6.remote_port, 443);
+        }
+        assert_eq!((conn.local_port, conn.remote_port), (51324, 443));
+    }
+
+    #[test]
+    fn test_socket_seed_scan() {
+        let root = tempfile::tempdir().unwrap();
+        // libssl, one established connection plus the listener
+        write_process(root.path(), 100, LIBSSL_MAPS, &[4001, 4002], false);
+        // libssl, two established connections (one over IPv6)
+        write_process(root.path(), 200, LIBSSL_MAPS, &[4003, 4004], true);
+        // No libssl
+        write_process(root.path(), 300, "", &[4001], false);
+        fs::create_dir_all(root.path().join("self")).unwrap();
+
+        let seed = SocketSeed::scan(root.path(), None);
+        assert_eq!(seed.len(), 2);
+        assert_eq!(seed.connections(100).len(), 1);
+        assert_eq!(seed.connections(100)[0].remote_port, 443);
+        assert_eq!(seed.connections(200).len(), 2);
+        assert!(seed.connections(300).is_empty());
+
+        let seed = SocketSeed::scan(root.path(), Some(&[200]));
+        assert_eq!(seed.len(), 1);
+        assert!(seed.connections(100).is_empty());
+    }
+
+    #[test]
+    fn test_socket_seed_attribute() {
+        let root = tempfile::tempdir().unwrap();
+        write_process(root.path(), 100, LIBSSL_MAPS, &[4001], false);
+        write_process(root.path(), 200, LIBSSL_MAPS, &[4003, 4004], true);
+        let seed = SocketSeed::scan(root.path(), None);
+
+        let event = |pid| RawCaptureEvent {
+            id: "raw".to_string(),
+            timestamp_ns: 0,
+            kind: oisp_core::plugins::RawEventKind::SslWrite,
+            pid,
+            tid: None,
+            data: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let mut sole = event(100);
+        seed.attribute(&mut sole);
+        assert_eq!(sole.metadata.remote_port, Some(443));
+        assert_eq!(sole.metadata.local_port, Some(51324));
+        #[cfg(target_endian = "little")]
+        assert_eq!(sole.metadata.remote_addr.as_deref(), Some("104.18.7.192"));
+
+        // Ambiguous, or not seeded
+        for pid in [200, 300] {
+            let mut other = event(pid);
+            seed.attribute(&mut other);
+            assert!(other.metadata.remote_addr.is_none());
+        }
+    }
+
     #[test]
     #[cfg(target_os = "linux")]
     fn test_proc_info_self() {
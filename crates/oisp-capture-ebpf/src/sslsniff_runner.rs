@@ -6,12 +6,18 @@
 //! 3. Parsing JSON events from its stdout
 //! 4. Converting to OISP events
 
-use oisp_core::plugins::{CapturePlugin, CaptureStats, PluginError, PluginResult, RawCaptureEvent};
+use crate::linux_proc::SocketSeed;
+use crate::target_pids::{LibraryLookup, SslsniffTargets};
+use oisp_core::plugins::{
+    CaptureErrorKind, CaptureErrorSender, CapturePlugin, CaptureStats, PluginError, PluginResult,
+    RawCaptureEvent, SharedProcessTargets,
+};
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
@@ -24,6 +30,10 @@ const EMBEDDED_SSLSNIFF: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sslsn
 #[cfg(not(all(target_os = "linux", embedded_sslsniff)))]
 const EMBEDDED_SSLSNIFF: &[u8] = &[];
 
+/// How long connections found by the startup /proc scan are used to
+/// attribute SSL events; after that a process may well have opened others
+const SOCKET_SEED_WINDOW: Duration = Duration::from_secs(60);
+
 /// Configuration for sslsniff runner
 ///
 /// Compatible with the old EbpfCaptureConfig for easy migration
@@ -45,6 +55,19 @@ pub struct SslsniffConfig {
     pub pid_filter: Option<u32>,
     /// Path to eBPF bytecode (not used, for compatibility) or sslsniff binary
     pub ebpf_bytecode_path: Option<String>,
+    /// Ring buffer size in bytes (None = sslsniff's built-in 2MB), rounded
+    /// up to what the kernel accepts: a power of two multiple of the page size
+    pub ringbuf_size: Option<usize>,
+    /// Start sslsniff with `--control` so processes can be attached and
+    /// detached at runtime. sslsniff then probes the library for every
+    /// process and filters pids in the kernel, so it is off unless
+    /// something (the web API) can send commands.
+    pub runtime_attach: bool,
+}
+
+/// Round a ring buffer size up to a power of two multiple of the page size
+pub fn ringbuf_size_bytes(requested: usize, page_size: usize) -> usize {
+    requested.max(page_size).next_power_of_two()
 }
 
 /// sslsniff-based SSL capture
@@ -54,32 +77,57 @@ pub struct SslsniffCapture {
     stats: Arc<CaptureStatsInner>,
     child: Option<Child>,
     extracted_path: Option<PathBuf>,
+    errors: Option<CaptureErrorSender>,
+    targets: Arc<SslsniffTargets>,
+    /// When sslsniff last printed a heartbeat (None for a build that never does)
+    heartbeat: Arc<Mutex<Option<Instant>>>,
 }
 
 struct CaptureStatsInner {
     events_captured: AtomicU64,
+    /// Events the pipeline could not accept
     events_dropped: AtomicU64,
+    /// Events the kernel dropped because the ring buffer was full
+    ringbuf_dropped: AtomicU64,
     bytes_captured: AtomicU64,
     errors: AtomicU64,
 }
 
+impl CaptureStatsInner {
+    /// Record the ring buffer drop total reported by sslsniff
+    fn record_ringbuf_dropped(&self, total: u64) {
+        let previous = self.ringbuf_dropped.swap(total, Ordering::Relaxed);
+        if total > previous {
+            warn!(
+                dropped = total - previous,
+                total, "sslsniff ring buffer full, events dropped (consider capture.ringbuf_size)"
+            );
+        }
+    }
+}
+
 impl SslsniffCapture {
     pub fn new() -> Self {
         Self::with_config(SslsniffConfig::default())
     }
 
     pub fn with_config(config: SslsniffConfig) -> Self {
+        let targets = Arc::new(SslsniffTargets::new(config.pid_filter));
         Self {
             config,
             running: Arc::new(AtomicBool::new(false)),
             stats: Arc::new(CaptureStatsInner {
                 events_captured: AtomicU64::new(0),
                 events_dropped: AtomicU64::new(0),
+                ringbuf_dropped: AtomicU64::new(0),
                 bytes_captured: AtomicU64::new(0),
                 errors: AtomicU64::new(0),
             }),
             child: None,
             extracted_path: None,
+            errors: None,
+            targets,
+            heartbeat: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -169,6 +217,29 @@ impl SslsniffCapture {
         Ok(extract_path)
     }
 
+    /// Load the eBPF programs and unload them again, without attaching
+    ///
+    /// Used by `oisp-sensor test` to surface verifier, BTF and permission
+    /// problems before a capture session. Errors are plain-language
+    /// descriptions of what to fix.
+    pub fn check_load(&mut self) -> Result<(), String> {
+        let sslsniff_path = self.get_sslsniff_path().map_err(|e| e.to_string())?;
+        let output = Command::new(&sslsniff_path)
+            .arg("--load-only")
+            .args(self.ringbuf_args())
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", sslsniff_path.display(), e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(explain_load_failure(&String::from_utf8_lossy(
+                &output.stderr,
+            )))
+        }
+    }
+
     /// Find libssl.so path
     fn find_libssl(&self) -> Option<String> {
         // Check configured paths first
@@ -211,57 +282,108 @@ impl SslsniffCapture {
         None
     }
 
-    /// Parse a JSON line from sslsniff into a RawCaptureEvent
-    /// Uses proc_cache to enrich with full process info from /proc
-    fn parse_sslsniff_event(
-        json_line: &str,
-        proc_cache: &mut crate::linux_proc::ProcInfoCache,
-    ) -> Option<RawCaptureEvent> {
-        use oisp_core::plugins::{RawEventKind, RawEventMetadata};
+    /// Every OpenSSL library the `pid_filter` process has loaded, not just
+    /// the one ldconfig resolves
+    fn pid_filter_libraries(&self, find_libraries: LibraryLookup) -> Vec<PathBuf> {
+        let Some(pid) = self.config.pid_filter else {
+            return Vec::new();
+        };
+        match find_libraries(pid) {
+            Ok(libraries) => {
+                let names: Vec<String> = libraries
+                    .iter()
+                    .map(|library| library.to_string_lossy().to_string())
+                    .collect();
+                info!(
+                    "Attaching SSL capture to pid {} ({})",
+                    pid,
+                    names.join(", ")
+                );
+                self.targets.started_with(pid, names);
+                libraries
+            }
+            Err(e) => {
+                warn!("Cannot list the OpenSSL libraries of pid {}: {}", pid, e);
+                Vec::new()
+            }
+        }
+    }
 
-        let value: serde_json::Value = serde_json::from_str(json_line).ok()?;
+    /// sslsniff invocation for the configured filters and ring buffer size,
+    /// probing `pid_libraries` for the `pid_filter` process
+    fn sslsniff_command(
+        &self,
+        sslsniff_path: &std::path::Path,
+        pid_libraries: &[PathBuf],
+    ) -> Command {
+        // Note: stderr goes to /dev/null to prevent buffer blocking
+        // sslsniff outputs JSON events to stdout only
+        let mut cmd = Command::new(sslsniff_path);
+        cmd.stdout(Stdio::piped()).stderr(Stdio::null());
+        cmd.args(self.ringbuf_args());
 
-        let function = value.get("function")?.as_str()?;
-        let kind = if function.contains("WRITE") || function.contains("SEND") {
-            RawEventKind::SslWrite
+        // Accept attach/detach commands on stdin
+        if self.config.runtime_attach {
+            cmd.stdin(Stdio::piped()).arg("--control");
         } else {
-            RawEventKind::SslRead
-        };
+            cmd.stdin(Stdio::null());
+        }
 
-        let timestamp_ns = value.get("timestamp_ns")?.as_u64()?;
-        let pid = value.get("pid")?.as_u64()? as u32;
-        let tid = value.get("tid").and_then(|t| t.as_u64()).map(|t| t as u32);
-        let comm = value.get("comm")?.as_str()?.to_string();
-        let data_str = value.get("data").and_then(|d| d.as_str()).unwrap_or("");
-
-        // CRITICAL: sslsniff encodes binary data as JSON string with escape sequences.
-        // When serde_json decodes e.g. \u008b, it becomes Unicode codepoint U+008B.
-        // But .as_bytes() would UTF-8 encode it as [0xC2, 0x8B] (2 bytes!), corrupting the data.
-        // We must treat it as Latin-1: each char's codepoint IS the byte value.
-        let data: Vec<u8> = data_str.chars().map(|c| c as u8).collect();
-
-        // Enrich with full process info from /proc
-        let (exe, ppid, uid) = if let Some(proc_info) = proc_cache.get(pid) {
-            (proc_info.exe.clone(), proc_info.ppid, proc_info.uid)
-        } else {
-            (None, None, None)
+        // Add binary path for statically-linked SSL (e.g., Node.js with embedded OpenSSL)
+        // This allows sslsniff to attach uprobes to the binary itself instead of libssl.so
+        if let Some(binary_path) = self.config.ssl_binary_paths.first() {
+            if !binary_path.is_empty() && std::path::Path::new(binary_path).exists() {
+                info!("Attaching to binary with embedded SSL: {}", binary_path);
+                cmd.args(["--binary-path", binary_path]);
+            }
+        }
+
+        // Add PID filter if specified
+        if let Some(pid) = self.config.pid_filter {
+            cmd.args(["-p", &pid.to_string()]);
+            for library in pid_libraries {
+                cmd.arg("--lib").arg(library);
+            }
+        }
+
+        // Add comm filter if specified
+        if let Some(comm) = self.config.comm_filter.first() {
+            cmd.args(["-c", comm]);
+        }
+
+        cmd
+    }
+
+    /// `--ringbuf-size` arguments, if a size is configured
+    fn ringbuf_args(&self) -> Vec<String> {
+        let Some(requested) = self.config.ringbuf_size else {
+            return Vec::new();
         };
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        let page_size = usize::try_from(page_size).unwrap_or(4096);
+        let size = ringbuf_size_bytes(requested, page_size);
+        if size != requested {
+            info!(
+                "Ring buffer size {} rounded up to {} (power of two multiple of the page size)",
+                requested, size
+            );
+        }
+        vec!["--ringbuf-size".to_string(), size.to_string()]
+    }
+
+    /// Enrich an event parsed from sslsniff with full process info from
+    /// /proc, using proc_cache
+    fn enrich_sslsniff_event(
+        mut event: RawCaptureEvent,
+        proc_cache: &mut crate::linux_proc::ProcInfoCache,
+    ) -> RawCaptureEvent {
+        if let Some(proc_info) = proc_cache.get(event.pid) {
+            event.metadata.exe = proc_info.exe.clone();
+            event.metadata.ppid = proc_info.ppid;
+            event.metadata.uid = proc_info.uid.or(event.metadata.uid);
+        }
 
-        Some(RawCaptureEvent {
-            id: ulid::Ulid::new().to_string(),
-            timestamp_ns,
-            kind,
-            pid,
-            tid,
-            data,
-            metadata: RawEventMetadata {
-                comm: Some(comm),
-                exe,
-                ppid,
-                uid,
-                ..Default::default()
-            },
-        })
+        event
     }
 }
 
@@ -326,30 +448,8 @@ impl CapturePlugin for SslsniffCapture {
             info!("Found libssl: {:?}", libssl);
         }
 
-        // Build command
-        // Note: stderr goes to /dev/null to prevent buffer blocking
-        // sslsniff outputs JSON events to stdout only
-        let mut cmd = Command::new(&sslsniff_path);
-        cmd.stdout(Stdio::piped()).stderr(Stdio::null());
-
-        // Add binary path for statically-linked SSL (e.g., Node.js with embedded OpenSSL)
-        // This allows sslsniff to attach uprobes to the binary itself instead of libssl.so
-        if let Some(binary_path) = self.config.ssl_binary_paths.first() {
-            if !binary_path.is_empty() && std::path::Path::new(binary_path).exists() {
-                info!("Attaching to binary with embedded SSL: {}", binary_path);
-                cmd.args(["--binary-path", binary_path]);
-            }
-        }
-
-        // Add PID filter if specified
-        if let Some(pid) = self.config.pid_filter {
-            cmd.args(["-p", &pid.to_string()]);
-        }
-
-        // Add comm filter if specified
-        if let Some(comm) = self.config.comm_filter.first() {
-            cmd.args(["-c", comm]);
-        }
+        let pid_libraries = self.pid_filter_libraries(crate::linux_proc::openssl_libraries);
+        let mut cmd = self.sslsniff_command(&sslsniff_path, &pid_libraries);
 
         // Start sslsniff
         info!("Starting sslsniff...");
@@ -360,12 +460,30 @@ impl CapturePlugin for SslsniffCapture {
         let stdout = child.stdout.take().ok_or_else(|| {
             PluginError::InitializationFailed("Failed to capture sslsniff stdout".into())
         })?;
+        if let Some(stdin) = child.stdin.take() {
+            self.targets.connect(Box::new(stdin));
+        }
 
         self.child = Some(child);
         self.running.store(true, Ordering::SeqCst);
 
         let running = self.running.clone();
         let stats = self.stats.clone();
+        let errors = self.errors.clone();
+        let targets = self.targets.clone();
+        let heartbeat = self.heartbeat.clone();
+
+        // Connections opened before capture started were never seen being
+        // set up; seed them from /proc so early events have a destination
+        let pid_filter = self.config.pid_filter.map(|pid| vec![pid]);
+        let socket_seed = SocketSeed::scan(std::path::Path::new("/proc"), pid_filter.as_deref());
+        if !socket_seed.is_empty() {
+            info!(
+                "Found {} running processes with established TLS library connections",
+                socket_seed.len()
+            );
+        }
+        let seed_expires = Instant::now() + SOCKET_SEED_WINDOW;
 
         // Spawn reader task
         std::thread::spawn(move || {
@@ -373,8 +491,10 @@ impl CapturePlugin for SslsniffCapture {
             // Create a proc cache for enriching events with /proc info
             let mut proc_cache = crate::linux_proc::ProcInfoCache::new();
             let mut events_since_cache_clear: u64 = 0;
+            // Large reads and writes arrive in several lines
+            let mut fragments = oisp_core::sslsniff::FragmentAssembler::new();
 
-            for line in reader.lines() {
+            'lines: for line in reader.lines() {
                 if !running.load(Ordering::SeqCst) {
                     break;
                 }
@@ -385,21 +505,63 @@ impl CapturePlugin for SslsniffCapture {
                             continue;
                         }
 
+                        if let Some(total) = oisp_core::sslsniff::parse_ringbuf_dropped(&line) {
+                            stats.record_ringbuf_dropped(total);
+                            continue;
+                        }
+
+                        if oisp_core::sslsniff::parse_heartbeat(&line).is_some() {
+                            *heartbeat.lock().unwrap() = Some(Instant::now());
+                            continue;
+                        }
+
+                        if let Some(result) = oisp_core::sslsniff::parse_control_result(&line) {
+                            let awaited = targets.resolve(&result);
+                            if !result.ok {
+                                let error = result.error.unwrap_or_default();
+                                warn!(
+                                    "sslsniff {} of pid {} failed: {}",
+                                    result.control, result.pid, error
+                                );
+                                // An awaited attach records only what succeeded
+                                if result.control == "attach" && !awaited {
+                                    targets.forget(result.pid, result.lib.as_deref());
+                                }
+                                if let Some(errors) = &errors {
+                                    errors.report(
+                                        CaptureErrorKind::AttachFailed,
+                                        format!(
+                                            "Failed to {} pid {}: {}",
+                                            result.control, result.pid, error
+                                        ),
+                                    );
+                                }
+                            }
+                            continue;
+                        }
+
                         // Debug log for every line from sslsniff
                         // Using warn! so it shows up without RUST_LOG=debug
                         // tracing::warn!("sslsniff raw line: {}", line);
 
-                        match Self::parse_sslsniff_event(&line, &mut proc_cache) {
-                            Some(event) => {
-                                stats.events_captured.fetch_add(1, Ordering::Relaxed);
-                                stats
-                                    .bytes_captured
-                                    .fetch_add(event.data.len() as u64, Ordering::Relaxed);
-
-                                // Send to pipeline (blocking)
-                                if tx.blocking_send(event).is_err() {
-                                    stats.events_dropped.fetch_add(1, Ordering::Relaxed);
-                                    break;
+                        match oisp_core::sslsniff::parse_fragment(&line) {
+                            Some(fragment) => {
+                                for event in fragments.push(fragment) {
+                                    let mut event =
+                                        Self::enrich_sslsniff_event(event, &mut proc_cache);
+                                    if Instant::now() < seed_expires {
+                                        socket_seed.attribute(&mut event);
+                                    }
+                                    stats.events_captured.fetch_add(1, Ordering::Relaxed);
+                                    stats
+                                        .bytes_captured
+                                        .fetch_add(event.data.len() as u64, Ordering::Relaxed);
+
+                                    // Send to pipeline (blocking)
+                                    if tx.blocking_send(event).is_err() {
+                                        stats.events_dropped.fetch_add(1, Ordering::Relaxed);
+                                        break 'lines;
+                                    }
                                 }
                             }
                             None => {
@@ -418,10 +580,29 @@ impl CapturePlugin for SslsniffCapture {
                     Err(e) => {
                         warn!("Error reading sslsniff output: {}", e);
                         stats.errors.fetch_add(1, Ordering::Relaxed);
+                        if let Some(errors) = &errors {
+                            errors.report(
+                                CaptureErrorKind::Io,
+                                format!("Error reading sslsniff output: {}", e),
+                            );
+                        }
                     }
                 }
             }
 
+            // EOF while still running means sslsniff exited on its own,
+            // typically because probes could not be attached
+            if running.load(Ordering::SeqCst) {
+                warn!("sslsniff exited unexpectedly");
+                if let Some(errors) = &errors {
+                    errors.report(
+                        CaptureErrorKind::Stopped,
+                        "sslsniff exited unexpectedly; SSL uprobes may have failed to attach \
+                         (requires root or CAP_BPF and a readable libssl)",
+                    );
+                }
+            }
+
             info!("sslsniff reader stopped");
         });
 
@@ -432,6 +613,7 @@ impl CapturePlugin for SslsniffCapture {
     async fn stop(&mut self) -> PluginResult<()> {
         info!("Stopping sslsniff capture...");
         self.running.store(false, Ordering::SeqCst);
+        self.targets.disconnect();
 
         if let Some(ref mut child) = self.child {
             // Send SIGINT for graceful shutdown
@@ -460,9 +642,177 @@ impl CapturePlugin for SslsniffCapture {
     fn stats(&self) -> CaptureStats {
         CaptureStats {
             events_captured: self.stats.events_captured.load(Ordering::Relaxed),
-            events_dropped: self.stats.events_dropped.load(Ordering::Relaxed),
+            events_dropped: self.stats.events_dropped.load(Ordering::Relaxed)
+                + self.stats.ringbuf_dropped.load(Ordering::Relaxed),
             bytes_captured: self.stats.bytes_captured.load(Ordering::Relaxed),
             errors: self.stats.errors.load(Ordering::Relaxed),
+            ..Default::default()
         }
     }
+
+    fn set_error_sender(&mut self, errors: CaptureErrorSender) {
+        self.errors = Some(errors);
+    }
+
+    fn process_targets(&self) -> Option<SharedProcessTargets> {
+        if !self.config.runtime_attach {
+            return None;
+        }
+        Some(self.targets.clone())
+    }
+
+    fn last_heartbeat(&self) -> Option<Instant> {
+        *self.heartbeat.lock().unwrap()
+    }
+}
+
+/// Turn sslsniff/libbpf load errors into an actionable message
+fn explain_load_failure(stderr: &str) -> String {
+    if stderr.contains("BEGIN PROG LOAD LOG") || stderr.contains("verifier") {
+        "The kernel verifier rejected the eBPF programs; the kernel is likely too old \
+         (ring buffers need Linux 5.8 or newer). Run with --verbose for the verifier log."
+            .to_string()
+    } else if stderr.contains("Operation not permitted") {
+        "Loading eBPF programs needs CAP_BPF and CAP_PERFMON (CAP_SYS_ADMIN before Linux 5.8): \
+         run as root, e.g. with sudo, or grant them with \
+         `sudo setcap cap_bpf,cap_perfmon,cap_sys_admin+ep $(which oisp-sensor)`."
+            .to_string()
+    } else if stderr.contains("vmlinux BTF") || stderr.contains("kernel BTF") {
+        "Kernel BTF is missing (/sys/kernel/btf/vmlinux): the kernel must be built with \
+         CONFIG_DEBUG_INFO_BTF=y."
+            .to_string()
+    } else if stderr.contains("RLIMIT_MEMLOCK") {
+        "The locked memory limit is too low for eBPF maps: raise it with `ulimit -l unlimited`."
+            .to_string()
+    } else {
+        let detail = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("sslsniff exited without an error message");
+        format!("Failed to load eBPF programs: {}", detail.trim())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::plugins::{ProcessTargetControl, ProcessTargetError};
+
+    #[test]
+    fn test_ringbuf_size_rounding() {
+        assert_eq!(ringbuf_size_bytes(3_000_000, 4096), 4_194_304);
+        assert_eq!(ringbuf_size_bytes(8 << 20, 4096), 8 << 20);
+        assert_eq!(ringbuf_size_bytes(1000, 4096), 4096);
+        assert_eq!(ringbuf_size_bytes(0, 16384), 16384);
+
+        let runner = SslsniffCapture::with_config(SslsniffConfig {
+            ringbuf_size: Some(3_000_000),
+            ..Default::default()
+        });
+        let cmd = runner.sslsniff_command(std::path::Path::new("/usr/bin/sslsniff"), &[]);
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(&args[..2], ["--ringbuf-size", "4194304"]);
+        assert!(!args.contains(&"--control"));
+        assert!(runner.process_targets().is_none());
+
+        let runner = SslsniffCapture::with_config(SslsniffConfig {
+            pid_filter: Some(42),
+            runtime_attach: true,
+            ..Default::default()
+        });
+        let cmd = runner.sslsniff_command(std::path::Path::new("/usr/bin/sslsniff"), &[]);
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert!(args.contains(&"--control"));
+        assert!(runner.process_targets().is_some());
+    }
+
+    #[test]
+    fn test_pid_filter_probes_every_loaded_library() {
+        fn lookup(pid: u32) -> Result<Vec<PathBuf>, ProcessTargetError> {
+            Ok(vec![
+                PathBuf::from(format!("/proc/{}/root/usr/lib/libssl.so.3", pid)),
+                PathBuf::from(format!("/proc/{}/root/opt/app/libssl.so.1.1", pid)),
+            ])
+        }
+
+        let runner = SslsniffCapture::with_config(SslsniffConfig {
+            pid_filter: Some(42),
+            ..Default::default()
+        });
+        let libraries = runner.pid_filter_libraries(lookup);
+        let cmd = runner.sslsniff_command(std::path::Path::new("/usr/bin/sslsniff"), &libraries);
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "-p",
+                "42",
+                "--lib",
+                "/proc/42/root/usr/lib/libssl.so.3",
+                "--lib",
+                "/proc/42/root/opt/app/libssl.so.1.1",
+            ]
+        );
+        assert!(!args.contains(&"--control"));
+        assert_eq!(
+            runner.targets.targets().libraries[&42],
+            [
+                "/proc/42/root/usr/lib/libssl.so.3",
+                "/proc/42/root/opt/app/libssl.so.1.1",
+            ]
+        );
+
+        // Failed library probes leave the process traced through libssl
+        runner
+            .targets
+            .forget(42, Some("/proc/42/root/usr/lib/libssl.so.3"));
+        runner
+            .targets
+            .forget(42, Some("/proc/42/root/opt/app/libssl.so.1.1"));
+        assert_eq!(runner.targets.targets().pids, vec![42]);
+
+        // Without a pid filter the process isn't known yet
+        let runner = SslsniffCapture::new();
+        assert!(runner.pid_filter_libraries(lookup).is_empty());
+    }
+
+    #[test]
+    fn test_ringbuf_drops_reported_as_dropped_events() {
+        let runner = SslsniffCapture::new();
+        runner.stats.record_ringbuf_dropped(3);
+        runner.stats.record_ringbuf_dropped(10);
+        runner.stats.events_dropped.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(runner.stats().events_dropped, 11);
+    }
+
+    #[test]
+    fn test_explain_load_failure_without_privileges() {
+        let stderr = "libbpf: map 'rb': failed to create: Operation not permitted(-1)\n\
+                      libbpf: failed to load object 'sslsniff_bpf'\n\
+                      libbpf: failed to load BPF skeleton 'sslsniff_bpf': -1\n\
+                      failed to load BPF object: -1 (Operation not permitted)\n";
+        let message = explain_load_failure(stderr);
+        assert!(message.contains("needs CAP_BPF"), "{}", message);
+        assert!(message.contains("run as root"), "{}", message);
+    }
+
+    #[test]
+    fn test_explain_load_failure_other_causes() {
+        let stderr = "libbpf: failed to find valid kernel BTF\n\
+                      libbpf: Error loading vmlinux BTF: -3\n\
+                      failed to open BPF object: -3 (No such process)\n";
+        assert!(explain_load_failure(stderr).contains("BTF is missing"));
+
+        let stderr =
+            "libbpf: prog 'probe_SSL_rw_enter': BPF program load failed: Invalid argument\n\
+                      libbpf: prog 'probe_SSL_rw_enter': -- BEGIN PROG LOAD LOG --\n\
+                      unknown func bpf_ringbuf_reserve#131\n";
+        assert!(explain_load_failure(stderr).contains("verifier rejected"));
+
+        assert_eq!(
+            explain_load_failure("something odd\n\n"),
+            "Failed to load eBPF programs: something odd"
+        );
+    }
 }
@@ -0,0 +1,487 @@
+//! Runtime process attach/detach for sslsniff
+//!
+//! sslsniff run with `--control` keeps the processes it traces in the
+//! `target_pids` BPF map and reads commands on stdin to change it:
+//! `attach <pid> <lib>` adds the pid and, if `<lib>` is not the library
+//! already probed, attaches uprobes to it for that process; `detach <pid>`
+//! undoes both. A process with several libssl versions loaded gets one
+//! `attach` per library; the `-p` process gets one `--lib` per library at
+//! startup instead. The results come back on stdout (see
+//! [`oisp_core::sslsniff::parse_control_result`]); an `attach` waits for
+//! them before it reports the process attached.
+
+use oisp_core::plugins::{ProcessTargetControl, ProcessTargetError, ProcessTargets};
+use oisp_core::sslsniff::ControlResult;
+use parking_lot::{Condvar, Mutex};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long an `attach` waits for sslsniff to report its result
+const ATTACH_RESULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Finds the OpenSSL libraries a process has loaded
+pub(crate) type LibraryLookup = fn(u32) -> Result<Vec<PathBuf>, ProcessTargetError>;
+
+/// Process filter of a running sslsniff, changed through its stdin
+pub struct SslsniffTargets {
+    state: Mutex<TargetState>,
+    /// Results awaited by `attach`, by pid and library; `None` until
+    /// sslsniff reports it
+    awaited: Mutex<BTreeMap<(u32, String), Option<ControlResult>>>,
+    result_ready: Condvar,
+    result_timeout: Duration,
+    find_library: LibraryLookup,
+}
+
+struct TargetState {
+    /// Whether only `pids` are traced (sslsniff started with `-p`)
+    filtered: bool,
+    /// The `-p` process, traced whatever its libraries' probes do
+    startup_pid: Option<u32>,
+    pids: BTreeSet<u32>,
+    /// Libraries sent with `attach` for each pid
+    libraries: BTreeMap<u32, Vec<String>>,
+    /// sslsniff's stdin while it runs
+    control: Option<Box<dyn Write + Send>>,
+}
+
+impl SslsniffTargets {
+    /// Targets for an sslsniff started with an optional `-p` filter
+    pub fn new(pid_filter: Option<u32>) -> Self {
+        Self::with_lookup(pid_filter, crate::linux_proc::openssl_libraries)
+    }
+
+    fn with_lookup(pid_filter: Option<u32>, find_library: LibraryLookup) -> Self {
+        Self {
+            state: Mutex::new(TargetState {
+                filtered: pid_filter.is_some(),
+                startup_pid: pid_filter,
+                pids: pid_filter.into_iter().collect(),
+                libraries: BTreeMap::new(),
+                control: None,
+            }),
+            awaited: Mutex::new(BTreeMap::new()),
+            result_ready: Condvar::new(),
+            result_timeout: ATTACH_RESULT_TIMEOUT,
+            find_library,
+        }
+    }
+
+    /// Connect to a started sslsniff's stdin
+    pub fn connect(&self, control: Box<dyn Write + Send>) {
+        self.state.lock().control = Some(control);
+    }
+
+    /// Forget the stdin of a stopped sslsniff
+    pub fn disconnect(&self) {
+        self.state.lock().control = None;
+    }
+
+    /// Record the libraries sslsniff was started with for the `-p` process
+    pub fn started_with(&self, pid: u32, libraries: Vec<String>) {
+        self.state.lock().libraries.insert(pid, libraries);
+    }
+
+    /// Drop a library whose attach sslsniff reported as failed, and the pid
+    /// with it once none of its libraries is left (or `library` is unknown);
+    /// the `-p` process stays traced through the library ldconfig resolves
+    pub fn forget(&self, pid: u32, library: Option<&str>) {
+        let mut state = self.state.lock();
+        if let (Some(library), Some(libraries)) = (library, state.libraries.get_mut(&pid)) {
+            libraries.retain(|l| l != library);
+            if !libraries.is_empty() || state.startup_pid == Some(pid) {
+                return;
+            }
+        }
+        state.pids.remove(&pid);
+        state.libraries.remove(&pid);
+    }
+
+    /// Hand a control result from sslsniff's stdout to the `attach` waiting
+    /// for it; returns false if none is
+    pub fn resolve(&self, result: &ControlResult) -> bool {
+        if result.control != "attach" {
+            return false;
+        }
+        let mut awaited = self.awaited.lock();
+        let mut resolved = false;
+        for ((pid, library), slot) in awaited.iter_mut() {
+            // A malformed command is reported without its library
+            let matches =
+                *pid == result.pid && result.lib.as_deref().is_none_or(|lib| lib == library);
+            if matches && slot.is_none() {
+                *slot = Some(result.clone());
+                resolved = true;
+            }
+        }
+        if resolved {
+            self.result_ready.notify_all();
+        }
+        resolved
+    }
+
+    /// Wait for sslsniff's result for each of `libraries`; `None` for any
+    /// not reported in time
+    fn await_results(&self, pid: u32, libraries: &[String]) -> Vec<Option<ControlResult>> {
+        let deadline = Instant::now() + self.result_timeout;
+        let mut awaited = self.awaited.lock();
+        while libraries
+            .iter()
+            .any(|library| matches!(awaited.get(&(pid, library.clone())), Some(None)))
+        {
+            if self
+                .result_ready
+                .wait_until(&mut awaited, deadline)
+                .timed_out()
+            {
+                break;
+            }
+        }
+        libraries
+            .iter()
+            .map(|library| awaited.remove(&(pid, library.clone())).flatten())
+            .collect()
+    }
+
+    fn send(state: &mut TargetState, command: &str) -> Result<(), ProcessTargetError> {
+        let control = state
+            .control
+            .as_mut()
+            .ok_or(ProcessTargetError::NotRunning)?;
+        writeln!(control, "{}", command)
+            .and_then(|_| control.flush())
+            .map_err(|e| {
+                ProcessTargetError::Failed(format!("Failed to send command to sslsniff: {}", e))
+            })
+    }
+}
+
+impl TargetState {
+    fn snapshot(&self) -> ProcessTargets {
+        ProcessTargets {
+            filtered: self.filtered,
+            pids: self.pids.iter().copied().collect(),
+            libraries: self.libraries.clone(),
+        }
+    }
+}
+
+impl ProcessTargetControl for SslsniffTargets {
+    fn attach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError> {
+        let libraries: Vec<String> = (self.find_library)(pid)?
+            .iter()
+            .map(|library| library.to_string_lossy().to_string())
+            .collect();
+        {
+            let mut state = self.state.lock();
+            let mut awaited = self.awaited.lock();
+            for library in &libraries {
+                awaited.insert((pid, library.clone()), None);
+            }
+            drop(awaited);
+            for library in &libraries {
+                if let Err(e) = Self::send(&mut state, &format!("attach {} {}", pid, library)) {
+                    let mut awaited = self.awaited.lock();
+                    for library in &libraries {
+                        awaited.remove(&(pid, library.clone()));
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        // The reader of sslsniff's stdout resolves these; the state lock is
+        // not held meanwhile, since that reader takes it too
+        let results = self.await_results(pid, &libraries);
+        let mut attached = Vec::new();
+        let mut errors = Vec::new();
+        for (library, result) in libraries.into_iter().zip(results) {
+            match result {
+                Some(result) if result.ok => attached.push(library),
+                Some(result) => {
+                    errors.push(format!("{}: {}", library, result.error.unwrap_or_default()))
+                }
+                None => errors.push(format!("{}: no result from sslsniff", library)),
+            }
+        }
+        if attached.is_empty() {
+            return Err(ProcessTargetError::Failed(format!(
+                "Failed to attach to pid {}: {}",
+                pid,
+                errors.join("; ")
+            )));
+        }
+
+        let mut state = self.state.lock();
+        state.pids.insert(pid);
+        info!(
+            "Attached SSL capture to pid {} ({})",
+            pid,
+            attached.join(", ")
+        );
+        state.libraries.insert(pid, attached);
+        Ok(state.snapshot())
+    }
+
+    fn detach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError> {
+        let mut state = self.state.lock();
+        // Without `-p` the kernel doesn't filter by pid, so removing the
+        // process's own probes would not stop its capture
+        if !state.filtered {
+            return Err(ProcessTargetError::NotFiltered);
+        }
+        if !state.pids.contains(&pid) {
+            return Err(ProcessTargetError::NotAttached(pid));
+        }
+        Self::send(&mut state, &format!("detach {}", pid))?;
+        state.pids.remove(&pid);
+        state.libraries.remove(&pid);
+        info!("Detached SSL capture from pid {}", pid);
+        Ok(state.snapshot())
+    }
+
+    fn targets(&self) -> ProcessTargets {
+        self.state.lock().snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Weak};
+
+    /// Captures what would be written to sslsniff's stdin, answering each
+    /// `attach` the way sslsniff would once connected with [`replying`]
+    #[derive(Clone, Default)]
+    struct Commands {
+        written: Arc<Mutex<Vec<u8>>>,
+        line: Arc<Mutex<String>>,
+        sslsniff: Option<Weak<SslsniffTargets>>,
+        /// Libraries whose attach fails
+        failing: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Write for Commands {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.lock().extend_from_slice(buf);
+            let mut line = self.line.lock();
+            line.push_str(std::str::from_utf8(buf).unwrap());
+            while let Some(end) = line.find('\n') {
+                let command: String = line.drain(..=end).collect();
+                self.reply(command.trim_end());
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Commands {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.written.lock())).unwrap()
+        }
+
+        fn fail(&self, library: &str) {
+            self.failing.lock().push(library.to_string());
+        }
+
+        fn reply(&self, command: &str) {
+            let Some(targets) = self.sslsniff.as_ref().and_then(Weak::upgrade) else {
+                return;
+            };
+            let mut parts = command.split(' ');
+            if parts.next() != Some("attach") {
+                return;
+            }
+            let pid = parts.next().unwrap().parse().unwrap();
+            let lib = parts.next().unwrap().to_string();
+            let failed = self.failing.lock().contains(&lib);
+            targets.resolve(&ControlResult {
+                control: "attach".to_string(),
+                pid,
+                lib: Some(lib),
+                ok: !failed,
+                error: failed.then(|| "SSL_write not found".to_string()),
+            });
+        }
+    }
+
+    /// Connect `targets` to a fake sslsniff that answers its commands
+    fn replying(targets: &Arc<SslsniffTargets>) -> Commands {
+        let commands = Commands {
+            sslsniff: Some(Arc::downgrade(targets)),
+            ..Default::default()
+        };
+        targets.connect(Box::new(commands.clone()));
+        commands
+    }
+
+    fn fake_lookup(pid: u32) -> Result<Vec<PathBuf>, ProcessTargetError> {
+        match pid {
+            100 | 200 => Ok(vec![PathBuf::from(format!(
+                "/proc/{}/root/usr/lib/libssl.so.3",
+                pid
+            ))]),
+            400 => Ok(vec![
+                PathBuf::from("/proc/400/root/usr/lib/libssl.so.3"),
+                PathBuf::from("/proc/400/root/opt/plugin/libssl.so.1.1"),
+            ]),
+            300 => Err(ProcessTargetError::NoTlsLibrary(pid)),
+            _ => Err(ProcessTargetError::NoSuchProcess(pid)),
+        }
+    }
+
+    #[test]
+    fn test_attach_detach_updates_filter() {
+        let targets = Arc::new(SslsniffTargets::with_lookup(Some(100), fake_lookup));
+        let commands = replying(&targets);
+        assert_eq!(
+            targets.targets(),
+            ProcessTargets {
+                filtered: true,
+                pids: vec![100],
+                ..Default::default()
+            }
+        );
+
+        let after = targets.attach(200).unwrap();
+        assert_eq!(after.pids, vec![100, 200]);
+        assert_eq!(
+            commands.take(),
+            "attach 200 /proc/200/root/usr/lib/libssl.so.3\n"
+        );
+
+        let after = targets.detach(100).unwrap();
+        assert_eq!(after.pids, vec![200]);
+        assert!(after.filtered);
+        assert_eq!(commands.take(), "detach 100\n");
+
+        assert_eq!(
+            targets.detach(100),
+            Err(ProcessTargetError::NotAttached(100))
+        );
+        assert_eq!(commands.take(), "");
+    }
+
+    #[test]
+    fn test_attach_validates_process() {
+        let targets = SslsniffTargets::with_lookup(None, fake_lookup);
+        let commands = Commands::default();
+        targets.connect(Box::new(commands.clone()));
+
+        assert_eq!(
+            targets.attach(300),
+            Err(ProcessTargetError::NoTlsLibrary(300))
+        );
+        assert_eq!(
+            targets.attach(999),
+            Err(ProcessTargetError::NoSuchProcess(999))
+        );
+        assert_eq!(commands.take(), "");
+        assert_eq!(targets.targets(), ProcessTargets::default());
+    }
+
+    #[test]
+    fn test_attach_requires_running_sslsniff() {
+        let targets = Arc::new(SslsniffTargets::with_lookup(Some(200), fake_lookup));
+        assert_eq!(targets.attach(100), Err(ProcessTargetError::NotRunning));
+
+        replying(&targets);
+        targets.attach(100).unwrap();
+        targets.disconnect();
+        assert_eq!(targets.detach(100), Err(ProcessTargetError::NotRunning));
+        assert_eq!(targets.targets().pids, vec![100, 200]);
+
+        targets.forget(100, None);
+        assert_eq!(targets.targets().pids, vec![200]);
+    }
+
+    #[test]
+    fn test_detach_requires_pid_filter() {
+        let targets = Arc::new(SslsniffTargets::with_lookup(None, fake_lookup));
+        let commands = replying(&targets);
+        targets.attach(100).unwrap();
+        commands.take();
+
+        // Every process is traced anyway, so detach can't stop anything
+        assert_eq!(targets.detach(100), Err(ProcessTargetError::NotFiltered));
+        assert_eq!(commands.take(), "");
+        assert_eq!(targets.targets().pids, vec![100]);
+    }
+
+    #[test]
+    fn test_attach_every_libssl_version() {
+        let targets = Arc::new(SslsniffTargets::with_lookup(None, fake_lookup));
+        let commands = replying(&targets);
+
+        let after = targets.attach(400).unwrap();
+        assert_eq!(after.pids, vec![400]);
+        assert_eq!(
+            after.libraries[&400],
+            vec![
+                "/proc/400/root/usr/lib/libssl.so.3",
+                "/proc/400/root/opt/plugin/libssl.so.1.1"
+            ]
+        );
+        assert_eq!(
+            commands.take(),
+            "attach 400 /proc/400/root/usr/lib/libssl.so.3\n\
+             attach 400 /proc/400/root/opt/plugin/libssl.so.1.1\n"
+        );
+
+        // One library failing leaves the process traced through the other
+        targets.forget(400, Some("/proc/400/root/opt/plugin/libssl.so.1.1"));
+        let now = targets.targets();
+        assert_eq!(now.pids, vec![400]);
+        assert_eq!(
+            now.libraries[&400],
+            vec!["/proc/400/root/usr/lib/libssl.so.3"]
+        );
+
+        targets.forget(400, Some("/proc/400/root/usr/lib/libssl.so.3"));
+        assert_eq!(targets.targets(), ProcessTargets::default());
+    }
+
+    #[test]
+    fn test_attach_reports_sslsniff_failure() {
+        let targets = Arc::new(SslsniffTargets::with_lookup(Some(100), fake_lookup));
+        let commands = replying(&targets);
+
+        // Only what sslsniff managed to probe is recorded
+        commands.fail("/proc/400/root/opt/plugin/libssl.so.1.1");
+        let after = targets.attach(400).unwrap();
+        assert_eq!(
+            after.libraries[&400],
+            vec!["/proc/400/root/usr/lib/libssl.so.3"]
+        );
+
+        commands.fail("/proc/200/root/usr/lib/libssl.so.3");
+        assert_eq!(
+            targets.attach(200),
+            Err(ProcessTargetError::Failed(
+                "Failed to attach to pid 200: /proc/200/root/usr/lib/libssl.so.3: SSL_write not found"
+                    .to_string()
+            ))
+        );
+        assert_eq!(targets.targets().pids, vec![100, 400]);
+    }
+
+    #[test]
+    fn test_attach_times_out_without_result() {
+        let mut targets = SslsniffTargets::with_lookup(Some(100), fake_lookup);
+        targets.result_timeout = Duration::from_millis(50);
+        targets.connect(Box::new(Commands::default()));
+
+        assert!(matches!(
+            targets.attach(200),
+            Err(ProcessTargetError::Failed(e)) if e.contains("no result from sslsniff")
+        ));
+        assert_eq!(targets.targets().pids, vec![100]);
+        assert!(targets.awaited.lock().is_empty());
+    }
+}
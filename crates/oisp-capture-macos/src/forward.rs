@@ -0,0 +1,390 @@
+//! Forwarding of socket server events to the pipeline
+//!
+//! The socket server has to keep reading the extension's socket however slow
+//! the pipeline is: a blocked send backs up into the Network Extension.
+//! Events are moved through a bounded buffer instead, and when the pipeline
+//! falls behind and the buffer fills, the [`OverflowPolicy`] decides what is
+//! lost. Every event that never reaches the pipeline is counted in
+//! `events_dropped`, including those still buffered at shutdown.
+
+use oisp_core::plugins::{RawCaptureEvent, RawEventKind};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::CaptureStatsInner;
+
+/// Default number of events buffered between the socket server and the pipeline
+pub const DEFAULT_FORWARD_BUFFER: usize = 1000;
+
+/// Largest event coalescing may build
+const MAX_COALESCED_BYTES: usize = 1024 * 1024;
+
+/// Minimum time between warnings about a full forward buffer
+const DROP_WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What to do with an event that arrives while the forward buffer is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Append the event's data to the latest buffered event of the same
+    /// connection when that is also the same direction, so no plaintext is
+    /// lost; otherwise drop the oldest event
+    #[default]
+    Coalesce,
+    /// Drop the oldest buffered event, favoring recent traffic
+    DropOldest,
+    /// Drop the incoming event, keeping what is already buffered
+    DropNewest,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Coalesce => "coalesce",
+            Self::DropOldest => "drop_oldest",
+            Self::DropNewest => "drop_newest",
+        }
+    }
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "coalesce" => Ok(Self::Coalesce),
+            "drop_oldest" => Ok(Self::DropOldest),
+            "drop_newest" => Ok(Self::DropNewest),
+            other => Err(format!("unknown overflow policy: {}", other)),
+        }
+    }
+}
+
+/// Events waiting for room in the pipeline channel
+struct ForwardBuffer {
+    events: VecDeque<RawCaptureEvent>,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl ForwardBuffer {
+    fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+        }
+    }
+
+    /// Buffer an event, returning whether one was dropped to stay in capacity
+    fn push(&mut self, event: RawCaptureEvent) -> bool {
+        if self.events.len() < self.capacity {
+            self.events.push_back(event);
+            return false;
+        }
+        let policy = self.policy;
+        match policy {
+            OverflowPolicy::Coalesce if self.coalesce(&event) => false,
+            OverflowPolicy::Coalesce | OverflowPolicy::DropOldest => {
+                self.events.pop_front();
+                self.events.push_back(event);
+                true
+            }
+            OverflowPolicy::DropNewest => true,
+        }
+    }
+
+    /// Append `event`'s data to the connection's latest buffered event
+    ///
+    /// Only when that event is in the same direction: merging across a
+    /// write and read would reorder a request and its response.
+    fn coalesce(&mut self, event: &RawCaptureEvent) -> bool {
+        if !matches!(event.kind, RawEventKind::SslRead | RawEventKind::SslWrite) {
+            return false;
+        }
+        let Some(latest) = self
+            .events
+            .iter_mut()
+            .rev()
+            .find(|buffered| same_connection(buffered, event))
+        else {
+            return false;
+        };
+        if std::mem::discriminant(&latest.kind) != std::mem::discriminant(&event.kind)
+            || latest.tid != event.tid
+            || latest.data.len() + event.data.len() > MAX_COALESCED_BYTES
+        {
+            return false;
+        }
+        latest.data.extend_from_slice(&event.data);
+        true
+    }
+}
+
+fn same_connection(a: &RawCaptureEvent, b: &RawCaptureEvent) -> bool {
+    a.pid == b.pid
+        && match (a.metadata.fd, b.metadata.fd) {
+            (Some(fa), Some(fb)) => fa == fb,
+            (None, None) => a.metadata.remote_addr == b.metadata.remote_addr,
+            _ => false,
+        }
+}
+
+/// Spawn the task moving events from `intake` to the pipeline's `output`
+///
+/// `intake` is always drained, so the socket server never waits on the
+/// pipeline. The task ends when `shutdown` fires (or its sender is dropped),
+/// when the pipeline channel closes, or once `intake` is closed and the
+/// buffer is flushed.
+pub(crate) fn spawn(
+    mut intake: mpsc::Receiver<RawCaptureEvent>,
+    output: mpsc::Sender<RawCaptureEvent>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    stats: Arc<CaptureStatsInner>,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut buffer = ForwardBuffer::new(capacity, policy);
+        let mut intake_open = true;
+        let mut last_warning: Option<Instant> = None;
+        let mut dropped_since_warning = 0u64;
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.changed() => break,
+                permit = output.reserve(), if !buffer.events.is_empty() => match permit {
+                    Ok(permit) => {
+                        if let Some(event) = buffer.events.pop_front() {
+                            permit.send(event);
+                        }
+                    }
+                    Err(_) => {
+                        warn!("Pipeline channel closed; no longer forwarding macOS events");
+                        break;
+                    }
+                },
+                event = intake.recv(), if intake_open => match event {
+                    Some(event) => {
+                        stats.events_captured.fetch_add(1, Ordering::Relaxed);
+                        stats
+                            .bytes_captured
+                            .fetch_add(event.data.len() as u64, Ordering::Relaxed);
+                        if buffer.push(event) {
+                            stats.events_dropped.fetch_add(1, Ordering::Relaxed);
+                            dropped_since_warning += 1;
+                            if last_warning.is_none_or(|at| at.elapsed() >= DROP_WARN_INTERVAL) {
+                                warn!(
+                                    "macOS forward buffer full ({} events), dropped {} (policy: {})",
+                                    buffer.capacity, dropped_since_warning, buffer.policy
+                                );
+                                last_warning = Some(Instant::now());
+                                dropped_since_warning = 0;
+                            }
+                        }
+                    }
+                    None => intake_open = false,
+                },
+                else => break,
+            }
+        }
+
+        // Hand over what fits without waiting; the rest is lost
+        intake.close();
+        while let Ok(event) = intake.try_recv() {
+            stats.events_captured.fetch_add(1, Ordering::Relaxed);
+            stats
+                .bytes_captured
+                .fetch_add(event.data.len() as u64, Ordering::Relaxed);
+            buffer.events.push_back(event);
+        }
+        let mut lost = 0u64;
+        for event in buffer.events.drain(..) {
+            if output.try_send(event).is_err() {
+                lost += 1;
+            }
+        }
+        if lost > 0 {
+            stats.events_dropped.fetch_add(lost, Ordering::Relaxed);
+            warn!("Dropped {} buffered macOS events at shutdown", lost);
+        }
+        info!("macOS event forwarding stopped");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::plugins::RawEventMetadata;
+
+    fn event(n: usize, fd: i32, kind: RawEventKind) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: n.to_string(),
+            timestamp_ns: n as u64 + 1,
+            kind,
+            pid: 42,
+            tid: Some(1),
+            data: format!("chunk{};", n).into_bytes(),
+            metadata: RawEventMetadata {
+                fd: Some(fd),
+                ..Default::default()
+            },
+        }
+    }
+
+    struct Forwarder {
+        intake: mpsc::Sender<RawCaptureEvent>,
+        output: mpsc::Receiver<RawCaptureEvent>,
+        stats: Arc<CaptureStatsInner>,
+        shutdown: watch::Sender<bool>,
+        handle: JoinHandle<()>,
+    }
+
+    /// A forwarder whose pipeline channel holds a single event
+    fn forwarder(capacity: usize, policy: OverflowPolicy) -> Forwarder {
+        let (intake, intake_rx) = mpsc::channel(4);
+        let (output_tx, output) = mpsc::channel(1);
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let stats = Arc::new(CaptureStatsInner::default());
+        let handle = spawn(
+            intake_rx,
+            output_tx,
+            capacity,
+            policy,
+            stats.clone(),
+            shutdown_rx,
+        );
+        Forwarder {
+            intake,
+            output,
+            stats,
+            shutdown,
+            handle,
+        }
+    }
+
+    /// Send `events` as the socket server would, failing if a send blocks
+    async fn feed(intake: &mpsc::Sender<RawCaptureEvent>, events: Vec<RawCaptureEvent>) {
+        for event in events {
+            tokio::time::timeout(Duration::from_secs(1), intake.send(event))
+                .await
+                .expect("socket server blocked on a slow pipeline")
+                .unwrap();
+        }
+    }
+
+    /// Read from the pipeline channel until nothing more arrives
+    async fn receive_all(output: &mut mpsc::Receiver<RawCaptureEvent>) -> Vec<RawCaptureEvent> {
+        let mut received = Vec::new();
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(100), output.recv()).await
+        {
+            received.push(event);
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_drops_counted() {
+        let mut f = forwarder(10, OverflowPolicy::DropNewest);
+
+        // The pipeline reads nothing while 100 events arrive
+        let events = (0..100)
+            .map(|n| event(n, n as i32, RawEventKind::SslRead))
+            .collect();
+        feed(&f.intake, events).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Then catches up
+        let received = receive_all(&mut f.output).await;
+
+        // One in the pipeline channel, ten buffered, the rest dropped
+        assert_eq!(received.len(), 11);
+        let ids: Vec<&str> = received.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]
+        );
+        assert_eq!(f.stats.events_captured.load(Ordering::Relaxed), 100);
+        assert_eq!(f.stats.events_dropped.load(Ordering::Relaxed), 89);
+
+        f.shutdown.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), f.handle)
+            .await
+            .expect("forwarder exits on shutdown")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_keeps_connection_data() {
+        let mut f = forwarder(2, OverflowPolicy::Coalesce);
+
+        // fd 5 streams a response, fd 6 writes once
+        let mut events = vec![
+            event(0, 5, RawEventKind::SslRead),
+            event(1, 5, RawEventKind::SslRead),
+            event(2, 6, RawEventKind::SslWrite),
+        ];
+        events.extend((3..8).map(|n| event(n, 5, RawEventKind::SslRead)));
+        feed(&f.intake, events).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let received = receive_all(&mut f.output).await;
+        assert_eq!(f.stats.events_dropped.load(Ordering::Relaxed), 0);
+        let data: Vec<String> = received
+            .iter()
+            .map(|e| String::from_utf8_lossy(&e.data).to_string())
+            .collect();
+        assert_eq!(
+            data,
+            [
+                "chunk0;",
+                "chunk1;chunk3;chunk4;chunk5;chunk6;chunk7;",
+                "chunk2;"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_with_open_intake_and_full_pipeline() {
+        let f = forwarder(10, OverflowPolicy::DropOldest);
+        let events = (0..5)
+            .map(|n| event(n, 5, RawEventKind::SslWrite))
+            .collect();
+        feed(&f.intake, events).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The socket server still holds its sender; shutdown alone ends the task
+        f.shutdown.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), f.handle)
+            .await
+            .expect("forwarder exits on shutdown")
+            .unwrap();
+
+        // One reached the pipeline channel, the other four were never delivered
+        assert_eq!(f.stats.events_captured.load(Ordering::Relaxed), 5);
+        assert_eq!(f.stats.events_dropped.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_parse_overflow_policy() {
+        assert_eq!("coalesce".parse(), Ok(OverflowPolicy::Coalesce));
+        assert_eq!("drop-oldest".parse(), Ok(OverflowPolicy::DropOldest));
+        assert_eq!("DROP_NEWEST".parse(), Ok(OverflowPolicy::DropNewest));
+        assert!("block".parse::<OverflowPolicy>().is_err());
+    }
+}
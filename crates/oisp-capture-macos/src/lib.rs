@@ -13,10 +13,13 @@
 //! - Notarized by Apple
 //! - Approved by the user in System Preferences
 
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub mod forward;
 #[cfg(target_os = "macos")]
 pub mod socket_server;
 
 use async_trait::async_trait;
+pub use forward::{OverflowPolicy, DEFAULT_FORWARD_BUFFER};
 use oisp_core::plugins::{
     CapturePlugin, CaptureStats, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
     RawCaptureEvent,
@@ -30,6 +33,8 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 #[cfg(target_os = "macos")]
+use tokio::sync::watch;
+#[cfg(target_os = "macos")]
 use tokio::task::JoinHandle;
 use tracing::info;
 #[cfg(target_os = "macos")]
@@ -52,6 +57,12 @@ pub struct MacOSCaptureConfig {
 
     /// Unix socket path for receiving events from Swift extension
     pub socket_path: String,
+
+    /// Events buffered between the socket server and the pipeline
+    pub forward_buffer: usize,
+
+    /// What is lost when the pipeline falls behind and the buffer fills
+    pub overflow_policy: OverflowPolicy,
 }
 
 impl Default for MacOSCaptureConfig {
@@ -62,6 +73,8 @@ impl Default for MacOSCaptureConfig {
             network: true,
             use_system_extension: true,
             socket_path: DEFAULT_SOCKET_PATH.to_string(),
+            forward_buffer: DEFAULT_FORWARD_BUFFER,
+            overflow_policy: OverflowPolicy::default(),
         }
     }
 }
@@ -75,8 +88,14 @@ pub struct MacOSCapture {
     socket_server: Option<SocketServer>,
     #[cfg(target_os = "macos")]
     server_handle: Option<JoinHandle<()>>,
+    #[cfg(target_os = "macos")]
+    forward_handle: Option<JoinHandle<()>>,
+    /// Stops the forwarding task
+    #[cfg(target_os = "macos")]
+    forward_shutdown: Option<watch::Sender<bool>>,
 }
 
+#[derive(Default)]
 struct CaptureStatsInner {
     events_captured: AtomicU64,
     events_dropped: AtomicU64,
@@ -93,16 +112,15 @@ impl MacOSCapture {
         Self {
             config,
             running: Arc::new(AtomicBool::new(false)),
-            stats: Arc::new(CaptureStatsInner {
-                events_captured: AtomicU64::new(0),
-                events_dropped: AtomicU64::new(0),
-                bytes_captured: AtomicU64::new(0),
-                errors: AtomicU64::new(0),
-            }),
+            stats: Arc::new(CaptureStatsInner::default()),
             #[cfg(target_os = "macos")]
             socket_server: None,
             #[cfg(target_os = "macos")]
             server_handle: None,
+            #[cfg(target_os = "macos")]
+            forward_handle: None,
+            #[cfg(target_os = "macos")]
+            forward_shutdown: None,
         }
     }
 
@@ -165,16 +183,28 @@ impl Plugin for MacOSCapture {
         if let Some(use_sysext) = config.get::<bool>("use_system_extension") {
             self.config.use_system_extension = use_sysext;
         }
+        if let Some(forward_buffer) = config.get::<usize>("forward_buffer") {
+            self.config.forward_buffer = forward_buffer;
+        }
+        if let Some(policy) = config.get::<String>("overflow_policy") {
+            self.config.overflow_policy =
+                policy.parse().map_err(PluginError::InitializationFailed)?;
+        }
         Ok(())
     }
 
     fn shutdown(&mut self) -> PluginResult<()> {
         self.running.store(false, Ordering::SeqCst);
 
-        // Stop the socket server
+        // Stop the socket server and forwarding
         #[cfg(target_os = "macos")]
-        if let Some(server) = &self.socket_server {
-            server.stop();
+        {
+            if let Some(server) = &self.socket_server {
+                server.stop();
+            }
+            if let Some(shutdown) = self.forward_shutdown.take() {
+                let _ = shutdown.send(true);
+            }
         }
 
         Ok(())
@@ -216,33 +246,19 @@ impl CapturePlugin for MacOSCapture {
                 let server = SocketServer::new(&self.config.socket_path);
                 let stats = self.stats.clone();
 
-                // Create a wrapper channel that updates our stats
-                let (internal_tx, mut internal_rx) = mpsc::channel::<RawCaptureEvent>(1000);
-
-                // Forward events from internal channel to external channel, updating stats
-                let external_tx = tx.clone();
-                let running = self.running.clone();
-                tokio::spawn(async move {
-                    while running.load(Ordering::SeqCst) {
-                        match internal_rx.recv().await {
-                            Some(event) => {
-                                let bytes = event.data.len() as u64;
-                                stats.events_captured.fetch_add(1, Ordering::Relaxed);
-                                stats.bytes_captured.fetch_add(bytes, Ordering::Relaxed);
-
-                                if external_tx.send(event).await.is_err() {
-                                    stats.events_dropped.fetch_add(1, Ordering::Relaxed);
-                                    warn!("Failed to forward event - channel closed");
-                                    break;
-                                }
-                            }
-                            None => {
-                                info!("Internal event channel closed");
-                                break;
-                            }
-                        }
-                    }
-                });
+                // Forward events to the pipeline through a bounded buffer,
+                // updating stats
+                let (internal_tx, internal_rx) = mpsc::channel::<RawCaptureEvent>(1000);
+                let (shutdown_tx, shutdown_rx) = watch::channel(false);
+                self.forward_handle = Some(forward::spawn(
+                    internal_rx,
+                    tx.clone(),
+                    self.config.forward_buffer,
+                    self.config.overflow_policy,
+                    stats,
+                    shutdown_rx,
+                ));
+                self.forward_shutdown = Some(shutdown_tx);
 
                 // Start the socket server
                 match server.start(internal_tx).await {
@@ -285,6 +301,15 @@ impl CapturePlugin for MacOSCapture {
                 let _ = handle.await;
             }
 
+            // Then for forwarding, which hands over what it can and counts
+            // the rest as dropped
+            if let Some(shutdown) = self.forward_shutdown.take() {
+                let _ = shutdown.send(true);
+            }
+            if let Some(handle) = self.forward_handle.take() {
+                let _ = handle.await;
+            }
+
             self.socket_server = None;
         }
 
@@ -302,6 +327,14 @@ impl CapturePlugin for MacOSCapture {
             events_dropped: self.stats.events_dropped.load(Ordering::Relaxed),
             bytes_captured: self.stats.bytes_captured.load(Ordering::Relaxed),
             errors: self.stats.errors.load(Ordering::Relaxed),
+            #[cfg(target_os = "macos")]
+            parse_errors: self
+                .socket_server
+                .as_ref()
+                .map(|s| s.stats().parse_diagnostics.histogram())
+                .unwrap_or_default(),
+            #[cfg(not(target_os = "macos"))]
+            parse_errors: Default::default(),
         }
     }
 }
@@ -1,15 +1,19 @@
 //! Unix socket server for receiving events from Swift Network Extension
 //!
-//! The Swift network extension sends events as newline-delimited JSON
-//! over a Unix domain socket.
+//! The Swift network extension sends events over a Unix domain socket,
+//! either as newline-delimited JSON or, after a handshake, as binary frames
+//! (see [`oisp_core::wire`]).
 
 use base64::prelude::*;
 use oisp_core::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use oisp_core::wire::{WireDecoder, WireMessage};
+use oisp_core::wire_diagnostics::{ParseDiagnostics, ParseErrorKind};
 use serde::Deserialize;
+use std::fmt::Display;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
@@ -106,6 +110,8 @@ pub struct SocketServerStats {
     pub bytes_received: AtomicU64,
     pub parse_errors: AtomicU64,
     pub connections: AtomicU64,
+    /// Parse errors by kind, with diagnostics for the latest
+    pub parse_diagnostics: ParseDiagnostics,
 }
 
 impl Default for SocketServerStats {
@@ -115,10 +121,19 @@ impl Default for SocketServerStats {
             bytes_received: AtomicU64::new(0),
             parse_errors: AtomicU64::new(0),
             connections: AtomicU64::new(0),
+            parse_diagnostics: ParseDiagnostics::new("socket"),
         }
     }
 }
 
+impl SocketServerStats {
+    /// Count a frame or line that failed to parse, logging a diagnostic
+    fn parse_failed(&self, kind: ParseErrorKind, input: &[u8], error: &dyn Display) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+        self.parse_diagnostics.record(kind, input, error);
+    }
+}
+
 /// Unix socket server for Swift Network Extension
 pub struct SocketServer {
     socket_path: String,
@@ -232,65 +247,80 @@ async fn handle_connection(
     stats: Arc<SocketServerStats>,
     running: Arc<AtomicBool>,
 ) -> Result<(), std::io::Error> {
-    let reader = BufReader::new(stream);
-    let mut lines = reader.lines();
+    let (mut reader, mut writer) = stream.into_split();
+    let mut decoder = WireDecoder::new();
+    let mut buf = vec![0u8; 64 * 1024];
 
     while running.load(Ordering::SeqCst) {
-        match lines.next_line().await {
-            Ok(Some(line)) => {
-                stats
-                    .bytes_received
-                    .fetch_add(line.len() as u64, Ordering::Relaxed);
-
-                // Parse the JSON event
-                match serde_json::from_str::<SwiftCaptureEvent>(&line) {
-                    Ok(swift_event) => {
-                        debug!(
-                            "Received event: {} from pid {}",
-                            swift_event.kind, swift_event.pid
-                        );
-
-                        // Convert to RawCaptureEvent
-                        match swift_event.into_raw_event() {
-                            Ok(event) => {
-                                stats.events_received.fetch_add(1, Ordering::Relaxed);
-
-                                // Send to the channel
-                                if let Err(e) = tx.send(event).await {
-                                    warn!("Failed to send event: {}", e);
-                                }
-                            }
-                            Err(e) => {
-                                stats.parse_errors.fetch_add(1, Ordering::Relaxed);
-                                warn!("Failed to convert event: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        stats.parse_errors.fetch_add(1, Ordering::Relaxed);
-                        warn!(
-                            "Failed to parse JSON: {} - line: {}",
-                            e,
-                            &line[..line.len().min(100)]
-                        );
-                    }
-                }
-            }
-            Ok(None) => {
-                // Connection closed
+        let n = match reader.read(&mut buf).await {
+            Ok(0) => {
                 info!("Connection closed by Swift extension");
                 break;
             }
+            Ok(n) => n,
             Err(e) => {
                 error!("Read error: {}", e);
                 break;
             }
+        };
+        stats.bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+        decoder.feed(&buf[..n]);
+
+        loop {
+            let event = match decoder.next_message() {
+                Ok(Some(WireMessage::Hello(ack))) => {
+                    info!(
+                        "Swift extension negotiated {:?} encoding (protocol v{})",
+                        ack.encoding, ack.version
+                    );
+                    writer.write_all(&ack.to_line()).await?;
+                    continue;
+                }
+                Ok(Some(WireMessage::Json(line))) => match parse_json_event(&line) {
+                    Ok(event) => event,
+                    Err((kind, e)) => {
+                        stats.parse_failed(kind, line.as_bytes(), &e);
+                        continue;
+                    }
+                },
+                Ok(Some(WireMessage::Event(event))) => *event,
+                Ok(None) => break,
+                Err(e) => {
+                    stats.parse_failed(ParseErrorKind::from(&e), decoder.failed_frame(), &e);
+                    if e.is_fatal() {
+                        error!("Closing connection: {}", e);
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+
+            debug!("Received event: {:?} from pid {}", event.kind, event.pid);
+            stats.events_received.fetch_add(1, Ordering::Relaxed);
+
+            // Send to the channel
+            if let Err(e) = tx.send(event).await {
+                warn!("Failed to send event: {}", e);
+            }
         }
     }
 
     Ok(())
 }
 
+/// Parse a JSON event line into a RawCaptureEvent
+fn parse_json_event(line: &str) -> Result<RawCaptureEvent, (ParseErrorKind, String)> {
+    serde_json::from_str::<SwiftCaptureEvent>(line)
+        .map_err(|e| {
+            (
+                ParseErrorKind::InvalidJson,
+                format!("Failed to parse JSON: {}", e),
+            )
+        })?
+        .into_raw_event()
+        .map_err(|e| (ParseErrorKind::InvalidEvent, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -328,4 +358,111 @@ mod tests {
         assert_eq!(raw.metadata.remote_addr, Some("api.openai.com".to_string()));
         assert_eq!(raw.metadata.remote_port, Some(443));
     }
+
+    const LEGACY_EVENT: &str = r#"{"id":"legacy","timestamp_ns":1,"kind":"SslRead","pid":42,"data":"SGk=","metadata":{"comm":"curl","exe":"/usr/bin/curl","uid":501}}"#;
+
+    fn spawn_connection() -> (
+        UnixStream,
+        mpsc::Receiver<RawCaptureEvent>,
+        Arc<SocketServerStats>,
+    ) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (tx, rx) = mpsc::channel(16);
+        let stats = Arc::new(SocketServerStats::default());
+        tokio::spawn(handle_connection(
+            server,
+            tx,
+            stats.clone(),
+            Arc::new(AtomicBool::new(true)),
+        ));
+        (client, rx, stats)
+    }
+
+    #[tokio::test]
+    async fn test_legacy_json_client() {
+        let (mut client, mut rx, _) = spawn_connection();
+        client
+            .write_all(format!("{}\n{}\n", LEGACY_EVENT, LEGACY_EVENT).as_bytes())
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let event = rx.recv().await.unwrap();
+            assert_eq!(event.id, "legacy");
+            assert_eq!(event.data, b"Hi");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_binary_client_handshake() {
+        use oisp_core::wire::{encode_event, Encoding, Hello, HelloAck};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (client, mut rx, stats) = spawn_connection();
+        let (read_half, mut write_half) = client.into_split();
+        write_half
+            .write_all(&Hello::new(vec![Encoding::Binary, Encoding::Json]).to_line())
+            .await
+            .unwrap();
+
+        let mut ack = String::new();
+        BufReader::new(read_half).read_line(&mut ack).await.unwrap();
+        let ack = HelloAck::parse(ack.trim_end()).expect("ack line");
+        assert_eq!(ack.encoding, Encoding::Binary);
+
+        let event = serde_json::from_str::<SwiftCaptureEvent>(LEGACY_EVENT)
+            .unwrap()
+            .into_raw_event()
+            .unwrap();
+        let mut frame = Vec::new();
+        encode_event(&event, &mut frame);
+        write_half.write_all(&frame).await.unwrap();
+
+        let received = rx.recv().await.unwrap();
+        assert_eq!(received.id, "legacy");
+        assert_eq!(received.metadata.comm.as_deref(), Some("curl"));
+        assert_eq!(received.data, b"Hi");
+        assert_eq!(stats.parse_errors.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_frame_diagnostic() {
+        use oisp_core::wire::{encode_event, Encoding, Hello};
+        use std::collections::BTreeMap;
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (client, mut rx, stats) = spawn_connection();
+        let (read_half, mut write_half) = client.into_split();
+        write_half
+            .write_all(&Hello::new(vec![Encoding::Binary]).to_line())
+            .await
+            .unwrap();
+        let mut ack = String::new();
+        BufReader::new(read_half).read_line(&mut ack).await.unwrap();
+
+        let event = serde_json::from_str::<SwiftCaptureEvent>(LEGACY_EVENT)
+            .unwrap()
+            .into_raw_event()
+            .unwrap();
+        let mut valid = Vec::new();
+        encode_event(&event, &mut valid);
+        // Same frame with an unassigned kind code
+        let mut malformed = valid.clone();
+        malformed[4] = 200;
+        write_half.write_all(&malformed).await.unwrap();
+        write_half.write_all(&valid).await.unwrap();
+
+        // The connection survives the bad frame
+        assert_eq!(rx.recv().await.unwrap().id, "legacy");
+        assert_eq!(stats.parse_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            stats.parse_diagnostics.histogram(),
+            BTreeMap::from([(ParseErrorKind::UnknownKind, 1)])
+        );
+        let diagnostics = stats.parse_diagnostics.recent();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ParseErrorKind::UnknownKind);
+        assert_eq!(diagnostics[0].len, malformed.len());
+        assert!(diagnostics[0].dump.starts_with("0000  "));
+    }
 }
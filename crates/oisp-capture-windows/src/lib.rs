@@ -207,15 +207,16 @@ impl CapturePlugin for WindowsCapture {
 
     fn stats(&self) -> CaptureStats {
         // Get stats from pipe server if available
-        let (events, bytes, errors) = if let Some(ref server) = self.pipe_server {
+        let (events, bytes, errors, parse_errors) = if let Some(ref server) = self.pipe_server {
             let stats = server.stats();
             (
                 stats.events_received.load(Ordering::Relaxed),
                 stats.bytes_received.load(Ordering::Relaxed),
                 stats.parse_errors.load(Ordering::Relaxed),
+                stats.parse_diagnostics.histogram(),
             )
         } else {
-            (0, 0, 0)
+            (0, 0, 0, Default::default())
         };
 
         CaptureStats {
@@ -223,6 +224,7 @@ impl CapturePlugin for WindowsCapture {
             events_dropped: self.stats.events_dropped.load(Ordering::Relaxed),
             bytes_captured: bytes,
             errors,
+            parse_errors,
         }
     }
 }
@@ -1,17 +1,25 @@
 //! Named Pipe server for receiving events from the Windows Redirector
 //!
-//! The redirector sends events as newline-delimited JSON over a Named Pipe.
+//! The redirector sends events over a Named Pipe, either as newline-delimited
+//! JSON or, after a handshake, as binary frames (see [`oisp_core::wire`]).
 //! This is the Windows equivalent of the macOS Unix socket server.
 
 use base64::prelude::*;
 use oisp_core::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use oisp_core::wire_diagnostics::ParseDiagnostics;
+#[cfg(any(target_os = "windows", test))]
+use oisp_core::wire_diagnostics::ParseErrorKind;
 use serde::Deserialize;
+#[cfg(any(target_os = "windows", test))]
+use std::fmt::Display;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+#[cfg(any(target_os = "windows", test))]
+use tracing::debug;
 use tracing::warn;
 #[cfg(target_os = "windows")]
-use tracing::{debug, error, info};
+use tracing::{error, info};
 
 /// Default named pipe path
 pub const DEFAULT_PIPE_PATH: &str = r"\\.\pipe\oisp-capture";
@@ -185,6 +193,8 @@ pub struct PipeServerStats {
     pub bytes_received: AtomicU64,
     pub parse_errors: AtomicU64,
     pub connections: AtomicU64,
+    /// Parse errors by kind, with diagnostics for the latest
+    pub parse_diagnostics: ParseDiagnostics,
 }
 
 impl Default for PipeServerStats {
@@ -194,10 +204,34 @@ impl Default for PipeServerStats {
             bytes_received: AtomicU64::new(0),
             parse_errors: AtomicU64::new(0),
             connections: AtomicU64::new(0),
+            parse_diagnostics: ParseDiagnostics::new("pipe"),
         }
     }
 }
 
+impl PipeServerStats {
+    /// Count a frame or line that failed to parse, logging a diagnostic
+    #[cfg(any(target_os = "windows", test))]
+    fn parse_failed(&self, kind: ParseErrorKind, input: &[u8], error: &dyn Display) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+        self.parse_diagnostics.record(kind, input, error);
+    }
+}
+
+/// Parse a JSON event line; `None` for events that carry no capture data
+#[cfg(any(target_os = "windows", test))]
+fn parse_event_line(line: &str) -> Result<Option<RawCaptureEvent>, (ParseErrorKind, String)> {
+    let event = serde_json::from_str::<RedirectorEvent>(line).map_err(|e| {
+        (
+            ParseErrorKind::InvalidJson,
+            format!("Failed to parse event: {}", e),
+        )
+    })?;
+    debug!("Received event type: {}", event.event_type);
+    // Only SSL data converts to a raw event
+    Ok(event.into_raw_event())
+}
+
 /// Named Pipe server for Windows Redirector
 pub struct PipeServer {
     pipe_path: String,
@@ -384,10 +418,11 @@ fn handle_pipe_connection_sync(
     stats: Arc<PipeServerStats>,
     running: Arc<AtomicBool>,
 ) {
-    use windows::Win32::Storage::FileSystem::ReadFile;
+    use oisp_core::wire::{WireDecoder, WireMessage};
+    use windows::Win32::Storage::FileSystem::{ReadFile, WriteFile};
 
     let mut buffer = vec![0u8; 65536];
-    let mut line_buffer = String::new();
+    let mut decoder = WireDecoder::new();
 
     while running.load(Ordering::SeqCst) {
         // Read from pipe
@@ -403,43 +438,54 @@ fn handle_pipe_connection_sync(
         stats
             .bytes_received
             .fetch_add(bytes_read as u64, Ordering::Relaxed);
-
-        // Convert to string and append to buffer
-        if let Ok(s) = std::str::from_utf8(&buffer[..bytes_read as usize]) {
-            line_buffer.push_str(s);
-
-            // Process complete lines
-            while let Some(newline_pos) = line_buffer.find('\n') {
-                let line = line_buffer[..newline_pos].to_string();
-                line_buffer = line_buffer[newline_pos + 1..].to_string();
-
-                if line.trim().is_empty() {
+        decoder.feed(&buffer[..bytes_read as usize]);
+
+        loop {
+            let raw_event = match decoder.next_message() {
+                Ok(Some(WireMessage::Hello(ack))) => {
+                    info!(
+                        "Redirector negotiated {:?} encoding (protocol v{})",
+                        ack.encoding, ack.version
+                    );
+                    let line = ack.to_line();
+                    let mut bytes_written = 0u32;
+                    let written = unsafe {
+                        WriteFile(
+                            pipe_handle,
+                            Some(line.as_slice()),
+                            Some(&mut bytes_written),
+                            None,
+                        )
+                    };
+                    if let Err(e) = written {
+                        error!("Failed to send handshake reply: {}", e);
+                        return;
+                    }
                     continue;
                 }
-
-                // Parse JSON event
-                match serde_json::from_str::<RedirectorEvent>(&line) {
-                    Ok(event) => {
-                        debug!("Received event type: {}", event.event_type);
-
-                        // Convert to RawCaptureEvent if applicable
-                        if let Some(raw_event) = event.into_raw_event() {
-                            stats.events_received.fetch_add(1, Ordering::Relaxed);
-
-                            if let Err(e) = tx.blocking_send(raw_event) {
-                                warn!("Failed to send event: {}", e);
-                            }
-                        }
+                Ok(Some(WireMessage::Json(line))) => match parse_event_line(&line) {
+                    Ok(Some(raw_event)) => raw_event,
+                    Ok(None) => continue,
+                    Err((kind, e)) => {
+                        stats.parse_failed(kind, line.as_bytes(), &e);
+                        continue;
                     }
-                    Err(e) => {
-                        stats.parse_errors.fetch_add(1, Ordering::Relaxed);
-                        warn!(
-                            "Failed to parse event: {} - line: {}...",
-                            e,
-                            &line[..line.len().min(50)]
-                        );
+                },
+                Ok(Some(WireMessage::Event(raw_event))) => *raw_event,
+                Ok(None) => break,
+                Err(e) => {
+                    stats.parse_failed(ParseErrorKind::from(&e), decoder.failed_frame(), &e);
+                    if e.is_fatal() {
+                        error!("Closing pipe connection: {}", e);
+                        return;
                     }
+                    continue;
                 }
+            };
+
+            stats.events_received.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = tx.blocking_send(raw_event) {
+                warn!("Failed to send event: {}", e);
             }
         }
     }
@@ -501,4 +547,30 @@ mod tests {
         // Connection events don't convert to raw events
         assert!(event.into_raw_event().is_none());
     }
+
+    #[test]
+    fn test_malformed_line_diagnostic() {
+        use oisp_core::wire::{WireDecoder, WireMessage};
+        use std::collections::BTreeMap;
+
+        let stats = PipeServerStats::default();
+        let mut decoder = WireDecoder::new();
+        decoder.feed(b"{\"type\":\"ssl_write\",\"timestamp_ns\":\"soon\"}\n");
+        let Some(WireMessage::Json(line)) = decoder.next_message().unwrap() else {
+            panic!("expected a JSON line");
+        };
+        let (kind, error) = parse_event_line(&line).unwrap_err();
+        stats.parse_failed(kind, line.as_bytes(), &error);
+
+        assert_eq!(stats.parse_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            stats.parse_diagnostics.histogram(),
+            BTreeMap::from([(ParseErrorKind::InvalidJson, 1)])
+        );
+        let diagnostics = stats.parse_diagnostics.recent();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ParseErrorKind::InvalidJson);
+        assert_eq!(diagnostics[0].len, line.len());
+        assert!(diagnostics[0].error.contains("expected u64"));
+    }
 }
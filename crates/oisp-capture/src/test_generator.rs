@@ -434,8 +434,7 @@ impl CapturePlugin for TestGenerator {
         CaptureStats {
             events_captured: self.stats.events_generated.load(Ordering::Relaxed),
             events_dropped: 0,
-            bytes_captured: 0,
-            errors: 0,
+            ..Default::default()
         }
     }
 }
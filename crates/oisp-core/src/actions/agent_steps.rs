@@ -0,0 +1,483 @@
+//! Agent step action plugin
+//!
+//! Derives agent structure from the AI traffic around it:
+//!
+//! - A model response that calls tools is a step in the agent's plan. It
+//!   becomes an `agent.plan_step` listing the tools, numbered by the tool
+//!   rounds already in the conversation and linked to its request and
+//!   response.
+//! - A vector store query (`agent.rag_retrieve`, from the decoder) usually
+//!   follows an embedding request for the question. The retrieval is linked
+//!   to the process's latest embedding request as its parent when that
+//!   request is recent enough.
+//!
+//! Runs as an action so the links use final event ids.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{
+    AgentContext, AgentFramework, AgentInfo, AgentPlanStepData, AgentPlanStepEvent, AiRequestEvent,
+    AiResponseEvent, EventEnvelope, MessageRole, OispEvent, PlanStepType, PlannedAction,
+    RelatedEvent, Relationship, RequestType,
+};
+use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginError, PluginInfo, PluginResult};
+
+/// Requests (or processes, for embeddings) tracked before the oldest is
+/// forgotten
+const MAX_TRACKED: usize = 10_000;
+
+/// Agent step configuration
+#[derive(Debug, Clone)]
+pub struct AgentStepConfig {
+    /// Longest gap between an embedding request and the retrieval it feeds
+    pub retrieval_window: Duration,
+}
+
+impl Default for AgentStepConfig {
+    fn default() -> Self {
+        Self {
+            retrieval_window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A request awaiting its response
+struct TrackedRequest {
+    event_id: String,
+    ts: DateTime<Utc>,
+    /// Tool rounds already answered in the request's conversation
+    prior_steps: usize,
+    agent: Option<AgentInfo>,
+}
+
+/// An embedding request that may feed a retrieval
+struct Embedding {
+    event_id: String,
+    ts: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct StepState {
+    requests: HashMap<String, TrackedRequest>,
+    /// Latest embedding request per pid
+    embeddings: HashMap<u32, Embedding>,
+}
+
+/// Agent step plugin - emits `agent.plan_step` for tool-calling responses
+/// and links retrievals to the embedding request before them
+pub struct AgentStepPlugin {
+    config: AgentStepConfig,
+    state: Mutex<StepState>,
+}
+
+impl AgentStepPlugin {
+    pub fn new(config: AgentStepConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(StepState::default()),
+        }
+    }
+
+    fn on_request(state: &mut StepState, request: &AiRequestEvent) {
+        let envelope = &request.envelope;
+        if request.data.request_type == Some(RequestType::Embedding) {
+            if let Some(pid) = envelope.process.as_ref().map(|p| p.pid) {
+                insert_bounded(
+                    &mut state.embeddings,
+                    pid,
+                    Embedding {
+                        event_id: envelope.event_id.clone(),
+                        ts: envelope.ts,
+                    },
+                    |e| e.ts,
+                );
+            }
+            return;
+        }
+
+        // Each run of tool results answers one earlier tool-calling step
+        let mut prior_steps = 0;
+        let mut in_results = false;
+        for message in &request.data.messages {
+            let result = matches!(message.role, MessageRole::Tool | MessageRole::Function);
+            if result && !in_results {
+                prior_steps += 1;
+            }
+            in_results = result;
+        }
+
+        insert_bounded(
+            &mut state.requests,
+            request.data.request_id.clone(),
+            TrackedRequest {
+                event_id: envelope.event_id.clone(),
+                ts: envelope.ts,
+                prior_steps,
+                agent: request.data.agent.as_ref().map(agent_info),
+            },
+            |r| r.ts,
+        );
+    }
+
+    fn on_response(state: &mut StepState, response: &AiResponseEvent) -> Option<OispEvent> {
+        let request = state.requests.remove(&response.data.request_id);
+        if response.data.tool_calls.is_empty() {
+            return None;
+        }
+
+        let tools: Vec<&str> = response
+            .data
+            .tool_calls
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect();
+        let step_index = request.as_ref().map_or(0, |r| r.prior_steps);
+
+        let mut envelope = context_envelope("agent.plan_step", &response.envelope);
+        if let Some(request) = &request {
+            envelope.related_events.push(RelatedEvent {
+                event_id: request.event_id.clone(),
+                relationship: Relationship::Parent,
+            });
+        }
+        envelope.related_events.push(RelatedEvent {
+            event_id: response.envelope.event_id.clone(),
+            relationship: Relationship::CausedBy,
+        });
+
+        Some(OispEvent::AgentPlanStep(AgentPlanStepEvent {
+            envelope,
+            data: AgentPlanStepData {
+                agent: request.and_then(|r| r.agent),
+                step_index: Some(step_index),
+                step_type: Some(if step_index == 0 {
+                    PlanStepType::Planning
+                } else {
+                    PlanStepType::Decision
+                }),
+                description: Some(format!("Call {}", tools.join(", "))),
+                planned_actions: tools
+                    .iter()
+                    .map(|tool| PlannedAction {
+                        action: Some("tool_call".to_string()),
+                        tool: Some(tool.to_string()),
+                        rationale: None,
+                    })
+                    .collect(),
+                context_files: Vec::new(),
+                iteration: None,
+            },
+        }))
+    }
+
+    /// Link a retrieval to the embedding request that preceded it
+    fn on_retrieval(&self, state: &StepState, envelope: &mut EventEnvelope) -> bool {
+        let Some(pid) = envelope.process.as_ref().map(|p| p.pid) else {
+            return false;
+        };
+        let Some(embedding) = state.embeddings.get(&pid) else {
+            return false;
+        };
+        let window = chrono::Duration::from_std(self.config.retrieval_window)
+            .unwrap_or(chrono::Duration::MAX);
+        let gap = envelope.ts - embedding.ts;
+        if gap < chrono::Duration::zero() || gap > window {
+            return false;
+        }
+        if envelope
+            .related_events
+            .iter()
+            .any(|r| r.event_id == embedding.event_id)
+        {
+            return false;
+        }
+        envelope.related_events.push(RelatedEvent {
+            event_id: embedding.event_id.clone(),
+            relationship: Relationship::Parent,
+        });
+        true
+    }
+}
+
+/// Insert into a bounded map, forgetting the oldest entry when full
+fn insert_bounded<K, V>(map: &mut HashMap<K, V>, key: K, value: V, ts: impl Fn(&V) -> DateTime<Utc>)
+where
+    K: std::hash::Hash + Eq + Clone,
+{
+    if !map.contains_key(&key) && map.len() >= MAX_TRACKED {
+        let oldest = map
+            .iter()
+            .min_by_key(|(_, v)| ts(v))
+            .map(|(k, _)| k.clone());
+        if let Some(oldest) = oldest {
+            map.remove(&oldest);
+        }
+    }
+    map.insert(key, value);
+}
+
+/// New envelope with the process, host and app context of `context`
+fn context_envelope(event_type: &str, context: &EventEnvelope) -> EventEnvelope {
+    let mut envelope = EventEnvelope::new(event_type);
+    envelope.ts = context.ts;
+    envelope.ts_mono = context.ts_mono;
+    envelope.host = context.host.clone();
+    envelope.actor = context.actor.clone();
+    envelope.process = context.process.clone();
+    envelope.app = context.app.clone();
+    envelope.source = context.source.clone();
+    envelope
+}
+
+fn agent_info(context: &AgentContext) -> AgentInfo {
+    let framework = context.framework.as_ref().map(|f| match f {
+        AgentFramework::Custom(name) => name.clone(),
+        known => serde_json::to_value(known)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default(),
+    });
+    AgentInfo {
+        name: context.name.clone(),
+        agent_type: None,
+        version: context.version.clone(),
+        framework,
+        session_id: None,
+        task_id: None,
+    }
+}
+
+impl PluginInfo for AgentStepPlugin {
+    fn name(&self) -> &str {
+        "agent-steps"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Derives agent plan steps and links retrievals to embedding requests"
+    }
+}
+
+impl Plugin for AgentStepPlugin {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl ActionPlugin for AgentStepPlugin {
+    async fn process(&self, mut event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+
+        let step = match &mut event {
+            OispEvent::AiRequest(request) => {
+                Self::on_request(&mut state, request);
+                None
+            }
+            OispEvent::AiResponse(response) => Self::on_response(&mut state, response),
+            OispEvent::AgentRagRetrieve(retrieval) => {
+                let linked = self.on_retrieval(&state, &mut retrieval.envelope);
+                let action = if linked {
+                    EventAction::Modified
+                } else {
+                    EventAction::Pass
+                };
+                return Ok((event, action));
+            }
+            _ => None,
+        };
+
+        match step {
+            Some(step) => Ok((event.clone(), EventAction::Replace(vec![event, step]))),
+            None => Ok((event, EventAction::Pass)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, ts: &str, data: serde_json::Value) -> OispEvent {
+        serde_json::from_value(serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": ulid::Ulid::new().to_string(),
+            "event_type": event_type,
+            "ts": ts,
+            "process": {"pid": 42, "name": "agent"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": data
+        }))
+        .unwrap()
+    }
+
+    fn chat_request(id: &str, ts: &str, messages: serde_json::Value) -> OispEvent {
+        event(
+            "ai.request",
+            ts,
+            serde_json::json!({
+                "request_id": id,
+                "model": {"id": "gpt-4o"},
+                "messages": messages,
+                "agent": {"framework": "lang_chain", "is_agentic": true}
+            }),
+        )
+    }
+
+    fn tool_response(id: &str, ts: &str, tools: &[&str]) -> OispEvent {
+        let calls: Vec<_> = tools
+            .iter()
+            .map(|name| serde_json::json!({"name": name}))
+            .collect();
+        event(
+            "ai.response",
+            ts,
+            serde_json::json!({"request_id": id, "tool_calls": calls}),
+        )
+    }
+
+    async fn run(plugin: &AgentStepPlugin, event: OispEvent) -> Vec<OispEvent> {
+        match plugin.process(event).await.unwrap() {
+            (_, EventAction::Replace(events)) => events,
+            (event, EventAction::Pass | EventAction::Modified) => vec![event],
+            (_, other) => panic!("unexpected action {:?}", other),
+        }
+    }
+
+    fn plan_step(events: &[OispEvent]) -> &AgentPlanStepEvent {
+        match events {
+            [_, OispEvent::AgentPlanStep(step)] => step,
+            other => panic!("expected response and plan step, got {:#?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_rounds_become_plan_steps() {
+        let plugin = AgentStepPlugin::new(AgentStepConfig::default());
+
+        let request = chat_request(
+            "req-1",
+            "2024-11-28T10:00:00Z",
+            serde_json::json!([{"role": "user", "content": "Weather in Paris and Rome?"}]),
+        );
+        let request_id = request.envelope().event_id.clone();
+        assert_eq!(run(&plugin, request).await.len(), 1);
+
+        let response = tool_response(
+            "req-1",
+            "2024-11-28T10:00:01Z",
+            &["get_weather", "get_weather"],
+        );
+        let response_id = response.envelope().event_id.clone();
+        let events = run(&plugin, response).await;
+        let step = plan_step(&events);
+        assert_eq!(step.data.step_index, Some(0));
+        assert_eq!(step.data.step_type, Some(PlanStepType::Planning));
+        assert_eq!(step.data.planned_actions.len(), 2);
+        assert_eq!(
+            step.data.planned_actions[0].tool.as_deref(),
+            Some("get_weather")
+        );
+        assert_eq!(
+            step.data.agent.as_ref().unwrap().framework.as_deref(),
+            Some("lang_chain")
+        );
+        let related: Vec<_> = step
+            .envelope
+            .related_events
+            .iter()
+            .map(|r| (r.event_id.as_str(), r.relationship))
+            .collect();
+        assert_eq!(
+            related,
+            vec![
+                (request_id.as_str(), Relationship::Parent),
+                (response_id.as_str(), Relationship::CausedBy)
+            ]
+        );
+
+        // The follow-up carries the tool results and calls one more tool
+        let request = chat_request(
+            "req-2",
+            "2024-11-28T10:00:02Z",
+            serde_json::json!([
+                {"role": "user", "content": "Weather in Paris and Rome?"},
+                {"role": "assistant"},
+                {"role": "tool", "tool_call_id": "a", "content": "18C"},
+                {"role": "tool", "tool_call_id": "b", "content": "24C"}
+            ]),
+        );
+        run(&plugin, request).await;
+        let events = run(
+            &plugin,
+            tool_response("req-2", "2024-11-28T10:00:03Z", &["compare"]),
+        )
+        .await;
+        let step = plan_step(&events);
+        assert_eq!(step.data.step_index, Some(1));
+        assert_eq!(step.data.step_type, Some(PlanStepType::Decision));
+        assert_eq!(step.data.description.as_deref(), Some("Call compare"));
+
+        // A final answer without tool calls is not a step
+        let answer = event(
+            "ai.response",
+            "2024-11-28T10:00:04Z",
+            serde_json::json!({"request_id": "req-3"}),
+        );
+        assert_eq!(run(&plugin, answer).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_linked_to_embedding() {
+        let plugin = AgentStepPlugin::new(AgentStepConfig::default());
+        let retrieval = |ts: &str| {
+            event(
+                "agent.rag_retrieve",
+                ts,
+                serde_json::json!({"source": {"type": "vector_db", "provider": "pinecone"}}),
+            )
+        };
+
+        // No embedding yet: nothing to link
+        let events = run(&plugin, retrieval("2024-11-28T10:00:00Z")).await;
+        assert!(events[0].envelope().related_events.is_empty());
+
+        let embedding = event(
+            "ai.request",
+            "2024-11-28T10:00:01Z",
+            serde_json::json!({
+                "request_id": "emb-1",
+                "request_type": "embedding",
+                "model": {"id": "text-embedding-3-small"}
+            }),
+        );
+        let embedding_id = embedding.envelope().event_id.clone();
+        run(&plugin, embedding).await;
+
+        let events = run(&plugin, retrieval("2024-11-28T10:00:02Z")).await;
+        let related = &events[0].envelope().related_events;
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].event_id, embedding_id);
+        assert_eq!(related[0].relationship, Relationship::Parent);
+
+        // Outside the window the embedding is unrelated
+        let events = run(&plugin, retrieval("2024-11-28T10:05:00Z")).await;
+        assert!(events[0].envelope().related_events.is_empty());
+    }
+}
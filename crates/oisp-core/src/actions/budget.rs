@@ -0,0 +1,393 @@
+//! Budget alert action plugin
+//!
+//! Accumulates the estimated cost of `ai.response` events per budget key
+//! (everything, each process, or each provider) over fixed windows aligned
+//! to the Unix epoch, so an hourly window resets at the top of every hour.
+//! The first response that takes a key over the threshold in a window is
+//! tagged with a [`PolicyAlert`] under the `budget_alert` attribute and, if
+//! configured, the alert is posted to a webhook. Further responses in the
+//! same window do not alert again.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::events::{AiResponseEvent, OispEvent};
+use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginError, PluginInfo, PluginResult};
+use crate::policy::{AlertSeverity, PolicyAlert};
+use crate::spec::DynamicProviderRegistry;
+
+/// Policy ID reported in budget alerts
+pub const BUDGET_POLICY_ID: &str = "budget";
+
+/// Event attribute holding the alert raised by a response
+pub const BUDGET_ALERT_ATTR: &str = "budget_alert";
+
+/// Webhook requests slower than this are abandoned
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Window entries kept before closed windows are pruned
+const MAX_TRACKED_KEYS: usize = 1024;
+
+/// What spend is accumulated against the threshold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// All AI spend seen by the sensor
+    #[default]
+    Global,
+    /// Spend per process (by name, or pid when unnamed)
+    Process,
+    /// Spend per provider
+    Provider,
+}
+
+/// Budget alert configuration
+#[derive(Debug, Clone)]
+pub struct BudgetAlertConfig {
+    /// Alert when spend in a window exceeds this many US dollars
+    pub threshold_usd: f64,
+    /// Window length; spend resets at each multiple of this since the epoch
+    pub window: Duration,
+    /// What spend is accumulated against the threshold
+    pub scope: BudgetScope,
+    /// Webhook receiving each alert as JSON
+    pub webhook_url: Option<String>,
+    /// Severity of raised alerts
+    pub severity: AlertSeverity,
+}
+
+impl Default for BudgetAlertConfig {
+    fn default() -> Self {
+        Self {
+            threshold_usd: 10.0,
+            window: Duration::from_secs(86400),
+            scope: BudgetScope::Global,
+            webhook_url: None,
+            severity: AlertSeverity::Warning,
+        }
+    }
+}
+
+/// Estimated cost of a response in US dollars
+///
+/// Uses the cost already on the response if present, otherwise the spec
+/// bundle's pricing for the model.
+pub(crate) fn response_cost_usd(
+    registry: &DynamicProviderRegistry,
+    response: &AiResponseEvent,
+) -> Option<f64> {
+    let usage = response.data.usage.as_ref()?;
+    if let Some(total) = usage.total_cost_usd {
+        return Some(total);
+    }
+    let provider = response.data.provider.as_ref()?;
+    let model = response.data.model.as_ref()?;
+    registry
+        .estimate_cost(
+            &provider.name,
+            &model.id,
+            usage.prompt_tokens.unwrap_or(0),
+            usage.completion_tokens.unwrap_or(0),
+        )
+        .map(|(_, _, total)| total)
+}
+
+/// Spend accumulated for one key in the current window
+struct Window {
+    /// Window start, in seconds since the epoch
+    start: i64,
+    spent_usd: f64,
+    alerted: bool,
+}
+
+/// Budget alert plugin - alerts once per window when AI spend crosses a threshold
+pub struct BudgetAlertPlugin {
+    config: BudgetAlertConfig,
+    registry: Arc<DynamicProviderRegistry>,
+    windows: Mutex<HashMap<String, Window>>,
+    http: reqwest::Client,
+}
+
+impl BudgetAlertPlugin {
+    /// Create a plugin pricing responses with `registry`'s spec bundle
+    pub fn new(config: BudgetAlertConfig, registry: Arc<DynamicProviderRegistry>) -> Self {
+        Self {
+            config,
+            registry,
+            windows: Mutex::new(HashMap::new()),
+            http: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn budget_key(&self, response: &AiResponseEvent) -> String {
+        match self.config.scope {
+            BudgetScope::Global => "global".to_string(),
+            BudgetScope::Process => match &response.envelope.process {
+                Some(p) => p.name.clone().unwrap_or_else(|| format!("pid:{}", p.pid)),
+                None => "unknown".to_string(),
+            },
+            BudgetScope::Provider => response
+                .data
+                .provider
+                .as_ref()
+                .map(|p| p.name.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+        }
+    }
+
+    /// Add a response's cost to its window, returning an alert if this
+    /// response took the window over the threshold
+    fn record(&self, response: &AiResponseEvent) -> PluginResult<Option<PolicyAlert>> {
+        let Some(cost) = response_cost_usd(&self.registry, response) else {
+            return Ok(None);
+        };
+        let window_secs = self.config.window.as_secs().max(1) as i64;
+        let ts = response.envelope.ts.timestamp();
+        let start = ts - ts.rem_euclid(window_secs);
+        let key = self.budget_key(response);
+
+        let mut windows = self
+            .windows
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+        if windows.len() >= MAX_TRACKED_KEYS && !windows.contains_key(&key) {
+            windows.retain(|_, w| w.start >= start);
+        }
+        let window = windows.entry(key.clone()).or_insert(Window {
+            start,
+            spent_usd: 0.0,
+            alerted: false,
+        });
+        if start > window.start {
+            *window = Window {
+                start,
+                spent_usd: 0.0,
+                alerted: false,
+            };
+        } else if start < window.start {
+            // Late event from a window that has already closed
+            return Ok(None);
+        }
+
+        window.spent_usd += cost;
+        if window.alerted || window.spent_usd <= self.config.threshold_usd {
+            return Ok(None);
+        }
+        window.alerted = true;
+
+        let mut context = HashMap::new();
+        context.insert("event_type".to_string(), "ai.response".into());
+        context.insert(
+            "scope".to_string(),
+            serde_json::to_value(self.config.scope)?,
+        );
+        context.insert("key".to_string(), key.clone().into());
+        context.insert("spent_usd".to_string(), window.spent_usd.into());
+        context.insert(
+            "threshold_usd".to_string(),
+            self.config.threshold_usd.into(),
+        );
+        context.insert("window_secs".to_string(), window_secs.into());
+        if let Some(window_start) = chrono::DateTime::from_timestamp(start, 0) {
+            context.insert("window_start".to_string(), window_start.to_rfc3339().into());
+        }
+
+        Ok(Some(PolicyAlert {
+            id: ulid::Ulid::new().to_string(),
+            policy_id: BUDGET_POLICY_ID.to_string(),
+            severity: self.config.severity,
+            message: format!(
+                "AI spend for {} reached ${:.2}, over the ${:.2} budget",
+                key, window.spent_usd, self.config.threshold_usd
+            ),
+            event_id: response.envelope.event_id.clone(),
+            timestamp: chrono::Utc::now(),
+            context,
+        }))
+    }
+
+    /// Post the alert in the background so the pipeline is not held up
+    fn send_webhook(&self, url: &str, alert: &PolicyAlert) {
+        let request = self.http.post(url).json(alert);
+        let url = url.to_string();
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!(url = url, "Budget alert webhook sent");
+                }
+                Ok(response) => {
+                    warn!(url = url, status = %response.status(), "Budget alert webhook failed");
+                }
+                Err(e) => warn!(url = url, error = %e, "Failed to send budget alert webhook"),
+            }
+        });
+    }
+}
+
+impl PluginInfo for BudgetAlertPlugin {
+    fn name(&self) -> &str {
+        "budget-alert"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Alerts when estimated AI spend crosses a budget"
+    }
+}
+
+impl Plugin for BudgetAlertPlugin {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl ActionPlugin for BudgetAlertPlugin {
+    async fn process(&self, mut event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+        let OispEvent::AiResponse(response) = &mut event else {
+            return Ok((event, EventAction::Pass));
+        };
+        let Some(alert) = self.record(response)? else {
+            return Ok((event, EventAction::Pass));
+        };
+
+        warn!(
+            event_id = alert.event_id,
+            spent_usd = ?alert.context.get("spent_usd"),
+            "{}",
+            alert.message
+        );
+        if let Some(url) = &self.config.webhook_url {
+            self.send_webhook(url, &alert);
+        }
+        response
+            .envelope
+            .attrs
+            .insert(BUDGET_ALERT_ATTR.to_string(), serde_json::to_value(&alert)?);
+
+        Ok((event, EventAction::Modified))
+    }
+
+    fn applies_to(&self, event: &OispEvent) -> bool {
+        matches!(event, OispEvent::AiResponse(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::OispSpecBundle;
+
+    fn registry() -> Arc<DynamicProviderRegistry> {
+        Arc::new(DynamicProviderRegistry::new(Arc::new(
+            OispSpecBundle::embedded(),
+        )))
+    }
+
+    fn response(ts: &str, provider: &str, usage: serde_json::Value) -> OispEvent {
+        serde_json::from_value(serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": ulid::Ulid::new().to_string(),
+            "event_type": "ai.response",
+            "ts": ts,
+            "process": {"pid": 42, "name": "python"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {
+                "request_id": "req",
+                "provider": {"name": provider},
+                "model": {"id": "gpt-4o"},
+                "usage": usage
+            }
+        }))
+        .unwrap()
+    }
+
+    async fn alerted(plugin: &BudgetAlertPlugin, event: OispEvent) -> bool {
+        let (event, action) = plugin.process(event).await.unwrap();
+        let tagged = event.envelope().attrs.contains_key(BUDGET_ALERT_ATTR);
+        assert_eq!(tagged, matches!(action, EventAction::Modified));
+        tagged
+    }
+
+    #[tokio::test]
+    async fn test_one_alert_per_window() {
+        let plugin = BudgetAlertPlugin::new(
+            BudgetAlertConfig {
+                threshold_usd: 0.01,
+                window: Duration::from_secs(3600),
+                ..Default::default()
+            },
+            registry(),
+        );
+        let cost = serde_json::json!({"total_cost_usd": 0.004});
+
+        let first_hour = [
+            "2024-01-01T10:00:00Z",
+            "2024-01-01T10:10:00Z",
+            "2024-01-01T10:20:00Z",
+            "2024-01-01T10:30:00Z",
+            "2024-01-01T10:59:59Z",
+        ];
+        let mut alerts = Vec::new();
+        for ts in first_hour {
+            alerts.push(alerted(&plugin, response(ts, "openai", cost.clone())).await);
+        }
+        // 0.004, 0.008, 0.012 crosses 0.01; later responses stay quiet
+        assert_eq!(alerts, [false, false, true, false, false]);
+
+        // The window resets at the top of the hour
+        let mut alerts = Vec::new();
+        for ts in [
+            "2024-01-01T11:00:00Z",
+            "2024-01-01T11:01:00Z",
+            "2024-01-01T11:02:00Z",
+            "2024-01-01T11:03:00Z",
+        ] {
+            alerts.push(alerted(&plugin, response(ts, "openai", cost.clone())).await);
+        }
+        assert_eq!(alerts, [false, false, true, false]);
+
+        // Late response from the closed window neither counts nor alerts
+        assert!(!alerted(&plugin, response("2024-01-01T10:45:00Z", "openai", cost)).await);
+    }
+
+    #[tokio::test]
+    async fn test_spec_pricing_per_provider() {
+        let registry = registry();
+        let (_, _, per_call) = registry
+            .estimate_cost("openai", "gpt-4o", 100_000, 10_000)
+            .unwrap();
+        let plugin = BudgetAlertPlugin::new(
+            BudgetAlertConfig {
+                threshold_usd: per_call * 1.5,
+                scope: BudgetScope::Provider,
+                ..Default::default()
+            },
+            registry,
+        );
+        let usage = serde_json::json!({"prompt_tokens": 100_000, "completion_tokens": 10_000});
+        let ts = "2024-01-01T10:00:00Z";
+
+        assert!(!alerted(&plugin, response(ts, "openai", usage.clone())).await);
+        // Another provider's spend is tracked separately (and unpriced here)
+        assert!(!alerted(&plugin, response(ts, "acme", usage.clone())).await);
+        assert!(alerted(&plugin, response(ts, "openai", usage.clone())).await);
+        assert!(!alerted(&plugin, response(ts, "openai", usage)).await);
+    }
+}
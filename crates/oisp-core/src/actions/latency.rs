@@ -0,0 +1,331 @@
+//! Slow response tagging action plugin
+//!
+//! Tags every `ai.response` with a `latency_ms` against two references:
+//! a fixed per-model threshold (`perf.slow`), and a rolling baseline of the
+//! model's recent latencies (`perf.bucket`). The threshold catches responses
+//! that are slow in absolute terms; the bucket shows where a response falls
+//! relative to what the model usually does, so a regression stands out even
+//! while it is under the threshold.
+
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{AiResponseEvent, OispEvent};
+use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginError, PluginInfo, PluginResult};
+
+/// Event attribute set to `true` when a response exceeds its model's threshold
+pub const PERF_SLOW_ATTR: &str = "perf.slow";
+
+/// Event attribute holding the response's percentile bucket
+pub const PERF_BUCKET_ATTR: &str = "perf.bucket";
+
+/// Models tracked before the baselines are reset
+const MAX_TRACKED_MODELS: usize = 1024;
+
+/// Typical thresholds by provider name (as decoded), for models without a
+/// configured one
+///
+/// Hosted frontier APIs routinely take tens of seconds for long answers,
+/// dedicated inference hardware is much faster, and local runtimes depend
+/// on the machine they run on.
+const PROVIDER_THRESHOLDS_MS: &[(&str, u64)] = &[
+    ("openai", 30_000),
+    ("azureopenai", 30_000),
+    ("awsbedrock", 30_000),
+    ("anthropic", 30_000),
+    ("google", 30_000),
+    ("mistral", 20_000),
+    ("cohere", 20_000),
+    ("groq", 5_000),
+    ("cerebras", 5_000),
+    ("sambanova", 5_000),
+    ("ollama", 60_000),
+    ("lmstudio", 60_000),
+    ("vllm", 60_000),
+];
+
+/// Threshold for providers not in [`PROVIDER_THRESHOLDS_MS`]
+const FALLBACK_THRESHOLD_MS: u64 = 30_000;
+
+/// Latency tagging configuration
+#[derive(Debug, Clone)]
+pub struct LatencyConfig {
+    /// Slow threshold per model id
+    pub model_thresholds: HashMap<String, Duration>,
+    /// Slow threshold for models without one (None = provider norm)
+    pub default_threshold: Option<Duration>,
+    /// Latencies kept per model for the rolling baseline
+    pub baseline_samples: usize,
+    /// Latencies needed before responses are bucketed
+    pub min_samples: usize,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            model_thresholds: HashMap::new(),
+            default_threshold: None,
+            baseline_samples: 200,
+            min_samples: 20,
+        }
+    }
+}
+
+/// Recent latencies of one model
+#[derive(Default)]
+struct Baseline {
+    samples: VecDeque<u64>,
+}
+
+impl Baseline {
+    /// Bucket of `latency_ms` among the current samples
+    fn bucket(&self, latency_ms: u64) -> &'static str {
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[((sorted.len() - 1) * p) / 100];
+
+        if latency_ms <= percentile(50) {
+            "p50"
+        } else if latency_ms <= percentile(90) {
+            "p90"
+        } else if latency_ms <= percentile(99) {
+            "p99"
+        } else {
+            "p99+"
+        }
+    }
+
+    fn push(&mut self, latency_ms: u64, capacity: usize) {
+        if self.samples.len() >= capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency_ms);
+    }
+}
+
+/// Latency plugin - tags slow responses and their percentile bucket
+pub struct LatencyPlugin {
+    config: LatencyConfig,
+    baselines: Mutex<HashMap<String, Baseline>>,
+}
+
+impl LatencyPlugin {
+    pub fn new(config: LatencyConfig) -> Self {
+        Self {
+            config,
+            baselines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Slow threshold for a response's model
+    fn threshold(&self, response: &AiResponseEvent) -> Duration {
+        if let Some(threshold) = response
+            .data
+            .model
+            .as_ref()
+            .and_then(|m| self.config.model_thresholds.get(&m.id))
+        {
+            return *threshold;
+        }
+        if let Some(threshold) = self.config.default_threshold {
+            return threshold;
+        }
+        let provider = response.data.provider.as_ref().map(|p| p.name.as_str());
+        let ms = PROVIDER_THRESHOLDS_MS
+            .iter()
+            .find(|(name, _)| Some(*name) == provider)
+            .map(|(_, ms)| *ms)
+            .unwrap_or(FALLBACK_THRESHOLD_MS);
+        Duration::from_millis(ms)
+    }
+
+    /// Bucket a latency against its model's baseline, then add it to it
+    fn record(&self, model: &str, latency_ms: u64) -> PluginResult<Option<&'static str>> {
+        let mut baselines = self
+            .baselines
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+        if baselines.len() >= MAX_TRACKED_MODELS && !baselines.contains_key(model) {
+            baselines.clear();
+        }
+        let baseline = baselines.entry(model.to_string()).or_default();
+
+        let bucket = (baseline.samples.len() >= self.config.min_samples.max(1))
+            .then(|| baseline.bucket(latency_ms));
+        baseline.push(latency_ms, self.config.baseline_samples.max(1));
+        Ok(bucket)
+    }
+}
+
+impl PluginInfo for LatencyPlugin {
+    fn name(&self) -> &str {
+        "latency"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Tags slow AI responses and their latency percentile per model"
+    }
+}
+
+impl Plugin for LatencyPlugin {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl ActionPlugin for LatencyPlugin {
+    async fn process(&self, mut event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+        let OispEvent::AiResponse(response) = &mut event else {
+            return Ok((event, EventAction::Pass));
+        };
+        let Some(latency_ms) = response.data.latency_ms else {
+            return Ok((event, EventAction::Pass));
+        };
+
+        let slow = Duration::from_millis(latency_ms) > self.threshold(response);
+        let model = response
+            .data
+            .model
+            .as_ref()
+            .map(|m| m.id.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        let bucket = self.record(&model, latency_ms)?;
+
+        let attrs = &mut response.envelope.attrs;
+        attrs.insert(PERF_SLOW_ATTR.to_string(), slow.into());
+        if let Some(bucket) = bucket {
+            attrs.insert(PERF_BUCKET_ATTR.to_string(), bucket.into());
+        }
+
+        Ok((event, EventAction::Modified))
+    }
+
+    fn applies_to(&self, event: &OispEvent) -> bool {
+        matches!(event, OispEvent::AiResponse(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(provider: &str, model: &str, latency_ms: u64) -> OispEvent {
+        serde_json::from_value(serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": ulid::Ulid::new().to_string(),
+            "event_type": "ai.response",
+            "ts": "2025-01-01T00:00:00Z",
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {
+                "request_id": "req",
+                "provider": {"name": provider},
+                "model": {"id": model},
+                "latency_ms": latency_ms
+            }
+        }))
+        .unwrap()
+    }
+
+    async fn tags(plugin: &LatencyPlugin, event: OispEvent) -> (bool, Option<String>) {
+        let (event, _) = plugin.process(event).await.unwrap();
+        let attrs = &event.envelope().attrs;
+        (
+            attrs[PERF_SLOW_ATTR].as_bool().unwrap(),
+            attrs
+                .get(PERF_BUCKET_ATTR)
+                .map(|b| b.as_str().unwrap().to_string()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_slow_around_threshold() {
+        let mut config = LatencyConfig::default();
+        config
+            .model_thresholds
+            .insert("gpt-4o".to_string(), Duration::from_millis(2000));
+        let plugin = LatencyPlugin::new(config);
+
+        assert!(!tags(&plugin, response("openai", "gpt-4o", 1999)).await.0);
+        assert!(!tags(&plugin, response("openai", "gpt-4o", 2000)).await.0);
+        assert!(tags(&plugin, response("openai", "gpt-4o", 2001)).await.0);
+
+        // Other models fall back to their provider's norm
+        assert!(!tags(&plugin, response("openai", "gpt-4.1", 29_000)).await.0);
+        assert!(tags(&plugin, response("openai", "gpt-4.1", 31_000)).await.0);
+        assert!(
+            tags(&plugin, response("groq", "llama-3.1-8b", 6_000))
+                .await
+                .0
+        );
+        assert!(!tags(&plugin, response("ollama", "llama3", 45_000)).await.0);
+    }
+
+    #[tokio::test]
+    async fn test_buckets_follow_rolling_baseline() {
+        let plugin = LatencyPlugin::new(LatencyConfig {
+            baseline_samples: 100,
+            min_samples: 100,
+            ..Default::default()
+        });
+
+        // 100..=10000ms in steps of 100: p50 = 5000, p90 = 9000, p99 = 9900
+        for i in 1..=100 {
+            let (_, bucket) = tags(&plugin, response("openai", "gpt-4o", i * 100)).await;
+            assert_eq!(bucket, None, "bucketed before the baseline filled");
+        }
+        assert_eq!(
+            tags(&plugin, response("openai", "gpt-4o", 5000)).await.1,
+            Some("p50".to_string())
+        );
+        assert_eq!(
+            tags(&plugin, response("openai", "gpt-4o", 8950)).await.1,
+            Some("p90".to_string())
+        );
+        assert_eq!(
+            tags(&plugin, response("openai", "gpt-4o", 9950)).await.1,
+            Some("p99+".to_string())
+        );
+
+        // A sustained regression moves the baseline, so it stops standing out
+        for _ in 0..100 {
+            tags(&plugin, response("openai", "gpt-4o", 20_000)).await;
+        }
+        assert_eq!(
+            tags(&plugin, response("openai", "gpt-4o", 20_000)).await.1,
+            Some("p50".to_string())
+        );
+
+        // Baselines are per model
+        assert_eq!(
+            tags(&plugin, response("openai", "gpt-4o-mini", 20_000))
+                .await
+                .1,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_responses_without_latency_untouched() {
+        let plugin = LatencyPlugin::new(LatencyConfig::default());
+        let mut event = response("openai", "gpt-4o", 0);
+        if let OispEvent::AiResponse(r) = &mut event {
+            r.data.latency_ms = None;
+        }
+        let (event, action) = plugin.process(event).await.unwrap();
+        assert!(matches!(action, EventAction::Pass));
+        assert!(event.envelope().attrs.is_empty());
+    }
+}
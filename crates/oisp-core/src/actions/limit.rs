@@ -0,0 +1,123 @@
+//! Event limit action plugin
+
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::events::OispEvent;
+use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginInfo, PluginResult};
+
+/// Event limit plugin - passes the first `max_events` events and drops the rest
+///
+/// Add it after the other actions so events they drop are not counted;
+/// events it passes are counted whether or not an exporter then accepts
+/// them. [`EventLimitPlugin::reached`] is notified once the last allowed
+/// event has passed, so the caller can stop the pipeline.
+pub struct EventLimitPlugin {
+    max_events: u64,
+    seen: AtomicU64,
+    reached: Arc<Notify>,
+}
+
+impl EventLimitPlugin {
+    pub fn new(max_events: u64) -> Self {
+        Self {
+            max_events,
+            seen: AtomicU64::new(0),
+            reached: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Notified once the limit has been reached
+    ///
+    /// The notification is kept until awaited, so it is not lost if the
+    /// limit is hit before anyone waits.
+    pub fn reached(&self) -> Arc<Notify> {
+        self.reached.clone()
+    }
+}
+
+impl PluginInfo for EventLimitPlugin {
+    fn name(&self) -> &str {
+        "event-limit"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Stops passing events after a fixed count"
+    }
+}
+
+impl Plugin for EventLimitPlugin {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl ActionPlugin for EventLimitPlugin {
+    async fn process(&self, event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+        let n = self.seen.fetch_add(1, Ordering::SeqCst) + 1;
+        if n > self.max_events {
+            return Ok((event, EventAction::Drop));
+        }
+        if n == self.max_events {
+            self.reached.notify_one();
+        }
+        Ok((event, EventAction::Pass))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Endpoint, EventEnvelope, NetworkConnectData, NetworkConnectEvent};
+
+    fn event() -> OispEvent {
+        OispEvent::NetworkConnect(NetworkConnectEvent {
+            envelope: EventEnvelope::new("network.connect"),
+            data: NetworkConnectData {
+                dest: Endpoint {
+                    ip: Some("127.0.0.1".to_string()),
+                    port: Some(443),
+                    domain: None,
+                    is_private: None,
+                    geo: None,
+                },
+                src: None,
+                protocol: None,
+                success: None,
+                error: None,
+                latency_ms: None,
+                tls: None,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_drops_after_limit() {
+        let limit = EventLimitPlugin::new(2);
+        let reached = limit.reached();
+
+        let (_, action) = limit.process(event()).await.unwrap();
+        assert!(matches!(action, EventAction::Pass));
+        let (_, action) = limit.process(event()).await.unwrap();
+        assert!(matches!(action, EventAction::Pass));
+        let (_, action) = limit.process(event()).await.unwrap();
+        assert!(matches!(action, EventAction::Drop));
+
+        // Hit before anyone waited, but still observed
+        tokio::time::timeout(std::time::Duration::from_secs(1), reached.notified())
+            .await
+            .unwrap();
+    }
+}
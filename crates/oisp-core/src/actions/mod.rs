@@ -1,7 +1,20 @@
 //! Action plugins for OISP Sensor
 //!
-//! Built-in action plugins for event processing, filtering, and redaction.
+//! Built-in action plugins for event processing, filtering, redaction,
+//! budget alerts, latency tagging, agent sessions and agent steps.
 
+mod agent_steps;
+mod budget;
+mod latency;
+mod limit;
 mod redaction;
+mod session;
 
+pub use agent_steps::{AgentStepConfig, AgentStepPlugin};
+pub use budget::{
+    BudgetAlertConfig, BudgetAlertPlugin, BudgetScope, BUDGET_ALERT_ATTR, BUDGET_POLICY_ID,
+};
+pub use latency::{LatencyConfig, LatencyPlugin, PERF_BUCKET_ATTR, PERF_SLOW_ATTR};
+pub use limit::EventLimitPlugin;
 pub use redaction::RedactionPlugin;
+pub use session::{SessionConfig, SessionPlugin, SESSION_END_REASON_ATTR, SESSION_ID_ATTR};
@@ -2,10 +2,11 @@
 
 use async_trait::async_trait;
 use std::any::Any;
+use tracing::warn;
 
 use crate::events::OispEvent;
 use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginConfig, PluginInfo, PluginResult};
-use crate::redaction::{RedactionConfig, RedactionMode};
+use crate::redaction::{redact_event_json, FieldRedaction, RedactionConfig, RedactionMode};
 
 /// Redaction action plugin - filters and redacts sensitive information
 pub struct RedactionPlugin {
@@ -37,6 +38,46 @@ impl RedactionPlugin {
             ..Default::default()
         })
     }
+
+    pub fn config(&self) -> &RedactionConfig {
+        &self.config
+    }
+
+    /// Content fields of the event this plugin would change, without
+    /// changing them
+    pub fn preview(&self, event: &serde_json::Value) -> Vec<FieldRedaction> {
+        redact_event_json(&mut event.clone(), &self.config)
+    }
+
+    /// A redacted copy of the event, or `None` if it needs no changes
+    ///
+    /// The event itself is not touched, so exporters with their own
+    /// redaction can each work from the same shared event.
+    pub fn redacted(&self, event: &OispEvent) -> Option<OispEvent> {
+        // Safe mode redacts sensitive patterns in content fields, minimal
+        // mode replaces the content entirely, full mode passes through
+        if self.config.mode == RedactionMode::Full || !event.is_ai_event() {
+            return None;
+        }
+
+        let mut json = match serde_json::to_value(event) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize event for redaction: {}", e);
+                return None;
+            }
+        };
+        if redact_event_json(&mut json, &self.config).is_empty() {
+            return None;
+        }
+        match serde_json::from_value(json) {
+            Ok(redacted) => Some(redacted),
+            Err(e) => {
+                warn!("Failed to deserialize redacted event: {}", e);
+                None
+            }
+        }
+    }
 }
 
 impl Default for RedactionPlugin {
@@ -93,18 +134,10 @@ impl Plugin for RedactionPlugin {
 #[async_trait]
 impl ActionPlugin for RedactionPlugin {
     async fn process(&self, event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
-        // In minimal mode, we might want to drop content entirely
-        // In safe mode, we redact sensitive patterns
-        // In full mode, we pass through
-
-        if self.config.mode == RedactionMode::Full {
-            return Ok((event, EventAction::Pass));
+        match self.redacted(&event) {
+            Some(redacted) => Ok((redacted, EventAction::Modified)),
+            None => Ok((event, EventAction::Pass)),
         }
-
-        // For now, just pass through - actual redaction would be implemented
-        // by walking the event structure and applying redaction to string fields
-
-        Ok((event, EventAction::Pass))
     }
 
     fn applies_to(&self, event: &OispEvent) -> bool {
@@ -112,3 +145,63 @@ impl ActionPlugin for RedactionPlugin {
         event.is_ai_event()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AiRequestEvent, EventEnvelope, MessageContent};
+
+    fn ai_request(content: &str) -> OispEvent {
+        OispEvent::AiRequest(AiRequestEvent {
+            envelope: EventEnvelope::new("ai.request"),
+            data: serde_json::from_value(serde_json::json!({
+                "request_id": "req-1",
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o"},
+                "messages": [{"role": "user", "content": content}],
+            }))
+            .unwrap(),
+        })
+    }
+
+    fn first_message(event: &OispEvent) -> String {
+        let OispEvent::AiRequest(request) = event else {
+            panic!("expected ai.request");
+        };
+        match &request.data.messages[0].content {
+            Some(MessageContent::Text(text)) => text.clone(),
+            other => panic!("unexpected content {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_safe_mode_redacts_message_content() {
+        let plugin = RedactionPlugin::safe_mode();
+        let (event, action) = plugin
+            .process(ai_request("key sk-proj-abc123def456ghi789jkl012"))
+            .await
+            .unwrap();
+        assert!(matches!(action, EventAction::Modified));
+        assert_eq!(first_message(&event), "key [API_KEY_REDACTED]");
+
+        let (event, action) = plugin.process(ai_request("hello")).await.unwrap();
+        assert!(matches!(action, EventAction::Pass));
+        assert_eq!(first_message(&event), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_full_and_minimal_modes() {
+        let content = "mail user@example.com";
+        let (event, _) = RedactionPlugin::full_capture()
+            .process(ai_request(content))
+            .await
+            .unwrap();
+        assert_eq!(first_message(&event), content);
+
+        let (event, _) = RedactionPlugin::minimal()
+            .process(ai_request(content))
+            .await
+            .unwrap();
+        assert_eq!(first_message(&event), "[REDACTED]");
+    }
+}
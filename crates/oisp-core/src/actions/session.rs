@@ -0,0 +1,552 @@
+//! Agent session action plugin
+//!
+//! Groups related `ai.request`/`ai.response` pairs into `agent.session`
+//! events. Requests belong to the same session when they share a
+//! conversation id, or otherwise come from the same process and endpoint
+//! with no gap longer than the idle timeout between them.
+//!
+//! A session emits `start` with its first request, `update` after every
+//! response (with running turn, token and cost totals) and `end` when the
+//! process exits or, since most clients never say they are done, when the
+//! next event arrives after the idle timeout. Session events are linked to
+//! the event that caused them, and requests and responses are tagged with a
+//! `session_id` attribute.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::budget::response_cost_usd;
+use crate::events::{
+    AgentSessionData, AgentSessionEvent, AiRequestEvent, AiResponseEvent, EventEnvelope, EventTime,
+    OispEvent, RelatedEvent, Relationship, SessionAction, SessionStats,
+};
+use crate::plugins::{ActionPlugin, EventAction, Plugin, PluginError, PluginInfo, PluginResult};
+use crate::spec::DynamicProviderRegistry;
+
+/// Event attribute holding the session a request or response belongs to
+pub const SESSION_ID_ATTR: &str = "session_id";
+
+/// Attribute on `end` events saying why the session ended
+pub const SESSION_END_REASON_ATTR: &str = "session_end_reason";
+
+/// Sessions kept before the least recently active is ended
+const MAX_SESSIONS: usize = 10_000;
+
+/// Session builder configuration
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// End a session when no request or response is seen for this long
+    pub idle_timeout: Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(1800),
+        }
+    }
+}
+
+/// Why a session ended
+#[derive(Debug, Clone, Copy)]
+enum EndReason {
+    IdleTimeout,
+    ProcessExit,
+    Evicted,
+}
+
+impl EndReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            EndReason::IdleTimeout => "idle_timeout",
+            EndReason::ProcessExit => "process_exit",
+            EndReason::Evicted => "evicted",
+        }
+    }
+}
+
+/// An active session
+struct Session {
+    id: String,
+    /// Envelope of the first request; host, process and app context are
+    /// copied from it onto session events
+    context: EventEnvelope,
+    /// First request, for the session's duration
+    started: EventTime,
+    /// Most recent request or response, for the session's duration
+    last_seen: EventTime,
+    last_activity: DateTime<Utc>,
+    stats: SessionStats,
+}
+
+impl Session {
+    /// Extend the session's duration to cover an event
+    fn see(&mut self, envelope: &EventEnvelope) {
+        let time = EventTime::of(envelope);
+        if time.since(&self.last_seen).skew_ms.is_none() {
+            self.last_seen = time;
+        }
+    }
+
+    fn event(
+        &self,
+        action: SessionAction,
+        ts: DateTime<Utc>,
+        cause: Option<&str>,
+    ) -> AgentSessionEvent {
+        let mut envelope = EventEnvelope::new("agent.session");
+        envelope.ts = ts;
+        envelope.host = self.context.host.clone();
+        envelope.actor = self.context.actor.clone();
+        envelope.process = self.context.process.clone();
+        envelope.app = self.context.app.clone();
+        envelope.source = self.context.source.clone();
+        if let Some(event_id) = cause {
+            envelope.related_events.push(RelatedEvent {
+                event_id: event_id.to_string(),
+                relationship: Relationship::CausedBy,
+            });
+        }
+
+        AgentSessionEvent {
+            envelope,
+            data: AgentSessionData {
+                agent: None,
+                action,
+                session_id: Some(self.id.clone()),
+                task_description: None,
+                duration_ms: (action == SessionAction::End)
+                    .then(|| self.last_seen.since(&self.started).ms),
+                stats: Some(self.stats.clone()),
+            },
+        }
+    }
+
+    fn end_event(&self, reason: EndReason, cause: Option<&str>) -> OispEvent {
+        let mut event = self.event(SessionAction::End, self.last_activity, cause);
+        event
+            .envelope
+            .attrs
+            .insert(SESSION_END_REASON_ATTR.to_string(), reason.as_str().into());
+        OispEvent::AgentSession(event)
+    }
+}
+
+#[derive(Default)]
+struct SessionState {
+    /// Active sessions by session key
+    sessions: HashMap<String, Session>,
+    /// Session key of each request still awaiting its response
+    requests: HashMap<String, String>,
+}
+
+impl SessionState {
+    fn end(&mut self, key: &str) -> Option<Session> {
+        let session = self.sessions.remove(key)?;
+        self.requests.retain(|_, k| k != key);
+        Some(session)
+    }
+
+    /// End sessions idle for longer than `timeout` as of `now`
+    fn expire(
+        &mut self,
+        now: DateTime<Utc>,
+        timeout: chrono::Duration,
+        cause: &str,
+    ) -> Vec<OispEvent> {
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, s)| now - s.last_activity > timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|key| self.end(key))
+            .map(|s| s.end_event(EndReason::IdleTimeout, Some(cause)))
+            .collect()
+    }
+}
+
+/// Session plugin - groups related AI requests into `agent.session` events
+pub struct SessionPlugin {
+    config: SessionConfig,
+    registry: Arc<DynamicProviderRegistry>,
+    state: Mutex<SessionState>,
+}
+
+impl SessionPlugin {
+    /// Create a plugin pricing responses with `registry`'s spec bundle
+    pub fn new(config: SessionConfig, registry: Arc<DynamicProviderRegistry>) -> Self {
+        Self {
+            config,
+            registry,
+            state: Mutex::new(SessionState::default()),
+        }
+    }
+
+    /// Conversation id if the request carries one, else process + endpoint
+    fn session_key(request: &AiRequestEvent) -> String {
+        if let Some(id) = request
+            .data
+            .conversation
+            .as_ref()
+            .and_then(|c| c.conversation_id.as_deref())
+        {
+            return format!("conversation:{}", id);
+        }
+        let pid = request
+            .envelope
+            .process
+            .as_ref()
+            .map(|p| p.pid)
+            .unwrap_or(0);
+        let endpoint = request
+            .data
+            .provider
+            .as_ref()
+            .map(|p| p.endpoint.as_deref().unwrap_or(&p.name))
+            .unwrap_or("unknown");
+        format!("process:{}:{}", pid, endpoint)
+    }
+
+    fn on_request(
+        state: &mut SessionState,
+        request: &mut AiRequestEvent,
+        emitted: &mut Vec<OispEvent>,
+    ) {
+        let key = Self::session_key(request);
+        let ts = request.envelope.ts;
+
+        if !state.sessions.contains_key(&key) && state.sessions.len() >= MAX_SESSIONS {
+            let oldest = state
+                .sessions
+                .iter()
+                .min_by_key(|(_, s)| s.last_activity)
+                .map(|(k, _)| k.clone());
+            if let Some(session) = oldest.and_then(|k| state.end(&k)) {
+                emitted.push(session.end_event(EndReason::Evicted, None));
+            }
+        }
+
+        let mut started = false;
+        let session = state.sessions.entry(key.clone()).or_insert_with(|| {
+            started = true;
+            Session {
+                id: ulid::Ulid::new().to_string(),
+                context: request.envelope.clone(),
+                started: EventTime::of(&request.envelope),
+                last_seen: EventTime::of(&request.envelope),
+                last_activity: ts,
+                stats: SessionStats::default(),
+            }
+        });
+        session.last_activity = session.last_activity.max(ts);
+        session.see(&request.envelope);
+        session.stats.llm_calls = Some(session.stats.llm_calls.unwrap_or(0) + 1);
+        if started {
+            emitted.push(OispEvent::AgentSession(session.event(
+                SessionAction::Start,
+                ts,
+                Some(&request.envelope.event_id),
+            )));
+        }
+
+        request
+            .envelope
+            .attrs
+            .insert(SESSION_ID_ATTR.to_string(), session.id.clone().into());
+        state.requests.insert(request.data.request_id.clone(), key);
+    }
+
+    fn on_response(
+        &self,
+        state: &mut SessionState,
+        response: &mut AiResponseEvent,
+        emitted: &mut Vec<OispEvent>,
+    ) {
+        let Some(key) = state.requests.remove(&response.data.request_id) else {
+            return;
+        };
+        let Some(session) = state.sessions.get_mut(&key) else {
+            return;
+        };
+        session.last_activity = session.last_activity.max(response.envelope.ts);
+        session.see(&response.envelope);
+
+        let stats = &mut session.stats;
+        if let Some(usage) = &response.data.usage {
+            let tokens = usage
+                .total_tokens
+                .unwrap_or(usage.prompt_tokens.unwrap_or(0) + usage.completion_tokens.unwrap_or(0));
+            stats.tokens_used = Some(stats.tokens_used.unwrap_or(0) + tokens);
+        }
+        if let Some(cost) = response_cost_usd(&self.registry, response) {
+            stats.estimated_cost_usd = Some(stats.estimated_cost_usd.unwrap_or(0.0) + cost);
+        }
+        if !response.data.tool_calls.is_empty() {
+            stats.tool_calls = Some(stats.tool_calls.unwrap_or(0) + response.data.tool_calls.len());
+        }
+
+        response
+            .envelope
+            .attrs
+            .insert(SESSION_ID_ATTR.to_string(), session.id.clone().into());
+        emitted.push(OispEvent::AgentSession(session.event(
+            SessionAction::Update,
+            response.envelope.ts,
+            Some(&response.envelope.event_id),
+        )));
+    }
+}
+
+impl PluginInfo for SessionPlugin {
+    fn name(&self) -> &str {
+        "agent-session"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Groups related AI requests into agent sessions"
+    }
+}
+
+impl Plugin for SessionPlugin {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl ActionPlugin for SessionPlugin {
+    async fn process(&self, mut event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+
+        let timeout =
+            chrono::Duration::from_std(self.config.idle_timeout).unwrap_or(chrono::Duration::MAX);
+        let envelope = event.envelope();
+        let mut before = state.expire(envelope.ts, timeout, &envelope.event_id);
+        let mut after = Vec::new();
+
+        match &mut event {
+            OispEvent::AiRequest(request) => Self::on_request(&mut state, request, &mut before),
+            OispEvent::AiResponse(response) => self.on_response(&mut state, response, &mut after),
+            OispEvent::ProcessExit(exit) => {
+                let pid = exit.envelope.process.as_ref().map(|p| p.pid);
+                let exited: Vec<String> = state
+                    .sessions
+                    .iter()
+                    .filter(|(_, s)| {
+                        pid.is_some() && s.context.process.as_ref().map(|p| p.pid) == pid
+                    })
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in exited {
+                    if let Some(session) = state.end(&key) {
+                        after.push(
+                            session
+                                .end_event(EndReason::ProcessExit, Some(&exit.envelope.event_id)),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let tagged = event.envelope().attrs.contains_key(SESSION_ID_ATTR);
+        if before.is_empty() && after.is_empty() {
+            let action = if tagged {
+                EventAction::Modified
+            } else {
+                EventAction::Pass
+            };
+            return Ok((event, action));
+        }
+
+        before.push(event.clone());
+        before.append(&mut after);
+        Ok((event, EventAction::Replace(before)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::OispSpecBundle;
+
+    fn plugin() -> SessionPlugin {
+        SessionPlugin::new(
+            SessionConfig {
+                idle_timeout: Duration::from_secs(600),
+            },
+            Arc::new(DynamicProviderRegistry::new(Arc::new(
+                OispSpecBundle::embedded(),
+            ))),
+        )
+    }
+
+    fn event(event_type: &str, ts: &str, data: serde_json::Value) -> OispEvent {
+        serde_json::from_value(serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": ulid::Ulid::new().to_string(),
+            "event_type": event_type,
+            "ts": ts,
+            "process": {"pid": 42, "name": "agent"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": data
+        }))
+        .unwrap()
+    }
+
+    fn request(id: &str, ts: &str) -> OispEvent {
+        event(
+            "ai.request",
+            ts,
+            serde_json::json!({
+                "request_id": id,
+                "provider": {"name": "openai", "endpoint": "https://api.openai.com/v1"},
+                "model": {"id": "gpt-4o"},
+                "conversation": {"conversation_id": "conv-1"}
+            }),
+        )
+    }
+
+    fn response(id: &str, ts: &str, tool_calls: usize) -> OispEvent {
+        let tool_calls: Vec<_> = (0..tool_calls)
+            .map(|i| serde_json::json!({"id": format!("call_{}", i), "name": "read_file"}))
+            .collect();
+        event(
+            "ai.response",
+            ts,
+            serde_json::json!({
+                "request_id": id,
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o"},
+                "tool_calls": tool_calls,
+                "usage": {"total_tokens": 150, "total_cost_usd": 0.01}
+            }),
+        )
+    }
+
+    /// Run an event through the plugin, returning the session events it emitted
+    async fn sessions(plugin: &SessionPlugin, event: OispEvent) -> Vec<AgentSessionEvent> {
+        let (event, action) = plugin.process(event).await.unwrap();
+        let events = match action {
+            EventAction::Replace(events) => events,
+            _ => vec![event],
+        };
+        events
+            .into_iter()
+            .filter_map(|e| match e {
+                OispEvent::AgentSession(s) => Some(s),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_two_turn_session() {
+        let plugin = plugin();
+
+        let start = sessions(&plugin, request("r1", "2024-01-01T10:00:00Z")).await;
+        assert_eq!(start.len(), 1);
+        assert_eq!(start[0].data.action, SessionAction::Start);
+        let session_id = start[0].data.session_id.clone().unwrap();
+
+        let update = sessions(&plugin, response("r1", "2024-01-01T10:00:02Z", 1)).await;
+        assert_eq!(update.len(), 1);
+        assert_eq!(update[0].data.action, SessionAction::Update);
+
+        // Second turn joins the same session
+        assert!(sessions(&plugin, request("r2", "2024-01-01T10:01:00Z"))
+            .await
+            .is_empty());
+        let update = sessions(&plugin, response("r2", "2024-01-01T10:01:03Z", 0)).await;
+        assert_eq!(update.len(), 1);
+        let data = &update[0].data;
+        assert_eq!(data.session_id.as_deref(), Some(session_id.as_str()));
+        let stats = data.stats.as_ref().unwrap();
+        assert_eq!(stats.llm_calls, Some(2));
+        assert_eq!(stats.tool_calls, Some(1));
+        assert_eq!(stats.tokens_used, Some(300));
+        assert!((stats.estimated_cost_usd.unwrap() - 0.02).abs() < 1e-9);
+
+        // Requests and responses carry the session id
+        let (tagged, _) = plugin
+            .process(request("r3", "2024-01-01T10:02:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(
+            tagged.envelope().attrs.get(SESSION_ID_ATTR),
+            Some(&session_id.clone().into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duration_uses_monotonic_time_across_clock_step() {
+        let plugin = plugin();
+        let with_mono = |mut event: OispEvent, ts_mono: u64| {
+            event.envelope_mut().ts_mono = Some(ts_mono);
+            event
+        };
+
+        sessions(
+            &plugin,
+            with_mono(request("r1", "2024-01-01T10:00:05Z"), 1_000_000_000),
+        )
+        .await;
+        // The wall clock stepped back 2s before the response was captured
+        sessions(
+            &plugin,
+            with_mono(response("r1", "2024-01-01T10:00:03Z", 0), 3_500_000_000),
+        )
+        .await;
+
+        let exit = event(
+            "process.exit",
+            "2024-01-01T10:00:04Z",
+            serde_json::json!({"exit_code": 0}),
+        );
+        let emitted = sessions(&plugin, exit).await;
+        assert_eq!(emitted[0].data.action, SessionAction::End);
+        assert_eq!(emitted[0].data.duration_ms, Some(2500));
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_ends_on_timeout() {
+        let plugin = plugin();
+        sessions(&plugin, request("r1", "2024-01-01T10:00:00Z")).await;
+        sessions(&plugin, response("r1", "2024-01-01T10:00:05Z", 0)).await;
+
+        // Any later event past the idle timeout closes the session
+        let emitted = sessions(&plugin, request("r2", "2024-01-01T11:00:00Z")).await;
+        assert_eq!(emitted.len(), 2);
+        let end = &emitted[0];
+        assert_eq!(end.data.action, SessionAction::End);
+        assert_eq!(end.data.duration_ms, Some(5000));
+        assert_eq!(end.data.stats.as_ref().unwrap().tokens_used, Some(150));
+        assert_eq!(
+            end.envelope.attrs.get(SESSION_END_REASON_ATTR),
+            Some(&"idle_timeout".into())
+        );
+
+        // ...and the new request starts a fresh one
+        assert_eq!(emitted[1].data.action, SessionAction::Start);
+        assert_ne!(emitted[1].data.session_id, end.data.session_id);
+    }
+}
@@ -7,9 +7,17 @@
 //! - Sink configuration schema
 //! - Hot-reload capability
 
+use crate::actions::{
+    AgentStepConfig, BudgetAlertConfig, BudgetScope, LatencyConfig, SessionConfig,
+};
+use crate::monotonic::TsMonoSource;
+use crate::pipeline::{ChannelPolicy, EventIds, SchemaValidation};
+use crate::policy::AlertSeverity;
+use crate::redaction::{RedactionConfig, RedactionMode, RedactionRule};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
@@ -56,6 +64,24 @@ pub struct SensorConfig {
 
     /// Correlation settings
     pub correlation: CorrelationSettings,
+
+    /// Kubernetes metadata settings
+    pub kubernetes: KubernetesSettings,
+
+    /// Process memory and CPU usage
+    pub process_resources: ProcessResourceSettings,
+
+    /// AI spend budget alerts
+    pub budget: BudgetSettings,
+
+    /// Slow AI response tagging
+    pub latency: LatencySettings,
+
+    /// AI provider detection
+    pub providers: ProviderSettings,
+
+    /// Labels stamped on every event's `source` (e.g. cluster, role)
+    pub source_labels: HashMap<String, String>,
 }
 
 /// Sensor settings
@@ -64,12 +90,37 @@ pub struct SensorConfig {
 pub struct SensorSettings {
     /// Log level: trace, debug, info, warn, error
     pub log_level: String,
+
+    /// Identifier stamped on events from this sensor; a random ULID per
+    /// run when unset
+    pub instance_id: Option<String>,
+
+    /// Check events against the spec's event schemas: off, warn, strict
+    pub schema_validation: SchemaValidation,
+
+    /// How captured events get their event_id: random, or content (derived
+    /// from the capture, stable across replays)
+    pub event_ids: EventIds,
+
+    /// Where events get ts_mono: capture (the capture timestamp, moved onto
+    /// the host monotonic clock), or sensor (the sensor's monotonic clock on
+    /// receipt)
+    pub ts_mono: TsMonoSource,
+
+    /// Never fetch the spec bundle over the network; use the cached or
+    /// embedded bundle only
+    pub air_gapped: bool,
 }
 
 impl Default for SensorSettings {
     fn default() -> Self {
         Self {
             log_level: "info".to_string(),
+            instance_id: None,
+            schema_validation: SchemaValidation::Off,
+            event_ids: EventIds::Random,
+            ts_mono: TsMonoSource::Capture,
+            air_gapped: false,
         }
     }
 }
@@ -104,6 +155,59 @@ pub struct CaptureSettings {
 
     /// Path to libssl.so for SSL interception
     pub libssl_path: Option<String>,
+
+    /// Attribute undecryptable connections by parsing the TLS ClientHello SNI
+    pub sni_extraction: bool,
+
+    /// Emit a `network.flow` rollup per connection when it ends
+    pub network_flows: bool,
+
+    /// Seconds without traffic after which a flow is closed
+    pub network_flow_idle_timeout_secs: u64,
+
+    /// Seconds without a chunk after which a streamed AI response is closed
+    /// with what was reassembled; active streams are never cut off
+    pub stream_timeout_secs: u64,
+
+    /// Report responses whose request was never captured, as on connections
+    /// established before the sensor started, as low-confidence
+    /// `ai.response` events
+    pub attach_existing: bool,
+
+    /// eBPF ring buffer size in bytes (Linux only, None = 2MB); rounded up
+    /// to a power of two multiple of the page size
+    pub ringbuf_size: Option<usize>,
+
+    /// What to do when the pipeline falls behind capture: "block" (default),
+    /// "drop_oldest" or "drop_newest"
+    pub channel_policy: ChannelPolicy,
+
+    /// Globs of `file.open` paths to report (empty = all)
+    pub file_path_include: Vec<String>,
+
+    /// Globs of `file.open` paths never reported; wins over
+    /// `file_path_include`. `/proc`, `/sys` and `/dev` are always excluded.
+    pub file_path_exclude: Vec<String>,
+
+    /// Seconds without events or heartbeats after which a capture source is
+    /// restarted (0 = no watchdog)
+    pub stall_timeout_secs: u64,
+
+    /// Consecutive restarts of a stalled capture source before giving up
+    pub max_capture_restarts: u32,
+}
+
+/// Default `file_path_exclude`: dependency trees and caches that agents and
+/// build tools open by the thousand
+pub fn default_file_path_exclude() -> Vec<String> {
+    [
+        "**/node_modules/**",
+        "**/__pycache__/**",
+        "**/.git/objects/**",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
 }
 
 impl Default for CaptureSettings {
@@ -118,6 +222,17 @@ impl Default for CaptureSettings {
             pid_filter: Vec::new(),
             ebpf_path: None,
             libssl_path: None,
+            sni_extraction: false,
+            network_flows: false,
+            network_flow_idle_timeout_secs: 120,
+            stream_timeout_secs: 600,
+            attach_existing: false,
+            ringbuf_size: None,
+            channel_policy: ChannelPolicy::Block,
+            file_path_include: Vec::new(),
+            file_path_exclude: default_file_path_exclude(),
+            stall_timeout_secs: crate::watchdog::DEFAULT_STALL_TIMEOUT.as_secs(),
+            max_capture_restarts: crate::watchdog::DEFAULT_MAX_RESTARTS,
         }
     }
 }
@@ -144,8 +259,97 @@ pub struct RedactionSettings {
     /// Redact phone numbers
     pub redact_phone_numbers: bool,
 
+    /// Replace base64 `data:image/...` URLs in content with a marker
+    pub redact_image_data: bool,
+
     /// Custom regex patterns to redact
     pub custom_patterns: Vec<String>,
+
+    /// Named patterns with their own replacement and fields
+    pub rules: Vec<RedactionRuleSettings>,
+
+    /// Truncate each request message beyond this many characters, keeping
+    /// a SHA-256 `content_hash` of the full text (unset = no truncation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_content_chars: Option<usize>,
+
+    /// Hash message content after redaction instead of the original text
+    pub hash_after_redaction: bool,
+}
+
+impl RedactionSettings {
+    /// Redaction for an exporter: these settings in the exporter's own
+    /// mode, if it sets one
+    pub fn for_exporter(&self, mode: Option<&str>) -> RedactionConfig {
+        match mode {
+            Some(mode) => RedactionSettings {
+                mode: mode.to_string(),
+                ..self.clone()
+            }
+            .to_redaction_config(),
+            None => self.to_redaction_config(),
+        }
+    }
+
+    /// Convert to the redaction engine configuration
+    pub fn to_redaction_config(&self) -> RedactionConfig {
+        let mode = match self.mode.to_lowercase().as_str() {
+            "full" => RedactionMode::Full,
+            "minimal" => RedactionMode::Minimal,
+            _ => RedactionMode::Safe,
+        };
+        RedactionConfig {
+            mode,
+            redact_api_keys: self.redact_api_keys,
+            redact_emails: self.redact_emails,
+            redact_credit_cards: self.redact_credit_cards,
+            redact_ssn: self.redact_ssn,
+            redact_phone_numbers: self.redact_phone_numbers,
+            redact_image_data: self.redact_image_data,
+            custom_patterns: self.custom_patterns.clone(),
+            // Rules were validated when the config was loaded
+            rules: self
+                .rules
+                .iter()
+                .filter_map(|rule| rule.compile().ok())
+                .collect(),
+        }
+    }
+}
+
+/// A named custom redaction rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionRuleSettings {
+    /// Name, reported as the finding type
+    pub name: String,
+
+    /// Regex to redact
+    pub pattern: String,
+
+    /// Replacement; `$1` or `${group}` insert capture groups
+    #[serde(default = "default_rule_replacement")]
+    pub replacement: String,
+
+    /// Event fields to apply to, as dotted paths under `data` with `*` for
+    /// any one segment (empty = all content)
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+fn default_rule_replacement() -> String {
+    "[CUSTOM_REDACTED]".to_string()
+}
+
+impl RedactionRuleSettings {
+    /// Compile into the redaction engine's rule
+    pub fn compile(&self) -> Result<RedactionRule, regex::Error> {
+        RedactionRule::new(
+            self.name.clone(),
+            &self.pattern,
+            self.replacement.clone(),
+            self.fields.clone(),
+        )
+    }
 }
 
 impl Default for RedactionSettings {
@@ -157,7 +361,11 @@ impl Default for RedactionSettings {
             redact_credit_cards: true,
             redact_ssn: true,
             redact_phone_numbers: false,
+            redact_image_data: true,
             custom_patterns: Vec::new(),
+            rules: Vec::new(),
+            max_content_chars: None,
+            hash_after_redaction: false,
         }
     }
 }
@@ -256,6 +464,49 @@ pub struct ExportSettings {
 
     /// Oximy Cloud export
     pub oximy: OximyExportConfig,
+
+    /// Retries and dead-letter file for events every exporter rejects
+    pub dead_letter: DeadLetterConfig,
+}
+
+impl ExportSettings {
+    /// Each exporter's own redaction mode, if it sets one
+    pub fn redaction_modes(&self) -> [(&'static str, Option<&str>); 6] {
+        [
+            ("jsonl", self.jsonl.redaction.as_deref()),
+            ("websocket", self.websocket.redaction.as_deref()),
+            ("otlp", self.otlp.redaction.as_deref()),
+            ("kafka", self.kafka.redaction.as_deref()),
+            ("webhook", self.webhook.redaction.as_deref()),
+            ("oximy", self.oximy.redaction.as_deref()),
+        ]
+    }
+
+    /// Concurrency limit of each network exporter
+    pub fn max_in_flight_limits(&self) -> [(&'static str, usize); 4] {
+        [
+            ("otlp", self.otlp.max_in_flight),
+            ("kafka", self.kafka.max_in_flight),
+            ("webhook", self.webhook.max_in_flight),
+            ("oximy", self.oximy.max_in_flight),
+        ]
+    }
+
+    /// Concurrency limit of the exporter named `exporter`, if it has one
+    pub fn max_in_flight(&self, exporter: &str) -> Option<usize> {
+        self.max_in_flight_limits()
+            .into_iter()
+            .find(|(name, _)| *name == exporter)
+            .map(|(_, limit)| limit)
+    }
+
+    /// Redaction mode set for the exporter named `exporter`
+    pub fn redaction_mode(&self, exporter: &str) -> Option<&str> {
+        self.redaction_modes()
+            .into_iter()
+            .find(|(name, _)| *name == exporter)
+            .and_then(|(_, mode)| mode)
+    }
 }
 
 /// JSONL export configuration
@@ -274,8 +525,36 @@ pub struct JsonlExportConfig {
     /// Flush after each event
     pub flush_each: bool,
 
+    /// Without `flush_each`, flush buffered events this often (0 = only
+    /// when the buffer fills, on SIGUSR1 and on shutdown)
+    pub flush_interval_ms: u64,
+
+    /// Without `flush_each`, flush once this many bytes are buffered
+    pub flush_bytes: usize,
+
+    /// Flush on SIGUSR1 (Unix), to snapshot the file mid-run. Every
+    /// exporter is flushed, and events held by `reorder_window_ms` are
+    /// written early.
+    pub flush_on_signal: bool,
+
     /// Pretty print JSON
     pub pretty: bool,
+
+    /// Hold events this many milliseconds and write them sorted by timestamp
+    /// (0 = off). Every event reaches the file up to this much later, and
+    /// events arriving later than the window are still written out of order.
+    pub reorder_window_ms: u64,
+
+    /// Most events held for reordering; beyond this the oldest are written early
+    pub reorder_max_events: usize,
+
+    /// Write events in this older OISP schema version (e.g. "0.1") for
+    /// consumers pinned to it; unset writes the current schema
+    pub schema_version: Option<String>,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
 }
 
 impl Default for JsonlExportConfig {
@@ -285,7 +564,46 @@ impl Default for JsonlExportConfig {
             path: "/var/lib/oisp-sensor/events.jsonl".to_string(),
             append: true,
             flush_each: true,
+            flush_interval_ms: 1000,
+            flush_bytes: 64 * 1024,
+            flush_on_signal: true,
             pretty: false,
+            reorder_window_ms: 0,
+            reorder_max_events: 10_000,
+            schema_version: None,
+            redaction: None,
+        }
+    }
+}
+
+/// Dead-letter configuration
+///
+/// When enabled, an event that every exporter rejects is retried and then
+/// appended to `path` with the exporters' errors, instead of being dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    /// Retry failed events and keep the ones that still fail
+    pub enabled: bool,
+
+    /// Dead-letter JSONL file
+    pub path: String,
+
+    /// Export attempts per event, including the first
+    pub max_attempts: u32,
+
+    /// Delay before the first retry in milliseconds, growing linearly after
+    /// that. Retries hold up the events behind them.
+    pub retry_delay_ms: u64,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "/var/lib/oisp-sensor/dead-letter.jsonl".to_string(),
+            max_attempts: crate::dead_letter::DEFAULT_EXPORT_ATTEMPTS,
+            retry_delay_ms: crate::dead_letter::DEFAULT_RETRY_DELAY.as_millis() as u64,
         }
     }
 }
@@ -305,6 +623,10 @@ pub struct WebSocketExportConfig {
 
     /// Buffer size for messages
     pub buffer_size: usize,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
 }
 
 impl Default for WebSocketExportConfig {
@@ -314,6 +636,7 @@ impl Default for WebSocketExportConfig {
             host: "127.0.0.1".to_string(),
             port: 7777,
             buffer_size: 1000,
+            redaction: None,
         }
     }
 }
@@ -351,6 +674,14 @@ pub struct OtlpExportConfig {
 
     /// Flush interval in milliseconds
     pub flush_interval_ms: u64,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
+
+    /// Sends to this destination allowed in progress at the same time;
+    /// further events wait for one to finish
+    pub max_in_flight: usize,
 }
 
 impl Default for OtlpExportConfig {
@@ -366,6 +697,8 @@ impl Default for OtlpExportConfig {
             bearer_token: None,
             batch_size: 100,
             flush_interval_ms: 5000,
+            redaction: None,
+            max_in_flight: 4,
         }
     }
 }
@@ -406,6 +739,17 @@ pub struct KafkaExportConfig {
 
     /// Message key mode: event_id, host_pid, none
     pub key_mode: String,
+
+    /// Publish events in this older OISP schema version (unset = current)
+    pub schema_version: Option<String>,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
+
+    /// Sends to this destination allowed in progress at the same time;
+    /// further events wait for one to finish
+    pub max_in_flight: usize,
 }
 
 impl Default for KafkaExportConfig {
@@ -422,6 +766,9 @@ impl Default for KafkaExportConfig {
             batch_size: 100,
             linger_ms: 100,
             key_mode: "event_id".to_string(),
+            schema_version: None,
+            redaction: None,
+            max_in_flight: 4,
         }
     }
 }
@@ -474,6 +821,17 @@ pub struct WebhookExportConfig {
 
     /// Initial retry delay in milliseconds
     pub retry_delay_ms: u64,
+
+    /// Send events in this older OISP schema version (unset = current)
+    pub schema_version: Option<String>,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
+
+    /// Sends to this destination allowed in progress at the same time;
+    /// further events wait for one to finish
+    pub max_in_flight: usize,
 }
 
 impl Default for WebhookExportConfig {
@@ -494,6 +852,9 @@ impl Default for WebhookExportConfig {
             flush_interval_ms: 5000,
             max_retries: 3,
             retry_delay_ms: 1000,
+            schema_version: None,
+            redaction: None,
+            max_in_flight: 4,
         }
     }
 }
@@ -519,6 +880,24 @@ pub struct OximyExportConfig {
 
     /// Flush interval in milliseconds
     pub flush_interval_ms: u64,
+
+    /// Redaction mode for this exporter: safe, full, minimal (unset = the
+    /// `[redaction]` mode)
+    pub redaction: Option<String>,
+
+    /// Sends to this destination allowed in progress at the same time;
+    /// further events wait for one to finish
+    pub max_in_flight: usize,
+
+    /// PEM file of extra CA certificates to trust for a self-hosted endpoint
+    pub ca_bundle_path: Option<String>,
+
+    /// Skip the certificate hostname check for a self-hosted endpoint
+    pub accept_invalid_hostnames: bool,
+
+    /// SQLite database holding events while the cloud is unreachable
+    /// (unset = `offline_queue.db` in the platform data directory)
+    pub offline_queue_path: Option<String>,
 }
 
 impl Default for OximyExportConfig {
@@ -530,6 +909,11 @@ impl Default for OximyExportConfig {
             device_id: None,
             batch_size: 100,
             flush_interval_ms: 5000,
+            redaction: None,
+            max_in_flight: 4,
+            ca_bundle_path: None,
+            accept_invalid_hostnames: false,
+            offline_queue_path: None,
         }
     }
 }
@@ -546,6 +930,16 @@ pub struct WebSettings {
 
     /// Port to bind
     pub port: u16,
+
+    /// Serve HTTPS/WSS with this certificate instead of plain HTTP
+    pub tls: Option<WebTlsSettings>,
+
+    /// Require credentials for the API and WebSocket
+    pub auth: Option<WebAuthSettings>,
+
+    /// Origins allowed to call the API cross-origin; `"*"` allows any,
+    /// empty allows only localhost
+    pub cors_origins: Vec<String>,
 }
 
 impl Default for WebSettings {
@@ -554,10 +948,37 @@ impl Default for WebSettings {
             enabled: true,
             host: "127.0.0.1".to_string(),
             port: 7777,
+            tls: None,
+            auth: None,
+            cors_origins: Vec::new(),
         }
     }
 }
 
+/// Web UI TLS certificate (PEM files, re-read on SIGHUP)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebTlsSettings {
+    /// Certificate chain path
+    pub cert_path: String,
+
+    /// Private key path
+    pub key_path: String,
+}
+
+/// Web API credentials; either a bearer token, basic auth, or both
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebAuthSettings {
+    /// Static token accepted as `Authorization: Bearer <token>`
+    pub token: Option<String>,
+
+    /// Basic-auth username
+    pub username: Option<String>,
+
+    /// Basic-auth password
+    pub password: Option<String>,
+}
+
 /// Correlation settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -570,6 +991,20 @@ pub struct CorrelationSettings {
 
     /// Maximum traces to keep in memory
     pub max_traces: usize,
+
+    /// Group related AI requests into `agent.session` events
+    pub sessions: bool,
+
+    /// End a session after this long without requests or responses (ms)
+    pub session_idle_timeout_ms: u64,
+
+    /// Emit `agent.plan_step` for tool-calling responses and link
+    /// `agent.rag_retrieve` events to the embedding request before them
+    pub agent_steps: bool,
+
+    /// Longest gap between an embedding request and a retrieval linked to
+    /// it (ms)
+    pub retrieval_window_ms: u64,
 }
 
 impl Default for CorrelationSettings {
@@ -578,6 +1013,216 @@ impl Default for CorrelationSettings {
             time_window_ms: 5000,
             max_trace_duration_ms: 300000,
             max_traces: 100,
+            sessions: false,
+            session_idle_timeout_ms: 1_800_000,
+            agent_steps: false,
+            retrieval_window_ms: 60_000,
+        }
+    }
+}
+
+impl CorrelationSettings {
+    /// Convert to SessionConfig for the session plugin
+    pub fn to_session_config(&self) -> SessionConfig {
+        SessionConfig {
+            idle_timeout: Duration::from_millis(self.session_idle_timeout_ms),
+        }
+    }
+
+    /// Convert to AgentStepConfig for the agent step plugin
+    pub fn to_agent_step_config(&self) -> AgentStepConfig {
+        AgentStepConfig {
+            retrieval_window: Duration::from_millis(self.retrieval_window_ms),
+        }
+    }
+}
+
+/// Kubernetes metadata settings
+///
+/// Pod, namespace and node of the sensor come from the Downward API
+/// (`POD_NAMESPACE`, `POD_NAME`, `NODE_NAME` environment variables).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KubernetesSettings {
+    /// Add `k8s.*` attributes to events
+    pub enabled: bool,
+
+    /// Kubelet pod list used to map container ids to pods,
+    /// e.g. "http://127.0.0.1:10255/pods"
+    pub kubelet_url: Option<String>,
+
+    /// File with a pod list in the kubelet `/pods` format (instead of kubelet_url)
+    pub pods_file: Option<String>,
+
+    /// Minimum seconds between pod list refreshes
+    pub refresh_interval_secs: u64,
+}
+
+impl Default for KubernetesSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kubelet_url: None,
+            pods_file: None,
+            refresh_interval_secs: 30,
+        }
+    }
+}
+
+/// Process resource usage settings
+///
+/// Adds `process.rss_bytes` and `process.cpu_time_ms` to event `attrs`
+/// (Linux). Each process is read from `/proc` at most once per interval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessResourceSettings {
+    /// Add resource usage attributes to events
+    pub enabled: bool,
+
+    /// Minimum seconds between samples of one process
+    pub sample_interval_secs: u64,
+}
+
+impl Default for ProcessResourceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_interval_secs: 10,
+        }
+    }
+}
+
+/// AI spend budget alert settings
+///
+/// Spend is estimated from response token usage and spec bundle pricing, and
+/// resets at every multiple of the window since the Unix epoch (so a 3600s
+/// window resets on the hour). One alert is raised per key per window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetSettings {
+    /// Enable budget alerts
+    pub enabled: bool,
+
+    /// Alert when estimated spend in a window exceeds this many US dollars
+    pub threshold_usd: f64,
+
+    /// Window length in seconds
+    pub window_secs: u64,
+
+    /// What spend is tracked: global, process, or provider
+    pub scope: BudgetScope,
+
+    /// Webhook receiving alerts as JSON
+    pub webhook_url: Option<String>,
+
+    /// Alert severity: info, warning, critical
+    pub severity: AlertSeverity,
+}
+
+impl Default for BudgetSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_usd: 100.0,
+            window_secs: 86400,
+            scope: BudgetScope::Global,
+            webhook_url: None,
+            severity: AlertSeverity::Warning,
+        }
+    }
+}
+
+impl BudgetSettings {
+    /// Convert to BudgetAlertConfig for the budget alert plugin
+    pub fn to_budget_config(&self) -> BudgetAlertConfig {
+        BudgetAlertConfig {
+            threshold_usd: self.threshold_usd,
+            window: Duration::from_secs(self.window_secs),
+            scope: self.scope,
+            webhook_url: self.webhook_url.clone(),
+            severity: self.severity,
+        }
+    }
+}
+
+/// Slow AI response tagging settings
+///
+/// `ai.response` events get `perf.slow` when their latency exceeds the
+/// model's threshold, and `perf.bucket` (p50, p90, p99 or p99+) once the
+/// model has a rolling baseline of `min_samples` latencies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencySettings {
+    /// Enable latency tagging
+    pub enabled: bool,
+
+    /// Slow threshold for models not in `model_thresholds_ms`
+    /// (None = a typical value for the provider)
+    pub default_threshold_ms: Option<u64>,
+
+    /// Slow threshold per model id, e.g. { "gpt-4o" = 20000 }
+    pub model_thresholds_ms: HashMap<String, u64>,
+
+    /// Latencies kept per model for the rolling baseline
+    pub baseline_samples: usize,
+
+    /// Latencies needed before responses are bucketed
+    pub min_samples: usize,
+}
+
+impl Default for LatencySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_threshold_ms: None,
+            model_thresholds_ms: HashMap::new(),
+            baseline_samples: 200,
+            min_samples: 20,
+        }
+    }
+}
+
+impl LatencySettings {
+    /// Convert to LatencyConfig for the latency plugin
+    pub fn to_latency_config(&self) -> LatencyConfig {
+        LatencyConfig {
+            model_thresholds: self
+                .model_thresholds_ms
+                .iter()
+                .map(|(model, ms)| (model.clone(), Duration::from_millis(*ms)))
+                .collect(),
+            default_threshold: self.default_threshold_ms.map(Duration::from_millis),
+            baseline_samples: self.baseline_samples,
+            min_samples: self.min_samples,
+        }
+    }
+}
+
+/// AI provider detection settings
+///
+/// Requests to hosts in the provider registries are always decoded. These
+/// settings cover self-hosted servers that speak the OpenAI API on hosts
+/// the registries cannot know.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSettings {
+    /// Decode requests to unknown hosts that match the OpenAI chat
+    /// completions, completions or embeddings schema, as `openai-compatible`
+    pub openai_compatible: bool,
+
+    /// Provider name per host or host:port, e.g. { "gpu-box.internal:8000" = "vllm" }
+    pub hosts: HashMap<String, String>,
+
+    /// Hosts whose provider detection result is cached (0 disables the cache)
+    pub detection_cache_size: usize,
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            openai_compatible: true,
+            hosts: HashMap::new(),
+            detection_cache_size: 1024,
         }
     }
 }
@@ -679,6 +1324,44 @@ impl ConfigLoader {
         if let Ok(val) = std::env::var("OISP_LOG_LEVEL") {
             config.sensor.log_level = val;
         }
+        if let Ok(val) = std::env::var("OISP_INSTANCE_ID") {
+            config.sensor.instance_id = Some(val);
+        }
+        if let Ok(val) = std::env::var("OISP_SCHEMA_VALIDATION") {
+            match val.parse() {
+                Ok(mode) => config.sensor.schema_validation = mode,
+                Err(e) => warn!("Ignoring OISP_SCHEMA_VALIDATION: {}", e),
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_EVENT_IDS") {
+            match val.parse() {
+                Ok(mode) => config.sensor.event_ids = mode,
+                Err(e) => warn!("Ignoring OISP_EVENT_IDS: {}", e),
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_TS_MONO") {
+            match val.parse() {
+                Ok(source) => config.sensor.ts_mono = source,
+                Err(e) => warn!("Ignoring OISP_TS_MONO: {}", e),
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_AIR_GAPPED") {
+            config.sensor.air_gapped = val.parse().unwrap_or(config.sensor.air_gapped);
+        }
+        if let Ok(val) = std::env::var("OISP_SOURCE_LABELS") {
+            // key=value pairs, comma-separated; merged over the config file
+            for pair in val.split(',') {
+                match pair.split_once('=') {
+                    Some((key, value)) if !key.trim().is_empty() => {
+                        config
+                            .source_labels
+                            .insert(key.trim().to_string(), value.trim().to_string());
+                    }
+                    _ if pair.trim().is_empty() => {}
+                    _ => warn!("Ignoring malformed OISP_SOURCE_LABELS entry: {}", pair),
+                }
+            }
+        }
 
         // Web settings
         if let Ok(val) = std::env::var("OISP_WEB_PORT") {
@@ -692,6 +1375,47 @@ impl ConfigLoader {
         if let Ok(val) = std::env::var("OISP_WEB_ENABLED") {
             config.web.enabled = val.parse().unwrap_or(config.web.enabled);
         }
+        if let (Ok(cert_path), Ok(key_path)) = (
+            std::env::var("OISP_WEB_TLS_CERT"),
+            std::env::var("OISP_WEB_TLS_KEY"),
+        ) {
+            config.web.tls = Some(WebTlsSettings {
+                cert_path,
+                key_path,
+            });
+        }
+        if let Ok(val) = std::env::var("OISP_WEB_AUTH_TOKEN") {
+            config.web.auth.get_or_insert_with(Default::default).token = Some(val);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("OISP_WEB_AUTH_USER"),
+            std::env::var("OISP_WEB_AUTH_PASSWORD"),
+        ) {
+            let auth = config.web.auth.get_or_insert_with(Default::default);
+            auth.username = Some(username);
+            auth.password = Some(password);
+        }
+        if let Ok(val) = std::env::var("OISP_WEB_CORS_ORIGINS") {
+            config.web.cors_origins = val
+                .split(',')
+                .map(|o| o.trim().to_string())
+                .filter(|o| !o.is_empty())
+                .collect();
+        }
+
+        // Kubernetes settings
+        if let Ok(val) = std::env::var("OISP_K8S_ENABLED") {
+            config.kubernetes.enabled = val.parse().unwrap_or(config.kubernetes.enabled);
+        }
+        if let Ok(val) = std::env::var("OISP_K8S_KUBELET_URL") {
+            config.kubernetes.kubelet_url = Some(val);
+        }
+
+        // Process resource settings
+        if let Ok(val) = std::env::var("OISP_PROCESS_RESOURCES_ENABLED") {
+            config.process_resources.enabled =
+                val.parse().unwrap_or(config.process_resources.enabled);
+        }
 
         // Capture settings
         if let Ok(val) = std::env::var("OISP_CAPTURE_SSL") {
@@ -706,11 +1430,40 @@ impl ConfigLoader {
         if let Ok(val) = std::env::var("OISP_CAPTURE_NETWORK") {
             config.capture.network = val.parse().unwrap_or(config.capture.network);
         }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_SNI") {
+            config.capture.sni_extraction = val.parse().unwrap_or(config.capture.sni_extraction);
+        }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_NETWORK_FLOWS") {
+            config.capture.network_flows = val.parse().unwrap_or(config.capture.network_flows);
+        }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_ATTACH_EXISTING") {
+            config.capture.attach_existing = val.parse().unwrap_or(config.capture.attach_existing);
+        }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_RINGBUF_SIZE") {
+            if let Ok(n) = val.parse() {
+                config.capture.ringbuf_size = Some(n);
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_CHANNEL_POLICY") {
+            match val.parse() {
+                Ok(policy) => config.capture.channel_policy = policy,
+                Err(e) => warn!("Ignoring OISP_CAPTURE_CHANNEL_POLICY: {}", e),
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_CAPTURE_STALL_TIMEOUT") {
+            config.capture.stall_timeout_secs =
+                val.parse().unwrap_or(config.capture.stall_timeout_secs);
+        }
 
         // Redaction settings
         if let Ok(val) = std::env::var("OISP_REDACTION_MODE") {
             config.redaction.mode = val;
         }
+        if let Ok(val) = std::env::var("OISP_MAX_CONTENT_CHARS") {
+            if let Ok(n) = val.parse() {
+                config.redaction.max_content_chars = Some(n);
+            }
+        }
 
         // Oximy settings
         if let Ok(val) = std::env::var("OISP_OXIMY_API_KEY") {
@@ -720,6 +1473,17 @@ impl ConfigLoader {
         if let Ok(val) = std::env::var("OISP_OXIMY_ENDPOINT") {
             config.export.oximy.endpoint = val;
         }
+        if let Ok(val) = std::env::var("OISP_OXIMY_CA_BUNDLE") {
+            config.export.oximy.ca_bundle_path = Some(val);
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_ACCEPT_INVALID_HOSTNAMES") {
+            config.export.oximy.accept_invalid_hostnames = val
+                .parse()
+                .unwrap_or(config.export.oximy.accept_invalid_hostnames);
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_OFFLINE_QUEUE_PATH") {
+            config.export.oximy.offline_queue_path = Some(val);
+        }
 
         // OTLP settings
         if let Ok(val) = std::env::var("OISP_OTLP_ENDPOINT") {
@@ -751,6 +1515,12 @@ impl ConfigLoader {
             config.export.webhook.enabled = val.parse().unwrap_or(config.export.webhook.enabled);
         }
 
+        // Dead-letter settings
+        if let Ok(val) = std::env::var("OISP_DEAD_LETTER_PATH") {
+            config.export.dead_letter.path = val;
+            config.export.dead_letter.enabled = true;
+        }
+
         // JSONL settings
         if let Ok(val) = std::env::var("OISP_JSONL_PATH") {
             config.export.jsonl.path = val;
@@ -805,6 +1575,43 @@ impl ConfigLoader {
                 config.redaction.mode, valid_modes
             )));
         }
+        for (exporter, mode) in config.export.redaction_modes() {
+            if let Some(mode) = mode {
+                if !valid_modes.contains(&mode.to_lowercase().as_str()) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Invalid redaction mode for the {} exporter: {}. Must be one of: {:?}",
+                        exporter, mode, valid_modes
+                    )));
+                }
+            }
+        }
+        for (exporter, limit) in config.export.max_in_flight_limits() {
+            if limit == 0 {
+                return Err(ConfigError::ValidationError(format!(
+                    "export.{}.max_in_flight must be greater than 0",
+                    exporter
+                )));
+            }
+        }
+        if config.redaction.max_content_chars == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "redaction.max_content_chars must be greater than 0".to_string(),
+            ));
+        }
+        for rule in &config.redaction.rules {
+            if rule.name.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Redaction rule with pattern {:?} needs a name",
+                    rule.pattern
+                )));
+            }
+            if let Err(e) = rule.compile() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid pattern in redaction rule {:?}: {}",
+                    rule.name, e
+                )));
+            }
+        }
 
         // Validate OTLP protocol
         if config.export.otlp.enabled {
@@ -828,6 +1635,12 @@ impl ConfigLoader {
             }
         }
 
+        if config.export.dead_letter.enabled && config.export.dead_letter.max_attempts == 0 {
+            return Err(ConfigError::ValidationError(
+                "dead_letter max_attempts must be at least 1".to_string(),
+            ));
+        }
+
         // Validate ports
         if config.web.port == 0 {
             return Err(ConfigError::ValidationError(
@@ -1145,11 +1958,110 @@ mod tests {
         assert_eq!(config.web.port, 8080);
     }
 
+    #[test]
+    fn test_parse_source_labels() {
+        let toml_str = r#"
+            [sensor]
+            instance_id = "sensor-eu-1"
+            schema_validation = "strict"
+            event_ids = "content"
+            ts_mono = "sensor"
+
+            [source_labels]
+            cluster = "prod-eu"
+            role = "gateway"
+        "#;
+        let config: SensorConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.sensor.instance_id.as_deref(), Some("sensor-eu-1"));
+        assert_eq!(config.sensor.schema_validation, SchemaValidation::Strict);
+        assert_eq!(config.sensor.event_ids, EventIds::Content);
+        assert_eq!(config.sensor.ts_mono, TsMonoSource::Sensor);
+        assert_eq!(config.source_labels["cluster"], "prod-eu");
+        assert_eq!(config.source_labels["role"], "gateway");
+    }
+
+    #[test]
+    fn test_parse_provider_hosts() {
+        assert!(SensorConfig::default().providers.openai_compatible);
+
+        let toml_str = r#"
+            [providers]
+            openai_compatible = false
+            detection_cache_size = 64
+
+            [providers.hosts]
+            "gpu-box.internal:8000" = "vllm"
+            "llm.corp.example" = "corp-gateway"
+        "#;
+        let config: SensorConfig = toml::from_str(toml_str).unwrap();
+        assert!(!config.providers.openai_compatible);
+        assert_eq!(config.providers.detection_cache_size, 64);
+        assert_eq!(config.providers.hosts["gpu-box.internal:8000"], "vllm");
+        assert_eq!(config.providers.hosts["llm.corp.example"], "corp-gateway");
+    }
+
+    #[test]
+    fn test_parse_file_path_filter() {
+        let defaults = SensorConfig::default();
+        assert!(defaults.capture.file_path_include.is_empty());
+        assert!(defaults
+            .capture
+            .file_path_exclude
+            .contains(&"**/node_modules/**".to_string()));
+
+        let toml_str = r#"
+            [capture]
+            file_path_include = ["~/projects/**"]
+            file_path_exclude = ["**/target/**"]
+        "#;
+        let config: SensorConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.capture.file_path_include, vec!["~/projects/**"]);
+        assert_eq!(config.capture.file_path_exclude, vec!["**/target/**"]);
+    }
+
+    #[test]
+    fn test_parse_capture_watchdog() {
+        let defaults = SensorConfig::default();
+        assert_eq!(defaults.capture.stall_timeout_secs, 30);
+        assert_eq!(defaults.capture.max_capture_restarts, 3);
+
+        let toml_str = r#"
+            [capture]
+            stall_timeout_secs = 0
+            max_capture_restarts = 5
+        "#;
+        let config: SensorConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.capture.stall_timeout_secs, 0);
+        assert_eq!(config.capture.max_capture_restarts, 5);
+    }
+
+    #[test]
+    fn test_parse_self_hosted_oximy() {
+        let toml_str = r#"
+            [sensor]
+            air_gapped = true
+
+            [export.oximy]
+            endpoint = "https://oximy.corp.internal"
+            ca_bundle_path = "/etc/oisp/corp-ca.pem"
+            accept_invalid_hostnames = true
+        "#;
+        let config: SensorConfig = toml::from_str(toml_str).unwrap();
+        assert!(config.sensor.air_gapped);
+        assert_eq!(
+            config.export.oximy.ca_bundle_path.as_deref(),
+            Some("/etc/oisp/corp-ca.pem")
+        );
+        assert!(config.export.oximy.accept_invalid_hostnames);
+        assert!(!SensorConfig::default().sensor.air_gapped);
+    }
+
     #[test]
     fn test_validation_invalid_log_level() {
         let config = SensorConfig {
             sensor: SensorSettings {
                 log_level: "invalid".to_string(),
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -1172,6 +2084,133 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_dead_letter_config() {
+        let config: SensorConfig = toml::from_str(
+            r#"
+                [export.dead_letter]
+                enabled = true
+                path = "/tmp/failed.jsonl"
+                max_attempts = 5
+            "#,
+        )
+        .unwrap();
+        assert!(config.export.dead_letter.enabled);
+        assert_eq!(config.export.dead_letter.path, "/tmp/failed.jsonl");
+        assert_eq!(config.export.dead_letter.max_attempts, 5);
+        assert_eq!(config.export.dead_letter.retry_delay_ms, 100);
+
+        let mut config = config;
+        config.export.dead_letter.max_attempts = 0;
+        assert!(ConfigLoader::new().validate(&config).is_err());
+    }
+
+    #[test]
+    fn test_redaction_rules_loaded_and_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+                [[redaction.rules]]
+                name = "ticket"
+                pattern = 'TICKET-\d+'
+                replacement = "[TICKET]"
+                fields = ["messages.*.content"]
+            "#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new()
+            .with_cli_path(Some(path))
+            .load()
+            .unwrap();
+        let redaction = config.redaction.to_redaction_config();
+        let result = crate::redaction::redact_field(
+            "blocked on TICKET-4821",
+            "messages.0.content",
+            &redaction,
+        );
+        assert_eq!(result.content, "blocked on [TICKET]");
+        assert_eq!(result.findings[0].finding_type, "ticket");
+    }
+
+    #[test]
+    fn test_per_exporter_redaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+                [redaction]
+                redact_emails = false
+
+                [export.jsonl]
+                redaction = "full"
+
+                [export.oximy]
+                redaction = "minimal"
+            "#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new()
+            .with_cli_path(Some(path.clone()))
+            .load()
+            .unwrap();
+        assert_eq!(config.export.redaction_mode("jsonl"), Some("full"));
+        assert_eq!(config.export.redaction_mode("otlp"), None);
+
+        let jsonl = config
+            .redaction
+            .for_exporter(config.export.redaction_mode("jsonl"));
+        let oximy = config
+            .redaction
+            .for_exporter(config.export.redaction_mode("oximy"));
+        let otlp = config
+            .redaction
+            .for_exporter(config.export.redaction_mode("otlp"));
+        assert_eq!(jsonl.mode, RedactionMode::Full);
+        assert_eq!(oximy.mode, RedactionMode::Minimal);
+        assert_eq!(otlp.mode, RedactionMode::Safe);
+        // The rest of [redaction] still applies
+        assert!(!oximy.redact_emails);
+
+        std::fs::write(&path, "[export.webhook]\nredaction = \"none\"\n").unwrap();
+        let err = ConfigLoader::new()
+            .with_cli_path(Some(path))
+            .load()
+            .unwrap_err();
+        assert!(err.to_string().contains("webhook exporter"));
+    }
+
+    #[test]
+    fn test_validation_invalid_redaction_rule_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+                [[redaction.rules]]
+                name = "ticket"
+                pattern = 'TICKET-(\d+'
+            "#,
+        )
+        .unwrap();
+
+        let err = ConfigLoader::new()
+            .with_cli_path(Some(path))
+            .load()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Invalid pattern in redaction rule \"ticket\""),
+            "{}",
+            err
+        );
+        assert!(err.contains("unclosed group"), "{}", err);
+    }
+
     #[test]
     fn test_serialize_config() {
         let config = SensorConfig::default();
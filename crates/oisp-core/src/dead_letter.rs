@@ -0,0 +1,105 @@
+//! Dead-letter sink for events no exporter accepted
+//!
+//! When every exporter rejects an event, the pipeline retries it a bounded
+//! number of times (see [`ExportRetry`]) and then appends it here, one JSON
+//! object per line with the attempts made and the exporters' errors. The
+//! `event` field is the event exactly as exporters saw it, so the file can be
+//! replayed later (`jq -c .event dead-letter.jsonl > events.jsonl`).
+
+use crate::events::OispEvent;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default number of export attempts before an event is dead-lettered
+pub const DEFAULT_EXPORT_ATTEMPTS: u32 = 3;
+
+/// Default delay before the first retry; each further retry waits longer
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How the pipeline retries events that every exporter rejected
+#[derive(Clone)]
+pub struct ExportRetry {
+    /// Export attempts per event, including the first (at least 1)
+    pub attempts: u32,
+
+    /// Delay before the first retry, multiplied by the attempt number for
+    /// later ones
+    pub delay: Duration,
+
+    /// Where events go after the last attempt (None = dropped)
+    pub dead_letter: Option<Arc<DeadLetterSink>>,
+}
+
+impl Default for ExportRetry {
+    /// One attempt and no dead-letter sink
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            delay: DEFAULT_RETRY_DELAY,
+            dead_letter: None,
+        }
+    }
+}
+
+/// One line of the dead-letter file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterRecord {
+    /// When the last attempt failed
+    pub failed_at: DateTime<Utc>,
+
+    /// Export attempts made
+    pub attempts: u32,
+
+    /// Error of each exporter on the last attempt
+    pub reason: String,
+
+    /// The event itself
+    pub event: OispEvent,
+}
+
+/// Append-only JSONL file of events that could not be exported
+pub struct DeadLetterSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl DeadLetterSink {
+    /// Open (or create) the dead-letter file at `path`, appending to it
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Path of the dead-letter file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `event`, written through so it survives a crash right after
+    pub fn write(&self, event: &OispEvent, attempts: u32, reason: &str) -> io::Result<()> {
+        let record = DeadLetterRecord {
+            failed_at: Utc::now(),
+            attempts,
+            reason: reason.to_string(),
+            event: event.clone(),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)?;
+        file.flush()
+    }
+}
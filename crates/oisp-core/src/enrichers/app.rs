@@ -3,37 +3,81 @@
 //! Enriches events with application information by matching
 //! process info against the app registry. Also enriches web context
 //! with web app identification when Origin/Referer headers are present.
+//! Electron helper processes are attributed to their main app (see
+//! [`super::electron`]).
 
 use async_trait::async_trait;
 use std::any::Any;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use super::electron;
 use crate::app_registry::AppRegistry;
-use crate::events::{OispEvent, WebAppType};
+use crate::events::{AppInfo, AppTier, OispEvent, ProcessInfo, WebAppType};
 use crate::plugins::{EnrichPlugin, Plugin, PluginInfo, PluginResult};
 
 /// App enricher - identifies applications from process info
 pub struct AppEnricher {
     registry: Arc<AppRegistry>,
+    /// Where Electron helpers' ancestors are read from
+    proc_root: PathBuf,
 }
 
 impl AppEnricher {
     /// Create a new AppEnricher with the given registry
     pub fn new(registry: Arc<AppRegistry>) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            proc_root: PathBuf::from("/proc"),
+        }
     }
 
     /// Create an AppEnricher with an empty registry
     pub fn empty() -> Self {
-        Self {
-            registry: Arc::new(AppRegistry::new()),
-        }
+        Self::new(Arc::new(AppRegistry::new()))
+    }
+
+    /// Read process ancestors from `proc_root` instead of /proc, e.g. a
+    /// host /proc mounted into the sensor's container
+    pub fn with_proc_root(mut self, proc_root: impl Into<PathBuf>) -> Self {
+        self.proc_root = proc_root.into();
+        self
     }
 
     /// Get the underlying registry
     pub fn registry(&self) -> &AppRegistry {
         &self.registry
     }
+
+    /// Identify the app of `process`
+    ///
+    /// A registry profile of the process itself wins. Otherwise an Electron
+    /// helper is identified by its main process: through the registry if
+    /// possible, else by the product name of the app bundle.
+    fn identify(&self, process: &ProcessInfo) -> AppInfo {
+        let app_info = self.registry.match_process(process).to_app_info();
+        if app_info.tier == AppTier::Profiled {
+            return app_info;
+        }
+
+        let Some(electron_app) = electron::resolve(&self.proc_root, process) else {
+            return app_info;
+        };
+        let mut main = electron_app.process;
+        if let Some(ref product_name) = electron_app.product_name {
+            main.name = Some(product_name.clone());
+        }
+        let main_info = self.registry.match_process(&main).to_app_info();
+        if main_info.tier != AppTier::Unknown {
+            return main_info;
+        }
+        match electron_app.product_name {
+            Some(product_name) => {
+                AppInfo::identified(product_name.to_lowercase().replace(' ', "-"), product_name)
+            }
+            None => app_info,
+        }
+    }
 }
 
 impl PluginInfo for AppEnricher {
@@ -76,12 +120,8 @@ impl EnrichPlugin for AppEnricher {
         if envelope.app.is_none() {
             // Need process info to match
             if let Some(ref process) = envelope.process {
-                let match_result = self.registry.match_process(process);
-                let app_info = match_result.to_app_info();
-
-                // Only set app info if we found something
                 // For Unknown tier, we still set it to indicate we tried
-                envelope.app = Some(app_info);
+                envelope.app = Some(self.identify(process));
             }
         }
 
@@ -106,15 +146,22 @@ impl EnrichPlugin for AppEnricher {
 
         Ok(())
     }
+
+    fn provides(&self) -> &[&'static str] {
+        &["app"]
+    }
+
+    fn requires(&self) -> &[&'static str] {
+        // Apps are matched on the executable path
+        &["process.exe"]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::app_registry::{AppMetadata, AppProfile, AppSignatures, MacOSSignature};
-    use crate::events::{
-        AiRequestData, AiRequestEvent, AppTier, EventEnvelope, OispEvent, ProcessInfo,
-    };
+    use crate::events::{AiRequestData, AiRequestEvent, EventEnvelope};
 
     fn create_test_registry() -> Arc<AppRegistry> {
         let mut registry = AppRegistry::new();
@@ -267,6 +314,85 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    /// Main process at pid 200 and a network helper at pid 400, two levels
+    /// below it, like a real Electron app on Linux
+    fn electron_chain(dir: &std::path::Path, install: &str, package_json: &str) -> PathBuf {
+        use crate::enrichers::electron::tests::fake_process;
+
+        let proc_root = dir.join("proc");
+        let install = dir.join(install);
+        std::fs::create_dir_all(install.join("resources/app")).unwrap();
+        std::fs::write(install.join("resources/app/package.json"), package_json).unwrap();
+        let exe = install.join("electron-app");
+
+        fake_process(&proc_root, 100, 1, "bash", &dir.join("bin/bash"), &[]);
+        fake_process(&proc_root, 200, 100, "electron-app", &exe, &[]);
+        fake_process(
+            &proc_root,
+            300,
+            200,
+            "Electron Helper",
+            &exe,
+            &["--type=zygote"],
+        );
+        fake_process(
+            &proc_root,
+            400,
+            300,
+            "Electron Helper",
+            &exe,
+            &["--type=utility"],
+        );
+        proc_root
+    }
+
+    #[cfg(unix)]
+    fn electron_helper() -> ProcessInfo {
+        ProcessInfo {
+            pid: 400,
+            ppid: Some(300),
+            name: Some("Electron Helper".to_string()),
+            cmdline: Some("electron-app --type=utility".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_electron_helper_resolved_through_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let proc_root = electron_chain(dir.path(), "opt/cursor", r#"{"productName": "Cursor"}"#);
+        let enricher = AppEnricher::new(create_test_registry()).with_proc_root(proc_root);
+
+        let mut event = create_test_event(electron_helper());
+        enricher.enrich(&mut event).await.unwrap();
+
+        let app = event.envelope().app.as_ref().unwrap();
+        assert_eq!(app.app_id.as_deref(), Some("cursor"));
+        assert_eq!(app.name.as_deref(), Some("Cursor"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_electron_helper_resolved_from_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let proc_root = electron_chain(
+            dir.path(),
+            "opt/Claude",
+            r#"{"name": "claude-desktop", "productName": "Claude Desktop"}"#,
+        );
+        let enricher = AppEnricher::new(create_test_registry()).with_proc_root(proc_root);
+
+        let mut event = create_test_event(electron_helper());
+        enricher.enrich(&mut event).await.unwrap();
+
+        let app = event.envelope().app.as_ref().unwrap();
+        assert_eq!(app.tier, AppTier::Identified);
+        assert_eq!(app.app_id.as_deref(), Some("claude-desktop"));
+        assert_eq!(app.name.as_deref(), Some("Claude Desktop"));
+    }
+
     #[tokio::test]
     async fn test_enrich_web_context() {
         let mut registry = AppRegistry::new();
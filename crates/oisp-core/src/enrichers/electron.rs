@@ -0,0 +1,279 @@
+//! Electron app resolution
+//!
+//! Electron apps (Cursor, VS Code, Claude Desktop, ...) make their network
+//! requests from helper processes whose name says little about the app:
+//! `Electron Helper`, `Cursor Helper (Renderer)`, or on Linux the app binary
+//! itself started with `--type=utility`. To attribute their traffic, the
+//! helper's ancestors are walked up to the main process of the same app, and
+//! the product name is read from the app bundle (`Cursor.app`) or from the
+//! `resources/app` directory next to the executable.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::events::ProcessInfo;
+
+/// Ancestors inspected before giving up on finding the main process
+const MAX_DEPTH: usize = 16;
+
+/// Main process of the Electron app a helper belongs to
+#[derive(Debug, Clone)]
+pub(crate) struct ElectronApp {
+    /// The main process, as read from /proc
+    pub process: ProcessInfo,
+
+    /// Product name, e.g. "Cursor" (None if it could not be determined)
+    pub product_name: Option<String>,
+}
+
+/// Whether `process` looks like an Electron helper process
+pub(crate) fn is_helper(process: &ProcessInfo) -> bool {
+    let helper_name = process
+        .name
+        .as_deref()
+        .is_some_and(|name| name.contains(" Helper") || name.eq_ignore_ascii_case("electron"));
+    let helper_args = process
+        .cmdline
+        .as_deref()
+        .is_some_and(|cmdline| cmdline.split_whitespace().any(|a| a.starts_with("--type=")));
+    let helper_bundle = process
+        .exe
+        .as_deref()
+        .is_some_and(|exe| exe.contains(" Helper.app/") || exe.contains(" Helper ("));
+    helper_name || helper_args || helper_bundle
+}
+
+/// Resolve the Electron app of a helper process
+///
+/// Returns None if `process` is not a helper or no ancestor belongs to the
+/// same app (e.g. the main process already exited).
+pub(crate) fn resolve(proc_root: &Path, process: &ProcessInfo) -> Option<ElectronApp> {
+    if !is_helper(process) {
+        return None;
+    }
+    let helper_root = process.exe.as_deref().map(app_root);
+
+    let mut ppid = process
+        .ppid
+        .or_else(|| read_process(proc_root, process.pid)?.ppid);
+    for _ in 0..MAX_DEPTH {
+        let pid = ppid.filter(|&pid| pid > 1)?;
+        let parent = read_process(proc_root, pid)?;
+
+        let same_app = match (&helper_root, parent.exe.as_deref()) {
+            (Some(helper_root), Some(exe)) => app_root(exe) == *helper_root,
+            _ => true,
+        };
+        if !same_app {
+            return None;
+        }
+        if !is_helper(&parent) {
+            let product_name = parent
+                .exe
+                .as_deref()
+                .and_then(product_name)
+                .or_else(|| parent.name.clone());
+            return Some(ElectronApp {
+                process: parent,
+                product_name,
+            });
+        }
+        ppid = parent.ppid;
+    }
+    None
+}
+
+/// Product name of the Electron app whose executable is `exe`
+///
+/// macOS: the outermost `.app` bundle (`/Applications/Cursor.app/...`).
+/// Elsewhere: `nameShort` of `resources/app/product.json` (VS Code and its
+/// forks), else `productName` of `resources/app/package.json`.
+pub(crate) fn product_name(exe: &str) -> Option<String> {
+    if let Some(bundle) = Path::new(exe)
+        .components()
+        .find_map(|c| c.as_os_str().to_str()?.strip_suffix(".app"))
+    {
+        return Some(bundle.to_string());
+    }
+
+    let resources = Path::new(exe).parent()?.join("resources").join("app");
+    json_string(&resources.join("product.json"), "nameShort")
+        .or_else(|| json_string(&resources.join("package.json"), "productName"))
+}
+
+/// Directory identifying an app: its outermost `.app` bundle, or the
+/// directory of the executable
+fn app_root(exe: &str) -> PathBuf {
+    let path = Path::new(exe);
+    let mut root = PathBuf::new();
+    for component in path.components() {
+        root.push(component);
+        if component.as_os_str().to_string_lossy().ends_with(".app") {
+            return root;
+        }
+    }
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+fn json_string(path: &Path, key: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    json.get(key)?
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Read a process from `<proc_root>/<pid>`
+fn read_process(proc_root: &Path, pid: u32) -> Option<ProcessInfo> {
+    let dir = proc_root.join(pid.to_string());
+
+    // comm may contain spaces ("Electron Helper"); fields resume after the last ')'
+    let stat = fs::read_to_string(dir.join("stat")).ok()?;
+    let ppid = stat
+        .rsplit_once(')')
+        .and_then(|(_, rest)| rest.split_whitespace().nth(1)?.parse().ok());
+
+    let exe = fs::read_link(dir.join("exe"))
+        .ok()
+        .map(|p| p.to_string_lossy().to_string());
+    let name = fs::read_to_string(dir.join("comm"))
+        .ok()
+        .map(|s| s.trim().to_string());
+    let cmdline = fs::read_to_string(dir.join("cmdline"))
+        .ok()
+        .map(|s| s.replace('\0', " ").trim().to_string());
+
+    Some(ProcessInfo {
+        pid,
+        ppid,
+        exe,
+        name,
+        cmdline,
+        ..Default::default()
+    })
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Create `<proc_root>/<pid>` the way procfs shows it
+    pub(crate) fn fake_process(
+        proc_root: &Path,
+        pid: u32,
+        ppid: u32,
+        comm: &str,
+        exe: &Path,
+        args: &[&str],
+    ) {
+        let dir = proc_root.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("stat"),
+            format!("{} ({}) S {} {} {} 0", pid, comm, ppid, pid, pid),
+        )
+        .unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        let mut cmdline = exe.to_string_lossy().to_string();
+        for arg in args {
+            cmdline.push('\0');
+            cmdline.push_str(arg);
+        }
+        fs::write(dir.join("cmdline"), cmdline).unwrap();
+        std::os::unix::fs::symlink(exe, dir.join("exe")).unwrap();
+    }
+
+    #[test]
+    fn test_is_helper() {
+        let process = |name: &str, cmdline: &str| ProcessInfo {
+            name: Some(name.to_string()),
+            cmdline: Some(cmdline.to_string()),
+            ..Default::default()
+        };
+        assert!(is_helper(&process("Electron Helper", "")));
+        assert!(is_helper(&process("Cursor Helper (Renderer)", "")));
+        assert!(is_helper(&process(
+            "cursor",
+            "/usr/share/cursor/cursor --type=utility --utility-sub-type=network.mojom.NetworkService"
+        )));
+        assert!(!is_helper(&process("cursor", "/usr/share/cursor/cursor")));
+        assert!(!is_helper(&process("python3", "python3 agent.py")));
+    }
+
+    #[test]
+    fn test_product_name() {
+        assert_eq!(
+            product_name("/Applications/Cursor.app/Contents/Frameworks/Cursor Helper (Plugin).app/Contents/MacOS/Cursor Helper (Plugin)")
+                .as_deref(),
+            Some("Cursor")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let resources = dir.path().join("resources/app");
+        fs::create_dir_all(&resources).unwrap();
+        let exe = dir.path().join("code").to_string_lossy().to_string();
+        assert_eq!(product_name(&exe), None);
+
+        fs::write(
+            resources.join("package.json"),
+            r#"{"name": "claude-desktop", "productName": "Claude"}"#,
+        )
+        .unwrap();
+        assert_eq!(product_name(&exe).as_deref(), Some("Claude"));
+
+        fs::write(
+            resources.join("product.json"),
+            r#"{"nameShort": "Code", "nameLong": "Visual Studio Code"}"#,
+        )
+        .unwrap();
+        assert_eq!(product_name(&exe).as_deref(), Some("Code"));
+    }
+
+    #[test]
+    fn test_resolve_walks_helpers_to_main_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let proc_root = dir.path().join("proc");
+        let install = dir.path().join("opt/Cursor");
+        fs::create_dir_all(install.join("resources/app")).unwrap();
+        fs::write(
+            install.join("resources/app/product.json"),
+            r#"{"nameShort": "Cursor"}"#,
+        )
+        .unwrap();
+        let exe = install.join("cursor");
+        let shell = dir.path().join("bin/bash");
+
+        fake_process(&proc_root, 100, 1, "bash", &shell, &[]);
+        fake_process(&proc_root, 200, 100, "cursor", &exe, &[]);
+        fake_process(&proc_root, 300, 200, "cursor", &exe, &["--type=zygote"]);
+        fake_process(
+            &proc_root,
+            400,
+            300,
+            "Electron Helper",
+            &exe,
+            &["--type=utility"],
+        );
+
+        let helper = read_process(&proc_root, 400).unwrap();
+        let app = resolve(&proc_root, &helper).unwrap();
+        assert_eq!(app.process.pid, 200);
+        assert_eq!(app.product_name.as_deref(), Some("Cursor"));
+
+        // The main process is not a helper of itself
+        let main = read_process(&proc_root, 200).unwrap();
+        assert!(resolve(&proc_root, &main).is_none());
+
+        // A helper whose ancestors belong to another app stays unresolved
+        fake_process(
+            &proc_root,
+            500,
+            100,
+            "Electron Helper",
+            &exe,
+            &["--type=utility"],
+        );
+        let orphan = read_process(&proc_root, 500).unwrap();
+        assert!(resolve(&proc_root, &orphan).is_none());
+    }
+}
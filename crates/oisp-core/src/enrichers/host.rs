@@ -151,4 +151,8 @@ impl EnrichPlugin for HostEnricher {
 
         Ok(())
     }
+
+    fn provides(&self) -> &[&'static str] {
+        &["host"]
+    }
 }
@@ -0,0 +1,454 @@
+//! Kubernetes metadata enrichment
+//!
+//! Adds `k8s.namespace`, `k8s.pod`, `k8s.node` and `k8s.container` to event
+//! `attrs`. The sensor's own pod and node come from Downward API environment
+//! variables (`POD_NAMESPACE`, `POD_NAME`, `NODE_NAME`). When a pod list
+//! source is configured, the container id resolved by the process enricher
+//! is mapped to the pod actually running it, using the kubelet `/pods`
+//! endpoint or a mounted file in the same format.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::events::OispEvent;
+use crate::plugins::{EnrichPlugin, Plugin, PluginInfo, PluginResult};
+
+/// Default minimum time between pod list refreshes
+pub const DEFAULT_POD_REFRESH: Duration = Duration::from_secs(30);
+
+/// Kubelet requests slower than this are abandoned
+const KUBELET_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Kubernetes location of a workload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PodMetadata {
+    pub namespace: Option<String>,
+    pub pod: Option<String>,
+    pub node: Option<String>,
+    pub container: Option<String>,
+}
+
+impl PodMetadata {
+    /// Read the sensor's own pod from Downward API environment variables
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Read Downward API variables through `lookup`
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let get = |key: &str| lookup(key).filter(|v| !v.is_empty());
+        Self {
+            namespace: get("POD_NAMESPACE"),
+            pod: get("POD_NAME"),
+            node: get("NODE_NAME"),
+            container: get("CONTAINER_NAME"),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.namespace.is_none()
+            && self.pod.is_none()
+            && self.node.is_none()
+            && self.container.is_none()
+    }
+}
+
+/// Where to read the pod list that maps container ids to pods
+#[derive(Debug, Clone)]
+pub enum PodSource {
+    /// Kubelet pod list endpoint, e.g. `http://127.0.0.1:10255/pods`
+    Kubelet(String),
+    /// File containing a pod list in the kubelet `/pods` format
+    File(PathBuf),
+}
+
+/// Container id -> pod mapping, refreshed on lookup misses
+struct PodIndex {
+    containers: HashMap<String, PodMetadata>,
+    fetched_at: Option<Instant>,
+}
+
+// Subset of the kubelet/API server PodList format
+#[derive(Deserialize)]
+struct PodList {
+    #[serde(default)]
+    items: Vec<Pod>,
+}
+
+#[derive(Deserialize)]
+struct Pod {
+    metadata: PodObjectMeta,
+    #[serde(default)]
+    spec: PodSpec,
+    #[serde(default)]
+    status: PodStatus,
+}
+
+#[derive(Deserialize)]
+struct PodObjectMeta {
+    name: String,
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PodSpec {
+    node_name: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PodStatus {
+    #[serde(default)]
+    container_statuses: Vec<ContainerStatus>,
+    #[serde(default)]
+    init_container_statuses: Vec<ContainerStatus>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContainerStatus {
+    name: String,
+    #[serde(rename = "containerID")]
+    container_id: Option<String>,
+}
+
+/// Build a container id -> pod map from a pod list
+fn index_pods(pod_list: &[u8]) -> Result<HashMap<String, PodMetadata>, serde_json::Error> {
+    let list: PodList = serde_json::from_slice(pod_list)?;
+    let mut containers = HashMap::new();
+
+    for pod in list.items {
+        let statuses = pod
+            .status
+            .container_statuses
+            .iter()
+            .chain(&pod.status.init_container_statuses);
+        for status in statuses {
+            // "containerd://<id>", "docker://<id>", "cri-o://<id>"
+            let Some(id) = status.container_id.as_deref() else {
+                continue;
+            };
+            let id = id.rsplit("://").next().unwrap_or(id);
+            containers.insert(
+                id.to_string(),
+                PodMetadata {
+                    namespace: pod.metadata.namespace.clone(),
+                    pod: Some(pod.metadata.name.clone()),
+                    node: pod.spec.node_name.clone(),
+                    container: Some(status.name.clone()),
+                },
+            );
+        }
+    }
+
+    Ok(containers)
+}
+
+/// Kubernetes enricher - adds pod, namespace, node and container attributes
+pub struct KubernetesEnricher {
+    local: PodMetadata,
+    source: Option<PodSource>,
+    refresh: Duration,
+    index: RwLock<PodIndex>,
+    http: reqwest::Client,
+}
+
+impl KubernetesEnricher {
+    /// Create an enricher for a sensor running in the pod described by `local`
+    pub fn new(local: PodMetadata) -> Self {
+        Self {
+            local,
+            source: None,
+            refresh: DEFAULT_POD_REFRESH,
+            index: RwLock::new(PodIndex {
+                containers: HashMap::new(),
+                fetched_at: None,
+            }),
+            http: reqwest::Client::builder()
+                .timeout(KUBELET_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Create an enricher from the Downward API environment
+    pub fn from_env() -> Self {
+        Self::new(PodMetadata::from_env())
+    }
+
+    /// Map container ids to pods using `source`, re-read at most every `refresh`
+    pub fn with_pod_source(mut self, source: PodSource, refresh: Duration) -> Self {
+        self.source = Some(source);
+        self.refresh = refresh;
+        self
+    }
+
+    /// Pod running the given container
+    ///
+    /// Refreshes the pod list on a miss, unless it was fetched less than
+    /// the refresh interval ago; a failed fetch keeps the previous list.
+    async fn lookup(&self, container_id: &str) -> Option<PodMetadata> {
+        let source = self.source.as_ref()?;
+
+        {
+            let index = self.index.read().await;
+            if let Some(pod) = index.containers.get(container_id) {
+                return Some(pod.clone());
+            }
+            if index
+                .fetched_at
+                .is_some_and(|at| at.elapsed() < self.refresh)
+            {
+                return None;
+            }
+        }
+
+        let mut index = self.index.write().await;
+        // Another task may have refreshed while we waited for the lock
+        if index
+            .fetched_at
+            .is_none_or(|at| at.elapsed() >= self.refresh)
+        {
+            index.fetched_at = Some(Instant::now());
+            match self.fetch(source).await {
+                Ok(containers) => {
+                    debug!("Loaded {} containers from pod list", containers.len());
+                    index.containers = containers;
+                }
+                Err(e) => warn!("Failed to load Kubernetes pod list: {}", e),
+            }
+        }
+        index.containers.get(container_id).cloned()
+    }
+
+    async fn fetch(&self, source: &PodSource) -> anyhow::Result<HashMap<String, PodMetadata>> {
+        let body = match source {
+            PodSource::Kubelet(url) => self
+                .http
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec(),
+            PodSource::File(path) => tokio::fs::read(path).await?,
+        };
+        Ok(index_pods(&body)?)
+    }
+}
+
+impl PluginInfo for KubernetesEnricher {
+    fn name(&self) -> &str {
+        "kubernetes-enricher"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Enriches events with Kubernetes pod metadata"
+    }
+}
+
+impl Plugin for KubernetesEnricher {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl EnrichPlugin for KubernetesEnricher {
+    async fn enrich(&self, event: &mut OispEvent) -> PluginResult<()> {
+        let container_id = event
+            .envelope()
+            .process
+            .as_ref()
+            .and_then(|p| p.container_id.clone());
+
+        let resolved = match container_id {
+            Some(id) => self.lookup(&id).await,
+            None => None,
+        };
+        let metadata = match resolved {
+            Some(pod) => PodMetadata {
+                node: pod.node.or_else(|| self.local.node.clone()),
+                ..pod
+            },
+            // Without a pod list, processes the sensor sees are assumed to
+            // share its pod (sidecar deployment); the node always matches
+            None if self.source.is_none() => self.local.clone(),
+            None => PodMetadata {
+                node: self.local.node.clone(),
+                ..Default::default()
+            },
+        };
+        if metadata.is_empty() {
+            return Ok(());
+        }
+
+        let attrs = &mut event.envelope_mut().attrs;
+        let fields = [
+            ("k8s.namespace", metadata.namespace),
+            ("k8s.pod", metadata.pod),
+            ("k8s.node", metadata.node),
+            ("k8s.container", metadata.container),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                attrs
+                    .entry(key.to_string())
+                    .or_insert_with(|| serde_json::Value::String(value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn provides(&self) -> &[&'static str] {
+        &["k8s"]
+    }
+
+    fn requires(&self) -> &[&'static str] {
+        &["process.container_id"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrichers::process_tree::container_id_from_cgroup;
+    use crate::events::{AiRequestData, AiRequestEvent, EventEnvelope, ProcessInfo};
+
+    const CONTAINER_ID: &str = "3f4b1d2c9a8e7f603f4b1d2c9a8e7f603f4b1d2c9a8e7f603f4b1d2c9a8e7f60";
+
+    fn env(vars: &[(&str, &str)]) -> PodMetadata {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        PodMetadata::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    fn event(container_id: Option<String>) -> OispEvent {
+        let mut envelope = EventEnvelope::new("ai.request");
+        envelope.process = Some(ProcessInfo {
+            pid: 4242,
+            container_id,
+            ..Default::default()
+        });
+        OispEvent::AiRequest(AiRequestEvent {
+            envelope,
+            data: AiRequestData {
+                request_id: "req-1".to_string(),
+                provider: None,
+                model: None,
+                auth: None,
+                request_type: None,
+                streaming: None,
+                messages: vec![],
+                messages_count: None,
+                has_system_prompt: None,
+                system_prompt_hash: None,
+                tools: vec![],
+                tools_count: None,
+                tool_choice: None,
+                parameters: None,
+                has_rag_context: None,
+                has_images: None,
+                image_count: None,
+                estimated_tokens: None,
+                conversation: None,
+                agent: None,
+            },
+        })
+    }
+
+    fn attr<'a>(event: &'a OispEvent, key: &str) -> Option<&'a str> {
+        event.envelope().attrs.get(key).and_then(|v| v.as_str())
+    }
+
+    #[tokio::test]
+    async fn test_downward_api_env() {
+        let enricher = KubernetesEnricher::new(env(&[
+            ("POD_NAMESPACE", "ml"),
+            ("POD_NAME", "agent-7d9f"),
+            ("NODE_NAME", "node-a"),
+            ("CONTAINER_NAME", ""),
+        ]));
+
+        let mut event = event(None);
+        enricher.enrich(&mut event).await.unwrap();
+
+        assert_eq!(attr(&event, "k8s.namespace"), Some("ml"));
+        assert_eq!(attr(&event, "k8s.pod"), Some("agent-7d9f"));
+        assert_eq!(attr(&event, "k8s.node"), Some("node-a"));
+        assert_eq!(attr(&event, "k8s.container"), None);
+    }
+
+    #[tokio::test]
+    async fn test_not_in_kubernetes() {
+        let enricher = KubernetesEnricher::new(env(&[]));
+        let mut event = event(None);
+        enricher.enrich(&mut event).await.unwrap();
+        assert!(event.envelope().attrs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_container_id_mapped_to_pod() {
+        let dir = tempfile::tempdir().unwrap();
+        let pods = dir.path().join("pods.json");
+        let pod_list = serde_json::json!({
+            "kind": "PodList",
+            "items": [{
+                "metadata": {"name": "chatbot-5c8d", "namespace": "apps"},
+                "spec": {"nodeName": "node-b"},
+                "status": {"containerStatuses": [
+                    {"name": "app", "containerID": format!("containerd://{}", CONTAINER_ID)},
+                    {"name": "proxy", "containerID": "containerd://ffff"}
+                ]}
+            }]
+        });
+        std::fs::write(&pods, pod_list.to_string()).unwrap();
+
+        let enricher = KubernetesEnricher::new(env(&[
+            ("POD_NAMESPACE", "oisp"),
+            ("POD_NAME", "oisp-sensor-x2k4"),
+            ("NODE_NAME", "node-b"),
+        ]))
+        .with_pod_source(PodSource::File(pods), DEFAULT_POD_REFRESH);
+
+        // Container id as the process enricher resolves it from /proc/<pid>/cgroup
+        let cgroup = format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1a2b.slice/cri-containerd-{}.scope\n",
+            CONTAINER_ID
+        );
+        let container_id = container_id_from_cgroup(&cgroup);
+        assert_eq!(container_id.as_deref(), Some(CONTAINER_ID));
+
+        let mut known = event(container_id);
+        enricher.enrich(&mut known).await.unwrap();
+        assert_eq!(attr(&known, "k8s.namespace"), Some("apps"));
+        assert_eq!(attr(&known, "k8s.pod"), Some("chatbot-5c8d"));
+        assert_eq!(attr(&known, "k8s.node"), Some("node-b"));
+        assert_eq!(attr(&known, "k8s.container"), Some("app"));
+
+        // Unknown containers get only the node, not the sensor's own pod
+        let mut other = event(Some("0123".to_string()));
+        enricher.enrich(&mut other).await.unwrap();
+        assert_eq!(attr(&other, "k8s.node"), Some("node-b"));
+        assert_eq!(attr(&other, "k8s.pod"), None);
+    }
+}
@@ -3,9 +3,16 @@
 //! Built-in enrichers that add context to events.
 
 mod app;
+mod electron;
 mod host;
+mod kubernetes;
 mod process_tree;
+mod resources;
+mod source;
 
 pub use app::AppEnricher;
 pub use host::HostEnricher;
+pub use kubernetes::{KubernetesEnricher, PodMetadata, PodSource, DEFAULT_POD_REFRESH};
 pub use process_tree::ProcessTreeEnricher;
+pub use resources::{ResourceEnricher, ResourceUsage, DEFAULT_SAMPLE_INTERVAL};
+pub use source::SourceEnricher;
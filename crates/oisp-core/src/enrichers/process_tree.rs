@@ -8,10 +8,15 @@ use std::sync::RwLock;
 use crate::events::OispEvent;
 use crate::plugins::{EnrichPlugin, Plugin, PluginInfo, PluginResult};
 
+/// Upper bound on processes remembered from exec events
+const MAX_CACHED_PROCESSES: usize = 65536;
+
 /// Process tree enricher - adds parent process information
+///
+/// Exec events are remembered by PID so the matching exit event, whose
+/// process is already gone from /proc, can be given the same parent.
 pub struct ProcessTreeEnricher {
-    /// Cache of process info by PID
-    #[allow(dead_code)]
+    /// Processes seen exec'ing, by PID (removed again on exit)
     process_cache: RwLock<HashMap<u32, CachedProcess>>,
 }
 
@@ -72,6 +77,37 @@ impl ProcessTreeEnricher {
         }
     }
 
+    /// Container id of a process, from its cgroup path
+    fn get_container_id(&self, pid: u32) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        {
+            std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+                .ok()
+                .and_then(|cgroup| container_id_from_cgroup(&cgroup))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = pid;
+            None
+        }
+    }
+
+    /// Remember an exec'd process until its exit event
+    fn remember(&self, pid: u32, process: CachedProcess) {
+        let mut cache = self.process_cache.write().unwrap();
+        // Exits can be missed; start over rather than grow without bound
+        if cache.len() >= MAX_CACHED_PROCESSES && !cache.contains_key(&pid) {
+            cache.clear();
+        }
+        cache.insert(pid, process);
+    }
+
+    /// Take the process remembered for an exiting PID
+    fn forget(&self, pid: u32) -> Option<CachedProcess> {
+        self.process_cache.write().unwrap().remove(&pid)
+    }
+
     /// Build the process tree for a given PID
     pub fn get_process_tree(&self, pid: u32) -> Vec<u32> {
         let mut tree = vec![pid];
@@ -95,6 +131,28 @@ impl ProcessTreeEnricher {
     }
 }
 
+/// Extract a container id from `/proc/<pid>/cgroup` contents
+///
+/// Handles cgroup v1 (`/kubepods/burstable/pod<uid>/<id>`, `/docker/<id>`)
+/// and v2 systemd scopes (`cri-containerd-<id>.scope`, `docker-<id>.scope`,
+/// `crio-<id>.scope`). Returns `None` for processes outside a container.
+pub(crate) fn container_id_from_cgroup(cgroup: &str) -> Option<String> {
+    const PREFIXES: [&str; 4] = ["cri-containerd-", "docker-", "crio-", "libpod-"];
+
+    cgroup.lines().find_map(|line| {
+        // hierarchy-id:controllers:path
+        let path = line.splitn(3, ':').nth(2)?;
+        path.rsplit('/').find_map(|segment| {
+            let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+            let id = PREFIXES
+                .iter()
+                .find_map(|prefix| segment.strip_prefix(prefix))
+                .unwrap_or(segment);
+            (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| id.to_string())
+        })
+    })
+}
+
 impl Default for ProcessTreeEnricher {
     fn default() -> Self {
         Self::new()
@@ -128,11 +186,32 @@ impl Plugin for ProcessTreeEnricher {
 #[async_trait]
 impl EnrichPlugin for ProcessTreeEnricher {
     async fn enrich(&self, event: &mut OispEvent) -> PluginResult<()> {
+        let is_exec = matches!(event, OispEvent::ProcessExec(_));
         let envelope = match event {
             OispEvent::AiRequest(e) => &mut e.envelope,
             OispEvent::AiResponse(e) => &mut e.envelope,
             OispEvent::ProcessExec(e) => &mut e.envelope,
             OispEvent::NetworkConnect(e) => &mut e.envelope,
+            OispEvent::ProcessExit(e) => {
+                // The process is gone from /proc; use what its exec told us
+                if let Some(proc) = &mut e.envelope.process {
+                    if let Some(cached) = self.forget(proc.pid) {
+                        if matches!(proc.ppid, None | Some(0)) {
+                            proc.ppid = cached.ppid;
+                        }
+                        if proc.exe.is_none() {
+                            proc.exe = cached.exe;
+                        }
+                        if proc.name.is_none() {
+                            proc.name = cached.name;
+                        }
+                        if proc.cmdline.is_none() {
+                            proc.cmdline = cached.cmdline;
+                        }
+                    }
+                }
+                return Ok(());
+            }
             _ => return Ok(()),
         };
 
@@ -152,8 +231,134 @@ impl EnrichPlugin for ProcessTreeEnricher {
                     }
                 }
             }
+            if proc.container_id.is_none() {
+                proc.container_id = self.get_container_id(proc.pid);
+            }
+            if is_exec {
+                self.remember(
+                    proc.pid,
+                    CachedProcess {
+                        ppid: proc.ppid,
+                        exe: proc.exe.clone(),
+                        name: proc.name.clone(),
+                        cmdline: proc.cmdline.clone(),
+                    },
+                );
+            }
         }
 
         Ok(())
     }
+
+    fn provides(&self) -> &[&'static str] {
+        &["process.ppid", "process.exe", "process.container_id"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{
+        EventEnvelope, ProcessExecData, ProcessExecEvent, ProcessExitData, ProcessExitEvent,
+        ProcessInfo,
+    };
+
+    fn process(pid: u32, ppid: Option<u32>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exit_ppid_backfilled_from_exec() {
+        // Well above pid_max, so /proc never has it
+        let pid = 4_000_000_001;
+        let enricher = ProcessTreeEnricher::new();
+
+        let mut envelope = EventEnvelope::new("process.exec");
+        envelope.process = Some(ProcessInfo {
+            name: Some("claude".to_string()),
+            exe: Some("/usr/local/bin/claude".to_string()),
+            ..process(pid, Some(4242))
+        });
+        let mut exec = OispEvent::ProcessExec(ProcessExecEvent {
+            envelope,
+            data: ProcessExecData {
+                exe: "/usr/local/bin/claude".to_string(),
+                args: Vec::new(),
+                cwd: None,
+                env: HashMap::new(),
+                interpreter: None,
+                script_path: None,
+                is_shell: None,
+                is_script: None,
+                is_interactive: None,
+                binary_hash: None,
+                code_signature: None,
+            },
+        });
+        enricher.enrich(&mut exec).await.unwrap();
+
+        let exit = || {
+            let mut envelope = EventEnvelope::new("process.exit");
+            envelope.process = Some(process(pid, Some(0)));
+            OispEvent::ProcessExit(ProcessExitEvent {
+                envelope,
+                data: ProcessExitData {
+                    exit_code: 0,
+                    signal: None,
+                    signal_name: None,
+                    runtime_ms: None,
+                    cpu_user_ms: None,
+                    cpu_system_ms: None,
+                    max_rss_kb: None,
+                    termination_type: None,
+                },
+            })
+        };
+
+        let mut event = exit();
+        enricher.enrich(&mut event).await.unwrap();
+        let proc = event.envelope().process.as_ref().unwrap();
+        assert_eq!(proc.ppid, Some(4242));
+        assert_eq!(proc.name.as_deref(), Some("claude"));
+        assert_eq!(proc.exe.as_deref(), Some("/usr/local/bin/claude"));
+
+        // Evicted on exit
+        assert!(enricher.process_cache.read().unwrap().is_empty());
+        let mut event = exit();
+        enricher.enrich(&mut event).await.unwrap();
+        assert_eq!(event.envelope().process.as_ref().unwrap().ppid, Some(0));
+    }
+
+    #[test]
+    fn test_container_id_from_cgroup() {
+        let id = "9c1f0b6a4e2d8c7b5a3f1e0d9c8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b";
+
+        // cgroup v1, Kubernetes with docker
+        let v1 = format!(
+            "12:memory:/kubepods/burstable/pod7e1c2f4a-1b2c-4d5e-8f90-a1b2c3d4e5f6/{}\n11:cpu:/",
+            id
+        );
+        assert_eq!(container_id_from_cgroup(&v1).as_deref(), Some(id));
+
+        // cgroup v2, systemd driver with containerd
+        let v2 = format!(
+            "0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod7e1c.slice/cri-containerd-{}.scope",
+            id
+        );
+        assert_eq!(container_id_from_cgroup(&v2).as_deref(), Some(id));
+
+        let docker = format!("0::/system.slice/docker-{}.scope", id);
+        assert_eq!(container_id_from_cgroup(&docker).as_deref(), Some(id));
+
+        // Host processes
+        assert_eq!(
+            container_id_from_cgroup("0::/user.slice/user-1000.slice/session-2.scope"),
+            None
+        );
+        assert_eq!(container_id_from_cgroup("0::/init.scope"), None);
+    }
 }
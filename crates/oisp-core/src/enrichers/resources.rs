@@ -0,0 +1,310 @@
+//! Process resource usage enrichment
+//!
+//! Adds `process.rss_bytes` (resident memory) and `process.cpu_time_ms`
+//! (user plus system CPU time) to event `attrs`, read from
+//! `/proc/<pid>/status` and `/proc/<pid>/stat`. Each process is sampled at
+//! most once per interval; events in between reuse the last sample. A
+//! process that has already exited keeps its last sample until it ages out,
+//! and one never sampled gets no attributes.
+
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::events::OispEvent;
+use crate::plugins::{EnrichPlugin, Plugin, PluginInfo, PluginResult};
+
+/// Default time between samples of one process
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Clock ticks per second of `/proc/<pid>/stat` times
+///
+/// USER_HZ is 100 on every architecture Linux exposes to userspace.
+const USER_HZ: u64 = 100;
+
+/// Samples older than this many intervals are dropped
+const STALE_INTERVALS: u32 = 6;
+
+/// Resource usage of a process at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub rss_bytes: Option<u64>,
+    pub cpu_time_ms: Option<u64>,
+}
+
+impl ResourceUsage {
+    fn is_empty(&self) -> bool {
+        self.rss_bytes.is_none() && self.cpu_time_ms.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    usage: ResourceUsage,
+    taken_at: Instant,
+}
+
+/// Resource enricher - adds memory and CPU usage of the event's process
+pub struct ResourceEnricher {
+    proc_root: PathBuf,
+    interval: Duration,
+    samples: RwLock<HashMap<u32, Sample>>,
+}
+
+impl ResourceEnricher {
+    /// Sample each process at most once per `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            proc_root: PathBuf::from("/proc"),
+            interval,
+            samples: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Read process files under `root` instead of `/proc`
+    pub fn with_proc_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.proc_root = root.into();
+        self
+    }
+
+    /// Resource usage of `pid`, from the last sample if it is recent enough
+    fn usage(&self, pid: u32) -> Option<ResourceUsage> {
+        let now = Instant::now();
+        let cached = self.samples.read().unwrap().get(&pid).copied();
+        if let Some(sample) = cached {
+            if now.duration_since(sample.taken_at) < self.interval {
+                return Some(sample.usage);
+            }
+        }
+
+        let usage = self.read(pid);
+        let mut samples = self.samples.write().unwrap();
+        let stale = self.interval * STALE_INTERVALS;
+        samples.retain(|_, sample| now.duration_since(sample.taken_at) < stale);
+        match usage {
+            Some(usage) => {
+                samples.insert(
+                    pid,
+                    Sample {
+                        usage,
+                        taken_at: now,
+                    },
+                );
+                Some(usage)
+            }
+            // Gone (short-lived process); the last sample is the best we have
+            None => samples.get(&pid).map(|sample| sample.usage),
+        }
+    }
+
+    /// Read the current usage of `pid` from procfs
+    fn read(&self, pid: u32) -> Option<ResourceUsage> {
+        let dir = self.proc_root.join(pid.to_string());
+        let usage = ResourceUsage {
+            rss_bytes: std::fs::read_to_string(dir.join("status"))
+                .ok()
+                .and_then(|status| parse_status_rss(&status)),
+            cpu_time_ms: std::fs::read_to_string(dir.join("stat"))
+                .ok()
+                .and_then(|stat| parse_stat_cpu_time(&stat)),
+        };
+        (!usage.is_empty()).then_some(usage)
+    }
+}
+
+impl Default for ResourceEnricher {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_INTERVAL)
+    }
+}
+
+/// Resident memory in bytes, from the `VmRSS` line of `/proc/<pid>/status`
+///
+/// Kernel threads have no `VmRSS` line.
+fn parse_status_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find_map(|l| l.strip_prefix("VmRSS:"))?;
+    let mut parts = line.split_whitespace();
+    let value: u64 = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some("kB") | None => value.checked_mul(1024),
+        Some(_) => None,
+    }
+}
+
+/// User plus system CPU time in milliseconds, from `/proc/<pid>/stat`
+///
+/// The command name may contain spaces and parentheses, so fields are
+/// counted from the last `)`.
+fn parse_stat_cpu_time(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // rest starts at field 3 (state); utime and stime are fields 14 and 15
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some((utime + stime) * 1000 / USER_HZ)
+}
+
+impl PluginInfo for ResourceEnricher {
+    fn name(&self) -> &str {
+        "resource-enricher"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Enriches events with process memory and CPU usage"
+    }
+
+    fn is_available(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+}
+
+impl Plugin for ResourceEnricher {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl EnrichPlugin for ResourceEnricher {
+    async fn enrich(&self, event: &mut OispEvent) -> PluginResult<()> {
+        let Some(pid) = event.envelope().process.as_ref().map(|p| p.pid) else {
+            return Ok(());
+        };
+        let Some(usage) = self.usage(pid) else {
+            return Ok(());
+        };
+
+        let attrs = &mut event.envelope_mut().attrs;
+        let fields = [
+            ("process.rss_bytes", usage.rss_bytes),
+            ("process.cpu_time_ms", usage.cpu_time_ms),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                attrs
+                    .entry(key.to_string())
+                    .or_insert_with(|| serde_json::Value::from(value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn provides(&self) -> &[&'static str] {
+        &["process.resources"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventEnvelope, ProcessExecData, ProcessExecEvent, ProcessInfo};
+
+    const STATUS: &str = "Name:\tpython3\n\
+        Umask:\t0022\n\
+        State:\tS (sleeping)\n\
+        Tgid:\t4242\n\
+        Pid:\t4242\n\
+        PPid:\t1\n\
+        VmPeak:\t  512000 kB\n\
+        VmSize:\t  498000 kB\n\
+        VmHWM:\t  130000 kB\n\
+        VmRSS:\t  123456 kB\n\
+        Threads:\t12\n";
+
+    // comm "my (agent) 1" contains spaces and parentheses
+    const STAT: &str = "4242 (my (agent) 1) S 1 4242 4242 0 -1 4194560 25000 0 12 0 \
+        1234 567 0 0 20 0 12 0 1000 509952000 30864 18446744073709551615 1 1 0 0 0 0 0 \
+        16781312 134234626 0 0 0 17 3 0 0 0 0 0\n";
+
+    fn event(pid: u32) -> OispEvent {
+        let mut envelope = EventEnvelope::new("process.exec");
+        envelope.process = Some(ProcessInfo {
+            pid,
+            ..Default::default()
+        });
+        OispEvent::ProcessExec(ProcessExecEvent {
+            envelope,
+            data: ProcessExecData {
+                exe: "/usr/bin/python3".to_string(),
+                args: vec![],
+                cwd: None,
+                env: Default::default(),
+                interpreter: None,
+                script_path: None,
+                is_shell: None,
+                is_script: None,
+                is_interactive: None,
+                binary_hash: None,
+                code_signature: None,
+            },
+        })
+    }
+
+    fn write_proc(root: &std::path::Path, pid: u32, status: &str, stat: &str) {
+        let dir = root.join(pid.to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("status"), status).unwrap();
+        std::fs::write(dir.join("stat"), stat).unwrap();
+    }
+
+    #[test]
+    fn test_parse_proc_files() {
+        assert_eq!(parse_status_rss(STATUS), Some(123456 * 1024));
+        assert_eq!(parse_status_rss("Name:\tkthreadd\nThreads:\t1\n"), None);
+
+        // (1234 + 567) ticks at 100 Hz
+        assert_eq!(parse_stat_cpu_time(STAT), Some(18010));
+        assert_eq!(parse_stat_cpu_time("4242 (python3) S 1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_samples_per_interval() {
+        let root = tempfile::tempdir().unwrap();
+        write_proc(root.path(), 4242, STATUS, STAT);
+        let enricher = ResourceEnricher::new(Duration::from_secs(3600)).with_proc_root(root.path());
+
+        let mut first = event(4242);
+        enricher.enrich(&mut first).await.unwrap();
+        let attrs = &first.envelope().attrs;
+        assert_eq!(attrs["process.rss_bytes"], 123456 * 1024);
+        assert_eq!(attrs["process.cpu_time_ms"], 18010);
+
+        // Within the interval the sample is reused, even once the process
+        // has exited
+        std::fs::remove_dir_all(root.path().join("4242")).unwrap();
+        let mut second = event(4242);
+        enricher.enrich(&mut second).await.unwrap();
+        assert_eq!(second.envelope().attrs["process.cpu_time_ms"], 18010);
+
+        // A process never sampled gets nothing
+        let mut unknown = event(7);
+        enricher.enrich(&mut unknown).await.unwrap();
+        assert!(!unknown.envelope().attrs.contains_key("process.rss_bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_enrich_resamples_after_interval() {
+        let root = tempfile::tempdir().unwrap();
+        write_proc(root.path(), 4242, STATUS, STAT);
+        let enricher = ResourceEnricher::new(Duration::ZERO).with_proc_root(root.path());
+
+        let mut first = event(4242);
+        enricher.enrich(&mut first).await.unwrap();
+        write_proc(root.path(), 4242, "VmRSS:\t 2048 kB\n", STAT);
+        let mut second = event(4242);
+        enricher.enrich(&mut second).await.unwrap();
+        assert_eq!(second.envelope().attrs["process.rss_bytes"], 2048 * 1024);
+    }
+}
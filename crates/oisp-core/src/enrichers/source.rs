@@ -0,0 +1,130 @@
+//! Sensor identity enrichment
+//!
+//! Stamps every event's `source` with the sensor instance id, the sensor
+//! version and the deployment labels from `source_labels`, so downstream
+//! systems can tell sensors in a fleet apart.
+
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use crate::events::OispEvent;
+use crate::plugins::{EnrichPlugin, Plugin, PluginInfo, PluginResult};
+
+/// Source enricher - adds sensor instance id and labels to events
+pub struct SourceEnricher {
+    instance_id: String,
+    labels: BTreeMap<String, String>,
+}
+
+impl SourceEnricher {
+    /// Create an enricher for this sensor instance
+    pub fn new(
+        instance_id: impl Into<String>,
+        labels: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        Self {
+            instance_id: instance_id.into(),
+            labels: labels.into_iter().collect(),
+        }
+    }
+
+    /// The instance id stamped on events
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+}
+
+impl PluginInfo for SourceEnricher {
+    fn name(&self) -> &str {
+        "source-enricher"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Enriches events with sensor instance id and deployment labels"
+    }
+}
+
+impl Plugin for SourceEnricher {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl EnrichPlugin for SourceEnricher {
+    async fn enrich(&self, event: &mut OispEvent) -> PluginResult<()> {
+        let source = &mut event.envelope_mut().source;
+
+        source.instance_id = Some(self.instance_id.clone());
+        if source.collector_version.is_none() {
+            source.collector_version = Some(crate::SENSOR_VERSION.to_string());
+        }
+        // Labels set while decoding win over configured ones
+        for (key, value) in &self.labels {
+            source
+                .labels
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        Ok(())
+    }
+
+    fn provides(&self) -> &[&'static str] {
+        &["source.instance_id"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventEnvelope, ProcessExitData, ProcessExitEvent};
+
+    #[tokio::test]
+    async fn test_labels_on_serialized_event() {
+        let enricher = SourceEnricher::new(
+            "sensor-a",
+            [
+                ("cluster".to_string(), "prod-eu".to_string()),
+                ("role".to_string(), "gateway".to_string()),
+            ],
+        );
+
+        let mut envelope = EventEnvelope::new("process.exit");
+        envelope.source.collector_version = None;
+        envelope
+            .source
+            .labels
+            .insert("role".to_string(), "decoder".to_string());
+        let mut event = OispEvent::ProcessExit(ProcessExitEvent {
+            envelope,
+            data: ProcessExitData {
+                exit_code: 0,
+                signal: None,
+                signal_name: None,
+                runtime_ms: None,
+                cpu_user_ms: None,
+                cpu_system_ms: None,
+                max_rss_kb: None,
+                termination_type: None,
+            },
+        });
+        enricher.enrich(&mut event).await.unwrap();
+
+        let json = serde_json::to_value(&event).unwrap();
+        let source = &json["source"];
+        assert_eq!(source["instance_id"], "sensor-a");
+        assert_eq!(source["collector_version"], crate::SENSOR_VERSION);
+        assert_eq!(source["labels"]["cluster"], "prod-eu");
+        assert_eq!(source["labels"]["role"], "decoder");
+    }
+}
@@ -497,6 +497,8 @@ pub struct AgentSessionData {
 #[serde(rename_all = "snake_case")]
 pub enum SessionAction {
     Start,
+    /// Aggregate stats changed while the session is active
+    Update,
     End,
     Pause,
     Resume,
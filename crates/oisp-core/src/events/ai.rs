@@ -405,6 +405,10 @@ pub struct Message {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_count: Option<usize>,
 
+    /// The images, described without their data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInfo>,
+
     /// Tool call ID this responds to
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
@@ -414,6 +418,36 @@ pub struct Message {
     pub name: Option<String>,
 }
 
+/// An image sent to the model
+///
+/// Inline images are recorded by format and size only; their base64 data is
+/// never kept in events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    /// How the image was sent
+    pub source: ImageSource,
+
+    /// MIME type, e.g. `image/png`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+
+    /// Approximate decoded size of an inline image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+}
+
+/// How an image was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Inline, base64-encoded (data URL or raw base64)
+    Base64,
+    /// Link to an image the provider fetches
+    Url,
+    /// Previously uploaded file
+    File,
+}
+
 /// Message role
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -529,6 +563,10 @@ pub struct ToolCall {
     /// Hash of arguments
     #[serde(skip_serializing_if = "Option::is_none")]
     pub arguments_hash: Option<String>,
+
+    /// Size of the full arguments in bytes, set when only a preview was kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments_size: Option<usize>,
 }
 
 /// Tool arguments
@@ -584,6 +622,9 @@ pub enum FinishReason {
     ToolCalls,
     ContentFilter,
     Error,
+    /// The response never completed (connection dropped, capture lost the
+    /// rest); set by the sensor, not the provider
+    Incomplete,
     Other,
 }
 
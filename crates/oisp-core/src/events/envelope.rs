@@ -2,7 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// The canonical envelope for all OISP events
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -513,6 +513,14 @@ pub struct Source {
     /// Sensor host if different from event host
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sensor_host: Option<String>,
+
+    /// Identifier of the sensor instance that emitted the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+
+    /// Deployment labels configured on the sensor (e.g. cluster, role)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
 }
 
 impl Default for Source {
@@ -523,6 +531,8 @@ impl Default for Source {
             capture_method: None,
             capture_point: None,
             sensor_host: None,
+            instance_id: None,
+            labels: BTreeMap::new(),
         }
     }
 }
@@ -8,6 +8,8 @@ pub mod envelope;
 pub mod file;
 pub mod network;
 pub mod process;
+pub mod schema;
+pub mod timing;
 
 pub use agent::*;
 pub use ai::*;
@@ -15,6 +17,8 @@ pub use envelope::*;
 pub use file::*;
 pub use network::*;
 pub use process::*;
+pub use schema::{SchemaError, SchemaTransform, SchemaVersion};
+pub use timing::{Elapsed, EventTime, CLOCK_SKEW_ATTR};
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -128,6 +132,33 @@ impl OispEvent {
             OispEvent::CaptureRaw(e) => &e.envelope,
         }
     }
+
+    /// Get a mutable reference to the envelope of any event
+    pub fn envelope_mut(&mut self) -> &mut EventEnvelope {
+        match self {
+            OispEvent::AiRequest(e) => &mut e.envelope,
+            OispEvent::AiResponse(e) => &mut e.envelope,
+            OispEvent::AiStreamingChunk(e) => &mut e.envelope,
+            OispEvent::AiEmbedding(e) => &mut e.envelope,
+            OispEvent::AgentToolCall(e) => &mut e.envelope,
+            OispEvent::AgentToolResult(e) => &mut e.envelope,
+            OispEvent::AgentPlanStep(e) => &mut e.envelope,
+            OispEvent::AgentRagRetrieve(e) => &mut e.envelope,
+            OispEvent::AgentSession(e) => &mut e.envelope,
+            OispEvent::ProcessExec(e) => &mut e.envelope,
+            OispEvent::ProcessExit(e) => &mut e.envelope,
+            OispEvent::ProcessFork(e) => &mut e.envelope,
+            OispEvent::FileOpen(e) => &mut e.envelope,
+            OispEvent::FileRead(e) => &mut e.envelope,
+            OispEvent::FileWrite(e) => &mut e.envelope,
+            OispEvent::FileClose(e) => &mut e.envelope,
+            OispEvent::NetworkConnect(e) => &mut e.envelope,
+            OispEvent::NetworkAccept(e) => &mut e.envelope,
+            OispEvent::NetworkFlow(e) => &mut e.envelope,
+            OispEvent::NetworkDns(e) => &mut e.envelope,
+            OispEvent::CaptureRaw(e) => &mut e.envelope,
+        }
+    }
 }
 
 /// Event type categories for filtering
@@ -0,0 +1,406 @@
+//! Event schema versions and downgrades for pinned consumers
+//!
+//! Events are always built in the schema this sensor emits
+//! ([`crate::OISP_VERSION`]). Exporters whose consumers are pinned to an
+//! older schema serialize through a [`SchemaTransform`], which replays
+//! [`SCHEMA_HISTORY`] backwards: fields added after the target are dropped
+//! and renamed fields get their old names back. Events of a type that did
+//! not exist yet cannot be downgraded and are rejected.
+
+use super::OispEvent;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A `major.minor` schema version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Schema this sensor emits
+    pub fn current() -> Self {
+        crate::OISP_VERSION
+            .parse()
+            .expect("OISP_VERSION is a major.minor version")
+    }
+}
+
+impl FromStr for SchemaVersion {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SchemaError::InvalidVersion(s.to_string());
+        let (major, minor) = s.trim().split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// How the serialized event shape changed
+///
+/// Paths are dotted JSON paths from the event root; a `[]` suffix steps into
+/// every element of an array (e.g. `data.tool_calls[].arguments_size`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaChange {
+    /// Field added at this path; dropped on downgrade
+    AddedField(&'static str),
+    /// Field at this path was previously called `old_name`; renamed back on downgrade
+    RenamedField {
+        path: &'static str,
+        old_name: &'static str,
+    },
+    /// Event type added; such events cannot be downgraded
+    AddedEventType,
+}
+
+/// A change made in a schema version
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaRevision {
+    /// Version that introduced the change
+    pub version: SchemaVersion,
+    /// Event type the change applies to (`None` for every event)
+    pub event_type: Option<&'static str>,
+    pub change: SchemaChange,
+}
+
+/// Changes to the event shape since [`OLDEST_SCHEMA`], oldest first
+///
+/// Empty while 0.1 is the only published schema. A spec release that
+/// changes the event shape adds its entries here, together with bumping
+/// [`crate::OISP_VERSION`].
+pub const SCHEMA_HISTORY: &[SchemaRevision] = &[];
+
+/// Oldest schema events can be downgraded to
+pub const OLDEST_SCHEMA: SchemaVersion = SchemaVersion::new(0, 1);
+
+/// Schema negotiation error
+#[derive(Error, Debug)]
+pub enum SchemaError {
+    #[error("invalid schema version '{0}' (expected major.minor, e.g. 0.1)")]
+    InvalidVersion(String),
+
+    #[error("schema {target} is newer than schema {current} emitted by this sensor")]
+    NewerThanCurrent {
+        target: SchemaVersion,
+        current: SchemaVersion,
+    },
+
+    #[error(
+        "schema {target} is older than {oldest}, the oldest schema events can be downgraded to"
+    )]
+    TooOld {
+        target: SchemaVersion,
+        oldest: SchemaVersion,
+    },
+
+    #[error("{event_type} events do not exist in schema {target}")]
+    UnsupportedEventType {
+        event_type: String,
+        target: SchemaVersion,
+    },
+
+    #[error("failed to serialize event: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Serializes events in a target schema version
+#[derive(Debug, Clone)]
+pub struct SchemaTransform {
+    target: SchemaVersion,
+    /// Changes newer than the target, newest first
+    changes: Vec<SchemaRevision>,
+}
+
+impl SchemaTransform {
+    /// Transform to `target` (e.g. `"0.1"`)
+    ///
+    /// Fails if the target is newer than the current schema or older than
+    /// [`OLDEST_SCHEMA`].
+    pub fn new(target: &str) -> Result<Self, SchemaError> {
+        Self::with_history(
+            target.parse()?,
+            SchemaVersion::current(),
+            OLDEST_SCHEMA,
+            SCHEMA_HISTORY,
+        )
+    }
+
+    fn with_history(
+        target: SchemaVersion,
+        current: SchemaVersion,
+        oldest: SchemaVersion,
+        history: &[SchemaRevision],
+    ) -> Result<Self, SchemaError> {
+        if target > current {
+            return Err(SchemaError::NewerThanCurrent { target, current });
+        }
+        if target < oldest {
+            return Err(SchemaError::TooOld { target, oldest });
+        }
+        let changes = history
+            .iter()
+            .rev()
+            .filter(|revision| revision.version > target)
+            .copied()
+            .collect();
+        Ok(Self { target, changes })
+    }
+
+    /// Target schema version
+    pub fn target(&self) -> SchemaVersion {
+        self.target
+    }
+
+    /// Serialize an event in the target schema
+    pub fn apply(&self, event: &OispEvent) -> Result<Value, SchemaError> {
+        let mut value = serde_json::to_value(event)?;
+        self.downgrade(&mut value)?;
+        Ok(value)
+    }
+
+    /// Rewrite an already serialized event into the target schema
+    pub fn downgrade(&self, event: &mut Value) -> Result<(), SchemaError> {
+        let event_type = event
+            .get("event_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        for revision in &self.changes {
+            if revision.event_type.is_some_and(|t| t != event_type) {
+                continue;
+            }
+            match revision.change {
+                SchemaChange::AddedEventType => {
+                    return Err(SchemaError::UnsupportedEventType {
+                        event_type,
+                        target: self.target,
+                    });
+                }
+                SchemaChange::AddedField(path) => {
+                    edit_path(event, path, &mut |parent, name| {
+                        parent.remove(name);
+                    });
+                }
+                SchemaChange::RenamedField { path, old_name } => {
+                    edit_path(event, path, &mut |parent, name| {
+                        if let Some(value) = parent.remove(name) {
+                            parent.insert(old_name.to_string(), value);
+                        }
+                    });
+                }
+            }
+        }
+
+        if let Some(root) = event.as_object_mut() {
+            root.insert(
+                "oisp_version".to_string(),
+                Value::String(self.target.to_string()),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Serialize an event as JSON text, downgraded when a target schema is set
+pub fn event_to_string(
+    event: &OispEvent,
+    schema: Option<&SchemaTransform>,
+) -> Result<String, SchemaError> {
+    Ok(match schema {
+        Some(schema) => serde_json::to_string(&schema.apply(event)?)?,
+        None => serde_json::to_string(event)?,
+    })
+}
+
+/// Serialize events as a JSON array, downgraded when a target schema is set
+pub fn events_to_string(
+    events: &[OispEvent],
+    schema: Option<&SchemaTransform>,
+) -> Result<String, SchemaError> {
+    Ok(match schema {
+        Some(schema) => {
+            let events = events
+                .iter()
+                .map(|event| schema.apply(event))
+                .collect::<Result<Vec<_>, _>>()?;
+            serde_json::to_string(&events)?
+        }
+        None => serde_json::to_string(events)?,
+    })
+}
+
+/// Call `edit` with each object holding the last segment of `path`
+fn edit_path(value: &mut Value, path: &str, edit: &mut dyn FnMut(&mut Map<String, Value>, &str)) {
+    match path.split_once('.') {
+        None => {
+            if let Some(parent) = value.as_object_mut() {
+                edit(parent, path);
+            }
+        }
+        Some((head, rest)) => {
+            let (name, each) = match head.strip_suffix("[]") {
+                Some(name) => (name, true),
+                None => (head, false),
+            };
+            let Some(child) = value.get_mut(name) else {
+                return;
+            };
+            if !each {
+                edit_path(child, rest, edit);
+            } else if let Some(items) = child.as_array_mut() {
+                for item in items {
+                    edit_path(item, rest, edit);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeSet;
+
+    const V0_1: SchemaVersion = SchemaVersion::new(0, 1);
+    const V0_2: SchemaVersion = SchemaVersion::new(0, 2);
+    const V0_3: SchemaVersion = SchemaVersion::new(0, 3);
+
+    /// A plausible future history, with this build's events as schema 0.3
+    const HISTORY: &[SchemaRevision] = &[
+        SchemaRevision {
+            version: V0_2,
+            event_type: Some("ai.response"),
+            change: SchemaChange::AddedField("data.tool_calls[].arguments_size"),
+        },
+        SchemaRevision {
+            version: V0_2,
+            event_type: None,
+            change: SchemaChange::AddedField("host"),
+        },
+        SchemaRevision {
+            version: V0_3,
+            event_type: Some("ai.response"),
+            change: SchemaChange::RenamedField {
+                path: "data.latency_ms",
+                old_name: "duration_ms",
+            },
+        },
+        SchemaRevision {
+            version: V0_3,
+            event_type: Some("agent.session"),
+            change: SchemaChange::AddedEventType,
+        },
+    ];
+
+    fn current_event() -> OispEvent {
+        serde_json::from_value(json!({
+            "oisp_version": "0.1",
+            "event_id": "evt-1",
+            "event_type": "ai.response",
+            "ts": "2024-01-01T12:00:01Z",
+            "host": {"hostname": "build-01"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {
+                "request_id": "req-1",
+                "success": true,
+                "latency_ms": 812,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "name": "write_file",
+                    "arguments": "{\"path\":",
+                    "arguments_hash": "sha256:0011223344556677",
+                    "arguments_size": 2097152
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn keys(value: &Value) -> BTreeSet<&str> {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_downgrade_to_older_schema() {
+        let event = current_event();
+
+        let transform = SchemaTransform::with_history(V0_1, V0_3, V0_1, HISTORY).unwrap();
+        let old = transform.apply(&event).unwrap();
+        assert_eq!(old["oisp_version"], "0.1");
+        assert!(!keys(&old).contains("host"));
+        assert!(keys(&old).contains("source"));
+        assert_eq!(
+            keys(&old["data"]),
+            BTreeSet::from(["request_id", "success", "duration_ms", "tool_calls"])
+        );
+        assert_eq!(old["data"]["duration_ms"], 812);
+        assert_eq!(
+            keys(&old["data"]["tool_calls"][0]),
+            BTreeSet::from(["id", "name", "arguments", "arguments_hash"])
+        );
+
+        // Only changes after the target are undone
+        let transform = SchemaTransform::with_history(V0_2, V0_3, V0_1, HISTORY).unwrap();
+        let mid = transform.apply(&event).unwrap();
+        assert_eq!(mid["oisp_version"], "0.2");
+        assert_eq!(mid["host"]["hostname"], "build-01");
+        assert_eq!(mid["data"]["duration_ms"], 812);
+        assert_eq!(mid["data"]["tool_calls"][0]["arguments_size"], 2097152);
+    }
+
+    #[test]
+    fn test_downgrade_rejections() {
+        let transform = SchemaTransform::with_history(V0_2, V0_3, V0_1, HISTORY).unwrap();
+        let mut session = json!({"oisp_version": "0.3", "event_type": "agent.session"});
+        let err = transform.downgrade(&mut session).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "agent.session events do not exist in schema 0.2"
+        );
+
+        let err = SchemaTransform::with_history(SchemaVersion::new(0, 4), V0_3, V0_1, HISTORY)
+            .unwrap_err();
+        assert!(matches!(err, SchemaError::NewerThanCurrent { .. }));
+        let err = SchemaTransform::with_history(SchemaVersion::new(0, 0), V0_3, V0_1, HISTORY)
+            .unwrap_err();
+        assert!(matches!(err, SchemaError::TooOld { .. }));
+        assert!(matches!(
+            SchemaTransform::new("latest"),
+            Err(SchemaError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_current_schema_is_unchanged() {
+        let event = current_event();
+        let transform = SchemaTransform::new(crate::OISP_VERSION).unwrap();
+        assert_eq!(
+            transform.apply(&event).unwrap(),
+            serde_json::to_value(&event).unwrap()
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Durations between events
+//!
+//! Events carry a wall-clock `ts` and, when captured, a monotonic `ts_mono`.
+//! Wall-clock time can step backwards under NTP adjustment, so durations use
+//! `ts_mono` when both ends have one and fall back to `ts` otherwise. A
+//! duration that still comes out negative is clamped to zero and the skew is
+//! reported alongside it.
+
+use super::EventEnvelope;
+use chrono::{DateTime, Utc};
+
+/// Attribute set on an event whose duration was clamped to zero because its
+/// timestamp precedes the start's; holds the skew in milliseconds
+pub const CLOCK_SKEW_ATTR: &str = "clock.skew_ms";
+
+/// When an event happened, by both clocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventTime {
+    pub ts: DateTime<Utc>,
+    /// Monotonic capture time in nanoseconds; 0 is treated as absent
+    pub ts_mono: Option<u64>,
+}
+
+/// Time between two events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed {
+    pub ms: u64,
+    /// How far the end preceded the start, when it did; `ms` is 0 then
+    pub skew_ms: Option<u64>,
+}
+
+impl EventTime {
+    pub fn of(envelope: &EventEnvelope) -> Self {
+        Self {
+            ts: envelope.ts,
+            ts_mono: envelope.ts_mono,
+        }
+    }
+
+    /// Time from `start` to this event
+    pub fn since(&self, start: &EventTime) -> Elapsed {
+        let delta_ms = match (start.ts_mono, self.ts_mono) {
+            (Some(start), Some(end)) if start > 0 && end > 0 => {
+                (end as i128 - start as i128) / 1_000_000
+            }
+            _ => (self.ts - start.ts).num_milliseconds() as i128,
+        };
+        if delta_ms < 0 {
+            Elapsed {
+                ms: 0,
+                skew_ms: Some(delta_ms.unsigned_abs().min(u64::MAX as u128) as u64),
+            }
+        } else {
+            Elapsed {
+                ms: delta_ms.min(u64::MAX as i128) as u64,
+                skew_ms: None,
+            }
+        }
+    }
+}
+
+impl EventEnvelope {
+    /// Time from `start` to this event, recording any clock skew in
+    /// [`CLOCK_SKEW_ATTR`]
+    pub fn elapsed_since(&mut self, start: &EventTime) -> u64 {
+        let elapsed = EventTime::of(self).since(start);
+        if let Some(skew_ms) = elapsed.skew_ms {
+            self.attrs
+                .insert(CLOCK_SKEW_ATTR.to_string(), skew_ms.into());
+        }
+        elapsed.ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn at(ts: DateTime<Utc>, ts_mono: Option<u64>) -> EventTime {
+        EventTime { ts, ts_mono }
+    }
+
+    #[test]
+    fn test_monotonic_preferred_over_skewed_wall_clock() {
+        let now = Utc::now();
+        // NTP stepped the wall clock back 2s between request and response
+        let request = at(now, Some(10_000_000_000));
+        let response = at(now - Duration::seconds(2), Some(10_450_000_000));
+        assert_eq!(
+            response.since(&request),
+            Elapsed {
+                ms: 450,
+                skew_ms: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_wall_clock_fallback_clamps_skew() {
+        let now = Utc::now();
+        let request = at(now, Some(10_000_000_000));
+        let response = at(now - Duration::milliseconds(300), None);
+        assert_eq!(
+            response.since(&request),
+            Elapsed {
+                ms: 0,
+                skew_ms: Some(300)
+            }
+        );
+        assert_eq!(
+            at(now + Duration::milliseconds(80), Some(0)).since(&request),
+            Elapsed {
+                ms: 80,
+                skew_ms: None
+            }
+        );
+
+        let mut envelope = EventEnvelope::new("ai.response");
+        envelope.ts = now - Duration::milliseconds(300);
+        assert_eq!(envelope.elapsed_since(&request), 0);
+        assert_eq!(envelope.attrs[CLOCK_SKEW_ATTR], 300);
+    }
+}
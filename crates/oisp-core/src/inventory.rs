@@ -0,0 +1,280 @@
+//! AI inventory built from recorded events
+//!
+//! Summarizes which providers, models and applications were seen in a
+//! capture, with token totals and estimated cost per model. Entries are
+//! sorted by request count (descending) then name, so output is stable
+//! across runs and diffs cleanly.
+
+use crate::spec::DynamicProviderRegistry;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Structured inventory of AI usage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Requests per provider
+    pub providers: Vec<InventoryEntry>,
+    /// Requests, tokens and cost per model
+    pub models: Vec<ModelInventoryEntry>,
+    /// Requests per application (process name)
+    pub apps: Vec<InventoryEntry>,
+    /// Totals across all models
+    pub totals: InventoryTotals,
+}
+
+/// A named request counter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub name: String,
+    pub requests: u64,
+}
+
+/// Per-model usage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInventoryEntry {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated cost from spec bundle pricing (None if the model has no pricing)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// Totals across the whole capture
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+#[derive(Default)]
+struct ModelAccumulator {
+    provider: Option<String>,
+    requests: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl Inventory {
+    /// Build an inventory from serialized OISP events
+    ///
+    /// Request counts come from `ai.request` events; token usage comes from
+    /// `ai.response` events, attributed to the model of the matching request
+    /// (by `request_id`) when one was recorded.
+    pub fn from_events(events: &[Value], registry: &DynamicProviderRegistry) -> Self {
+        let mut providers: HashMap<String, u64> = HashMap::new();
+        let mut apps: HashMap<String, u64> = HashMap::new();
+        let mut models: HashMap<String, ModelAccumulator> = HashMap::new();
+        // request_id -> (provider, model)
+        let mut requests: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
+
+        for event in events {
+            if event.get("event_type").and_then(|v| v.as_str()) != Some("ai.request") {
+                continue;
+            }
+            let Some(data) = event.get("data") else {
+                continue;
+            };
+
+            let provider = provider_name(data);
+            let model = model_id(data);
+
+            if let Some(p) = &provider {
+                *providers.entry(p.clone()).or_default() += 1;
+            }
+            if let Some(m) = &model {
+                let acc = models.entry(m.clone()).or_default();
+                acc.requests += 1;
+                if acc.provider.is_none() {
+                    acc.provider = provider.clone();
+                }
+            }
+            if let Some(app) = event
+                .get("process")
+                .and_then(|p| p.get("name"))
+                .and_then(|n| n.as_str())
+            {
+                *apps.entry(app.to_string()).or_default() += 1;
+            }
+            if let Some(id) = data.get("request_id").and_then(|i| i.as_str()) {
+                requests.insert(id.to_string(), (provider, model));
+            }
+        }
+
+        for event in events {
+            if event.get("event_type").and_then(|v| v.as_str()) != Some("ai.response") {
+                continue;
+            }
+            let Some(data) = event.get("data") else {
+                continue;
+            };
+            let Some(usage) = data.get("usage") else {
+                continue;
+            };
+
+            let (req_provider, req_model) = data
+                .get("request_id")
+                .and_then(|i| i.as_str())
+                .and_then(|id| requests.get(id).cloned())
+                .unwrap_or_default();
+
+            let Some(model) = req_model.or_else(|| model_id(data)) else {
+                continue;
+            };
+
+            let acc = models.entry(model).or_default();
+            if acc.provider.is_none() {
+                acc.provider = req_provider.or_else(|| provider_name(data));
+            }
+            acc.input_tokens += usage
+                .get("prompt_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(0);
+            acc.output_tokens += usage
+                .get("completion_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(0);
+        }
+
+        let mut totals = InventoryTotals::default();
+        let mut model_entries: Vec<ModelInventoryEntry> = models
+            .into_iter()
+            .map(|(model, acc)| {
+                let estimated_cost_usd = acc
+                    .provider
+                    .as_deref()
+                    .and_then(|p| {
+                        registry.estimate_cost(p, &model, acc.input_tokens, acc.output_tokens)
+                    })
+                    .map(|(_, _, total)| round_usd(total));
+
+                totals.requests += acc.requests;
+                totals.input_tokens += acc.input_tokens;
+                totals.output_tokens += acc.output_tokens;
+                totals.estimated_cost_usd += estimated_cost_usd.unwrap_or(0.0);
+
+                ModelInventoryEntry {
+                    model,
+                    provider: acc.provider,
+                    requests: acc.requests,
+                    input_tokens: acc.input_tokens,
+                    output_tokens: acc.output_tokens,
+                    estimated_cost_usd,
+                }
+            })
+            .collect();
+        model_entries.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.model.cmp(&b.model)));
+        totals.estimated_cost_usd = round_usd(totals.estimated_cost_usd);
+
+        Self {
+            providers: sorted_entries(providers),
+            models: model_entries,
+            apps: sorted_entries(apps),
+            totals,
+        }
+    }
+}
+
+fn provider_name(data: &Value) -> Option<String> {
+    data.get("provider")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(String::from)
+}
+
+fn model_id(data: &Value) -> Option<String> {
+    data.get("model")
+        .and_then(|m| m.get("id"))
+        .and_then(|i| i.as_str())
+        .map(String::from)
+}
+
+/// Sort by count descending, then name ascending
+fn sorted_entries(counts: HashMap<String, u64>) -> Vec<InventoryEntry> {
+    let mut entries: Vec<InventoryEntry> = counts
+        .into_iter()
+        .map(|(name, requests)| InventoryEntry { name, requests })
+        .collect();
+    entries.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.name.cmp(&b.name)));
+    entries
+}
+
+/// Round to micro-dollars so float noise doesn't leak into output
+fn round_usd(value: f64) -> f64 {
+    (value * 1_000_000.0).round() / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::OispSpecBundle;
+    use std::sync::Arc;
+
+    const FIXTURE: &str = r#"{"event_type":"ai.request","process":{"pid":1,"name":"cursor"},"data":{"request_id":"r1","provider":{"name":"openai"},"model":{"id":"gpt-4o"}}}
+{"event_type":"ai.response","data":{"request_id":"r1","usage":{"prompt_tokens":1000,"completion_tokens":500}}}
+{"event_type":"ai.request","process":{"pid":2,"name":"python"},"data":{"request_id":"r2","provider":{"name":"anthropic"},"model":{"id":"claude-3-5-sonnet-20241022"}}}
+{"event_type":"ai.response","data":{"request_id":"r2","usage":{"prompt_tokens":2000,"completion_tokens":1000}}}
+{"event_type":"ai.request","process":{"pid":1,"name":"cursor"},"data":{"request_id":"r3","provider":{"name":"openai"},"model":{"id":"gpt-4o"}}}
+{"event_type":"ai.request","process":{"pid":3,"name":"ollama-cli"},"data":{"request_id":"r4","provider":{"name":"ollama"},"model":{"id":"llama3.2"}}}
+{"event_type":"process.exec","process":{"pid":4,"name":"bash"},"data":{}}"#;
+
+    fn fixture_events() -> Vec<Value> {
+        FIXTURE
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_inventory_json_matches_expected() {
+        let registry = DynamicProviderRegistry::new(Arc::new(OispSpecBundle::embedded()));
+        let inventory = Inventory::from_events(&fixture_events(), &registry);
+
+        let expected = serde_json::json!({
+            "providers": [
+                {"name": "openai", "requests": 2},
+                {"name": "anthropic", "requests": 1},
+                {"name": "ollama", "requests": 1}
+            ],
+            "models": [
+                {"model": "gpt-4o", "provider": "openai", "requests": 2,
+                 "input_tokens": 1000, "output_tokens": 500, "estimated_cost_usd": 0.0075},
+                {"model": "claude-3-5-sonnet-20241022", "provider": "anthropic", "requests": 1,
+                 "input_tokens": 2000, "output_tokens": 1000, "estimated_cost_usd": 0.021},
+                {"model": "llama3.2", "provider": "ollama", "requests": 1,
+                 "input_tokens": 0, "output_tokens": 0}
+            ],
+            "apps": [
+                {"name": "cursor", "requests": 2},
+                {"name": "ollama-cli", "requests": 1},
+                {"name": "python", "requests": 1}
+            ],
+            "totals": {
+                "requests": 4,
+                "input_tokens": 3000,
+                "output_tokens": 1500,
+                "estimated_cost_usd": 0.0285
+            }
+        });
+
+        assert_eq!(serde_json::to_value(&inventory).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_inventory_ordering_is_deterministic() {
+        let registry = DynamicProviderRegistry::new(Arc::new(OispSpecBundle::embedded()));
+        let events = fixture_events();
+        let mut reversed = events.clone();
+        reversed.reverse();
+
+        let a = serde_json::to_string(&Inventory::from_events(&events, &registry)).unwrap();
+        let b = serde_json::to_string(&Inventory::from_events(&reversed, &registry)).unwrap();
+        assert_eq!(a, b);
+    }
+}
@@ -11,13 +11,17 @@
 //! - **Actions**: Built-in action plugins (redaction)
 //! - **Policy**: Policy engine for security rules (block, redact, alert)
 //! - **Trace**: Event correlation and trace building
+//! - **Inventory**: Provider/model/app usage summaries from recorded events
 
 pub mod actions;
 pub mod app_registry;
 pub mod config;
+pub mod dead_letter;
 pub mod enrichers;
 pub mod events;
+pub mod inventory;
 pub mod metrics;
+pub mod monotonic;
 pub mod pipeline;
 pub mod plugins;
 pub mod policy;
@@ -25,37 +29,55 @@ pub mod providers;
 pub mod redaction;
 pub mod replay;
 pub mod spec;
+pub mod sslsniff;
 pub mod trace;
+pub mod watchdog;
+pub mod wire;
+pub mod wire_diagnostics;
 
 // Re-export commonly used types
-pub use actions::RedactionPlugin;
+pub use actions::{
+    AgentStepPlugin, BudgetAlertPlugin, EventLimitPlugin, LatencyPlugin, RedactionPlugin,
+    SessionPlugin,
+};
 pub use app_registry::{
     AppProfile, AppRegistry, AppRegistryError, LiveRegistry, MatchResult, REFRESH_INTERVAL_SECS,
     REGISTRY_URL,
 };
 pub use config::{
-    spawn_sighup_reload_handler, CaptureSettings, ConfigError, ConfigLoader, ConfigResult,
-    CorrelationSettings, ExportSettings, JsonlExportConfig, KafkaExportConfig, OtlpExportConfig,
-    OximyExportConfig, RedactionSettings, SensorConfig, SensorSettings, SharedConfig, WebSettings,
-    WebSocketExportConfig, WebhookExportConfig,
+    spawn_sighup_reload_handler, BudgetSettings, CaptureSettings, ConfigError, ConfigLoader,
+    ConfigResult, CorrelationSettings, DeadLetterConfig, ExportSettings, JsonlExportConfig,
+    KafkaExportConfig, KubernetesSettings, LatencySettings, OtlpExportConfig, OximyExportConfig,
+    ProcessResourceSettings, ProviderSettings, RedactionSettings, SensorConfig, SensorSettings,
+    SharedConfig, WebAuthSettings, WebSettings, WebSocketExportConfig, WebTlsSettings,
+    WebhookExportConfig,
+};
+pub use dead_letter::{DeadLetterRecord, DeadLetterSink, ExportRetry};
+pub use enrichers::{
+    AppEnricher, HostEnricher, KubernetesEnricher, ProcessTreeEnricher, ResourceEnricher,
+    SourceEnricher,
 };
-pub use enrichers::{AppEnricher, HostEnricher, ProcessTreeEnricher};
 pub use events::{
     Actor, AppInfo, AppTier, Confidence, EventEnvelope, EventType, Host, OispEvent, ProcessInfo,
     Source,
 };
+pub use inventory::Inventory;
 pub use metrics::{create_metrics, MetricsCollector, SharedMetrics};
-pub use pipeline::{Pipeline, PipelineConfig};
+pub use monotonic::{CaptureClock, MonoClock, TsMonoSource};
+pub use pipeline::{ChannelPolicy, EventIds, Pipeline, PipelineConfig, SchemaValidation};
 pub use plugins::{
-    ActionPlugin, CapturePlugin, DecodePlugin, EnrichPlugin, ExportPlugin, Plugin, PluginInfo,
+    ActionPlugin, CaptureError, CaptureErrorKind, CaptureErrorSender, CapturePlugin, DecodePlugin,
+    EnrichPlugin, ExportPlugin, Plugin, PluginInfo,
 };
 pub use providers::{Provider, ProviderRegistry};
 pub use replay::{EventReplay, ReplayConfig};
 pub use spec::{
-    bundle_refresh_interval, bundle_url, DynamicProviderRegistry, OispSpecBundle, SpecLoader,
+    air_gapped, bundle_refresh_interval, bundle_url, set_air_gapped, validate_event,
+    DynamicProviderRegistry, EventValidator, OispSpecBundle, SchemaViolation, SpecLoader,
     DEFAULT_BUNDLE_URL,
 };
 pub use trace::{AgentTrace, CorrelationConfig, Span, SpanKind};
+pub use watchdog::WatchdogConfig;
 
 // Policy engine exports
 pub use policy::{
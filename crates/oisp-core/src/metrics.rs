@@ -2,10 +2,15 @@
 //!
 //! Provides metrics collection for monitoring sensor health and performance.
 
-use std::collections::HashMap;
+use crate::events::AiResponseData;
+use crate::plugins::{CaptureError, CapturePlugin, CapturePluginStats, RawEventKind};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Global metrics collector
 #[derive(Debug)]
@@ -18,6 +23,62 @@ pub struct MetricsCollector {
     pub pipeline: PipelineMetrics,
     /// Process resource metrics (pid -> ProcessMetrics)
     pub processes: parking_lot::RwLock<HashMap<u32, ProcessMetrics>>,
+    /// Per-provider latency and error rates, from decoded AI responses
+    pub providers: ProviderHealth,
+    /// Recent processing time of each pipeline stage
+    pub stages: StageLatency,
+    /// Most recent capture errors (oldest first)
+    capture_errors: parking_lot::RwLock<VecDeque<CaptureError>>,
+    /// Capture plugins whose stats are reported per plugin
+    capture_plugins: parking_lot::RwLock<CapturePlugins>,
+    /// Decode failures by (reason, provider)
+    decode_failures: parking_lot::RwLock<HashMap<(String, String), u64>>,
+    /// Export operations in progress, by exporter
+    export_in_flight: parking_lot::RwLock<BTreeMap<String, Arc<AtomicU64>>>,
+    /// Per-interval counter increments
+    rollup: parking_lot::Mutex<Rollup>,
+}
+
+/// Shared handle to a capture plugin owned by the pipeline
+pub type SharedCapturePlugin = Arc<tokio::sync::RwLock<Box<dyn CapturePlugin>>>;
+
+#[derive(Clone, Default)]
+struct CapturePlugins(Vec<SharedCapturePlugin>);
+
+impl fmt::Debug for CapturePlugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CapturePlugins({})", self.0.len())
+    }
+}
+
+/// Number of capture errors kept for diagnostics
+const MAX_CAPTURE_ERRORS: usize = 50;
+
+/// How often the pipeline rolls counters up into the series
+pub const ROLLUP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Buckets kept in the series (one hour at [`ROLLUP_INTERVAL`])
+pub const MAX_SERIES_BUCKETS: usize = 360;
+
+/// Counter values by name, e.g. `pipeline.events_exported`
+pub type CounterValues = BTreeMap<&'static str, u64>;
+
+/// Counter increments over one rollup interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeriesBucket {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub counters: CounterValues,
+}
+
+#[derive(Debug)]
+struct Rollup {
+    /// Counter values at the last rollup
+    last: CounterValues,
+    /// Increments since the last rollup that a reset took out of the counters
+    pending: CounterValues,
+    last_at: DateTime<Utc>,
+    buckets: VecDeque<SeriesBucket>,
 }
 
 impl Default for MetricsCollector {
@@ -33,7 +94,220 @@ impl MetricsCollector {
             capture: CaptureMetrics::default(),
             pipeline: PipelineMetrics::default(),
             processes: parking_lot::RwLock::new(HashMap::new()),
+            providers: ProviderHealth::default(),
+            stages: StageLatency::default(),
+            capture_errors: parking_lot::RwLock::new(VecDeque::new()),
+            capture_plugins: parking_lot::RwLock::new(CapturePlugins::default()),
+            decode_failures: parking_lot::RwLock::new(HashMap::new()),
+            export_in_flight: parking_lot::RwLock::new(BTreeMap::new()),
+            rollup: parking_lot::Mutex::new(Rollup {
+                last: CounterValues::new(),
+                pending: CounterValues::new(),
+                last_at: Utc::now(),
+                buckets: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The monotonic counters covered by [`reset`](Self::reset) and the series
+    fn counters(&self) -> [(&'static str, &AtomicU64); 15] {
+        [
+            ("capture.ssl_events", &self.capture.ssl_events),
+            ("capture.network_events", &self.capture.network_events),
+            ("capture.process_events", &self.capture.process_events),
+            ("capture.file_events", &self.capture.file_events),
+            ("capture.bytes_captured", &self.capture.bytes_captured),
+            ("capture.errors", &self.capture.errors),
+            ("capture.dropped", &self.capture.dropped),
+            ("capture.ringbuf_polls", &self.capture.ringbuf_polls),
+            ("capture.restarts", &self.capture.restarts),
+            ("pipeline.events_processed", &self.pipeline.events_processed),
+            ("pipeline.events_exported", &self.pipeline.events_exported),
+            ("pipeline.ai_events", &self.pipeline.ai_events),
+            ("pipeline.events_dropped", &self.pipeline.events_dropped),
+            ("pipeline.events_invalid", &self.pipeline.events_invalid),
+            (
+                "pipeline.events_dead_lettered",
+                &self.pipeline.events_dead_lettered,
+            ),
+        ]
+    }
+
+    /// Current value of each counter
+    pub fn counter_values(&self) -> CounterValues {
+        self.counters()
+            .into_iter()
+            .map(|(name, counter)| (name, counter.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Set every counter back to zero, returning the values they had
+    ///
+    /// Each counter is swapped atomically, so an increment racing with the
+    /// reset lands either in the returned value or in the new count, never
+    /// in neither. The counters are not swapped all at once, so the returned
+    /// values are not a single point-in-time snapshot. Increments not yet
+    /// rolled up still go into the next series bucket. Gauges, latency
+    /// windows and the recent error list are not affected.
+    pub fn reset(&self) -> CounterValues {
+        let mut rollup = self.rollup.lock();
+        let mut taken = CounterValues::new();
+        for (name, counter) in self.counters() {
+            let value = counter.swap(0, Ordering::Relaxed);
+            let last = rollup.last.remove(name).unwrap_or(0);
+            *rollup.pending.entry(name).or_default() += value.saturating_sub(last);
+            taken.insert(name, value);
+        }
+        taken
+    }
+
+    /// Close the current series bucket with the increments since the last
+    /// rollup
+    pub fn rollup(&self) {
+        self.rollup_at(Utc::now());
+    }
+
+    fn rollup_at(&self, now: DateTime<Utc>) {
+        let mut rollup = self.rollup.lock();
+        let pending = std::mem::take(&mut rollup.pending);
+        let mut counters = CounterValues::new();
+        for (name, counter) in self.counters() {
+            let value = counter.load(Ordering::Relaxed);
+            let last = rollup.last.insert(name, value).unwrap_or(0);
+            let increment = value.saturating_sub(last) + pending.get(name).copied().unwrap_or(0);
+            counters.insert(name, increment);
+        }
+
+        let bucket = SeriesBucket {
+            start: rollup.last_at,
+            end: now,
+            counters,
+        };
+        rollup.last_at = now;
+        if rollup.buckets.len() >= MAX_SERIES_BUCKETS {
+            rollup.buckets.pop_front();
+        }
+        rollup.buckets.push_back(bucket);
+    }
+
+    /// Series buckets that ended within `window`, oldest first
+    pub fn series(&self, window: Duration) -> Vec<SeriesBucket> {
+        self.series_at(window, Utc::now())
+    }
+
+    fn series_at(&self, window: Duration, now: DateTime<Utc>) -> Vec<SeriesBucket> {
+        let since = chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| now.checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        self.rollup
+            .lock()
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.end > since)
+            .cloned()
+            .collect()
+    }
+
+    /// Record a capture error (counted and kept for diagnostics)
+    pub fn record_capture_error(&self, error: CaptureError) {
+        self.capture.errors.fetch_add(1, Ordering::Relaxed);
+        let mut errors = self.capture_errors.write();
+        if errors.len() >= MAX_CAPTURE_ERRORS {
+            errors.pop_front();
         }
+        errors.push_back(error);
+    }
+
+    /// Count a raw event received from a capture plugin
+    pub fn record_raw_event(&self, kind: &RawEventKind, bytes: usize) {
+        let counter = match kind {
+            RawEventKind::SslWrite | RawEventKind::SslRead => &self.capture.ssl_events,
+            RawEventKind::ProcessExec | RawEventKind::ProcessExit | RawEventKind::ProcessFork => {
+                &self.capture.process_events
+            }
+            RawEventKind::FileOpen
+            | RawEventKind::FileRead
+            | RawEventKind::FileWrite
+            | RawEventKind::FileClose => &self.capture.file_events,
+            RawEventKind::NetworkConnect
+            | RawEventKind::NetworkAccept
+            | RawEventKind::NetworkSend
+            | RawEventKind::NetworkRecv
+            | RawEventKind::DnsQuery => &self.capture.network_events,
+            RawEventKind::Other(_) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.capture
+            .bytes_captured
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Raw events captured so far, across all kinds
+    pub fn events_captured(&self) -> u64 {
+        self.capture.ssl_events.load(Ordering::Relaxed)
+            + self.capture.network_events.load(Ordering::Relaxed)
+            + self.capture.process_events.load(Ordering::Relaxed)
+            + self.capture.file_events.load(Ordering::Relaxed)
+    }
+
+    /// Count a decode failure
+    pub fn record_decode_failure(&self, reason: &str, provider: &str) {
+        *self
+            .decode_failures
+            .write()
+            .entry((reason.to_string(), provider.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Decode failures counted so far
+    pub fn decode_failures(&self) -> u64 {
+        self.decode_failures.read().values().sum()
+    }
+
+    /// In-flight gauge for an exporter, kept up to date by the exporter's
+    /// concurrency limit
+    pub fn export_in_flight_gauge(&self, exporter: &str) -> Arc<AtomicU64> {
+        self.export_in_flight
+            .write()
+            .entry(exporter.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Export operations currently in progress, by exporter
+    pub fn exports_in_flight(&self) -> BTreeMap<String, u64> {
+        self.export_in_flight
+            .read()
+            .iter()
+            .map(|(name, gauge)| (name.clone(), gauge.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Most recent capture errors, oldest first
+    pub fn capture_errors(&self) -> Vec<CaptureError> {
+        self.capture_errors.read().iter().cloned().collect()
+    }
+
+    /// Track a capture plugin so its stats are reported per plugin
+    pub fn register_capture_plugin(&self, plugin: SharedCapturePlugin) {
+        self.capture_plugins.write().0.push(plugin);
+    }
+
+    /// Stats of each registered capture plugin, in registration order
+    pub async fn capture_plugin_stats(&self) -> Vec<CapturePluginStats> {
+        let plugins = self.capture_plugins.read().clone();
+        let mut stats = Vec::with_capacity(plugins.0.len());
+        for plugin in &plugins.0 {
+            let plugin = plugin.read().await;
+            stats.push(CapturePluginStats {
+                name: plugin.name().to_string(),
+                available: plugin.is_available(),
+                running: plugin.is_running(),
+                stats: plugin.stats(),
+            });
+        }
+        stats
     }
 
     /// Get uptime in seconds
@@ -87,6 +361,15 @@ impl MetricsCollector {
             self.capture.errors.load(Ordering::Relaxed)
         ));
 
+        output.push_str(
+            "# HELP oisp_capture_restarts_total Capture plugins restarted after stalling\n",
+        );
+        output.push_str("# TYPE oisp_capture_restarts_total counter\n");
+        output.push_str(&format!(
+            "oisp_capture_restarts_total {}\n\n",
+            self.capture.restarts.load(Ordering::Relaxed)
+        ));
+
         output.push_str("# HELP oisp_capture_dropped_total Total events dropped\n");
         output.push_str("# TYPE oisp_capture_dropped_total counter\n");
         output.push_str(&format!(
@@ -118,6 +401,85 @@ impl MetricsCollector {
             self.pipeline.ai_events.load(Ordering::Relaxed)
         ));
 
+        output.push_str(
+            "# HELP oisp_pipeline_events_dropped_total Raw events dropped because the pipeline buffer was full\n",
+        );
+        output.push_str("# TYPE oisp_pipeline_events_dropped_total counter\n");
+        output.push_str(&format!(
+            "oisp_pipeline_events_dropped_total {}\n\n",
+            self.pipeline.events_dropped.load(Ordering::Relaxed)
+        ));
+
+        output.push_str(
+            "# HELP oisp_pipeline_events_invalid_total Events that failed schema validation\n",
+        );
+        output.push_str("# TYPE oisp_pipeline_events_invalid_total counter\n");
+        output.push_str(&format!(
+            "oisp_pipeline_events_invalid_total {}\n\n",
+            self.pipeline.events_invalid.load(Ordering::Relaxed)
+        ));
+
+        output.push_str(
+            "# HELP oisp_pipeline_events_dead_lettered_total Events written to the dead-letter file after every exporter failed\n",
+        );
+        output.push_str("# TYPE oisp_pipeline_events_dead_lettered_total counter\n");
+        output.push_str(&format!(
+            "oisp_pipeline_events_dead_lettered_total {}\n\n",
+            self.pipeline.events_dead_lettered.load(Ordering::Relaxed)
+        ));
+
+        let stages = self.stages.snapshot();
+        if !stages.is_empty() {
+            output.push_str(
+                "# HELP oisp_pipeline_stage_latency_microseconds Recent processing time per pipeline stage\n",
+            );
+            output.push_str("# TYPE oisp_pipeline_stage_latency_microseconds gauge\n");
+            for stage in &stages {
+                for (quantile, value) in [("0.5", stage.p50_us), ("0.99", stage.p99_us)] {
+                    output.push_str(&format!(
+                        "oisp_pipeline_stage_latency_microseconds{{stage=\"{}\",quantile=\"{}\"}} {}\n",
+                        stage.stage.as_str(),
+                        quantile,
+                        value
+                    ));
+                }
+            }
+            output.push('\n');
+        }
+
+        let decode_failures = self.decode_failures.read();
+        if !decode_failures.is_empty() {
+            output.push_str(
+                "# HELP oisp_decode_failures_total Captured exchanges that failed to decode\n",
+            );
+            output.push_str("# TYPE oisp_decode_failures_total counter\n");
+            let mut classes: Vec<_> = decode_failures.iter().collect();
+            classes.sort();
+            for ((reason, provider), count) in classes {
+                output.push_str(&format!(
+                    "oisp_decode_failures_total{{reason=\"{}\",provider=\"{}\"}} {}\n",
+                    reason, provider, count
+                ));
+            }
+            output.push('\n');
+        }
+        drop(decode_failures);
+
+        let in_flight = self.exports_in_flight();
+        if !in_flight.is_empty() {
+            output.push_str(
+                "# HELP oisp_export_in_flight Export operations in progress per exporter\n",
+            );
+            output.push_str("# TYPE oisp_export_in_flight gauge\n");
+            for (exporter, count) in &in_flight {
+                output.push_str(&format!(
+                    "oisp_export_in_flight{{exporter=\"{}\"}} {}\n",
+                    exporter, count
+                ));
+            }
+            output.push('\n');
+        }
+
         // Ring buffer metrics
         output.push_str("# HELP oisp_ringbuf_polls_total Total ring buffer poll operations\n");
         output.push_str("# TYPE oisp_ringbuf_polls_total counter\n");
@@ -126,6 +488,58 @@ impl MetricsCollector {
             self.capture.ringbuf_polls.load(Ordering::Relaxed)
         ));
 
+        // Provider health (rolling window)
+        let providers = self.providers.snapshot();
+        if !providers.is_empty() {
+            output.push_str(
+                "# HELP oisp_provider_requests AI responses per provider in the rolling window\n",
+            );
+            output.push_str("# TYPE oisp_provider_requests gauge\n");
+            for p in &providers {
+                output.push_str(&format!(
+                    "oisp_provider_requests{{provider=\"{}\"}} {}\n",
+                    p.provider, p.requests
+                ));
+            }
+            output.push('\n');
+
+            output.push_str(
+                "# HELP oisp_provider_errors AI error responses per provider in the rolling window\n",
+            );
+            output.push_str("# TYPE oisp_provider_errors gauge\n");
+            for p in &providers {
+                output.push_str(&format!(
+                    "oisp_provider_errors{{provider=\"{}\",class=\"client\"}} {}\n",
+                    p.provider, p.client_errors
+                ));
+                output.push_str(&format!(
+                    "oisp_provider_errors{{provider=\"{}\",class=\"provider\"}} {}\n",
+                    p.provider, p.provider_errors
+                ));
+            }
+            output.push('\n');
+
+            output.push_str(
+                "# HELP oisp_provider_latency_ms AI response latency per provider in the rolling window\n",
+            );
+            output.push_str("# TYPE oisp_provider_latency_ms gauge\n");
+            for p in &providers {
+                for (quantile, value) in [
+                    ("0.5", p.latency_p50_ms),
+                    ("0.95", p.latency_p95_ms),
+                    ("0.99", p.latency_p99_ms),
+                ] {
+                    if let Some(value) = value {
+                        output.push_str(&format!(
+                            "oisp_provider_latency_ms{{provider=\"{}\",quantile=\"{}\"}} {}\n",
+                            p.provider, quantile, value
+                        ));
+                    }
+                }
+            }
+            output.push('\n');
+        }
+
         // Process metrics
         let processes = self.processes.read();
         if !processes.is_empty() {
@@ -197,13 +611,22 @@ impl MetricsCollector {
                 "errors": self.capture.errors.load(Ordering::Relaxed),
                 "dropped": self.capture.dropped.load(Ordering::Relaxed),
                 "ringbuf_polls": self.capture.ringbuf_polls.load(Ordering::Relaxed),
+                "restarts": self.capture.restarts.load(Ordering::Relaxed),
+                "recent_errors": self.capture_errors(),
             },
             "pipeline": {
                 "events_processed": self.pipeline.events_processed.load(Ordering::Relaxed),
                 "events_exported": self.pipeline.events_exported.load(Ordering::Relaxed),
                 "ai_events": self.pipeline.ai_events.load(Ordering::Relaxed),
+                "events_dropped": self.pipeline.events_dropped.load(Ordering::Relaxed),
+                "events_invalid": self.pipeline.events_invalid.load(Ordering::Relaxed),
+                "events_dead_lettered": self.pipeline.events_dead_lettered.load(Ordering::Relaxed),
+                "decode_failures": self.decode_failures(),
+                "stage_latency": self.stages.snapshot(),
+                "exports_in_flight": self.exports_in_flight(),
             },
             "processes": process_metrics,
+            "providers": self.providers.snapshot(),
         })
     }
 
@@ -271,6 +694,8 @@ pub struct CaptureMetrics {
     pub errors: AtomicU64,
     pub dropped: AtomicU64,
     pub ringbuf_polls: AtomicU64,
+    /// Capture plugins restarted by the watchdog after stalling
+    pub restarts: AtomicU64,
 }
 
 /// Pipeline-related metrics
@@ -279,6 +704,256 @@ pub struct PipelineMetrics {
     pub events_processed: AtomicU64,
     pub events_exported: AtomicU64,
     pub ai_events: AtomicU64,
+    /// Raw events discarded because the pipeline buffer was full
+    pub events_dropped: AtomicU64,
+    /// Events that failed schema validation
+    pub events_invalid: AtomicU64,
+    /// Events written to the dead-letter file after every exporter failed
+    pub events_dead_lettered: AtomicU64,
+}
+
+/// A stage of the event pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStage {
+    Decode,
+    Enrich,
+    Action,
+    Export,
+}
+
+impl PipelineStage {
+    /// All stages, in pipeline order
+    pub const ALL: [PipelineStage; 4] = [
+        PipelineStage::Decode,
+        PipelineStage::Enrich,
+        PipelineStage::Action,
+        PipelineStage::Export,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PipelineStage::Decode => "decode",
+            PipelineStage::Enrich => "enrich",
+            PipelineStage::Action => "action",
+            PipelineStage::Export => "export",
+        }
+    }
+}
+
+/// Samples kept per stage; percentiles cover the most recent ones
+const MAX_STAGE_SAMPLES: usize = 1024;
+
+/// Recent processing times of each pipeline stage
+#[derive(Debug, Default)]
+pub struct StageLatency {
+    /// Microseconds per sample, oldest first
+    samples: parking_lot::Mutex<HashMap<PipelineStage, VecDeque<u64>>>,
+}
+
+impl StageLatency {
+    /// Record how long one pass through a stage took
+    pub fn record(&self, stage: PipelineStage, elapsed: Duration) {
+        let mut samples = self.samples.lock();
+        let stage_samples = samples.entry(stage).or_default();
+        if stage_samples.len() >= MAX_STAGE_SAMPLES {
+            stage_samples.pop_front();
+        }
+        stage_samples.push_back(elapsed.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    /// Percentiles of each stage that has samples, in pipeline order
+    pub fn snapshot(&self) -> Vec<StageLatencySnapshot> {
+        let samples = self.samples.lock();
+        PipelineStage::ALL
+            .iter()
+            .filter_map(|stage| {
+                let mut latencies: Vec<u64> = samples.get(stage)?.iter().copied().collect();
+                if latencies.is_empty() {
+                    return None;
+                }
+                latencies.sort_unstable();
+                Some(StageLatencySnapshot {
+                    stage: *stage,
+                    samples: latencies.len(),
+                    p50_us: percentile(&latencies, 50.0).unwrap_or_default(),
+                    p99_us: percentile(&latencies, 99.0).unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Latency percentiles of one pipeline stage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageLatencySnapshot {
+    pub stage: PipelineStage,
+    pub samples: usize,
+    pub p50_us: u64,
+    pub p99_us: u64,
+}
+
+/// Default rolling window for provider health
+const PROVIDER_HEALTH_WINDOW: Duration = Duration::from_secs(300);
+
+/// Samples kept per provider, bounding memory under heavy traffic
+const MAX_PROVIDER_SAMPLES: usize = 10_000;
+
+/// How an AI response ended, by HTTP status class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResponseOutcome {
+    Success,
+    /// 4xx: the request was rejected (auth, quota, bad input)
+    ClientError,
+    /// 5xx: the provider failed
+    ProviderError,
+}
+
+impl ResponseOutcome {
+    /// Responses without a status code count toward volume and latency only
+    fn from_status(status: Option<u16>) -> Self {
+        match status {
+            Some(400..=499) => Self::ClientError,
+            Some(500..=599) => Self::ProviderError,
+            _ => Self::Success,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ResponseSample {
+    at: Instant,
+    latency_ms: Option<u64>,
+    outcome: ResponseOutcome,
+}
+
+/// Per-provider request volume, error rates and latency over a rolling window
+///
+/// Fed from decoded `ai.response` events, so it reflects what providers
+/// returned rather than sensor-side processing time.
+#[derive(Debug)]
+pub struct ProviderHealth {
+    window: Duration,
+    samples: parking_lot::RwLock<HashMap<String, VecDeque<ResponseSample>>>,
+}
+
+impl Default for ProviderHealth {
+    fn default() -> Self {
+        Self::new(PROVIDER_HEALTH_WINDOW)
+    }
+}
+
+impl ProviderHealth {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: parking_lot::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a decoded AI response
+    pub fn record_response(&self, response: &AiResponseData) {
+        let provider = response
+            .provider
+            .as_ref()
+            .map(|p| p.name.as_str())
+            .unwrap_or("unknown");
+        self.record_at(
+            provider,
+            response.latency_ms,
+            response.status_code,
+            Instant::now(),
+        );
+    }
+
+    fn record_at(&self, provider: &str, latency_ms: Option<u64>, status: Option<u16>, at: Instant) {
+        let mut samples = self.samples.write();
+        let entries = samples.entry(provider.to_string()).or_default();
+        if entries.len() >= MAX_PROVIDER_SAMPLES {
+            entries.pop_front();
+        }
+        entries.push_back(ResponseSample {
+            at,
+            latency_ms,
+            outcome: ResponseOutcome::from_status(status),
+        });
+    }
+
+    /// Current health per provider, sorted by provider name
+    pub fn snapshot(&self) -> Vec<ProviderHealthSnapshot> {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Vec<ProviderHealthSnapshot> {
+        let mut samples = self.samples.write();
+
+        // Expire samples that fell out of the window
+        for entries in samples.values_mut() {
+            while entries
+                .front()
+                .is_some_and(|s| now.saturating_duration_since(s.at) > self.window)
+            {
+                entries.pop_front();
+            }
+        }
+        samples.retain(|_, entries| !entries.is_empty());
+
+        let mut snapshots: Vec<ProviderHealthSnapshot> = samples
+            .iter()
+            .map(|(provider, entries)| ProviderHealthSnapshot::from_samples(provider, entries))
+            .collect();
+        snapshots.sort_by(|a, b| a.provider.cmp(&b.provider));
+        snapshots
+    }
+}
+
+/// Health of one provider over the rolling window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderHealthSnapshot {
+    pub provider: String,
+    pub requests: u64,
+    /// 4xx responses
+    pub client_errors: u64,
+    /// 5xx responses
+    pub provider_errors: u64,
+    pub client_error_rate: f64,
+    pub provider_error_rate: f64,
+    pub latency_p50_ms: Option<u64>,
+    pub latency_p95_ms: Option<u64>,
+    pub latency_p99_ms: Option<u64>,
+}
+
+impl ProviderHealthSnapshot {
+    fn from_samples(provider: &str, samples: &VecDeque<ResponseSample>) -> Self {
+        let requests = samples.len() as u64;
+        let count = |outcome| samples.iter().filter(|s| s.outcome == outcome).count() as u64;
+        let client_errors = count(ResponseOutcome::ClientError);
+        let provider_errors = count(ResponseOutcome::ProviderError);
+
+        let mut latencies: Vec<u64> = samples.iter().filter_map(|s| s.latency_ms).collect();
+        latencies.sort_unstable();
+
+        Self {
+            provider: provider.to_string(),
+            requests,
+            client_errors,
+            provider_errors,
+            client_error_rate: client_errors as f64 / requests as f64,
+            provider_error_rate: provider_errors as f64 / requests as f64,
+            latency_p50_ms: percentile(&latencies, 50.0),
+            latency_p95_ms: percentile(&latencies, 95.0),
+            latency_p99_ms: percentile(&latencies, 99.0),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], pct: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
 }
 
 /// Per-process resource metrics
@@ -426,3 +1101,219 @@ pub type SharedMetrics = Arc<MetricsCollector>;
 pub fn create_metrics() -> SharedMetrics {
     Arc::new(MetricsCollector::new())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_health_percentiles_and_error_classes() {
+        let health = ProviderHealth::default();
+        let now = Instant::now();
+
+        // 90 fast successes, 6 slow successes, 3 rate limits, 1 server error
+        for i in 0..90 {
+            health.record_at("openai", Some(100 + i), Some(200), now);
+        }
+        for _ in 0..6 {
+            health.record_at("openai", Some(5_000), Some(200), now);
+        }
+        for _ in 0..3 {
+            health.record_at("openai", Some(50), Some(429), now);
+        }
+        health.record_at("openai", Some(30_000), Some(503), now);
+        health.record_at("anthropic", Some(800), Some(200), now);
+
+        let snapshot = health.snapshot_at(now);
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].provider, "anthropic");
+        assert_eq!(snapshot[0].latency_p99_ms, Some(800));
+
+        let openai = &snapshot[1];
+        assert_eq!(openai.requests, 100);
+        assert_eq!(openai.client_errors, 3);
+        assert_eq!(openai.provider_errors, 1);
+        assert!((openai.client_error_rate - 0.03).abs() < f64::EPSILON);
+        assert!((openai.provider_error_rate - 0.01).abs() < f64::EPSILON);
+        // Sorted: 3x50, 100..=189, 6x5000, 30000
+        assert_eq!(openai.latency_p50_ms, Some(146));
+        assert_eq!(openai.latency_p95_ms, Some(5_000));
+        assert_eq!(openai.latency_p99_ms, Some(5_000));
+    }
+
+    #[test]
+    fn test_provider_health_rolling_window() {
+        let health = ProviderHealth::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        health.record_at("openai", Some(100), Some(500), start);
+        health.record_at(
+            "openai",
+            Some(200),
+            Some(200),
+            start + Duration::from_secs(45),
+        );
+
+        let snapshot = health.snapshot_at(start + Duration::from_secs(90));
+        assert_eq!(snapshot[0].requests, 1);
+        assert_eq!(snapshot[0].provider_errors, 0);
+        assert_eq!(snapshot[0].latency_p50_ms, Some(200));
+
+        assert!(health
+            .snapshot_at(start + Duration::from_secs(200))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_provider_health_in_prometheus_and_json() {
+        let metrics = MetricsCollector::new();
+        metrics
+            .providers
+            .record_at("openai", Some(120), Some(502), Instant::now());
+
+        let prom = metrics.to_prometheus();
+        assert!(prom.contains("oisp_provider_requests{provider=\"openai\"} 1"));
+        assert!(prom.contains("oisp_provider_errors{provider=\"openai\",class=\"provider\"} 1"));
+        assert!(prom.contains("oisp_provider_latency_ms{provider=\"openai\",quantile=\"0.5\"} 120"));
+
+        let json = metrics.to_json();
+        assert_eq!(json["providers"][0]["provider"], "openai");
+        assert_eq!(json["providers"][0]["provider_errors"], 1);
+    }
+
+    #[test]
+    fn test_stage_latency_percentiles_keep_recent_samples() {
+        let metrics = MetricsCollector::new();
+        for us in 1..=100 {
+            metrics
+                .stages
+                .record(PipelineStage::Decode, Duration::from_micros(us));
+        }
+        metrics
+            .stages
+            .record(PipelineStage::Export, Duration::from_millis(3));
+
+        let snapshot = metrics.stages.snapshot();
+        assert_eq!(
+            snapshot,
+            vec![
+                StageLatencySnapshot {
+                    stage: PipelineStage::Decode,
+                    samples: 100,
+                    p50_us: 50,
+                    p99_us: 99,
+                },
+                StageLatencySnapshot {
+                    stage: PipelineStage::Export,
+                    samples: 1,
+                    p50_us: 3_000,
+                    p99_us: 3_000,
+                },
+            ]
+        );
+
+        for _ in 0..MAX_STAGE_SAMPLES {
+            metrics
+                .stages
+                .record(PipelineStage::Decode, Duration::from_micros(7));
+        }
+        assert_eq!(metrics.stages.snapshot()[0].p99_us, 7);
+
+        let prom = metrics.to_prometheus();
+        assert!(prom.contains(
+            "oisp_pipeline_stage_latency_microseconds{stage=\"export\",quantile=\"0.99\"} 3000"
+        ));
+        assert_eq!(
+            metrics.to_json()["pipeline"]["stage_latency"][1]["stage"],
+            "export"
+        );
+    }
+
+    #[test]
+    fn test_raw_events_counted_by_kind() {
+        let metrics = MetricsCollector::new();
+        metrics.record_raw_event(&RawEventKind::SslWrite, 100);
+        metrics.record_raw_event(&RawEventKind::SslRead, 20);
+        metrics.record_raw_event(&RawEventKind::ProcessExec, 0);
+        metrics.record_raw_event(&RawEventKind::Other("custom".into()), 5);
+
+        assert_eq!(metrics.capture.ssl_events.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.capture.process_events.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.capture.bytes_captured.load(Ordering::Relaxed), 120);
+        assert_eq!(metrics.events_captured(), 3);
+    }
+
+    #[test]
+    fn test_reset_returns_counts_and_loses_no_increments() {
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics
+            .pipeline
+            .events_exported
+            .fetch_add(5, Ordering::Relaxed);
+
+        let taken = metrics.reset();
+        assert_eq!(taken["pipeline.events_exported"], 5);
+        assert_eq!(taken["capture.ssl_events"], 0);
+        assert_eq!(metrics.counter_values()["pipeline.events_exported"], 0);
+
+        // Increments racing with resets end up in exactly one of them
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let metrics = metrics.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        metrics.pipeline.ai_events.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        let mut total = 0;
+        while writers.iter().any(|w| !w.is_finished()) {
+            total += metrics.reset()["pipeline.ai_events"];
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        total += metrics.reset()["pipeline.ai_events"];
+        assert_eq!(total, 40_000);
+    }
+
+    #[test]
+    fn test_series_buckets_hold_increments_per_interval() {
+        let metrics = MetricsCollector::new();
+        let t0 = metrics.rollup.lock().last_at;
+        let at = |secs| t0 + chrono::Duration::seconds(secs);
+
+        metrics.capture.ssl_events.fetch_add(3, Ordering::Relaxed);
+        metrics.rollup_at(at(10));
+        metrics.capture.ssl_events.fetch_add(4, Ordering::Relaxed);
+        // A reset between rollups still counts toward the bucket
+        assert_eq!(metrics.reset()["capture.ssl_events"], 7);
+        metrics.capture.ssl_events.fetch_add(1, Ordering::Relaxed);
+        metrics.rollup_at(at(20));
+        metrics.rollup_at(at(30));
+
+        let series = metrics.series_at(Duration::from_secs(3600), at(30));
+        let ssl: Vec<u64> = series
+            .iter()
+            .map(|b| b.counters["capture.ssl_events"])
+            .collect();
+        assert_eq!(ssl, vec![3, 5, 0]);
+        assert_eq!(series[1].start, at(10));
+        assert_eq!(series[1].end, at(20));
+
+        // Only buckets that ended inside the window
+        let recent = metrics.series_at(Duration::from_secs(15), at(30));
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].end, at(20));
+
+        // Bounded
+        for i in 0..MAX_SERIES_BUCKETS as i64 {
+            metrics.rollup_at(at(40 + i * 10));
+        }
+        assert_eq!(
+            metrics.series(Duration::from_secs(u64::MAX)).len(),
+            MAX_SERIES_BUCKETS
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Monotonic event time (`ts_mono`)
+//!
+//! Every event the pipeline exports carries `ts_mono`: nanoseconds on the
+//! host's monotonic clock, so durations and ordering between events hold up
+//! when the wall clock steps. On Linux and macOS the base is
+//! `CLOCK_MONOTONIC`, time since boot (not counting suspend on Linux). eBPF
+//! timestamps (`bpf_ktime_get_ns`) are already on this clock. On Windows,
+//! which has no equivalent shared with the capture helper, the base is the
+//! sensor's start.
+//!
+//! Capture sources stamp `timestamp_ns` with either the monotonic clock
+//! (eBPF) or the wall clock (the macOS and Windows helpers, generated
+//! events); see [`CaptureClock`]. Wall-clock stamps are moved onto the
+//! monotonic base by the offset between the two clocks when the sensor
+//! started, which keeps their spacing and order. Events without a capture
+//! timestamp (sessions, alerts, timeouts) are stamped with the sensor's
+//! monotonic clock when the pipeline sees them. Replayed events keep the
+//! `ts_mono` they were recorded with.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::events::EventEnvelope;
+use crate::plugins::RawCaptureEvent;
+
+/// Clock a capture source stamps `timestamp_ns` with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureClock {
+    /// Nanoseconds since the Unix epoch
+    #[default]
+    Realtime,
+    /// Host monotonic clock, the `ts_mono` base
+    Monotonic,
+}
+
+/// Where exported events get `ts_mono` from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TsMonoSource {
+    /// The capture timestamp, moved onto the monotonic base
+    #[default]
+    Capture,
+    /// The sensor's monotonic clock when the pipeline receives the event,
+    /// for capture sources whose timestamps can't be trusted
+    Sensor,
+}
+
+impl TsMonoSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Capture => "capture",
+            Self::Sensor => "sensor",
+        }
+    }
+}
+
+impl fmt::Display for TsMonoSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TsMonoSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "capture" => Ok(Self::Capture),
+            "sensor" => Ok(Self::Sensor),
+            other => Err(format!("unknown ts_mono source: {}", other)),
+        }
+    }
+}
+
+/// The sensor's monotonic clock, and conversion of capture timestamps onto it
+#[derive(Debug, Clone, Copy)]
+pub struct MonoClock {
+    source: TsMonoSource,
+    started: Instant,
+    /// Monotonic time at `started`
+    started_mono: u64,
+    /// Wall-clock time at `started`, in nanoseconds since the Unix epoch
+    started_wall: u64,
+}
+
+impl MonoClock {
+    pub fn new(source: TsMonoSource) -> Self {
+        let started = Instant::now();
+        let started_mono = host_monotonic_ns();
+        let started_wall = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            source,
+            started,
+            started_mono,
+            started_wall,
+        }
+    }
+
+    pub fn source(&self) -> TsMonoSource {
+        self.source
+    }
+
+    /// Current monotonic time
+    pub fn now(&self) -> u64 {
+        // 0 means absent in `ts_mono`
+        (self.started_mono + self.started.elapsed().as_nanos() as u64).max(1)
+    }
+
+    /// A capture timestamp on the monotonic base; `None` for a source that
+    /// left it unset (0)
+    pub fn from_capture(&self, timestamp_ns: u64, clock: CaptureClock) -> Option<u64> {
+        if timestamp_ns == 0 {
+            return None;
+        }
+        match clock {
+            CaptureClock::Monotonic => Some(timestamp_ns),
+            CaptureClock::Realtime => {
+                let mono =
+                    self.started_mono as i128 + timestamp_ns as i128 - self.started_wall as i128;
+                Some(mono.clamp(1, u64::MAX as i128) as u64)
+            }
+        }
+    }
+
+    /// Set `ts_mono` on an event decoded from `raw`
+    ///
+    /// Decoders copy `raw.timestamp_ns` (or an earlier capture's, for a
+    /// response matched to its request) into `ts_mono`; that is converted.
+    /// An event without one gets the capture's time.
+    pub fn stamp_decoded(&self, raw: &RawCaptureEvent, envelope: &mut EventEnvelope) {
+        let clock = raw.metadata.clock;
+        envelope.ts_mono = match self.source {
+            TsMonoSource::Sensor => Some(self.now()),
+            TsMonoSource::Capture => envelope
+                .ts_mono
+                .and_then(|ts| self.from_capture(ts, clock))
+                .or_else(|| self.from_capture(raw.timestamp_ns, clock))
+                .or_else(|| Some(self.now())),
+        };
+    }
+
+    /// Stamp an event that has no `ts_mono` with the current time
+    pub fn fill(&self, envelope: &mut EventEnvelope) {
+        if envelope.ts_mono.is_none_or(|ts| ts == 0) {
+            envelope.ts_mono = Some(self.now());
+        }
+    }
+}
+
+impl Default for MonoClock {
+    fn default() -> Self {
+        Self::new(TsMonoSource::default())
+    }
+}
+
+/// Host `CLOCK_MONOTONIC` in nanoseconds
+#[cfg(unix)]
+fn host_monotonic_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: clock_gettime only writes to the timespec passed to it
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) } != 0 {
+        return 0;
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// No host clock shared with the capture helper; the sensor's start is 0
+#[cfg(not(unix))]
+fn host_monotonic_ns() -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::{RawEventKind, RawEventMetadata};
+
+    fn raw(timestamp_ns: u64, clock: CaptureClock) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: "raw".to_string(),
+            timestamp_ns,
+            kind: RawEventKind::SslWrite,
+            pid: 1,
+            tid: None,
+            data: Vec::new(),
+            metadata: RawEventMetadata {
+                clock,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_realtime_capture_moved_onto_monotonic_base() {
+        let clock = MonoClock::new(TsMonoSource::Capture);
+        let wall_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+
+        // A wall-clock stamp from now lands close to the monotonic now
+        let converted = clock
+            .from_capture(wall_now, CaptureClock::Realtime)
+            .unwrap();
+        let now = clock.now();
+        assert!(
+            now.abs_diff(converted) < 1_000_000_000,
+            "{} vs {}",
+            converted,
+            now
+        );
+
+        // Spacing is kept
+        let later = clock
+            .from_capture(wall_now + 250_000_000, CaptureClock::Realtime)
+            .unwrap();
+        assert_eq!(later - converted, 250_000_000);
+
+        assert_eq!(clock.from_capture(42, CaptureClock::Monotonic), Some(42));
+        assert_eq!(clock.from_capture(0, CaptureClock::Monotonic), None);
+    }
+
+    #[test]
+    fn test_ts_mono_ordering_matches_capture_order() {
+        let clock = MonoClock::new(TsMonoSource::Capture);
+        let base = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+
+        for capture_clock in [CaptureClock::Realtime, CaptureClock::Monotonic] {
+            let start = match capture_clock {
+                CaptureClock::Realtime => base,
+                CaptureClock::Monotonic => clock.now(),
+            };
+            // Captured in order, some within the same microsecond
+            let stamps: Vec<u64> = [0, 1, 1_000, 1_001, 5_000_000, 5_000_000, 9_000_000_000]
+                .iter()
+                .map(|offset| {
+                    let raw = raw(start + offset, capture_clock);
+                    // One decoder copies the capture time, one leaves it unset
+                    let mut copied = EventEnvelope::new("ai.request");
+                    copied.ts_mono = Some(raw.timestamp_ns);
+                    let mut unset = EventEnvelope::new("process.exec");
+                    clock.stamp_decoded(&raw, &mut copied);
+                    clock.stamp_decoded(&raw, &mut unset);
+                    assert_eq!(copied.ts_mono, unset.ts_mono);
+                    copied.ts_mono.unwrap()
+                })
+                .collect();
+            assert!(
+                stamps.windows(2).all(|w| w[0] <= w[1]),
+                "{:?}: {:?}",
+                capture_clock,
+                stamps
+            );
+            assert_eq!(stamps[6] - stamps[0], 9_000_000_000);
+        }
+    }
+
+    #[test]
+    fn test_sensor_source_and_synthetic_events() {
+        let clock = MonoClock::new(TsMonoSource::Sensor);
+        let mut first = EventEnvelope::new("ai.request");
+        first.ts_mono = Some(5);
+        clock.stamp_decoded(&raw(5, CaptureClock::Monotonic), &mut first);
+        let mut second = EventEnvelope::new("ai.response");
+        clock.stamp_decoded(&raw(3, CaptureClock::Monotonic), &mut second);
+        assert!(first.ts_mono.unwrap() > 5);
+        assert!(second.ts_mono >= first.ts_mono);
+
+        // Synthetic events are stamped, stamped events left alone
+        let mut synthetic = EventEnvelope::new("agent.session");
+        clock.fill(&mut synthetic);
+        assert!(synthetic.ts_mono >= second.ts_mono);
+        let stamped = synthetic.ts_mono;
+        clock.fill(&mut synthetic);
+        assert_eq!(synthetic.ts_mono, stamped);
+
+        assert_eq!("Sensor".parse(), Ok(TsMonoSource::Sensor));
+        assert!("boot".parse::<TsMonoSource>().is_err());
+    }
+}
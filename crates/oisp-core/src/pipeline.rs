@@ -1,15 +1,28 @@
 //! Event pipeline - orchestrates the flow from capture to export
 
+use crate::dead_letter::{DeadLetterSink, ExportRetry};
 use crate::events::{EventEnvelope, OispEvent};
+use crate::metrics::{create_metrics, PipelineStage, SharedMetrics, ROLLUP_INTERVAL};
+use crate::monotonic::{MonoClock, TsMonoSource};
 use crate::plugins::{
-    ActionPlugin, CapturePlugin, DecodePlugin, EnrichPlugin, EventAction, ExportPlugin,
-    PluginError, PluginResult, RawCaptureEvent,
+    ActionPlugin, CaptureError, CaptureErrorKind, CaptureErrorSender, CapturePlugin,
+    CapturePluginStats, DecodePlugin, EnrichPlugin, EventAction, ExportPlugin, PluginError,
+    PluginResult, RawCaptureEvent, SharedProcessTargets,
 };
+use crate::spec::validate_event;
 use crate::trace::TraceBuilder;
+use crate::watchdog::WatchdogConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, RwLock};
-use tracing::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, Notify, RwLock};
+use tracing::{debug, debug_span, error, info, info_span, warn, Instrument, Span};
 
 /// Pipeline configuration
 #[derive(Debug, Clone)]
@@ -25,6 +38,308 @@ pub struct PipelineConfig {
 
     /// Maximum events to buffer before dropping
     pub max_buffer: usize,
+
+    /// Channel buffer size for capture errors
+    pub error_buffer_size: usize,
+
+    /// What to do with raw events when the raw event buffer is full
+    pub channel_policy: ChannelPolicy,
+
+    /// Whether to check events against the spec's event schemas before export
+    pub schema_validation: SchemaValidation,
+
+    /// Export attempts per event while every exporter rejects it. Retries
+    /// hold up the events behind it, so keep this small.
+    pub export_attempts: u32,
+
+    /// Delay before the first export retry, growing linearly after that
+    pub export_retry_delay: Duration,
+
+    /// How captured events get their `event_id`
+    pub event_ids: EventIds,
+
+    /// Where events get their `ts_mono`
+    pub ts_mono: TsMonoSource,
+
+    /// Restart capture plugins that stop delivering events and heartbeats
+    /// (None = no watchdog)
+    pub watchdog: Option<WatchdogConfig>,
+
+    /// Flush every exporter on SIGUSR1 while running (Unix only), so files
+    /// can be snapshotted without stopping the sensor
+    pub flush_on_signal: bool,
+}
+
+/// Behavior when the raw event buffer between capture and decode is full
+///
+/// The buffer fills when decode, enrich or export fall behind capture.
+/// Blocking loses nothing inside the sensor, but it stalls the capture
+/// reader, so the backlog moves into the kernel ring buffer and events are
+/// lost there once it overflows (counted in the capture plugin's
+/// `events_dropped`). The drop policies keep capture reading and discard in
+/// the pipeline instead, where the loss is counted in
+/// `oisp_pipeline_events_dropped_total` and the choice of which events to
+/// lose is explicit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelPolicy {
+    /// Wait for room (backpressure on capture)
+    #[default]
+    Block,
+    /// Discard the oldest buffered event to make room, favoring recent traffic
+    DropOldest,
+    /// Discard the incoming event, keeping what is already buffered
+    DropNewest,
+}
+
+impl ChannelPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::DropOldest => "drop_oldest",
+            Self::DropNewest => "drop_newest",
+        }
+    }
+}
+
+impl fmt::Display for ChannelPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ChannelPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "block" => Ok(Self::Block),
+            "drop_oldest" => Ok(Self::DropOldest),
+            "drop_newest" => Ok(Self::DropNewest),
+            other => Err(format!("unknown channel policy: {}", other)),
+        }
+    }
+}
+
+/// Checking of emitted events against the OISP spec's event schemas
+///
+/// Meant for conformance testing: every event is serialized and walked
+/// against its schema, which costs more than the rest of the pipeline for
+/// small events. Failures are counted in `oisp_pipeline_events_invalid_total`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaValidation {
+    /// No validation
+    #[default]
+    Off,
+    /// Log events that fail validation and export them anyway
+    Warn,
+    /// Log and drop events that fail validation
+    Strict,
+}
+
+impl SchemaValidation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Warn => "warn",
+            Self::Strict => "strict",
+        }
+    }
+}
+
+impl fmt::Display for SchemaValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SchemaValidation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "strict" => Ok(Self::Strict),
+            other => Err(format!("unknown schema validation mode: {}", other)),
+        }
+    }
+}
+
+/// How the pipeline assigns `event_id` to events decoded from capture
+///
+/// Random ids are unique per run, so replaying or backfilling the same
+/// capture exports every event under a new id. Content ids are derived from
+/// the captured data instead (see [`content_event_id`]), so re-exporting a
+/// capture yields the same ids and downstream stores can deduplicate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventIds {
+    /// A new random ULID per event
+    #[default]
+    Random,
+    /// A ULID hashed from the raw capture the event was decoded from
+    Content,
+}
+
+impl EventIds {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::Content => "content",
+        }
+    }
+}
+
+impl fmt::Display for EventIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventIds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "random" => Ok(Self::Random),
+            "content" => Ok(Self::Content),
+            other => Err(format!("unknown event id mode: {}", other)),
+        }
+    }
+}
+
+/// Deterministic `event_id` for the `index`th event decoded from `raw`
+///
+/// A ULID whose time part is the capture timestamp in milliseconds, so ids
+/// still sort in capture order, and whose random part is a SHA-256 over the
+/// event type, pid, capture timestamp and captured bytes. The raw event's own
+/// `id` is left out since capture plugins generate it randomly.
+pub fn content_event_id(raw: &RawCaptureEvent, event_type: &str, index: usize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(event_type.as_bytes());
+    hasher.update([0]);
+    hasher.update(raw.pid.to_le_bytes());
+    hasher.update(raw.timestamp_ns.to_le_bytes());
+    hasher.update((index as u64).to_le_bytes());
+    hasher.update(Sha256::digest(&raw.data));
+    let digest = hasher.finalize();
+
+    let mut random = [0u8; 16];
+    random.copy_from_slice(&digest[..16]);
+    ulid::Ulid::from_parts(raw.timestamp_ns / 1_000_000, u128::from_le_bytes(random)).to_string()
+}
+
+/// Minimum time between warnings about a full raw event buffer
+const DROP_WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often decoders are asked for events due to timeouts
+const DECODER_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bounded raw event buffer that discards instead of blocking when full
+///
+/// Capture plugins still send into an mpsc channel; a relay task drains it
+/// into this queue as fast as events arrive, so senders only wait for the
+/// relay, never for the pipeline.
+struct DropQueue {
+    events: std::sync::Mutex<VecDeque<RawCaptureEvent>>,
+    capacity: usize,
+    policy: ChannelPolicy,
+    /// Signalled when an event is pushed or the intake closes
+    ready: Notify,
+    closed: AtomicBool,
+}
+
+impl DropQueue {
+    /// Start relaying `intake` into a new queue
+    fn spawn(
+        mut intake: mpsc::Receiver<RawCaptureEvent>,
+        capacity: usize,
+        policy: ChannelPolicy,
+        metrics: SharedMetrics,
+    ) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            events: std::sync::Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            policy,
+            ready: Notify::new(),
+            closed: AtomicBool::new(false),
+        });
+
+        let relay = queue.clone();
+        tokio::spawn(async move {
+            let mut last_warning: Option<Instant> = None;
+            let mut dropped_since_warning = 0u64;
+            while let Some(event) = intake.recv().await {
+                if relay.push(event) {
+                    metrics
+                        .pipeline
+                        .events_dropped
+                        .fetch_add(1, Ordering::Relaxed);
+                    dropped_since_warning += 1;
+                    if last_warning.is_none_or(|at| at.elapsed() >= DROP_WARN_INTERVAL) {
+                        warn!(
+                            "Raw event buffer full ({} events), dropped {} (policy: {})",
+                            relay.capacity, dropped_since_warning, relay.policy
+                        );
+                        last_warning = Some(Instant::now());
+                        dropped_since_warning = 0;
+                    }
+                }
+            }
+            relay.closed.store(true, Ordering::Release);
+            relay.ready.notify_one();
+        });
+
+        queue
+    }
+
+    /// Buffer an event, returning whether one was dropped to stay in capacity
+    fn push(&self, event: RawCaptureEvent) -> bool {
+        let mut events = self.events.lock().unwrap();
+        let dropped = if events.len() < self.capacity {
+            events.push_back(event);
+            false
+        } else {
+            if self.policy == ChannelPolicy::DropOldest {
+                events.pop_front();
+                events.push_back(event);
+            }
+            true
+        };
+        drop(events);
+        self.ready.notify_one();
+        dropped
+    }
+
+    /// Next buffered event; None once the intake is closed and drained
+    async fn pop(&self) -> Option<RawCaptureEvent> {
+        loop {
+            if let Some(event) = self.events.lock().unwrap().pop_front() {
+                return Some(event);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.ready.notified().await;
+        }
+    }
+}
+
+/// Receiving end of the raw event buffer, per [`ChannelPolicy`]
+enum RawReceiver {
+    Channel(mpsc::Receiver<RawCaptureEvent>),
+    Queue(Arc<DropQueue>),
+}
+
+impl RawReceiver {
+    async fn recv(&mut self) -> Option<RawCaptureEvent> {
+        match self {
+            Self::Channel(rx) => rx.recv().await,
+            Self::Queue(queue) => queue.pop().await,
+        }
+    }
 }
 
 impl Default for PipelineConfig {
@@ -34,6 +349,15 @@ impl Default for PipelineConfig {
             event_buffer_size: 5000,
             build_traces: true,
             max_buffer: 100000,
+            error_buffer_size: 256,
+            channel_policy: ChannelPolicy::Block,
+            schema_validation: SchemaValidation::Off,
+            export_attempts: 1,
+            export_retry_delay: crate::dead_letter::DEFAULT_RETRY_DELAY,
+            event_ids: EventIds::Random,
+            ts_mono: TsMonoSource::Capture,
+            watchdog: None,
+            flush_on_signal: false,
         }
     }
 }
@@ -57,33 +381,50 @@ pub struct Pipeline {
     /// Export plugins
     export_plugins: Vec<Arc<Box<dyn ExportPlugin>>>,
 
+    /// Retries and dead-letter sink for events every exporter rejects
+    export_retry: ExportRetry,
+
     /// Trace builder
     trace_builder: Option<Arc<RwLock<TraceBuilder>>>,
 
     /// Broadcast channel for events (for UI, etc.)
     event_broadcast: broadcast::Sender<Arc<OispEvent>>,
 
+    /// Metrics, including capture errors reported by plugins
+    metrics: SharedMetrics,
+
     /// Running state
     running: Arc<RwLock<bool>>,
 
     /// Shutdown signal
     shutdown_tx: Option<broadcast::Sender<()>>,
+
+    /// Clock for `ts_mono`
+    mono: MonoClock,
 }
 
 impl Pipeline {
     /// Create a new pipeline with configuration
     pub fn new(config: PipelineConfig) -> Self {
         let (event_broadcast, _) = broadcast::channel(config.event_buffer_size);
+        let export_retry = ExportRetry {
+            attempts: config.export_attempts.max(1),
+            delay: config.export_retry_delay,
+            dead_letter: None,
+        };
 
         Self {
+            mono: MonoClock::new(config.ts_mono),
             config,
             capture_plugins: Vec::new(),
             decode_plugins: Vec::new(),
             enrich_plugins: Vec::new(),
             action_plugins: Vec::new(),
             export_plugins: Vec::new(),
+            export_retry,
             trace_builder: None,
             event_broadcast,
+            metrics: create_metrics(),
             running: Arc::new(RwLock::new(false)),
             shutdown_tx: None,
         }
@@ -91,7 +432,9 @@ impl Pipeline {
 
     /// Add a capture plugin
     pub fn add_capture(&mut self, plugin: Box<dyn CapturePlugin>) {
-        self.capture_plugins.push(Arc::new(RwLock::new(plugin)));
+        let plugin = Arc::new(RwLock::new(plugin));
+        self.metrics.register_capture_plugin(plugin.clone());
+        self.capture_plugins.push(plugin);
     }
 
     /// Add a decode plugin
@@ -116,6 +459,11 @@ impl Pipeline {
         self.export_plugins.push(Arc::new(plugin));
     }
 
+    /// Write events that every exporter rejected on all attempts to `sink`
+    pub fn set_dead_letter(&mut self, sink: DeadLetterSink) {
+        self.export_retry.dead_letter = Some(Arc::new(sink));
+    }
+
     /// Enable trace building
     pub fn enable_traces(&mut self) {
         self.trace_builder = Some(Arc::new(RwLock::new(TraceBuilder::new())));
@@ -136,8 +484,57 @@ impl Pipeline {
         self.trace_builder.clone()
     }
 
+    /// Get the metrics collector (for sharing with web server, etc.)
+    pub fn metrics(&self) -> SharedMetrics {
+        self.metrics.clone()
+    }
+
+    /// Runtime process attach/detach handle of the first capture plugin
+    /// that supports it
+    pub async fn process_targets(&self) -> Option<SharedProcessTargets> {
+        for plugin in &self.capture_plugins {
+            if let Some(targets) = plugin.read().await.process_targets() {
+                return Some(targets);
+            }
+        }
+        None
+    }
+
+    /// Whether any capture plugin reports the first outbound bytes of
+    /// connections (see [`CapturePlugin::reports_network_send`])
+    pub async fn captures_report_network_send(&self) -> bool {
+        for plugin in &self.capture_plugins {
+            if plugin.read().await.reports_network_send() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Capture errors reported so far, oldest first
+    pub fn capture_errors(&self) -> Vec<CaptureError> {
+        self.metrics.capture_errors()
+    }
+
+    /// Stats of each capture plugin
+    pub async fn capture_stats(&self) -> Vec<CapturePluginStats> {
+        self.metrics.capture_plugin_stats().await
+    }
+
+    /// Order enrichers so each runs after the enrichers it depends on
+    ///
+    /// Called by [`Pipeline::start`]. Enrichers without dependencies between
+    /// them keep the order they were added in. Fails if an enricher requires
+    /// a field no enricher provides, or if dependencies form a cycle.
+    pub fn resolve_enrich_order(&mut self) -> PluginResult<()> {
+        self.enrich_plugins = order_enrichers(std::mem::take(&mut self.enrich_plugins))?;
+        Ok(())
+    }
+
     /// Start the pipeline
     pub async fn start(&mut self) -> PluginResult<()> {
+        self.resolve_enrich_order()?;
+
         let mut running = self.running.write().await;
         if *running {
             return Err(PluginError::OperationFailed(
@@ -151,36 +548,107 @@ impl Pipeline {
         self.shutdown_tx = Some(shutdown_tx.clone());
 
         // Channel for raw events from capture plugins
-        let (raw_tx, mut raw_rx) = mpsc::channel::<RawCaptureEvent>(self.config.raw_buffer_size);
+        let (raw_tx, raw_rx) = mpsc::channel::<RawCaptureEvent>(self.config.raw_buffer_size);
+        let mut raw_rx = match self.config.channel_policy {
+            ChannelPolicy::Block => RawReceiver::Channel(raw_rx),
+            policy => RawReceiver::Queue(DropQueue::spawn(
+                raw_rx,
+                self.config.raw_buffer_size,
+                policy,
+                self.metrics.clone(),
+            )),
+        };
+
+        // Channel for structured errors from capture plugins
+        let (error_tx, mut error_rx) = mpsc::channel::<CaptureError>(self.config.error_buffer_size);
 
         // Start all capture plugins
         for capture in &self.capture_plugins {
             let tx = raw_tx.clone();
             let mut capture = capture.write().await;
+            let name = capture.name().to_string();
+            capture.set_error_sender(CaptureErrorSender::new(&name, error_tx.clone()));
             if let Err(e) = capture.start(tx).await {
-                error!("Failed to start capture plugin {}: {}", capture.name(), e);
+                error!("Failed to start capture plugin {}: {}", name, e);
+                self.metrics.record_capture_error(CaptureError::new(
+                    name,
+                    CaptureErrorKind::Start,
+                    e.to_string(),
+                ));
             } else {
-                info!("Started capture plugin: {}", capture.name());
+                info!("Started capture plugin: {}", name);
             }
         }
 
-        // Drop the original sender so the channel closes when all captures stop
+        if let Some(watchdog) = self.config.watchdog {
+            crate::watchdog::spawn(
+                self.capture_plugins.clone(),
+                raw_tx.clone(),
+                self.metrics.clone(),
+                watchdog,
+                shutdown_tx.subscribe(),
+            );
+        }
+
+        // Drop the original senders so the channels close when all captures stop
         drop(raw_tx);
+        drop(error_tx);
+
+        // Errors reported while starting are visible as soon as start() returns
+        while let Ok(err) = error_rx.try_recv() {
+            warn!("Capture error from {}: {}", err.plugin, err.message);
+            self.metrics.record_capture_error(err);
+        }
+
+        // Counter increments per interval, for /api/metrics/series
+        let metrics = self.metrics.clone();
+        let mut rollup_shutdown = shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ROLLUP_INTERVAL);
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => metrics.rollup(),
+                    _ = rollup_shutdown.recv() => break,
+                }
+            }
+        });
+
+        if self.config.flush_on_signal {
+            self.spawn_flush_on_signal(shutdown_tx.subscribe());
+        }
+
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            while let Some(err) = error_rx.recv().await {
+                warn!("Capture error from {}: {}", err.plugin, err.message);
+                metrics.record_capture_error(err);
+            }
+        });
 
         // Clone references for the processing task
         let decode_plugins = self.decode_plugins.clone();
         let enrich_plugins = self.enrich_plugins.clone();
         let action_plugins = self.action_plugins.clone();
         let export_plugins = self.export_plugins.clone();
+        let export_retry = self.export_retry.clone();
         let trace_builder = self.trace_builder.clone();
         let event_broadcast = self.event_broadcast.clone();
+        let metrics = self.metrics.clone();
+        let schema_validation = self.config.schema_validation;
+        let event_ids = self.config.event_ids;
+        let mono = self.mono;
         let running = self.running.clone();
         let mut shutdown_rx = shutdown_tx.subscribe();
 
         // Main processing loop
         tokio::spawn(async move {
+            let mut decoder_tick = tokio::time::interval(DECODER_TICK_INTERVAL);
+            decoder_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
             loop {
                 tokio::select! {
+                    // Disabled once all senders drop; the decoder tick keeps
+                    // the loop alive until shutdown
                     Some(raw_event) = raw_rx.recv() => {
                         // Debug log for raw event reception
                         info!("Received raw event: id={}, kind={:?}, size={} bytes",
@@ -193,29 +661,62 @@ impl Pipeline {
                             &enrich_plugins,
                             &action_plugins,
                             &export_plugins,
+                            &export_retry,
                             trace_builder.as_ref(),
                             &event_broadcast,
+                            &metrics,
+                            schema_validation,
+                            event_ids,
+                            &mono,
                         ).await {
                             debug!("Error processing event: {}", e);
                         }
                     }
+                    _ = decoder_tick.tick() => {
+                        let events = decode_plugins
+                            .iter()
+                            .flat_map(|decoder| decoder.tick(&mono))
+                            .collect();
+                        Self::process_held_events(
+                            events,
+                            &enrich_plugins,
+                            &action_plugins,
+                            &export_plugins,
+                            &export_retry,
+                            trace_builder.as_ref(),
+                            &event_broadcast,
+                            &metrics,
+                            schema_validation,
+                            &mono,
+                        ).await;
+                    }
                     _ = shutdown_rx.recv() => {
                         info!("Pipeline shutdown signal received");
                         break;
                     }
-                    else => {
-                        // All senders dropped, channel closed
-                        break;
-                    }
                 }
             }
 
-            // Flush all export plugins
-            for export in &export_plugins {
-                if let Err(e) = export.flush().await {
-                    warn!("Error flushing export plugin {}: {}", export.name(), e);
-                }
-            }
+            // Whatever decoders still hold goes out before the final flush
+            let events = decode_plugins
+                .iter()
+                .flat_map(|decoder| decoder.flush(&mono))
+                .collect();
+            Self::process_held_events(
+                events,
+                &enrich_plugins,
+                &action_plugins,
+                &export_plugins,
+                &export_retry,
+                trace_builder.as_ref(),
+                &event_broadcast,
+                &metrics,
+                schema_validation,
+                &mono,
+            )
+            .await;
+
+            flush_exports(&export_plugins).await;
 
             *running.write().await = false;
             info!("Pipeline stopped");
@@ -224,6 +725,41 @@ impl Pipeline {
         Ok(())
     }
 
+    /// Flush every exporter each time SIGUSR1 arrives, until shutdown
+    ///
+    /// The handler is installed before this returns, so a signal sent once
+    /// `start` is done is never lost to the default action (terminate).
+    #[cfg(unix)]
+    fn spawn_flush_on_signal(&self, mut shutdown_rx: broadcast::Receiver<()>) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigusr1 = match signal(SignalKind::user_defined1()) {
+            Ok(sigusr1) => sigusr1,
+            Err(e) => {
+                warn!("Failed to set up SIGUSR1 flush handler: {}", e);
+                return;
+            }
+        };
+        let export_plugins = self.export_plugins.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(()) = sigusr1.recv() => {
+                        info!("Received SIGUSR1, flushing exporters");
+                        flush_exports(&export_plugins).await;
+                    }
+                    _ = shutdown_rx.recv() => break,
+                    else => break,
+                }
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn spawn_flush_on_signal(&self, _shutdown_rx: broadcast::Receiver<()>) {
+        debug!("SIGUSR1 flush not available on this platform");
+    }
+
     /// Stop the pipeline
     pub async fn stop(&mut self) -> PluginResult<()> {
         // Send shutdown signal
@@ -251,19 +787,30 @@ impl Pipeline {
     }
 
     /// Process a single raw event through the pipeline
+    #[allow(clippy::too_many_arguments)]
     async fn process_raw_event(
         raw: RawCaptureEvent,
         decode_plugins: &[Arc<Box<dyn DecodePlugin>>],
         enrich_plugins: &[Arc<Box<dyn EnrichPlugin>>],
         action_plugins: &[Arc<Box<dyn ActionPlugin>>],
         export_plugins: &[Arc<Box<dyn ExportPlugin>>],
+        export_retry: &ExportRetry,
         trace_builder: Option<&Arc<RwLock<TraceBuilder>>>,
         event_broadcast: &broadcast::Sender<Arc<OispEvent>>,
+        metrics: &SharedMetrics,
+        schema_validation: SchemaValidation,
+        event_ids: EventIds,
+        mono: &MonoClock,
     ) -> PluginResult<()> {
+        metrics.record_raw_event(&raw.kind, raw.data.len());
+
         // 0. CREATE RAW CAPTURE EVENT (for debugging/visibility)
         let mut raw_envelope = EventEnvelope::new("capture.raw");
+        if event_ids == EventIds::Content {
+            raw_envelope.event_id = content_event_id(&raw, "capture.raw", 0);
+        }
         raw_envelope.ts = chrono::Utc::now();
-        raw_envelope.ts_mono = Some(raw.timestamp_ns);
+        mono.stamp_decoded(&raw, &mut raw_envelope);
         raw_envelope.process = Some(crate::events::ProcessInfo {
             pid: raw.pid,
             ppid: raw.metadata.ppid,
@@ -297,84 +844,345 @@ impl Pipeline {
 
         // 1. DECODE: Find a decoder and decode the raw event
         let mut events = Vec::new();
-        for decoder in decode_plugins {
-            if decoder.can_decode(&raw) {
-                match decoder.decode(raw.clone()).await {
-                    Ok(decoded) => {
-                        events = decoded;
-                        break;
-                    }
-                    Err(e) => {
-                        debug!("Decoder {} failed: {}", decoder.name(), e);
+        let decode_started = Instant::now();
+        let decode_span = debug_span!("decode", raw_id = %raw.id, kind = ?raw.kind, pid = raw.pid);
+        async {
+            for decoder in decode_plugins {
+                if decoder.can_decode(&raw) {
+                    match decoder.decode(raw.clone()).await {
+                        Ok(decoded) => {
+                            events = decoded;
+                            break;
+                        }
+                        Err(e) => {
+                            debug!("Decoder {} failed: {}", decoder.name(), e);
+                        }
                     }
                 }
             }
         }
+        .instrument(decode_span)
+        .await;
+        metrics
+            .stages
+            .record(PipelineStage::Decode, decode_started.elapsed());
 
         if events.is_empty() {
             return Ok(()); // No decoder handled this event
         }
+        if event_ids == EventIds::Content {
+            for (index, event) in events.iter_mut().enumerate() {
+                let event_id = content_event_id(&raw, event.event_type(), index);
+                event.envelope_mut().event_id = event_id;
+            }
+        }
+        for event in &mut events {
+            mono.stamp_decoded(&raw, event.envelope_mut());
+        }
+        metrics
+            .pipeline
+            .events_processed
+            .fetch_add(events.len() as u64, Ordering::Relaxed);
 
-        // Process each decoded event
-        for mut event in events {
-            // 2. ENRICH: Add context to the event
-            for enricher in enrich_plugins {
-                if enricher.applies_to(&event) {
-                    if let Err(e) = enricher.enrich(&mut event).await {
-                        debug!("Enricher {} failed: {}", enricher.name(), e);
-                    }
+        // Process each decoded event in its own span so a single event's
+        // enrich -> action -> export path can be filtered by event_id
+        for event in events {
+            let span = Self::event_span(&event, Some(&raw.id));
+            Self::process_decoded_event(
+                event,
+                enrich_plugins,
+                action_plugins,
+                export_plugins,
+                export_retry,
+                trace_builder,
+                event_broadcast,
+                metrics,
+                schema_validation,
+                Some(mono),
+            )
+            .instrument(span)
+            .await;
+        }
+
+        Ok(())
+    }
+
+    /// Run events decoders emitted on a timer or at shutdown through the
+    /// rest of the pipeline
+    #[allow(clippy::too_many_arguments)]
+    async fn process_held_events(
+        events: Vec<OispEvent>,
+        enrich_plugins: &[Arc<Box<dyn EnrichPlugin>>],
+        action_plugins: &[Arc<Box<dyn ActionPlugin>>],
+        export_plugins: &[Arc<Box<dyn ExportPlugin>>],
+        export_retry: &ExportRetry,
+        trace_builder: Option<&Arc<RwLock<TraceBuilder>>>,
+        event_broadcast: &broadcast::Sender<Arc<OispEvent>>,
+        metrics: &SharedMetrics,
+        schema_validation: SchemaValidation,
+        mono: &MonoClock,
+    ) {
+        metrics
+            .pipeline
+            .events_processed
+            .fetch_add(events.len() as u64, Ordering::Relaxed);
+        for event in events {
+            let span = Self::event_span(&event, None);
+            Self::process_decoded_event(
+                event,
+                enrich_plugins,
+                action_plugins,
+                export_plugins,
+                export_retry,
+                trace_builder,
+                event_broadcast,
+                metrics,
+                schema_validation,
+                Some(mono),
+            )
+            .instrument(span)
+            .await;
+        }
+    }
+
+    /// Span for one event's path through the pipeline
+    ///
+    /// Fields are identifiers only; never add content here.
+    fn event_span(event: &OispEvent, raw_id: Option<&str>) -> Span {
+        let envelope = event.envelope();
+        let span = info_span!(
+            "event",
+            event_id = %envelope.event_id,
+            event_type = %envelope.event_type,
+            raw_id = tracing::field::Empty,
+            trace_id = tracing::field::Empty,
+        );
+        if let Some(raw_id) = raw_id {
+            span.record("raw_id", raw_id);
+        }
+        if let Some(ctx) = &envelope.trace_context {
+            span.record("trace_id", ctx.trace_id.as_str());
+        }
+        span
+    }
+
+    /// Run a decoded event through enrich, action and export stages
+    #[allow(clippy::too_many_arguments)]
+    async fn process_decoded_event(
+        mut event: OispEvent,
+        enrich_plugins: &[Arc<Box<dyn EnrichPlugin>>],
+        action_plugins: &[Arc<Box<dyn ActionPlugin>>],
+        export_plugins: &[Arc<Box<dyn ExportPlugin>>],
+        export_retry: &ExportRetry,
+        trace_builder: Option<&Arc<RwLock<TraceBuilder>>>,
+        event_broadcast: &broadcast::Sender<Arc<OispEvent>>,
+        metrics: &SharedMetrics,
+        schema_validation: SchemaValidation,
+        mono: Option<&MonoClock>,
+    ) {
+        if event.is_ai_event() {
+            metrics.pipeline.ai_events.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // 2. ENRICH: Add context to the event
+        let enrich_started = Instant::now();
+        for enricher in enrich_plugins {
+            if enricher.applies_to(&event) {
+                if let Err(e) = enricher.enrich(&mut event).await {
+                    debug!("Enricher {} failed: {}", enricher.name(), e);
                 }
             }
+        }
+
+        metrics
+            .stages
+            .record(PipelineStage::Enrich, enrich_started.elapsed());
 
-            // 3. ACTION: Filter/transform/redact
-            let mut current_events = vec![event];
-            for action in action_plugins {
-                let mut next_events = Vec::new();
-                for evt in current_events {
-                    if action.applies_to(&evt) {
-                        match action.process(evt).await {
-                            Ok((processed, action_result)) => match action_result {
-                                EventAction::Pass => next_events.push(processed),
-                                EventAction::Modified => next_events.push(processed),
-                                EventAction::Drop => {} // Don't add to next
-                                EventAction::Replace(replacements) => {
-                                    next_events.extend(replacements);
-                                }
-                            },
-                            Err(e) => {
-                                debug!("Action {} failed: {}", action.name(), e);
+        // 3. ACTION: Filter/transform/redact
+        let action_started = Instant::now();
+        let mut current_events = vec![event];
+        for action in action_plugins {
+            let mut next_events = Vec::new();
+            for evt in current_events {
+                if action.applies_to(&evt) {
+                    match action.process(evt).await {
+                        Ok((processed, action_result)) => match action_result {
+                            EventAction::Pass => next_events.push(processed),
+                            EventAction::Modified => next_events.push(processed),
+                            EventAction::Drop => {} // Don't add to next
+                            EventAction::Replace(replacements) => {
+                                next_events.extend(replacements);
                             }
+                        },
+                        Err(e) => {
+                            debug!("Action {} failed: {}", action.name(), e);
                         }
-                    } else {
-                        next_events.push(evt);
                     }
+                } else {
+                    next_events.push(evt);
                 }
-                current_events = next_events;
             }
+            current_events = next_events;
+        }
+        metrics
+            .stages
+            .record(PipelineStage::Action, action_started.elapsed());
 
-            // 4. Process final events
-            for final_event in current_events {
-                let event_arc = Arc::new(final_event);
+        // 4. Process final events
+        for mut final_event in current_events {
+            // Events made by actions (sessions, alerts) have no capture time
+            if let Some(mono) = mono {
+                mono.fill(final_event.envelope_mut());
+            }
 
-                // Add to trace builder if enabled
-                if let Some(tb) = trace_builder {
-                    let mut builder = tb.write().await;
-                    builder.add_event((*event_arc).clone());
+            if schema_validation != SchemaValidation::Off {
+                let violations = validate_event(&final_event);
+                if !violations.is_empty() {
+                    metrics
+                        .pipeline
+                        .events_invalid
+                        .fetch_add(1, Ordering::Relaxed);
+                    let violations: Vec<String> =
+                        violations.iter().map(ToString::to_string).collect();
+                    warn!(
+                        event_type = final_event.event_type(),
+                        dropped = schema_validation == SchemaValidation::Strict,
+                        "Event failed schema validation: {}",
+                        violations.join("; ")
+                    );
+                    if schema_validation == SchemaValidation::Strict {
+                        continue;
+                    }
                 }
+            }
 
-                // Broadcast to subscribers
-                let _ = event_broadcast.send(event_arc.clone());
+            let event_arc = Arc::new(final_event);
 
-                // 5. EXPORT: Send to all exporters
-                for exporter in export_plugins {
-                    if let Err(e) = exporter.export(&event_arc).await {
-                        debug!("Exporter {} failed: {}", exporter.name(), e);
-                    }
+            if let OispEvent::AiResponse(response) = event_arc.as_ref() {
+                metrics.providers.record_response(&response.data);
+            }
+
+            // Add to trace builder if enabled
+            if let Some(tb) = trace_builder {
+                let mut builder = tb.write().await;
+                builder.add_event((*event_arc).clone());
+                if let Some(trace_id) = event_arc
+                    .envelope()
+                    .process
+                    .as_ref()
+                    .and_then(|p| builder.active_trace_id(p.pid))
+                {
+                    Span::current().record("trace_id", trace_id);
                 }
             }
+
+            // Broadcast to subscribers
+            let _ = event_broadcast.send(event_arc.clone());
+
+            // 5. EXPORT: Send to all exporters
+            let export_started = Instant::now();
+            let exported =
+                Self::export_event(&event_arc, export_plugins, export_retry, metrics).await;
+            metrics
+                .stages
+                .record(PipelineStage::Export, export_started.elapsed());
+            if exported {
+                metrics
+                    .pipeline
+                    .events_exported
+                    .fetch_add(1, Ordering::Relaxed);
+            }
         }
+    }
 
-        Ok(())
+    /// Send an event to every exporter, retrying while none accepts it
+    ///
+    /// Returns whether at least one exporter took the event. One that
+    /// failed all attempts goes to the dead-letter sink, if there is one.
+    async fn export_event(
+        event: &OispEvent,
+        export_plugins: &[Arc<Box<dyn ExportPlugin>>],
+        retry: &ExportRetry,
+        metrics: &SharedMetrics,
+    ) -> bool {
+        if export_plugins.is_empty() {
+            return false;
+        }
+
+        let attempts = retry.attempts.max(1);
+        let mut errors = Vec::new();
+        for attempt in 1..=attempts {
+            errors.clear();
+            for exporter in export_plugins {
+                if let Err(e) = exporter.export(event).await {
+                    debug!("Exporter {} failed: {}", exporter.name(), e);
+                    errors.push(format!("{}: {}", exporter.name(), e));
+                }
+            }
+            if errors.len() < export_plugins.len() {
+                return true;
+            }
+            if attempt < attempts {
+                tokio::time::sleep(retry.delay * attempt).await;
+            }
+        }
+
+        let reason = errors.join("; ");
+        let Some(sink) = &retry.dead_letter else {
+            debug!("No exporter accepted the event: {}", reason);
+            return false;
+        };
+        match sink.write(event, attempts, &reason) {
+            Ok(()) => {
+                metrics
+                    .pipeline
+                    .events_dead_lettered
+                    .fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    attempts,
+                    "No exporter accepted the event, written to {}: {}",
+                    sink.path().display(),
+                    reason
+                );
+            }
+            Err(e) => error!(
+                "Failed to write event to dead-letter file {}: {} (export errors: {})",
+                sink.path().display(),
+                e,
+                reason
+            ),
+        }
+        false
+    }
+
+    /// Inject an already-decoded event, skipping capture and decode
+    ///
+    /// The event runs through the enrich, action and export stages (and the
+    /// trace builder, if enabled) exactly like a freshly decoded event,
+    /// except that `ts_mono` is left as it is.
+    pub async fn process_event(&self, event: OispEvent) {
+        let span = Self::event_span(&event, None);
+        Self::process_decoded_event(
+            event,
+            &self.enrich_plugins,
+            &self.action_plugins,
+            &self.export_plugins,
+            &self.export_retry,
+            self.trace_builder.as_ref(),
+            &self.event_broadcast,
+            &self.metrics,
+            self.config.schema_validation,
+            // Injected events keep their recorded times; the sensor's clock
+            // means nothing next to them
+            None,
+        )
+        .instrument(span)
+        .await;
+    }
+
+    /// Flush all export plugins
+    pub async fn flush(&self) {
+        flush_exports(&self.export_plugins).await;
     }
 
     /// Check if pipeline is running
@@ -382,3 +1190,1147 @@ impl Pipeline {
         *self.running.read().await
     }
 }
+
+async fn flush_exports(export_plugins: &[Arc<Box<dyn ExportPlugin>>]) {
+    for export in export_plugins {
+        if let Err(e) = export.flush().await {
+            warn!("Error flushing export plugin {}: {}", export.name(), e);
+        }
+    }
+}
+
+/// Topologically sort enrichers by their declared `requires`/`provides`
+fn order_enrichers(
+    mut remaining: Vec<Arc<Box<dyn EnrichPlugin>>>,
+) -> PluginResult<Vec<Arc<Box<dyn EnrichPlugin>>>> {
+    // Whether another enricher in `plugins` provides `field` to the one at `index`
+    fn provided_by_other(
+        plugins: &[Arc<Box<dyn EnrichPlugin>>],
+        index: usize,
+        field: &str,
+    ) -> bool {
+        plugins
+            .iter()
+            .enumerate()
+            .any(|(i, p)| i != index && p.provides().contains(&field))
+    }
+
+    for (index, plugin) in remaining.iter().enumerate() {
+        if let Some(field) = plugin
+            .requires()
+            .iter()
+            .find(|field| !provided_by_other(&remaining, index, field))
+        {
+            return Err(PluginError::ConfigurationError(format!(
+                "Enricher {} requires {}, which no enricher provides",
+                plugin.name(),
+                field
+            )));
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        // First enricher (in add order) whose providers have all been placed
+        let ready = (0..remaining.len()).find(|&index| {
+            remaining[index]
+                .requires()
+                .iter()
+                .all(|field| !provided_by_other(&remaining, index, field))
+        });
+        match ready {
+            Some(index) => ordered.push(remaining.remove(index)),
+            None => {
+                let names: Vec<&str> = remaining.iter().map(|p| p.name()).collect();
+                return Err(PluginError::ConfigurationError(format!(
+                    "Enricher dependency cycle between: {}",
+                    names.join(", ")
+                )));
+            }
+        }
+    }
+
+    debug!(
+        "Enricher order: {}",
+        ordered
+            .iter()
+            .map(|p| p.name())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    );
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{ProcessExitEvent, TraceContext};
+    use crate::plugins::{
+        CaptureErrorKind, CaptureStats, Plugin, PluginInfo, RawEventKind, RawEventMetadata,
+    };
+    use async_trait::async_trait;
+    use std::any::Any;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    type Fields = Vec<(String, String)>;
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    /// Records the fields of every span in scope for each log event
+    #[derive(Clone, Default)]
+    struct CaptureLayer {
+        scopes: Arc<Mutex<Vec<Fields>>>,
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CaptureLayer {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(fields);
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                    values.record(&mut FieldVisitor(fields));
+                }
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut fields = Fields::new();
+            if let Some(scope) = ctx.event_scope(event) {
+                for span in scope {
+                    if let Some(f) = span.extensions().get::<Fields>() {
+                        fields.extend(f.iter().cloned());
+                    }
+                }
+            }
+            self.scopes.lock().unwrap().push(fields);
+        }
+    }
+
+    /// Decodes every raw event into a process.exit event with a trace context
+    struct TestDecoder;
+
+    impl PluginInfo for TestDecoder {
+        fn name(&self) -> &str {
+            "test-decoder"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for TestDecoder {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl DecodePlugin for TestDecoder {
+        fn can_decode(&self, _raw: &RawCaptureEvent) -> bool {
+            true
+        }
+
+        async fn decode(&self, _raw: RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
+            let mut envelope = EventEnvelope::new("process.exit");
+            envelope.trace_context = Some(TraceContext {
+                trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+                span_id: "00f067aa0ba902b7".to_string(),
+                trace_flags: None,
+            });
+            Ok(vec![OispEvent::ProcessExit(ProcessExitEvent {
+                envelope,
+                data: serde_json::from_value(serde_json::json!({"exit_code": 0})).unwrap(),
+            })])
+        }
+    }
+
+    /// Decodes nothing, but holds one event until the pipeline stops
+    struct HoldingDecoder;
+
+    impl PluginInfo for HoldingDecoder {
+        fn name(&self) -> &str {
+            "holding-decoder"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for HoldingDecoder {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl DecodePlugin for HoldingDecoder {
+        fn can_decode(&self, _raw: &RawCaptureEvent) -> bool {
+            false
+        }
+
+        async fn decode(&self, _raw: RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
+            Ok(Vec::new())
+        }
+
+        fn flush(&self, _mono: &MonoClock) -> Vec<OispEvent> {
+            let mut envelope = EventEnvelope::new("process.exit");
+            envelope.event_id = "held".to_string();
+            vec![OispEvent::ProcessExit(ProcessExitEvent {
+                envelope,
+                data: serde_json::from_value(serde_json::json!({"exit_code": 0})).unwrap(),
+            })]
+        }
+    }
+
+    /// Logs from inside export and remembers exported event IDs
+    struct TestExporter {
+        exported: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PluginInfo for TestExporter {
+        fn name(&self) -> &str {
+            "test-exporter"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for TestExporter {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl ExportPlugin for TestExporter {
+        async fn export(&self, event: &OispEvent) -> PluginResult<()> {
+            if !matches!(event, OispEvent::CaptureRaw(_)) {
+                info!("exporting event");
+                self.exported
+                    .lock()
+                    .unwrap()
+                    .push(event.envelope().event_id.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_span_carries_event_id() {
+        let layer = CaptureLayer::default();
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let decoders: Vec<Arc<Box<dyn DecodePlugin>>> = vec![Arc::new(Box::new(TestDecoder))];
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let exporters: Vec<Arc<Box<dyn ExportPlugin>>> = vec![Arc::new(Box::new(TestExporter {
+            exported: exported.clone(),
+        }))];
+        let (tx, _rx) = broadcast::channel(16);
+
+        let raw = RawCaptureEvent {
+            id: "raw-1".to_string(),
+            timestamp_ns: 0,
+            kind: RawEventKind::SslWrite,
+            pid: 42,
+            tid: None,
+            data: b"sk-secret-prompt-text".to_vec(),
+            metadata: RawEventMetadata::default(),
+        };
+
+        Pipeline::process_raw_event(
+            raw,
+            &decoders,
+            &[],
+            &[],
+            &exporters,
+            &ExportRetry::default(),
+            None,
+            &tx,
+            &create_metrics(),
+            SchemaValidation::Off,
+            EventIds::Random,
+            &MonoClock::default(),
+        )
+        .await
+        .unwrap();
+
+        let exported = exported.lock().unwrap().clone();
+        assert_eq!(exported.len(), 1);
+        let event_id = &exported[0];
+
+        let scopes = layer.scopes.lock().unwrap();
+        let export_scope = scopes
+            .iter()
+            .find(|fields| fields.iter().any(|(k, v)| k == "event_id" && v == event_id))
+            .expect("export log should be inside the event span");
+        assert!(export_scope
+            .iter()
+            .any(|(k, v)| k == "trace_id" && v == "4bf92f3577b34da6a3ce929d0e0e4736"));
+        assert!(export_scope
+            .iter()
+            .any(|(k, v)| k == "event_type" && v == "process.exit"));
+
+        // Span fields never carry captured content
+        assert!(scopes
+            .iter()
+            .flatten()
+            .all(|(_, v)| !v.contains("sk-secret-prompt-text")));
+    }
+
+    /// Reports a permission problem, then fails to start
+    struct FailingCapture;
+
+    impl PluginInfo for FailingCapture {
+        fn name(&self) -> &str {
+            "failing-capture"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for FailingCapture {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl CapturePlugin for FailingCapture {
+        async fn start(&mut self, _tx: mpsc::Sender<RawCaptureEvent>) -> PluginResult<()> {
+            Err(PluginError::InitializationFailed(
+                "failed to attach uprobe: permission denied".into(),
+            ))
+        }
+
+        async fn stop(&mut self) -> PluginResult<()> {
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            false
+        }
+
+        fn set_error_sender(&mut self, errors: CaptureErrorSender) {
+            errors.report(CaptureErrorKind::PermissionDenied, "CAP_BPF not available");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_start_error_propagates_to_stats() {
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_capture(Box::new(FailingCapture));
+        pipeline.start().await.unwrap();
+
+        let errors = pipeline.capture_errors();
+        assert_eq!(errors.len(), 2);
+        let start = errors
+            .iter()
+            .find(|e| e.kind == CaptureErrorKind::Start)
+            .unwrap();
+        assert_eq!(start.plugin, "failing-capture");
+        assert!(start.message.contains("permission denied"));
+        assert!(errors
+            .iter()
+            .any(|e| e.kind == CaptureErrorKind::PermissionDenied));
+
+        let stats = pipeline.metrics().to_json();
+        assert_eq!(stats["capture"]["errors"], 2);
+        assert_eq!(
+            stats["capture"]["recent_errors"][0]["plugin"],
+            "failing-capture"
+        );
+
+        pipeline.stop().await.unwrap();
+    }
+
+    /// Capture plugin with fixed stats
+    struct StatsCapture {
+        name: &'static str,
+        running: bool,
+        stats: CaptureStats,
+    }
+
+    impl PluginInfo for StatsCapture {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for StatsCapture {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl CapturePlugin for StatsCapture {
+        async fn start(&mut self, _tx: mpsc::Sender<RawCaptureEvent>) -> PluginResult<()> {
+            self.running = true;
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> PluginResult<()> {
+            self.running = false;
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            self.running
+        }
+
+        fn stats(&self) -> CaptureStats {
+            self.stats.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_stats_reported_per_plugin() {
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_capture(Box::new(StatsCapture {
+            name: "ebpf",
+            running: false,
+            stats: CaptureStats {
+                events_captured: 120,
+                events_dropped: 3,
+                bytes_captured: 4096,
+                errors: 1,
+                ..Default::default()
+            },
+        }));
+        pipeline.add_capture(Box::new(StatsCapture {
+            name: "test-generator",
+            running: false,
+            stats: CaptureStats {
+                events_captured: 7,
+                ..Default::default()
+            },
+        }));
+        pipeline.start().await.unwrap();
+
+        let stats = pipeline.capture_stats().await;
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].name, "ebpf");
+        assert!(stats[0].available && stats[0].running);
+        assert_eq!(stats[0].stats.events_captured, 120);
+        assert_eq!(stats[0].stats.events_dropped, 3);
+        assert_eq!(stats[0].stats.bytes_captured, 4096);
+        assert_eq!(stats[0].stats.errors, 1);
+        assert_eq!(stats[1].name, "test-generator");
+        assert_eq!(stats[1].stats.events_captured, 7);
+        assert_eq!(stats[1].stats.events_dropped, 0);
+
+        let json = serde_json::to_value(&stats[1]).unwrap();
+        assert_eq!(json["events_captured"], 7);
+        assert_eq!(json["running"], true);
+
+        pipeline.stop().await.unwrap();
+        assert!(!pipeline.capture_stats().await[0].running);
+    }
+
+    /// Records each raw event id, taking `delay` per event
+    struct SlowDecoder {
+        delay: Duration,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PluginInfo for SlowDecoder {
+        fn name(&self) -> &str {
+            "slow-decoder"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for SlowDecoder {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl DecodePlugin for SlowDecoder {
+        fn can_decode(&self, _raw: &RawCaptureEvent) -> bool {
+            true
+        }
+
+        async fn decode(&self, raw: RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
+            tokio::time::sleep(self.delay).await;
+            self.seen.lock().unwrap().push(raw.id);
+            Ok(Vec::new())
+        }
+    }
+
+    /// Sends `count` events as fast as the pipeline accepts them, then
+    /// records how many the decoder had seen by then
+    struct BurstCapture {
+        count: usize,
+        seen: Arc<Mutex<Vec<String>>>,
+        seen_when_done: Arc<Mutex<Option<usize>>>,
+    }
+
+    impl PluginInfo for BurstCapture {
+        fn name(&self) -> &str {
+            "burst-capture"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for BurstCapture {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl CapturePlugin for BurstCapture {
+        async fn start(&mut self, tx: mpsc::Sender<RawCaptureEvent>) -> PluginResult<()> {
+            let count = self.count;
+            let seen = self.seen.clone();
+            let seen_when_done = self.seen_when_done.clone();
+            tokio::spawn(async move {
+                for i in 0..count {
+                    let raw = RawCaptureEvent {
+                        id: i.to_string(),
+                        timestamp_ns: 0,
+                        kind: RawEventKind::SslWrite,
+                        pid: 42,
+                        tid: None,
+                        data: Vec::new(),
+                        metadata: RawEventMetadata::default(),
+                    };
+                    tx.send(raw).await.unwrap();
+                }
+                *seen_when_done.lock().unwrap() = Some(seen.lock().unwrap().len());
+            });
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> PluginResult<()> {
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+    }
+
+    /// Result of a burst into a slow pipeline
+    struct Burst {
+        /// Event ids the decoder saw, in order
+        seen: Vec<usize>,
+        /// Events the decoder had seen when the capture finished sending
+        seen_when_done: usize,
+        dropped: u64,
+    }
+
+    const BURST: usize = 50;
+    const BUFFER: usize = 4;
+
+    async fn burst(policy: ChannelPolicy) -> Burst {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_when_done = Arc::new(Mutex::new(None));
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            raw_buffer_size: BUFFER,
+            channel_policy: policy,
+            ..Default::default()
+        });
+        pipeline.add_decode(Box::new(SlowDecoder {
+            delay: Duration::from_millis(5),
+            seen: seen.clone(),
+        }));
+        pipeline.add_capture(Box::new(BurstCapture {
+            count: BURST,
+            seen: seen.clone(),
+            seen_when_done: seen_when_done.clone(),
+        }));
+        pipeline.start().await.unwrap();
+
+        // Every event is either decoded or counted as dropped
+        let metrics = pipeline.metrics();
+        let dropped = || metrics.pipeline.events_dropped.load(Ordering::Relaxed);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while seen.lock().unwrap().len() + (dropped() as usize) < BURST {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("burst not drained");
+        pipeline.stop().await.unwrap();
+
+        let seen = seen
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        let seen_when_done = seen_when_done.lock().unwrap().unwrap();
+        Burst {
+            seen,
+            seen_when_done,
+            dropped: dropped(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_policy_backpressures_capture() {
+        let burst = burst(ChannelPolicy::Block).await;
+
+        assert_eq!(burst.dropped, 0);
+        assert_eq!(burst.seen, (0..BURST).collect::<Vec<_>>());
+        // The capture could only finish once the decoder had worked through
+        // all but a buffer's worth of events
+        assert!(
+            burst.seen_when_done >= BURST - BUFFER - 2,
+            "capture finished after {} events",
+            burst.seen_when_done
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_policy_keeps_buffered_events() {
+        let burst = burst(ChannelPolicy::DropNewest).await;
+
+        assert!(burst.seen_when_done < BURST / 2, "capture was blocked");
+        assert!(burst.dropped > 0);
+        assert_eq!(burst.seen.len() + burst.dropped as usize, BURST);
+        // The first events get through; the tail of the burst is discarded
+        assert_eq!(burst.seen[0], 0);
+        assert!(!burst.seen.contains(&(BURST - 1)));
+        assert!(burst.seen.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_policy_keeps_latest_events() {
+        let burst = burst(ChannelPolicy::DropOldest).await;
+
+        assert!(burst.seen_when_done < BURST / 2, "capture was blocked");
+        assert!(burst.dropped > 0);
+        assert_eq!(burst.seen.len() + burst.dropped as usize, BURST);
+        // The end of the burst is always delivered
+        assert_eq!(burst.seen[burst.seen.len() - BUFFER..], [46, 47, 48, 49]);
+        assert!(burst.seen.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_channel_policy_parse() {
+        assert_eq!("block".parse(), Ok(ChannelPolicy::Block));
+        assert_eq!("drop-oldest".parse(), Ok(ChannelPolicy::DropOldest));
+        assert_eq!("DROP_NEWEST".parse(), Ok(ChannelPolicy::DropNewest));
+        assert!("drop".parse::<ChannelPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_held_events_flushed_at_shutdown() {
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_decode(Box::new(HoldingDecoder));
+        pipeline.add_export(Box::new(TestExporter {
+            exported: exported.clone(),
+        }));
+
+        pipeline.start().await.unwrap();
+        pipeline.stop().await.unwrap();
+
+        assert_eq!(*exported.lock().unwrap(), vec!["held".to_string()]);
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.pipeline.events_processed.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_raw_event_counted_through_each_stage() {
+        let decoders: Vec<Arc<Box<dyn DecodePlugin>>> = vec![Arc::new(Box::new(TestDecoder))];
+        let exporters: Vec<Arc<Box<dyn ExportPlugin>>> = vec![Arc::new(Box::new(TestExporter {
+            exported: Arc::new(Mutex::new(Vec::new())),
+        }))];
+        let (tx, _rx) = broadcast::channel(16);
+        let metrics = create_metrics();
+
+        let raw = RawCaptureEvent {
+            id: "raw-1".to_string(),
+            timestamp_ns: 0,
+            kind: RawEventKind::SslWrite,
+            pid: 42,
+            tid: None,
+            data: b"hello".to_vec(),
+            metadata: RawEventMetadata::default(),
+        };
+        Pipeline::process_raw_event(
+            raw,
+            &decoders,
+            &[],
+            &[],
+            &exporters,
+            &ExportRetry::default(),
+            None,
+            &tx,
+            &metrics,
+            SchemaValidation::Off,
+            EventIds::Random,
+            &MonoClock::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(metrics.events_captured(), 1);
+        assert_eq!(metrics.capture.bytes_captured.load(Ordering::Relaxed), 5);
+        assert_eq!(metrics.pipeline.events_processed.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.pipeline.events_exported.load(Ordering::Relaxed), 1);
+        let stages: Vec<PipelineStage> =
+            metrics.stages.snapshot().iter().map(|s| s.stage).collect();
+        assert_eq!(stages, PipelineStage::ALL);
+    }
+
+    fn raw_write(id: &str, timestamp_ns: u64, data: &[u8]) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: id.to_string(),
+            timestamp_ns,
+            kind: RawEventKind::SslWrite,
+            pid: 42,
+            tid: None,
+            data: data.to_vec(),
+            metadata: RawEventMetadata::default(),
+        }
+    }
+
+    /// Event ids exported for `raw` by a fresh pipeline, as in a new run
+    async fn exported_ids(raw: RawCaptureEvent, event_ids: EventIds) -> Vec<String> {
+        let decoders: Vec<Arc<Box<dyn DecodePlugin>>> = vec![Arc::new(Box::new(TestDecoder))];
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let exporters: Vec<Arc<Box<dyn ExportPlugin>>> = vec![Arc::new(Box::new(TestExporter {
+            exported: exported.clone(),
+        }))];
+        let (tx, _rx) = broadcast::channel(16);
+        Pipeline::process_raw_event(
+            raw,
+            &decoders,
+            &[],
+            &[],
+            &exporters,
+            &ExportRetry::default(),
+            None,
+            &tx,
+            &create_metrics(),
+            SchemaValidation::Off,
+            event_ids,
+            &MonoClock::default(),
+        )
+        .await
+        .unwrap();
+        let ids = exported.lock().unwrap().clone();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_content_event_ids_are_deterministic() {
+        // Capture plugins give each raw event a random id; replays differ in it
+        let first = exported_ids(
+            raw_write("raw-a", 5_000_000_000, b"hello"),
+            EventIds::Content,
+        )
+        .await;
+        let again = exported_ids(
+            raw_write("raw-b", 5_000_000_000, b"hello"),
+            EventIds::Content,
+        )
+        .await;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first, again);
+
+        let id = ulid::Ulid::from_string(&first[0]).unwrap();
+        assert_eq!(id.timestamp_ms(), 5_000);
+
+        let other_data = exported_ids(
+            raw_write("raw-a", 5_000_000_000, b"world"),
+            EventIds::Content,
+        )
+        .await;
+        let other_time = exported_ids(
+            raw_write("raw-a", 5_000_000_001, b"hello"),
+            EventIds::Content,
+        )
+        .await;
+        assert_ne!(first, other_data);
+        assert_ne!(first, other_time);
+
+        // Random ids stay the default
+        let random = exported_ids(
+            raw_write("raw-a", 5_000_000_000, b"hello"),
+            EventIds::Random,
+        )
+        .await;
+        let random_again = exported_ids(
+            raw_write("raw-a", 5_000_000_000, b"hello"),
+            EventIds::Random,
+        )
+        .await;
+        assert_ne!(random, random_again);
+        assert_eq!(PipelineConfig::default().event_ids, EventIds::Random);
+    }
+
+    #[tokio::test]
+    async fn test_every_event_gets_ts_mono_in_capture_order() {
+        let decoders: Vec<Arc<Box<dyn DecodePlugin>>> = vec![Arc::new(Box::new(TestDecoder))];
+        let (tx, mut rx) = broadcast::channel(64);
+        let mono = MonoClock::new(TsMonoSource::Capture);
+        let wall_now = chrono::Utc::now().timestamp_nanos_opt().unwrap() as u64;
+
+        // Wall-clock captures, as from the macOS helper; TestDecoder leaves
+        // ts_mono unset
+        for (i, offset) in [0u64, 2_000, 2_000, 40_000_000].iter().enumerate() {
+            Pipeline::process_raw_event(
+                raw_write(&format!("raw-{}", i), wall_now + offset, b"x"),
+                &decoders,
+                &[],
+                &[],
+                &[],
+                &ExportRetry::default(),
+                None,
+                &tx,
+                &create_metrics(),
+                SchemaValidation::Off,
+                EventIds::Random,
+                &mono,
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut raw = Vec::new();
+        let mut decoded = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            let ts_mono = event.envelope().ts_mono.expect("ts_mono set");
+            match event.as_ref() {
+                OispEvent::CaptureRaw(_) => raw.push(ts_mono),
+                _ => decoded.push(ts_mono),
+            }
+        }
+        assert_eq!(raw, decoded);
+        assert_eq!(decoded.len(), 4);
+        assert!(decoded.windows(2).all(|w| w[0] <= w[1]), "{:?}", decoded);
+        assert_eq!(decoded[3] - decoded[0], 40_000_000);
+        assert!(decoded[0].abs_diff(mono.now()) < 1_000_000_000);
+    }
+
+    #[test]
+    fn test_content_event_id_distinguishes_events_of_one_capture() {
+        let raw = raw_write("raw-1", 0, b"HTTP/1.1 200 OK");
+        let ids = [
+            content_event_id(&raw, "ai.response", 0),
+            content_event_id(&raw, "ai.response", 1),
+            content_event_id(&raw, "ai.request", 0),
+        ];
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+        assert_eq!(ids[0], content_event_id(&raw, "ai.response", 0));
+        assert_eq!("Content".parse(), Ok(EventIds::Content));
+        assert!("hash".parse::<EventIds>().is_err());
+    }
+
+    fn ai_request(request_id: &str) -> OispEvent {
+        OispEvent::AiRequest(crate::events::AiRequestEvent {
+            envelope: EventEnvelope::new("ai.request"),
+            data: serde_json::from_value(serde_json::json!({"request_id": request_id})).unwrap(),
+        })
+    }
+
+    async fn export_with_validation(mode: SchemaValidation) -> (usize, u64) {
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            schema_validation: mode,
+            ..Default::default()
+        });
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        pipeline.add_export(Box::new(TestExporter {
+            exported: exported.clone(),
+        }));
+
+        pipeline.process_event(ai_request("req-1")).await;
+        // An empty request_id violates the ai.request schema
+        pipeline.process_event(ai_request("")).await;
+
+        let exported = exported.lock().unwrap().len();
+        let invalid = pipeline
+            .metrics()
+            .pipeline
+            .events_invalid
+            .load(Ordering::Relaxed);
+        (exported, invalid)
+    }
+
+    #[tokio::test]
+    async fn test_schema_validation_modes() {
+        assert_eq!(export_with_validation(SchemaValidation::Off).await, (2, 0));
+        assert_eq!(export_with_validation(SchemaValidation::Warn).await, (2, 1));
+        assert_eq!(
+            export_with_validation(SchemaValidation::Strict).await,
+            (1, 1)
+        );
+    }
+
+    /// Exporter that rejects every event, counting the attempts
+    struct FailingExporter {
+        name: &'static str,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl PluginInfo for FailingExporter {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for FailingExporter {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl ExportPlugin for FailingExporter {
+        async fn export(&self, _event: &OispEvent) -> PluginResult<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(PluginError::OperationFailed("connection refused".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_rejected_by_all_exporters_is_dead_lettered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead-letter.jsonl");
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            export_attempts: 3,
+            export_retry_delay: Duration::ZERO,
+            ..Default::default()
+        });
+        pipeline.set_dead_letter(DeadLetterSink::open(&path).unwrap());
+        let calls = Arc::new(AtomicUsize::new(0));
+        for name in ["otlp-exporter", "kafka-exporter"] {
+            pipeline.add_export(Box::new(FailingExporter {
+                name,
+                calls: calls.clone(),
+            }));
+        }
+
+        let event = ai_request("req-1");
+        let event_id = event.envelope().event_id.clone();
+        pipeline.process_event(event).await;
+
+        // Both exporters tried on each of the 3 attempts
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.pipeline.events_exported.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            metrics
+                .pipeline
+                .events_dead_lettered
+                .load(Ordering::Relaxed),
+            1
+        );
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: crate::dead_letter::DeadLetterRecord = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record.attempts, 3);
+        assert_eq!(
+            record.reason,
+            "otlp-exporter: Plugin operation failed: connection refused; \
+             kafka-exporter: Plugin operation failed: connection refused"
+        );
+        assert_eq!(record.event.envelope().event_id, event_id);
+        assert_eq!(record.event.event_type(), "ai.request");
+    }
+
+    #[tokio::test]
+    async fn test_event_accepted_by_one_exporter_is_not_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead-letter.jsonl");
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            export_attempts: 3,
+            export_retry_delay: Duration::ZERO,
+            ..Default::default()
+        });
+        pipeline.set_dead_letter(DeadLetterSink::open(&path).unwrap());
+        let calls = Arc::new(AtomicUsize::new(0));
+        pipeline.add_export(Box::new(FailingExporter {
+            name: "otlp-exporter",
+            calls: calls.clone(),
+        }));
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        pipeline.add_export(Box::new(TestExporter {
+            exported: exported.clone(),
+        }));
+
+        pipeline.process_event(ai_request("req-1")).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(exported.lock().unwrap().len(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    /// Enricher that only declares dependencies
+    struct DeclaredEnricher {
+        name: &'static str,
+        provides: &'static [&'static str],
+        requires: &'static [&'static str],
+    }
+
+    impl PluginInfo for DeclaredEnricher {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for DeclaredEnricher {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl EnrichPlugin for DeclaredEnricher {
+        async fn enrich(&self, _event: &mut OispEvent) -> PluginResult<()> {
+            Ok(())
+        }
+
+        fn provides(&self) -> &[&'static str] {
+            self.provides
+        }
+
+        fn requires(&self) -> &[&'static str] {
+            self.requires
+        }
+    }
+
+    fn enricher(
+        name: &'static str,
+        provides: &'static [&'static str],
+        requires: &'static [&'static str],
+    ) -> Box<dyn EnrichPlugin> {
+        Box::new(DeclaredEnricher {
+            name,
+            provides,
+            requires,
+        })
+    }
+
+    fn enrich_order(pipeline: &Pipeline) -> Vec<&str> {
+        pipeline.enrich_plugins.iter().map(|p| p.name()).collect()
+    }
+
+    #[test]
+    fn test_enrichers_ordered_by_dependencies() {
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_enrich(enricher("k8s", &["k8s"], &["process.container_id"]));
+        pipeline.add_enrich(enricher("host", &["host"], &[]));
+        pipeline.add_enrich(enricher("app", &["app"], &["process.exe"]));
+        pipeline.add_enrich(enricher(
+            "process",
+            &["process.exe", "process.container_id"],
+            &[],
+        ));
+        pipeline.resolve_enrich_order().unwrap();
+
+        // Independent enrichers keep their add order
+        assert_eq!(enrich_order(&pipeline), ["host", "process", "k8s", "app"]);
+    }
+
+    #[test]
+    fn test_enricher_cycle_and_missing_dependency_rejected() {
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_enrich(enricher("a", &["a"], &["b"]));
+        pipeline.add_enrich(enricher("b", &["b"], &["c"]));
+        pipeline.add_enrich(enricher("c", &["c"], &["a"]));
+        pipeline.add_enrich(enricher("host", &["host"], &[]));
+        let err = pipeline.resolve_enrich_order().unwrap_err().to_string();
+        assert!(err.contains("cycle"), "{}", err);
+        assert!(err.contains("a, b, c") && !err.contains("host"), "{}", err);
+
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_enrich(enricher("k8s", &["k8s"], &["process.container_id"]));
+        let err = pipeline.resolve_enrich_order().unwrap_err().to_string();
+        assert!(err.contains("k8s requires process.container_id"), "{}", err);
+    }
+}
@@ -4,8 +4,15 @@
 //! is defined as a trait, enabling extensibility and custom implementations.
 
 use crate::events::OispEvent;
+use crate::monotonic::{CaptureClock, MonoClock};
+use crate::wire_diagnostics::ParseErrorKind;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 use tokio::sync::mpsc;
 
@@ -30,6 +37,9 @@ pub enum PluginError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Schema error: {0}")]
+    SchemaError(#[from] crate::events::SchemaError),
+
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
@@ -191,6 +201,8 @@ pub struct RawEventMetadata {
     pub bundle_id: Option<String>,
     /// Additional data
     pub extra: std::collections::HashMap<String, serde_json::Value>,
+    /// Clock `timestamp_ns` was taken from
+    pub clock: CaptureClock,
 }
 
 /// Capture plugin - produces raw events from system capture
@@ -209,10 +221,167 @@ pub trait CapturePlugin: Plugin {
     fn stats(&self) -> CaptureStats {
         CaptureStats::default()
     }
+
+    /// Receive the sender for structured capture errors
+    ///
+    /// Called by the pipeline before `start`. Plugins that can fail after
+    /// starting (lost probes, exited helpers, read errors) should keep the
+    /// sender and report through it instead of only logging.
+    fn set_error_sender(&mut self, errors: CaptureErrorSender) {
+        let _ = errors;
+    }
+
+    /// Handle for attaching to and detaching from processes while running
+    ///
+    /// `None` for plugins that can't change their process filter at runtime.
+    fn process_targets(&self) -> Option<SharedProcessTargets> {
+        None
+    }
+
+    /// Whether the first outbound bytes of each connection are reported as
+    /// [`RawEventKind::NetworkSend`], which SNI extraction needs
+    fn reports_network_send(&self) -> bool {
+        false
+    }
+
+    /// When the plugin last showed its capture source is alive, whether or
+    /// not it captured anything
+    ///
+    /// Lets the pipeline's watchdog tell an idle plugin from a stalled one
+    /// and restart the latter. `None` (the default) opts out of the watchdog.
+    fn last_heartbeat(&self) -> Option<Instant> {
+        None
+    }
+}
+
+/// Runtime control over which processes a capture plugin traces
+pub trait ProcessTargetControl: Send + Sync {
+    /// Start tracing a process
+    fn attach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError>;
+
+    /// Stop tracing a process previously attached
+    ///
+    /// Only possible while capture is filtered by process; otherwise every
+    /// process is traced anyway and this fails with
+    /// [`ProcessTargetError::NotFiltered`].
+    fn detach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError>;
+
+    /// Current process filter
+    fn targets(&self) -> ProcessTargets;
+}
+
+/// Shared handle to a plugin's [`ProcessTargetControl`]
+pub type SharedProcessTargets = Arc<dyn ProcessTargetControl>;
+
+/// Processes a capture plugin has been told to trace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProcessTargets {
+    /// Whether capture is limited to `pids`; when false every process is
+    /// traced and `pids` only lists processes with their own probes
+    pub filtered: bool,
+    pub pids: Vec<u32>,
+    /// TLS libraries probed for each pid attached at runtime; a process
+    /// can load more than one (e.g. libssl.so.1.1 and libssl.so.3)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub libraries: BTreeMap<u32, Vec<String>>,
+}
+
+/// Why a process could not be attached or detached
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ProcessTargetError {
+    #[error("Process {0} does not exist")]
+    NoSuchProcess(u32),
+
+    #[error("Process {0} has no OpenSSL library loaded")]
+    NoTlsLibrary(u32),
+
+    #[error("Process {0} is not attached")]
+    NotAttached(u32),
+
+    #[error("Capture is not running")]
+    NotRunning,
+
+    #[error("Capture is not filtered by process, so every process is traced")]
+    NotFiltered,
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Category of a capture error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureErrorKind {
+    /// Plugin failed to start
+    Start,
+    /// Missing privileges (root, CAP_BPF, entitlements)
+    PermissionDenied,
+    /// Probe or hook could not be attached
+    AttachFailed,
+    /// Reading from the capture source failed
+    Io,
+    /// Capture source stopped unexpectedly
+    Stopped,
+    /// No events or heartbeats for the watchdog's stall timeout
+    Stalled,
+    /// Anything else
+    Other,
+}
+
+/// A structured error reported by a capture plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureError {
+    /// Name of the plugin that reported the error
+    pub plugin: String,
+    pub kind: CaptureErrorKind,
+    /// Human-readable, actionable message
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl CaptureError {
+    pub fn new(
+        plugin: impl Into<String>,
+        kind: CaptureErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            plugin: plugin.into(),
+            kind,
+            message: message.into(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Bounded sender for [`CaptureError`] records
+///
+/// Reporting never blocks: when the channel is full the error is dropped,
+/// so a flood of errors cannot stall capture.
+#[derive(Debug, Clone)]
+pub struct CaptureErrorSender {
+    plugin: String,
+    tx: mpsc::Sender<CaptureError>,
+}
+
+impl CaptureErrorSender {
+    pub fn new(plugin: impl Into<String>, tx: mpsc::Sender<CaptureError>) -> Self {
+        Self {
+            plugin: plugin.into(),
+            tx,
+        }
+    }
+
+    /// Report an error; returns false if it was dropped
+    pub fn report(&self, kind: CaptureErrorKind, message: impl Into<String>) -> bool {
+        self.tx
+            .try_send(CaptureError::new(self.plugin.clone(), kind, message))
+            .is_ok()
+    }
 }
 
 /// Capture statistics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CaptureStats {
     /// Total events captured
     pub events_captured: u64,
@@ -222,6 +391,22 @@ pub struct CaptureStats {
     pub bytes_captured: u64,
     /// Errors encountered
     pub errors: u64,
+    /// Transport parse failures by kind, for plugins fed by a capture helper
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub parse_errors: BTreeMap<ParseErrorKind, u64>,
+}
+
+/// Statistics of a single capture plugin
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturePluginStats {
+    /// Plugin name
+    pub name: String,
+    /// Whether the plugin is available on this platform
+    pub available: bool,
+    /// Whether the plugin is currently capturing
+    pub running: bool,
+    #[serde(flatten)]
+    pub stats: CaptureStats,
 }
 
 // =============================================================================
@@ -242,6 +427,23 @@ pub trait DecodePlugin: Plugin {
     fn priority(&self) -> i32 {
         0
     }
+
+    /// Events due because time passed, e.g. state held for input that
+    /// never came; the pipeline calls this about once a second
+    ///
+    /// Events derived from a capture should have `ts_mono` stamped with
+    /// [`MonoClock::stamp_decoded`]; the rest are stamped with the time
+    /// they are emitted.
+    fn tick(&self, mono: &MonoClock) -> Vec<OispEvent> {
+        let _ = mono;
+        Vec::new()
+    }
+
+    /// Events still held back, emitted when the pipeline stops
+    fn flush(&self, mono: &MonoClock) -> Vec<OispEvent> {
+        let _ = mono;
+        Vec::new()
+    }
 }
 
 // =============================================================================
@@ -249,6 +451,10 @@ pub trait DecodePlugin: Plugin {
 // =============================================================================
 
 /// Enrich plugin - adds context to events
+///
+/// Enrichers declare the fields they fill in and the fields they read that
+/// other enrichers fill in (e.g. `"process.container_id"`). The pipeline runs
+/// each enricher after every enricher providing a field it requires.
 #[async_trait]
 pub trait EnrichPlugin: Plugin {
     /// Enrich an event with additional context
@@ -259,6 +465,16 @@ pub trait EnrichPlugin: Plugin {
         let _ = event;
         true
     }
+
+    /// Fields this enricher fills in
+    fn provides(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Fields this enricher reads that another enricher must fill in first
+    fn requires(&self) -> &[&'static str] {
+        &[]
+    }
 }
 
 // =============================================================================
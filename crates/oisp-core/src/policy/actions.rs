@@ -274,7 +274,9 @@ impl ActionExecutor {
             redact_credit_cards: patterns.iter().any(|p| p == "credit_card" || p == "cc"),
             redact_ssn: patterns.iter().any(|p| p == "ssn"),
             redact_phone_numbers: patterns.iter().any(|p| p == "phone"),
+            redact_image_data: patterns.iter().any(|p| p == "image_data"),
             custom_patterns: custom_patterns.to_vec(),
+            rules: Vec::new(),
         };
 
         // If "all" pattern is specified, enable all built-in patterns
@@ -284,6 +286,7 @@ impl ActionExecutor {
             config.redact_credit_cards = true;
             config.redact_ssn = true;
             config.redact_phone_numbers = true;
+            config.redact_image_data = true;
         }
 
         // Apply redaction to each specified field
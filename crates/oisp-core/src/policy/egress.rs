@@ -0,0 +1,254 @@
+//! Egress allowlist - flags connections to domains outside an allowlist
+//!
+//! Configured from the `settings.egress` section of the policy file:
+//!
+//! ```yaml
+//! settings:
+//!   egress:
+//!     mode: alert
+//!     allowlist:
+//!       - api.openai.com
+//!       - "*.anthropic.com"
+//! ```
+//!
+//! Detection only: `network.connect` events to a domain outside the
+//! allowlist are tagged with the `egress_violation` attribute and raise an
+//! alert, but the connection itself is not blocked.
+
+use super::{AlertSeverity, PolicyAlert};
+use crate::events::OispEvent;
+use crate::providers::ProviderRegistry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Policy ID reported on egress alerts
+pub const EGRESS_POLICY_ID: &str = "egress-allowlist";
+
+/// Event attribute set on connections that violate the allowlist
+pub const EGRESS_VIOLATION_ATTR: &str = "egress_violation";
+
+/// Egress enforcement mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EgressMode {
+    /// Allowlist is not checked (default)
+    #[default]
+    Off,
+    /// Tag and alert on connections outside the allowlist
+    Alert,
+}
+
+/// Egress allowlist settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EgressSettings {
+    /// Enforcement mode
+    #[serde(default)]
+    pub mode: EgressMode,
+
+    /// Allowed domains; exact names or `*.suffix` wildcards
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+
+    /// Only check connections to known AI provider domains
+    #[serde(default = "default_true")]
+    pub ai_domains_only: bool,
+
+    /// Severity of raised alerts
+    #[serde(default)]
+    pub severity: AlertSeverity,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for EgressSettings {
+    fn default() -> Self {
+        Self {
+            mode: EgressMode::Off,
+            allowlist: vec![],
+            ai_domains_only: true,
+            severity: AlertSeverity::Warning,
+        }
+    }
+}
+
+impl EgressSettings {
+    /// Whether a domain matches the allowlist
+    pub fn is_allowed(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        self.allowlist.iter().any(|pattern| {
+            let pattern = pattern.to_ascii_lowercase();
+            match pattern.strip_prefix("*.") {
+                Some(suffix) => domain
+                    .strip_suffix(suffix)
+                    .is_some_and(|rest| rest.ends_with('.')),
+                None => domain == pattern,
+            }
+        })
+    }
+}
+
+/// Egress allowlist checker
+pub struct EgressPolicy {
+    settings: EgressSettings,
+    providers: ProviderRegistry,
+}
+
+impl EgressPolicy {
+    /// Create from settings
+    pub fn new(settings: EgressSettings) -> Self {
+        Self {
+            settings,
+            providers: ProviderRegistry::new(),
+        }
+    }
+
+    /// Current settings
+    pub fn settings(&self) -> &EgressSettings {
+        &self.settings
+    }
+
+    /// Check a `network.connect` event against the allowlist
+    ///
+    /// Tags a violating event and returns the alert for it. Other event
+    /// types, connections without a known domain, and (by default)
+    /// non-AI domains are never flagged.
+    pub fn check(&self, event: &mut OispEvent) -> Option<PolicyAlert> {
+        if self.settings.mode == EgressMode::Off {
+            return None;
+        }
+
+        let OispEvent::NetworkConnect(connect) = event else {
+            return None;
+        };
+
+        let domain = connect
+            .data
+            .dest
+            .domain
+            .clone()
+            .or_else(|| connect.data.tls.as_ref().and_then(|t| t.sni.clone()))?;
+
+        if self.settings.ai_domains_only && !self.providers.is_ai_domain(&domain) {
+            return None;
+        }
+        if self.settings.is_allowed(&domain) {
+            return None;
+        }
+
+        connect
+            .envelope
+            .attrs
+            .insert(EGRESS_VIOLATION_ATTR.to_string(), true.into());
+
+        let mut context = HashMap::new();
+        context.insert("domain".to_string(), domain.clone().into());
+        context.insert("event_type".to_string(), "network.connect".into());
+        if let Some(process) = &connect.envelope.process {
+            context.insert("pid".to_string(), process.pid.into());
+        }
+
+        Some(PolicyAlert {
+            id: ulid::Ulid::new().to_string(),
+            policy_id: EGRESS_POLICY_ID.to_string(),
+            severity: self.settings.severity,
+            message: format!("Connection to non-allowlisted domain {}", domain),
+            event_id: connect.envelope.event_id.clone(),
+            timestamp: chrono::Utc::now(),
+            context,
+        })
+    }
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self::new(EgressSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Endpoint, EventEnvelope, NetworkConnectData, NetworkConnectEvent};
+
+    fn connect_event(domain: &str) -> OispEvent {
+        OispEvent::NetworkConnect(NetworkConnectEvent {
+            envelope: EventEnvelope::new("network.connect"),
+            data: NetworkConnectData {
+                dest: Endpoint {
+                    ip: Some("104.18.7.192".to_string()),
+                    port: Some(443),
+                    domain: Some(domain.to_string()),
+                    is_private: None,
+                    geo: None,
+                },
+                src: None,
+                protocol: None,
+                success: None,
+                error: None,
+                latency_ms: None,
+                tls: None,
+            },
+        })
+    }
+
+    fn policy() -> EgressPolicy {
+        EgressPolicy::new(EgressSettings {
+            mode: EgressMode::Alert,
+            allowlist: vec!["api.openai.com".to_string(), "*.anthropic.com".to_string()],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_allowlist_matching() {
+        let settings = policy().settings;
+        assert!(settings.is_allowed("api.openai.com"));
+        assert!(settings.is_allowed("API.OpenAI.com."));
+        assert!(settings.is_allowed("api.anthropic.com"));
+        assert!(!settings.is_allowed("anthropic.com"));
+        assert!(!settings.is_allowed("evilanthropic.com"));
+        assert!(!settings.is_allowed("api.mistral.ai"));
+    }
+
+    #[test]
+    fn test_disallowed_domain_flagged() {
+        let mut event = connect_event("api.mistral.ai");
+        let alert = policy().check(&mut event).expect("should be flagged");
+
+        assert_eq!(alert.policy_id, EGRESS_POLICY_ID);
+        assert_eq!(alert.event_id, event.envelope().event_id);
+        assert_eq!(alert.context["domain"], "api.mistral.ai");
+        assert_eq!(
+            event.envelope().attrs.get(EGRESS_VIOLATION_ATTR),
+            Some(&serde_json::json!(true))
+        );
+    }
+
+    #[test]
+    fn test_allowed_domain_not_flagged() {
+        for domain in ["api.openai.com", "api.anthropic.com"] {
+            let mut event = connect_event(domain);
+            assert!(policy().check(&mut event).is_none(), "{}", domain);
+            assert!(!event.envelope().attrs.contains_key(EGRESS_VIOLATION_ATTR));
+        }
+    }
+
+    #[test]
+    fn test_scope_and_mode() {
+        // Non-AI domains are ignored unless ai_domains_only is off
+        let mut event = connect_event("example.com");
+        assert!(policy().check(&mut event).is_none());
+
+        let all = EgressPolicy::new(EgressSettings {
+            ai_domains_only: false,
+            ..policy().settings
+        });
+        assert!(all.check(&mut event).is_some());
+
+        // Off mode never flags
+        let mut event = connect_event("api.mistral.ai");
+        assert!(EgressPolicy::default().check(&mut event).is_none());
+    }
+}
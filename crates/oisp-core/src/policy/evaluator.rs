@@ -7,9 +7,11 @@
 //! 4. Executes the first matching policy's action
 
 use super::actions::{ActionExecutor, PolicyActionType};
+use super::egress::{EgressPolicy, EgressSettings};
 use super::parser::Policy;
-use super::{DefaultAction, PolicyResult};
+use super::{DefaultAction, PolicyAlert, PolicyResult};
 use crate::events::OispEvent;
+use std::cmp::Reverse;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info, trace};
@@ -35,6 +37,8 @@ pub struct PolicyEvaluator {
     action_executor: ActionExecutor,
     /// Default action when no policy matches
     default_action: DefaultAction,
+    /// Egress allowlist
+    egress: RwLock<EgressPolicy>,
     /// Enable debug logging
     debug: bool,
 }
@@ -48,12 +52,13 @@ impl PolicyEvaluator {
     ) -> Self {
         let mut sorted = policies;
         // Sort by priority (higher first)
-        sorted.sort_by(|a, b| b.priority.cmp(&a.priority));
+        sorted.sort_by_key(|p| Reverse(p.priority));
 
         Self {
             policies: Arc::new(RwLock::new(sorted)),
             action_executor: ActionExecutor::new(webhook_url),
             default_action,
+            egress: RwLock::new(EgressPolicy::default()),
             debug: false,
         }
     }
@@ -67,12 +72,27 @@ impl PolicyEvaluator {
     /// Update policies (used for hot-reload)
     pub async fn update_policies(&self, policies: Vec<Policy>) {
         let mut sorted = policies;
-        sorted.sort_by(|a, b| b.priority.cmp(&a.priority));
+        sorted.sort_by_key(|p| Reverse(p.priority));
         let count = sorted.len();
         *self.policies.write().await = sorted;
         info!(count = count, "Policies updated");
     }
 
+    /// Update egress allowlist settings (used for hot-reload)
+    pub async fn update_egress(&self, settings: EgressSettings) {
+        *self.egress.write().await = EgressPolicy::new(settings);
+    }
+
+    /// Get current egress allowlist settings
+    pub async fn egress_settings(&self) -> EgressSettings {
+        self.egress.read().await.settings().clone()
+    }
+
+    /// Check an event against the egress allowlist, tagging it on a violation
+    pub async fn check_egress(&self, event: &mut OispEvent) -> Option<PolicyAlert> {
+        self.egress.read().await.check(event)
+    }
+
     /// Get current policy count
     pub async fn policy_count(&self) -> usize {
         self.policies.read().await.len()
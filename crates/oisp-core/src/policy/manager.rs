@@ -6,6 +6,7 @@
 //! - Default policy generation
 //! - Policy validation
 
+use super::egress::EgressSettings;
 use super::evaluator::PolicyEvaluator;
 use super::parser::{example_policy_file, parse_policies, parse_policies_file, Policy};
 use super::{DefaultAction, PolicyConfig};
@@ -94,7 +95,7 @@ impl PolicyManager {
     /// Create a new policy manager
     pub async fn new(config: PolicyManagerConfig) -> Result<Self, PolicyManagerError> {
         // Load initial policies
-        let policies = Self::load_policies_from_file(&config).await?;
+        let (policies, egress) = Self::load_policies_from_file(&config).await?;
 
         info!(
             path = %config.policy_file.display(),
@@ -107,6 +108,7 @@ impl PolicyManager {
             config.default_action,
             config.webhook_url.clone(),
         ));
+        evaluator.update_egress(egress).await;
 
         let mut manager = Self {
             config,
@@ -129,10 +131,10 @@ impl PolicyManager {
         Self::new(PolicyManagerConfig::from(config)).await
     }
 
-    /// Load policies from file
+    /// Load enabled policies and egress settings from file
     async fn load_policies_from_file(
         config: &PolicyManagerConfig,
-    ) -> Result<Vec<Policy>, PolicyManagerError> {
+    ) -> Result<(Vec<Policy>, EgressSettings), PolicyManagerError> {
         let path = &config.policy_file;
 
         // Check if file exists
@@ -148,7 +150,7 @@ impl PolicyManager {
                     path = %path.display(),
                     "Policy file not found, using empty policy set"
                 );
-                return Ok(vec![]);
+                return Ok((vec![], EgressSettings::default()));
             }
         }
 
@@ -161,7 +163,7 @@ impl PolicyManager {
         let enabled_policies: Vec<Policy> =
             file.policies.into_iter().filter(|p| p.enabled).collect();
 
-        Ok(enabled_policies)
+        Ok((enabled_policies, file.settings.egress))
     }
 
     /// Create a default policy file
@@ -290,6 +292,7 @@ impl PolicyManager {
                         );
 
                         evaluator.update_policies(enabled).await;
+                        evaluator.update_egress(file.settings.egress).await;
                         *last_hash.write().await = Some(new_hash);
                     }
                     Err(e) => {
@@ -318,8 +321,9 @@ impl PolicyManager {
             ..self.config.clone()
         };
 
-        let policies = Self::load_policies_from_file(&config).await?;
+        let (policies, egress) = Self::load_policies_from_file(&config).await?;
         self.evaluator.update_policies(policies).await;
+        self.evaluator.update_egress(egress).await;
 
         // Update hash
         if let Ok(content) = std::fs::read_to_string(&self.config.policy_file) {
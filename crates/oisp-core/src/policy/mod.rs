@@ -42,10 +42,14 @@
 //!       type: block
 //!       reason: "Blocked: unknown application"
 //! ```
+//!
+//! The optional `settings.egress` section configures an egress allowlist;
+//! see [`egress`].
 
 pub mod actions;
 pub mod audit;
 pub mod condition;
+pub mod egress;
 pub mod evaluator;
 pub mod manager;
 pub mod parser;
@@ -55,6 +59,7 @@ pub mod plugin;
 pub use actions::{ActionExecutor, ActionResult, PolicyAction, PolicyActionType};
 pub use audit::{AuditEvent, AuditLogger, AuditSeverity};
 pub use condition::{Condition, ConditionOp, FieldPath};
+pub use egress::{EgressMode, EgressPolicy, EgressSettings};
 pub use evaluator::{EvaluationResult, PolicyEvaluator};
 pub use manager::{PolicyManager, PolicyManagerConfig};
 pub use parser::{parse_policies, Policy, PolicyFile};
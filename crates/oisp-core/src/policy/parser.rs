@@ -4,6 +4,7 @@
 
 use super::actions::PolicyAction;
 use super::condition::Condition;
+use super::egress::EgressSettings;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use thiserror::Error;
@@ -53,6 +54,10 @@ pub struct PolicyFileSettings {
     /// Default action when no policy matches
     #[serde(default)]
     pub default_action: Option<String>,
+
+    /// Egress allowlist
+    #[serde(default)]
+    pub egress: EgressSettings,
 }
 
 /// A single policy definition
@@ -15,7 +15,7 @@ use crate::plugins::{
 use async_trait::async_trait;
 use std::any::Any;
 use std::sync::Arc;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
 
 /// Policy plugin - evaluates events against policies and executes actions
 pub struct PolicyPlugin {
@@ -205,7 +205,7 @@ impl Plugin for PolicyPlugin {
 
 #[async_trait]
 impl ActionPlugin for PolicyPlugin {
-    async fn process(&self, event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
+    async fn process(&self, mut event: OispEvent) -> PluginResult<(OispEvent, EventAction)> {
         // If not initialized, pass through
         let manager = match &self.manager {
             Some(m) => m,
@@ -225,6 +225,24 @@ impl ActionPlugin for PolicyPlugin {
             "Evaluating event against policies"
         );
 
+        // Egress allowlist: tags the event and raises an alert, never blocks
+        let egress_alert = evaluator.check_egress(&mut event).await;
+        if let Some(ref alert) = egress_alert {
+            warn!(
+                event_id = event_id,
+                domain = ?alert.context.get("domain"),
+                "Egress allowlist violation"
+            );
+            if let Some(ref logger) = self.audit_logger {
+                logger.log_alert(alert, &event).await;
+            }
+        }
+        let pass = if egress_alert.is_some() {
+            EventAction::Modified
+        } else {
+            EventAction::Pass
+        };
+
         // Evaluate and execute
         let result = evaluator.evaluate_and_execute(event.clone()).await;
 
@@ -270,7 +288,7 @@ impl ActionPlugin for PolicyPlugin {
                     action = ?result.action,
                     "Event allowed by policy"
                 );
-                Ok((event, EventAction::Pass))
+                Ok((event, pass))
             }
             PolicyActionType::Redact => {
                 // For redact, we need to get the modified event from the action executor
@@ -296,7 +314,7 @@ impl ActionPlugin for PolicyPlugin {
                     }
                 }
 
-                Ok((event, EventAction::Pass))
+                Ok((event, pass))
             }
         }
     }
@@ -462,4 +480,66 @@ policies:
         let content = std::fs::read_to_string(&audit_file).unwrap_or_default();
         assert!(content.contains("allow-all") || content.contains("allow"));
     }
+
+    #[tokio::test]
+    async fn test_policy_plugin_egress_allowlist() {
+        use crate::events::{Endpoint, NetworkConnectData, NetworkConnectEvent};
+        use crate::policy::egress::EGRESS_VIOLATION_ATTR;
+
+        let dir = tempdir().unwrap();
+        let policy_file = dir.path().join("policies.yaml");
+
+        let yaml = r#"
+version: "1"
+policies: []
+settings:
+  egress:
+    mode: alert
+    allowlist:
+      - api.openai.com
+"#;
+        std::fs::write(&policy_file, yaml).unwrap();
+
+        let config = PolicyConfig {
+            policy_file,
+            hot_reload: false,
+            audit_enabled: false,
+            ..Default::default()
+        };
+        let plugin = PolicyPlugin::create_initialized(config).await.unwrap();
+
+        let connect = |domain: &str| {
+            OispEvent::NetworkConnect(NetworkConnectEvent {
+                envelope: EventEnvelope::new("network.connect"),
+                data: NetworkConnectData {
+                    dest: Endpoint {
+                        ip: None,
+                        port: Some(443),
+                        domain: Some(domain.to_string()),
+                        is_private: None,
+                        geo: None,
+                    },
+                    src: None,
+                    protocol: None,
+                    success: None,
+                    error: None,
+                    latency_ms: None,
+                    tls: None,
+                },
+            })
+        };
+
+        // Disallowed AI domain is flagged but still passed through
+        let (event, action) = plugin.process(connect("api.anthropic.com")).await.unwrap();
+        assert!(matches!(action, EventAction::Modified));
+        assert_eq!(
+            event.envelope().attrs.get(EGRESS_VIOLATION_ATTR),
+            Some(&serde_json::json!(true))
+        );
+
+        // Allowlisted domain is untouched
+        let (event, action) = plugin.process(connect("api.openai.com")).await.unwrap();
+        assert!(matches!(action, EventAction::Pass));
+        assert!(!event.envelope().attrs.contains_key(EGRESS_VIOLATION_ATTR));
+    }
 }
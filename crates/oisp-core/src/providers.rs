@@ -122,7 +122,8 @@ impl ProviderRegistry {
                 "generativelanguage.googleapis.com".into(),
                 "aiplatform.googleapis.com".into(),
             ],
-            domain_patterns: vec![],
+            // Vertex AI regional endpoints, e.g. us-central1-aiplatform.googleapis.com
+            domain_patterns: vec!["*-aiplatform.googleapis.com".into()],
             api_key_prefixes: vec![],
             auth_header: None,
         });
@@ -330,6 +331,11 @@ impl ProviderRegistry {
             .map(|(_, provider)| provider)
     }
 
+    /// Number of built-in providers
+    pub fn provider_count(&self) -> usize {
+        self.providers.len()
+    }
+
     /// Get config for a provider
     pub fn get_config(&self, provider: Provider) -> Option<&ProviderConfig> {
         self.providers.iter().find(|c| c.provider == provider)
@@ -402,6 +408,10 @@ mod tests {
             registry.detect_from_domain("myinstance.openai.azure.com"),
             Some(Provider::AzureOpenAI)
         );
+        assert_eq!(
+            registry.detect_from_domain("europe-west4-aiplatform.googleapis.com"),
+            Some(Provider::Google)
+        );
     }
 
     #[test]
@@ -2,7 +2,9 @@
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
+use std::ops::Range;
 use std::sync::LazyLock;
 
 /// Redaction mode
@@ -19,6 +21,38 @@ pub enum RedactionMode {
     Minimal,
 }
 
+impl RedactionMode {
+    /// How much content the mode keeps, for comparing modes
+    fn retained(self) -> u8 {
+        match self {
+            RedactionMode::Minimal => 0,
+            RedactionMode::Safe => 1,
+            RedactionMode::Full => 2,
+        }
+    }
+
+    /// Whether events redacted in this mode can still be redacted to `other`
+    pub fn keeps_more_than(self, other: RedactionMode) -> bool {
+        self.retained() > other.retained()
+    }
+}
+
+/// The configuration that keeps the most content
+///
+/// With per-exporter redaction the action stage runs this one, so every
+/// exporter can redact its own copy further.
+pub fn least_redacting<'a>(
+    configs: impl IntoIterator<Item = &'a RedactionConfig>,
+) -> Option<&'a RedactionConfig> {
+    configs.into_iter().reduce(|richest, config| {
+        if config.mode.keeps_more_than(richest.mode) {
+            config
+        } else {
+            richest
+        }
+    })
+}
+
 /// Redaction configuration
 #[derive(Debug, Clone)]
 pub struct RedactionConfig {
@@ -28,7 +62,11 @@ pub struct RedactionConfig {
     pub redact_credit_cards: bool,
     pub redact_ssn: bool,
     pub redact_phone_numbers: bool,
+    /// Replace base64 `data:image/...` URLs in content, keeping events small
+    pub redact_image_data: bool,
     pub custom_patterns: Vec<String>,
+    /// Named patterns with their own replacement, applied after the others
+    pub rules: Vec<RedactionRule>,
 }
 
 impl Default for RedactionConfig {
@@ -40,8 +78,57 @@ impl Default for RedactionConfig {
             redact_credit_cards: true,
             redact_ssn: true,
             redact_phone_numbers: false,
+            redact_image_data: true,
             custom_patterns: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// A named custom pattern with its own replacement, compiled once
+#[derive(Debug, Clone)]
+pub struct RedactionRule {
+    /// Reported as the finding type
+    pub name: String,
+    pub pattern: Regex,
+    /// Replacement template; `$1` or `${group}` insert capture groups
+    pub replacement: String,
+    /// Event fields the rule applies to, as dotted paths under `data` where
+    /// `*` matches any one segment (e.g. `messages.*.content`); empty means
+    /// everywhere
+    pub fields: Vec<String>,
+}
+
+impl RedactionRule {
+    pub fn new(
+        name: impl Into<String>,
+        pattern: &str,
+        replacement: impl Into<String>,
+        fields: Vec<String>,
+    ) -> Result<Self, regex::Error> {
+        Ok(Self {
+            name: name.into(),
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.into(),
+            fields,
+        })
+    }
+
+    /// Whether the rule applies to a field; rules limited to fields never
+    /// apply to content redacted outside an event (`field` is `None`)
+    pub fn applies_to(&self, field: Option<&str>) -> bool {
+        if self.fields.is_empty() {
+            return true;
         }
+        let Some(field) = field else {
+            return false;
+        };
+        self.fields.iter().any(|pattern| {
+            let pattern: Vec<&str> = pattern.split('.').collect();
+            let field: Vec<&str> = field.split('.').collect();
+            pattern.len() == field.len()
+                && pattern.iter().zip(&field).all(|(p, f)| *p == "*" || p == f)
+        })
     }
 }
 
@@ -56,6 +143,7 @@ pub struct RedactionPatterns {
     pub aws_keys: Regex,
     pub github_tokens: Regex,
     pub slack_tokens: Regex,
+    pub image_data: Regex,
 }
 
 static PATTERNS: LazyLock<RedactionPatterns> = LazyLock::new(|| {
@@ -84,6 +172,7 @@ static PATTERNS: LazyLock<RedactionPatterns> = LazyLock::new(|| {
         aws_keys: Regex::new(r"AKIA[0-9A-Z]{16}").unwrap(),
         github_tokens: Regex::new(r"gh[pousr]_[a-zA-Z0-9]{36,}").unwrap(),
         slack_tokens: Regex::new(r"xox[baprs]-[0-9a-zA-Z-]+").unwrap(),
+        image_data: Regex::new(r"data:image/[a-zA-Z0-9.+-]+;base64,[A-Za-z0-9+/=]+").unwrap(),
     }
 });
 
@@ -109,8 +198,98 @@ pub struct RedactionFinding {
     pub count: usize,
 }
 
+/// A pattern applied by `redact`
+struct Rule<'a> {
+    finding_type: &'a str,
+    pattern: Regex,
+    replacement: &'a str,
+}
+
+/// Patterns enabled by the configuration for a field, in the order they are
+/// applied
+fn rules<'a>(config: &'a RedactionConfig, field: Option<&str>) -> Vec<Rule<'a>> {
+    let rule = |finding_type, pattern: &Regex, replacement| Rule {
+        finding_type,
+        pattern: pattern.clone(),
+        replacement,
+    };
+    let mut rules = Vec::new();
+
+    // First, so the other patterns don't scan the image data
+    if config.redact_image_data {
+        rules.push(rule(
+            "image_data",
+            &PATTERNS.image_data,
+            "[IMAGE_DATA_REDACTED]",
+        ));
+    }
+    if config.redact_api_keys {
+        for pattern in &PATTERNS.api_keys {
+            rules.push(rule("api_key", pattern, "[API_KEY_REDACTED]"));
+        }
+        rules.push(rule("jwt", &PATTERNS.jwt, "[JWT_REDACTED]"));
+        rules.push(rule("aws_key", &PATTERNS.aws_keys, "[AWS_KEY_REDACTED]"));
+        rules.push(rule(
+            "github_token",
+            &PATTERNS.github_tokens,
+            "[GITHUB_TOKEN_REDACTED]",
+        ));
+        rules.push(rule(
+            "slack_token",
+            &PATTERNS.slack_tokens,
+            "[SLACK_TOKEN_REDACTED]",
+        ));
+    }
+    if config.redact_emails {
+        rules.push(rule("email", &PATTERNS.emails, "[EMAIL_REDACTED]"));
+    }
+    if config.redact_credit_cards {
+        rules.push(rule(
+            "credit_card",
+            &PATTERNS.credit_cards,
+            "[CREDIT_CARD_REDACTED]",
+        ));
+    }
+    if config.redact_ssn {
+        rules.push(rule("ssn", &PATTERNS.ssn, "[SSN_REDACTED]"));
+    }
+    if config.redact_phone_numbers {
+        rules.push(rule("phone", &PATTERNS.phone_numbers, "[PHONE_REDACTED]"));
+    }
+    for pattern_str in &config.custom_patterns {
+        if let Ok(pattern) = Regex::new(pattern_str) {
+            rules.push(Rule {
+                finding_type: "custom",
+                pattern,
+                replacement: "[CUSTOM_REDACTED]",
+            });
+        }
+    }
+    for custom in &config.rules {
+        if custom.applies_to(field) {
+            rules.push(Rule {
+                finding_type: &custom.name,
+                pattern: custom.pattern.clone(),
+                replacement: &custom.replacement,
+            });
+        }
+    }
+    rules
+}
+
 /// Redact sensitive content from a string
+///
+/// Rules limited to certain event fields are skipped; see `redact_field`.
 pub fn redact(content: &str, config: &RedactionConfig) -> RedactionResult {
+    redact_in(content, None, config)
+}
+
+/// Redact an event field, given as a dotted path under `data`
+pub fn redact_field(content: &str, field: &str, config: &RedactionConfig) -> RedactionResult {
+    redact_in(content, Some(field), config)
+}
+
+fn redact_in(content: &str, field: Option<&str>, config: &RedactionConfig) -> RedactionResult {
     let original_length = content.len();
     let hash = hash_content(content);
 
@@ -138,155 +317,169 @@ pub fn redact(content: &str, config: &RedactionConfig) -> RedactionResult {
     let mut result = content.to_string();
     let mut findings = Vec::new();
 
-    // API keys
-    if config.redact_api_keys {
-        for pattern in &PATTERNS.api_keys {
-            let count = pattern.find_iter(&result).count();
-            if count > 0 {
-                result = pattern
-                    .replace_all(&result, "[API_KEY_REDACTED]")
-                    .to_string();
-                findings.push(RedactionFinding {
-                    finding_type: "api_key".to_string(),
-                    count,
-                });
-            }
-        }
-
-        // JWT
-        let count = PATTERNS.jwt.find_iter(&result).count();
+    for rule in rules(config, field) {
+        let count = rule.pattern.find_iter(&result).count();
         if count > 0 {
-            result = PATTERNS
-                .jwt
-                .replace_all(&result, "[JWT_REDACTED]")
+            result = rule
+                .pattern
+                .replace_all(&result, rule.replacement)
                 .to_string();
             findings.push(RedactionFinding {
-                finding_type: "jwt".to_string(),
+                finding_type: rule.finding_type.to_string(),
                 count,
             });
         }
+    }
 
-        // AWS keys
-        let count = PATTERNS.aws_keys.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .aws_keys
-                .replace_all(&result, "[AWS_KEY_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "aws_key".to_string(),
-                count,
-            });
-        }
+    RedactionResult {
+        content: result,
+        findings,
+        hash,
+        original_length,
+    }
+}
 
-        // GitHub tokens
-        let count = PATTERNS.github_tokens.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .github_tokens
-                .replace_all(&result, "[GITHUB_TOKEN_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "github_token".to_string(),
-                count,
-            });
-        }
+/// A span of the original content that `redact` removes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionMatch {
+    /// Type of finding, as in `RedactionFinding`
+    pub finding_type: String,
+    /// Byte range in the original content
+    pub range: Range<usize>,
+}
 
-        // Slack tokens
-        let count = PATTERNS.slack_tokens.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .slack_tokens
-                .replace_all(&result, "[SLACK_TOKEN_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "slack_token".to_string(),
-                count,
-            });
+/// Locate what `redact` would remove from the original content
+///
+/// Patterns are tried in the order `redact` applies them; a match that
+/// overlaps an earlier one is skipped, as it would already have been
+/// replaced. `field` is as for `redact_field`.
+pub fn find_matches(
+    content: &str,
+    field: Option<&str>,
+    config: &RedactionConfig,
+) -> Vec<RedactionMatch> {
+    match config.mode {
+        RedactionMode::Full => return Vec::new(),
+        RedactionMode::Minimal if content.is_empty() => return Vec::new(),
+        RedactionMode::Minimal => {
+            return vec![RedactionMatch {
+                finding_type: "full_content".to_string(),
+                range: 0..content.len(),
+            }]
         }
+        RedactionMode::Safe => {}
     }
 
-    // Emails
-    if config.redact_emails {
-        let count = PATTERNS.emails.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .emails
-                .replace_all(&result, "[EMAIL_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "email".to_string(),
-                count,
-            });
+    let mut matches: Vec<RedactionMatch> = Vec::new();
+    for rule in rules(config, field) {
+        for m in rule.pattern.find_iter(content) {
+            let overlaps = matches
+                .iter()
+                .any(|prev| m.start() < prev.range.end && prev.range.start < m.end());
+            if !overlaps {
+                matches.push(RedactionMatch {
+                    finding_type: rule.finding_type.to_string(),
+                    range: m.range(),
+                });
+            }
         }
     }
+    matches.sort_by_key(|m| m.range.start);
+    matches
+}
 
-    // Credit cards
-    if config.redact_credit_cards {
-        let count = PATTERNS.credit_cards.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .credit_cards
-                .replace_all(&result, "[CREDIT_CARD_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "credit_card".to_string(),
-                count,
-            });
-        }
+/// Wrap each match in `[[type:...]]` for display
+pub fn mark_matches(content: &str, matches: &[RedactionMatch]) -> String {
+    let mut marked = String::with_capacity(content.len());
+    let mut pos = 0;
+    for m in matches {
+        marked.push_str(&content[pos..m.range.start]);
+        marked.push_str(&format!(
+            "[[{}:{}]]",
+            m.finding_type,
+            &content[m.range.clone()]
+        ));
+        pos = m.range.end;
     }
+    marked.push_str(&content[pos..]);
+    marked
+}
 
-    // SSN
-    if config.redact_ssn {
-        let count = PATTERNS.ssn.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .ssn
-                .replace_all(&result, "[SSN_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "ssn".to_string(),
-                count,
-            });
-        }
-    }
+/// Event fields whose values carry prompt or response content
+const CONTENT_FIELDS: &[&str] = &["content", "arguments"];
 
-    // Phone numbers
-    if config.redact_phone_numbers {
-        let count = PATTERNS.phone_numbers.find_iter(&result).count();
-        if count > 0 {
-            result = PATTERNS
-                .phone_numbers
-                .replace_all(&result, "[PHONE_REDACTED]")
-                .to_string();
-            findings.push(RedactionFinding {
-                finding_type: "phone".to_string(),
-                count,
-            });
-        }
+/// Other fields treated as content: provider error messages can quote the
+/// prompt or part of the API key, and retrieval queries are user questions
+const CONTENT_PATHS: &[&str] = &["/data/error/message", "/data/query"];
+
+/// A content field of an event that redaction changed
+#[derive(Debug, Clone)]
+pub struct FieldRedaction {
+    /// JSON pointer to the field, e.g. `/data/messages/0/content`
+    pub path: String,
+    /// The same field as a dotted path under `data`, e.g. `messages.0.content`
+    pub field: String,
+    /// The field before redaction
+    pub original: String,
+    pub result: RedactionResult,
+}
+
+/// Redact the content fields of a serialized event in place
+///
+/// Only strings under `content` and `arguments` keys of the event's `data`,
+/// and the provider's error message, are touched; identifiers, model names
+/// and usage are left alone.
+pub fn redact_event_json(event: &mut Value, config: &RedactionConfig) -> Vec<FieldRedaction> {
+    let mut redactions = Vec::new();
+    if config.mode == RedactionMode::Full {
+        return redactions;
     }
+    if let Some(data) = event.get_mut("data") {
+        walk(data, "/data", false, config, &mut redactions);
+    }
+    redactions
+}
 
-    // Custom patterns
-    for pattern_str in &config.custom_patterns {
-        if let Ok(pattern) = Regex::new(pattern_str) {
-            let count = pattern.find_iter(&result).count();
-            if count > 0 {
-                result = pattern
-                    .replace_all(&result, "[CUSTOM_REDACTED]")
-                    .to_string();
-                findings.push(RedactionFinding {
-                    finding_type: "custom".to_string(),
-                    count,
+fn walk(
+    value: &mut Value,
+    path: &str,
+    in_content: bool,
+    config: &RedactionConfig,
+    redactions: &mut Vec<FieldRedaction>,
+) {
+    match value {
+        Value::String(s) if in_content => {
+            let field = path
+                .strip_prefix("/data/")
+                .unwrap_or_default()
+                .replace('/', ".");
+            let result = redact_field(s, &field, config);
+            if result.content != *s {
+                let original = std::mem::replace(s, result.content.clone());
+                redactions.push(FieldRedaction {
+                    path: path.to_string(),
+                    field,
+                    original,
+                    result,
                 });
             }
         }
-    }
-
-    RedactionResult {
-        content: result,
-        findings,
-        hash,
-        original_length,
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let path = format!("{}/{}", path, key);
+                let in_content = in_content
+                    || CONTENT_FIELDS.contains(&key.as_str())
+                    || CONTENT_PATHS.contains(&path.as_str());
+                walk(child, &path, in_content, config, redactions);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                let path = format!("{}/{}", path, i);
+                walk(item, &path, in_content, config, redactions);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -340,6 +533,23 @@ mod tests {
         assert!(!result.content.contains("user@example.com"));
     }
 
+    #[test]
+    fn test_image_data_redaction() {
+        let content = format!(
+            "What is in data:image/png;base64,iVBORw0KGgo{}= ?",
+            "A".repeat(4096)
+        );
+        let result = redact(&content, &RedactionConfig::default());
+        assert_eq!(result.content, "What is in [IMAGE_DATA_REDACTED] ?");
+        assert_eq!(result.findings[0].finding_type, "image_data");
+
+        let keep = RedactionConfig {
+            redact_image_data: false,
+            ..Default::default()
+        };
+        assert_eq!(redact(&content, &keep).content, content);
+    }
+
     #[test]
     fn test_full_mode() {
         let config = RedactionConfig {
@@ -363,4 +573,148 @@ mod tests {
 
         assert_eq!(result.content, "[REDACTED]");
     }
+
+    #[test]
+    fn test_find_and_mark_matches() {
+        let config = RedactionConfig::default();
+        let content = "key sk-proj-abc123def456ghi789jkl012, mail user@example.com";
+        let matches = find_matches(content, None, &config);
+
+        let types: Vec<&str> = matches.iter().map(|m| m.finding_type.as_str()).collect();
+        assert_eq!(types, ["api_key", "email"]);
+        assert_eq!(
+            &content[matches[0].range.clone()],
+            "sk-proj-abc123def456ghi789jkl012"
+        );
+        assert_eq!(
+            mark_matches(content, &matches),
+            "key [[api_key:sk-proj-abc123def456ghi789jkl012]], mail [[email:user@example.com]]"
+        );
+    }
+
+    #[test]
+    fn test_redact_event_json_content_only() {
+        let mut event = serde_json::json!({
+            "event_type": "ai.request",
+            "data": {
+                "request_id": "user@example.com",
+                "messages": [
+                    {"role": "user", "content": "mail user@example.com"},
+                    {"role": "user", "content": "nothing to see"},
+                ],
+                "tool_calls": [{"name": "send", "arguments": {"to": "a@b.io"}}],
+            }
+        });
+        let redactions = redact_event_json(&mut event, &RedactionConfig::default());
+
+        let paths: Vec<&str> = redactions.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/data/messages/0/content",
+                "/data/tool_calls/0/arguments/to"
+            ]
+        );
+        assert_eq!(redactions[0].original, "mail user@example.com");
+        assert_eq!(
+            event["data"]["messages"][0]["content"],
+            "mail [EMAIL_REDACTED]"
+        );
+        assert_eq!(event["data"]["request_id"], "user@example.com");
+    }
+
+    #[test]
+    fn test_redact_event_json_error_message() {
+        let mut event = serde_json::json!({
+            "event_type": "ai.response",
+            "data": {
+                "request_id": "req-1",
+                "error": {
+                    "type": "invalid_request_error",
+                    "code": "invalid_api_key",
+                    "message": "Incorrect API key provided: sk-proj-abc123def456ghi789jkl012",
+                },
+            }
+        });
+        let redactions = redact_event_json(&mut event, &RedactionConfig::default());
+
+        assert_eq!(redactions.len(), 1);
+        assert_eq!(redactions[0].field, "error.message");
+        let message = event["data"]["error"]["message"].as_str().unwrap();
+        assert!(!message.contains("sk-proj-abc123"), "{}", message);
+        assert_eq!(event["data"]["error"]["code"], "invalid_api_key");
+    }
+
+    #[test]
+    fn test_redact_event_json_rag_query() {
+        let mut event = serde_json::json!({
+            "event_type": "agent.rag_retrieve",
+            "data": {
+                "source": {"type": "vector_db", "provider": "chroma"},
+                "query": "refund policy for jane@example.com",
+            }
+        });
+        let redactions = redact_event_json(&mut event, &RedactionConfig::default());
+
+        assert_eq!(redactions.len(), 1);
+        assert_eq!(event["data"]["query"], "refund policy for [EMAIL_REDACTED]");
+    }
+
+    #[test]
+    fn test_custom_rule_replacement_and_fields() {
+        let config = RedactionConfig {
+            rules: vec![
+                RedactionRule::new(
+                    "ticket",
+                    r"(?<project>[A-Z]+)-\d{4,}",
+                    "[TICKET:$project]",
+                    Vec::new(),
+                )
+                .unwrap(),
+                RedactionRule::new(
+                    "codename",
+                    r"Project \w+",
+                    "[CODENAME]",
+                    vec!["messages.*.content".to_string()],
+                )
+                .unwrap(),
+            ],
+            ..Default::default()
+        };
+
+        let content = "Project Falcon: see OPS-12345";
+        let result = redact(content, &config);
+        assert_eq!(result.content, "Project Falcon: see [TICKET:OPS]");
+        assert_eq!(result.findings[0].finding_type, "ticket");
+
+        let result = redact_field(content, "messages.3.content", &config);
+        assert_eq!(result.content, "[CODENAME]: see [TICKET:OPS]");
+        let result = redact_field(content, "tool_calls.0.arguments", &config);
+        assert_eq!(result.content, "Project Falcon: see [TICKET:OPS]");
+    }
+
+    #[test]
+    fn test_least_redacting() {
+        let config = |mode| RedactionConfig {
+            mode,
+            ..Default::default()
+        };
+        let (safe, full, minimal) = (
+            config(RedactionMode::Safe),
+            config(RedactionMode::Full),
+            config(RedactionMode::Minimal),
+        );
+
+        assert_eq!(
+            least_redacting([&safe, &minimal, &full]).unwrap().mode,
+            RedactionMode::Full
+        );
+        assert_eq!(
+            least_redacting([&minimal, &safe]).unwrap().mode,
+            RedactionMode::Safe
+        );
+        assert!(least_redacting([]).is_none());
+        assert!(RedactionMode::Safe.keeps_more_than(RedactionMode::Minimal));
+        assert!(!RedactionMode::Safe.keeps_more_than(RedactionMode::Safe));
+    }
 }
@@ -4,6 +4,7 @@
 //! enabling development and testing without requiring live capture capabilities.
 
 use crate::events::OispEvent;
+use crate::pipeline::Pipeline;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -81,12 +82,30 @@ impl EventReplay {
     ///
     /// Returns the number of events replayed
     pub async fn run(&self, event_tx: broadcast::Sender<Arc<OispEvent>>) -> anyhow::Result<u64> {
+        self.run_into(ReplaySink::Broadcast(&event_tx)).await
+    }
+
+    /// Run the replay through a pipeline's enrich, action and export stages
+    ///
+    /// Capture and decode are skipped since events are already decoded.
+    /// Unlike [`EventReplay::run`], no events are dropped when consumers are
+    /// slow: each event is fully exported before the next one is read.
+    /// Exporters are flushed once the replay finishes.
+    ///
+    /// Returns the number of events replayed
+    pub async fn run_through(&self, pipeline: &Pipeline) -> anyhow::Result<u64> {
+        let count = self.run_into(ReplaySink::Pipeline(pipeline)).await?;
+        pipeline.flush().await;
+        Ok(count)
+    }
+
+    async fn run_into(&self, sink: ReplaySink<'_>) -> anyhow::Result<u64> {
         self.running.store(true, Ordering::Relaxed);
 
         let mut total_events = 0u64;
 
         loop {
-            let events_this_pass = self.replay_file(&event_tx).await?;
+            let events_this_pass = self.replay_file(&sink).await?;
             total_events += events_this_pass;
 
             if !self.config.loop_playback || !self.running.load(Ordering::Relaxed) {
@@ -101,10 +120,7 @@ impl EventReplay {
     }
 
     /// Replay a single pass through the file
-    async fn replay_file(
-        &self,
-        event_tx: &broadcast::Sender<Arc<OispEvent>>,
-    ) -> anyhow::Result<u64> {
+    async fn replay_file(&self, sink: &ReplaySink<'_>) -> anyhow::Result<u64> {
         let file = tokio::fs::File::open(&self.config.input_file).await?;
         let reader = BufReader::new(file);
         let mut lines = reader.lines();
@@ -166,21 +182,26 @@ impl EventReplay {
             }
             last_timestamp = Some(current_timestamp);
 
-            // Broadcast the event
-            let event_arc = Arc::new(event);
-            match event_tx.send(event_arc.clone()) {
-                Ok(receivers) => {
-                    debug!(
-                        "Replayed event {} ({}) to {} receivers",
-                        event_arc.envelope().event_id,
-                        event_arc.event_type(),
-                        receivers
-                    );
-                }
-                Err(err) => {
-                    // No receivers, but that's okay - web server might not be connected yet
-                    debug!("No receivers for event: {}", err);
+            match sink {
+                ReplaySink::Broadcast(event_tx) => {
+                    // Broadcast the event
+                    let event_arc = Arc::new(event);
+                    match event_tx.send(event_arc.clone()) {
+                        Ok(receivers) => {
+                            debug!(
+                                "Replayed event {} ({}) to {} receivers",
+                                event_arc.envelope().event_id,
+                                event_arc.event_type(),
+                                receivers
+                            );
+                        }
+                        Err(err) => {
+                            // No receivers, but that's okay - web server might not be connected yet
+                            debug!("No receivers for event: {}", err);
+                        }
+                    }
                 }
+                ReplaySink::Pipeline(pipeline) => pipeline.process_event(event).await,
             }
 
             event_count += 1;
@@ -195,6 +216,12 @@ impl EventReplay {
     }
 }
 
+/// Where replayed events are sent
+enum ReplaySink<'a> {
+    Broadcast(&'a broadcast::Sender<Arc<OispEvent>>),
+    Pipeline(&'a Pipeline),
+}
+
 /// Read events from a JSONL file without replaying (for validation/testing)
 pub async fn read_events_from_file(path: &PathBuf) -> anyhow::Result<Vec<OispEvent>> {
     let file = tokio::fs::File::open(path).await?;
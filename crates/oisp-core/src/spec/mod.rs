@@ -8,11 +8,18 @@
 //! 2. Fetch latest from remote (if network enabled)
 //! 3. Fall back to embedded bundle (compile-time)
 
+mod validate;
+
+pub use validate::{
+    validate_event, validate_event_json, EventValidator, SchemaViolation, ViolationKind,
+};
+
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
 use std::time::{Duration, SystemTime};
 use tracing::{info, warn};
 
@@ -28,6 +35,23 @@ pub fn bundle_url() -> String {
     std::env::var("OISP_BUNDLE_URL").unwrap_or_else(|_| DEFAULT_BUNDLE_URL.to_string())
 }
 
+/// Set by [`set_air_gapped`] once the sensor configuration is loaded
+static AIR_GAPPED: AtomicBool = AtomicBool::new(false);
+
+/// Disable (or re-enable) fetching the spec bundle over the network
+///
+/// Air-gapped sensors only use the cached or embedded bundle.
+pub fn set_air_gapped(air_gapped: bool) {
+    AIR_GAPPED.store(air_gapped, Ordering::Relaxed);
+}
+
+/// Whether spec bundle network fetches are disabled, by [`set_air_gapped`]
+/// or the OISP_AIR_GAPPED environment variable
+pub fn air_gapped() -> bool {
+    AIR_GAPPED.load(Ordering::Relaxed)
+        || std::env::var("OISP_AIR_GAPPED").is_ok_and(|v| v.parse().unwrap_or(false))
+}
+
 /// Get the bundle refresh interval from environment variable or use default
 /// Supports OISP_BUNDLE_REFRESH_SECS environment variable (in seconds)
 pub fn bundle_refresh_interval() -> Duration {
@@ -40,10 +64,10 @@ pub fn bundle_refresh_interval() -> Duration {
 
 /// Embedded spec bundle (compile-time fallback)
 /// This is updated when the sensor is built
-const EMBEDDED_BUNDLE: &str = include_str!("../data/oisp-spec-bundle.json");
+const EMBEDDED_BUNDLE: &str = include_str!("../../data/oisp-spec-bundle.json");
 
 /// The complete OISP spec bundle
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OispSpecBundle {
     /// Schema URL
     #[serde(rename = "$schema", default)]
@@ -84,6 +108,11 @@ pub struct OispSpecBundle {
     /// Model statistics
     #[serde(default)]
     pub model_stats: ModelStats,
+
+    /// JSON schemas for emitted events: `envelope` for the common fields,
+    /// plus one per event type for its `data`
+    #[serde(default)]
+    pub event_schemas: HashMap<String, serde_json::Value>,
 }
 
 /// Provider specification
@@ -389,7 +418,12 @@ pub struct ModelStats {
 impl OispSpecBundle {
     /// Load the embedded bundle (always available)
     pub fn embedded() -> Self {
-        serde_json::from_str(EMBEDDED_BUNDLE).expect("embedded spec bundle should be valid JSON")
+        Self::try_embedded().expect("embedded spec bundle should be valid JSON")
+    }
+
+    /// Parse the embedded bundle
+    pub fn try_embedded() -> Result<Self, serde_json::Error> {
+        serde_json::from_str(EMBEDDED_BUNDLE)
     }
 
     /// Load from a file path
@@ -406,8 +440,11 @@ impl OispSpecBundle {
         std::fs::write(path, content)
     }
 
-    /// Load with fallback strategy: cache -> embedded
+    /// Load with fallback strategy: cache -> embedded -> empty
     /// (Network fetching is async and handled separately)
+    ///
+    /// An empty bundle has no providers; decoders then fall back to the
+    /// built-in provider set.
     pub fn load_with_fallback(cache_path: Option<&Path>) -> Self {
         // Try cached file first
         if let Some(path) = cache_path {
@@ -425,8 +462,19 @@ impl OispSpecBundle {
         }
 
         // Fall back to embedded
-        info!("Using embedded spec bundle");
-        Self::embedded()
+        match Self::try_embedded() {
+            Ok(bundle) => {
+                info!("Using embedded spec bundle");
+                bundle
+            }
+            Err(e) => {
+                warn!(
+                    "Embedded spec bundle is invalid, continuing without it: {}",
+                    e
+                );
+                Self::default()
+            }
+        }
     }
 
     /// Check if the bundle should be refreshed
@@ -464,6 +512,83 @@ impl OispSpecBundle {
     pub fn get_extraction_rules(&self, provider: &str) -> Option<&ExtractionRuleSet> {
         self.extraction_rules.get(provider)
     }
+
+    /// Providers in the bundle with the domains they are detected on, sorted
+    /// by ID
+    ///
+    /// `filter` is a case-insensitive substring. A provider is listed in full
+    /// if it matches its ID, name or one of its domains; otherwise it is
+    /// listed with just the models that match, if any.
+    pub fn list_providers(&self, filter: Option<&str>) -> Vec<ProviderListing> {
+        let filter = filter.map(str::to_lowercase);
+        let matches = |s: &str| {
+            filter
+                .as_deref()
+                .is_none_or(|f| s.to_lowercase().contains(f))
+        };
+
+        let mut listings: Vec<ProviderListing> = self
+            .providers
+            .values()
+            .filter_map(|provider| {
+                let mut domains: BTreeSet<String> = provider.domains.iter().cloned().collect();
+                domains.extend(
+                    self.domain_index
+                        .iter()
+                        .filter(|(_, id)| **id == provider.id)
+                        .map(|(domain, _)| domain.clone()),
+                );
+                domains.extend(
+                    self.domain_patterns
+                        .iter()
+                        .filter(|p| p.provider == provider.id)
+                        .map(|p| p.pattern.clone()),
+                );
+                let models: BTreeSet<String> = self
+                    .models
+                    .values()
+                    .filter(|m| m.provider == provider.id)
+                    .map(|m| m.id.clone())
+                    .collect();
+
+                let provider_matches = matches(&provider.id)
+                    || matches(&provider.display_name)
+                    || domains.iter().any(|d| matches(d));
+                let models: Vec<String> = if provider_matches {
+                    models.into_iter().collect()
+                } else {
+                    let models: Vec<String> = models.into_iter().filter(|m| matches(m)).collect();
+                    if models.is_empty() {
+                        return None;
+                    }
+                    models
+                };
+
+                Some(ProviderListing {
+                    id: provider.id.clone(),
+                    display_name: provider.display_name.clone(),
+                    provider_type: provider.provider_type.clone(),
+                    domains: domains.into_iter().collect(),
+                    models,
+                })
+            })
+            .collect();
+        listings.sort_by(|a, b| a.id.cmp(&b.id));
+        listings
+    }
+}
+
+/// A provider as listed by `oisp-sensor providers list`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderListing {
+    pub id: String,
+    pub display_name: String,
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    /// Exact domains and wildcard patterns the provider is detected on
+    pub domains: Vec<String>,
+    /// Model IDs in the bundle's model registry
+    pub models: Vec<String>,
 }
 
 /// Spec bundle loader with caching and refresh
@@ -490,13 +615,18 @@ impl SpecLoader {
         let url = bundle_url();
         let bundle = OispSpecBundle::load_with_fallback(Some(&cache_path));
 
-        info!("SpecLoader initialized with bundle URL: {}", url);
+        let network_enabled = !air_gapped();
+        if network_enabled {
+            info!("SpecLoader initialized with bundle URL: {}", url);
+        } else {
+            info!("SpecLoader initialized air-gapped; spec bundle network fetch disabled");
+        }
 
         Self {
             bundle: Arc::new(bundle),
             cache_path,
             bundle_url: url,
-            network_enabled: true,
+            network_enabled,
         }
     }
 
@@ -512,11 +642,44 @@ impl SpecLoader {
         }
     }
 
+    /// Create from a custom bundle file, without network refresh
+    ///
+    /// A missing or invalid file leaves the loader with an empty bundle
+    /// (see [`SpecLoader::is_degraded`]) instead of failing.
+    pub fn from_file(path: &Path) -> Self {
+        let bundle = match OispSpecBundle::from_file(path) {
+            Ok(bundle) => {
+                info!("Loaded spec bundle from {}", path.display());
+                bundle
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load spec bundle from {}, continuing without it: {}",
+                    path.display(),
+                    e
+                );
+                OispSpecBundle::default()
+            }
+        };
+
+        Self {
+            bundle: Arc::new(bundle),
+            cache_path: path.to_path_buf(),
+            bundle_url: bundle_url(),
+            network_enabled: false,
+        }
+    }
+
     /// Get the current bundle
     pub fn bundle(&self) -> Arc<OispSpecBundle> {
         Arc::clone(&self.bundle)
     }
 
+    /// Whether no usable bundle could be loaded
+    pub fn is_degraded(&self) -> bool {
+        self.bundle.providers.is_empty()
+    }
+
     /// Get default cache path
     pub fn default_cache_path() -> PathBuf {
         // Try XDG cache dir, fall back to /tmp
@@ -529,9 +692,17 @@ impl SpecLoader {
         }
     }
 
+    /// Whether the bundle may be refreshed from the network
+    ///
+    /// Always false in air-gapped mode, even for a loader created before
+    /// [`set_air_gapped`] was called.
+    pub fn network_enabled(&self) -> bool {
+        self.network_enabled && !air_gapped()
+    }
+
     /// Check if refresh is needed
     pub fn needs_refresh(&self) -> bool {
-        self.network_enabled && OispSpecBundle::needs_refresh(&self.cache_path)
+        self.network_enabled() && OispSpecBundle::needs_refresh(&self.cache_path)
     }
 
     /// Refresh the bundle (sync, for use in blocking contexts)
@@ -560,6 +731,16 @@ impl Default for SpecLoader {
     }
 }
 
+/// Canonical identity of a model seen under one of its aliases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedModel {
+    /// Canonical model ID, e.g. `gpt-4o` for `gpt-4o-2024-08-06`
+    pub id: String,
+
+    /// Model family from the provider's extraction rules, e.g. `gpt-4o`
+    pub family: Option<String>,
+}
+
 /// Dynamic provider registry using the spec bundle
 pub struct DynamicProviderRegistry {
     /// The spec bundle
@@ -567,6 +748,27 @@ pub struct DynamicProviderRegistry {
 
     /// Compiled domain patterns
     compiled_patterns: Vec<(Regex, String)>,
+
+    /// Compiled model family patterns per provider
+    family_patterns: HashMap<String, Vec<(Regex, String)>>,
+}
+
+/// Snapshot and alias suffixes stripped to find a model's canonical ID:
+/// `-2024-08-06`, `-20241022`, `-0613`, `-latest`
+static ALIAS_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"-(\d{4}-\d{2}-\d{2}|\d{8}|\d{4}|latest)$").unwrap());
+
+/// Compile a model family pattern
+///
+/// Bundle patterns may use negative lookahead (`^gpt-4(?!o)`) to exclude a
+/// more specific family, which the regex crate does not support. Such groups
+/// are dropped; the more specific family still wins because the longest
+/// match is preferred.
+fn compile_family_pattern(pattern: &str) -> Option<Regex> {
+    Regex::new(pattern).ok().or_else(|| {
+        let lookahead = Regex::new(r"\(\?!(?:[^()\\]|\\.)*\)").ok()?;
+        Regex::new(&lookahead.replace_all(pattern, "")).ok()
+    })
 }
 
 impl DynamicProviderRegistry {
@@ -580,9 +782,23 @@ impl DynamicProviderRegistry {
             }
         }
 
+        let mut family_patterns = HashMap::new();
+        for (provider, rules) in &bundle.extraction_rules {
+            let patterns: Vec<(Regex, String)> = rules
+                .model_families
+                .iter()
+                .filter_map(|(family, spec)| {
+                    let pattern = spec.get("pattern")?.as_str()?;
+                    Some((compile_family_pattern(pattern)?, family.clone()))
+                })
+                .collect();
+            family_patterns.insert(provider.clone(), patterns);
+        }
+
         Self {
             bundle,
             compiled_patterns,
+            family_patterns,
         }
     }
 
@@ -642,7 +858,35 @@ impl DynamicProviderRegistry {
         self.bundle.get_model(provider, model_id)
     }
 
+    /// Map a model alias to its canonical ID and family
+    ///
+    /// Snapshot dates and `-latest` are stripped, so `gpt-4o-2024-08-06` and
+    /// `gpt-4o` both map to `gpt-4o`. Returns `None` for models the bundle
+    /// does not list under either name.
+    pub fn normalize_model(&self, provider: &str, model_id: &str) -> Option<NormalizedModel> {
+        let base = ALIAS_SUFFIX.replace(model_id, "");
+        if self.get_model(provider, model_id).is_none() && self.get_model(provider, &base).is_none()
+        {
+            return None;
+        }
+
+        let family = self.family_patterns.get(provider).and_then(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|(re, family)| re.find(&base).map(|m| (m.len(), family)))
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, family)| family.clone())
+        });
+
+        Some(NormalizedModel {
+            id: base.into_owned(),
+            family,
+        })
+    }
+
     /// Estimate cost for a request
+    ///
+    /// Canonical IDs without their own pricing use the `-latest` alias's.
     pub fn estimate_cost(
         &self,
         provider: &str,
@@ -650,7 +894,9 @@ impl DynamicProviderRegistry {
         input_tokens: u64,
         output_tokens: u64,
     ) -> Option<(f64, f64, f64)> {
-        let model = self.get_model(provider, model_id)?;
+        let model = self
+            .get_model(provider, model_id)
+            .or_else(|| self.get_model(provider, &format!("{}-latest", model_id)))?;
 
         let input_cost = model.input_cost_per_1k? * (input_tokens as f64 / 1000.0);
         let output_cost = model.output_cost_per_1k? * (output_tokens as f64 / 1000.0);
@@ -691,6 +937,23 @@ mod tests {
         OispSpecBundle::embedded()
     }
 
+    #[test]
+    fn test_air_gapped_skips_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = SpecLoader::with_config(
+            dir.path().join("missing.json"),
+            DEFAULT_BUNDLE_URL.to_string(),
+            true,
+        );
+        assert!(loader.needs_refresh());
+
+        set_air_gapped(true);
+        assert!(!loader.network_enabled());
+        assert!(!loader.needs_refresh());
+        set_air_gapped(false);
+        assert!(loader.needs_refresh());
+    }
+
     #[test]
     fn test_load_embedded() {
         let bundle = OispSpecBundle::embedded();
@@ -698,6 +961,22 @@ mod tests {
         assert!(!bundle.domain_index.is_empty());
     }
 
+    #[test]
+    fn test_unloadable_bundle_is_degraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let corrupt = dir.path().join("bundle.json");
+        std::fs::write(&corrupt, "{not json").unwrap();
+
+        let loader = SpecLoader::from_file(&corrupt);
+        assert!(loader.is_degraded());
+        assert!(SpecLoader::from_file(&dir.path().join("missing.json")).is_degraded());
+
+        // A corrupt cache still falls back to the embedded bundle
+        assert!(!OispSpecBundle::load_with_fallback(Some(&corrupt))
+            .providers
+            .is_empty());
+    }
+
     #[test]
     fn test_domain_detection() {
         let bundle = Arc::new(test_bundle());
@@ -753,4 +1032,85 @@ mod tests {
         let rules = rules.unwrap();
         assert!(rules.endpoints.contains_key("chat_completions"));
     }
+
+    #[test]
+    fn test_normalize_model_aliases() {
+        let bundle = Arc::new(test_bundle());
+        let registry = DynamicProviderRegistry::new(bundle);
+        let normalize = |provider: &str, id: &str| {
+            registry
+                .normalize_model(provider, id)
+                .map(|m| (m.id, m.family.unwrap_or_default()))
+        };
+        let expect = |id: &str, family: &str| Some((id.to_string(), family.to_string()));
+
+        assert_eq!(normalize("openai", "gpt-4o"), expect("gpt-4o", "gpt-4o"));
+        assert_eq!(
+            normalize("openai", "gpt-4o-2024-08-06"),
+            expect("gpt-4o", "gpt-4o")
+        );
+        assert_eq!(
+            normalize("openai", "gpt-4o-mini-2024-07-18"),
+            expect("gpt-4o-mini", "gpt-4o")
+        );
+        assert_eq!(normalize("openai", "gpt-4-0613"), expect("gpt-4", "gpt-4"));
+        assert_eq!(
+            normalize("anthropic", "claude-3-5-sonnet-20241022"),
+            expect("claude-3-5-sonnet", "claude-3.5")
+        );
+        assert_eq!(
+            normalize("anthropic", "claude-3-5-sonnet-latest"),
+            expect("claude-3-5-sonnet", "claude-3.5")
+        );
+        assert_eq!(
+            normalize("anthropic", "claude-3-opus-20240229"),
+            expect("claude-3-opus", "claude-3")
+        );
+
+        // Unknown models are left alone
+        assert_eq!(
+            registry.normalize_model("openai", "my-finetune-2024-01-01"),
+            None
+        );
+        assert_eq!(registry.normalize_model("nonexistent", "gpt-4o"), None);
+
+        // Canonical IDs without their own pricing fall back to `-latest`
+        assert!(registry
+            .estimate_cost("anthropic", "claude-3-5-sonnet", 1000, 1000)
+            .is_some());
+    }
+
+    #[test]
+    fn test_list_providers() {
+        let bundle = test_bundle();
+        let all = bundle.list_providers(None);
+        assert_eq!(all.len(), bundle.providers.len());
+        assert!(all.windows(2).all(|w| w[0].id < w[1].id));
+
+        let openai = all.iter().find(|p| p.id == "openai").unwrap();
+        assert!(openai.domains.contains(&"api.openai.com".to_string()));
+        assert!(openai.models.contains(&"gpt-4o".to_string()));
+        let azure = all.iter().find(|p| p.id == "azure_openai").unwrap();
+        assert!(azure.domains.contains(&"*.openai.azure.com".to_string()));
+
+        // Matching the provider lists all of its models
+        let filtered = bundle.list_providers(Some("OpenAI.com"));
+        assert!(filtered.iter().any(|p| p.id == "openai"));
+        assert!(filtered.iter().all(|p| p.id != "anthropic"));
+        assert_eq!(
+            filtered.iter().find(|p| p.id == "openai").unwrap().models,
+            openai.models
+        );
+
+        // Matching only models lists just those
+        let filtered = bundle.list_providers(Some("claude-3-5-sonnet"));
+        let anthropic = filtered.iter().find(|p| p.id == "anthropic").unwrap();
+        assert!(!anthropic.models.is_empty());
+        assert!(anthropic
+            .models
+            .iter()
+            .all(|m| m.contains("claude-3-5-sonnet")));
+
+        assert!(bundle.list_providers(Some("no-such-provider")).is_empty());
+    }
 }
@@ -0,0 +1,334 @@
+//! Event validation against the spec bundle's event schemas
+//!
+//! The bundle carries an `envelope` schema for the fields every event shares
+//! and one schema per event type for its `data`. Validation covers the JSON
+//! Schema keywords those schemas use (`type`, `required`, `properties`,
+//! `items`, `enum`, `minimum`, `minLength`); other keywords are ignored.
+
+use super::OispSpecBundle;
+use crate::events::OispEvent;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Key of the common envelope schema in `event_schemas`
+const ENVELOPE_SCHEMA: &str = "envelope";
+
+/// Validator built from the embedded bundle
+static EMBEDDED_VALIDATOR: LazyLock<EventValidator> =
+    LazyLock::new(|| EventValidator::new(&OispSpecBundle::embedded()));
+
+/// What is wrong at a location in the event
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    /// A required field is absent
+    MissingRequired,
+    /// The value has the wrong JSON type
+    WrongType {
+        expected: String,
+        found: &'static str,
+    },
+    /// The value is not one of the allowed values
+    NotInEnum { value: String },
+    /// A number is below the schema's minimum
+    BelowMinimum { minimum: f64 },
+    /// A string is shorter than the schema's minimum length
+    TooShort { min_length: u64 },
+    /// The bundle has no schema for the event type
+    UnknownEventType { event_type: String },
+}
+
+/// A single schema violation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, e.g. `/data/request_id`
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        match &self.kind {
+            ViolationKind::MissingRequired => write!(f, "{}: missing required field", path),
+            ViolationKind::WrongType { expected, found } => {
+                write!(f, "{}: expected {}, found {}", path, expected, found)
+            }
+            ViolationKind::NotInEnum { value } => {
+                write!(f, "{}: {} is not an allowed value", path, value)
+            }
+            ViolationKind::BelowMinimum { minimum } => {
+                write!(f, "{}: below minimum {}", path, minimum)
+            }
+            ViolationKind::TooShort { min_length } => {
+                write!(f, "{}: shorter than {} characters", path, min_length)
+            }
+            ViolationKind::UnknownEventType { event_type } => {
+                write!(f, "{}: no schema for event type {}", path, event_type)
+            }
+        }
+    }
+}
+
+/// Checks events against the event schemas of a spec bundle
+#[derive(Debug, Clone, Default)]
+pub struct EventValidator {
+    envelope: Option<Value>,
+    event_types: HashMap<String, Value>,
+}
+
+impl EventValidator {
+    pub fn new(bundle: &OispSpecBundle) -> Self {
+        let mut event_types = bundle.event_schemas.clone();
+        let envelope = event_types.remove(ENVELOPE_SCHEMA);
+        Self {
+            envelope,
+            event_types,
+        }
+    }
+
+    /// Whether the bundle had any event schemas to validate against
+    pub fn has_schemas(&self) -> bool {
+        self.envelope.is_some() || !self.event_types.is_empty()
+    }
+
+    /// Validate an event as it would be serialized for export
+    pub fn validate(&self, event: &OispEvent) -> Vec<SchemaViolation> {
+        self.validate_json(&serde_json::to_value(event).unwrap_or_default())
+    }
+
+    /// Validate a serialized event; an empty result means it conforms
+    pub fn validate_json(&self, event: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        if let Some(schema) = &self.envelope {
+            check(schema, event, "", &mut violations);
+        }
+
+        let Some(event_type) = event.get("event_type").and_then(Value::as_str) else {
+            return violations;
+        };
+        match self.event_types.get(event_type) {
+            Some(schema) => {
+                if let Some(data) = event.get("data") {
+                    check(schema, data, "/data", &mut violations);
+                }
+            }
+            None if !self.event_types.is_empty() => violations.push(SchemaViolation {
+                path: "/event_type".to_string(),
+                kind: ViolationKind::UnknownEventType {
+                    event_type: event_type.to_string(),
+                },
+            }),
+            None => {}
+        }
+        violations
+    }
+}
+
+/// Validate an event against the embedded bundle's schemas
+pub fn validate_event(event: &OispEvent) -> Vec<SchemaViolation> {
+    EMBEDDED_VALIDATOR.validate(event)
+}
+
+/// Validate a serialized event against the embedded bundle's schemas
+pub fn validate_event_json(event: &Value) -> Vec<SchemaViolation> {
+    EMBEDDED_VALIDATOR.validate_json(event)
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let mut violation = |kind| {
+        violations.push(SchemaViolation {
+            path: path.to_string(),
+            kind,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| has_type(value, t)) {
+            violation(ViolationKind::WrongType {
+                expected: allowed.join(" or "),
+                found: type_name(value),
+            });
+            // Nested keywords assume the declared type
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            violation(ViolationKind::NotInEnum {
+                value: value.to_string(),
+            });
+        }
+    }
+
+    if let (Some(minimum), Some(n)) = (
+        schema.get("minimum").and_then(Value::as_f64),
+        value.as_f64(),
+    ) {
+        if n < minimum {
+            violation(ViolationKind::BelowMinimum { minimum });
+        }
+    }
+
+    if let (Some(min_length), Some(s)) = (
+        schema.get("minLength").and_then(Value::as_u64),
+        value.as_str(),
+    ) {
+        if (s.chars().count() as u64) < min_length {
+            violation(ViolationKind::TooShort { min_length });
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    violations.push(SchemaViolation {
+                        path: format!("{}/{}", path, field),
+                        kind: ViolationKind::MissingRequired,
+                    });
+                }
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (field, property) in properties {
+                if let Some(child) = object.get(field) {
+                    check(property, child, &format!("{}/{}", path, field), violations);
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            check(items, item, &format!("{}/{}", path, i), violations);
+        }
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AiRequestEvent, EventEnvelope};
+
+    fn ai_request() -> OispEvent {
+        OispEvent::AiRequest(AiRequestEvent {
+            envelope: EventEnvelope::new("ai.request"),
+            data: serde_json::from_value(serde_json::json!({
+                "request_id": "req-1",
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o"},
+                "messages": [{"role": "user", "content": "hi"}],
+            }))
+            .unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_valid_event_passes() {
+        assert_eq!(validate_event(&ai_request()), Vec::new());
+    }
+
+    #[test]
+    fn test_missing_required_field_reported() {
+        let mut event = serde_json::to_value(ai_request()).unwrap();
+        event["data"].as_object_mut().unwrap().remove("request_id");
+        event["confidence"]["level"] = "certain".into();
+
+        let violations = validate_event_json(&event);
+        assert_eq!(
+            violations,
+            vec![
+                SchemaViolation {
+                    path: "/confidence/level".to_string(),
+                    kind: ViolationKind::NotInEnum {
+                        value: "\"certain\"".to_string()
+                    },
+                },
+                SchemaViolation {
+                    path: "/data/request_id".to_string(),
+                    kind: ViolationKind::MissingRequired,
+                },
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "/data/request_id: missing required field"
+        );
+    }
+
+    #[test]
+    fn test_wrong_type_and_unknown_event_type() {
+        let mut event = serde_json::to_value(ai_request()).unwrap();
+        event["data"]["messages"][0]["role"] = 1.into();
+        event["data"]["model"]["id"] = "".into();
+        assert_eq!(
+            validate_event_json(&event),
+            vec![
+                SchemaViolation {
+                    path: "/data/messages/0/role".to_string(),
+                    kind: ViolationKind::WrongType {
+                        expected: "string".to_string(),
+                        found: "integer",
+                    },
+                },
+                SchemaViolation {
+                    path: "/data/model/id".to_string(),
+                    kind: ViolationKind::TooShort { min_length: 1 },
+                },
+            ]
+        );
+
+        event["event_type"] = "ai.telepathy".into();
+        assert!(matches!(
+            &validate_event_json(&event)[..],
+            [SchemaViolation {
+                kind: ViolationKind::UnknownEventType { .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_bundle_without_schemas_accepts_everything() {
+        let validator = EventValidator::new(&OispSpecBundle::default());
+        assert!(!validator.has_schemas());
+        assert!(validator.validate_json(&serde_json::json!({})).is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+//! Parsing of sslsniff's JSON line output
+//!
+//! sslsniff prints one JSON object per SSL read or write. Its `data` string
+//! is not ordinary JSON text: printable ASCII and valid UTF-8 sequences are
+//! copied through as-is, and every other byte is written as a `\u00XX`
+//! escape. Decoding it as a JSON string and mapping each char back to a byte
+//! would turn multi-byte UTF-8 (common inside gzip bodies) into the wrong
+//! bytes, so the payload is unescaped from the raw text instead.
+//!
+//! About once a second, if it has changed, sslsniff also prints the total
+//! number of events the kernel dropped because the ring buffer was full, as
+//! `{"ringbuf_dropped":N}`. Every five seconds it prints
+//! `{"heartbeat":<monotonic ms>}`, so a quiet sslsniff can be told apart from
+//! a stuck one.
+//!
+//! A read or write larger than sslsniff's capture buffer is printed as
+//! several lines with the same timestamp, each carrying the `offset` of its
+//! data in the call and `last` on the final one. [`FragmentAssembler`] joins
+//! them back into one event. Lines from older builds have neither field and
+//! are complete on their own.
+//!
+//! When run with `--control`, sslsniff reads `attach <pid> <lib>` and
+//! `detach <pid>` commands on stdin and answers each with
+//! `{"control":"attach","pid":N,"ok":true}`, plus an `error` string on
+//! failure.
+
+use crate::monotonic::CaptureClock;
+use crate::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use tracing::debug;
+
+#[derive(Deserialize)]
+struct SslsniffLine<'a> {
+    function: String,
+    timestamp_ns: u64,
+    comm: String,
+    pid: u32,
+    tid: Option<u32>,
+    uid: Option<u32>,
+    #[serde(default)]
+    offset: u32,
+    #[serde(default = "default_last")]
+    last: bool,
+    #[serde(borrow)]
+    data: Option<&'a RawValue>,
+}
+
+fn default_last() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct RingbufDropsLine {
+    ringbuf_dropped: u64,
+}
+
+#[derive(Deserialize)]
+struct HeartbeatLine {
+    heartbeat: u64,
+}
+
+/// Outcome of an attach or detach command
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ControlResult {
+    /// Command answered, `attach` or `detach`
+    pub control: String,
+    pub pid: u32,
+    /// Library an attach probed
+    #[serde(default)]
+    pub lib: Option<String>,
+    pub ok: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Result of a control command, if the line is one
+pub fn parse_control_result(line: &str) -> Option<ControlResult> {
+    // Data lines can be hundreds of KB; only parse what can be a result
+    if !line.starts_with(r#"{"control":"#) {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+/// Total ring buffer drops, if the line is a drop report
+pub fn parse_ringbuf_dropped(line: &str) -> Option<u64> {
+    serde_json::from_str::<RingbufDropsLine>(line)
+        .ok()
+        .map(|l| l.ringbuf_dropped)
+}
+
+/// sslsniff's monotonic clock in milliseconds, if the line is a heartbeat
+pub fn parse_heartbeat(line: &str) -> Option<u64> {
+    serde_json::from_str::<HeartbeatLine>(line)
+        .ok()
+        .map(|l| l.heartbeat)
+}
+
+/// Part of the data of one SSL read or write
+#[derive(Debug, Clone)]
+pub struct SslFragment {
+    /// The event, holding only this fragment's data
+    pub event: RawCaptureEvent,
+    /// Position of the data within the call
+    pub offset: u32,
+    /// No more fragments follow for this call
+    pub last: bool,
+}
+
+/// Parse one line of sslsniff output
+///
+/// Returns `None` for lines that are not sslsniff events. Only the fields
+/// sslsniff knows about are filled in; callers enrich the rest (exe, ppid)
+/// from `/proc`. A fragment of a larger call is returned as is; see
+/// [`parse_fragment`] and [`FragmentAssembler`] to join them.
+pub fn parse_line(line: &str) -> Option<RawCaptureEvent> {
+    parse_fragment(line).map(|fragment| fragment.event)
+}
+
+/// Parse one line of sslsniff output, keeping its position in the call
+pub fn parse_fragment(line: &str) -> Option<SslFragment> {
+    let parsed: SslsniffLine = serde_json::from_str(line).ok()?;
+
+    let kind = if parsed.function.contains("WRITE") || parsed.function.contains("SEND") {
+        RawEventKind::SslWrite
+    } else {
+        RawEventKind::SslRead
+    };
+    let data = match parsed.data {
+        Some(raw) if raw.get() != "null" => unescape_data(raw.get())?,
+        _ => Vec::new(),
+    };
+
+    let event = RawCaptureEvent {
+        id: ulid::Ulid::new().to_string(),
+        timestamp_ns: parsed.timestamp_ns,
+        kind,
+        pid: parsed.pid,
+        tid: parsed.tid,
+        data,
+        metadata: RawEventMetadata {
+            comm: Some(parsed.comm),
+            uid: parsed.uid,
+            // sslsniff stamps events with bpf_ktime_get_ns
+            clock: CaptureClock::Monotonic,
+            ..Default::default()
+        },
+    };
+
+    Some(SslFragment {
+        event,
+        offset: parsed.offset,
+        last: parsed.last,
+    })
+}
+
+/// How long, in capture time, an incomplete call waits for its next fragment
+const FRAGMENT_TIMEOUT_NS: u64 = 5_000_000_000;
+
+/// Data held across all incomplete calls before the oldest are passed on
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// Joins the fragments of SSL reads and writes split by sslsniff
+///
+/// Fragments of one call arrive in order, but may be interleaved with
+/// those of other threads. If a fragment is lost (ring buffer full), the
+/// data received so far is passed on as if the call had been truncated.
+/// That also happens when a call has waited [`FRAGMENT_TIMEOUT_NS`] behind
+/// the newest event seen, which covers a lost final fragment and threads
+/// or processes that have exited, and to the oldest calls once the pending
+/// data passes [`MAX_PENDING_BYTES`].
+#[derive(Debug)]
+pub struct FragmentAssembler {
+    /// Incomplete calls by (pid, tid)
+    pending: HashMap<(u32, Option<u32>), RawCaptureEvent>,
+    /// Total data held in `pending`
+    pending_bytes: usize,
+    max_pending_bytes: usize,
+    /// Newest timestamp seen, the clock for timing out calls
+    latest_ns: u64,
+}
+
+impl Default for FragmentAssembler {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            pending_bytes: 0,
+            max_pending_bytes: MAX_PENDING_BYTES,
+            latest_ns: 0,
+        }
+    }
+}
+
+impl FragmentAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a fragment, returning the events it completes
+    pub fn push(&mut self, fragment: SslFragment) -> Vec<RawCaptureEvent> {
+        let SslFragment {
+            event,
+            offset,
+            last,
+        } = fragment;
+        let key = (event.pid, event.tid);
+        self.latest_ns = self.latest_ns.max(event.timestamp_ns);
+        let mut complete = Vec::new();
+
+        let partial = self.pending.remove(&key);
+        if let Some(partial) = &partial {
+            self.pending_bytes -= partial.data.len();
+        }
+        let event = match partial {
+            Some(mut partial)
+                if offset > 0
+                    && partial.timestamp_ns == event.timestamp_ns
+                    && partial.data.len() == offset as usize =>
+            {
+                partial.data.extend_from_slice(&event.data);
+                partial
+            }
+            partial => {
+                if let Some(partial) = partial {
+                    debug!(
+                        "Incomplete SSL data from pid {}: {} bytes",
+                        partial.pid,
+                        partial.data.len()
+                    );
+                    complete.push(partial);
+                }
+                if offset > 0 {
+                    // The start of the call was lost
+                    debug!(
+                        "Dropping SSL fragment at offset {} from pid {}",
+                        offset, event.pid
+                    );
+                    self.evict(&mut complete);
+                    return complete;
+                }
+                event
+            }
+        };
+
+        if last {
+            complete.push(event);
+        } else {
+            self.pending_bytes += event.data.len();
+            self.pending.insert(key, event);
+        }
+        self.evict(&mut complete);
+        complete
+    }
+
+    /// Pass on calls that timed out, then the oldest while over the byte cap
+    fn evict(&mut self, complete: &mut Vec<RawCaptureEvent>) {
+        let cutoff = self.latest_ns.saturating_sub(FRAGMENT_TIMEOUT_NS);
+        let stale: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, partial)| partial.timestamp_ns < cutoff)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in stale {
+            self.pass_on(key, "timed out", complete);
+        }
+
+        while self.pending_bytes > self.max_pending_bytes {
+            let Some(oldest) = self
+                .pending
+                .iter()
+                .min_by_key(|(_, partial)| partial.timestamp_ns)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.pass_on(oldest, "over the pending data limit", complete);
+        }
+    }
+
+    fn pass_on(
+        &mut self,
+        key: (u32, Option<u32>),
+        reason: &str,
+        complete: &mut Vec<RawCaptureEvent>,
+    ) {
+        if let Some(partial) = self.pending.remove(&key) {
+            self.pending_bytes -= partial.data.len();
+            debug!(
+                "Incomplete SSL data from pid {} ({}): {} bytes",
+                partial.pid,
+                reason,
+                partial.data.len()
+            );
+            complete.push(partial);
+        }
+    }
+
+    /// Calls still waiting for fragments
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Recover the captured bytes from sslsniff's quoted `data` string
+fn unescape_data(quoted: &str) -> Option<Vec<u8>> {
+    let text = quoted.strip_prefix('"')?.strip_suffix('"')?.as_bytes();
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        let b = text[i];
+        i += 1;
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+
+        let escape = *text.get(i)?;
+        i += 1;
+        match escape {
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'u' => {
+                let hex = std::str::from_utf8(text.get(i..i + 4)?).ok()?;
+                let code = u32::from_str_radix(hex, 16).ok()?;
+                i += 4;
+                match u8::try_from(code) {
+                    // sslsniff only escapes single bytes
+                    Ok(byte) => out.push(byte),
+                    Err(_) => {
+                        let c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                }
+            }
+            // \" \\ \/
+            other => out.push(other),
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let line = r#"{"function":"WRITE/SEND","timestamp_ns":123456789,"comm":"curl","pid":42,"len":5,"buf_size":5,"uid":1000,"tid":43,"latency_ms":0,"is_handshake":false,"data":"GET \"/\"\r\n","truncated":false}"#;
+        let event = parse_line(line).unwrap();
+
+        assert!(matches!(event.kind, RawEventKind::SslWrite));
+        assert_eq!(event.timestamp_ns, 123456789);
+        assert_eq!(event.pid, 42);
+        assert_eq!(event.tid, Some(43));
+        assert_eq!(event.metadata.uid, Some(1000));
+        assert_eq!(event.metadata.comm.as_deref(), Some("curl"));
+        assert_eq!(event.data, b"GET \"/\"\r\n");
+
+        assert!(parse_line("libbpf: loading object").is_none());
+        assert_eq!(parse_ringbuf_dropped(line), None);
+    }
+
+    #[test]
+    fn test_ringbuf_dropped_line() {
+        let line = r#"{"ringbuf_dropped":17}"#;
+        assert_eq!(parse_ringbuf_dropped(line), Some(17));
+        assert!(parse_line(line).is_none());
+        assert_eq!(parse_heartbeat(line), None);
+    }
+
+    #[test]
+    fn test_heartbeat_line() {
+        let line = r#"{"heartbeat":123456789}"#;
+        assert_eq!(parse_heartbeat(line), Some(123456789));
+        assert!(parse_line(line).is_none());
+        assert_eq!(parse_ringbuf_dropped(line), None);
+        assert!(parse_control_result(line).is_none());
+    }
+
+    #[test]
+    fn test_control_result_line() {
+        let line = r#"{"control":"attach","pid":4242,"lib":"/proc/4242/root/usr/lib/libssl.so.1.1","ok":false,"error":"SSL_write not found"}"#;
+        assert_eq!(
+            parse_control_result(line),
+            Some(ControlResult {
+                control: "attach".to_string(),
+                pid: 4242,
+                lib: Some("/proc/4242/root/usr/lib/libssl.so.1.1".to_string()),
+                ok: false,
+                error: Some("SSL_write not found".to_string()),
+            })
+        );
+        assert!(parse_line(line).is_none());
+        assert_eq!(parse_ringbuf_dropped(line), None);
+
+        let ok = parse_control_result(r#"{"control":"detach","pid":7,"ok":true}"#).unwrap();
+        assert!(ok.ok && ok.error.is_none() && ok.lib.is_none());
+        assert!(parse_control_result(r#"{"ringbuf_dropped":17}"#).is_none());
+    }
+
+    #[test]
+    fn test_binary_data_is_byte_exact() {
+        // sslsniff copies valid UTF-8 through but escapes lone high bytes,
+        // so "é" as UTF-8 and a bare 0xE9 byte must stay distinct
+        let line = "{\"function\":\"READ/RECV\",\"timestamp_ns\":1,\"comm\":\"python3\",\"pid\":1,\"tid\":1,\"uid\":0,\"data\":\"\u{e9}\\u00e9\\u001f\\u008b\\u0000\",\"truncated\":false}";
+        let event = parse_line(line).unwrap();
+
+        assert!(matches!(event.kind, RawEventKind::SslRead));
+        assert_eq!(event.data, vec![0xC3, 0xA9, 0xE9, 0x1F, 0x8B, 0x00]);
+    }
+
+    fn fragment_line(tid: u32, offset: usize, last: bool, data: &[u8]) -> String {
+        fragment_line_at(1000 + tid as u64, tid, offset, last, data)
+    }
+
+    fn fragment_line_at(
+        timestamp_ns: u64,
+        tid: u32,
+        offset: usize,
+        last: bool,
+        data: &[u8],
+    ) -> String {
+        format!(
+            "{{\"function\":\"WRITE/SEND\",\"timestamp_ns\":{},\"comm\":\"python3\",\"pid\":7,\"len\":12288,\"buf_size\":{},\"offset\":{},\"last\":{},\"uid\":0,\"tid\":{},\"data\":\"{}\",\"truncated\":false}}",
+            timestamp_ns,
+            data.len(),
+            offset,
+            last,
+            tid,
+            std::str::from_utf8(data).unwrap()
+        )
+    }
+
+    #[test]
+    fn test_reassemble_fragmented_write() {
+        let body: Vec<u8> = (0..12 * 1024).map(|i| b'a' + (i % 26) as u8).collect();
+        let chunks: Vec<&[u8]> = body.chunks(4096).collect();
+        assert_eq!(chunks.len(), 3);
+
+        let mut assembler = FragmentAssembler::new();
+        let first = parse_fragment(&fragment_line(1, 0, false, chunks[0])).unwrap();
+        assert_eq!((first.offset, first.last), (0, false));
+        assert!(assembler.push(first).is_empty());
+        // Another thread's write in between
+        let other = assembler.push(parse_fragment(&fragment_line(2, 0, true, b"ping")).unwrap());
+        assert_eq!(other.len(), 1);
+        assert_eq!(other[0].data, b"ping");
+        assert!(assembler
+            .push(parse_fragment(&fragment_line(1, 4096, false, chunks[1])).unwrap())
+            .is_empty());
+        let done =
+            assembler.push(parse_fragment(&fragment_line(1, 8192, true, chunks[2])).unwrap());
+
+        assert_eq!(done.len(), 1);
+        assert!(matches!(done[0].kind, RawEventKind::SslWrite));
+        assert_eq!(done[0].timestamp_ns, 1001);
+        assert_eq!(done[0].data, body);
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_lost_fragment_passes_on_partial_data() {
+        let mut assembler = FragmentAssembler::new();
+        let first = parse_fragment(&fragment_line(1, 0, false, b"POST /v1")).unwrap();
+        assert!(assembler.push(first).is_empty());
+
+        // The middle fragment was dropped; the tail alone is useless
+        let tail = assembler.push(parse_fragment(&fragment_line(1, 16, true, b"}")).unwrap());
+        assert_eq!(tail.len(), 1);
+        assert_eq!(tail[0].data, b"POST /v1");
+        assert_eq!(assembler.pending(), 0);
+
+        // Lines without fragment fields are complete
+        let line = r#"{"function":"READ/RECV","timestamp_ns":1,"comm":"curl","pid":1,"tid":1,"uid":0,"data":"ok","truncated":false}"#;
+        let whole = parse_fragment(line).unwrap();
+        assert!(whole.last);
+        assert_eq!(assembler.push(whole).len(), 1);
+    }
+
+    #[test]
+    fn test_lost_final_fragment_times_out() {
+        let mut assembler = FragmentAssembler::new();
+        let first = parse_fragment(&fragment_line_at(1_000, 1, 0, false, b"POST /v1")).unwrap();
+        assert!(assembler.push(first).is_empty());
+        let middle = parse_fragment(&fragment_line_at(1_000, 1, 8, false, b"/chat")).unwrap();
+        assert!(assembler.push(middle).is_empty());
+
+        // The last fragment never arrives and thread 1 goes quiet
+        let soon = assembler.push(
+            parse_fragment(&fragment_line_at(1_000_000_000, 2, 0, true, b"ping")).unwrap(),
+        );
+        assert_eq!(soon.len(), 1);
+        assert_eq!(assembler.pending(), 1);
+
+        let later = assembler.push(
+            parse_fragment(&fragment_line_at(
+                1_000 + FRAGMENT_TIMEOUT_NS + 1,
+                2,
+                0,
+                true,
+                b"pong",
+            ))
+            .unwrap(),
+        );
+        assert_eq!(later.len(), 2);
+        assert_eq!(later[0].data, b"pong");
+        assert_eq!(later[1].data, b"POST /v1/chat");
+        assert_eq!(later[1].tid, Some(1));
+        assert_eq!(assembler.pending(), 0);
+        assert_eq!(assembler.pending_bytes, 0);
+    }
+
+    #[test]
+    fn test_pending_data_is_capped() {
+        let mut assembler = FragmentAssembler {
+            max_pending_bytes: 10,
+            ..Default::default()
+        };
+        for (ts, tid) in [(100, 1), (200, 2)] {
+            let start = parse_fragment(&fragment_line_at(ts, tid, 0, false, b"abcd")).unwrap();
+            assert!(assembler.push(start).is_empty());
+        }
+        assert_eq!(assembler.pending_bytes, 8);
+
+        // A third call puts the total over the cap; the oldest goes first
+        let third = parse_fragment(&fragment_line_at(300, 3, 0, false, b"efgh")).unwrap();
+        let evicted = assembler.push(third);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].tid, Some(1));
+        assert_eq!(evicted[0].data, b"abcd");
+        assert_eq!(assembler.pending(), 2);
+        assert_eq!(assembler.pending_bytes, 8);
+    }
+
+    #[test]
+    fn test_null_data() {
+        let line = r#"{"function":"HANDSHAKE","timestamp_ns":1,"comm":"curl","pid":1,"tid":1,"uid":0,"data":null,"truncated":false}"#;
+        assert!(parse_line(line).unwrap().data.is_empty());
+    }
+}
@@ -4,8 +4,8 @@
 //! for connecting related events into complete agent traces.
 
 use crate::events::{
-    AgentToolCallEvent, AgentToolResultEvent, AiRequestEvent, AiResponseEvent, FileWriteEvent,
-    NetworkConnectEvent, OispEvent, ProcessExecEvent,
+    AgentToolCallEvent, AgentToolResultEvent, AiRequestEvent, AiResponseEvent, EventTime,
+    FileWriteEvent, NetworkConnectEvent, OispEvent, ProcessExecEvent,
 };
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
@@ -97,7 +97,7 @@ impl AgentTrace {
     /// Get duration of the trace
     pub fn duration(&self) -> Duration {
         let end = self.ended_at.unwrap_or_else(Utc::now);
-        end - self.started_at
+        (end - self.started_at).max(Duration::zero())
     }
 
     /// Mark trace as complete
@@ -179,7 +179,7 @@ impl Span {
 
     pub fn complete(&mut self, status: SpanStatus) {
         self.end_time = Some(Utc::now());
-        self.duration_ms = Some((Utc::now() - self.start_time).num_milliseconds() as u64);
+        self.duration_ms = Some((Utc::now() - self.start_time).num_milliseconds().max(0) as u64);
         self.status = status;
     }
 }
@@ -258,7 +258,7 @@ struct PendingRequest {
     trace_id: String,
     span_id: String,
     pid: u32,
-    started_at: DateTime<Utc>,
+    started_at: EventTime,
 }
 
 #[allow(dead_code)]
@@ -267,7 +267,7 @@ struct PendingToolCall {
     span_id: String,
     request_id: Option<String>,
     tool_name: String,
-    started_at: DateTime<Utc>,
+    started_at: EventTime,
 }
 
 impl TraceBuilder {
@@ -299,6 +299,11 @@ impl TraceBuilder {
         self.cleanup_stale_traces();
     }
 
+    /// Trace ID of the active trace for a process, if any
+    pub fn active_trace_id(&self, pid: u32) -> Option<&str> {
+        self.active_traces.get(&pid).map(|t| t.trace_id.as_str())
+    }
+
     fn handle_ai_request(&mut self, event: &AiRequestEvent) {
         let pid = event.envelope.process.as_ref().map(|p| p.pid).unwrap_or(0);
 
@@ -326,7 +331,7 @@ impl TraceBuilder {
                 trace_id: trace.trace_id.clone(),
                 span_id: span.span_id.clone(),
                 pid,
-                started_at: event.envelope.ts,
+                started_at: EventTime::of(&event.envelope),
             },
         );
 
@@ -348,6 +353,10 @@ impl TraceBuilder {
                     } else {
                         SpanStatus::Error
                     });
+                    // Time between the captured request and response rather
+                    // than when the builder happened to see them
+                    span.duration_ms =
+                        Some(EventTime::of(&event.envelope).since(&pending.started_at).ms);
                     span.event_ids.push(event.envelope.event_id.clone());
 
                     // Update token counts
@@ -377,7 +386,7 @@ impl TraceBuilder {
                                 span_id: tool_span.span_id.clone(),
                                 request_id: Some(event.data.request_id.clone()),
                                 tool_name: tool_call.name.clone(),
-                                started_at: event.envelope.ts,
+                                started_at: EventTime::of(&event.envelope),
                             },
                         );
 
@@ -439,7 +448,9 @@ impl TraceBuilder {
                     } else {
                         SpanStatus::Error
                     });
-                    span.duration_ms = event.data.duration_ms;
+                    span.duration_ms = event.data.duration_ms.or_else(|| {
+                        Some(EventTime::of(&event.envelope).since(&pending.started_at).ms)
+                    });
                     span.event_ids.push(event.envelope.event_id.clone());
                 }
             }
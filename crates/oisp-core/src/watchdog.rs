@@ -0,0 +1,324 @@
+//! Capture watchdog
+//!
+//! A capture source can stop delivering events without failing outright:
+//! the sslsniff helper may hang or exit, leaving the sensor running but
+//! blind. Capture plugins that report a heartbeat
+//! ([`CapturePlugin::last_heartbeat`]) are watched, and when neither an event
+//! nor a heartbeat arrived for the stall timeout the plugin is stopped and
+//! started again. Restarts back off exponentially and stop after a bounded
+//! number of attempts; each one is counted in `oisp_capture_restarts_total`
+//! and reported as a `stalled` capture error.
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::metrics::SharedMetrics;
+use crate::plugins::{CaptureError, CaptureErrorKind, CapturePlugin, RawCaptureEvent};
+
+/// Default time without events or heartbeats before a capture plugin is
+/// considered stalled
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of consecutive restarts before the watchdog gives up
+pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+
+/// Longest wait between restart attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// Watchdog settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// Time without events or heartbeats before a plugin is restarted
+    pub stall_timeout: Duration,
+
+    /// Consecutive restarts before giving up on a plugin. The count resets
+    /// once a restarted plugin stays healthy through its backoff window.
+    pub max_restarts: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+        }
+    }
+}
+
+type SharedCapture = Arc<RwLock<Box<dyn CapturePlugin>>>;
+
+/// Restart bookkeeping for one capture plugin
+struct Watched {
+    plugin: SharedCapture,
+    /// The plugin's `events_captured` at the last check
+    events_seen: u64,
+    last_event: Instant,
+    restarts: u32,
+    /// No restart before this, so a restarted plugin has time to come up
+    backoff_until: Option<Instant>,
+    gave_up: bool,
+}
+
+/// Spawn the watchdog over `plugins`
+///
+/// Restarted plugins send to `tx`, so the watchdog keeps the raw event
+/// channel open until `shutdown` fires.
+pub(crate) fn spawn(
+    plugins: Vec<SharedCapture>,
+    tx: mpsc::Sender<RawCaptureEvent>,
+    metrics: SharedMetrics,
+    config: WatchdogConfig,
+    mut shutdown: broadcast::Receiver<()>,
+) -> JoinHandle<()> {
+    let mut watched: Vec<Watched> = plugins
+        .into_iter()
+        .map(|plugin| Watched {
+            plugin,
+            events_seen: 0,
+            last_event: Instant::now(),
+            restarts: 0,
+            backoff_until: None,
+            gave_up: false,
+        })
+        .collect();
+
+    tokio::spawn(async move {
+        let period = (config.stall_timeout / 4).max(Duration::from_millis(10));
+        let mut interval = tokio::time::interval(period);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.recv() => break,
+            }
+
+            for entry in &mut watched {
+                check(entry, &tx, &metrics, &config).await;
+            }
+        }
+    })
+}
+
+async fn check(
+    entry: &mut Watched,
+    tx: &mpsc::Sender<RawCaptureEvent>,
+    metrics: &SharedMetrics,
+    config: &WatchdogConfig,
+) {
+    let now = Instant::now();
+    let (name, heartbeat, captured) = {
+        let plugin = entry.plugin.read().await;
+        (
+            plugin.name().to_string(),
+            plugin.last_heartbeat(),
+            plugin.stats().events_captured,
+        )
+    };
+    if captured != entry.events_seen {
+        entry.events_seen = captured;
+        entry.last_event = now;
+    }
+    let Some(heartbeat) = heartbeat else {
+        return;
+    };
+
+    let stalled = now.duration_since(heartbeat) >= config.stall_timeout
+        && now.duration_since(entry.last_event) >= config.stall_timeout;
+    let backing_off = entry.backoff_until.is_some_and(|until| now < until);
+
+    if !stalled {
+        if entry.restarts > 0 && !backing_off {
+            info!(
+                "Capture plugin {} recovered after {} restart(s)",
+                name, entry.restarts
+            );
+            entry.restarts = 0;
+            entry.backoff_until = None;
+            entry.gave_up = false;
+        }
+        return;
+    }
+    if backing_off || entry.gave_up {
+        return;
+    }
+
+    if entry.restarts >= config.max_restarts {
+        entry.gave_up = true;
+        error!(
+            "Capture plugin {} still stalled after {} restart(s); giving up",
+            name, entry.restarts
+        );
+        metrics.record_capture_error(CaptureError::new(
+            &name,
+            CaptureErrorKind::Stalled,
+            format!(
+                "Still stalled after {} restart(s); no longer restarting",
+                entry.restarts
+            ),
+        ));
+        return;
+    }
+
+    entry.restarts += 1;
+    let idle = now.duration_since(heartbeat.max(entry.last_event));
+    warn!(
+        "Capture plugin {} stalled: no events or heartbeat for {:?}; restarting (attempt {}/{})",
+        name, idle, entry.restarts, config.max_restarts
+    );
+    metrics.capture.restarts.fetch_add(1, Ordering::Relaxed);
+    metrics.record_capture_error(CaptureError::new(
+        &name,
+        CaptureErrorKind::Stalled,
+        format!(
+            "No events or heartbeat for {}s; restarting (attempt {}/{})",
+            idle.as_secs(),
+            entry.restarts,
+            config.max_restarts
+        ),
+    ));
+
+    let mut plugin = entry.plugin.write().await;
+    if let Err(e) = plugin.stop().await {
+        warn!("Error stopping capture plugin {}: {}", name, e);
+    }
+    if let Err(e) = plugin.start(tx.clone()).await {
+        error!("Failed to restart capture plugin {}: {}", name, e);
+        metrics.record_capture_error(CaptureError::new(
+            &name,
+            CaptureErrorKind::Start,
+            e.to_string(),
+        ));
+    }
+
+    // Wait 2x, 4x, 8x ... the stall timeout before the next attempt
+    let backoff = config
+        .stall_timeout
+        .saturating_mul(2u32.saturating_pow(entry.restarts))
+        .min(MAX_BACKOFF);
+    entry.backoff_until = Some(Instant::now() + backoff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{Pipeline, PipelineConfig};
+    use crate::plugins::{Plugin, PluginInfo, PluginResult};
+    use async_trait::async_trait;
+    use std::any::Any;
+    use std::sync::atomic::AtomicU32;
+
+    /// Counts its starts; its heartbeat is fixed at creation (stalled) or
+    /// always current (idle but alive)
+    struct HeartbeatCapture {
+        starts: Arc<AtomicU32>,
+        heartbeat: Option<Instant>,
+        running: bool,
+    }
+
+    impl HeartbeatCapture {
+        fn new(starts: Arc<AtomicU32>, stalled: bool) -> Self {
+            Self {
+                starts,
+                heartbeat: stalled.then(Instant::now),
+                running: false,
+            }
+        }
+    }
+
+    impl PluginInfo for HeartbeatCapture {
+        fn name(&self) -> &str {
+            "heartbeat-capture"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+    }
+
+    impl Plugin for HeartbeatCapture {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl CapturePlugin for HeartbeatCapture {
+        async fn start(&mut self, _tx: mpsc::Sender<RawCaptureEvent>) -> PluginResult<()> {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            self.running = true;
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> PluginResult<()> {
+            self.running = false;
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            self.running
+        }
+
+        fn last_heartbeat(&self) -> Option<Instant> {
+            self.heartbeat.or_else(|| Some(Instant::now()))
+        }
+    }
+
+    fn watched_pipeline(capture: HeartbeatCapture, max_restarts: u32) -> Pipeline {
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            watchdog: Some(WatchdogConfig {
+                stall_timeout: Duration::from_millis(20),
+                max_restarts,
+            }),
+            ..Default::default()
+        });
+        pipeline.add_capture(Box::new(capture));
+        pipeline
+    }
+
+    #[tokio::test]
+    async fn test_stalled_capture_restarted_until_limit() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let mut pipeline = watched_pipeline(HeartbeatCapture::new(starts.clone(), true), 2);
+        pipeline.start().await.unwrap();
+
+        // Restarts after 20ms, then after 40ms and 80ms of backoff, then gives up
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+
+        let stats = pipeline.metrics().to_json();
+        assert_eq!(stats["capture"]["restarts"], 2);
+        let stalled: Vec<_> = pipeline
+            .capture_errors()
+            .into_iter()
+            .filter(|e| e.kind == CaptureErrorKind::Stalled)
+            .collect();
+        assert_eq!(stalled.len(), 3);
+        assert_eq!(stalled[0].plugin, "heartbeat-capture");
+        assert!(stalled[0].message.contains("attempt 1/2"));
+        assert!(stalled[2].message.contains("no longer restarting"));
+
+        pipeline.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_capture_with_heartbeat_left_alone() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let mut pipeline = watched_pipeline(HeartbeatCapture::new(starts.clone(), false), 2);
+        pipeline.start().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert_eq!(pipeline.metrics().to_json()["capture"]["restarts"], 0);
+
+        pipeline.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,738 @@
+//! Wire encoding for capture transports (macOS socket, Windows pipe)
+//!
+//! Capture helpers (the Swift network extension, the Windows redirector)
+//! stream [`RawCaptureEvent`]s to the sensor. Two encodings exist:
+//!
+//! - **JSON**: one JSON object per line, with the payload base64 encoded.
+//!   Each transport defines its own JSON schema. This is the fallback and
+//!   what clients that predate the handshake send.
+//! - **Binary**: length-prefixed frames with the fixed layout below, so the
+//!   payload is sent as raw bytes.
+//!
+//! # Handshake
+//!
+//! A client that supports binary frames starts the connection with a hello
+//! line listing the encodings it accepts, in order of preference:
+//!
+//! ```text
+//! {"oisp_hello":{"version":1,"encodings":["binary","json"]}}
+//! ```
+//!
+//! The sensor answers with the encoding to use for the rest of the
+//! connection, and the client must wait for this line before sending events:
+//!
+//! ```text
+//! {"oisp_hello_ack":{"version":1,"encoding":"binary"}}
+//! ```
+//!
+//! If the first line is not a hello, the client is treated as a legacy JSON
+//! client and that line as its first event.
+//!
+//! # Binary frame layout
+//!
+//! All integers are little-endian. Strings are UTF-8 and, like the payload,
+//! prefixed with a `u32` byte length.
+//!
+//! | Field | Type | Notes |
+//! |-------|------|-------|
+//! | length | `u32` | Bytes in the frame after this field |
+//! | kind | `u8` | See [`kind_code`]; `255` = other, named by `kind_name` |
+//! | flags | `u16` | Which optional fields follow, see `FLAG_*` |
+//! | timestamp_ns | `u64` | |
+//! | pid | `u32` | |
+//! | tid, ppid, uid | `u32` | Each present if its flag is set |
+//! | fd | `i32` | If flagged |
+//! | remote_port, local_port | `u16` | Each present if its flag is set |
+//! | id | string | |
+//! | kind_name | string | Only for kind `255` |
+//! | comm, exe, path, remote_addr, local_addr, bundle_id | string | Each present if its flag is set |
+//! | extra | string | JSON object, if flagged |
+//! | data | bytes | Captured payload |
+
+use crate::monotonic::CaptureClock;
+use crate::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Highest handshake version this build speaks
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest binary frame accepted
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Bytes of a failed frame kept for [`WireDecoder::failed_frame`]
+const FAILED_FRAME_KEPT: usize = 256;
+
+/// Kind code for [`RawEventKind::Other`]
+const KIND_OTHER: u8 = 255;
+
+// Presence flags for optional frame fields
+pub const FLAG_TID: u16 = 1 << 0;
+pub const FLAG_PPID: u16 = 1 << 1;
+pub const FLAG_UID: u16 = 1 << 2;
+pub const FLAG_FD: u16 = 1 << 3;
+pub const FLAG_REMOTE_PORT: u16 = 1 << 4;
+pub const FLAG_LOCAL_PORT: u16 = 1 << 5;
+pub const FLAG_COMM: u16 = 1 << 6;
+pub const FLAG_EXE: u16 = 1 << 7;
+pub const FLAG_PATH: u16 = 1 << 8;
+pub const FLAG_REMOTE_ADDR: u16 = 1 << 9;
+pub const FLAG_LOCAL_ADDR: u16 = 1 << 10;
+pub const FLAG_BUNDLE_ID: u16 = 1 << 11;
+pub const FLAG_EXTRA: u16 = 1 << 12;
+
+/// Wire encoding errors
+#[derive(Error, Debug)]
+pub enum WireError {
+    #[error("Frame of {0} bytes exceeds the {MAX_FRAME_SIZE} byte limit")]
+    FrameTooLarge(usize),
+
+    #[error("Frame truncated")]
+    Truncated,
+
+    #[error("Unknown event kind code: {0}")]
+    UnknownKind(u8),
+
+    #[error("Invalid UTF-8 in frame")]
+    InvalidUtf8,
+
+    #[error("Invalid extra metadata: {0}")]
+    InvalidExtra(#[from] serde_json::Error),
+}
+
+impl WireError {
+    /// Whether the stream can no longer be decoded and should be closed
+    ///
+    /// Other errors only affect the current frame.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, WireError::FrameTooLarge(_))
+    }
+}
+
+/// Event encoding used on a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Json,
+    Binary,
+}
+
+/// Client hello, the first line of a negotiating connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    /// Accepted encodings, most preferred first
+    pub encodings: Vec<Encoding>,
+}
+
+/// Sensor reply to a [`Hello`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HelloAck {
+    pub version: u32,
+    pub encoding: Encoding,
+}
+
+#[derive(Serialize)]
+struct HelloLine {
+    oisp_hello: Hello,
+}
+
+/// Hello as received; encodings newer than this build are dropped
+#[derive(Deserialize)]
+struct ReceivedHello {
+    version: u32,
+    #[serde(default)]
+    encodings: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ReceivedHelloLine {
+    oisp_hello: ReceivedHello,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HelloAckLine {
+    oisp_hello_ack: HelloAck,
+}
+
+impl Hello {
+    /// Hello offering `encodings` at the current protocol version
+    pub fn new(encodings: Vec<Encoding>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            encodings,
+        }
+    }
+
+    /// Parse a hello line; `None` if the line is anything else
+    pub fn parse(line: &str) -> Option<Self> {
+        let hello = serde_json::from_str::<ReceivedHelloLine>(line)
+            .ok()?
+            .oisp_hello;
+        Some(Self {
+            version: hello.version,
+            encodings: hello
+                .encodings
+                .into_iter()
+                .filter_map(|e| serde_json::from_value(e).ok())
+                .collect(),
+        })
+    }
+
+    /// Serialize as a newline-terminated hello line
+    pub fn to_line(&self) -> Vec<u8> {
+        to_line(&HelloLine {
+            oisp_hello: self.clone(),
+        })
+    }
+
+    /// Pick the encoding for this client
+    ///
+    /// The client's first supported choice wins; anything unknown, or a
+    /// version-0 client, gets JSON.
+    pub fn negotiate(&self) -> HelloAck {
+        let encoding = if self.version == 0 {
+            Encoding::Json
+        } else {
+            self.encodings.first().copied().unwrap_or(Encoding::Json)
+        };
+        HelloAck {
+            version: self.version.min(PROTOCOL_VERSION),
+            encoding,
+        }
+    }
+}
+
+impl HelloAck {
+    /// Parse an ack line; `None` if the line is anything else
+    pub fn parse(line: &str) -> Option<Self> {
+        serde_json::from_str::<HelloAckLine>(line)
+            .ok()
+            .map(|l| l.oisp_hello_ack)
+    }
+
+    /// Serialize as a newline-terminated ack line
+    pub fn to_line(&self) -> Vec<u8> {
+        to_line(&HelloAckLine {
+            oisp_hello_ack: self.clone(),
+        })
+    }
+}
+
+fn to_line<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut line = serde_json::to_vec(value).expect("handshake types always serialize");
+    line.push(b'\n');
+    line
+}
+
+/// Message decoded from a connection
+#[derive(Debug)]
+pub enum WireMessage {
+    /// The client sent a hello; write this ack back before reading on
+    Hello(HelloAck),
+    /// A JSON event line in the transport's own schema
+    Json(String),
+    /// A binary-encoded event
+    Event(Box<RawCaptureEvent>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecoderState {
+    AwaitingHello,
+    Json,
+    Binary,
+}
+
+/// Incremental decoder for one connection
+///
+/// Feed it bytes as they are read and drain messages with
+/// [`WireDecoder::next_message`]; it handles the handshake and switches
+/// encoding once negotiated.
+#[derive(Debug)]
+pub struct WireDecoder {
+    state: DecoderState,
+    buf: Vec<u8>,
+    /// Start of the binary frame the last error was returned for
+    failed: Vec<u8>,
+}
+
+impl Default for WireDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WireDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: DecoderState::AwaitingHello,
+            buf: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Negotiated encoding, once the first line has been seen
+    pub fn encoding(&self) -> Option<Encoding> {
+        match self.state {
+            DecoderState::AwaitingHello => None,
+            DecoderState::Json => Some(Encoding::Json),
+            DecoderState::Binary => Some(Encoding::Binary),
+        }
+    }
+
+    /// Append bytes read from the connection
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Start of the binary frame that the last error was returned for
+    ///
+    /// For diagnostics: the frame itself has already been consumed. JSON
+    /// errors are reported by the transport, which has the line.
+    pub fn failed_frame(&self) -> &[u8] {
+        &self.failed
+    }
+
+    /// Next complete message, or `None` if more bytes are needed
+    pub fn next_message(&mut self) -> Result<Option<WireMessage>, WireError> {
+        match self.state {
+            DecoderState::AwaitingHello => {
+                let Some(line) = self.take_line() else {
+                    return Ok(None);
+                };
+                match Hello::parse(&line) {
+                    Some(hello) => {
+                        let ack = hello.negotiate();
+                        self.state = match ack.encoding {
+                            Encoding::Json => DecoderState::Json,
+                            Encoding::Binary => DecoderState::Binary,
+                        };
+                        Ok(Some(WireMessage::Hello(ack)))
+                    }
+                    None => {
+                        // Legacy client: no handshake, the line is an event
+                        self.state = DecoderState::Json;
+                        Ok(Some(WireMessage::Json(line)))
+                    }
+                }
+            }
+            DecoderState::Json => Ok(self.take_line().map(WireMessage::Json)),
+            DecoderState::Binary => {
+                let Some(len) = self.buf.get(..4) else {
+                    return Ok(None);
+                };
+                let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+                if len > MAX_FRAME_SIZE {
+                    self.failed.clear();
+                    self.failed
+                        .extend_from_slice(&self.buf[..self.buf.len().min(FAILED_FRAME_KEPT)]);
+                    return Err(WireError::FrameTooLarge(len));
+                }
+                if self.buf.len() < 4 + len {
+                    return Ok(None);
+                }
+                let frame: Vec<u8> = self.buf.drain(..4 + len).collect();
+                decode_event(&frame[4..])
+                    .map(|e| Some(WireMessage::Event(Box::new(e))))
+                    .inspect_err(|_| {
+                        self.failed.clear();
+                        self.failed
+                            .extend_from_slice(&frame[..frame.len().min(FAILED_FRAME_KEPT)]);
+                    })
+            }
+        }
+    }
+
+    /// Next non-empty line, without its terminator
+    fn take_line(&mut self) -> Option<String> {
+        loop {
+            let newline = self.buf.iter().position(|&b| b == b'\n')?;
+            let line: Vec<u8> = self.buf.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if !line.trim().is_empty() {
+                return Some(line.to_string());
+            }
+        }
+    }
+}
+
+/// Wire code for an event kind
+pub fn kind_code(kind: &RawEventKind) -> u8 {
+    match kind {
+        RawEventKind::SslWrite => 0,
+        RawEventKind::SslRead => 1,
+        RawEventKind::ProcessExec => 2,
+        RawEventKind::ProcessExit => 3,
+        RawEventKind::ProcessFork => 4,
+        RawEventKind::FileOpen => 5,
+        RawEventKind::FileRead => 6,
+        RawEventKind::FileWrite => 7,
+        RawEventKind::FileClose => 8,
+        RawEventKind::NetworkConnect => 9,
+        RawEventKind::NetworkAccept => 10,
+        RawEventKind::NetworkSend => 11,
+        RawEventKind::NetworkRecv => 12,
+        RawEventKind::DnsQuery => 13,
+        RawEventKind::Other(_) => KIND_OTHER,
+    }
+}
+
+fn kind_from_code(
+    code: u8,
+    other_name: impl FnOnce() -> Result<String, WireError>,
+) -> Result<RawEventKind, WireError> {
+    Ok(match code {
+        0 => RawEventKind::SslWrite,
+        1 => RawEventKind::SslRead,
+        2 => RawEventKind::ProcessExec,
+        3 => RawEventKind::ProcessExit,
+        4 => RawEventKind::ProcessFork,
+        5 => RawEventKind::FileOpen,
+        6 => RawEventKind::FileRead,
+        7 => RawEventKind::FileWrite,
+        8 => RawEventKind::FileClose,
+        9 => RawEventKind::NetworkConnect,
+        10 => RawEventKind::NetworkAccept,
+        11 => RawEventKind::NetworkSend,
+        12 => RawEventKind::NetworkRecv,
+        13 => RawEventKind::DnsQuery,
+        KIND_OTHER => RawEventKind::Other(other_name()?),
+        other => return Err(WireError::UnknownKind(other)),
+    })
+}
+
+/// Append `event` to `out` as a length-prefixed binary frame
+pub fn encode_event(event: &RawCaptureEvent, out: &mut Vec<u8>) {
+    let meta = &event.metadata;
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+
+    let mut flags = 0u16;
+    for (present, flag) in [
+        (event.tid.is_some(), FLAG_TID),
+        (meta.ppid.is_some(), FLAG_PPID),
+        (meta.uid.is_some(), FLAG_UID),
+        (meta.fd.is_some(), FLAG_FD),
+        (meta.remote_port.is_some(), FLAG_REMOTE_PORT),
+        (meta.local_port.is_some(), FLAG_LOCAL_PORT),
+        (meta.comm.is_some(), FLAG_COMM),
+        (meta.exe.is_some(), FLAG_EXE),
+        (meta.path.is_some(), FLAG_PATH),
+        (meta.remote_addr.is_some(), FLAG_REMOTE_ADDR),
+        (meta.local_addr.is_some(), FLAG_LOCAL_ADDR),
+        (meta.bundle_id.is_some(), FLAG_BUNDLE_ID),
+        (!meta.extra.is_empty(), FLAG_EXTRA),
+    ] {
+        if present {
+            flags |= flag;
+        }
+    }
+
+    out.push(kind_code(&event.kind));
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&event.timestamp_ns.to_le_bytes());
+    out.extend_from_slice(&event.pid.to_le_bytes());
+    for v in [event.tid, meta.ppid, meta.uid].into_iter().flatten() {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    if let Some(fd) = meta.fd {
+        out.extend_from_slice(&fd.to_le_bytes());
+    }
+    for port in [meta.remote_port, meta.local_port].into_iter().flatten() {
+        out.extend_from_slice(&port.to_le_bytes());
+    }
+
+    put_bytes(out, event.id.as_bytes());
+    if let RawEventKind::Other(name) = &event.kind {
+        put_bytes(out, name.as_bytes());
+    }
+    for s in [
+        &meta.comm,
+        &meta.exe,
+        &meta.path,
+        &meta.remote_addr,
+        &meta.local_addr,
+        &meta.bundle_id,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        put_bytes(out, s.as_bytes());
+    }
+    if !meta.extra.is_empty() {
+        let extra = serde_json::to_vec(&meta.extra).expect("JSON values always serialize");
+        put_bytes(out, &extra);
+    }
+    put_bytes(out, &event.data);
+
+    let len = (out.len() - start - 4) as u32;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Decode one binary frame body (without its length prefix)
+pub fn decode_event(frame: &[u8]) -> Result<RawCaptureEvent, WireError> {
+    let mut r = FrameReader { buf: frame };
+
+    let code = r.u8()?;
+    let flags = u16::from_le_bytes(r.array()?);
+    let has = |flag: u16| flags & flag != 0;
+
+    let timestamp_ns = u64::from_le_bytes(r.array()?);
+    let pid = u32::from_le_bytes(r.array()?);
+    let mut opt_u32 = |flag| -> Result<Option<u32>, WireError> {
+        has(flag)
+            .then(|| r.array().map(u32::from_le_bytes))
+            .transpose()
+    };
+    let tid = opt_u32(FLAG_TID)?;
+    let ppid = opt_u32(FLAG_PPID)?;
+    let uid = opt_u32(FLAG_UID)?;
+    let fd = has(FLAG_FD)
+        .then(|| r.array().map(i32::from_le_bytes))
+        .transpose()?;
+    let remote_port = has(FLAG_REMOTE_PORT)
+        .then(|| r.array().map(u16::from_le_bytes))
+        .transpose()?;
+    let local_port = has(FLAG_LOCAL_PORT)
+        .then(|| r.array().map(u16::from_le_bytes))
+        .transpose()?;
+
+    let id = r.string()?;
+    let kind = kind_from_code(code, || r.string())?;
+    let mut opt_string = |flag| has(flag).then(|| r.string()).transpose();
+    let comm = opt_string(FLAG_COMM)?;
+    let exe = opt_string(FLAG_EXE)?;
+    let path = opt_string(FLAG_PATH)?;
+    let remote_addr = opt_string(FLAG_REMOTE_ADDR)?;
+    let local_addr = opt_string(FLAG_LOCAL_ADDR)?;
+    let bundle_id = opt_string(FLAG_BUNDLE_ID)?;
+    let extra = match has(FLAG_EXTRA) {
+        true => serde_json::from_slice(r.bytes()?)?,
+        false => Default::default(),
+    };
+    let data = r.bytes()?.to_vec();
+
+    Ok(RawCaptureEvent {
+        id,
+        timestamp_ns,
+        kind,
+        pid,
+        tid,
+        data,
+        metadata: RawEventMetadata {
+            exe,
+            comm,
+            ppid,
+            uid,
+            fd,
+            path,
+            remote_addr,
+            remote_port,
+            local_addr,
+            local_port,
+            bundle_id,
+            extra,
+            // The macOS and Windows helpers stamp events with the wall clock
+            clock: CaptureClock::Realtime,
+        },
+    })
+}
+
+struct FrameReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], WireError> {
+        if self.buf.len() < n {
+            return Err(WireError::Truncated);
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], WireError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], WireError> {
+        let len = u32::from_le_bytes(self.array()?) as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, WireError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| WireError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_event() -> RawCaptureEvent {
+        let mut extra = std::collections::HashMap::new();
+        extra.insert("sni".to_string(), serde_json::json!("api.openai.com"));
+        RawCaptureEvent {
+            id: "01JABCDEF".to_string(),
+            timestamp_ns: 1_703_680_000_000_000_000,
+            kind: RawEventKind::SslWrite,
+            pid: 12345,
+            tid: Some(67890),
+            data: b"POST /v1/chat/completions HTTP/1.1\r\n\r\n\x00\xff".to_vec(),
+            metadata: RawEventMetadata {
+                exe: Some("/usr/bin/python3".to_string()),
+                comm: Some("python3".to_string()),
+                ppid: Some(1),
+                uid: Some(501),
+                fd: Some(-1),
+                path: Some("/tmp/x".to_string()),
+                remote_addr: Some("api.openai.com".to_string()),
+                remote_port: Some(443),
+                local_addr: Some("10.0.0.2".to_string()),
+                local_port: Some(51234),
+                bundle_id: Some("com.example.app".to_string()),
+                extra,
+                clock: CaptureClock::Realtime,
+            },
+        }
+    }
+
+    fn round_trip(event: &RawCaptureEvent) -> RawCaptureEvent {
+        let mut frame = Vec::new();
+        encode_event(event, &mut frame);
+        let len = u32::from_le_bytes(frame[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, frame.len() - 4);
+        decode_event(&frame[4..]).unwrap()
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let event = full_event();
+        assert_eq!(format!("{:?}", round_trip(&event)), format!("{:?}", event));
+
+        // Every optional field absent
+        let minimal = RawCaptureEvent {
+            id: String::new(),
+            timestamp_ns: 0,
+            kind: RawEventKind::Other("custom".to_string()),
+            pid: 7,
+            tid: None,
+            data: vec![],
+            metadata: RawEventMetadata::default(),
+        };
+        assert_eq!(
+            format!("{:?}", round_trip(&minimal)),
+            format!("{:?}", minimal)
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        let mut frame = Vec::new();
+        encode_event(&full_event(), &mut frame);
+        let body = &frame[4..];
+
+        assert!(matches!(
+            decode_event(&body[..body.len() - 1]),
+            Err(WireError::Truncated)
+        ));
+
+        let mut bad_kind = body.to_vec();
+        bad_kind[0] = 200;
+        assert!(matches!(
+            decode_event(&bad_kind),
+            Err(WireError::UnknownKind(200))
+        ));
+    }
+
+    #[test]
+    fn test_negotiates_binary() {
+        let mut decoder = WireDecoder::new();
+        decoder.feed(&Hello::new(vec![Encoding::Binary, Encoding::Json]).to_line());
+
+        let Some(WireMessage::Hello(ack)) = decoder.next_message().unwrap() else {
+            panic!("expected hello");
+        };
+        assert_eq!(ack.encoding, Encoding::Binary);
+        assert_eq!(ack.version, PROTOCOL_VERSION);
+        let line = String::from_utf8(ack.to_line()).unwrap();
+        assert_eq!(HelloAck::parse(line.trim_end()), Some(ack));
+        assert_eq!(decoder.encoding(), Some(Encoding::Binary));
+
+        // Two frames delivered in arbitrary chunks
+        let mut bytes = Vec::new();
+        encode_event(&full_event(), &mut bytes);
+        encode_event(&full_event(), &mut bytes);
+        let (first, rest) = bytes.split_at(10);
+        decoder.feed(first);
+        assert!(decoder.next_message().unwrap().is_none());
+        decoder.feed(rest);
+        for _ in 0..2 {
+            let Some(WireMessage::Event(event)) = decoder.next_message().unwrap() else {
+                panic!("expected event");
+            };
+            assert_eq!(event.data, full_event().data);
+        }
+        assert!(decoder.next_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_old_client_falls_back_to_json() {
+        let mut decoder = WireDecoder::new();
+        decoder.feed(b"{\"id\":\"a\",\"kind\":\"SslWrite\"}\r\n\n{\"id\":\"b\"}\n");
+
+        let Some(WireMessage::Json(line)) = decoder.next_message().unwrap() else {
+            panic!("expected json");
+        };
+        assert_eq!(line, r#"{"id":"a","kind":"SslWrite"}"#);
+        assert_eq!(decoder.encoding(), Some(Encoding::Json));
+        let Some(WireMessage::Json(line)) = decoder.next_message().unwrap() else {
+            panic!("expected json");
+        };
+        assert_eq!(line, r#"{"id":"b"}"#);
+        assert!(decoder.next_message().unwrap().is_none());
+
+        // Unknown encodings are ignored rather than failing the handshake
+        let hello = Hello::parse(r#"{"oisp_hello":{"version":2,"encodings":["cbor","json"]}}"#)
+            .expect("should parse");
+        assert_eq!(hello.encodings, vec![Encoding::Json]);
+        assert_eq!(hello.negotiate().version, PROTOCOL_VERSION);
+
+        // A client that only offers JSON, or an unversioned hello, also gets JSON
+        for hello in [
+            Hello::new(vec![Encoding::Json]),
+            Hello {
+                version: 0,
+                encodings: vec![Encoding::Binary],
+            },
+        ] {
+            assert_eq!(hello.negotiate().encoding, Encoding::Json);
+        }
+    }
+
+    #[test]
+    fn test_oversized_frame_is_fatal() {
+        let mut decoder = WireDecoder::new();
+        decoder.feed(&Hello::new(vec![Encoding::Binary]).to_line());
+        decoder.next_message().unwrap();
+
+        decoder.feed(&(MAX_FRAME_SIZE as u32 + 1).to_le_bytes());
+        let err = decoder.next_message().unwrap_err();
+        assert!(err.is_fatal());
+        assert_eq!(
+            decoder.failed_frame(),
+            &(MAX_FRAME_SIZE as u32 + 1).to_le_bytes()
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Diagnostics for input the capture transports fail to parse
+//!
+//! The socket and pipe servers only used to count parse errors, which made
+//! a protocol mismatch with a capture helper hard to debug. [`ParseDiagnostics`]
+//! keeps a histogram of error kinds and, at a bounded rate, logs the decode
+//! error with a hexdump of the start of the offending frame or line.
+//!
+//! Frames carry captured traffic, so the dump masks anything that looks
+//! sensitive: what the redaction patterns match, the values of credential
+//! headers, and long opaque tokens such as base64 payloads.
+
+use crate::redaction::{find_matches, RedactionConfig};
+use crate::wire::WireError;
+use parking_lot::Mutex;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Bytes of the offending input included in a diagnostic
+pub const DUMP_BYTES: usize = 64;
+
+/// Diagnostics emitted per [`DIAGNOSTIC_WINDOW`]; further errors are only counted
+pub const MAX_DIAGNOSTICS_PER_WINDOW: u32 = 5;
+
+/// Rate limit window for diagnostics
+pub const DIAGNOSTIC_WINDOW: Duration = Duration::from_secs(60);
+
+/// Diagnostics kept for inspection
+const RECENT_DIAGNOSTICS: usize = 16;
+
+/// Byte shown in place of masked content
+const MASK: u8 = b'*';
+
+/// Values of headers and fields that carry credentials
+static CREDENTIAL_VALUES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)(?:authorization|proxy-authorization|cookie|set-cookie|x-api-key|api-key|password|token)"?\s*[:=]\s*"?([^\r\n",}]+)"#,
+    )
+    .unwrap()
+});
+
+/// Long runs of token characters: keys, base64 payloads, session ids
+static OPAQUE_TOKENS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9+/=_\-]{24,}").unwrap());
+
+/// What went wrong parsing a frame or line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorKind {
+    FrameTooLarge,
+    Truncated,
+    UnknownKind,
+    InvalidUtf8,
+    InvalidExtra,
+    /// A JSON line that is not valid JSON or does not match the schema
+    InvalidJson,
+    /// Well-formed input with unusable values (bad base64, unknown kind name)
+    InvalidEvent,
+}
+
+impl ParseErrorKind {
+    pub const ALL: [ParseErrorKind; 7] = [
+        ParseErrorKind::FrameTooLarge,
+        ParseErrorKind::Truncated,
+        ParseErrorKind::UnknownKind,
+        ParseErrorKind::InvalidUtf8,
+        ParseErrorKind::InvalidExtra,
+        ParseErrorKind::InvalidJson,
+        ParseErrorKind::InvalidEvent,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseErrorKind::FrameTooLarge => "frame_too_large",
+            ParseErrorKind::Truncated => "truncated",
+            ParseErrorKind::UnknownKind => "unknown_kind",
+            ParseErrorKind::InvalidUtf8 => "invalid_utf8",
+            ParseErrorKind::InvalidExtra => "invalid_extra",
+            ParseErrorKind::InvalidJson => "invalid_json",
+            ParseErrorKind::InvalidEvent => "invalid_event",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&WireError> for ParseErrorKind {
+    fn from(error: &WireError) -> Self {
+        match error {
+            WireError::FrameTooLarge(_) => ParseErrorKind::FrameTooLarge,
+            WireError::Truncated => ParseErrorKind::Truncated,
+            WireError::UnknownKind(_) => ParseErrorKind::UnknownKind,
+            WireError::InvalidUtf8 => ParseErrorKind::InvalidUtf8,
+            WireError::InvalidExtra(_) => ParseErrorKind::InvalidExtra,
+        }
+    }
+}
+
+/// One logged parse failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseDiagnostic {
+    pub kind: ParseErrorKind,
+    pub error: String,
+    /// Length of the offending input
+    pub len: usize,
+    /// Hexdump of its first [`DUMP_BYTES`] bytes, sensitive content masked
+    pub dump: String,
+    /// Failures not logged since the previous diagnostic, due to the rate limit
+    pub suppressed: u64,
+}
+
+/// Parse error histogram and rate-limited diagnostics for one transport
+pub struct ParseDiagnostics {
+    /// Transport name, for log messages
+    transport: &'static str,
+    counts: [AtomicU64; ParseErrorKind::ALL.len()],
+    limiter: Mutex<RateWindow>,
+    recent: Mutex<VecDeque<ParseDiagnostic>>,
+}
+
+#[derive(Default)]
+struct RateWindow {
+    started: Option<Instant>,
+    emitted: u32,
+    suppressed: u64,
+}
+
+impl ParseDiagnostics {
+    pub fn new(transport: &'static str) -> Self {
+        Self {
+            transport,
+            counts: Default::default(),
+            limiter: Mutex::new(RateWindow::default()),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_DIAGNOSTICS)),
+        }
+    }
+
+    /// Count a parse failure and, unless rate limited, log a diagnostic
+    ///
+    /// `input` is the frame or line that failed; only its start is dumped.
+    pub fn record(
+        &self,
+        kind: ParseErrorKind,
+        input: &[u8],
+        error: &dyn fmt::Display,
+    ) -> Option<ParseDiagnostic> {
+        self.counts[kind.index()].fetch_add(1, Ordering::Relaxed);
+
+        let suppressed = {
+            let mut window = self.limiter.lock();
+            let now = Instant::now();
+            if window
+                .started
+                .is_none_or(|started| now.duration_since(started) >= DIAGNOSTIC_WINDOW)
+            {
+                window.started = Some(now);
+                window.emitted = 0;
+            }
+            if window.emitted >= MAX_DIAGNOSTICS_PER_WINDOW {
+                window.suppressed += 1;
+                return None;
+            }
+            window.emitted += 1;
+            std::mem::take(&mut window.suppressed)
+        };
+
+        let diagnostic = ParseDiagnostic {
+            kind,
+            error: error.to_string(),
+            len: input.len(),
+            dump: redacted_hexdump(&input[..input.len().min(DUMP_BYTES)]),
+            suppressed,
+        };
+        warn!(
+            "Failed to parse {} input ({}, {} bytes): {}{}\n{}",
+            self.transport,
+            kind,
+            diagnostic.len,
+            diagnostic.error,
+            if suppressed > 0 {
+                format!(" ({} similar errors suppressed)", suppressed)
+            } else {
+                String::new()
+            },
+            diagnostic.dump
+        );
+
+        let mut recent = self.recent.lock();
+        if recent.len() == RECENT_DIAGNOSTICS {
+            recent.pop_front();
+        }
+        recent.push_back(diagnostic.clone());
+        Some(diagnostic)
+    }
+
+    /// Total parse failures
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Parse failures by kind, omitting kinds that have not occurred
+    pub fn histogram(&self) -> BTreeMap<ParseErrorKind, u64> {
+        ParseErrorKind::ALL
+            .iter()
+            .map(|kind| (*kind, self.counts[kind.index()].load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Most recent diagnostics, oldest first
+    pub fn recent(&self) -> Vec<ParseDiagnostic> {
+        self.recent.lock().iter().cloned().collect()
+    }
+}
+
+/// Hexdump of `bytes`, 16 per line, with sensitive content masked
+///
+/// Masked bytes show as `**` in the hex column and `*` in the text column.
+pub fn redacted_hexdump(bytes: &[u8]) -> String {
+    let masked = sensitive_ranges(bytes);
+    let is_masked = |i: usize| masked.iter().any(|r| r.contains(&i));
+
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let offset = line * 16;
+        let mut hex = String::with_capacity(48);
+        let mut text = String::with_capacity(16);
+        for (i, b) in chunk.iter().enumerate() {
+            if is_masked(offset + i) {
+                hex.push_str("** ");
+                text.push(MASK as char);
+            } else {
+                hex.push_str(&format!("{:02x} ", b));
+                text.push(if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                });
+            }
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("{:04x}  {:<48} |{}|", offset, hex, text));
+    }
+    out
+}
+
+/// Byte ranges of `bytes` that must not appear in a dump
+fn sensitive_ranges(bytes: &[u8]) -> Vec<Range<usize>> {
+    // Replace non-ASCII bytes one for one, so offsets in the text are
+    // offsets in the input
+    let text: String = bytes
+        .iter()
+        .map(|&b| if b.is_ascii() { b as char } else { '.' })
+        .collect();
+
+    let mut ranges: Vec<Range<usize>> = find_matches(&text, None, &RedactionConfig::default())
+        .into_iter()
+        .map(|m| m.range)
+        .collect();
+    ranges.extend(
+        CREDENTIAL_VALUES
+            .captures_iter(bytes)
+            .filter_map(|c| c.get(1))
+            .map(|m| m.range()),
+    );
+    ranges.extend(OPAQUE_TOKENS.find_iter(bytes).map(|m| m.range()));
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump_layout() {
+        let dump = redacted_hexdump(b"{\"id\":\"x\",\"pid\":1}\n\x00\xff");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0000  7b 22 69 64 22 3a "));
+        assert!(lines[0].ends_with("|{\"id\":\"x\",\"pid\":|"));
+        assert!(lines[1].starts_with("0010  31 7d 0a 00 ff "));
+        assert!(lines[1].ends_with("|1}...|"));
+    }
+
+    /// Text column of a dump, joined across lines
+    fn dump_text(dump: &str) -> String {
+        dump.lines().map(|line| &line[56..line.len() - 1]).collect()
+    }
+
+    #[test]
+    fn test_hexdump_masks_secrets() {
+        let input = b"POST /v1 HTTP/1.1\r\nAuthorization: Bearer abc\r\nX: sk-abcdefghijklmnopqrstuvwxyz \"data\":\"SGVsbG8gV29ybGQgdGhpcyBpcyBzZWNyZXQ=\"";
+        let dump = redacted_hexdump(input);
+        for secret in ["Bearer", "abc\r", "sk-abc", "SGVsbG8", "53 47 56"] {
+            assert!(!dump.contains(secret), "{} leaked:\n{}", secret, dump);
+        }
+        // Structure stays readable
+        let text = dump_text(&dump);
+        assert_eq!(
+            text,
+            format!(
+                "POST /v1 HTTP/1.1..Authorization: {}..X: {} \"data\":\"{}\"",
+                "*".repeat(10),
+                "*".repeat(29),
+                "*".repeat(36)
+            )
+        );
+    }
+
+    #[test]
+    fn test_histogram_and_rate_limit() {
+        let diagnostics = ParseDiagnostics::new("test");
+        let error = "bad frame";
+        let emitted = (0..MAX_DIAGNOSTICS_PER_WINDOW + 3)
+            .filter(|_| {
+                diagnostics
+                    .record(ParseErrorKind::Truncated, b"\x01\x02", &error)
+                    .is_some()
+            })
+            .count();
+        assert_eq!(emitted, MAX_DIAGNOSTICS_PER_WINDOW as usize);
+        assert!(diagnostics
+            .record(ParseErrorKind::InvalidJson, b"{", &error)
+            .is_none());
+
+        assert_eq!(diagnostics.total(), MAX_DIAGNOSTICS_PER_WINDOW as u64 + 4);
+        assert_eq!(
+            diagnostics.histogram(),
+            BTreeMap::from([
+                (
+                    ParseErrorKind::Truncated,
+                    MAX_DIAGNOSTICS_PER_WINDOW as u64 + 3
+                ),
+                (ParseErrorKind::InvalidJson, 1),
+            ])
+        );
+        assert_eq!(
+            diagnostics.recent().len(),
+            MAX_DIAGNOSTICS_PER_WINDOW as usize
+        );
+
+        // A new window reports how many were dropped
+        diagnostics.limiter.lock().started = Some(Instant::now() - DIAGNOSTIC_WINDOW);
+        let next = diagnostics
+            .record(ParseErrorKind::Truncated, b"", &error)
+            .unwrap();
+        assert_eq!(next.suppressed, 4);
+    }
+}
@@ -0,0 +1,148 @@
+//! Decoding a large response delivered in many SSL reads
+//!
+//! Run with `cargo bench -p oisp-decode`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use oisp_core::plugins::{DecodePlugin, RawCaptureEvent, RawEventKind, RawEventMetadata};
+use oisp_decode::HttpDecoder;
+use std::io::Write;
+
+/// Size of each simulated SSL read
+const READ_SIZE: usize = 16 * 1024;
+
+fn raw(kind: RawEventKind, data: &[u8]) -> RawCaptureEvent {
+    RawCaptureEvent {
+        id: "bench".to_string(),
+        timestamp_ns: 1_000_000_000,
+        kind,
+        pid: 4242,
+        tid: Some(1),
+        data: data.to_vec(),
+        metadata: RawEventMetadata {
+            comm: Some("bench".to_string()),
+            fd: Some(7),
+            ..Default::default()
+        },
+    }
+}
+
+/// Request plus a chat completion split into SSL reads, either gzipped and
+/// chunked or sent as-is with a Content-Length
+fn capture(content_len: usize, gzip: bool) -> (RawCaptureEvent, Vec<RawCaptureEvent>) {
+    let request_body = r#"{"model":"gpt-4o","messages":[{"role":"user","content":"Hello"}]}"#;
+    let request = format!(
+        "POST /v1/chat/completions HTTP/1.1\r\n\
+         Host: api.openai.com\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         \r\n\
+         {}",
+        request_body.len(),
+        request_body
+    );
+
+    // Pseudo-random words, so the body does not compress to almost nothing
+    let words = [
+        "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+    ];
+    let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut content = String::with_capacity(content_len + 16);
+    while content.len() < content_len {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        content.push_str(words[(seed >> 61) as usize]);
+        content.push_str(&format!("{} ", seed >> 48));
+    }
+    let body = serde_json::json!({
+        "id": "chatcmpl-bench",
+        "object": "chat.completion",
+        "model": "gpt-4o-2024-08-06",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 9, "completion_tokens": 4096, "total_tokens": 4105}
+    });
+    let body = body.to_string();
+
+    let response = if gzip {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(body.as_bytes()).unwrap();
+        let compressed = gz.finish().unwrap();
+
+        let mut response = b"HTTP/1.1 200 OK\r\n\
+            Content-Type: application/json\r\n\
+            Content-Encoding: gzip\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n"
+            .to_vec();
+        for chunk in compressed.chunks(4096) {
+            response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            response.extend_from_slice(chunk);
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"0\r\n\r\n");
+        response
+    } else {
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             \r\n\
+             {}",
+            body.len(),
+            body
+        )
+        .into_bytes()
+    };
+
+    let reads = response
+        .chunks(READ_SIZE)
+        .map(|read| raw(RawEventKind::SslRead, read))
+        .collect();
+    (raw(RawEventKind::SslWrite, request.as_bytes()), reads)
+}
+
+fn bench_large_response(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("reassembly");
+
+    let cases = [
+        ("gzip_chunked", 256 * 1024, true),
+        ("gzip_chunked", 4 * 1024 * 1024, true),
+        ("identity", 4 * 1024 * 1024, false),
+    ];
+    for (name, content_len, gzip) in cases {
+        let (request, reads) = capture(content_len, gzip);
+        let wire_len: usize = reads.iter().map(|r| r.data.len()).sum();
+        group.throughput(Throughput::Bytes(wire_len as u64));
+        group.bench_function(format!("{}_{}k", name, content_len / 1024), |b| {
+            b.iter_batched(
+                || (HttpDecoder::new(), request.clone(), reads.clone()),
+                |(decoder, request, reads)| {
+                    runtime.block_on(async {
+                        decoder.decode(request).await.unwrap();
+                        let mut events = Vec::new();
+                        for read in reads {
+                            events.extend(decoder.decode(read).await.unwrap());
+                        }
+                        assert_eq!(events.len(), 1);
+                        events
+                    })
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_large_response);
+criterion_main!(benches);
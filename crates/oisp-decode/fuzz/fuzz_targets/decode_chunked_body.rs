@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oisp_decode::http::{decode_chunked_body, is_chunked_body_complete};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = is_chunked_body_complete(data);
+    if let Some(body) = decode_chunked_body(data) {
+        assert!(body.len() <= data.len());
+    }
+});
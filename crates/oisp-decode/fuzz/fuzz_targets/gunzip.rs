@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oisp_decode::http::{gunzip, gunzip_lenient};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = gunzip(data);
+    let _ = gunzip_lenient(data);
+});
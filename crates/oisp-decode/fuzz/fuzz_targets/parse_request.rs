@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oisp_decode::http::{is_http_request, parse_request};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = is_http_request(data);
+    if let Some(request) = parse_request(data) {
+        let _ = request.has_web_context();
+    }
+});
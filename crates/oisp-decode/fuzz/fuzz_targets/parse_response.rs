@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oisp_decode::http::{extract_partial_body, is_http_response, parse_response};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = is_http_response(data);
+    let _ = extract_partial_body(data);
+    let _ = parse_response(data);
+});
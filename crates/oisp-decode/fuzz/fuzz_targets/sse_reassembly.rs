@@ -0,0 +1,31 @@
+#![no_main]
+
+//! Feeds a stream body in pieces, as it arrives over several SSL reads. The
+//! first byte picks the piece size so the fuzzer explores split points.
+
+use libfuzzer_sys::fuzz_target;
+use oisp_decode::ndjson::OllamaStreamReassembler;
+use oisp_decode::sse::{AnthropicStreamReassembler, SseParser, StreamReassembler};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, body)) = data.split_first() else {
+        return;
+    };
+    let piece = usize::from(split).max(1);
+
+    let mut parser = SseParser::new();
+    let mut openai = StreamReassembler::new();
+    let mut anthropic = AnthropicStreamReassembler::new();
+    let mut ollama = OllamaStreamReassembler::new();
+    for read in body.chunks(piece) {
+        parser.feed(read);
+        openai.feed(read);
+        anthropic.feed(read);
+        ollama.feed(read);
+    }
+
+    let _ = parser.take_events();
+    let _ = (openai.content(), openai.usage(), openai.finish_reason());
+    let _ = (anthropic.content(), anthropic.usage(), anthropic.model());
+    let _ = (ollama.content(), ollama.usage(), ollama.model());
+});
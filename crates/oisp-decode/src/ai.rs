@@ -1,11 +1,15 @@
 //! AI request/response parsing
 
+use crate::http::MultipartForm;
+use crate::tool_args::tool_call;
 use oisp_core::events::{
-    AgentContext, AiRequestData, AiResponseData, Choice, ConversationContext, FinishReason,
-    Message, MessageContent, MessageRole, ModelInfo, ModelParameters, ProviderInfo, RequestType,
-    ThinkingBlock, ThinkingMode, ToolArguments, ToolCall, ToolDefinition, ToolType, Usage,
+    AgentContext, AiEmbeddingData, AiRequestData, AiResponseData, Choice, ConversationContext,
+    ErrorInfo, FinishReason, ImageInfo, ImageSource, Message, MessageContent, MessageRole,
+    ModelInfo, ModelParameters, ProviderInfo, RequestType, ThinkingBlock, ThinkingMode, ToolCall,
+    ToolDefinition, ToolType, Usage,
 };
 use oisp_core::providers::Provider;
+use oisp_core::redaction::{redact, RedactionConfig};
 use serde_json::Value;
 use sha2::{Digest, Sha256};
 
@@ -170,6 +174,122 @@ pub fn parse_ai_response(
     })
 }
 
+/// `ErrorInfo::code` of rate limit errors from providers without their own code
+pub const RATE_LIMIT_ERROR: &str = "rate_limit_exceeded";
+
+/// `ErrorInfo::code` of context length errors from providers without their
+/// own code
+pub const CONTEXT_LENGTH_ERROR: &str = "context_length_exceeded";
+
+/// Parse the body of a failed (non-2xx) AI response
+///
+/// The error is always set, with whatever the body provides; see
+/// [`parse_error_body`].
+pub fn parse_error_response(
+    body: &Value,
+    request_id: &str,
+    provider: Provider,
+    status_code: u16,
+) -> AiResponseData {
+    let error = parse_error_body(body, status_code).unwrap_or(ErrorInfo {
+        error_type: None,
+        message: None,
+        code: None,
+    });
+
+    AiResponseData {
+        request_id: request_id.to_string(),
+        // Anthropic error bodies carry the request ID
+        provider_request_id: body
+            .get("request_id")
+            .and_then(|i| i.as_str())
+            .map(String::from),
+        provider: Some(ProviderInfo {
+            name: format!("{:?}", provider).to_lowercase(),
+            endpoint: None,
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model: None,
+        status_code: Some(status_code),
+        success: Some(false),
+        error: Some(error),
+        choices: Vec::new(),
+        tool_calls: Vec::new(),
+        tool_calls_count: None,
+        usage: None,
+        latency_ms: None,
+        time_to_first_token_ms: None,
+        was_cached: None,
+        finish_reason: None,
+        thinking: None,
+    }
+}
+
+/// Parse a provider error body
+///
+/// Handles OpenAI's `{"error": {"message", "type", "param", "code"}}`,
+/// Anthropic's `{"type": "error", "error": {"type", "message"}}`, a bare
+/// `{"error": "message"}` and AWS's `{"message": "..."}`. `code` is the provider's own when it sends one;
+/// otherwise rate limits and context length errors get [`RATE_LIMIT_ERROR`]
+/// and [`CONTEXT_LENGTH_ERROR`], so they read the same for every provider.
+pub fn parse_error_body(body: &Value, status_code: u16) -> Option<ErrorInfo> {
+    let mut error = match body.get("error") {
+        Some(Value::Object(e)) => ErrorInfo {
+            error_type: e.get("type").and_then(|t| t.as_str()).map(String::from),
+            message: e.get("message").and_then(|m| m.as_str()).map(String::from),
+            code: match e.get("code") {
+                Some(Value::String(code)) => Some(code.clone()),
+                Some(Value::Number(code)) => Some(code.to_string()),
+                _ => None,
+            },
+        },
+        Some(Value::String(message)) => ErrorInfo {
+            error_type: None,
+            message: Some(message.clone()),
+            code: None,
+        },
+        _ if body.get("message").is_some_and(|m| m.is_string()) => ErrorInfo {
+            error_type: None,
+            message: body
+                .get("message")
+                .and_then(|m| m.as_str())
+                .map(String::from),
+            code: None,
+        },
+        _ if status_code == 429 => ErrorInfo {
+            error_type: None,
+            message: None,
+            code: None,
+        },
+        _ => return None,
+    };
+
+    if error.code.is_none() {
+        if status_code == 429 || error.error_type.as_deref() == Some("rate_limit_error") {
+            error.code = Some(RATE_LIMIT_ERROR.to_string());
+        } else if error
+            .message
+            .as_deref()
+            .is_some_and(is_context_length_message)
+        {
+            error.code = Some(CONTEXT_LENGTH_ERROR.to_string());
+        }
+    }
+    Some(error)
+}
+
+/// Whether an error message says the prompt exceeds the context window
+fn is_context_length_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    // Anthropic: "prompt is too long: 210000 tokens > 200000 maximum"
+    // OpenAI-compatible servers: "This model's maximum context length is ..."
+    message.contains("prompt is too long")
+        || message.contains("maximum context length")
+        || message.contains("context window")
+}
+
 fn parse_messages(messages: Option<&Value>) -> Vec<Message> {
     messages
         .and_then(|m| m.as_array())
@@ -184,24 +304,154 @@ fn parse_single_message(msg: &Value) -> Message {
         .map(parse_role)
         .unwrap_or(MessageRole::User);
 
-    let content = msg.get("content");
-    let content_str = content.and_then(|c| c.as_str());
+    // Multimodal content is an array of text and image parts
+    let mut message = match msg.get("content") {
+        Some(Value::String(text)) => text_message(role, Some(text.clone())),
+        Some(Value::Array(parts)) => parts_message(role, parts),
+        _ => text_message(role, None),
+    };
+    message.tool_call_id = msg
+        .get("tool_call_id")
+        .and_then(|t| t.as_str())
+        .map(String::from);
+    message.name = msg.get("name").and_then(|n| n.as_str()).map(String::from);
+    message
+}
 
-    Message {
-        role,
-        content: content_str.map(|s| MessageContent::Text(s.to_string())),
-        content_hash: content_str.map(hash_content),
-        content_length: content_str.map(|s| s.len()),
-        has_images: None,
-        image_count: None,
-        tool_call_id: msg
-            .get("tool_call_id")
-            .and_then(|t| t.as_str())
-            .map(String::from),
-        name: msg.get("name").and_then(|n| n.as_str()).map(String::from),
+/// Build a message from content parts: text parts are concatenated, image
+/// parts are described by [`image_part_info`]
+fn parts_message(role: MessageRole, parts: &[Value]) -> Message {
+    let mut text = String::new();
+    let mut images = Vec::new();
+    for part in parts {
+        match part.get("type").and_then(|t| t.as_str()) {
+            Some("input_text") | Some("output_text") | Some("text") => {
+                if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                    text.push_str(t);
+                }
+            }
+            _ => images.extend(image_part_info(part)),
+        }
+    }
+    let mut message = text_message(role, (!text.is_empty()).then_some(text));
+    set_images(&mut message, images);
+    message
+}
+
+fn set_images(message: &mut Message, images: Vec<ImageInfo>) {
+    if !images.is_empty() {
+        message.has_images = Some(true);
+        message.image_count = Some(images.len());
+        message.images = images;
+    }
+}
+
+/// Describe an image content part without keeping its data
+///
+/// Handles OpenAI chat (`image_url`), Responses API (`input_image`) and
+/// Anthropic (`image` with a `source`) parts; other parts return None.
+pub fn image_part_info(part: &Value) -> Option<ImageInfo> {
+    match part.get("type")?.as_str()? {
+        "image_url" => {
+            let image_url = part.get("image_url")?;
+            let url = image_url.get("url").unwrap_or(image_url).as_str()?;
+            Some(url_image_info(url))
+        }
+        "input_image" => Some(match part.get("image_url").and_then(|u| u.as_str()) {
+            Some(url) => url_image_info(url),
+            None => ImageInfo {
+                source: ImageSource::File,
+                media_type: None,
+                size_bytes: None,
+            },
+        }),
+        "image" => {
+            let source = part.get("source")?;
+            let media_type = source
+                .get("media_type")
+                .and_then(|m| m.as_str())
+                .map(String::from);
+            Some(match source.get("type").and_then(|t| t.as_str()) {
+                Some("base64") => {
+                    let data = source.get("data").and_then(|d| d.as_str()).unwrap_or("");
+                    ImageInfo {
+                        media_type: media_type.or_else(|| sniff_media_type(data)),
+                        ..base64_image_info(data)
+                    }
+                }
+                Some("url") => ImageInfo {
+                    source: ImageSource::Url,
+                    media_type,
+                    size_bytes: None,
+                },
+                _ => ImageInfo {
+                    source: ImageSource::File,
+                    media_type,
+                    size_bytes: None,
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Describe an image given by URL, which may be a `data:` URL
+fn url_image_info(url: &str) -> ImageInfo {
+    let Some(data_url) = url.strip_prefix("data:") else {
+        return ImageInfo {
+            source: ImageSource::Url,
+            media_type: None,
+            size_bytes: None,
+        };
+    };
+    let (header, data) = data_url.split_once(',').unwrap_or((data_url, ""));
+    let media_type = header
+        .split(';')
+        .next()
+        .filter(|m| !m.is_empty())
+        .map(String::from);
+    if header.ends_with(";base64") {
+        ImageInfo {
+            media_type: media_type.or_else(|| sniff_media_type(data)),
+            ..base64_image_info(data)
+        }
+    } else {
+        ImageInfo {
+            source: ImageSource::Base64,
+            media_type,
+            size_bytes: Some(data.len() as u64),
+        }
+    }
+}
+
+/// Describe a base64-encoded image, sniffing its format from the leading bytes
+pub fn base64_image_info(data: &str) -> ImageInfo {
+    let data = data.trim_end();
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count().min(2);
+    ImageInfo {
+        source: ImageSource::Base64,
+        media_type: sniff_media_type(data),
+        size_bytes: Some(
+            (data.len() / 4 * 3 + data.len() % 4 * 3 / 4).saturating_sub(padding) as u64,
+        ),
     }
 }
 
+/// Image format from the base64 encoding of its magic bytes
+fn sniff_media_type(data: &str) -> Option<String> {
+    const SIGNATURES: [(&str, &str); 5] = [
+        ("iVBORw0KGgo", "image/png"),
+        ("/9j/", "image/jpeg"),
+        ("R0lGOD", "image/gif"),
+        ("UklGR", "image/webp"),
+        ("Qk", "image/bmp"),
+    ];
+    SIGNATURES
+        .iter()
+        .find(|(prefix, _)| data.starts_with(prefix))
+        .map(|(_, media_type)| media_type.to_string())
+}
+
 fn parse_role(role: &str) -> MessageRole {
     match role.to_lowercase().as_str() {
         "system" => MessageRole::System,
@@ -257,16 +507,9 @@ fn extract_tool_calls(body: &Value) -> Vec<ToolCall> {
                     let arguments = tc
                         .get("function")
                         .and_then(|f| f.get("arguments"))
-                        .and_then(|a| a.as_str())
-                        .map(|s| ToolArguments::String(s.to_string()));
+                        .filter(|a| a.is_string());
 
-                    Some(ToolCall {
-                        id,
-                        name: name.to_string(),
-                        tool_type: Some(ToolType::Function),
-                        arguments,
-                        arguments_hash: None,
-                    })
+                    Some(tool_call(id, name, arguments))
                 })
                 .collect()
         })
@@ -318,10 +561,17 @@ fn parse_finish_reason(reason: &str) -> Option<FinishReason> {
 }
 
 fn detect_request_type(body: &Value) -> RequestType {
+    let model = body.get("model").and_then(Value::as_str).unwrap_or("");
     if body.get("messages").is_some() {
         RequestType::Chat
+    } else if body.get("prompt").is_some() && body.get("size").is_some() {
+        RequestType::Image
     } else if body.get("prompt").is_some() {
         RequestType::Completion
+    } else if body.get("input").is_some() && body.get("voice").is_some() {
+        RequestType::Audio
+    } else if body.get("input").is_some() && model.contains("moderation") {
+        RequestType::Moderation
     } else if body.get("input").is_some() {
         RequestType::Embedding
     } else {
@@ -329,6 +579,97 @@ fn detect_request_type(body: &Value) -> RequestType {
     }
 }
 
+/// Request type implied by an API path
+///
+/// Covers the OpenAI paths (also served by most compatible servers) and the
+/// Anthropic, Gemini, Cohere and Ollama equivalents. `None` when the path
+/// does not tell, in which case the body shape decides.
+pub fn request_type_from_path(path: &str) -> Option<RequestType> {
+    let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+    let last = path.rsplit('/').next().unwrap_or(path);
+
+    // Gemini appends the method to the model: `models/gemini-pro:generateContent`
+    if let Some((_, method)) = last.rsplit_once(':') {
+        return match method {
+            "generateContent" | "streamGenerateContent" => Some(RequestType::Chat),
+            "embedContent" | "batchEmbedContents" => Some(RequestType::Embedding),
+            _ => None,
+        };
+    }
+    if path.contains("/audio/") {
+        // transcriptions, translations, speech
+        return Some(RequestType::Audio);
+    }
+    if path.contains("/images/") {
+        // generations, edits, variations
+        return Some(RequestType::Image);
+    }
+    if path.ends_with("/chat/completions") || path.ends_with("/v1/messages") {
+        return Some(RequestType::Chat);
+    }
+    match last {
+        "embeddings" | "embed" => Some(RequestType::Embedding),
+        "moderations" => Some(RequestType::Moderation),
+        "chat" | "responses" => Some(RequestType::Chat),
+        "completions" | "complete" | "generate" => Some(RequestType::Completion),
+        _ => None,
+    }
+}
+
+/// Whether a request goes to an embeddings, moderation, image or speech
+/// endpoint, whose bodies carry `input` or `prompt` rather than messages
+pub fn is_non_chat_request(path: &str, body: &Value) -> bool {
+    matches!(
+        request_type_from_path(path),
+        Some(
+            RequestType::Embedding
+                | RequestType::Moderation
+                | RequestType::Image
+                | RequestType::Audio
+        )
+    ) && (body.get("input").is_some() || body.get("prompt").is_some())
+}
+
+/// Summarize an embeddings response as `ai.embedding` data
+///
+/// Vectors are read from OpenAI's `data[].embedding` (floats or base64),
+/// Ollama's and Cohere's `embeddings[]` and Gemini's `embedding.values`;
+/// usage, latency, provider and model come from the parsed response.
+pub fn parse_embedding_response(body: &Value, response: &AiResponseData) -> AiEmbeddingData {
+    let vectors: Vec<&Value> = if let Some(data) = body.get("data").and_then(Value::as_array) {
+        data.iter().filter_map(|d| d.get("embedding")).collect()
+    } else if let Some(embeddings) = body.get("embeddings").and_then(Value::as_array) {
+        embeddings
+            .iter()
+            .map(|e| e.get("values").unwrap_or(e))
+            .collect()
+    } else if let Some(embedding) = body.get("embedding") {
+        vec![embedding.get("values").unwrap_or(embedding)]
+    } else {
+        Vec::new()
+    };
+
+    let dimensions = vectors.first().and_then(|vector| match vector {
+        Value::Array(values) => Some(values.len()),
+        // `encoding_format: base64` packs little-endian f32s
+        Value::String(encoded) => {
+            let padding = encoded.bytes().rev().take_while(|b| *b == b'=').count();
+            Some((encoded.len() / 4 * 3).saturating_sub(padding) / 4)
+        }
+        _ => None,
+    });
+
+    let usage = response.usage.as_ref();
+    AiEmbeddingData {
+        provider: response.provider.clone(),
+        model: response.model.clone(),
+        input_count: (!vectors.is_empty()).then_some(vectors.len()),
+        total_tokens: usage.and_then(|u| u.total_tokens.or(u.prompt_tokens)),
+        dimensions,
+        latency_ms: response.latency_ms,
+    }
+}
+
 /// Extract thinking/reasoning blocks from response
 fn extract_thinking_block(
     body: &Value,
@@ -436,12 +777,65 @@ fn extract_model_family(model_id: &str) -> Option<String> {
     }
 }
 
-fn hash_content(content: &str) -> String {
+pub(crate) fn hash_content(content: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(content.as_bytes());
     format!("sha256:{}", hex::encode(&hasher.finalize()[..8]))
 }
 
+/// Per-message content limits applied to decoded requests
+///
+/// Messages longer than `max_content_chars` are truncated, and every message
+/// gets a `content_hash` so identical prompts can be correlated without
+/// retaining the full text. The hash is the same one the parsers set, so it
+/// matches hashes from requests decoded without limits.
+#[derive(Debug, Clone)]
+pub struct ContentLimits {
+    /// Maximum characters of message content to keep
+    pub max_content_chars: usize,
+    /// Redact before hashing and truncating (otherwise the original text is hashed)
+    pub hash_after_redaction: bool,
+    /// Redaction applied when `hash_after_redaction` is set
+    pub redaction: RedactionConfig,
+}
+
+/// Apply [`ContentLimits`] to every message of a request
+///
+/// `content_length` keeps the length of the original text.
+pub fn apply_content_limits(request: &mut AiRequestData, limits: &ContentLimits) {
+    for message in &mut request.messages {
+        let Some(MessageContent::Text(original)) = &message.content else {
+            continue;
+        };
+
+        let text = if limits.hash_after_redaction {
+            redact(original, &limits.redaction).content
+        } else {
+            original.clone()
+        };
+
+        message.content_length = Some(original.len());
+        message.content_hash = Some(hash_content(&text));
+        message.content = Some(MessageContent::Text(truncate_chars(
+            text,
+            limits.max_content_chars,
+        )));
+    }
+
+    request.system_prompt_hash = request
+        .messages
+        .iter()
+        .find(|m| matches!(m.role, MessageRole::System))
+        .and_then(|m| m.content_hash.clone());
+}
+
+fn truncate_chars(mut text: String, max_chars: usize) -> String {
+    if let Some((idx, _)) = text.char_indices().nth(max_chars) {
+        text.truncate(idx);
+    }
+    text
+}
+
 /// Detect if a request body looks like an AI/LLM request
 pub fn is_ai_request(body: &Value) -> bool {
     // Check for common AI API patterns
@@ -452,6 +846,24 @@ pub fn is_ai_request(body: &Value) -> bool {
     (has_prompt || has_messages) && has_model
 }
 
+/// Whether a request follows the OpenAI chat-completions, completions or
+/// embeddings schema, which self-hosted servers (vLLM, LM Studio, TGI)
+/// expose on arbitrary hosts
+pub fn is_openai_compatible_request(path: &str, body: &Value) -> bool {
+    let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+    let has_model = body.get("model").is_some_and(Value::is_string);
+    let fits_endpoint = if path.ends_with("/chat/completions") {
+        body.get("messages").is_some_and(Value::is_array)
+    } else if path.ends_with("/completions") {
+        body.get("prompt").is_some()
+    } else if path.ends_with("/embeddings") {
+        body.get("input").is_some()
+    } else {
+        false
+    };
+    has_model && fits_endpoint
+}
+
 /// Detect provider from request/response shape
 pub fn detect_provider_from_body(body: &Value) -> Option<Provider> {
     // Anthropic uses "claude" models and has specific fields
@@ -485,6 +897,11 @@ pub fn detect_provider_from_body(body: &Value) -> Option<Provider> {
         return Some(Provider::OpenAI);
     }
 
+    // Check for OpenAI Responses API structure
+    if is_responses_api_response(body) {
+        return Some(Provider::OpenAI);
+    }
+
     // Check for Anthropic response structure
     if body.get("content").is_some() && body.get("type").and_then(|t| t.as_str()) == Some("message")
     {
@@ -577,8 +994,8 @@ pub fn parse_anthropic_request(body: &Value, endpoint: &str) -> Option<AiRequest
                 .unwrap_or_default(),
         }),
         has_rag_context: None,
-        has_images: None,
-        image_count: None,
+        has_images: Some(messages.iter().any(|m| m.has_images == Some(true))),
+        image_count: Some(messages.iter().filter_map(|m| m.image_count).sum()),
         estimated_tokens: None,
         conversation,
         agent,
@@ -603,14 +1020,7 @@ pub fn parse_anthropic_response(body: &Value, request_id: &str) -> Option<AiResp
             Some("tool_use") => {
                 let id = block.get("id").and_then(|i| i.as_str()).map(String::from);
                 if let Some(name) = block.get("name").and_then(|n| n.as_str()) {
-                    let input = block.get("input");
-                    tool_calls.push(ToolCall {
-                        id,
-                        name: name.to_string(),
-                        tool_type: Some(ToolType::Function),
-                        arguments: input.map(|i| ToolArguments::String(i.to_string())),
-                        arguments_hash: None,
-                    });
+                    tool_calls.push(tool_call(id, name, block.get("input")));
                 }
             }
             _ => {}
@@ -686,6 +1096,7 @@ pub fn parse_anthropic_response(body: &Value, request_id: &str) -> Option<AiResp
                 content_length: Some(text_content.len()),
                 has_images: None,
                 image_count: None,
+                images: Vec::new(),
                 tool_call_id: None,
                 name: None,
             }),
@@ -702,130 +1113,1483 @@ pub fn parse_anthropic_response(body: &Value, request_id: &str) -> Option<AiResp
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Check if a request targets the OpenAI Responses API (`/v1/responses`)
+///
+/// Responses API requests carry `input` items instead of `messages`, so they
+/// are not picked up by [`is_ai_request`].
+pub fn is_responses_api_request(path: &str, body: &Value) -> bool {
+    let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+    path.ends_with("/responses")
+        && body.get("model").is_some()
+        && (body.get("input").is_some() || body.get("previous_response_id").is_some())
+}
 
-    #[test]
-    fn test_is_ai_request_openai() {
-        let body: Value = serde_json::json!({
-            "model": "gpt-4",
-            "messages": [
-                {"role": "user", "content": "Hello"}
-            ]
-        });
-        assert!(is_ai_request(&body));
-    }
+/// Event attribute summarizing a `multipart/form-data` request's fields and files
+pub const MULTIPART_ATTR: &str = "multipart";
 
-    #[test]
-    fn test_is_ai_request_anthropic() {
-        let body: Value = serde_json::json!({
-            "model": "claude-3-opus-20240229",
-            "messages": [
-                {"role": "user", "content": "Hello"}
-            ],
-            "max_tokens": 1024
-        });
-        assert!(is_ai_request(&body));
-    }
+/// Parse a `multipart/form-data` request (audio transcription and
+/// translation, image edits, file uploads)
+///
+/// The model comes from the `model` field and the request type from the
+/// path; uploaded images are counted like images in chat messages.
+pub fn parse_multipart_request(
+    form: &MultipartForm,
+    provider: Provider,
+    path: &str,
+    endpoint: &str,
+) -> AiRequestData {
+    let model = form.field("model").map(|id| ModelInfo {
+        id: id.to_string(),
+        name: None,
+        family: extract_model_family(id),
+        version: None,
+        capabilities: None,
+        context_window: None,
+        max_output_tokens: None,
+    });
 
-    #[test]
-    fn test_is_ai_request_embedding() {
-        let body: Value = serde_json::json!({
-            "model": "text-embedding-ada-002",
-            "input": "Hello world"
-        });
-        // This should NOT be detected as AI request (no messages)
-        assert!(!is_ai_request(&body));
-    }
+    let request_type = request_type_from_path(path).unwrap_or(RequestType::Other);
 
-    #[test]
-    fn test_detect_provider_openai() {
-        let body: Value = serde_json::json!({
-            "model": "gpt-4-turbo",
-            "choices": [{"message": {"content": "Hi"}}]
-        });
-        assert_eq!(detect_provider_from_body(&body), Some(Provider::OpenAI));
-    }
+    let image_count = form
+        .files()
+        .filter(|f| {
+            f.content_type
+                .as_deref()
+                .is_some_and(|t| t.starts_with("image/"))
+        })
+        .count();
 
-    #[test]
-    fn test_detect_provider_anthropic() {
-        let body: Value = serde_json::json!({
-            "model": "claude-3-sonnet",
-            "content": [{"type": "text", "text": "Hi"}],
-            "type": "message"
-        });
-        assert_eq!(detect_provider_from_body(&body), Some(Provider::Anthropic));
+    AiRequestData {
+        request_id: ulid::Ulid::new().to_string(),
+        provider: Some(ProviderInfo {
+            name: format!("{:?}", provider).to_lowercase(),
+            endpoint: Some(endpoint.to_string()),
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model,
+        auth: None,
+        request_type: Some(request_type),
+        streaming: Some(form.field("stream") == Some("true")),
+        messages: Vec::new(),
+        messages_count: None,
+        has_system_prompt: None,
+        system_prompt_hash: None,
+        tools: Vec::new(),
+        tools_count: None,
+        tool_choice: None,
+        parameters: None,
+        has_rag_context: None,
+        has_images: Some(image_count > 0),
+        image_count: Some(image_count),
+        estimated_tokens: None,
+        conversation: None,
+        agent: None,
     }
+}
 
-    #[test]
-    fn test_parse_openai_request() {
-        let body: Value = serde_json::json!({
-            "model": "gpt-4-turbo",
-            "messages": [
-                {"role": "system", "content": "You are helpful."},
-                {"role": "user", "content": "Hello!"}
-            ],
-            "temperature": 0.7,
-            "max_tokens": 100,
-            "stream": true
-        });
-
-        let request = parse_ai_request(
-            &body,
-            Provider::OpenAI,
-            "https://api.openai.com/v1/chat/completions",
-        )
-        .unwrap();
+/// Field names and file metadata of a form, without any content
+pub fn multipart_summary(form: &MultipartForm) -> Value {
+    let fields: Vec<&str> = form
+        .parts
+        .iter()
+        .filter(|p| !p.is_file())
+        .filter_map(|p| p.name.as_deref())
+        .collect();
+    let files: Vec<Value> = form
+        .files()
+        .map(|f| {
+            serde_json::json!({
+                "field": f.name,
+                "filename": f.filename,
+                "content_type": f.content_type,
+                "size": f.size,
+            })
+        })
+        .collect();
+    serde_json::json!({"fields": fields, "files": files})
+}
 
-        assert_eq!(request.model.as_ref().unwrap().id, "gpt-4-turbo");
-        assert_eq!(request.messages.len(), 2);
-        assert_eq!(request.streaming, Some(true));
-        assert_eq!(request.has_system_prompt, Some(true));
-        assert!(request.system_prompt_hash.is_some());
-        assert_eq!(request.parameters.as_ref().unwrap().temperature, Some(0.7));
-    }
+/// Check if a response body is an OpenAI Responses API response object
+pub fn is_responses_api_response(body: &Value) -> bool {
+    body.get("object").and_then(|o| o.as_str()) == Some("response") && body.get("output").is_some()
+}
 
-    #[test]
-    fn test_parse_openai_response() {
-        let body: Value = serde_json::json!({
-            "id": "chatcmpl-abc123",
-            "object": "chat.completion",
-            "model": "gpt-4-turbo",
-            "choices": [{
-                "index": 0,
-                "message": {
-                    "role": "assistant",
-                    "content": "Hello! How can I help?"
-                },
-                "finish_reason": "stop"
-            }],
-            "usage": {
-                "prompt_tokens": 10,
-                "completion_tokens": 8,
-                "total_tokens": 18
-            }
+/// Parse an OpenAI Responses API request
+///
+/// Maps `instructions` to a system message, `input` (a string or a list of
+/// input items) to messages, and `tools` to tool definitions.
+pub fn parse_responses_request(
+    body: &Value,
+    provider: Provider,
+    endpoint: &str,
+) -> Option<AiRequestData> {
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|id| ModelInfo {
+            id: id.to_string(),
+            name: None,
+            family: extract_model_family(id),
+            version: None,
+            capabilities: None,
+            context_window: None,
+            max_output_tokens: None,
         });
 
-        let response = parse_ai_response(&body, "test-request-id", Provider::OpenAI).unwrap();
+    let mut messages = Vec::new();
 
-        assert_eq!(
-            response.provider_request_id,
-            Some("chatcmpl-abc123".to_string())
-        );
-        assert_eq!(response.choices.len(), 1);
-        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
-        assert_eq!(response.usage.as_ref().unwrap().prompt_tokens, Some(10));
-        assert_eq!(response.usage.as_ref().unwrap().completion_tokens, Some(8));
+    let instructions = body.get("instructions").and_then(|i| i.as_str());
+    if let Some(text) = instructions {
+        messages.push(text_message(MessageRole::System, Some(text.to_string())));
     }
 
-    #[test]
-    fn test_parse_openai_tool_call_response() {
-        let body: Value = serde_json::json!({
-            "id": "chatcmpl-abc123",
-            "model": "gpt-4-turbo",
-            "choices": [{
-                "index": 0,
+    match body.get("input") {
+        Some(Value::String(text)) => {
+            messages.push(text_message(MessageRole::User, Some(text.clone())));
+        }
+        Some(Value::Array(items)) => {
+            messages.extend(items.iter().filter_map(parse_responses_input_item));
+        }
+        _ => {}
+    }
+
+    let tools = parse_responses_tools(body.get("tools"));
+
+    let streaming = body
+        .get("stream")
+        .and_then(|s| s.as_bool())
+        .unwrap_or(false);
+
+    let has_system_prompt = messages
+        .iter()
+        .any(|m| matches!(m.role, MessageRole::System));
+
+    let system_prompt_hash = messages
+        .iter()
+        .find(|m| matches!(m.role, MessageRole::System))
+        .and_then(|m| m.content_hash.clone());
+
+    // Build conversation context
+    let context_window = model.as_ref().and_then(|m| m.context_window);
+    let conversation = Some(ConversationContext::from_messages(
+        &messages,
+        context_window,
+    ));
+
+    // Detect agent context
+    let agent = AgentContext::detect(&tools, &messages);
+
+    let image_count: usize = messages.iter().filter_map(|m| m.image_count).sum();
+
+    Some(AiRequestData {
+        request_id: ulid::Ulid::new().to_string(),
+        provider: Some(ProviderInfo {
+            name: format!("{:?}", provider).to_lowercase(),
+            endpoint: Some(endpoint.to_string()),
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model,
+        auth: None,
+        request_type: Some(RequestType::Chat),
+        streaming: Some(streaming),
+        messages: messages.clone(),
+        messages_count: Some(messages.len()),
+        has_system_prompt: Some(has_system_prompt),
+        system_prompt_hash,
+        tools: tools.clone(),
+        tools_count: Some(tools.len()),
+        tool_choice: body.get("tool_choice").map(|tc| format!("{}", tc)),
+        parameters: Some(ModelParameters {
+            temperature: body.get("temperature").and_then(|t| t.as_f64()),
+            top_p: body.get("top_p").and_then(|t| t.as_f64()),
+            max_tokens: body.get("max_output_tokens").and_then(|t| t.as_u64()),
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: Vec::new(),
+        }),
+        has_rag_context: None,
+        has_images: Some(image_count > 0),
+        image_count: Some(image_count),
+        estimated_tokens: None,
+        conversation,
+        agent,
+    })
+}
+
+/// Parse an OpenAI Responses API response
+///
+/// Maps `output` message items to a single choice, `function_call` items to
+/// tool calls and `reasoning` summaries to a thinking block.
+pub fn parse_responses_response(
+    body: &Value,
+    request_id: &str,
+    provider: Provider,
+) -> Option<AiResponseData> {
+    let output = body.get("output").and_then(|o| o.as_array())?;
+
+    let mut text_content = String::new();
+    let mut reasoning_summary = String::new();
+    let mut tool_calls = Vec::new();
+
+    for item in output {
+        match item.get("type").and_then(|t| t.as_str()) {
+            Some("message") => {
+                if let Some(parts) = item.get("content").and_then(|c| c.as_array()) {
+                    for part in parts {
+                        if part.get("type").and_then(|t| t.as_str()) == Some("output_text") {
+                            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                text_content.push_str(text);
+                            }
+                        }
+                    }
+                }
+            }
+            Some("function_call") => {
+                if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
+                    let id = item
+                        .get("call_id")
+                        .or_else(|| item.get("id"))
+                        .and_then(|i| i.as_str())
+                        .map(String::from);
+                    let arguments = item.get("arguments").filter(|a| a.is_string());
+                    tool_calls.push(tool_call(id, name, arguments));
+                }
+            }
+            Some("reasoning") => {
+                if let Some(summary) = item.get("summary").and_then(|s| s.as_array()) {
+                    for part in summary {
+                        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                            reasoning_summary.push_str(text);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let usage = parse_responses_usage(body.get("usage"));
+    let finish_reason = responses_finish_reason(body, !tool_calls.is_empty());
+
+    let error = body
+        .get("error")
+        .filter(|e| !e.is_null())
+        .map(|e| ErrorInfo {
+            error_type: e.get("type").and_then(|t| t.as_str()).map(String::from),
+            message: e.get("message").and_then(|m| m.as_str()).map(String::from),
+            code: e.get("code").and_then(|c| c.as_str()).map(String::from),
+        });
+
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|id| ModelInfo {
+            id: id.to_string(),
+            name: None,
+            family: extract_model_family(id),
+            version: None,
+            capabilities: None,
+            context_window: None,
+            max_output_tokens: None,
+        });
+
+    let provider_id = format!("{:?}", provider).to_lowercase();
+
+    // Reasoning summaries are exposed as text; otherwise fall back to token counts
+    let thinking = if reasoning_summary.is_empty() {
+        extract_thinking_block(body, &provider_id, usage.as_ref())
+    } else {
+        Some(ThinkingBlock {
+            enabled: Some(true),
+            content_hash: Some(hash_content(&reasoning_summary)),
+            content_length: Some(reasoning_summary.len()),
+            content: Some(MessageContent::Text(reasoning_summary)),
+            tokens: usage.as_ref().and_then(|u| u.reasoning_tokens),
+            duration_ms: None,
+            mode: Some(ThinkingMode::Reasoning),
+        })
+    };
+
+    Some(AiResponseData {
+        request_id: request_id.to_string(),
+        provider_request_id: body.get("id").and_then(|i| i.as_str()).map(String::from),
+        provider: Some(ProviderInfo {
+            name: provider_id,
+            endpoint: None,
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model,
+        status_code: None,
+        success: Some(error.is_none()),
+        error,
+        choices: vec![Choice {
+            index: 0,
+            message: Some(text_message(
+                MessageRole::Assistant,
+                (!text_content.is_empty()).then_some(text_content),
+            )),
+            finish_reason,
+        }],
+        tool_calls: tool_calls.clone(),
+        tool_calls_count: Some(tool_calls.len()),
+        usage,
+        latency_ms: None,
+        time_to_first_token_ms: None,
+        was_cached: None,
+        finish_reason,
+        thinking,
+    })
+}
+
+/// Parse a single Responses API input item into a message
+fn parse_responses_input_item(item: &Value) -> Option<Message> {
+    let item_type = item
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("message");
+
+    match item_type {
+        "message" => {
+            let role = item
+                .get("role")
+                .and_then(|r| r.as_str())
+                .map(|r| match r {
+                    // "developer" is the Responses API name for system instructions
+                    "developer" => MessageRole::System,
+                    other => parse_role(other),
+                })
+                .unwrap_or(MessageRole::User);
+
+            let mut message = match item.get("content") {
+                Some(Value::String(text)) => text_message(role, Some(text.clone())),
+                Some(Value::Array(parts)) => parts_message(role, parts),
+                _ => text_message(role, None),
+            };
+            message.name = item.get("name").and_then(|n| n.as_str()).map(String::from);
+            Some(message)
+        }
+        "function_call" => {
+            let mut message = text_message(MessageRole::Assistant, None);
+            message.name = item.get("name").and_then(|n| n.as_str()).map(String::from);
+            message.tool_call_id = item
+                .get("call_id")
+                .and_then(|c| c.as_str())
+                .map(String::from);
+            Some(message)
+        }
+        "function_call_output" => {
+            let output = item
+                .get("output")
+                .and_then(|o| o.as_str())
+                .map(String::from);
+            let mut message = text_message(MessageRole::Tool, output);
+            message.tool_call_id = item
+                .get("call_id")
+                .and_then(|c| c.as_str())
+                .map(String::from);
+            Some(message)
+        }
+        // Reasoning items and item references carry no conversational content
+        _ => None,
+    }
+}
+
+/// Parse Responses API tools, including built-in (hosted) tools
+fn parse_responses_tools(tools: Option<&Value>) -> Vec<ToolDefinition> {
+    tools
+        .and_then(|t| t.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|tool| {
+                    let tool_type = tool.get("type").and_then(|t| t.as_str())?;
+                    let (name, kind) = match tool_type {
+                        "function" => (
+                            tool.get("name")
+                                .or_else(|| tool.get("function").and_then(|f| f.get("name")))
+                                .and_then(|n| n.as_str())?,
+                            ToolType::Function,
+                        ),
+                        "file_search" => (tool_type, ToolType::FileSearch),
+                        "code_interpreter" => (tool_type, ToolType::CodeInterpreter),
+                        t if t.starts_with("computer_use") => (tool_type, ToolType::ComputerUse),
+                        "mcp" => (
+                            tool.get("server_label")
+                                .and_then(|l| l.as_str())
+                                .unwrap_or(tool_type),
+                            ToolType::Other,
+                        ),
+                        _ => (tool_type, ToolType::Other),
+                    };
+
+                    Some(ToolDefinition {
+                        name: name.to_string(),
+                        tool_type: Some(kind),
+                        description: tool
+                            .get("description")
+                            .and_then(|d| d.as_str())
+                            .map(String::from),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse Responses API usage (`input_tokens`/`output_tokens` with detail objects)
+fn parse_responses_usage(usage: Option<&Value>) -> Option<Usage> {
+    let u = usage?;
+    Some(Usage {
+        prompt_tokens: u.get("input_tokens").and_then(|t| t.as_u64()),
+        completion_tokens: u.get("output_tokens").and_then(|t| t.as_u64()),
+        total_tokens: u.get("total_tokens").and_then(|t| t.as_u64()),
+        cached_tokens: u
+            .get("input_tokens_details")
+            .and_then(|d| d.get("cached_tokens"))
+            .and_then(|t| t.as_u64()),
+        reasoning_tokens: u
+            .get("output_tokens_details")
+            .and_then(|d| d.get("reasoning_tokens"))
+            .and_then(|t| t.as_u64()),
+        input_cost_usd: None,
+        output_cost_usd: None,
+        total_cost_usd: None,
+    })
+}
+
+/// Derive a finish reason from the Responses API `status` field
+fn responses_finish_reason(body: &Value, has_tool_calls: bool) -> Option<FinishReason> {
+    match body.get("status").and_then(|s| s.as_str())? {
+        "completed" if has_tool_calls => Some(FinishReason::ToolCalls),
+        "completed" => Some(FinishReason::Stop),
+        "incomplete" => match body
+            .get("incomplete_details")
+            .and_then(|d| d.get("reason"))
+            .and_then(|r| r.as_str())
+        {
+            Some("content_filter") => Some(FinishReason::ContentFilter),
+            _ => Some(FinishReason::Length),
+        },
+        "failed" => Some(FinishReason::Error),
+        _ => Some(FinishReason::Other),
+    }
+}
+
+/// Build a message from role and optional text content
+fn text_message(role: MessageRole, text: Option<String>) -> Message {
+    Message {
+        role,
+        content_hash: text.as_deref().map(hash_content),
+        content_length: text.as_ref().map(|s| s.len()),
+        content: text.map(MessageContent::Text),
+        has_images: None,
+        image_count: None,
+        images: Vec::new(),
+        tool_call_id: None,
+        name: None,
+    }
+}
+
+/// Images of an Ollama message or generate request: bare base64 strings
+fn ollama_images(value: &Value) -> Vec<ImageInfo> {
+    value
+        .get("images")
+        .and_then(|i| i.as_array())
+        .map(|images| {
+            images
+                .iter()
+                .filter_map(|i| i.as_str())
+                .map(base64_image_info)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Check if a request targets Ollama's native API (`/api/chat` or `/api/generate`)
+///
+/// Ollama's OpenAI-compatible shim (`/v1/chat/completions`) is handled by
+/// [`parse_ai_request`] like any other OpenAI-style endpoint.
+pub fn is_ollama_native_request(path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+    path == "/api/chat" || path == "/api/generate"
+}
+
+/// Parse an Ollama native API request
+///
+/// Differences from the OpenAI format: `stream` defaults to `true`, sampling
+/// parameters live under `options`, images are base64 strings in a per-message
+/// `images` array, and `/api/generate` takes `system` + `prompt` instead of
+/// `messages`.
+pub fn parse_ollama_request(body: &Value, path: &str, endpoint: &str) -> Option<AiRequestData> {
+    let mut request = parse_ai_request(body, Provider::Ollama, endpoint)?;
+
+    let is_generate = path
+        .split('?')
+        .next()
+        .unwrap_or(path)
+        .trim_end_matches('/')
+        .ends_with("/api/generate");
+
+    if is_generate {
+        let mut messages = Vec::new();
+        if let Some(system) = body.get("system").and_then(|s| s.as_str()) {
+            messages.push(text_message(MessageRole::System, Some(system.to_string())));
+        }
+        if let Some(prompt) = body.get("prompt").and_then(|p| p.as_str()) {
+            let mut message = text_message(MessageRole::User, Some(prompt.to_string()));
+            set_images(&mut message, ollama_images(body));
+            messages.push(message);
+        }
+
+        request.has_system_prompt = Some(
+            messages
+                .iter()
+                .any(|m| matches!(m.role, MessageRole::System)),
+        );
+        request.system_prompt_hash = messages
+            .iter()
+            .find(|m| matches!(m.role, MessageRole::System))
+            .and_then(|m| m.content_hash.clone());
+        request.conversation = Some(ConversationContext::from_messages(&messages, None));
+        request.messages_count = Some(messages.len());
+        request.messages = messages;
+        request.request_type = Some(RequestType::Completion);
+    } else if let Some(raw_messages) = body.get("messages").and_then(|m| m.as_array()) {
+        // Native chat messages carry images alongside content
+        for (message, raw) in request.messages.iter_mut().zip(raw_messages) {
+            set_images(message, ollama_images(raw));
+        }
+        request.request_type = Some(RequestType::Chat);
+    }
+
+    let image_count: usize = request.messages.iter().filter_map(|m| m.image_count).sum();
+    request.has_images = Some(image_count > 0);
+    request.image_count = Some(image_count);
+
+    // Ollama streams unless explicitly disabled
+    request.streaming = Some(body.get("stream").and_then(|s| s.as_bool()).unwrap_or(true));
+
+    if let Some(options) = body.get("options") {
+        request.parameters = Some(ModelParameters {
+            temperature: options.get("temperature").and_then(|t| t.as_f64()),
+            top_p: options.get("top_p").and_then(|t| t.as_f64()),
+            max_tokens: options.get("num_predict").and_then(|t| t.as_u64()),
+            frequency_penalty: options.get("frequency_penalty").and_then(|t| t.as_f64()),
+            presence_penalty: options.get("presence_penalty").and_then(|t| t.as_f64()),
+            stop: options
+                .get("stop")
+                .and_then(|s| s.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    }
+
+    Some(request)
+}
+
+/// Check if a response body is an Ollama native API response
+pub fn is_ollama_response(body: &Value) -> bool {
+    body.get("done").map(|d| d.is_boolean()).unwrap_or(false)
+        && (body.get("message").is_some() || body.get("response").is_some())
+}
+
+/// Parse an Ollama native API response (`/api/chat` or `/api/generate`)
+///
+/// Streaming responses should first be merged with
+/// [`crate::ndjson::OllamaStreamReassembler::to_response`].
+pub fn parse_ollama_response(body: &Value, request_id: &str) -> Option<AiResponseData> {
+    if !is_ollama_response(body) {
+        return None;
+    }
+
+    let message = match body.get("message") {
+        Some(m) => parse_single_message(m),
+        None => text_message(
+            MessageRole::Assistant,
+            body.get("response")
+                .and_then(|r| r.as_str())
+                .map(String::from),
+        ),
+    };
+
+    // Ollama tool calls have no id and pass arguments as a JSON object
+    let tool_calls: Vec<ToolCall> = body
+        .get("message")
+        .and_then(|m| m.get("tool_calls"))
+        .and_then(|t| t.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|tc| {
+                    let function = tc.get("function")?;
+                    Some(tool_call(
+                        tc.get("id").and_then(|i| i.as_str()).map(String::from),
+                        function.get("name")?.as_str()?,
+                        function.get("arguments"),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let prompt_tokens = body.get("prompt_eval_count").and_then(|t| t.as_u64());
+    let completion_tokens = body.get("eval_count").and_then(|t| t.as_u64());
+    let usage = (prompt_tokens.is_some() || completion_tokens.is_some()).then(|| Usage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: match (prompt_tokens, completion_tokens) {
+            (Some(p), Some(c)) => Some(p + c),
+            _ => None,
+        },
+        cached_tokens: None,
+        reasoning_tokens: None,
+        input_cost_usd: None,
+        output_cost_usd: None,
+        total_cost_usd: None,
+    });
+
+    let finish_reason = if !tool_calls.is_empty() {
+        Some(FinishReason::ToolCalls)
+    } else {
+        body.get("done_reason")
+            .and_then(|r| r.as_str())
+            .and_then(parse_finish_reason)
+    };
+
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|id| ModelInfo {
+            id: id.to_string(),
+            name: None,
+            family: extract_model_family(id),
+            version: None,
+            capabilities: None,
+            context_window: None,
+            max_output_tokens: None,
+        });
+
+    Some(AiResponseData {
+        request_id: request_id.to_string(),
+        provider_request_id: None,
+        provider: Some(ProviderInfo {
+            name: "ollama".to_string(),
+            endpoint: None,
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model,
+        status_code: None,
+        success: Some(true),
+        error: None,
+        choices: vec![Choice {
+            index: 0,
+            message: Some(message),
+            finish_reason,
+        }],
+        tool_calls: tool_calls.clone(),
+        tool_calls_count: Some(tool_calls.len()),
+        usage,
+        // total_duration is reported in nanoseconds
+        latency_ms: body
+            .get("total_duration")
+            .and_then(|d| d.as_u64())
+            .map(|ns| ns / 1_000_000),
+        time_to_first_token_ms: None,
+        was_cached: None,
+        finish_reason,
+        thinking: None,
+    })
+}
+
+/// The `{model}:{method}` segment of a Gemini API or Vertex AI path
+fn gemini_model_method(path: &str) -> Option<(&str, &str)> {
+    let path = path.split('?').next().unwrap_or(path);
+    let (_, rest) = path.rsplit_once("models/")?;
+    rest.split_once(':')
+}
+
+/// Check if a request is a Gemini `generateContent` or `streamGenerateContent`
+/// call, on the Gemini API or Vertex AI
+///
+/// The model is named in the path rather than the body, which carries
+/// `contents` instead of `messages`.
+pub fn is_gemini_request(path: &str, body: &Value) -> bool {
+    matches!(
+        gemini_model_method(path),
+        Some((_, "generateContent" | "streamGenerateContent"))
+    ) && body.get("contents").is_some_and(|c| c.is_array())
+}
+
+/// Vertex AI project and location from a path like
+/// `/v1/projects/{project}/locations/{location}/publishers/google/models/...`
+fn vertex_project_location(path: &str) -> (Option<String>, Option<String>) {
+    let path = path.split('?').next().unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').collect();
+    let after = |name: &str| {
+        segments
+            .windows(2)
+            .find(|w| w[0] == name)
+            .map(|w| w[1].to_string())
+            .filter(|s| !s.is_empty())
+    };
+    (after("projects"), after("locations"))
+}
+
+/// Build a message from a Gemini `Content` (`role` + `parts`)
+///
+/// Text parts are concatenated; thought summaries are left out. Inline and
+/// file image parts are described like other images, and a part answering a
+/// function call makes it a tool message.
+fn gemini_message(content: &Value) -> Message {
+    let parts = content
+        .get("parts")
+        .and_then(|p| p.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let mut role = match content.get("role").and_then(|r| r.as_str()) {
+        Some("model") => MessageRole::Assistant,
+        Some("function") => MessageRole::Tool,
+        Some("system") => MessageRole::System,
+        _ => MessageRole::User,
+    };
+    let mut text = String::new();
+    let mut images = Vec::new();
+    let mut name = None;
+    for part in parts {
+        if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+            continue;
+        }
+        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+            text.push_str(t);
+        } else if let Some(inline) = part.get("inlineData") {
+            let mime_type = inline.get("mimeType").and_then(|m| m.as_str());
+            if mime_type.is_none_or(|m| m.starts_with("image/")) {
+                let data = inline.get("data").and_then(|d| d.as_str()).unwrap_or("");
+                let info = base64_image_info(data);
+                images.push(ImageInfo {
+                    media_type: mime_type.map(String::from).or(info.media_type.clone()),
+                    ..info
+                });
+            }
+        } else if let Some(file) = part.get("fileData") {
+            let mime_type = file.get("mimeType").and_then(|m| m.as_str());
+            if mime_type.is_some_and(|m| m.starts_with("image/")) {
+                images.push(ImageInfo {
+                    source: ImageSource::Url,
+                    media_type: mime_type.map(String::from),
+                    size_bytes: None,
+                });
+            }
+        } else if let Some(response) = part.get("functionResponse") {
+            role = MessageRole::Tool;
+            name = response
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(String::from);
+        }
+    }
+
+    let mut message = text_message(role, (!text.is_empty()).then_some(text));
+    set_images(&mut message, images);
+    message.name = name;
+    message
+}
+
+/// Parse a Gemini API or Vertex AI `generateContent` request
+///
+/// `contents` become the messages and `systemInstruction` a system message.
+/// The model and whether the response streams come from the path, sampling
+/// parameters from `generationConfig`. On Vertex AI the project and location
+/// in the path are reported as the provider's project and region.
+pub fn parse_gemini_request(body: &Value, path: &str, endpoint: &str) -> Option<AiRequestData> {
+    let (model_id, method) = gemini_model_method(path)?;
+    let model = ModelInfo {
+        id: model_id.to_string(),
+        name: None,
+        family: extract_model_family(model_id),
+        version: None,
+        capabilities: None,
+        context_window: None,
+        max_output_tokens: None,
+    };
+
+    let mut messages = Vec::new();
+    if let Some(system) = body
+        .get("systemInstruction")
+        .or_else(|| body.get("system_instruction"))
+    {
+        let mut message = gemini_message(system);
+        message.role = MessageRole::System;
+        messages.push(message);
+    }
+    messages.extend(body.get("contents")?.as_array()?.iter().map(gemini_message));
+
+    let tools: Vec<ToolDefinition> = body
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .map(|tools| {
+            tools
+                .iter()
+                .flat_map(|tool| {
+                    let declarations = tool
+                        .get("functionDeclarations")
+                        .or_else(|| tool.get("function_declarations"))
+                        .and_then(|d| d.as_array())
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    let declared = declarations.iter().filter_map(|decl| {
+                        Some(ToolDefinition {
+                            name: decl.get("name")?.as_str()?.to_string(),
+                            tool_type: Some(ToolType::Function),
+                            description: decl
+                                .get("description")
+                                .and_then(|d| d.as_str())
+                                .map(String::from),
+                        })
+                    });
+                    // Built-in tools are objects keyed by their name
+                    let builtin = tool
+                        .as_object()
+                        .into_iter()
+                        .flat_map(|obj| obj.keys())
+                        .filter(|key| {
+                            !matches!(
+                                key.as_str(),
+                                "functionDeclarations" | "function_declarations"
+                            )
+                        })
+                        .map(|key| ToolDefinition {
+                            name: key.clone(),
+                            tool_type: Some(if key == "codeExecution" {
+                                ToolType::CodeInterpreter
+                            } else {
+                                ToolType::Other
+                            }),
+                            description: None,
+                        });
+                    declared.chain(builtin).collect::<Vec<_>>()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let parameters = body.get("generationConfig").map(|config| ModelParameters {
+        temperature: config.get("temperature").and_then(|t| t.as_f64()),
+        top_p: config.get("topP").and_then(|t| t.as_f64()),
+        max_tokens: config.get("maxOutputTokens").and_then(|t| t.as_u64()),
+        frequency_penalty: config.get("frequencyPenalty").and_then(|t| t.as_f64()),
+        presence_penalty: config.get("presencePenalty").and_then(|t| t.as_f64()),
+        stop: config
+            .get("stopSequences")
+            .and_then(|s| s.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|s| s.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    });
+
+    let system_prompt_hash = messages
+        .iter()
+        .find(|m| matches!(m.role, MessageRole::System))
+        .and_then(|m| m.content_hash.clone());
+    let image_count: usize = messages.iter().filter_map(|m| m.image_count).sum();
+    let (project_id, region) = vertex_project_location(path);
+
+    Some(AiRequestData {
+        request_id: ulid::Ulid::new().to_string(),
+        provider: Some(ProviderInfo {
+            name: "google".to_string(),
+            endpoint: Some(endpoint.to_string()),
+            region,
+            organization_id: None,
+            project_id,
+        }),
+        model: Some(model),
+        auth: None,
+        request_type: Some(RequestType::Chat),
+        streaming: Some(method == "streamGenerateContent"),
+        messages_count: Some(messages.len()),
+        has_system_prompt: Some(system_prompt_hash.is_some()),
+        system_prompt_hash,
+        tools_count: Some(tools.len()),
+        tool_choice: body
+            .get("toolConfig")
+            .and_then(|c| c.get("functionCallingConfig"))
+            .and_then(|c| c.get("mode"))
+            .and_then(|m| m.as_str())
+            .map(|m| m.to_lowercase()),
+        parameters,
+        has_rag_context: None,
+        has_images: Some(image_count > 0),
+        image_count: Some(image_count),
+        estimated_tokens: None,
+        conversation: Some(ConversationContext::from_messages(&messages, None)),
+        agent: AgentContext::detect(&tools, &messages),
+        messages,
+        tools,
+    })
+}
+
+/// Check if a response body is a Gemini `GenerateContentResponse`
+pub fn is_gemini_response(body: &Value) -> bool {
+    body.get("candidates").is_some_and(|c| c.is_array())
+        || (body.get("usageMetadata").is_some() && body.get("choices").is_none())
+        || body
+            .get("promptFeedback")
+            .and_then(|f| f.get("blockReason"))
+            .is_some()
+}
+
+fn gemini_finish_reason(reason: &str) -> Option<FinishReason> {
+    match reason {
+        "STOP" => Some(FinishReason::Stop),
+        "MAX_TOKENS" => Some(FinishReason::Length),
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY" => {
+            Some(FinishReason::ContentFilter)
+        }
+        "MALFORMED_FUNCTION_CALL" => Some(FinishReason::Error),
+        _ => Some(FinishReason::Other),
+    }
+}
+
+/// Parse a Gemini API or Vertex AI `GenerateContentResponse`
+///
+/// `candidates` become the choices, `functionCall` parts tool calls and
+/// `usageMetadata` the usage. A prompt blocked before generation has no
+/// candidates and finishes with `content_filter`. Streaming responses
+/// should first be merged with
+/// [`crate::gemini::GeminiStreamReassembler::to_response`].
+pub fn parse_gemini_response(body: &Value, request_id: &str) -> Option<AiResponseData> {
+    if !is_gemini_response(body) {
+        return None;
+    }
+
+    let candidates = body
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let choices: Vec<Choice> = candidates
+        .iter()
+        .enumerate()
+        .map(|(idx, candidate)| Choice {
+            index: candidate
+                .get("index")
+                .and_then(|i| i.as_u64())
+                .map_or(idx, |i| i as usize),
+            message: candidate.get("content").map(gemini_message),
+            finish_reason: candidate
+                .get("finishReason")
+                .and_then(|f| f.as_str())
+                .and_then(gemini_finish_reason),
+        })
+        .collect();
+
+    // Gemini function calls pass arguments as a JSON object and may have no id
+    let tool_calls: Vec<ToolCall> = candidates
+        .first()
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| {
+                    let call = part.get("functionCall")?;
+                    Some(tool_call(
+                        call.get("id").and_then(|i| i.as_str()).map(String::from),
+                        call.get("name")?.as_str()?,
+                        call.get("args"),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let usage = body.get("usageMetadata").map(|u| {
+        let count = |field: &str| u.get(field).and_then(|t| t.as_u64());
+        let prompt_tokens = count("promptTokenCount");
+        let completion_tokens = count("candidatesTokenCount");
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: count("totalTokenCount").or(match (prompt_tokens, completion_tokens) {
+                (Some(p), Some(c)) => Some(p + c),
+                _ => None,
+            }),
+            cached_tokens: count("cachedContentTokenCount"),
+            reasoning_tokens: count("thoughtsTokenCount"),
+            input_cost_usd: None,
+            output_cost_usd: None,
+            total_cost_usd: None,
+        }
+    });
+
+    let blocked = body
+        .get("promptFeedback")
+        .and_then(|f| f.get("blockReason"))
+        .is_some();
+    let finish_reason = if !tool_calls.is_empty() {
+        Some(FinishReason::ToolCalls)
+    } else if candidates.is_empty() && blocked {
+        Some(FinishReason::ContentFilter)
+    } else {
+        choices.first().and_then(|c| c.finish_reason)
+    };
+
+    let model = body
+        .get("modelVersion")
+        .and_then(|m| m.as_str())
+        .map(|id| ModelInfo {
+            id: id.to_string(),
+            name: None,
+            family: extract_model_family(id),
+            version: None,
+            capabilities: None,
+            context_window: None,
+            max_output_tokens: None,
+        });
+
+    Some(AiResponseData {
+        request_id: request_id.to_string(),
+        provider_request_id: body
+            .get("responseId")
+            .and_then(|i| i.as_str())
+            .map(String::from),
+        provider: Some(ProviderInfo {
+            name: "google".to_string(),
+            endpoint: None,
+            region: None,
+            organization_id: None,
+            project_id: None,
+        }),
+        model,
+        status_code: None,
+        success: Some(true),
+        error: None,
+        choices,
+        tool_calls: tool_calls.clone(),
+        tool_calls_count: Some(tool_calls.len()),
+        usage,
+        latency_ms: None,
+        time_to_first_token_ms: None,
+        was_cached: None,
+        finish_reason,
+        thinking: None,
+    })
+}
+
+/// Model id and action of a Bedrock InvokeModel path,
+/// `/model/{modelId}/invoke` or `/model/{modelId}/invoke-with-response-stream`
+///
+/// The model id may be percent-encoded (`anthropic.claude-v2%3A1`) and may be
+/// an inference profile or ARN.
+fn bedrock_invoke_path(path: &str) -> Option<(String, &str)> {
+    let path = path.split('?').next().unwrap_or(path);
+    let rest = path.strip_prefix("/model/")?;
+    let (model_id, action) = rest.rsplit_once('/')?;
+    if model_id.is_empty() || !matches!(action, "invoke" | "invoke-with-response-stream") {
+        return None;
+    }
+    Some((percent_decode(model_id), action))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Check if a request is a Bedrock InvokeModel call
+pub fn is_bedrock_invoke_request(path: &str) -> bool {
+    bedrock_invoke_path(path).is_some()
+}
+
+/// The vendor model name of a Bedrock model id: `claude-3-haiku-20240307-v1:0`
+/// for `us.anthropic.claude-3-haiku-20240307-v1:0` or an ARN ending in it
+fn bedrock_base_model(model_id: &str) -> &str {
+    let id = model_id.rsplit('/').next().unwrap_or(model_id);
+    // Cross-region inference profiles prefix the geography
+    let id = match id.split_once('.') {
+        Some(("us" | "eu" | "apac" | "global" | "us-gov", rest)) => rest,
+        _ => id,
+    };
+    id.split_once('.').map_or(id, |(_, model)| model)
+}
+
+/// Region of a `bedrock-runtime.{region}.amazonaws.com` host
+fn bedrock_region(host: &str) -> Option<String> {
+    let host = host.split(':').next().unwrap_or(host);
+    host.strip_prefix("bedrock-runtime.")
+        .or_else(|| host.strip_prefix("bedrock-runtime-fips."))
+        .and_then(|rest| rest.strip_suffix(".amazonaws.com"))
+        .filter(|region| !region.is_empty() && !region.contains('.'))
+        .map(String::from)
+}
+
+/// Parse a Bedrock InvokeModel request
+///
+/// Bedrock passes the body through to the model, so its schema depends on
+/// the model family: Anthropic Messages (`messages`), Amazon Titan
+/// (`inputText` + `textGenerationConfig`), or a `prompt` with flat sampling
+/// parameters (Meta Llama, Mistral, Cohere). The model id comes from the
+/// path, the region from the host, and streaming from the
+/// `invoke-with-response-stream` action.
+pub fn parse_bedrock_request(
+    body: &Value,
+    host: &str,
+    path: &str,
+    endpoint: &str,
+) -> Option<AiRequestData> {
+    let (model_id, action) = bedrock_invoke_path(path)?;
+    let embedding = model_id.contains("embed");
+
+    let mut request = if body.get("messages").is_some() {
+        parse_anthropic_request(body, endpoint)?
+    } else {
+        let (prompt, config) = match body.get("inputText") {
+            Some(text) => (text, body.get("textGenerationConfig")),
+            None => (body.get("prompt")?, Some(body)),
+        };
+        let mut request = parse_ai_request(body, Provider::AwsBedrock, endpoint)?;
+        let messages = vec![text_message(
+            MessageRole::User,
+            prompt.as_str().map(String::from),
+        )];
+        request.conversation = Some(ConversationContext::from_messages(&messages, None));
+        request.messages_count = Some(messages.len());
+        request.messages = messages;
+        request.request_type = Some(RequestType::Completion);
+        request.parameters = config.map(|config| {
+            let float = |names: &[&str]| names.iter().find_map(|n| config.get(*n)?.as_f64());
+            ModelParameters {
+                temperature: float(&["temperature"]),
+                top_p: float(&["topP", "top_p", "p"]),
+                max_tokens: ["maxTokenCount", "max_gen_len", "max_tokens"]
+                    .iter()
+                    .find_map(|n| config.get(*n)?.as_u64()),
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: config
+                    .get("stopSequences")
+                    .or_else(|| config.get("stop"))
+                    .or_else(|| config.get("stop_sequences"))
+                    .and_then(|s| s.as_array())
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|s| s.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        });
+        request
+    };
+
+    request.provider = Some(ProviderInfo {
+        name: "awsbedrock".to_string(),
+        endpoint: Some(endpoint.to_string()),
+        region: bedrock_region(host),
+        organization_id: None,
+        project_id: None,
+    });
+    request.model = Some(ModelInfo {
+        family: extract_model_family(bedrock_base_model(&model_id)),
+        id: model_id,
+        name: None,
+        version: None,
+        capabilities: None,
+        context_window: None,
+        max_output_tokens: None,
+    });
+    request.streaming = Some(action == "invoke-with-response-stream");
+    if embedding {
+        request.request_type = Some(RequestType::Embedding);
+    }
+    Some(request)
+}
+
+fn bedrock_finish_reason(reason: &str) -> Option<FinishReason> {
+    match reason.to_ascii_lowercase().as_str() {
+        "finish" | "stop" | "end_turn" | "stop_sequence" | "complete" => Some(FinishReason::Stop),
+        "length" | "max_tokens" => Some(FinishReason::Length),
+        "content_filtered" => Some(FinishReason::ContentFilter),
+        "tool_use" | "tool_calls" => Some(FinishReason::ToolCalls),
+        _ => Some(FinishReason::Other),
+    }
+}
+
+/// Parse a Bedrock InvokeModel response
+///
+/// The body is the model's own: Anthropic Messages, Amazon Titan
+/// (`results[]`, or `embedding` for Titan embeddings), Meta Llama
+/// (`generation`) or Mistral (`outputs[]`). Usage comes from the body, or
+/// from the invocation metrics of a stream merged with
+/// [`crate::bedrock::BedrockStreamReassembler::to_response`]. The model
+/// is only in Anthropic bodies; callers fill it from the request otherwise.
+pub fn parse_bedrock_response(body: &Value, request_id: &str) -> Option<AiResponseData> {
+    let count = |v: Option<&Value>| v.and_then(|t| t.as_u64());
+
+    let mut response = if body.get("content").is_some_and(|c| c.is_array()) {
+        parse_anthropic_response(body, request_id)?
+    } else {
+        let (text, reason, prompt_tokens, completion_tokens) =
+            if let Some(result) = body.get("results").and_then(|r| r.get(0)) {
+                (
+                    result.get("outputText"),
+                    result.get("completionReason"),
+                    count(body.get("inputTextTokenCount")),
+                    count(result.get("tokenCount")),
+                )
+            } else if let Some(generation) = body.get("generation") {
+                (
+                    Some(generation),
+                    body.get("stop_reason"),
+                    count(body.get("prompt_token_count")),
+                    count(body.get("generation_token_count")),
+                )
+            } else if let Some(output) = body.get("outputs").and_then(|o| o.get(0)) {
+                (output.get("text"), output.get("stop_reason"), None, None)
+            } else if body.get("embedding").is_some() {
+                (None, None, count(body.get("inputTextTokenCount")), None)
+            } else {
+                return None;
+            };
+
+        let finish_reason = reason
+            .and_then(|r| r.as_str())
+            .and_then(bedrock_finish_reason);
+        let choices = text
+            .map(|t| Choice {
+                index: 0,
+                message: Some(text_message(
+                    MessageRole::Assistant,
+                    t.as_str().map(String::from),
+                )),
+                finish_reason,
+            })
+            .into_iter()
+            .collect();
+        let usage = (prompt_tokens.is_some() || completion_tokens.is_some()).then(|| Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: match (prompt_tokens, completion_tokens) {
+                (Some(p), Some(c)) => Some(p + c),
+                _ => None,
+            },
+            cached_tokens: None,
+            reasoning_tokens: None,
+            input_cost_usd: None,
+            output_cost_usd: None,
+            total_cost_usd: None,
+        });
+
+        AiResponseData {
+            request_id: request_id.to_string(),
+            provider_request_id: None,
+            provider: None,
+            model: None,
+            status_code: None,
+            success: Some(true),
+            error: None,
+            choices,
+            tool_calls: Vec::new(),
+            tool_calls_count: Some(0),
+            usage,
+            latency_ms: None,
+            time_to_first_token_ms: None,
+            was_cached: None,
+            finish_reason,
+            thinking: None,
+        }
+    };
+
+    // Bedrock's own counts are what it bills
+    if let Some(metrics) = body.get(crate::bedrock::INVOCATION_METRICS) {
+        let usage = response.usage.get_or_insert(Usage {
+            prompt_tokens: None,
+            completion_tokens: None,
+            total_tokens: None,
+            cached_tokens: None,
+            reasoning_tokens: None,
+            input_cost_usd: None,
+            output_cost_usd: None,
+            total_cost_usd: None,
+        });
+        usage.prompt_tokens = count(metrics.get("inputTokenCount")).or(usage.prompt_tokens);
+        usage.completion_tokens =
+            count(metrics.get("outputTokenCount")).or(usage.completion_tokens);
+        if let (Some(p), Some(c)) = (usage.prompt_tokens, usage.completion_tokens) {
+            usage.total_tokens = Some(p + c);
+        }
+    }
+
+    response.provider = Some(ProviderInfo {
+        name: "awsbedrock".to_string(),
+        endpoint: None,
+        region: None,
+        organization_id: None,
+        project_id: None,
+    });
+    Some(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::events::ToolArguments;
+
+    #[test]
+    fn test_is_ai_request_openai() {
+        let body: Value = serde_json::json!({
+            "model": "gpt-4",
+            "messages": [
+                {"role": "user", "content": "Hello"}
+            ]
+        });
+        assert!(is_ai_request(&body));
+    }
+
+    #[test]
+    fn test_is_ai_request_anthropic() {
+        let body: Value = serde_json::json!({
+            "model": "claude-3-opus-20240229",
+            "messages": [
+                {"role": "user", "content": "Hello"}
+            ],
+            "max_tokens": 1024
+        });
+        assert!(is_ai_request(&body));
+    }
+
+    #[test]
+    fn test_is_ai_request_embedding() {
+        let body: Value = serde_json::json!({
+            "model": "text-embedding-ada-002",
+            "input": "Hello world"
+        });
+        // This should NOT be detected as AI request (no messages)
+        assert!(!is_ai_request(&body));
+    }
+
+    #[test]
+    fn test_detect_provider_openai() {
+        let body: Value = serde_json::json!({
+            "model": "gpt-4-turbo",
+            "choices": [{"message": {"content": "Hi"}}]
+        });
+        assert_eq!(detect_provider_from_body(&body), Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_detect_provider_anthropic() {
+        let body: Value = serde_json::json!({
+            "model": "claude-3-sonnet",
+            "content": [{"type": "text", "text": "Hi"}],
+            "type": "message"
+        });
+        assert_eq!(detect_provider_from_body(&body), Some(Provider::Anthropic));
+    }
+
+    #[test]
+    fn test_parse_openai_request() {
+        let body: Value = serde_json::json!({
+            "model": "gpt-4-turbo",
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "Hello!"}
+            ],
+            "temperature": 0.7,
+            "max_tokens": 100,
+            "stream": true
+        });
+
+        let request = parse_ai_request(
+            &body,
+            Provider::OpenAI,
+            "https://api.openai.com/v1/chat/completions",
+        )
+        .unwrap();
+
+        assert_eq!(request.model.as_ref().unwrap().id, "gpt-4-turbo");
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.streaming, Some(true));
+        assert_eq!(request.has_system_prompt, Some(true));
+        assert!(request.system_prompt_hash.is_some());
+        assert_eq!(request.parameters.as_ref().unwrap().temperature, Some(0.7));
+    }
+
+    #[test]
+    fn test_parse_openai_response() {
+        let body: Value = serde_json::json!({
+            "id": "chatcmpl-abc123",
+            "object": "chat.completion",
+            "model": "gpt-4-turbo",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "Hello! How can I help?"
+                },
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": 10,
+                "completion_tokens": 8,
+                "total_tokens": 18
+            }
+        });
+
+        let response = parse_ai_response(&body, "test-request-id", Provider::OpenAI).unwrap();
+
+        assert_eq!(
+            response.provider_request_id,
+            Some("chatcmpl-abc123".to_string())
+        );
+        assert_eq!(response.choices.len(), 1);
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.usage.as_ref().unwrap().prompt_tokens, Some(10));
+        assert_eq!(response.usage.as_ref().unwrap().completion_tokens, Some(8));
+    }
+
+    #[test]
+    fn test_parse_openai_tool_call_response() {
+        let body: Value = serde_json::json!({
+            "id": "chatcmpl-abc123",
+            "model": "gpt-4-turbo",
+            "choices": [{
+                "index": 0,
                 "message": {
                     "role": "assistant",
                     "content": null,
@@ -849,6 +2613,33 @@ mod tests {
         assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
     }
 
+    #[test]
+    fn test_parse_anthropic_response_large_tool_input() {
+        let patch = "+ added line\n".repeat(200_000);
+        let body: Value = serde_json::json!({
+            "id": "msg_abc123",
+            "model": "claude-3-5-sonnet-20241022",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "apply_patch",
+                "input": {"path": "src/lib.rs", "patch": patch}
+            }],
+            "stop_reason": "tool_use"
+        });
+
+        let response = parse_anthropic_response(&body, "test-request-id").unwrap();
+        let call = &response.tool_calls[0];
+        assert_eq!(call.name, "apply_patch");
+        assert!(call.arguments_size.unwrap() > patch.len());
+        assert!(call.arguments_hash.is_some());
+        assert!(matches!(
+            &call.arguments,
+            Some(ToolArguments::String(preview))
+                if preview.len() <= crate::tool_args::ARGUMENTS_PREVIEW
+        ));
+    }
+
     #[test]
     fn test_parse_anthropic_request() {
         let body: Value = serde_json::json!({
@@ -871,6 +2662,83 @@ mod tests {
         assert_eq!(request.parameters.as_ref().unwrap().max_tokens, Some(1024));
     }
 
+    #[test]
+    fn test_multimodal_images_described_without_data() {
+        let png = format!("iVBORw0KGgo{}", "A".repeat(4085));
+        let jpeg = format!("/9j/{}==", "B".repeat(2042));
+        let body: Value = serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Compare these"},
+                    {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", png)}},
+                    {"type": "image_url", "image_url": {"url": format!("data:;base64,{}", jpeg), "detail": "low"}}
+                ]
+            }]
+        });
+
+        let request = parse_ai_request(&body, Provider::OpenAI, "https://api.openai.com").unwrap();
+        assert_eq!(request.has_images, Some(true));
+        assert_eq!(request.image_count, Some(2));
+
+        let message = &request.messages[0];
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Compare these"));
+        assert_eq!(
+            message.images,
+            vec![
+                ImageInfo {
+                    source: ImageSource::Base64,
+                    media_type: Some("image/png".to_string()),
+                    size_bytes: Some(3072),
+                },
+                // No MIME type in the data URL: sniffed from the data
+                ImageInfo {
+                    source: ImageSource::Base64,
+                    media_type: Some("image/jpeg".to_string()),
+                    size_bytes: Some(1534),
+                },
+            ]
+        );
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(!json.contains(&png[..64]));
+        assert!(!json.contains(&jpeg[..64]));
+    }
+
+    #[test]
+    fn test_anthropic_and_ollama_images() {
+        let body: Value = serde_json::json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "max_tokens": 100,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/webp", "data": "UklGRgAAAAA="}},
+                    {"type": "image", "source": {"type": "url", "url": "https://example.com/cat.png"}},
+                    {"type": "text", "text": "Describe"}
+                ]
+            }]
+        });
+        let request = parse_anthropic_request(&body, "https://api.anthropic.com").unwrap();
+        assert_eq!(request.image_count, Some(2));
+        let images = &request.messages[0].images;
+        assert_eq!(images[0].media_type.as_deref(), Some("image/webp"));
+        assert_eq!(images[0].size_bytes, Some(8));
+        assert_eq!(images[1].source, ImageSource::Url);
+
+        let body: Value = serde_json::json!({
+            "model": "llava",
+            "prompt": "What is this?",
+            "images": ["R0lGODlhAQABAAAAACw="]
+        });
+        let request =
+            parse_ollama_request(&body, "/api/generate", "http://localhost:11434").unwrap();
+        let image = &request.messages[0].images[0];
+        assert_eq!(image.media_type.as_deref(), Some("image/gif"));
+        assert_eq!(image.size_bytes, Some(14));
+    }
+
     #[test]
     fn test_parse_anthropic_response() {
         let body: Value = serde_json::json!({
@@ -896,6 +2764,93 @@ mod tests {
         assert_eq!(response.usage.as_ref().unwrap().prompt_tokens, Some(25));
     }
 
+    #[test]
+    fn test_parse_openai_error_bodies() {
+        let rate_limit: Value = serde_json::json!({
+            "error": {
+                "message": "Rate limit reached for gpt-4o in organization org-abc on tokens per min (TPM): Limit 30000, Used 29800, Requested 1200.",
+                "type": "tokens",
+                "param": null,
+                "code": "rate_limit_exceeded"
+            }
+        });
+        let response = parse_error_response(&rate_limit, "req-1", Provider::OpenAI, 429);
+        assert_eq!(response.success, Some(false));
+        assert_eq!(response.status_code, Some(429));
+        let error = response.error.unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("tokens"));
+        assert_eq!(error.code.as_deref(), Some(RATE_LIMIT_ERROR));
+        assert!(error.message.unwrap().starts_with("Rate limit reached"));
+
+        let context: Value = serde_json::json!({
+            "error": {
+                "message": "This model's maximum context length is 128000 tokens. However, your messages resulted in 130512 tokens. Please reduce the length of the messages.",
+                "type": "invalid_request_error",
+                "param": "messages",
+                "code": "context_length_exceeded"
+            }
+        });
+        let error = parse_error_body(&context, 400).unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("invalid_request_error"));
+        assert_eq!(error.code.as_deref(), Some(CONTEXT_LENGTH_ERROR));
+
+        let invalid_key: Value = serde_json::json!({
+            "error": {
+                "message": "Incorrect API key provided: sk-proj-****abcd. You can find your API key at https://platform.openai.com/account/api-keys.",
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "invalid_api_key"
+            }
+        });
+        let error = parse_error_body(&invalid_key, 401).unwrap();
+        assert_eq!(error.code.as_deref(), Some("invalid_api_key"));
+
+        // Not an error body, and not a status that says what went wrong
+        assert!(parse_error_body(&serde_json::json!({"detail": "nope"}), 500).is_none());
+        let error = parse_error_body(&serde_json::json!({}), 429).unwrap();
+        assert_eq!(error.code.as_deref(), Some(RATE_LIMIT_ERROR));
+    }
+
+    #[test]
+    fn test_parse_anthropic_error_bodies() {
+        let rate_limit: Value = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": "rate_limit_error",
+                "message": "This request would exceed the rate limit for your organization of 50,000 input tokens per minute."
+            },
+            "request_id": "req_011CSHoEeqs5C35K2UUqR7Fy"
+        });
+        let response = parse_error_response(&rate_limit, "req-1", Provider::Anthropic, 429);
+        assert_eq!(response.provider.unwrap().name, "anthropic");
+        assert_eq!(
+            response.provider_request_id.as_deref(),
+            Some("req_011CSHoEeqs5C35K2UUqR7Fy")
+        );
+        let error = response.error.unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("rate_limit_error"));
+        assert_eq!(error.code.as_deref(), Some(RATE_LIMIT_ERROR));
+
+        let context: Value = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": "invalid_request_error",
+                "message": "prompt is too long: 210519 tokens > 200000 maximum"
+            }
+        });
+        let error = parse_error_body(&context, 400).unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("invalid_request_error"));
+        assert_eq!(error.code.as_deref(), Some(CONTEXT_LENGTH_ERROR));
+
+        let auth: Value = serde_json::json!({
+            "type": "error",
+            "error": {"type": "authentication_error", "message": "invalid x-api-key"}
+        });
+        let error = parse_error_body(&auth, 401).unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("authentication_error"));
+        assert_eq!(error.code, None);
+    }
+
     #[test]
     fn test_parse_anthropic_tool_response() {
         let body: Value = serde_json::json!({
@@ -933,6 +2888,98 @@ mod tests {
             detect_request_type(&serde_json::json!({})),
             RequestType::Other
         );
+        assert_eq!(
+            detect_request_type(
+                &serde_json::json!({"model": "dall-e-3", "prompt": "A cat", "size": "1024x1024"})
+            ),
+            RequestType::Image
+        );
+        assert_eq!(
+            detect_request_type(
+                &serde_json::json!({"model": "tts-1", "input": "Hi", "voice": "alloy"})
+            ),
+            RequestType::Audio
+        );
+        assert_eq!(
+            detect_request_type(
+                &serde_json::json!({"model": "omni-moderation-latest", "input": "Hi"})
+            ),
+            RequestType::Moderation
+        );
+    }
+
+    #[test]
+    fn test_request_type_from_path() {
+        let cases = [
+            ("/v1/chat/completions", Some(RequestType::Chat)),
+            ("/v1/messages", Some(RequestType::Chat)),
+            ("/v1/messages?beta=true", Some(RequestType::Chat)),
+            ("/v1/responses", Some(RequestType::Chat)),
+            ("/api/chat", Some(RequestType::Chat)),
+            ("/v2/chat", Some(RequestType::Chat)),
+            (
+                "/v1beta/models/gemini-1.5-pro:streamGenerateContent",
+                Some(RequestType::Chat),
+            ),
+            ("/v1/completions", Some(RequestType::Completion)),
+            ("/v1/complete", Some(RequestType::Completion)),
+            ("/api/generate", Some(RequestType::Completion)),
+            ("/v1/embeddings", Some(RequestType::Embedding)),
+            (
+                "/openai/deployments/ada/embeddings",
+                Some(RequestType::Embedding),
+            ),
+            ("/api/embed", Some(RequestType::Embedding)),
+            (
+                "/v1beta/models/text-embedding-004:embedContent",
+                Some(RequestType::Embedding),
+            ),
+            ("/v1/moderations", Some(RequestType::Moderation)),
+            ("/v1/images/generations", Some(RequestType::Image)),
+            ("/v1/images/edits", Some(RequestType::Image)),
+            ("/v1/audio/transcriptions", Some(RequestType::Audio)),
+            ("/v1/audio/speech", Some(RequestType::Audio)),
+            ("/v1/models", None),
+            ("/v1/threads/thread_abc/messages", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(request_type_from_path(path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_parse_embedding_response() {
+        let body = serde_json::json!({
+            "object": "list",
+            "model": "text-embedding-3-small",
+            "data": [
+                {"object": "embedding", "index": 0, "embedding": [0.1, 0.2, 0.3]},
+                {"object": "embedding", "index": 1, "embedding": [0.4, 0.5, 0.6]}
+            ],
+            "usage": {"prompt_tokens": 8, "total_tokens": 8}
+        });
+        let mut response = parse_ai_response(&body, "req", Provider::OpenAI).unwrap();
+        response.latency_ms = Some(42);
+
+        let data = parse_embedding_response(&body, &response);
+        assert_eq!(data.model.unwrap().id, "text-embedding-3-small");
+        assert_eq!(data.input_count, Some(2));
+        assert_eq!(data.dimensions, Some(3));
+        assert_eq!(data.total_tokens, Some(8));
+        assert_eq!(data.latency_ms, Some(42));
+
+        // Four f32s, base64-encoded
+        let body = serde_json::json!({
+            "data": [{"embedding": "AAAAAAAAgD8AAABAAABAQA=="}]
+        });
+        assert_eq!(
+            parse_embedding_response(&body, &response).dimensions,
+            Some(4)
+        );
+
+        let body = serde_json::json!({"embedding": {"values": [0.1, 0.2]}});
+        let data = parse_embedding_response(&body, &response);
+        assert_eq!((data.input_count, data.dimensions), (Some(1), Some(2)));
     }
 
     #[test]
@@ -1000,4 +3047,473 @@ mod tests {
             Some("Get the current weather".to_string())
         );
     }
+
+    #[test]
+    fn test_is_responses_api_request() {
+        let body = serde_json::json!({"model": "gpt-4o", "input": "Hello"});
+
+        assert!(is_responses_api_request("/v1/responses", &body));
+        assert!(is_responses_api_request("/v1/responses?stream=true", &body));
+        assert!(!is_responses_api_request("/v1/chat/completions", &body));
+        assert!(!is_responses_api_request(
+            "/v1/responses",
+            &serde_json::json!({"model": "gpt-4o"})
+        ));
+    }
+
+    #[test]
+    fn test_is_openai_compatible_request() {
+        let chat = serde_json::json!({"model": "qwen2.5", "messages": []});
+        let completion = serde_json::json!({"model": "qwen2.5", "prompt": "Once"});
+        let embedding = serde_json::json!({"model": "bge-m3", "input": ["a", "b"]});
+
+        assert!(is_openai_compatible_request("/v1/chat/completions", &chat));
+        assert!(is_openai_compatible_request(
+            "/openai/v1/chat/completions/",
+            &chat
+        ));
+        assert!(is_openai_compatible_request(
+            "/v1/completions?x=1",
+            &completion
+        ));
+        assert!(is_openai_compatible_request("/v1/embeddings", &embedding));
+
+        // Right fields on the wrong endpoint, or the other way around
+        assert!(!is_openai_compatible_request("/v1/jobs", &chat));
+        assert!(!is_openai_compatible_request(
+            "/v1/chat/completions",
+            &completion
+        ));
+        assert!(!is_openai_compatible_request(
+            "/v1/chat/completions",
+            &serde_json::json!({"messages": []})
+        ));
+    }
+
+    #[test]
+    fn test_parse_responses_request() {
+        let body = serde_json::json!({
+            "model": "gpt-4o",
+            "instructions": "You are helpful",
+            "input": [
+                {"role": "user", "content": [
+                    {"type": "input_text", "text": "What is in this image?"},
+                    {"type": "input_image", "image_url": "https://example.com/a.png"}
+                ]},
+                {"type": "function_call", "call_id": "call_1", "name": "lookup", "arguments": "{}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "42"}
+            ],
+            "tools": [
+                {"type": "function", "name": "lookup", "description": "Look something up"},
+                {"type": "file_search", "vector_store_ids": ["vs_1"]}
+            ],
+            "max_output_tokens": 256,
+            "stream": true
+        });
+
+        let request = parse_responses_request(
+            &body,
+            Provider::OpenAI,
+            "https://api.openai.com/v1/responses",
+        )
+        .unwrap();
+
+        assert_eq!(request.model.as_ref().unwrap().id, "gpt-4o");
+        assert_eq!(request.messages.len(), 4);
+        assert!(matches!(request.messages[0].role, MessageRole::System));
+        assert!(matches!(request.messages[1].role, MessageRole::User));
+        assert_eq!(request.messages[1].image_count, Some(1));
+        assert!(matches!(request.messages[2].role, MessageRole::Assistant));
+        assert!(matches!(request.messages[3].role, MessageRole::Tool));
+        assert_eq!(request.messages[3].tool_call_id, Some("call_1".to_string()));
+        assert_eq!(request.has_system_prompt, Some(true));
+        assert_eq!(request.has_images, Some(true));
+        assert_eq!(request.streaming, Some(true));
+        assert_eq!(request.tools_count, Some(2));
+        assert_eq!(request.tools[0].name, "lookup");
+        assert!(matches!(
+            request.tools[1].tool_type,
+            Some(ToolType::FileSearch)
+        ));
+        assert_eq!(request.parameters.unwrap().max_tokens, Some(256));
+    }
+
+    #[test]
+    fn test_parse_responses_response() {
+        let body = serde_json::json!({
+            "id": "resp_123",
+            "object": "response",
+            "model": "o3-mini",
+            "status": "completed",
+            "output": [
+                {"type": "reasoning", "summary": [{"type": "summary_text", "text": "Thinking it over"}]},
+                {"type": "message", "role": "assistant", "content": [
+                    {"type": "output_text", "text": "Hello!"}
+                ]}
+            ],
+            "usage": {
+                "input_tokens": 10,
+                "output_tokens": 20,
+                "total_tokens": 30,
+                "input_tokens_details": {"cached_tokens": 4},
+                "output_tokens_details": {"reasoning_tokens": 12}
+            }
+        });
+
+        assert!(is_responses_api_response(&body));
+        assert_eq!(detect_provider_from_body(&body), Some(Provider::OpenAI));
+
+        let response = parse_responses_response(&body, "req_1", Provider::OpenAI).unwrap();
+
+        assert_eq!(response.provider_request_id, Some("resp_123".to_string()));
+        assert_eq!(response.success, Some(true));
+        assert!(matches!(response.finish_reason, Some(FinishReason::Stop)));
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hello!"));
+
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(10));
+        assert_eq!(usage.completion_tokens, Some(20));
+        assert_eq!(usage.cached_tokens, Some(4));
+        assert_eq!(usage.reasoning_tokens, Some(12));
+
+        let thinking = response.thinking.unwrap();
+        assert!(matches!(thinking.mode, Some(ThinkingMode::Reasoning)));
+        assert_eq!(thinking.tokens, Some(12));
+    }
+
+    #[test]
+    fn test_parse_responses_response_function_call() {
+        let body = serde_json::json!({
+            "id": "resp_456",
+            "object": "response",
+            "model": "gpt-4o",
+            "status": "completed",
+            "output": [
+                {"type": "function_call", "id": "fc_1", "call_id": "call_1",
+                 "name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+            ]
+        });
+
+        let response = parse_responses_response(&body, "req_2", Provider::OpenAI).unwrap();
+
+        assert!(matches!(
+            response.finish_reason,
+            Some(FinishReason::ToolCalls)
+        ));
+        assert_eq!(response.tool_calls_count, Some(1));
+        assert_eq!(response.tool_calls[0].name, "get_weather");
+        assert_eq!(response.tool_calls[0].id, Some("call_1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_ollama_generate_request() {
+        let body = serde_json::json!({
+            "model": "llava:7b",
+            "system": "Describe images tersely",
+            "prompt": "What is in this picture?",
+            "images": ["iVBORw0KGgoAAAANSUhEUgAA"],
+            "stream": false
+        });
+
+        assert!(is_ollama_native_request("/api/generate"));
+        assert!(!is_ollama_native_request("/v1/chat/completions"));
+
+        let request = parse_ollama_request(
+            &body,
+            "/api/generate",
+            "http://localhost:11434/api/generate",
+        )
+        .unwrap();
+
+        assert_eq!(request.provider.as_ref().unwrap().name, "ollama");
+        assert_eq!(request.model.as_ref().unwrap().id, "llava:7b");
+        assert_eq!(request.request_type, Some(RequestType::Completion));
+        assert_eq!(request.streaming, Some(false));
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.has_system_prompt, Some(true));
+        assert_eq!(request.image_count, Some(1));
+    }
+
+    #[test]
+    fn test_parse_ollama_chat_response() {
+        let body = serde_json::json!({
+            "model": "llama3.2",
+            "created_at": "2024-11-01T10:00:00Z",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    {"function": {"name": "get_weather", "arguments": {"city": "Toronto"}}}
+                ]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "total_duration": 885095291u64,
+            "prompt_eval_count": 120,
+            "eval_count": 18
+        });
+
+        let response = parse_ollama_response(&body, "req_1").unwrap();
+
+        assert_eq!(response.model.as_ref().unwrap().id, "llama3.2");
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(response.tool_calls[0].name, "get_weather");
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, Some(138));
+        assert_eq!(response.latency_ms, Some(885));
+    }
+
+    #[test]
+    fn test_parse_gemini_request() {
+        // Captured from a Vertex AI client, image data shortened
+        let body = serde_json::json!({
+            "contents": [
+                {"role": "user", "parts": [
+                    {"text": "What is in this picture?"},
+                    {"inlineData": {"mimeType": "image/jpeg", "data": "/9j/4AAQSkZJRgABAQ"}}
+                ]},
+                {"role": "model", "parts": [{"text": "A lighthouse."}]},
+                {"role": "user", "parts": [{"text": "Where might it be?"}]}
+            ],
+            "systemInstruction": {"role": "system", "parts": [{"text": "You are a travel guide."}]},
+            "generationConfig": {
+                "temperature": 0.2,
+                "topP": 0.95,
+                "maxOutputTokens": 1024,
+                "stopSequences": ["END"]
+            },
+            "tools": [{"googleSearch": {}}],
+            "toolConfig": {"functionCallingConfig": {"mode": "AUTO"}}
+        });
+        let path = "/v1/projects/acme-ml/locations/europe-west4/publishers/google/models/gemini-1.5-flash-002:generateContent";
+        assert!(is_gemini_request(path, &body));
+        assert!(!is_ai_request(&body));
+
+        let request = parse_gemini_request(&body, path, "https://x").unwrap();
+        assert_eq!(request.model.as_ref().unwrap().id, "gemini-1.5-flash-002");
+        assert_eq!(
+            request.model.as_ref().unwrap().family.as_deref(),
+            Some("gemini")
+        );
+        assert_eq!(request.streaming, Some(false));
+        assert_eq!(request.messages_count, Some(4));
+        assert!(matches!(request.messages[0].role, MessageRole::System));
+        assert!(matches!(request.messages[2].role, MessageRole::Assistant));
+        assert_eq!(request.image_count, Some(1));
+        assert_eq!(
+            request.messages[1].images[0].media_type.as_deref(),
+            Some("image/jpeg")
+        );
+        let parameters = request.parameters.as_ref().unwrap();
+        assert_eq!(parameters.max_tokens, Some(1024));
+        assert_eq!(parameters.stop, vec!["END".to_string()]);
+        assert_eq!(request.tools[0].name, "googleSearch");
+        assert_eq!(request.tool_choice.as_deref(), Some("auto"));
+        let provider = request.provider.as_ref().unwrap();
+        assert_eq!(provider.project_id.as_deref(), Some("acme-ml"));
+        assert_eq!(provider.region.as_deref(), Some("europe-west4"));
+
+        // Same body on the Gemini API
+        let path = "/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse";
+        let request = parse_gemini_request(&body, path, "https://x").unwrap();
+        assert_eq!(request.streaming, Some(true));
+        assert!(request.provider.as_ref().unwrap().region.is_none());
+    }
+
+    #[test]
+    fn test_parse_gemini_response() {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Thinking it over", "thought": true},
+                    {"text": "It is probably in Brittany."}
+                ]},
+                "finishReason": "MAX_TOKENS",
+                "avgLogprobs": -0.21
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 1290,
+                "candidatesTokenCount": 8,
+                "totalTokenCount": 1318,
+                "thoughtsTokenCount": 20
+            },
+            "modelVersion": "gemini-2.5-flash"
+        });
+        let response = parse_gemini_response(&body, "req_1").unwrap();
+        assert_eq!(response.finish_reason, Some(FinishReason::Length));
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert!(matches!(
+            &message.content,
+            Some(MessageContent::Text(t)) if t == "It is probably in Brittany."
+        ));
+        let usage = response.usage.as_ref().unwrap();
+        assert_eq!(usage.total_tokens, Some(1318));
+        assert_eq!(usage.reasoning_tokens, Some(20));
+
+        // Blocked prompts get no candidates
+        let blocked = serde_json::json!({
+            "promptFeedback": {"blockReason": "SAFETY"},
+            "usageMetadata": {"promptTokenCount": 12, "totalTokenCount": 12}
+        });
+        let response = parse_gemini_response(&blocked, "req_2").unwrap();
+        assert!(response.choices.is_empty());
+        assert_eq!(response.finish_reason, Some(FinishReason::ContentFilter));
+
+        assert!(parse_gemini_response(&serde_json::json!({"choices": []}), "req_3").is_none());
+    }
+
+    #[test]
+    fn test_parse_bedrock_llama() {
+        let body = serde_json::json!({
+            "prompt": "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nName a color.<|eot_id|>",
+            "max_gen_len": 64,
+            "temperature": 0.5,
+            "top_p": 0.9
+        });
+        let path = "/model/us.meta.llama3-1-8b-instruct-v1:0/invoke";
+        assert!(is_bedrock_invoke_request(path));
+        assert!(!is_bedrock_invoke_request("/model/x/converse"));
+
+        let request = parse_bedrock_request(
+            &body,
+            "bedrock-runtime.eu-central-1.amazonaws.com",
+            path,
+            "https://x",
+        )
+        .unwrap();
+        assert_eq!(
+            request.model.as_ref().unwrap().id,
+            "us.meta.llama3-1-8b-instruct-v1:0"
+        );
+        assert_eq!(request.messages_count, Some(1));
+        let parameters = request.parameters.as_ref().unwrap();
+        assert_eq!(parameters.max_tokens, Some(64));
+        assert_eq!(parameters.top_p, Some(0.9));
+        assert_eq!(
+            request.provider.as_ref().unwrap().region.as_deref(),
+            Some("eu-central-1")
+        );
+
+        let response = parse_bedrock_response(
+            &serde_json::json!({
+                "generation": "Blue.",
+                "prompt_token_count": 17,
+                "generation_token_count": 3,
+                "stop_reason": "stop"
+            }),
+            "req_1",
+        )
+        .unwrap();
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, Some(20));
+        assert!(parse_bedrock_response(&serde_json::json!({"foo": 1}), "req_2").is_none());
+
+        assert_eq!(
+            bedrock_base_model("arn:aws:bedrock:us-east-1:123456789012:inference-profile/us.anthropic.claude-3-5-sonnet-20240620-v1:0"),
+            "claude-3-5-sonnet-20240620-v1:0"
+        );
+    }
+
+    fn limits(max_content_chars: usize, hash_after_redaction: bool) -> ContentLimits {
+        ContentLimits {
+            max_content_chars,
+            hash_after_redaction,
+            redaction: RedactionConfig::default(),
+        }
+    }
+
+    fn request_with(system: &str, user: &str) -> AiRequestData {
+        let body = serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": system},
+                {"role": "user", "content": user}
+            ]
+        });
+        parse_ai_request(
+            &body,
+            Provider::OpenAI,
+            "https://api.openai.com/v1/chat/completions",
+        )
+        .unwrap()
+    }
+
+    fn text(message: &Message) -> &str {
+        match &message.content {
+            Some(MessageContent::Text(t)) => t,
+            other => panic!("expected text content, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_content_limits_truncate() {
+        let long = "é".repeat(50);
+        let mut request = request_with("Be brief.", &long);
+        apply_content_limits(&mut request, &limits(10, false));
+
+        assert_eq!(text(&request.messages[0]), "Be brief.");
+        assert_eq!(text(&request.messages[1]), "é".repeat(10));
+        assert_eq!(request.messages[1].content_length, Some(long.len()));
+        assert_eq!(
+            request.messages[1].content_hash.as_deref(),
+            Some(hash_content(&long).as_str())
+        );
+        assert_eq!(request.system_prompt_hash, request.messages[0].content_hash);
+    }
+
+    #[test]
+    fn test_content_limits_hash_is_stable() {
+        let prompt = format!("Summarize this document: {}", "lorem ipsum ".repeat(100));
+        let mut a = request_with("sys", &prompt);
+        let mut b = request_with("sys", &prompt);
+        let mut c = request_with("sys", &format!("{}!", prompt));
+        for request in [&mut a, &mut b, &mut c] {
+            apply_content_limits(request, &limits(32, false));
+        }
+
+        // Truncated text is identical for all three; only the hash tells them apart
+        assert_eq!(text(&a.messages[1]), text(&c.messages[1]));
+        assert_eq!(a.messages[1].content_hash, b.messages[1].content_hash);
+        assert_ne!(a.messages[1].content_hash, c.messages[1].content_hash);
+        assert!(a.messages[1]
+            .content_hash
+            .as_deref()
+            .unwrap()
+            .starts_with("sha256:"));
+    }
+
+    #[test]
+    fn test_content_limits_with_redaction() {
+        let a = "Contact alice@example.com about the invoice";
+        let b = "Contact bob@example.org about the invoice";
+
+        // Pre-redaction hashes differ and content is kept as sent
+        let mut pre_a = request_with("sys", a);
+        let mut pre_b = request_with("sys", b);
+        apply_content_limits(&mut pre_a, &limits(1000, false));
+        apply_content_limits(&mut pre_b, &limits(1000, false));
+        assert_eq!(text(&pre_a.messages[1]), a);
+        assert_ne!(
+            pre_a.messages[1].content_hash,
+            pre_b.messages[1].content_hash
+        );
+
+        // Post-redaction hashes match and content no longer holds the email
+        let mut post_a = request_with("sys", a);
+        let mut post_b = request_with("sys", b);
+        apply_content_limits(&mut post_a, &limits(1000, true));
+        apply_content_limits(&mut post_b, &limits(1000, true));
+        assert!(!text(&post_a.messages[1]).contains("alice@example.com"));
+        assert_eq!(
+            post_a.messages[1].content_hash,
+            post_b.messages[1].content_hash
+        );
+
+        // Minimal mode leaves nothing but the marker, truncated
+        let mut minimal = request_with("sys", a);
+        let mut config = limits(4, true);
+        config.redaction.mode = oisp_core::redaction::RedactionMode::Minimal;
+        apply_content_limits(&mut minimal, &config);
+        assert_eq!(text(&minimal.messages[1]), "[RED");
+    }
 }
@@ -0,0 +1,514 @@
+//! AWS Bedrock streaming response parsing
+//!
+//! `invoke-with-response-stream` responses use the AWS event stream binary
+//! framing (`application/vnd.amazon.eventstream`). Each `chunk` event's
+//! payload is `{"bytes": "<base64>"}`, wrapping the model's own streaming
+//! chunk (Anthropic message events, Titan `outputText`, Llama `generation`,
+//! ...). The last chunk also carries `amazon-bedrock-invocationMetrics`
+//! with the token counts Bedrock billed.
+
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Prelude (total length, headers length, prelude CRC) plus message CRC
+const FRAMING_LEN: usize = 16;
+
+/// Messages claiming to be larger than this are treated as garbage
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// Key of the token counts Bedrock adds to the last chunk of a stream and
+/// to merged stream bodies
+pub const INVOCATION_METRICS: &str = "amazon-bedrock-invocationMetrics";
+
+/// A single event stream message
+#[derive(Debug, Clone)]
+pub struct EventStreamMessage {
+    /// String-valued headers (`:event-type`, `:message-type`, ...)
+    pub headers: HashMap<String, String>,
+    pub payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Parser for the AWS event stream binary framing
+///
+/// Each message is a 12-byte prelude (total length, headers length, CRC32 of
+/// the first 8 bytes), the headers, the payload and a CRC32 of the message.
+/// The prelude CRC is checked so that bytes which are not a message (chunked
+/// transfer encoding size lines, a capture that starts mid-stream) are
+/// skipped until the next message; the message CRC is not, TLS already
+/// guarantees the bytes.
+#[derive(Default)]
+pub struct EventStreamParser {
+    buffer: Vec<u8>,
+    messages: Vec<EventStreamMessage>,
+}
+
+impl EventStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add data to the parser
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+
+        let mut pos = 0;
+        while self.buffer.len() - pos >= 12 {
+            let prelude = &self.buffer[pos..pos + 12];
+            let total = u32::from_be_bytes(prelude[0..4].try_into().unwrap()) as usize;
+            let headers_len = u32::from_be_bytes(prelude[4..8].try_into().unwrap()) as usize;
+            let crc = u32::from_be_bytes(prelude[8..12].try_into().unwrap());
+
+            if crc32fast::hash(&prelude[0..8]) != crc
+                || !(FRAMING_LEN..=MAX_MESSAGE_LEN).contains(&total)
+                || headers_len > total - FRAMING_LEN
+            {
+                pos += 1;
+                continue;
+            }
+            if self.buffer.len() - pos < total {
+                break;
+            }
+
+            let message = &self.buffer[pos..pos + total];
+            let headers = parse_headers(&message[12..12 + headers_len]);
+            let payload = message[12 + headers_len..total - 4].to_vec();
+            self.messages.push(EventStreamMessage { headers, payload });
+            pos += total;
+        }
+        self.buffer.drain(..pos);
+    }
+
+    /// Take all parsed messages
+    pub fn take_messages(&mut self) -> Vec<EventStreamMessage> {
+        std::mem::take(&mut self.messages)
+    }
+}
+
+/// Header values are typed; only strings are kept
+fn parse_headers(mut data: &[u8]) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    while let Some((&name_len, rest)) = data.split_first() {
+        let Some((name, rest)) = rest.split_at_checked(name_len as usize) else {
+            break;
+        };
+        let Some((&value_type, rest)) = rest.split_first() else {
+            break;
+        };
+        let value_len = match value_type {
+            // true, false
+            0 | 1 => 0,
+            // byte, short, int, long, timestamp, uuid
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            // bytes, string: 2-byte length prefix
+            6 | 7 if rest.len() >= 2 => 2 + u16::from_be_bytes([rest[0], rest[1]]) as usize,
+            _ => break,
+        };
+        let Some((value, rest)) = rest.split_at_checked(value_len) else {
+            break;
+        };
+        if value_type == 7 {
+            headers.insert(
+                String::from_utf8_lossy(name).to_string(),
+                String::from_utf8_lossy(&value[2..]).to_string(),
+            );
+        }
+        data = rest;
+    }
+    headers
+}
+
+/// Shape of the model chunks in a Bedrock stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkFormat {
+    /// Anthropic Messages events (`message_start`, `content_block_delta`, ...)
+    Anthropic,
+    /// Amazon Titan text (`outputText`)
+    Titan,
+    /// Meta Llama (`generation`)
+    Llama,
+    /// Mistral (`outputs[].text`)
+    Mistral,
+}
+
+/// A tool call streamed by an Anthropic model
+struct ToolUse {
+    id: Option<String>,
+    name: String,
+    input_json: String,
+}
+
+/// Reassemble a Bedrock `invoke-with-response-stream` response
+#[derive(Default)]
+pub struct BedrockStreamReassembler {
+    parser: EventStreamParser,
+    format: Option<ChunkFormat>,
+    complete_content: String,
+    tool_uses: Vec<ToolUse>,
+    stop_reason: Option<String>,
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    model: Option<String>,
+    message_id: Option<String>,
+    metrics: Option<Value>,
+    exception: Option<(String, String)>,
+    chunks: usize,
+    complete: bool,
+}
+
+impl BedrockStreamReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed data and parse chunks
+    pub fn feed(&mut self, data: &[u8]) {
+        self.parser.feed(data);
+
+        for message in self.parser.take_messages() {
+            // Errors after the stream started (throttling, validation, ...)
+            // arrive as exception messages with a `{"message"}` payload
+            if message.header(":message-type") == Some("exception") {
+                let error_type = message
+                    .header(":exception-type")
+                    .unwrap_or("exception")
+                    .to_string();
+                let text = serde_json::from_slice::<Value>(&message.payload)
+                    .ok()
+                    .and_then(|p| p.get("message").and_then(|m| m.as_str()).map(String::from))
+                    .unwrap_or_default();
+                self.exception = Some((error_type, text));
+                self.complete = true;
+                continue;
+            }
+            if message.header(":event-type") != Some("chunk") {
+                continue;
+            }
+
+            let chunk = serde_json::from_slice::<Value>(&message.payload)
+                .ok()
+                .and_then(|p| p.get("bytes").and_then(|b| b.as_str()).map(String::from))
+                .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok())
+                .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+            if let Some(chunk) = chunk {
+                self.add_chunk(&chunk);
+            }
+        }
+    }
+
+    fn add_chunk(&mut self, chunk: &Value) {
+        self.chunks += 1;
+        if let Some(metrics) = chunk.get(INVOCATION_METRICS) {
+            self.metrics = Some(metrics.clone());
+            self.complete = true;
+        }
+
+        let text = |v: Option<&Value>| v.and_then(|t| t.as_str()).map(String::from);
+        let count = |v: Option<&Value>| v.and_then(|t| t.as_u64());
+
+        if let Some(event_type) = chunk.get("type").and_then(|t| t.as_str()) {
+            self.format.get_or_insert(ChunkFormat::Anthropic);
+            match event_type {
+                "message_start" => {
+                    let message = chunk.get("message");
+                    self.message_id = text(message.and_then(|m| m.get("id")));
+                    self.model = text(message.and_then(|m| m.get("model")));
+                    self.input_tokens = count(
+                        message
+                            .and_then(|m| m.get("usage"))
+                            .and_then(|u| u.get("input_tokens")),
+                    );
+                }
+                "content_block_start" => {
+                    let block = chunk.get("content_block");
+                    if block.and_then(|b| b.get("type")).and_then(|t| t.as_str())
+                        == Some("tool_use")
+                    {
+                        self.tool_uses.push(ToolUse {
+                            id: text(block.and_then(|b| b.get("id"))),
+                            name: text(block.and_then(|b| b.get("name"))).unwrap_or_default(),
+                            input_json: String::new(),
+                        });
+                    }
+                }
+                "content_block_delta" => {
+                    let delta = chunk.get("delta");
+                    if let Some(t) = text(delta.and_then(|d| d.get("text"))) {
+                        self.complete_content.push_str(&t);
+                    } else if let (Some(partial), Some(tool)) = (
+                        text(delta.and_then(|d| d.get("partial_json"))),
+                        self.tool_uses.last_mut(),
+                    ) {
+                        tool.input_json.push_str(&partial);
+                    }
+                }
+                "message_delta" => {
+                    self.stop_reason = text(chunk.get("delta").and_then(|d| d.get("stop_reason")));
+                    if let Some(output) =
+                        count(chunk.get("usage").and_then(|u| u.get("output_tokens")))
+                    {
+                        self.output_tokens = Some(output);
+                    }
+                }
+                "message_stop" => self.complete = true,
+                _ => {}
+            }
+            return;
+        }
+
+        let (format, content, reason) = if let Some(output) = chunk.get("outputText") {
+            self.input_tokens = count(chunk.get("inputTextTokenCount")).or(self.input_tokens);
+            self.output_tokens =
+                count(chunk.get("totalOutputTextTokenCount")).or(self.output_tokens);
+            (ChunkFormat::Titan, output, chunk.get("completionReason"))
+        } else if let Some(generation) = chunk.get("generation") {
+            self.input_tokens = count(chunk.get("prompt_token_count")).or(self.input_tokens);
+            self.output_tokens = count(chunk.get("generation_token_count")).or(self.output_tokens);
+            (ChunkFormat::Llama, generation, chunk.get("stop_reason"))
+        } else if let Some(output) = chunk.get("outputs").and_then(|o| o.get(0)) {
+            (
+                ChunkFormat::Mistral,
+                output.get("text").unwrap_or(&Value::Null),
+                output.get("stop_reason"),
+            )
+        } else {
+            return;
+        };
+        self.format.get_or_insert(format);
+        if let Some(t) = content.as_str() {
+            self.complete_content.push_str(t);
+        }
+        if let Some(reason) = text(reason) {
+            self.stop_reason = Some(reason);
+        }
+    }
+
+    /// Check if stream is complete: the invocation metrics, Anthropic's
+    /// `message_stop` or an exception was seen
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Get complete content
+    pub fn content(&self) -> &str {
+        &self.complete_content
+    }
+
+    /// Get the exception (type, message) that ended the stream, if any
+    pub fn exception(&self) -> Option<(&str, &str)> {
+        self.exception
+            .as_ref()
+            .map(|(t, m)| (t.as_str(), m.as_str()))
+    }
+
+    /// Get token usage (input, output), preferring the invocation metrics
+    pub fn usage(&self) -> (Option<u64>, Option<u64>) {
+        let metric = |field: &str| {
+            self.metrics
+                .as_ref()
+                .and_then(|m| m.get(field))
+                .and_then(|v| v.as_u64())
+        };
+        (
+            metric("inputTokenCount").or(self.input_tokens),
+            metric("outputTokenCount").or(self.output_tokens),
+        )
+    }
+
+    /// Merge the stream into the model's non-streaming response body
+    ///
+    /// The result can be handed to [`crate::ai::parse_bedrock_response`],
+    /// with the invocation metrics kept under [`INVOCATION_METRICS`]. `None`
+    /// if no model chunk was parsed.
+    pub fn to_response(&self) -> Option<Value> {
+        if self.chunks == 0 {
+            return None;
+        }
+
+        let mut merged = match self.format? {
+            ChunkFormat::Anthropic => {
+                let mut content = Vec::new();
+                if !self.complete_content.is_empty() {
+                    content.push(serde_json::json!({
+                        "type": "text",
+                        "text": self.complete_content,
+                    }));
+                }
+                for tool in &self.tool_uses {
+                    let input = serde_json::from_str::<Value>(&tool.input_json)
+                        .unwrap_or_else(|_| serde_json::json!({}));
+                    content.push(serde_json::json!({
+                        "type": "tool_use",
+                        "id": tool.id,
+                        "name": tool.name,
+                        "input": input,
+                    }));
+                }
+                serde_json::json!({
+                    "id": self.message_id,
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model,
+                    "content": content,
+                    "stop_reason": self.stop_reason,
+                    "usage": {
+                        "input_tokens": self.input_tokens,
+                        "output_tokens": self.output_tokens,
+                    },
+                })
+            }
+            ChunkFormat::Titan => serde_json::json!({
+                "inputTextTokenCount": self.input_tokens,
+                "results": [{
+                    "outputText": self.complete_content,
+                    "tokenCount": self.output_tokens,
+                    "completionReason": self.stop_reason,
+                }],
+            }),
+            ChunkFormat::Llama => serde_json::json!({
+                "generation": self.complete_content,
+                "prompt_token_count": self.input_tokens,
+                "generation_token_count": self.output_tokens,
+                "stop_reason": self.stop_reason,
+            }),
+            ChunkFormat::Mistral => serde_json::json!({
+                "outputs": [{
+                    "text": self.complete_content,
+                    "stop_reason": self.stop_reason,
+                }],
+            }),
+        };
+        if let (Some(metrics), Some(obj)) = (&self.metrics, merged.as_object_mut()) {
+            obj.insert(INVOCATION_METRICS.to_string(), metrics.clone());
+        }
+        Some(merged)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Encode an event stream message with string headers
+    pub(crate) fn event_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut encoded_headers = Vec::new();
+        for (name, value) in headers {
+            encoded_headers.push(name.len() as u8);
+            encoded_headers.extend_from_slice(name.as_bytes());
+            encoded_headers.push(7);
+            encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            encoded_headers.extend_from_slice(value.as_bytes());
+        }
+        let total = 12 + encoded_headers.len() + payload.len() + 4;
+
+        let mut message = Vec::with_capacity(total);
+        message.extend_from_slice(&(total as u32).to_be_bytes());
+        message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+        let prelude_crc = crc32fast::hash(&message);
+        message.extend_from_slice(&prelude_crc.to_be_bytes());
+        message.extend_from_slice(&encoded_headers);
+        message.extend_from_slice(payload);
+        let crc = crc32fast::hash(&message);
+        message.extend_from_slice(&crc.to_be_bytes());
+        message
+    }
+
+    /// A `chunk` event wrapping a model chunk, as Bedrock sends it
+    pub(crate) fn chunk_event(chunk: &Value) -> Vec<u8> {
+        let bytes = base64::engine::general_purpose::STANDARD.encode(chunk.to_string());
+        event_message(
+            &[
+                (":event-type", "chunk"),
+                (":content-type", "application/json"),
+                (":message-type", "event"),
+            ],
+            serde_json::json!({ "bytes": bytes }).to_string().as_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_event_stream_parser_resyncs() {
+        let first = event_message(&[(":event-type", "chunk")], b"{\"a\":1}");
+        let second = event_message(&[(":event-type", "chunk")], b"{\"b\":2}");
+
+        // Chunked transfer encoding size line before the second message
+        let mut data = first.clone();
+        data.extend_from_slice(format!("{:x}\r\n", second.len()).as_bytes());
+        data.extend_from_slice(&second);
+
+        let mut parser = EventStreamParser::new();
+        let (head, tail) = data.split_at(first.len() + 5);
+        parser.feed(head);
+        assert_eq!(parser.take_messages().len(), 1);
+        parser.feed(tail);
+        let messages = parser.take_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].header(":event-type"), Some("chunk"));
+        assert_eq!(messages[0].payload, b"{\"b\":2}");
+    }
+
+    #[test]
+    fn test_bedrock_anthropic_stream() {
+        // Captured from anthropic.claude-3-haiku-20240307-v1:0, ids shortened
+        let chunks = [
+            serde_json::json!({"type": "message_start", "message": {"id": "msg_bdrk_01", "type": "message", "role": "assistant", "model": "claude-3-haiku-20240307", "content": [], "stop_reason": null, "usage": {"input_tokens": 14, "output_tokens": 1}}}),
+            serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hello"}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": " there!"}}),
+            serde_json::json!({"type": "content_block_stop", "index": 0}),
+            serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 6}}),
+            serde_json::json!({"type": "message_stop", "amazon-bedrock-invocationMetrics": {"inputTokenCount": 14, "outputTokenCount": 6, "invocationLatency": 412, "firstByteLatency": 301}}),
+        ];
+        let stream: Vec<u8> = chunks.iter().flat_map(chunk_event).collect();
+
+        let mut reassembler = BedrockStreamReassembler::new();
+        for piece in stream.chunks(50) {
+            reassembler.feed(piece);
+        }
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.content(), "Hello there!");
+        assert_eq!(reassembler.usage(), (Some(14), Some(6)));
+        let merged = reassembler.to_response().unwrap();
+        assert_eq!(merged["stop_reason"], "end_turn");
+        assert_eq!(merged["model"], "claude-3-haiku-20240307");
+        assert_eq!(merged[INVOCATION_METRICS]["outputTokenCount"], 6);
+    }
+
+    #[test]
+    fn test_bedrock_exception_ends_stream() {
+        let mut reassembler = BedrockStreamReassembler::new();
+        reassembler.feed(&chunk_event(&serde_json::json!({
+            "outputText": "Partial", "index": 0, "completionReason": null
+        })));
+        assert!(!reassembler.is_complete());
+
+        reassembler.feed(&event_message(
+            &[
+                (":exception-type", "throttlingException"),
+                (":content-type", "application/json"),
+                (":message-type", "exception"),
+            ],
+            b"{\"message\":\"Too many requests, please wait before trying again.\"}",
+        ));
+        assert!(reassembler.is_complete());
+        assert_eq!(
+            reassembler.exception(),
+            Some((
+                "throttlingException",
+                "Too many requests, please wait before trying again."
+            ))
+        );
+        assert_eq!(reassembler.content(), "Partial");
+    }
+}
@@ -0,0 +1,73 @@
+//! Monotonic clock used for decoder timeouts
+//!
+//! [`HttpDecoder`](crate::HttpDecoder) reads time through a [`Clock`] so that
+//! stale-state cleanup can be driven deterministically in tests with a
+//! [`MockClock`] instead of sleeping.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Source of monotonic time
+pub trait Clock: Send + Sync {
+    /// Current instant
+    fn now(&self) -> Instant;
+}
+
+/// Clock backed by [`Instant::now`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Manually advanced clock for tests
+///
+/// Starts at the instant it was created and only moves on [`MockClock::advance`].
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<Instant>,
+}
+
+impl MockClock {
+    /// Create a clock frozen at the current instant
+    pub fn new() -> Self {
+        Self {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_advances_only_when_told() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now().duration_since(start), Duration::from_secs(90));
+    }
+}
@@ -4,36 +4,73 @@
 //! Handles HTTP request/response correlation and AI provider detection.
 
 use crate::ai::{
-    detect_provider_from_body, is_ai_request, parse_ai_request, parse_ai_response,
-    parse_anthropic_request, parse_anthropic_response,
+    apply_content_limits, detect_provider_from_body, is_ai_request, is_bedrock_invoke_request,
+    is_gemini_request, is_gemini_response, is_non_chat_request, is_ollama_native_request,
+    is_openai_compatible_request, is_responses_api_request, is_responses_api_response,
+    multipart_summary, parse_ai_request, parse_ai_response, parse_anthropic_request,
+    parse_anthropic_response, parse_bedrock_request, parse_bedrock_response,
+    parse_embedding_response, parse_error_response, parse_gemini_request, parse_gemini_response,
+    parse_multipart_request, parse_ollama_request, parse_ollama_response, parse_responses_request,
+    parse_responses_response, request_type_from_path, ContentLimits, MULTIPART_ATTR,
 };
-use crate::http::{is_http_request, is_http_response, parse_request, parse_response};
+use crate::bedrock::BedrockStreamReassembler;
+use crate::failures::{DecodeFailure, FailureLog};
+use crate::flow::{ClosedFlow, FlowTracker};
+use crate::gemini::GeminiStreamReassembler;
+use crate::http::{
+    is_h2_preface, is_http_request, is_http_response, multipart_boundary, parse_request,
+    parse_response, skip_interim_responses, H2FrameReassembler, H2Message, MultipartParser,
+    ParsedHttpRequest,
+};
+use crate::ndjson::OllamaStreamReassembler;
+use crate::rag::{self, Retrieved, VectorQuery};
 use crate::sse::{AnthropicStreamReassembler, StreamReassembler};
+use crate::tls::{parse_client_hello, ClientHelloInfo, ClientHelloParse, MAX_CLIENT_HELLO_LEN};
 
 use oisp_core::events::*;
+use oisp_core::metrics::SharedMetrics;
+use oisp_core::monotonic::{CaptureClock, MonoClock};
 use oisp_core::plugins::{
     DecodePlugin, Plugin, PluginConfig, PluginInfo, PluginResult, RawCaptureEvent, RawEventKind,
 };
 use oisp_core::providers::{Provider, ProviderRegistry};
-use oisp_core::spec::{DynamicProviderRegistry, SpecLoader};
+use oisp_core::spec::{DynamicProviderRegistry, OispSpecBundle, SpecLoader};
 
 use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
 use std::any::Any;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use tracing::{debug, info, trace, warn};
 
+use crate::clock::{Clock, SystemClock};
+use crate::provider_cache::{BodyShape, ProviderCache, DEFAULT_PROVIDER_CACHE_CAPACITY};
+
 /// Maximum time to keep a pending request before discarding
 const PENDING_REQUEST_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
 
+/// Default time without a chunk before a started stream is closed
+///
+/// Longer than [`PENDING_REQUEST_TIMEOUT`]: a model can think for minutes
+/// between chunks, and an idle stream is cut off for good.
+pub const DEFAULT_STREAM_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often stale state is swept
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a connect is held for its ClientHello before it is reported
+/// without the SNI
+const SNI_HOLD_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Maximum number of pending requests to keep (prevents memory leaks)
 const MAX_PENDING_REQUESTS: usize = 10000;
 
 /// HTTP decoder plugin
 pub struct HttpDecoder {
-    /// Spec-driven provider registry (95+ providers from spec bundle)
-    spec_registry: Arc<DynamicProviderRegistry>,
+    /// Spec-driven provider registry (95+ providers from spec bundle),
+    /// replaced when the bundle is swapped
+    spec_registry: RwLock<Arc<DynamicProviderRegistry>>,
     /// Legacy provider registry (for Provider enum conversion, backward compatibility)
     legacy_registry: ProviderRegistry,
     // Track partial requests being reassembled
@@ -46,205 +83,69 @@ pub struct HttpDecoder {
     stream_reassemblers: RwLock<HashMap<CorrelationKey, StreamReassembler>>,
     // Track Anthropic streaming responses
     anthropic_reassemblers: RwLock<HashMap<CorrelationKey, AnthropicStreamReassembler>>,
+    // Track Ollama native (NDJSON) streaming responses
+    ollama_reassemblers: RwLock<HashMap<CorrelationKey, OllamaStreamReassembler>>,
+    // Track Gemini streamGenerateContent responses (SSE or JSON array)
+    gemini_reassemblers: RwLock<HashMap<CorrelationKey, GeminiStreamReassembler>>,
+    // Track Bedrock invoke-with-response-stream responses (AWS event stream)
+    bedrock_reassemblers: RwLock<HashMap<CorrelationKey, BedrockStreamReassembler>>,
+    // Vector store queries awaiting their results
+    pending_retrievals: RwLock<HashMap<CorrelationKey, PendingRetrieval>>,
+    // Parse the TLS ClientHello SNI from the first outbound bytes after connect
+    sni_extraction: bool,
+    // Truncate and hash request message content
+    content_limits: Option<ContentLimits>,
+    // Connects held back until their first outbound TLS record is seen
+    pending_connects: RwLock<HashMap<CorrelationKey, PendingConnect>>,
+    // HTTP/2 connections, keyed per connection (no TID or stream id)
+    h2_connections: RwLock<HashMap<CorrelationKey, H2Connection>>,
+    // Per-connection byte rollups, when network flows are enabled
+    flows: Option<RwLock<FlowTracker>>,
+    // Rate-limited warnings for repeated decode failures
+    failures: FailureLog,
+    // Sensor metrics, where every decode failure is counted
+    metrics: Option<SharedMetrics>,
+    // Classify OpenAI-schema requests to unknown hosts as `openai-compatible`
+    openai_compatible: bool,
+    // Provider names for hosts (`host` or `host:port`), checked before the registries
+    provider_hosts: HashMap<String, String>,
+    // Detection results per host, cleared when the spec bundle is swapped
+    provider_cache: ProviderCache<DetectedProvider>,
+    // Time without a chunk before a started stream is closed
+    stream_timeout: Duration,
+    // Decode responses whose request was never seen
+    attach_existing: bool,
     // Last cleanup time
     last_cleanup: RwLock<Instant>,
+    // Time source for timeouts and cleanup
+    clock: Arc<dyn Clock>,
 }
 
-#[derive(Clone)]
+/// Buffers a response body across SSL reads
+///
+/// Reassemblers stay in their map while buffering and are moved out once
+/// complete, so the body is never copied per read.
 struct ResponseReassembler {
     headers: crate::http::ParsedHttpResponse,
-    body_buffer: Vec<u8>,
+    body_buffer: BytesMut,
     created_at: Instant,
+    last_fed: Instant,
 }
 
 impl ResponseReassembler {
-    fn new(headers: crate::http::ParsedHttpResponse) -> Self {
-        let body_initial = headers.body.clone().unwrap_or_default();
+    fn new(mut headers: crate::http::ParsedHttpResponse, now: Instant) -> Self {
+        let body_initial = headers.body.take().unwrap_or_default();
         Self {
             headers,
-            body_buffer: body_initial,
-            created_at: Instant::now(),
+            body_buffer: BytesMut::from(Bytes::from(body_initial)),
+            created_at: now,
+            last_fed: now,
         }
     }
 
-    fn feed(&mut self, data: &[u8]) {
+    fn feed(&mut self, data: &[u8], now: Instant) {
         self.body_buffer.extend_from_slice(data);
-    }
-
-    /// Try standard gzip decompression using flate2
-    fn try_gzip_decompress(data: &[u8]) -> Option<Vec<u8>> {
-        use flate2::bufread::GzDecoder;
-        use std::io::{BufReader, Read};
-
-        let reader = BufReader::new(data);
-        let mut decoder = GzDecoder::new(reader);
-        let mut decompressed = Vec::new();
-
-        match decoder.read_to_end(&mut decompressed) {
-            Ok(_) if !decompressed.is_empty() => Some(decompressed),
-            Ok(_) => {
-                info!("Gzip decompress returned empty");
-                None
-            }
-            Err(e) => {
-                info!("Gzip decompress failed: {}", e);
-                None
-            }
-        }
-    }
-
-    /// Try decompression by skipping the gzip wrapper and using raw deflate
-    /// Handles streaming gzip with sync-flush markers (00 00 00 ff ff or 00 00 ff ff)
-    fn try_miniz_decompress(data: &[u8]) -> Option<Vec<u8>> {
-        use flate2::Decompress;
-        use flate2::FlushDecompress;
-
-        // Skip gzip header (minimum 10 bytes)
-        if data.len() <= 10 || data[0] != 0x1f || data[1] != 0x8b {
-            return None;
-        }
-
-        let mut header_end = 10;
-        let flags = data[3];
-
-        // Check for extra field (FEXTRA)
-        if flags & 0x04 != 0 && header_end + 2 <= data.len() {
-            let xlen = u16::from_le_bytes([data[header_end], data[header_end + 1]]) as usize;
-            header_end += 2 + xlen;
-        }
-        // Check for filename (FNAME)
-        if flags & 0x08 != 0 {
-            while header_end < data.len() && data[header_end] != 0 {
-                header_end += 1;
-            }
-            header_end += 1;
-        }
-        // Check for comment (FCOMMENT)
-        if flags & 0x10 != 0 {
-            while header_end < data.len() && data[header_end] != 0 {
-                header_end += 1;
-            }
-            header_end += 1;
-        }
-        // Check for header CRC (FHCRC)
-        if flags & 0x02 != 0 {
-            header_end += 2;
-        }
-
-        if header_end >= data.len() {
-            return None;
-        }
-
-        // Check for sync-flush marker at the start of deflate data
-        // Streaming gzip often starts with an empty sync-flush: 00 00 00 ff ff or 00 00 ff ff
-        let mut deflate_start = header_end;
-
-        // Pattern 1: 00 00 00 ff ff (5 bytes - empty stored block with sync flush)
-        if data.len() >= deflate_start + 5
-            && data[deflate_start] == 0x00
-            && data[deflate_start + 1] == 0x00
-            && data[deflate_start + 2] == 0x00
-            && data[deflate_start + 3] == 0xff
-            && data[deflate_start + 4] == 0xff
-        {
-            info!("Found 5-byte sync-flush marker at start, skipping");
-            deflate_start += 5;
-        }
-        // Pattern 2: 00 00 ff ff (4 bytes)
-        else if data.len() >= deflate_start + 4
-            && data[deflate_start] == 0x00
-            && data[deflate_start + 1] == 0x00
-            && data[deflate_start + 2] == 0xff
-            && data[deflate_start + 3] == 0xff
-        {
-            info!("Found 4-byte sync-flush marker at start, skipping");
-            deflate_start += 4;
-        }
-
-        // The deflate data, excluding the 8-byte gzip trailer (if present)
-        let deflate_end = if data.len() >= deflate_start + 8 {
-            data.len() - 8
-        } else {
-            data.len()
-        };
-
-        if deflate_start >= deflate_end {
-            info!("No deflate data after skipping sync-flush markers");
-            return None;
-        }
-
-        let deflate_data = &data[deflate_start..deflate_end];
-
-        info!(
-            "Trying streaming deflate on {} bytes (deflate_start={}, deflate_end={}), first 10 bytes: {:?}",
-            deflate_data.len(),
-            deflate_start,
-            deflate_end,
-            &deflate_data[..std::cmp::min(10, deflate_data.len())]
-        );
-
-        // Use low-level Decompress API which can return partial results
-        let mut decompress = Decompress::new(false); // false = raw deflate (no zlib header)
-        let mut output = vec![0u8; 10 * 1024 * 1024]; // 10MB max
-        let mut total_out = 0;
-        let mut total_in = 0;
-
-        loop {
-            let before_in = decompress.total_in() as usize;
-            let before_out = decompress.total_out() as usize;
-
-            let input = &deflate_data[total_in..];
-            let out_slice = &mut output[total_out..];
-
-            if input.is_empty() || out_slice.is_empty() {
-                break;
-            }
-
-            match decompress.decompress(input, out_slice, FlushDecompress::Sync) {
-                Ok(status) => {
-                    let bytes_in = decompress.total_in() as usize - before_in;
-                    let bytes_out = decompress.total_out() as usize - before_out;
-                    total_in += bytes_in;
-                    total_out += bytes_out;
-
-                    match status {
-                        flate2::Status::Ok => {
-                            // Continue decompressing
-                            if bytes_in == 0 && bytes_out == 0 {
-                                break; // No progress
-                            }
-                        }
-                        flate2::Status::BufError => {
-                            // Need more output space or hit end of input
-                            break;
-                        }
-                        flate2::Status::StreamEnd => {
-                            // Done!
-                            break;
-                        }
-                    }
-                }
-                Err(e) => {
-                    info!(
-                        "Streaming deflate error after {} bytes out: {}",
-                        total_out, e
-                    );
-                    break;
-                }
-            }
-        }
-
-        if total_out > 0 {
-            output.truncate(total_out);
-            info!(
-                "Streaming deflate succeeded: {} in -> {} out",
-                total_in, total_out
-            );
-            Some(output)
-        } else {
-            info!("Streaming deflate produced no output");
-            None
-        }
+        self.last_fed = now;
     }
 
     fn is_complete(&self) -> bool {
@@ -290,7 +191,7 @@ impl ResponseReassembler {
         false
     }
 
-    fn decompress_if_needed(&mut self) {
+    fn decompress_if_needed(&mut self) -> BodyDecoding {
         info!(
             "decompress_if_needed: is_gzipped={}, is_chunked={}, body_buffer_len={}",
             self.headers.is_gzipped,
@@ -308,13 +209,13 @@ impl ResponseReassembler {
                         self.body_buffer.len(),
                         decoded.len()
                     );
-                    decoded
+                    Bytes::from(decoded)
                 } else {
                     info!("Chunked decode FAILED, using raw buffer");
-                    self.body_buffer.clone()
+                    self.body_buffer.split().freeze()
                 }
             } else {
-                self.body_buffer.clone()
+                self.body_buffer.split().freeze()
             };
 
             info!(
@@ -325,35 +226,36 @@ impl ResponseReassembler {
             );
 
             // Try standard gzip decompression first
-            if let Some(decompressed) = Self::try_gzip_decompress(&raw_data) {
+            if let Some(decompressed) = crate::http::gunzip(&raw_data) {
                 info!(
                     "Gzip decompress succeeded: {} -> {} bytes",
                     raw_data.len(),
                     decompressed.len()
                 );
-                self.body_buffer = decompressed;
-                return;
+                self.body_buffer = BytesMut::from(Bytes::from(decompressed));
+                return BodyDecoding::Intact;
             }
 
             // Try using miniz_oxide directly with lenient parsing for truncated/incomplete streams
             if raw_data.len() > 10 && raw_data[0] == 0x1f && raw_data[1] == 0x8b {
-                if let Some(decompressed) = Self::try_miniz_decompress(&raw_data) {
+                if let Some(decompressed) = crate::http::gunzip_lenient(&raw_data) {
                     info!(
                         "Miniz decompress succeeded: {} -> {} bytes",
                         raw_data.len(),
                         decompressed.len()
                     );
-                    self.body_buffer = decompressed;
-                    return;
+                    self.body_buffer = BytesMut::from(Bytes::from(decompressed));
+                    return BodyDecoding::Lenient;
                 }
             }
 
             info!("All decompression methods failed, using raw data");
-            self.body_buffer = raw_data;
+            self.body_buffer = BytesMut::from(raw_data);
+            return BodyDecoding::Failed;
         } else if self.headers.is_chunked {
             // Not gzipped, but still chunked - need to decode chunks
             if let Some(decoded) = crate::http::decode_chunked_body(&self.body_buffer) {
-                self.body_buffer = decoded;
+                self.body_buffer = BytesMut::from(Bytes::from(decoded));
             }
         }
 
@@ -364,24 +266,50 @@ impl ResponseReassembler {
                 &self.body_buffer[..std::cmp::min(100, self.body_buffer.len())]
             )
         );
+
+        BodyDecoding::Intact
     }
 }
 
-#[derive(Clone)]
+/// Outcome of decoding a response body's transfer/content encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyDecoding {
+    /// Body decoded cleanly (or needed no decoding)
+    Intact,
+    /// Decompressed by the lenient fallback, so the stream was likely cut short
+    Lenient,
+    /// Decompression failed and the raw bytes were kept
+    Failed,
+}
+
 struct RequestReassembler {
-    buffer: Vec<u8>,
+    buffer: BytesMut,
     expected_body_len: Option<usize>,
     header_len: Option<usize>,
+    /// Body uses `Transfer-Encoding: chunked` (takes precedence over Content-Length)
+    is_chunked: bool,
+    /// Client sent `Expect: 100-continue`, so the body follows the headers
+    /// only after the server's `100 Continue`
+    expects_continue: bool,
+    /// Parser a `multipart/form-data` body is fed to instead of the buffer,
+    /// so file uploads are not held in memory
+    multipart: Option<MultipartParser>,
+    /// Body bytes fed to `multipart`
+    multipart_len: usize,
     created_at: Instant,
 }
 
 impl RequestReassembler {
-    fn new(data: &[u8]) -> Self {
+    fn new(data: &[u8], now: Instant) -> Self {
         let mut reassembler = Self {
-            buffer: data.to_vec(),
+            buffer: BytesMut::from(data),
             expected_body_len: None,
             header_len: None,
-            created_at: Instant::now(),
+            is_chunked: false,
+            expects_continue: false,
+            multipart: None,
+            multipart_len: 0,
+            created_at: now,
         };
         reassembler.try_parse_headers();
         reassembler
@@ -392,28 +320,66 @@ impl RequestReassembler {
         let mut req = httparse::Request::new(&mut headers);
         if let Ok(httparse::Status::Complete(header_len)) = req.parse(&self.buffer) {
             self.header_len = Some(header_len);
+            let mut boundary = None;
             for header in req.headers.iter() {
-                if header.name.to_lowercase() == "content-length" {
+                if header.name.eq_ignore_ascii_case("content-length") {
                     if let Ok(val_str) = std::str::from_utf8(header.value) {
                         if let Ok(len) = val_str.trim().parse::<usize>() {
                             self.expected_body_len = Some(len);
                         }
                     }
+                } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+                    self.is_chunked = String::from_utf8_lossy(header.value)
+                        .to_lowercase()
+                        .contains("chunked");
+                } else if header.name.eq_ignore_ascii_case("content-type") {
+                    boundary = multipart_boundary(&String::from_utf8_lossy(header.value));
+                } else if header.name.eq_ignore_ascii_case("expect") {
+                    self.expects_continue = header.value.eq_ignore_ascii_case(b"100-continue");
                 }
             }
+
+            // Chunked multipart bodies are rare; they are buffered and parsed whole
+            if let (Some(boundary), false) = (boundary, self.is_chunked) {
+                let mut parser = MultipartParser::new(&boundary);
+                parser.feed(&self.buffer[header_len..]);
+                self.multipart_len = self.buffer.len() - header_len;
+                self.buffer.truncate(header_len);
+                self.multipart = Some(parser);
+            }
         }
     }
 
     fn feed(&mut self, data: &[u8]) {
+        if let Some(parser) = &mut self.multipart {
+            parser.feed(data);
+            self.multipart_len += data.len();
+            return;
+        }
         self.buffer.extend_from_slice(data);
         if self.header_len.is_none() {
             self.try_parse_headers();
         }
     }
 
+    /// Body bytes received so far
+    fn body_len(&self, header_len: usize) -> usize {
+        if self.multipart.is_some() {
+            self.multipart_len
+        } else {
+            self.buffer.len() - header_len
+        }
+    }
+
     fn is_complete(&self) -> bool {
         match (self.header_len, self.expected_body_len) {
-            (Some(h_len), Some(b_len)) => self.buffer.len() >= h_len + b_len,
+            (Some(h_len), _) if self.is_chunked => {
+                crate::http::is_chunked_body_complete(&self.buffer[h_len..])
+            }
+            (Some(h_len), Some(b_len)) => self.body_len(h_len) >= b_len,
+            // Headers alone are not the request when the body is still to
+            // come after `100 Continue`
+            (Some(h_len), None) if self.expects_continue => self.buffer.len() > h_len,
             (Some(h_len), None) => {
                 // If no content-length, assume complete if headers end with \r\n\r\n
                 // (Though for POST this usually means no body)
@@ -432,6 +398,8 @@ struct CorrelationKey {
     tid: Option<u32>,
     /// File descriptor if available (for multiple connections per process)
     fd: Option<i32>,
+    /// HTTP/2 stream id (for multiplexed requests on one connection)
+    stream_id: Option<u32>,
 }
 
 impl CorrelationKey {
@@ -440,6 +408,7 @@ impl CorrelationKey {
             pid: raw.pid,
             tid: raw.tid,
             fd: raw.metadata.fd,
+            stream_id: None,
         }
     }
 
@@ -449,84 +418,625 @@ impl CorrelationKey {
             pid: self.pid,
             tid: None,
             fd: self.fd,
+            stream_id: self.stream_id,
+        }
+    }
+
+    /// Key for one HTTP/2 stream on this connection
+    ///
+    /// Streams are not tied to a thread, so the TID is dropped.
+    fn with_stream(&self, stream_id: u32) -> Self {
+        Self {
+            pid: self.pid,
+            tid: None,
+            fd: self.fd,
+            stream_id: Some(stream_id),
         }
     }
 }
 
+/// Frame reassembly state for both directions of an HTTP/2 connection
+///
+/// Each direction has its own HPACK dynamic table.
+struct H2Connection {
+    outbound: H2FrameReassembler,
+    inbound: H2FrameReassembler,
+    last_seen: Instant,
+}
+
+/// A vector store query awaiting its response
+struct PendingRetrieval {
+    query: VectorQuery,
+    /// Envelope built from the query, emitted as-is if no response arrives
+    envelope: EventEnvelope,
+    /// Clock of the query's capture timestamp, copied into `envelope.ts_mono`
+    clock: CaptureClock,
+    created_at: Instant,
+}
+
 #[derive(Clone)]
 struct PendingRequest {
     request_id: String,
     request_data: AiRequestData,
     timestamp: chrono::DateTime<chrono::Utc>,
+    /// Capture timestamp of the request (`RawCaptureEvent::timestamp_ns`)
+    ts_mono: Option<u64>,
     #[allow(dead_code)]
     created_at: Instant,
     provider: Provider,
+    /// How the request's provider was identified
+    detection: ProviderDetection,
     is_streaming: bool,
+    /// When bytes of a streamed response were last seen; once set, the
+    /// request times out on stream idleness instead of its age
+    stream_activity: Option<Instant>,
+    /// Request went to Ollama's native API, so the response is Ollama JSON/NDJSON
+    ollama_native: bool,
+    /// Request was a Gemini `generateContent` call, so the response is a
+    /// `GenerateContentResponse` or a stream of them
+    gemini: bool,
+    /// Request was a Bedrock InvokeModel call, so the response is the
+    /// model's own body or an AWS event stream
+    bedrock: bool,
     #[allow(dead_code)]
     host: Option<String>,
     /// Web context (Origin, Referer, User-Agent) for browser-originated requests
     web_context: Option<WebContext>,
+    /// Process and actor of the request, for a response synthesized on timeout
+    process: Option<ProcessInfo>,
+    actor: Option<Actor>,
+}
+
+impl PendingRequest {
+    /// Milliseconds from this request to `response`
+    ///
+    /// Uses the capture timestamps when both sides have one, so latency
+    /// reflects when the bytes were seen rather than when they were decoded
+    /// and is immune to wall-clock steps. Skew is recorded on `response`.
+    fn latency_ms(&self, response: &mut EventEnvelope) -> u64 {
+        response.elapsed_since(&EventTime {
+            ts: self.timestamp,
+            ts_mono: self.ts_mono,
+        })
+    }
+}
+
+/// A `network.connect` waiting for the connection's ClientHello
+struct PendingConnect {
+    raw: RawCaptureEvent,
+    buffer: Vec<u8>,
+    created_at: Instant,
+}
+
+/// Decode-time signals that determine an event's [`Confidence`]
+#[derive(Debug, Clone, Default)]
+struct DecodeSignals {
+    /// Body ended before the payload was complete (missing stream terminator,
+    /// lenient decompression)
+    truncated: bool,
+    /// Request and response were paired via a fallback correlation key
+    fallback_correlation: bool,
+    /// Compressed body could not be decompressed
+    decompression_failed: bool,
+    /// Provider was confirmed by the body as well as the endpoint
+    provider_confirmed_by_body: bool,
+    /// Body points to a different provider than the endpoint
+    provider_mismatch: bool,
+    /// How the request's provider was identified
+    detection: ProviderDetection,
+    /// No response completed before the request timed out
+    timed_out: bool,
+    /// Response seen without its request
+    orphan: bool,
+}
+
+/// How a request's provider was identified
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ProviderDetection {
+    /// The host is in a provider registry
+    #[default]
+    KnownEndpoint,
+    /// The host is mapped to a provider in the configuration
+    ConfiguredHost,
+    /// Unknown host, but the body matches the OpenAI request schema
+    RequestSchema,
+    /// No request was seen; the response body names the provider
+    ResponseBody,
+}
+
+/// Provider a request's host resolved to
+#[derive(Debug, Clone)]
+struct DetectedProvider {
+    /// Provider name reported in events
+    id: String,
+    /// Provider enum for parsing
+    provider: Provider,
+    detection: ProviderDetection,
+}
+
+/// Provider name for requests identified by [`ProviderDetection::RequestSchema`]
+const OPENAI_COMPATIBLE: &str = "openai-compatible";
+
+/// Parser to use for a configured or schema-detected provider name
+fn provider_from_name(name: &str) -> Provider {
+    match name {
+        "openai" => Provider::OpenAI,
+        "azureopenai" => Provider::AzureOpenAI,
+        "anthropic" => Provider::Anthropic,
+        "ollama" => Provider::Ollama,
+        "lmstudio" => Provider::LmStudio,
+        "vllm" => Provider::Vllm,
+        _ => Provider::OpenAICompatible,
+    }
+}
+
+impl DecodeSignals {
+    fn confidence(&self) -> Confidence {
+        let mut level = ConfidenceLevel::High;
+        let mut completeness = Completeness::Full;
+        let mut reasons = vec!["tls_boundary_capture".to_string()];
+
+        if self.truncated {
+            level = ConfidenceLevel::Medium;
+            completeness = Completeness::Partial;
+            reasons.push("truncated_body".to_string());
+        }
+
+        if self.fallback_correlation {
+            level = ConfidenceLevel::Medium;
+            completeness = Completeness::Partial;
+            reasons.push("fallback_correlation".to_string());
+        }
+
+        if self.provider_mismatch {
+            level = ConfidenceLevel::Medium;
+            reasons.push("provider_mismatch".to_string());
+        }
+
+        if self.detection == ProviderDetection::RequestSchema {
+            level = ConfidenceLevel::Medium;
+            reasons.push("schema_heuristic".to_string());
+        }
+
+        if self.timed_out {
+            level = ConfidenceLevel::Low;
+            completeness = Completeness::Partial;
+            reasons.push("response_timeout".to_string());
+        }
+
+        if self.decompression_failed {
+            level = ConfidenceLevel::Low;
+            completeness = Completeness::Partial;
+            reasons.push("decompression_failed".to_string());
+        }
+
+        if self.orphan {
+            level = ConfidenceLevel::Low;
+            completeness = Completeness::Partial;
+            reasons.push("orphan_response".to_string());
+        }
+
+        let ai_detection_method = match self.detection {
+            ProviderDetection::RequestSchema => "request_schema",
+            ProviderDetection::ResponseBody => "response_body",
+            ProviderDetection::ConfiguredHost => "configured_host",
+            ProviderDetection::KnownEndpoint if self.provider_confirmed_by_body => {
+                "known_endpoint+body"
+            }
+            ProviderDetection::KnownEndpoint => "known_endpoint",
+        };
+
+        Confidence {
+            level,
+            completeness,
+            reasons,
+            content_source: Some("tls_boundary".to_string()),
+            ai_detection_method: Some(ai_detection_method.to_string()),
+        }
+    }
+
+    /// Record how the response body's provider hints compare with the request's
+    fn check_provider(&mut self, expected: Provider, body: &serde_json::Value) {
+        match detect_provider_from_body(body) {
+            Some(p) if p == expected => self.provider_confirmed_by_body = true,
+            // Local runtimes, proxies and Bedrock serve models from many vendors
+            Some(_)
+                if !expected.is_local()
+                    && !matches!(
+                        expected,
+                        Provider::Unknown | Provider::OpenAICompatible | Provider::AwsBedrock
+                    ) =>
+            {
+                self.provider_mismatch = true
+            }
+            _ => {}
+        }
+    }
 }
 
 impl HttpDecoder {
     /// Create a new decoder with default spec bundle
     pub fn new() -> Self {
-        let spec_loader = SpecLoader::new();
+        Self::with_spec_loader(&SpecLoader::new())
+    }
+
+    /// Create a decoder with a specific spec loader (for testing or custom bundles)
+    ///
+    /// If the loader has no providers (the bundle failed to load), detection
+    /// falls back to the built-in provider set, which covers the major
+    /// providers.
+    pub fn with_spec_loader(spec_loader: &SpecLoader) -> Self {
         let spec_registry = Arc::new(DynamicProviderRegistry::new(spec_loader.bundle()));
-        let provider_count = spec_registry.provider_ids().len();
-        info!(
-            "Initialized HttpDecoder with {} providers from spec bundle",
-            provider_count
-        );
+        let legacy_registry = ProviderRegistry::new();
+        if spec_loader.is_degraded() {
+            warn!(
+                "Spec bundle unavailable; HttpDecoder using {} built-in providers only",
+                legacy_registry.provider_count()
+            );
+        } else {
+            info!(
+                "Initialized HttpDecoder with {} providers from spec bundle",
+                spec_registry.provider_ids().len()
+            );
+        }
 
         Self {
-            spec_registry,
-            legacy_registry: ProviderRegistry::new(),
+            spec_registry: RwLock::new(spec_registry),
+            legacy_registry,
             partial_requests: RwLock::new(HashMap::new()),
             partial_responses: RwLock::new(HashMap::new()),
             pending_requests: RwLock::new(HashMap::new()),
             stream_reassemblers: RwLock::new(HashMap::new()),
             anthropic_reassemblers: RwLock::new(HashMap::new()),
-            last_cleanup: RwLock::new(Instant::now()),
+            ollama_reassemblers: RwLock::new(HashMap::new()),
+            gemini_reassemblers: RwLock::new(HashMap::new()),
+            bedrock_reassemblers: RwLock::new(HashMap::new()),
+            pending_retrievals: RwLock::new(HashMap::new()),
+            sni_extraction: false,
+            content_limits: None,
+            pending_connects: RwLock::new(HashMap::new()),
+            h2_connections: RwLock::new(HashMap::new()),
+            flows: None,
+            failures: FailureLog::default(),
+            metrics: None,
+            openai_compatible: true,
+            provider_hosts: HashMap::new(),
+            provider_cache: ProviderCache::new(DEFAULT_PROVIDER_CACHE_CAPACITY),
+            stream_timeout: DEFAULT_STREAM_TIMEOUT,
+            attach_existing: false,
+            last_cleanup: RwLock::new(SystemClock.now()),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Create a decoder with a specific spec loader (for testing or custom bundles)
-    pub fn with_spec_loader(spec_loader: &SpecLoader) -> Self {
-        let spec_registry = Arc::new(DynamicProviderRegistry::new(spec_loader.bundle()));
-        let provider_count = spec_registry.provider_ids().len();
+    /// Number of providers the decoder can detect
+    ///
+    /// The spec bundle's providers, or the built-in set when the bundle
+    /// could not be loaded.
+    pub fn provider_count(&self) -> usize {
+        self.spec_registry()
+            .provider_ids()
+            .len()
+            .max(self.legacy_registry.provider_count())
+    }
+
+    /// Enable SNI extraction for connections we cannot decrypt
+    ///
+    /// When enabled, `network.connect` events are held until the first
+    /// outbound bytes on the socket (`NetworkSend`) arrive, and the TLS
+    /// ClientHello SNI is attached to the event and used for provider
+    /// detection. A connect whose first bytes don't arrive within
+    /// [`SNI_HOLD_TIMEOUT`], or still held when the pipeline stops, is
+    /// reported without the SNI (see [`DecodePlugin::tick`]).
+    ///
+    /// Requires a capture backend that reports `NetworkSend` (see
+    /// `CapturePlugin::reports_network_send`); none in this repository does
+    /// yet, so the sensor leaves this off whatever the config says.
+    pub fn with_sni_extraction(mut self, enabled: bool) -> Self {
+        self.sni_extraction = enabled;
+        self
+    }
+
+    /// Close a streamed response after this long without a chunk
+    ///
+    /// The stream's request gets an incomplete `ai.response` with what was
+    /// reassembled. A stream that keeps sending is never closed, however
+    /// long it runs; until its first bytes arrive, the request times out
+    /// like any other.
+    pub fn with_stream_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_timeout = timeout;
+        self
+    }
+
+    /// Decode responses whose request was never seen (default: off)
+    ///
+    /// A process attached mid-connection (`--attach-existing`) answers
+    /// requests sent before capture started. When enabled, such a response
+    /// becomes a low-confidence `ai.response` with reason `orphan_response`
+    /// instead of being dropped. The provider comes from the connection's
+    /// destination, else from the response body. Streamed responses are not
+    /// reconstructed without their request.
+    pub fn with_attach_existing(mut self, enabled: bool) -> Self {
+        self.attach_existing = enabled;
+        self
+    }
+
+    /// Emit a `network.flow` event per connection when it ends
+    ///
+    /// Connects, SSL reads and SSL writes are rolled up per (pid, remote
+    /// address:port) with plaintext byte counts and first/last timestamps.
+    /// A flow ends when its socket is closed or after `idle_timeout` without
+    /// traffic; idle flows are noticed on the next captured event. See
+    /// [`FlowTracker`].
+    pub fn with_network_flows(mut self, idle_timeout: Duration) -> Self {
+        self.flows = Some(RwLock::new(FlowTracker::new(idle_timeout)));
+        self
+    }
+
+    /// Count decode failures in the sensor metrics
+    pub fn with_metrics(mut self, metrics: SharedMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Classify requests to unknown hosts by their body (default: on)
+    ///
+    /// Self-hosted servers (vLLM, LM Studio, TGI, LiteLLM) expose the OpenAI
+    /// API on whatever host they run on. When enabled, a request to a host
+    /// no registry knows is decoded if it matches the OpenAI chat
+    /// completions, completions or embeddings schema (see
+    /// [`is_openai_compatible_request`]), with provider `openai-compatible`.
+    pub fn with_openai_compatible(mut self, enabled: bool) -> Self {
+        self.openai_compatible = enabled;
+        self
+    }
+
+    /// Attribute traffic to specific hosts to named providers
+    ///
+    /// Keys are a host (`gpu-box.internal`) or host and port
+    /// (`gpu-box.internal:8000`); the more specific key wins. Mapped hosts
+    /// take precedence over the provider registries. Known names (`vllm`,
+    /// `lmstudio`, `ollama`, `openai`, `anthropic`, `azureopenai`) select
+    /// that provider's parser; any other name is parsed as OpenAI-compatible
+    /// and reported as given.
+    pub fn with_provider_hosts(mut self, hosts: HashMap<String, String>) -> Self {
+        self.provider_hosts = hosts
+            .into_iter()
+            .map(|(host, name)| (host.to_ascii_lowercase(), name.to_ascii_lowercase()))
+            .collect();
+        self
+    }
+
+    /// Number of hosts whose provider detection result is cached
+    ///
+    /// 0 disables the cache. Defaults to 1024.
+    pub fn with_provider_cache_capacity(mut self, capacity: usize) -> Self {
+        self.provider_cache = ProviderCache::new(capacity);
+        self
+    }
+
+    /// Replace the spec bundle used for provider detection
+    ///
+    /// Cached detection results came from the old bundle and are dropped.
+    pub fn set_spec_bundle(&self, bundle: Arc<OispSpecBundle>) {
+        let registry = Arc::new(DynamicProviderRegistry::new(bundle));
         info!(
-            "Initialized HttpDecoder with {} providers from custom spec bundle",
-            provider_count
+            "Spec bundle swapped; HttpDecoder now has {} providers",
+            registry.provider_ids().len()
         );
+        *self.spec_registry.write().unwrap() = registry;
+        self.provider_cache.clear();
+    }
 
-        Self {
-            spec_registry,
-            legacy_registry: ProviderRegistry::new(),
-            partial_requests: RwLock::new(HashMap::new()),
-            partial_responses: RwLock::new(HashMap::new()),
-            pending_requests: RwLock::new(HashMap::new()),
-            stream_reassemblers: RwLock::new(HashMap::new()),
-            anthropic_reassemblers: RwLock::new(HashMap::new()),
-            last_cleanup: RwLock::new(Instant::now()),
+    fn spec_registry(&self) -> Arc<DynamicProviderRegistry> {
+        self.spec_registry.read().unwrap().clone()
+    }
+
+    /// Provider for a request's host, or `None` if it is not an AI provider
+    ///
+    /// Hosts mapped in the configuration come first, then spec-driven
+    /// detection (95+ providers from spec bundle), then the built-in
+    /// registry. Unknown hosts are left to the request schema check when
+    /// OpenAI-compatible detection is enabled.
+    fn detect_provider(&self, domain: &str, shape: BodyShape) -> Option<DetectedProvider> {
+        self.provider_cache.get_or_detect(domain, shape, || {
+            if let Some(name) = self.configured_provider(domain) {
+                debug!("Configured provider '{}' for domain {}", name, domain);
+                return Some(DetectedProvider {
+                    id: name.to_string(),
+                    provider: provider_from_name(name),
+                    detection: ProviderDetection::ConfiguredHost,
+                });
+            }
+
+            let legacy = self.legacy_registry.detect_from_domain(domain);
+            if let Some(id) = self.spec_registry().detect_from_domain(domain) {
+                debug!(
+                    "Spec registry detected provider '{}' for domain {}",
+                    id, domain
+                );
+                // Provider enum for existing code paths (backward compatibility)
+                let provider = match legacy {
+                    Some(p) => p,
+                    None if id == "ollama" => Provider::Ollama,
+                    None => Provider::Unknown,
+                };
+                return Some(DetectedProvider {
+                    id: id.to_string(),
+                    provider,
+                    detection: ProviderDetection::KnownEndpoint,
+                });
+            }
+
+            match legacy {
+                Some(p) => {
+                    debug!(
+                        "Legacy registry detected provider {:?} for domain {}",
+                        p, domain
+                    );
+                    Some(DetectedProvider {
+                        id: format!("{:?}", p).to_lowercase(),
+                        provider: p,
+                        detection: ProviderDetection::KnownEndpoint,
+                    })
+                }
+                None if self.openai_compatible && shape == BodyShape::Json => {
+                    // Decoded only if the body matches the schema
+                    Some(DetectedProvider {
+                        id: OPENAI_COMPATIBLE.to_string(),
+                        provider: provider_from_name(OPENAI_COMPATIBLE),
+                        detection: ProviderDetection::RequestSchema,
+                    })
+                }
+                None => None,
+            }
+        })
+    }
+
+    /// Provider name configured for a `host[:port]`
+    fn configured_provider(&self, host: &str) -> Option<&str> {
+        let host = host.to_ascii_lowercase();
+        let bare = host.rsplit_once(':').map_or(host.as_str(), |(h, _)| h);
+        self.provider_hosts
+            .get(&host)
+            .or_else(|| self.provider_hosts.get(bare))
+            .map(String::as_str)
+    }
+
+    /// Count a decode failure and log it, rate-limited per reason and provider
+    fn decode_failed(
+        &self,
+        failure: DecodeFailure,
+        provider: &str,
+        detail: &dyn std::fmt::Display,
+    ) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_decode_failure(failure.as_str(), provider);
         }
+        self.failures
+            .record(failure, provider, detail, self.clock.now());
+    }
+
+    /// Use a different time source for timeouts and cleanup
+    ///
+    /// Defaults to [`SystemClock`]; tests pass a
+    /// [`MockClock`](crate::clock::MockClock) to expire state without sleeping.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        *self.last_cleanup.get_mut().unwrap() = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Truncate request message content and keep a hash of each message
+    ///
+    /// See [`ContentLimits`] for how hashing interacts with redaction.
+    pub fn with_content_limits(mut self, limits: ContentLimits) -> Self {
+        self.content_limits = Some(limits);
+        self
     }
 
     /// Cleanup stale pending requests periodically
-    fn maybe_cleanup(&self) {
+    /// Replace a model alias with its canonical ID and family from the spec
+    /// bundle, keeping the ID the application sent in `model.version`
+    fn normalize_model(&self, event: &mut OispEvent) {
+        let (provider, model) = match event {
+            OispEvent::AiRequest(e) => (&e.data.provider, &mut e.data.model),
+            OispEvent::AiResponse(e) => (&e.data.provider, &mut e.data.model),
+            _ => return,
+        };
+        let (Some(provider), Some(model)) = (provider, model) else {
+            return;
+        };
+        let Some(normalized) = self
+            .spec_registry()
+            .normalize_model(&provider.name, &model.id)
+        else {
+            return;
+        };
+
+        if normalized.id != model.id {
+            let raw = std::mem::replace(&mut model.id, normalized.id);
+            model.version.get_or_insert(raw);
+        }
+        if normalized.family.is_some() {
+            model.family = normalized.family;
+        }
+    }
+
+    /// Sweep stale state if the cleanup interval has passed
+    ///
+    /// Returns an incomplete `ai.response` for each request that timed out.
+    /// Outside decoding a raw event, `mono` converts the capture timestamps
+    /// the pipeline would otherwise convert.
+    fn maybe_cleanup(&self, mono: Option<&MonoClock>) -> Vec<OispEvent> {
+        let now = self.clock.now();
         let should_cleanup = {
             let last = self.last_cleanup.read().unwrap();
-            last.elapsed() > Duration::from_secs(60) // Cleanup every minute
+            now.duration_since(*last) > CLEANUP_INTERVAL
         };
 
-        if should_cleanup {
-            self.cleanup_stale_requests();
-            *self.last_cleanup.write().unwrap() = Instant::now();
+        if !should_cleanup {
+            return Vec::new();
+        }
+        let events = self.cleanup_stale_requests(mono);
+        *self.last_cleanup.write().unwrap() = now;
+        events
+    }
+
+    /// Report requests and retrievals still waiting for a response as
+    /// incomplete: those that timed out, or all of them with `all`
+    fn expire_pending(&self, all: bool, mono: Option<&MonoClock>) -> Vec<OispEvent> {
+        let now = self.clock.now();
+
+        // Requests whose response never completed get a response marking
+        // them incomplete, with whatever part of a stream was reassembled.
+        // Runs before the partial responses are swept, which may hold it.
+        let expired: Vec<(CorrelationKey, PendingRequest)> = {
+            let mut pending = self.pending_requests.write().unwrap();
+            let keys: Vec<CorrelationKey> = pending
+                .iter()
+                .filter(|(_, req)| all || self.is_stale(req, now))
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.into_iter()
+                .filter_map(|key| pending.remove(&key).map(|req| (key, req)))
+                .collect()
+        };
+        if !expired.is_empty() {
+            debug!("Expired {} pending requests", expired.len());
         }
+        let mut events: Vec<OispEvent> = expired
+            .iter()
+            .map(|(key, req)| self.incomplete_response(key, req, all))
+            .collect();
+
+        // Retrievals whose results never arrived are still reported
+        let mut retrievals = self.pending_retrievals.write().unwrap();
+        let keys: Vec<CorrelationKey> = retrievals
+            .iter()
+            .filter(|(_, r)| all || now.duration_since(r.created_at) >= PENDING_REQUEST_TIMEOUT)
+            .map(|(key, _)| key.clone())
+            .collect();
+        events.extend(
+            keys.iter()
+                .filter_map(|key| retrievals.remove(key))
+                .map(|pending| {
+                    let clock = pending.clock;
+                    let mut event = rag_retrieve_event(pending, None, None);
+                    if let Some(mono) = mono {
+                        let envelope = event.envelope_mut();
+                        envelope.ts_mono =
+                            envelope.ts_mono.and_then(|ts| mono.from_capture(ts, clock));
+                    }
+                    event
+                }),
+        );
+        events
     }
 
-    fn cleanup_stale_requests(&self) {
-        let now = Instant::now();
+    fn cleanup_stale_requests(&self, mono: Option<&MonoClock>) -> Vec<OispEvent> {
+        let now = self.clock.now();
+        let events = self.expire_pending(false, mono);
 
         // Cleanup partial requests
         {
@@ -537,18 +1047,13 @@ impl HttpDecoder {
         // Cleanup partial responses
         {
             let mut partial = self.partial_responses.write().unwrap();
-            partial.retain(|_, resp| now.duration_since(resp.created_at) < PENDING_REQUEST_TIMEOUT);
-        }
-
-        // Cleanup pending requests
-        {
-            let mut pending = self.pending_requests.write().unwrap();
-            let before = pending.len();
-            pending.retain(|_, req| now.duration_since(req.created_at) < PENDING_REQUEST_TIMEOUT);
-            let removed = before - pending.len();
-            if removed > 0 {
-                debug!("Cleaned up {} stale pending requests", removed);
-            }
+            partial.retain(|_, resp| {
+                if resp.headers.is_streaming {
+                    now.duration_since(resp.last_fed) < self.stream_timeout
+                } else {
+                    now.duration_since(resp.created_at) < PENDING_REQUEST_TIMEOUT
+                }
+            });
         }
 
         // Cleanup stream reassemblers (keep for 5 minutes)
@@ -573,142 +1078,469 @@ impl HttpDecoder {
                 reassemblers.clear();
             }
         }
-    }
 
-    fn decode_ssl_write(&self, raw: &RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
-        self.maybe_cleanup();
-        let mut events = Vec::new();
+        {
+            let mut connections = self.h2_connections.write().unwrap();
+            connections.retain(|_, c| now.duration_since(c.last_seen) < PENDING_REQUEST_TIMEOUT);
+        }
 
-        let key = CorrelationKey::from_event(raw);
+        // Backstop for a decoder used without the pipeline's ticks, which
+        // report held connects long before this
+        {
+            let mut connects = self.pending_connects.write().unwrap();
+            connects.retain(|_, c| now.duration_since(c.created_at) < PENDING_REQUEST_TIMEOUT);
+        }
 
-        // Check if we have an existing partial request for this connection
-        let is_new_request = is_http_request(&raw.data);
-        let reassembler_opt = {
-            let mut partial = self.partial_requests.write().unwrap();
-            if is_new_request {
-                // New request starting - replace any old one for this key
-                let reassembler = RequestReassembler::new(&raw.data);
-                partial.insert(key.clone(), reassembler);
-                partial.get(&key).cloned()
-            } else {
-                // Not a new request, see if it's a continuation of a partial one
-                if let Some(reassembler) = partial.get_mut(&key) {
-                    reassembler.feed(&raw.data);
-                    Some(reassembler.clone())
+        {
+            let mut reassemblers = self.ollama_reassemblers.write().unwrap();
+            if reassemblers.len() > MAX_PENDING_REQUESTS {
+                warn!(
+                    "Too many Ollama reassemblers ({}), clearing oldest",
+                    reassemblers.len()
+                );
+                reassemblers.clear();
+            }
+        }
+
+        {
+            let mut reassemblers = self.gemini_reassemblers.write().unwrap();
+            if reassemblers.len() > MAX_PENDING_REQUESTS {
+                warn!(
+                    "Too many Gemini reassemblers ({}), clearing oldest",
+                    reassemblers.len()
+                );
+                reassemblers.clear();
+            }
+        }
+
+        {
+            let mut reassemblers = self.bedrock_reassemblers.write().unwrap();
+            if reassemblers.len() > MAX_PENDING_REQUESTS {
+                warn!(
+                    "Too many Bedrock reassemblers ({}), clearing oldest",
+                    reassemblers.len()
+                );
+                reassemblers.clear();
+            }
+        }
+
+        events
+    }
+
+    /// Whether a pending request has waited too long for its response
+    fn is_stale(&self, req: &PendingRequest, now: Instant) -> bool {
+        match req.stream_activity {
+            Some(last) => now.duration_since(last) >= self.stream_timeout,
+            None => now.duration_since(req.created_at) >= PENDING_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Note response bytes for a request, restarting its stream idle timer
+    fn note_stream_activity(&self, key: &CorrelationKey, streaming_response: bool) {
+        let now = self.clock.now();
+        let mut pending = self.pending_requests.write().unwrap();
+        let key = if pending.contains_key(key) {
+            key.clone()
+        } else {
+            key.without_tid()
+        };
+        if let Some(req) = pending.get_mut(&key) {
+            if req.is_streaming || streaming_response {
+                req.stream_activity = Some(now);
+            }
+        }
+    }
+
+    /// Synthetic `ai.response` for a request that timed out without one
+    ///
+    /// The response is unsuccessful with finish reason `incomplete` and low
+    /// confidence, and carries the content and usage a stream reassembler
+    /// collected for the request, if any. The `ai.request` itself was
+    /// emitted when the request was decoded. `shutdown` marks a request cut
+    /// short by the sensor stopping rather than by a timeout.
+    fn incomplete_response(
+        &self,
+        key: &CorrelationKey,
+        pending: &PendingRequest,
+        shutdown: bool,
+    ) -> OispEvent {
+        // A stream whose body is still being reassembled has not reached the
+        // stream reassemblers yet
+        let buffered = self.partial_responses.write().unwrap().remove(key);
+        if let Some(mut buffered) = buffered {
+            if pending.is_streaming || buffered.headers.is_streaming {
+                buffered.decompress_if_needed();
+                let body = &buffered.body_buffer;
+                if pending.ollama_native {
+                    let mut reassemblers = self.ollama_reassemblers.write().unwrap();
+                    reassemblers.entry(key.clone()).or_default().feed(body);
+                } else if pending.gemini {
+                    let mut reassemblers = self.gemini_reassemblers.write().unwrap();
+                    reassemblers.entry(key.clone()).or_default().feed(body);
+                } else if pending.bedrock {
+                    let mut reassemblers = self.bedrock_reassemblers.write().unwrap();
+                    reassemblers.entry(key.clone()).or_default().feed(body);
+                } else if pending.provider == Provider::Anthropic {
+                    let mut reassemblers = self.anthropic_reassemblers.write().unwrap();
+                    reassemblers.entry(key.clone()).or_default().feed(body);
                 } else {
-                    None
+                    let mut reassemblers = self.stream_reassemblers.write().unwrap();
+                    reassemblers.entry(key.clone()).or_default().feed(body);
                 }
             }
+        }
+
+        let partial = if let Some(r) = self.stream_reassemblers.write().unwrap().remove(key) {
+            Some((r.content().to_string(), r.usage()))
+        } else if let Some(r) = self.anthropic_reassemblers.write().unwrap().remove(key) {
+            Some((r.content().to_string(), r.usage()))
+        } else if let Some(r) = self.ollama_reassemblers.write().unwrap().remove(key) {
+            Some((r.content().to_string(), r.usage()))
+        } else if let Some(r) = self.gemini_reassemblers.write().unwrap().remove(key) {
+            Some((r.content().to_string(), r.usage()))
+        } else {
+            self.bedrock_reassemblers
+                .write()
+                .unwrap()
+                .remove(key)
+                .map(|r| (r.content().to_string(), r.usage()))
         };
 
-        let reassembler = match reassembler_opt {
-            Some(r) => r,
-            None => {
-                info!("SSL write is not HTTP request and no partial request found, skipping (data starts with: {:?})", 
-                    String::from_utf8_lossy(&raw.data[..std::cmp::min(raw.data.len(), 20)]));
-                return Ok(events);
+        let signals = DecodeSignals {
+            timed_out: true,
+            detection: pending.detection,
+            ..Default::default()
+        };
+        let mut envelope = EventEnvelope::new("ai.response");
+        envelope.process = pending.process.clone();
+        envelope.actor = pending.actor.clone();
+        envelope.web_context = pending.web_context.clone();
+        envelope.source = Source {
+            collector: "oisp-sensor".to_string(),
+            collector_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            capture_method: Some(CaptureMethod::TlsBoundary),
+            ..Default::default()
+        };
+        envelope.confidence = signals.confidence();
+
+        let (choices, usage) = match partial {
+            Some((content, (input_tokens, output_tokens))) => {
+                let usage = (input_tokens.is_some() || output_tokens.is_some()).then(|| Usage {
+                    prompt_tokens: input_tokens,
+                    completion_tokens: output_tokens,
+                    total_tokens: match (input_tokens, output_tokens) {
+                        (Some(i), Some(o)) => Some(i + o),
+                        _ => None,
+                    },
+                    ..Default::default()
+                });
+                let choice = Choice {
+                    index: 0,
+                    message: Some(Message {
+                        role: MessageRole::Assistant,
+                        content_length: Some(content.len()),
+                        content: Some(MessageContent::Text(content)),
+                        content_hash: None,
+                        has_images: None,
+                        image_count: None,
+                        images: Vec::new(),
+                        tool_call_id: None,
+                        name: None,
+                    }),
+                    finish_reason: Some(FinishReason::Incomplete),
+                };
+                (vec![choice], usage)
             }
+            None => (Vec::new(), None),
         };
 
-        if !reassembler.is_complete() {
-            debug!(
-                "HTTP request not yet complete, buffering (current size: {} bytes)",
-                reassembler.buffer.len()
+        OispEvent::AiResponse(AiResponseEvent {
+            envelope,
+            data: AiResponseData {
+                request_id: pending.request_id.clone(),
+                provider_request_id: None,
+                provider: pending.request_data.provider.clone(),
+                model: pending.request_data.model.clone(),
+                status_code: None,
+                success: Some(false),
+                error: Some(ErrorInfo {
+                    error_type: Some("incomplete_response".to_string()),
+                    message: Some(match pending.stream_activity {
+                        _ if shutdown => "sensor stopped before the response completed".to_string(),
+                        Some(_) => format!("stream idle for {}s", self.stream_timeout.as_secs()),
+                        None => format!(
+                            "no complete response within {}s",
+                            PENDING_REQUEST_TIMEOUT.as_secs()
+                        ),
+                    }),
+                    code: None,
+                }),
+                tool_calls_count: Some(0),
+                choices,
+                tool_calls: Vec::new(),
+                usage,
+                latency_ms: None,
+                time_to_first_token_ms: None,
+                was_cached: None,
+                finish_reason: Some(FinishReason::Incomplete),
+                thinking: None,
+            },
+        })
+    }
+
+    /// Feed bytes to the connection's HTTP/2 state, if it is an HTTP/2 connection
+    ///
+    /// Returns `None` for HTTP/1.x traffic. A client preface starts tracking
+    /// the connection; HTTP/1.x on the same socket (fd reuse) ends it.
+    fn feed_h2(&self, key: &CorrelationKey, data: &[u8], outbound: bool) -> Option<Vec<H2Message>> {
+        let conn_key = key.without_tid();
+        let mut connections = self.h2_connections.write().unwrap();
+
+        if outbound && is_h2_preface(data) {
+            debug!("HTTP/2 connection preface on {:?}", conn_key);
+            connections.insert(
+                conn_key.clone(),
+                H2Connection {
+                    outbound: H2FrameReassembler::client(),
+                    inbound: H2FrameReassembler::server(),
+                    last_seen: self.clock.now(),
+                },
             );
-            return Ok(events);
+        } else if (outbound && is_http_request(data)) || (!outbound && is_http_response(data)) {
+            connections.remove(&conn_key);
+            return None;
+        }
+
+        let conn = connections.get_mut(&conn_key)?;
+        conn.last_seen = self.clock.now();
+        let reassembler = if outbound {
+            &mut conn.outbound
+        } else {
+            &mut conn.inbound
+        };
+        let messages = reassembler.feed(data);
+        if reassembler.is_failed() {
+            self.decode_failed(
+                DecodeFailure::Http2,
+                "unknown",
+                &format_args!("{:?}", conn_key),
+            );
+            connections.remove(&conn_key);
         }
+        Some(messages)
+    }
 
-        // Request is complete! Remove from partials and proceed to decode
-        self.partial_requests.write().unwrap().remove(&key);
+    fn decode_ssl_write(&self, raw: &RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
+        let mut events = self.maybe_cleanup(None);
 
-        let http_req = match parse_request(&reassembler.buffer) {
-            Some(req) => req,
-            None => {
-                info!("Failed to parse reassembled HTTP request");
-                return Ok(events);
+        let key = CorrelationKey::from_event(raw);
+
+        // HTTP/2 connections are reassembled per stream
+        if let Some(messages) = self.feed_h2(&key, &raw.data, true) {
+            for message in messages {
+                if let Some(http_req) = message.to_request() {
+                    let stream_key = key.with_stream(message.stream_id);
+                    events.extend(self.decode_http_request(raw, stream_key, http_req)?);
+                }
             }
-        };
+            return Ok(events);
+        }
 
-        // Check if this is an AI provider using spec-driven detection first
-        let domain = http_req.host.as_deref().unwrap_or("");
+        // Check if we have an existing partial request for this connection
+        let is_new_request = is_http_request(&raw.data);
 
-        // Use spec-driven detection (95+ providers from spec bundle)
-        let provider_id = match self.spec_registry.detect_from_domain(domain) {
-            Some(id) => {
+        // Postgres connections carry pgvector searches as plain SQL
+        if !is_new_request {
+            if let Some(query) = rag::parse_postgres_query(&raw.data) {
                 debug!(
-                    "Spec registry detected provider '{}' for domain {}",
-                    id, domain
+                    "pgvector query on {:?} for pid={}",
+                    query.collection, key.pid
                 );
-                id.to_string()
+                self.track_retrieval(raw, key, query);
+                return Ok(events);
             }
-            None => {
-                // Fall back to legacy registry for backward compatibility
-                match self.legacy_registry.detect_from_domain(domain) {
-                    Some(p) => {
-                        debug!(
-                            "Legacy registry detected provider {:?} for domain {}",
-                            p, domain
-                        );
-                        format!("{:?}", p).to_lowercase()
-                    }
-                    None => {
-                        debug!("Domain {} is not a known AI provider", domain);
-                        return Ok(events);
-                    }
+        }
+        let reassembler = {
+            let mut partial = self.partial_requests.write().unwrap();
+            let reassembler = if is_new_request {
+                // New request starting - replace any old one for this key
+                let reassembler = RequestReassembler::new(&raw.data, self.clock.now());
+                partial.insert(key.clone(), reassembler);
+                partial.get(&key)
+            } else {
+                // Not a new request, see if it's a continuation of a partial one
+                partial.get_mut(&key).map(|reassembler| {
+                    reassembler.feed(&raw.data);
+                    &*reassembler
+                })
+            };
+
+            match reassembler {
+                Some(r) if !r.is_complete() => {
+                    debug!(
+                        "HTTP request not yet complete, buffering (current size: {} bytes)",
+                        r.buffer.len()
+                    );
+                    return Ok(events);
                 }
+                // Request is complete! Remove from partials and proceed to decode
+                Some(_) => partial.remove(&key).unwrap(),
+                None => {
+                    info!("SSL write is not HTTP request and no partial request found, skipping (data starts with: {:?})",
+                        String::from_utf8_lossy(&raw.data[..std::cmp::min(raw.data.len(), 20)]));
+                    return Ok(events);
+                }
+            }
+        };
+
+        let mut http_req = match parse_request(&reassembler.buffer) {
+            Some(req) => req,
+            None => {
+                self.decode_failed(
+                    DecodeFailure::HttpRequest,
+                    "unknown",
+                    &format_args!("{} bytes", reassembler.buffer.len()),
+                );
+                return Ok(events);
             }
         };
+        if let Some(parser) = reassembler.multipart {
+            http_req.multipart = Some(parser.finish());
+        }
+
+        events.extend(self.decode_http_request(raw, key, http_req)?);
+        Ok(events)
+    }
+
+    /// Decode a complete HTTP request (from HTTP/1.1 or an HTTP/2 stream)
+    fn decode_http_request(
+        &self,
+        raw: &RawCaptureEvent,
+        key: CorrelationKey,
+        http_req: ParsedHttpRequest,
+    ) -> PluginResult<Vec<OispEvent>> {
+        let mut events = Vec::new();
 
-        // Convert to Provider enum for existing code paths (backward compatibility)
-        let provider = self
-            .legacy_registry
-            .detect_from_domain(domain)
-            .unwrap_or(Provider::Unknown);
+        let domain = http_req.host.as_deref().unwrap_or("");
+        let shape = if http_req.multipart.is_some() {
+            BodyShape::Multipart
+        } else {
+            BodyShape::Json
+        };
+        let Some(DetectedProvider {
+            id: provider_id,
+            provider,
+            detection,
+        }) = self.detect_provider(domain, shape)
+        else {
+            debug!("Domain {} is not a known AI provider", domain);
+            self.maybe_track_http_retrieval(raw, key, &http_req);
+            return Ok(events);
+        };
 
         debug!(
             "Detected AI provider: id='{}', enum={:?} for domain {}",
             provider_id, provider, domain
         );
 
-        // Try to parse body as JSON
-        let body = match &http_req.body {
-            Some(b) => b,
-            None => {
-                trace!("No body in HTTP request");
+        let endpoint = format!("https://{}{}", domain, http_req.path);
+        let mut signals = DecodeSignals::default();
+        let ollama_native =
+            provider == Provider::Ollama && is_ollama_native_request(&http_req.path);
+        let mut gemini = false;
+        let bedrock = provider == Provider::AwsBedrock && is_bedrock_invoke_request(&http_req.path);
+
+        // File uploads (audio transcription, image edits) are form-encoded
+        let mut request_data = if let Some(form) = &http_req.multipart {
+            parse_multipart_request(form, provider, &http_req.path, &endpoint)
+        } else {
+            // Try to parse body as JSON
+            let body = match &http_req.body {
+                Some(b) => b,
+                None => {
+                    trace!("No body in HTTP request");
+                    return Ok(events);
+                }
+            };
+
+            let json: serde_json::Value = match serde_json::from_slice(body) {
+                Ok(j) => j,
+                Err(e) => {
+                    trace!("Failed to parse request body as JSON: {}", e);
+                    return Ok(events);
+                }
+            };
+
+            if detection == ProviderDetection::RequestSchema
+                && !is_openai_compatible_request(&http_req.path, &json)
+            {
+                debug!("Domain {} is not a known AI provider", domain);
+                self.maybe_track_http_retrieval(raw, key, &http_req);
                 return Ok(events);
             }
-        };
 
-        let json: serde_json::Value = match serde_json::from_slice(body) {
-            Ok(j) => j,
-            Err(e) => {
-                trace!("Failed to parse request body as JSON: {}", e);
+            let is_responses_api = is_responses_api_request(&http_req.path, &json);
+            gemini = is_gemini_request(&http_req.path, &json);
+
+            if !is_responses_api
+                && !gemini
+                && !bedrock
+                && !is_ai_request(&json)
+                && !is_non_chat_request(&http_req.path, &json)
+            {
+                trace!("Request does not look like an AI request");
                 return Ok(events);
             }
-        };
 
-        if !is_ai_request(&json) {
-            trace!("Request does not look like an AI request");
-            return Ok(events);
-        }
+            signals.check_provider(provider, &json);
 
-        let endpoint = format!("https://{}{}", domain, http_req.path);
+            // Parse request based on provider
+            let request_data = match provider {
+                _ if is_responses_api => parse_responses_request(&json, provider, &endpoint),
+                _ if ollama_native => parse_ollama_request(&json, &http_req.path, &endpoint),
+                _ if gemini => parse_gemini_request(&json, &http_req.path, &endpoint),
+                _ if bedrock => parse_bedrock_request(&json, domain, &http_req.path, &endpoint),
+                Provider::Anthropic => parse_anthropic_request(&json, &endpoint),
+                _ => parse_ai_request(&json, provider, &endpoint),
+            };
 
-        // Parse request based on provider
-        let request_data = match provider {
-            Provider::Anthropic => parse_anthropic_request(&json, &endpoint),
-            _ => parse_ai_request(&json, provider, &endpoint),
+            match request_data {
+                Some(data) => data,
+                None => {
+                    self.decode_failed(
+                        DecodeFailure::AiRequest,
+                        &format!("{:?}", provider).to_lowercase(),
+                        &http_req.path,
+                    );
+                    return Ok(events);
+                }
+            }
         };
 
-        let request_data = match request_data {
-            Some(data) => data,
-            None => {
-                trace!("Failed to parse AI request data");
-                return Ok(events);
+        // Report mapped and schema-detected providers by the name they were
+        // resolved to rather than the parser's
+        if detection != ProviderDetection::KnownEndpoint {
+            if let Some(info) = request_data.provider.as_mut() {
+                info.name = provider_id.clone();
             }
-        };
+        }
+        signals.detection = detection;
+
+        // The endpoint says more about the request type than the body shape
+        if let Some(request_type) = request_type_from_path(&http_req.path) {
+            request_data.request_type = Some(request_type);
+        }
+
+        if let Some(limits) = &self.content_limits {
+            apply_content_limits(&mut request_data, limits);
+        }
 
-        let envelope = self.create_envelope(raw, "ai.request");
+        let mut envelope = self.create_ai_envelope(raw, "ai.request", &signals);
+        if let Some(form) = &http_req.multipart {
+            envelope
+                .attrs
+                .insert(MULTIPART_ATTR.to_string(), multipart_summary(form));
+        }
         let is_streaming = request_data.streaming.unwrap_or(false);
 
         // Extract web context from HTTP headers (Origin, Referer, User-Agent)
@@ -745,11 +1577,19 @@ impl HttpDecoder {
                     request_id: request_data.request_id.clone(),
                     request_data: request_data.clone(),
                     timestamp: envelope.ts,
-                    created_at: Instant::now(),
+                    ts_mono: envelope.ts_mono,
+                    created_at: self.clock.now(),
                     provider,
+                    detection,
                     is_streaming,
+                    stream_activity: None,
+                    ollama_native,
+                    gemini,
+                    bedrock,
                     host: http_req.host.clone(),
                     web_context: web_context.clone(),
+                    process: envelope.process.clone(),
+                    actor: envelope.actor.clone(),
                 },
             );
         }
@@ -778,13 +1618,39 @@ impl HttpDecoder {
     }
 
     fn decode_ssl_read(&self, raw: &RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
-        self.maybe_cleanup();
-        let mut events = Vec::new();
+        let mut events = self.maybe_cleanup(None);
 
         let key = CorrelationKey::from_event(raw);
 
+        // HTTP/2 responses are complete once their stream ends
+        if let Some(messages) = self.feed_h2(&key, &raw.data, false) {
+            for message in messages {
+                if let Some(http_resp) = message.to_response() {
+                    let stream_key = key.with_stream(message.stream_id);
+                    self.decode_http_response(
+                        raw,
+                        &stream_key,
+                        ResponseReassembler::new(http_resp, self.clock.now()),
+                        DecodeSignals {
+                            truncated: message.truncated,
+                            ..Default::default()
+                        },
+                        &mut events,
+                    );
+                }
+            }
+            return Ok(events);
+        }
+
+        // Interim responses (`100 Continue`) do not answer the request
+        let data = skip_interim_responses(&raw.data);
+        if data.is_empty() && !raw.data.is_empty() {
+            debug!("Skipping interim HTTP response for pid={}", key.pid);
+            return Ok(events);
+        }
+
         // 1. Check for existing partial response
-        let is_new_response = is_http_response(&raw.data);
+        let is_new_response = is_http_response(data);
 
         info!(
             "decode_ssl_read: pid={}, tid={:?}, fd={:?}, is_new_response={}, data_len={}, data_start={:?}",
@@ -792,11 +1658,14 @@ impl HttpDecoder {
             key.tid,
             key.fd,
             is_new_response,
-            raw.data.len(),
-            String::from_utf8_lossy(&raw.data[..std::cmp::min(50, raw.data.len())])
+            data.len(),
+            String::from_utf8_lossy(&data[..std::cmp::min(50, data.len())])
         );
 
-        let reassembler_opt: Option<ResponseReassembler> = {
+        let mut signals = DecodeSignals::default();
+
+        // Key of the reassembler this read was fed to, if any
+        let fed_key: Option<CorrelationKey> = {
             let mut partials = self.partial_responses.write().unwrap();
 
             // Log all current partial responses
@@ -807,39 +1676,48 @@ impl HttpDecoder {
             );
 
             if is_new_response {
-                if let Some(http_resp) = parse_response(&raw.data) {
+                if let Some(http_resp) = parse_response(data) {
                     info!("New HTTP response: status={}, is_chunked={}, is_gzipped={}, content_length={:?}",
                         http_resp.status_code, http_resp.is_chunked, http_resp.is_gzipped, http_resp.content_length);
-                    let reassembler = ResponseReassembler::new(http_resp);
+                    let reassembler = ResponseReassembler::new(http_resp, self.clock.now());
                     partials.insert(key.clone(), reassembler);
-                    partials.get(&key).cloned()
+                    Some(key.clone())
+                } else if data.windows(4).any(|w| w == b"\r\n\r\n") {
+                    self.decode_failed(
+                        DecodeFailure::HttpResponse,
+                        "unknown",
+                        &format_args!("{} bytes", data.len()),
+                    );
+                    None
                 } else {
-                    info!("Failed to parse HTTP response");
+                    info!("Incomplete HTTP response head, skipping");
                     None
                 }
             } else if let Some(reassembler) = partials.get_mut(&key) {
                 info!(
                     "Feeding {} bytes to existing reassembler for key {:?}",
-                    raw.data.len(),
+                    data.len(),
                     key
                 );
-                reassembler.feed(&raw.data);
-                Some(reassembler.clone())
+                reassembler.feed(data, self.clock.now());
+                Some(key.clone())
             } else {
                 // Try without fd as fallback
                 let key_no_fd = CorrelationKey {
                     pid: key.pid,
                     tid: key.tid,
                     fd: None,
+                    stream_id: None,
                 };
                 if let Some(reassembler) = partials.get_mut(&key_no_fd) {
                     info!(
                         "Feeding {} bytes to reassembler via key_no_fd {:?}",
-                        raw.data.len(),
+                        data.len(),
                         key_no_fd
                     );
-                    reassembler.feed(&raw.data);
-                    Some(reassembler.clone())
+                    reassembler.feed(data, self.clock.now());
+                    signals.fallback_correlation = true;
+                    Some(key_no_fd)
                 } else {
                     info!(
                         "No matching reassembler found for key {:?} or key_no_fd {:?}",
@@ -851,124 +1729,373 @@ impl HttpDecoder {
         };
 
         // 2. If we have a reassembler, check if it's complete
-        if let Some(mut reassembler) = reassembler_opt {
-            info!(
-                "Response reassembler: body_buffer_len={}, is_complete={}",
-                reassembler.body_buffer.len(),
-                reassembler.is_complete()
-            );
-
-            if reassembler.is_complete() {
+        if let Some(partial_key) = fed_key {
+            let (complete, streaming) = {
+                let mut partials = self.partial_responses.write().unwrap();
+                let Some(reassembler) = partials.get(&partial_key) else {
+                    return Ok(events);
+                };
+                let streaming = reassembler.headers.is_streaming;
                 info!(
-                    "Response COMPLETE for pid={}, buffer ends with: {:?}",
-                    key.pid,
-                    &reassembler.body_buffer[reassembler.body_buffer.len().saturating_sub(10)..]
+                    "Response reassembler: body_buffer_len={}, is_complete={}",
+                    reassembler.body_buffer.len(),
+                    reassembler.is_complete()
                 );
-
-                // Remove from partials
-                self.partial_responses.write().unwrap().remove(&key);
-
-                // Find the matching pending request
-                let pending_opt = {
-                    let pending = self.pending_requests.read().unwrap();
-                    pending
-                        .get(&key)
-                        .cloned()
-                        .or_else(|| pending.get(&key.without_tid()).cloned())
-                };
-
-                if let Some(pending_req) = pending_opt {
+                if reassembler.is_complete() {
                     info!(
-                        "Found pending request for response: request_id={}",
-                        pending_req.request_id
+                        "Response COMPLETE for pid={}, buffer ends with: {:?}",
+                        key.pid,
+                        &reassembler.body_buffer
+                            [reassembler.body_buffer.len().saturating_sub(10)..]
                     );
-                    // Decompress body if needed
-                    reassembler.decompress_if_needed();
-
-                    // Update headers with full body
-                    let mut full_resp = reassembler.headers;
-                    full_resp.body = Some(reassembler.body_buffer);
-
-                    if full_resp.is_streaming || pending_req.is_streaming {
-                        self.handle_streaming_response(
-                            &key,
-                            &pending_req,
-                            &full_resp.body,
-                            raw,
-                            &mut events,
-                        );
-                    } else {
-                        self.handle_complete_response(
-                            &key,
-                            &pending_req,
-                            &full_resp,
-                            raw,
-                            &mut events,
-                        );
-                    }
+                    // Remove from partials
+                    (partials.remove(&partial_key), streaming)
+                } else {
+                    (None, streaming)
                 }
+            };
+            self.note_stream_activity(&partial_key, streaming);
+
+            if let Some(reassembler) = complete {
+                self.decode_http_response(raw, &key, reassembler, signals, &mut events);
             }
             return Ok(events);
         }
 
-        // 3. Fallback for unexpected data or AI-specific streaming
-        let pending_opt = {
-            let pending = self.pending_requests.read().unwrap();
-            pending
-                .get(&key)
-                .cloned()
-                .or_else(|| pending.get(&key.without_tid()).cloned())
-        };
+        // Results of a pgvector query arrive as Postgres backend messages
+        if let Some(retrieved) = rag::parse_postgres_results(data) {
+            if let Some(pending) = self.take_retrieval(&key) {
+                events.push(rag_retrieve_event(pending, Some(raw), Some(retrieved)));
+                return Ok(events);
+            }
+        }
 
-        if let Some(pending_req) = pending_opt {
+        // 3. Fallback for unexpected data or AI-specific streaming
+        if let Some((pending_key, pending_req)) = self.find_pending(&key, &mut signals) {
             if pending_req.is_streaming {
-                self.handle_streaming_chunk(&key, &pending_req, &raw.data, raw, &mut events);
+                self.note_stream_activity(&pending_key, true);
+                self.handle_streaming_chunk(
+                    &pending_key,
+                    &pending_req,
+                    data,
+                    &mut signals,
+                    raw,
+                    &mut events,
+                );
             }
         }
 
         Ok(events)
     }
 
-    fn handle_streaming_response(
+    /// Correlate a complete response with its pending request and emit events
+    fn decode_http_response(
         &self,
-        key: &CorrelationKey,
-        pending_req: &PendingRequest,
-        body: &Option<Vec<u8>>,
         raw: &RawCaptureEvent,
+        key: &CorrelationKey,
+        mut reassembler: ResponseReassembler,
+        mut signals: DecodeSignals,
         events: &mut Vec<OispEvent>,
     ) {
-        let body = match body {
-            Some(b) => b,
-            None => return,
+        // Find the matching pending request
+        if let Some((pending_key, pending_req)) = self.find_pending(key, &mut signals) {
+            info!(
+                "Found pending request for response: request_id={}",
+                pending_req.request_id
+            );
+            // Body framing tells us the stream ended, even without a terminal event
+            let body_ended =
+                reassembler.headers.is_chunked || reassembler.headers.content_length.is_some();
+
+            // Decompress body if needed
+            match reassembler.decompress_if_needed() {
+                BodyDecoding::Intact => {}
+                BodyDecoding::Lenient => signals.truncated = true,
+                BodyDecoding::Failed => signals.decompression_failed = true,
+            }
+
+            // Update headers with full body
+            let mut full_resp = reassembler.headers;
+            full_resp.body = Some(Vec::from(reassembler.body_buffer));
+
+            // A failed streaming request gets a plain JSON error body
+            let failed = !(200..300).contains(&full_resp.status_code);
+            if full_resp.is_streaming || (pending_req.is_streaming && !failed) {
+                self.handle_streaming_response(
+                    &pending_key,
+                    &pending_req,
+                    &full_resp.body,
+                    body_ended,
+                    &mut signals,
+                    raw,
+                    events,
+                );
+            } else {
+                self.handle_complete_response(
+                    &pending_key,
+                    &pending_req,
+                    &full_resp,
+                    &mut signals,
+                    raw,
+                    events,
+                );
+            }
+        } else if let Some(pending) = self.take_retrieval(key) {
+            if reassembler.decompress_if_needed() == BodyDecoding::Failed {
+                debug!(
+                    "Could not decompress {} response for pid={}",
+                    pending.query.store, key.pid
+                );
+            }
+            let retrieved = if (200..300).contains(&reassembler.headers.status_code) {
+                serde_json::from_slice(&reassembler.body_buffer)
+                    .ok()
+                    .and_then(|body| rag::parse_http_results(pending.query.store, &body))
+            } else {
+                None
+            };
+            events.push(rag_retrieve_event(pending, Some(raw), retrieved));
+        } else if self.attach_existing {
+            events.extend(self.orphan_response(raw, reassembler));
+        }
+    }
+
+    /// Best-effort `ai.response` for a response whose request was never seen
+    ///
+    /// Returns `None` for streamed responses, bodies that aren't JSON, and
+    /// responses neither the destination nor the body tie to a provider.
+    fn orphan_response(
+        &self,
+        raw: &RawCaptureEvent,
+        mut reassembler: ResponseReassembler,
+    ) -> Option<OispEvent> {
+        if reassembler.headers.is_streaming {
+            debug!(
+                "Skipping streamed response without a request for pid={}",
+                raw.pid
+            );
+            return None;
+        }
+        let mut signals = DecodeSignals {
+            orphan: true,
+            ..Default::default()
         };
+        match reassembler.decompress_if_needed() {
+            BodyDecoding::Intact => {}
+            BodyDecoding::Lenient => signals.truncated = true,
+            BodyDecoding::Failed => return None,
+        }
+        let json: serde_json::Value = serde_json::from_slice(&reassembler.body_buffer).ok()?;
+
+        let detected = self.destination_provider(raw).or_else(|| {
+            detect_provider_from_body(&json).map(|provider| DetectedProvider {
+                id: format!("{:?}", provider).to_lowercase(),
+                provider,
+                detection: ProviderDetection::ResponseBody,
+            })
+        })?;
+        signals.detection = detected.detection;
+        signals.check_provider(detected.provider, &json);
+
+        let request_id = ulid::Ulid::new().to_string();
+        let status_code = reassembler.headers.status_code;
+        let failed = !(200..300).contains(&status_code);
+        let mut data = match detected.provider {
+            _ if failed => Some(parse_error_response(
+                &json,
+                &request_id,
+                detected.provider,
+                status_code,
+            )),
+            _ if is_responses_api_response(&json) => {
+                parse_responses_response(&json, &request_id, detected.provider)
+            }
+            Provider::Google if is_gemini_response(&json) => {
+                parse_gemini_response(&json, &request_id)
+            }
+            Provider::Anthropic => parse_anthropic_response(&json, &request_id),
+            _ => parse_ai_response(&json, &request_id, detected.provider),
+        }?;
+        if !failed && data.choices.is_empty() && data.usage.is_none() {
+            return None;
+        }
 
-        match pending_req.provider {
-            Provider::Anthropic => {
-                let mut reassemblers = self.anthropic_reassemblers.write().unwrap();
-                let reassembler = reassemblers.entry(key.clone()).or_default();
-                reassembler.feed(body);
+        if detected.detection == ProviderDetection::ConfiguredHost {
+            if let Some(info) = data.provider.as_mut() {
+                info.name = detected.id;
+            }
+        }
+        data.status_code = Some(status_code);
+        debug!(
+            "Orphan response for pid={} attributed to {:?}",
+            raw.pid,
+            data.provider.as_ref().map(|p| &p.name)
+        );
 
-                if reassembler.is_complete() {
-                    // Build complete response
-                    let envelope = self.create_envelope(raw, "ai.response");
-                    // Add web context from pending request
-                    let envelope = if let Some(ref ctx) = pending_req.web_context {
-                        envelope.with_web_context(ctx.clone())
-                    } else {
-                        envelope
-                    };
-                    let latency = envelope.ts - pending_req.timestamp;
+        Some(OispEvent::AiResponse(AiResponseEvent {
+            envelope: self.create_ai_envelope(raw, "ai.response", &signals),
+            data,
+        }))
+    }
 
-                    let (input_tokens, output_tokens) = reassembler.usage();
+    /// Provider of the connection `raw` was captured on, from its destination
+    ///
+    /// The capture backend reports the remote host or address, on Linux
+    /// from the sockets seeded at capture start for connections that predate
+    /// it. Only registry and configured hosts count; the request schema
+    /// check has no request to look at.
+    fn destination_provider(&self, raw: &RawCaptureEvent) -> Option<DetectedProvider> {
+        let addr = raw.metadata.remote_addr.as_deref()?;
+        let with_port = raw
+            .metadata
+            .remote_port
+            .map(|port| format!("{}:{}", addr, port));
+        let detected = with_port
+            .as_deref()
+            .into_iter()
+            .chain([addr])
+            .filter_map(|host| self.detect_provider(host, BodyShape::Json))
+            .find(|detected| detected.detection != ProviderDetection::RequestSchema);
+        detected
+    }
 
-                    let response_data = AiResponseData {
-                        request_id: pending_req.request_id.clone(),
-                        provider_request_id: None,
-                        provider: pending_req.request_data.provider.clone(),
-                        model: pending_req.request_data.model.clone(),
-                        status_code: Some(200),
-                        success: Some(true),
-                        error: None,
+    /// Hold a vector store query from a request to an unrecognized host
+    fn maybe_track_http_retrieval(
+        &self,
+        raw: &RawCaptureEvent,
+        key: CorrelationKey,
+        http_req: &ParsedHttpRequest,
+    ) {
+        let Some(body) = &http_req.body else {
+            return;
+        };
+        let Ok(json) = serde_json::from_slice::<serde_json::Value>(body) else {
+            return;
+        };
+        let host = http_req.host.as_deref().unwrap_or("");
+        if let Some(query) =
+            rag::parse_http_query(host, &http_req.path, http_req.user_agent(), &json)
+        {
+            debug!(
+                "{} query on {:?} for pid={}",
+                query.store, query.collection, key.pid
+            );
+            self.track_retrieval(raw, key, query);
+        }
+    }
+
+    fn track_retrieval(&self, raw: &RawCaptureEvent, key: CorrelationKey, query: VectorQuery) {
+        let mut retrievals = self.pending_retrievals.write().unwrap();
+        if retrievals.len() >= MAX_PENDING_REQUESTS {
+            warn!("Max pending retrievals reached, removing oldest");
+            if let Some(oldest_key) = retrievals
+                .iter()
+                .min_by_key(|(_, r)| r.created_at)
+                .map(|(k, _)| k.clone())
+            {
+                retrievals.remove(&oldest_key);
+            }
+        }
+        retrievals.insert(
+            key,
+            PendingRetrieval {
+                query,
+                envelope: self.create_envelope(raw, "agent.rag_retrieve"),
+                clock: raw.metadata.clock,
+                created_at: self.clock.now(),
+            },
+        );
+    }
+
+    /// Take the pending retrieval a response answers, falling back to a
+    /// TID-less key
+    fn take_retrieval(&self, key: &CorrelationKey) -> Option<PendingRetrieval> {
+        let mut retrievals = self.pending_retrievals.write().unwrap();
+        retrievals
+            .remove(key)
+            .or_else(|| retrievals.remove(&key.without_tid()))
+    }
+
+    /// Find the pending request for a response, falling back to a TID-less key
+    ///
+    /// Returns the key the request is stored under so callers clean up the
+    /// right entry.
+    fn find_pending(
+        &self,
+        key: &CorrelationKey,
+        signals: &mut DecodeSignals,
+    ) -> Option<(CorrelationKey, PendingRequest)> {
+        let pending = self.pending_requests.read().unwrap();
+        if let Some(req) = pending.get(key) {
+            signals.detection = req.detection;
+            return Some((key.clone(), req.clone()));
+        }
+
+        let fallback = key.without_tid();
+        let req = pending.get(&fallback)?;
+        signals.fallback_correlation = true;
+        signals.detection = req.detection;
+        Some((fallback, req.clone()))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_streaming_response(
+        &self,
+        key: &CorrelationKey,
+        pending_req: &PendingRequest,
+        body: &Option<Vec<u8>>,
+        body_ended: bool,
+        signals: &mut DecodeSignals,
+        raw: &RawCaptureEvent,
+        events: &mut Vec<OispEvent>,
+    ) {
+        let body = match body {
+            Some(b) => b,
+            None => return,
+        };
+
+        if pending_req.ollama_native {
+            self.handle_ollama_stream(key, pending_req, body, body_ended, signals, raw, events);
+            return;
+        }
+        if pending_req.gemini {
+            self.handle_gemini_stream(key, pending_req, body, body_ended, signals, raw, events);
+            return;
+        }
+        if pending_req.bedrock {
+            self.handle_bedrock_stream(key, pending_req, body, body_ended, signals, raw, events);
+            return;
+        }
+
+        match pending_req.provider {
+            Provider::Anthropic => {
+                let mut reassemblers = self.anthropic_reassemblers.write().unwrap();
+                let reassembler = reassemblers.entry(key.clone()).or_default();
+                reassembler.feed(body);
+
+                if reassembler.is_complete() || body_ended {
+                    signals.truncated |= !reassembler.is_complete();
+
+                    // Build complete response
+                    let envelope = self.create_ai_envelope(raw, "ai.response", signals);
+                    // Add web context from pending request
+                    let mut envelope = if let Some(ref ctx) = pending_req.web_context {
+                        envelope.with_web_context(ctx.clone())
+                    } else {
+                        envelope
+                    };
+                    let latency_ms = pending_req.latency_ms(&mut envelope);
+
+                    let (input_tokens, output_tokens) = reassembler.usage();
+
+                    let response_data = AiResponseData {
+                        request_id: pending_req.request_id.clone(),
+                        provider_request_id: None,
+                        provider: pending_req.request_data.provider.clone(),
+                        model: pending_req.request_data.model.clone(),
+                        status_code: Some(200),
+                        success: Some(true),
+                        error: None,
                         choices: vec![Choice {
                             index: 0,
                             message: Some(Message {
@@ -980,6 +2107,7 @@ impl HttpDecoder {
                                 content_length: Some(reassembler.content().len()),
                                 has_images: None,
                                 image_count: None,
+                                images: Vec::new(),
                                 tool_call_id: None,
                                 name: None,
                             }),
@@ -1005,7 +2133,7 @@ impl HttpDecoder {
                             output_cost_usd: None,
                             total_cost_usd: None,
                         }),
-                        latency_ms: Some(latency.num_milliseconds() as u64),
+                        latency_ms: Some(latency_ms),
                         time_to_first_token_ms: None,
                         was_cached: None,
                         finish_reason: reassembler.stop_reason().map(|r| match r {
@@ -1031,15 +2159,35 @@ impl HttpDecoder {
                 let reassembler = reassemblers.entry(key.clone()).or_default();
                 reassembler.feed(body);
 
-                if reassembler.is_complete() {
-                    let envelope = self.create_envelope(raw, "ai.response");
+                if reassembler.is_complete() || body_ended {
+                    signals.truncated |= !reassembler.is_complete();
+
+                    let envelope = self.create_ai_envelope(raw, "ai.response", signals);
                     // Add web context from pending request
-                    let envelope = if let Some(ref ctx) = pending_req.web_context {
+                    let mut envelope = if let Some(ref ctx) = pending_req.web_context {
                         envelope.with_web_context(ctx.clone())
                     } else {
                         envelope
                     };
-                    let latency = envelope.ts - pending_req.timestamp;
+                    let latency_ms = pending_req.latency_ms(&mut envelope);
+
+                    // Usage is only present when the provider reports it in-stream
+                    // (Responses API always does; Chat Completions needs include_usage)
+                    let (input_tokens, output_tokens) = reassembler.usage();
+                    let usage =
+                        (input_tokens.is_some() || output_tokens.is_some()).then(|| Usage {
+                            prompt_tokens: input_tokens,
+                            completion_tokens: output_tokens,
+                            total_tokens: match (input_tokens, output_tokens) {
+                                (Some(i), Some(o)) => Some(i + o),
+                                _ => None,
+                            },
+                            cached_tokens: None,
+                            reasoning_tokens: None,
+                            input_cost_usd: None,
+                            output_cost_usd: None,
+                            total_cost_usd: None,
+                        });
 
                     let response_data = AiResponseData {
                         request_id: pending_req.request_id.clone(),
@@ -1047,7 +2195,7 @@ impl HttpDecoder {
                         provider: pending_req.request_data.provider.clone(),
                         model: pending_req.request_data.model.clone(),
                         status_code: Some(200),
-                        success: Some(true),
+                        success: Some(reassembler.finish_reason() != Some("error")),
                         error: None,
                         choices: vec![Choice {
                             index: 0,
@@ -1060,6 +2208,7 @@ impl HttpDecoder {
                                 content_length: Some(reassembler.content().len()),
                                 has_images: None,
                                 image_count: None,
+                                images: Vec::new(),
                                 tool_call_id: None,
                                 name: None,
                             }),
@@ -1067,13 +2216,15 @@ impl HttpDecoder {
                                 "stop" => FinishReason::Stop,
                                 "length" => FinishReason::Length,
                                 "tool_calls" => FinishReason::ToolCalls,
+                                "content_filter" => FinishReason::ContentFilter,
+                                "error" => FinishReason::Error,
                                 _ => FinishReason::Other,
                             }),
                         }],
                         tool_calls: Vec::new(),
                         tool_calls_count: Some(0),
-                        usage: None, // Streaming responses often don't include usage
-                        latency_ms: Some(latency.num_milliseconds() as u64),
+                        usage,
+                        latency_ms: Some(latency_ms),
                         time_to_first_token_ms: None,
                         was_cached: None,
                         finish_reason: reassembler.finish_reason().map(|r| match r {
@@ -1101,9 +2252,23 @@ impl HttpDecoder {
         key: &CorrelationKey,
         pending_req: &PendingRequest,
         data: &[u8],
+        signals: &mut DecodeSignals,
         raw: &RawCaptureEvent,
         events: &mut Vec<OispEvent>,
     ) {
+        if pending_req.ollama_native {
+            self.handle_ollama_stream(key, pending_req, data, false, signals, raw, events);
+            return;
+        }
+        if pending_req.gemini {
+            self.handle_gemini_stream(key, pending_req, data, false, signals, raw, events);
+            return;
+        }
+        if pending_req.bedrock {
+            self.handle_bedrock_stream(key, pending_req, data, false, signals, raw, events);
+            return;
+        }
+
         // Feed to appropriate reassembler based on provider
         match pending_req.provider {
             Provider::Anthropic => {
@@ -1113,14 +2278,14 @@ impl HttpDecoder {
                 // Check completion similar to above
                 if reassembler.is_complete() {
                     // Build and emit response (same logic as above)
-                    let envelope = self.create_envelope(raw, "ai.response");
+                    let envelope = self.create_ai_envelope(raw, "ai.response", signals);
                     // Add web context from pending request
-                    let envelope = if let Some(ref ctx) = pending_req.web_context {
+                    let mut envelope = if let Some(ref ctx) = pending_req.web_context {
                         envelope.with_web_context(ctx.clone())
                     } else {
                         envelope
                     };
-                    let latency = envelope.ts - pending_req.timestamp;
+                    let latency_ms = pending_req.latency_ms(&mut envelope);
 
                     let (input_tokens, output_tokens) = reassembler.usage();
 
@@ -1143,6 +2308,7 @@ impl HttpDecoder {
                                 content_length: Some(reassembler.content().len()),
                                 has_images: None,
                                 image_count: None,
+                                images: Vec::new(),
                                 tool_call_id: None,
                                 name: None,
                             }),
@@ -1163,7 +2329,7 @@ impl HttpDecoder {
                             output_cost_usd: None,
                             total_cost_usd: None,
                         }),
-                        latency_ms: Some(latency.num_milliseconds() as u64),
+                        latency_ms: Some(latency_ms),
                         time_to_first_token_ms: None,
                         was_cached: None,
                         finish_reason: Some(FinishReason::Stop),
@@ -1187,11 +2353,186 @@ impl HttpDecoder {
         }
     }
 
+    /// Feed Ollama NDJSON stream data and emit a response once `done` is seen
+    #[allow(clippy::too_many_arguments)]
+    fn handle_ollama_stream(
+        &self,
+        key: &CorrelationKey,
+        pending_req: &PendingRequest,
+        data: &[u8],
+        body_ended: bool,
+        signals: &mut DecodeSignals,
+        raw: &RawCaptureEvent,
+        events: &mut Vec<OispEvent>,
+    ) {
+        let mut reassemblers = self.ollama_reassemblers.write().unwrap();
+        let reassembler = reassemblers.entry(key.clone()).or_default();
+        reassembler.feed(data);
+
+        if !reassembler.is_complete() {
+            if !body_ended {
+                return;
+            }
+            signals.truncated = true;
+        }
+
+        let merged = reassembler.to_response();
+        reassemblers.remove(key);
+        self.pending_requests.write().unwrap().remove(key);
+
+        let Some(mut response_data) = merged
+            .as_ref()
+            .and_then(|body| parse_ollama_response(body, &pending_req.request_id))
+        else {
+            self.decode_failed(DecodeFailure::AiStream, "ollama", &pending_req.request_id);
+            return;
+        };
+
+        let envelope = self.create_ai_envelope(raw, "ai.response", signals);
+        let mut envelope = if let Some(ref ctx) = pending_req.web_context {
+            envelope.with_web_context(ctx.clone())
+        } else {
+            envelope
+        };
+        let latency_ms = pending_req.latency_ms(&mut envelope);
+
+        response_data.provider = pending_req.request_data.provider.clone();
+        response_data.status_code = Some(200);
+        response_data.latency_ms = Some(latency_ms);
+
+        events.push(OispEvent::AiResponse(AiResponseEvent {
+            envelope,
+            data: response_data,
+        }));
+    }
+
+    /// Feed Gemini stream data and emit a response once it finishes
+    #[allow(clippy::too_many_arguments)]
+    fn handle_gemini_stream(
+        &self,
+        key: &CorrelationKey,
+        pending_req: &PendingRequest,
+        data: &[u8],
+        body_ended: bool,
+        signals: &mut DecodeSignals,
+        raw: &RawCaptureEvent,
+        events: &mut Vec<OispEvent>,
+    ) {
+        let mut reassemblers = self.gemini_reassemblers.write().unwrap();
+        let reassembler = reassemblers.entry(key.clone()).or_default();
+        reassembler.feed(data);
+
+        if !reassembler.is_complete() {
+            if !body_ended {
+                return;
+            }
+            signals.truncated = true;
+        }
+
+        let merged = reassembler.to_response();
+        reassemblers.remove(key);
+        self.pending_requests.write().unwrap().remove(key);
+
+        let Some(mut response_data) = merged
+            .as_ref()
+            .and_then(|body| parse_gemini_response(body, &pending_req.request_id))
+        else {
+            self.decode_failed(DecodeFailure::AiStream, "google", &pending_req.request_id);
+            return;
+        };
+
+        let envelope = self.create_ai_envelope(raw, "ai.response", signals);
+        let mut envelope = if let Some(ref ctx) = pending_req.web_context {
+            envelope.with_web_context(ctx.clone())
+        } else {
+            envelope
+        };
+        let latency_ms = pending_req.latency_ms(&mut envelope);
+
+        response_data.provider = pending_req.request_data.provider.clone();
+        response_data.status_code = Some(200);
+        response_data.latency_ms = Some(latency_ms);
+
+        events.push(OispEvent::AiResponse(AiResponseEvent {
+            envelope,
+            data: response_data,
+        }));
+    }
+
+    /// Feed Bedrock event stream data and emit a response once it finishes
+    #[allow(clippy::too_many_arguments)]
+    fn handle_bedrock_stream(
+        &self,
+        key: &CorrelationKey,
+        pending_req: &PendingRequest,
+        data: &[u8],
+        body_ended: bool,
+        signals: &mut DecodeSignals,
+        raw: &RawCaptureEvent,
+        events: &mut Vec<OispEvent>,
+    ) {
+        let mut reassemblers = self.bedrock_reassemblers.write().unwrap();
+        let reassembler = reassemblers.entry(key.clone()).or_default();
+        reassembler.feed(data);
+
+        if !reassembler.is_complete() {
+            if !body_ended {
+                return;
+            }
+            signals.truncated = true;
+        }
+
+        // A stream that failed part way still answered 200
+        let response_data = match reassembler.exception() {
+            Some((error_type, message)) => Some(parse_error_response(
+                &serde_json::json!({ "error": { "type": error_type, "message": message } }),
+                &pending_req.request_id,
+                pending_req.provider,
+                200,
+            )),
+            None => reassembler
+                .to_response()
+                .and_then(|body| parse_bedrock_response(&body, &pending_req.request_id)),
+        };
+        reassemblers.remove(key);
+        self.pending_requests.write().unwrap().remove(key);
+
+        let Some(mut response_data) = response_data else {
+            self.decode_failed(
+                DecodeFailure::AiStream,
+                "awsbedrock",
+                &pending_req.request_id,
+            );
+            return;
+        };
+
+        let envelope = self.create_ai_envelope(raw, "ai.response", signals);
+        let mut envelope = if let Some(ref ctx) = pending_req.web_context {
+            envelope.with_web_context(ctx.clone())
+        } else {
+            envelope
+        };
+        let latency_ms = pending_req.latency_ms(&mut envelope);
+
+        response_data.provider = pending_req.request_data.provider.clone();
+        if response_data.model.is_none() {
+            response_data.model = pending_req.request_data.model.clone();
+        }
+        response_data.status_code = Some(200);
+        response_data.latency_ms = Some(latency_ms);
+
+        events.push(OispEvent::AiResponse(AiResponseEvent {
+            envelope,
+            data: response_data,
+        }));
+    }
+
     fn handle_complete_response(
         &self,
         key: &CorrelationKey,
         pending_req: &PendingRequest,
         http_resp: &crate::http::ParsedHttpResponse,
+        signals: &mut DecodeSignals,
         raw: &RawCaptureEvent,
         events: &mut Vec<OispEvent>,
     ) {
@@ -1223,10 +2564,36 @@ impl HttpDecoder {
 
         info!("handle_complete_response: JSON parsed successfully");
 
-        // Detect provider from body or use the one from request
-        let provider = detect_provider_from_body(&json).unwrap_or(pending_req.provider);
+        signals.check_provider(pending_req.provider, &json);
+
+        // Detect provider from body or use the one from request. Local runtimes
+        // and OpenAI-compatible servers serve models from many vendors, so the
+        // body would misattribute them.
+        let serves_many = pending_req.provider.is_local()
+            || matches!(
+                pending_req.provider,
+                Provider::OpenAICompatible | Provider::AwsBedrock
+            );
+        let provider = if serves_many {
+            pending_req.provider
+        } else {
+            detect_provider_from_body(&json).unwrap_or(pending_req.provider)
+        };
 
+        let failed = !(200..300).contains(&http_resp.status_code);
         let response_data = match provider {
+            _ if failed => Some(parse_error_response(
+                &json,
+                &pending_req.request_id,
+                provider,
+                http_resp.status_code,
+            )),
+            _ if pending_req.ollama_native => parse_ollama_response(&json, &pending_req.request_id),
+            _ if pending_req.gemini => parse_gemini_response(&json, &pending_req.request_id),
+            _ if pending_req.bedrock => parse_bedrock_response(&json, &pending_req.request_id),
+            _ if is_responses_api_response(&json) => {
+                parse_responses_response(&json, &pending_req.request_id, provider)
+            }
             Provider::Anthropic => parse_anthropic_response(&json, &pending_req.request_id),
             _ => parse_ai_response(&json, &pending_req.request_id, provider),
         };
@@ -1234,35 +2601,67 @@ impl HttpDecoder {
         let response_data = match response_data {
             Some(data) => data,
             None => {
-                trace!("Failed to parse AI response data");
+                self.decode_failed(
+                    DecodeFailure::AiResponse,
+                    &format!("{:?}", provider).to_lowercase(),
+                    &pending_req.request_id,
+                );
                 return;
             }
         };
 
-        let envelope = self.create_envelope(raw, "ai.response");
+        let envelope = self.create_ai_envelope(raw, "ai.response", signals);
         // Add web context from pending request
-        let envelope = if let Some(ref ctx) = pending_req.web_context {
+        let mut envelope = if let Some(ref ctx) = pending_req.web_context {
             envelope.with_web_context(ctx.clone())
         } else {
             envelope
         };
-        let latency = envelope.ts - pending_req.timestamp;
+        let latency_ms = pending_req.latency_ms(&mut envelope);
 
         let mut response_data = response_data;
-        response_data.latency_ms = Some(latency.num_milliseconds() as u64);
+        // Vertex AI's project and region are only known from the request
+        if pending_req.detection != ProviderDetection::KnownEndpoint
+            || pending_req.gemini
+            || pending_req.bedrock
+        {
+            response_data.provider = pending_req.request_data.provider.clone();
+        }
+        // Bedrock only names the model in the path, except for Anthropic models
+        if pending_req.bedrock && response_data.model.is_none() {
+            response_data.model = pending_req.request_data.model.clone();
+        }
+        response_data.latency_ms = Some(latency_ms);
         response_data.status_code = Some(http_resp.status_code);
 
         debug!(
             "Parsed AI response: status={}, latency={}ms, has_web_context={}",
             http_resp.status_code,
-            latency.num_milliseconds(),
+            latency_ms,
             pending_req.web_context.is_some()
         );
 
+        // Successful embeddings also get an ai.embedding summary
+        let embedding = (pending_req.request_data.request_type == Some(RequestType::Embedding)
+            && (200..300).contains(&http_resp.status_code))
+        .then(|| {
+            let mut data = parse_embedding_response(&json, &response_data);
+            if data.model.is_none() {
+                data.model = pending_req.request_data.model.clone();
+            }
+            let envelope = self.create_ai_envelope(raw, "ai.embedding", signals);
+            let envelope = match &pending_req.web_context {
+                Some(ctx) => envelope.with_web_context(ctx.clone()),
+                None => envelope,
+            };
+            OispEvent::AiEmbedding(AiEmbeddingEvent { envelope, data })
+        });
+
         events.push(OispEvent::AiResponse(AiResponseEvent {
             envelope,
             data: response_data,
         }));
+        events.extend(embedding);
 
         // Cleanup
         self.pending_requests.write().unwrap().remove(key);
@@ -1292,13 +2691,93 @@ impl HttpDecoder {
     }
 
     fn decode_network_connect(&self, raw: &RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
-        let envelope = self.create_envelope(raw, "network.connect");
+        if !self.sni_extraction {
+            return Ok(vec![self.network_connect_event(raw, None)]);
+        }
+
+        let mut events = self.maybe_cleanup(None);
+
+        // Hold the connect until the first outbound record tells us the SNI.
+        // A reused key means the previous connection never sent anything.
+        let key = CorrelationKey::from_event(raw).without_tid();
+        let previous = self.pending_connects.write().unwrap().insert(
+            key,
+            PendingConnect {
+                raw: raw.clone(),
+                buffer: Vec::new(),
+                created_at: self.clock.now(),
+            },
+        );
+
+        events.extend(previous.map(|p| self.network_connect_event(&p.raw, None)));
+        Ok(events)
+    }
+
+    fn decode_network_send(&self, raw: &RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
+        let key = CorrelationKey::from_event(raw).without_tid();
+        let mut connects = self.pending_connects.write().unwrap();
+
+        let Some(pending) = connects.get_mut(&key) else {
+            return Ok(Vec::new());
+        };
+        pending.buffer.extend_from_slice(&raw.data);
+
+        let tls = match parse_client_hello(&pending.buffer) {
+            ClientHelloParse::Complete(info) => Some(info),
+            ClientHelloParse::NotClientHello => None,
+            ClientHelloParse::Incomplete if pending.buffer.len() > MAX_CLIENT_HELLO_LEN => None,
+            ClientHelloParse::Incomplete => return Ok(Vec::new()),
+        };
+
+        let pending = connects.remove(&key).expect("pending connect present");
+        Ok(vec![self.network_connect_event(&pending.raw, tls)])
+    }
+
+    /// Report connects held longer than [`SNI_HOLD_TIMEOUT`] (all of them
+    /// with `all`) without the SNI
+    fn release_connects(&self, mono: &MonoClock, all: bool) -> Vec<OispEvent> {
+        let now = self.clock.now();
+        let mut connects = self.pending_connects.write().unwrap();
+        let due: Vec<CorrelationKey> = connects
+            .iter()
+            .filter(|(_, c)| all || now.duration_since(c.created_at) >= SNI_HOLD_TIMEOUT)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.iter()
+            .filter_map(|key| connects.remove(key))
+            .map(|pending| {
+                let mut event = self.network_connect_event(&pending.raw, None);
+                mono.stamp_decoded(&pending.raw, event.envelope_mut());
+                event
+            })
+            .collect()
+    }
+
+    fn network_connect_event(
+        &self,
+        raw: &RawCaptureEvent,
+        tls: Option<ClientHelloInfo>,
+    ) -> OispEvent {
+        let mut envelope = self.create_envelope(raw, "network.connect");
+        let sni = tls.as_ref().and_then(|t| t.sni.clone());
+
+        if let Some(provider_id) = sni.as_deref().and_then(|host| {
+            self.spec_registry()
+                .detect_from_domain(host)
+                .map(str::to_string)
+        }) {
+            debug!("SNI {:?} attributed to provider '{}'", sni, provider_id);
+            envelope
+                .attrs
+                .insert("ai_provider".to_string(), provider_id.into());
+            envelope.confidence.ai_detection_method = Some("tls_sni".to_string());
+        }
 
         let data = NetworkConnectData {
             dest: Endpoint {
                 ip: raw.metadata.remote_addr.clone(),
                 port: raw.metadata.remote_port,
-                domain: None,
+                domain: sni.clone(),
                 is_private: None,
                 geo: None,
             },
@@ -1313,13 +2792,46 @@ impl HttpDecoder {
             success: Some(true),
             error: None,
             latency_ms: None,
-            tls: None,
+            tls: tls.map(|info| TlsInfo {
+                version: info.version,
+                cipher_suite: None,
+                sni,
+                alpn: info.alpn.into_iter().next(),
+                certificate: None,
+                ja3_fingerprint: None,
+                ja3s_fingerprint: None,
+            }),
         };
 
-        Ok(vec![OispEvent::NetworkConnect(NetworkConnectEvent {
-            envelope,
-            data,
-        })])
+        OispEvent::NetworkConnect(NetworkConnectEvent { envelope, data })
+    }
+
+    /// Feed a raw event to the flow tracker, returning flows that ended
+    fn track_flows(&self, raw: &RawCaptureEvent) -> Vec<OispEvent> {
+        let Some(flows) = &self.flows else {
+            return Vec::new();
+        };
+        let closed = flows.write().unwrap().observe(raw, self.clock.now());
+        closed
+            .into_iter()
+            .map(|flow| self.network_flow_event(flow))
+            .collect()
+    }
+
+    fn network_flow_event(&self, flow: ClosedFlow) -> OispEvent {
+        let raw = RawCaptureEvent {
+            id: ulid::Ulid::new().to_string(),
+            timestamp_ns: flow.last_ns,
+            kind: RawEventKind::Other("network.flow".to_string()),
+            pid: flow.pid,
+            tid: None,
+            data: Vec::new(),
+            metadata: flow.metadata,
+        };
+        OispEvent::NetworkFlow(NetworkFlowEvent {
+            envelope: self.create_envelope(&raw, "network.flow"),
+            data: flow.data,
+        })
     }
 
     fn create_envelope(&self, raw: &RawCaptureEvent, event_type: &str) -> EventEnvelope {
@@ -1358,17 +2870,35 @@ impl HttpDecoder {
                 RawEventKind::SslRead => Some("ssl_read".to_string()),
                 _ => None,
             },
-            sensor_host: None,
+            ..Default::default()
         };
 
-        envelope.confidence = Confidence {
-            level: ConfidenceLevel::High,
-            completeness: Completeness::Full,
-            reasons: vec!["tls_boundary_capture".to_string()],
-            content_source: Some("tls_boundary".to_string()),
-            ai_detection_method: Some("known_endpoint".to_string()),
-        };
+        envelope.confidence = DecodeSignals::default().confidence();
+
+        envelope
+    }
 
+    /// Create an envelope for an AI event, scoring confidence from decode signals
+    fn create_ai_envelope(
+        &self,
+        raw: &RawCaptureEvent,
+        event_type: &str,
+        signals: &DecodeSignals,
+    ) -> EventEnvelope {
+        let mut envelope = self.create_envelope(raw, event_type);
+        envelope.confidence = signals.confidence();
+
+        // Destination, when the capture knows the connection the data went through
+        if let Some(addr) = &raw.metadata.remote_addr {
+            envelope
+                .attrs
+                .insert("server.address".to_string(), addr.clone().into());
+        }
+        if let Some(port) = raw.metadata.remote_port {
+            envelope
+                .attrs
+                .insert("server.port".to_string(), port.into());
+        }
         envelope
     }
 
@@ -1378,6 +2908,13 @@ impl HttpDecoder {
             pending_requests: self.pending_requests.read().unwrap().len(),
             stream_reassemblers: self.stream_reassemblers.read().unwrap().len(),
             anthropic_reassemblers: self.anthropic_reassemblers.read().unwrap().len(),
+            ollama_reassemblers: self.ollama_reassemblers.read().unwrap().len(),
+            gemini_reassemblers: self.gemini_reassemblers.read().unwrap().len(),
+            bedrock_reassemblers: self.bedrock_reassemblers.read().unwrap().len(),
+            pending_retrievals: self.pending_retrievals.read().unwrap().len(),
+            provider_cache_entries: self.provider_cache.len(),
+            provider_cache_hits: self.provider_cache.hits(),
+            provider_detections: self.provider_cache.detections(),
         }
     }
 }
@@ -1388,6 +2925,68 @@ pub struct DecoderStats {
     pub pending_requests: usize,
     pub stream_reassemblers: usize,
     pub anthropic_reassemblers: usize,
+    pub ollama_reassemblers: usize,
+    pub gemini_reassemblers: usize,
+    pub bedrock_reassemblers: usize,
+    /// Vector store queries awaiting results
+    pub pending_retrievals: usize,
+    /// Hosts with a cached provider detection result
+    pub provider_cache_entries: usize,
+    /// Requests whose provider came from the cache
+    pub provider_cache_hits: u64,
+    /// Requests that ran full provider detection
+    pub provider_detections: u64,
+}
+
+/// `agent.rag_retrieve` for a vector store query
+///
+/// With a response, the event is timed at the response and carries the
+/// latency; `retrieved` is absent when the results could not be read.
+fn rag_retrieve_event(
+    pending: PendingRetrieval,
+    response: Option<&RawCaptureEvent>,
+    retrieved: Option<Retrieved>,
+) -> OispEvent {
+    let PendingRetrieval {
+        query,
+        mut envelope,
+        ..
+    } = pending;
+    let requested = EventTime::of(&envelope);
+    let latency_ms = response.map(|raw| {
+        envelope.ts = chrono::Utc::now();
+        envelope.ts_mono = Some(raw.timestamp_ns);
+        envelope.source.capture_point = Some("ssl_read".to_string());
+        envelope.elapsed_since(&requested)
+    });
+    for (name, value) in query.attrs() {
+        envelope.attrs.insert(name.to_string(), value);
+    }
+
+    let (results_count, results) = match retrieved {
+        Some(r) => (Some(r.count), r.results),
+        None => (None, Vec::new()),
+    };
+    OispEvent::AgentRagRetrieve(AgentRagRetrieveEvent {
+        envelope,
+        data: AgentRagRetrieveData {
+            agent: query.framework.clone().map(|framework| AgentInfo {
+                name: None,
+                agent_type: None,
+                version: None,
+                framework: Some(framework),
+                session_id: None,
+                task_id: None,
+            }),
+            source: Some(query.source()),
+            query_hash: query.query_hash(),
+            query: query.query,
+            results_count,
+            results,
+            latency_ms,
+            tokens_retrieved: None,
+        },
+    })
 }
 
 impl Default for HttpDecoder {
@@ -1428,28 +3027,56 @@ impl Plugin for HttpDecoder {
 #[async_trait]
 impl DecodePlugin for HttpDecoder {
     fn can_decode(&self, raw: &RawCaptureEvent) -> bool {
-        matches!(
-            raw.kind,
+        match raw.kind {
             RawEventKind::SslWrite
-                | RawEventKind::SslRead
-                | RawEventKind::ProcessExec
-                | RawEventKind::NetworkConnect
-        )
+            | RawEventKind::SslRead
+            | RawEventKind::ProcessExec
+            | RawEventKind::NetworkConnect => true,
+            RawEventKind::NetworkSend => self.sni_extraction,
+            // Socket closes end flows; other closes are left to SystemDecoder
+            RawEventKind::FileClose => match (&self.flows, raw.metadata.fd) {
+                (Some(flows), Some(fd)) => flows.read().unwrap().is_socket(raw.pid, fd),
+                _ => false,
+            },
+            _ => false,
+        }
     }
 
     async fn decode(&self, raw: RawCaptureEvent) -> PluginResult<Vec<OispEvent>> {
-        match raw.kind {
+        let flows = self.track_flows(&raw);
+        let mut events = match raw.kind {
             RawEventKind::SslWrite => self.decode_ssl_write(&raw),
             RawEventKind::SslRead => self.decode_ssl_read(&raw),
             RawEventKind::ProcessExec => self.decode_process_exec(&raw),
             RawEventKind::NetworkConnect => self.decode_network_connect(&raw),
+            RawEventKind::NetworkSend => self.decode_network_send(&raw),
             _ => Ok(Vec::new()),
+        }?;
+        for event in &mut events {
+            self.normalize_model(event);
         }
+        events.extend(flows);
+        Ok(events)
     }
 
     fn priority(&self) -> i32 {
         100 // High priority for HTTP decoder
     }
+
+    fn tick(&self, mono: &MonoClock) -> Vec<OispEvent> {
+        self.failures.flush_due(self.clock.now());
+        // Requests time out even when no further traffic is decoded
+        let mut events = self.maybe_cleanup(Some(mono));
+        events.extend(self.release_connects(mono, false));
+        events
+    }
+
+    fn flush(&self, mono: &MonoClock) -> Vec<OispEvent> {
+        self.failures.flush_all();
+        let mut events = self.expire_pending(true, Some(mono));
+        events.extend(self.release_connects(mono, true));
+        events
+    }
 }
 
 #[cfg(test)]
@@ -1501,10 +3128,418 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_decode_openai_response() {
+    async fn test_request_carries_known_destination() {
         let decoder = HttpDecoder::new();
-
-        // First send request
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"messages\":[]}";
+
+        let mut raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        raw.metadata.remote_addr = Some("104.18.7.192".to_string());
+        raw.metadata.remote_port = Some(443);
+        let events = decoder.decode(raw).await.unwrap();
+        let attrs = &events[0].envelope().attrs;
+        assert_eq!(attrs["server.address"], "104.18.7.192");
+        assert_eq!(attrs["server.port"], 443);
+
+        // Unknown destination: no attributes
+        let raw = create_raw_event(RawEventKind::SslWrite, request, 1235);
+        let events = decoder.decode(raw).await.unwrap();
+        assert!(!events[0].envelope().attrs.contains_key("server.address"));
+    }
+
+    #[tokio::test]
+    async fn test_decode_multipart_transcription_request() {
+        let decoder = HttpDecoder::new();
+
+        // As sent by the OpenAI Python SDK for audio.transcriptions.create
+        let boundary = "3c8f6a1d0d9b4e7c9a2f5b6e8d1c0a47";
+        let audio: Vec<u8> = (0..2 * 1024 * 1024u32).map(|i| (i % 253) as u8).collect();
+        let mut body = format!(
+            "--{b}\r\n\
+             Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+             whisper-1\r\n\
+             --{b}\r\n\
+             Content-Disposition: form-data; name=\"response_format\"\r\n\r\n\
+             json\r\n\
+             --{b}\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"standup.m4a\"\r\n\
+             Content-Type: audio/mp4\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        body.extend_from_slice(&audio);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let mut request = format!(
+            "POST /v1/audio/transcriptions HTTP/1.1\r\n\
+             Host: api.openai.com\r\n\
+             Accept: application/json\r\n\
+             Content-Type: multipart/form-data; boundary={}\r\n\
+             User-Agent: OpenAI/Python 1.54.3\r\n\
+             Content-Length: {}\r\n\
+             \r\n",
+            boundary,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(&body);
+
+        let mut events = Vec::new();
+        for write in request.chunks(16 * 1024) {
+            let raw = create_raw_event(RawEventKind::SslWrite, write, 1234);
+            events.extend(decoder.decode(raw).await.unwrap());
+
+            // The upload is parsed as it arrives rather than buffered
+            let partial = decoder.partial_requests.read().unwrap();
+            assert!(partial.values().all(|r| r.buffer.len() < 1024));
+        }
+
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        assert_eq!(req.data.model.as_ref().unwrap().id, "whisper-1");
+        assert!(matches!(req.data.request_type, Some(RequestType::Audio)));
+        assert_eq!(
+            req.data.provider.as_ref().unwrap().endpoint.as_deref(),
+            Some("https://api.openai.com/v1/audio/transcriptions")
+        );
+        assert_eq!(req.data.has_images, Some(false));
+
+        let summary = &req.envelope.attrs[crate::ai::MULTIPART_ATTR];
+        assert_eq!(
+            summary["fields"],
+            serde_json::json!(["model", "response_format"])
+        );
+        assert_eq!(
+            summary["files"],
+            serde_json::json!([{
+                "field": "file",
+                "filename": "standup.m4a",
+                "content_type": "audio/mp4",
+                "size": audio.len()
+            }])
+        );
+        assert_eq!(decoder.stats().pending_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_type_from_endpoint() {
+        let decoder = HttpDecoder::new();
+        let cases = [
+            (
+                "/v1/embeddings",
+                r#"{"model":"text-embedding-3-small","input":["a","b"]}"#,
+                RequestType::Embedding,
+            ),
+            (
+                "/v1/moderations",
+                r#"{"model":"omni-moderation-latest","input":"Hello"}"#,
+                RequestType::Moderation,
+            ),
+            (
+                "/v1/images/generations",
+                r#"{"model":"dall-e-3","prompt":"A cat","n":1}"#,
+                RequestType::Image,
+            ),
+            (
+                "/v1/audio/speech",
+                r#"{"model":"tts-1","input":"Hello","voice":"alloy"}"#,
+                RequestType::Audio,
+            ),
+            (
+                "/v1/completions",
+                r#"{"model":"gpt-3.5-turbo-instruct","prompt":"Hello"}"#,
+                RequestType::Completion,
+            ),
+            (
+                "/v1/chat/completions",
+                r#"{"model":"gpt-4o","messages":[]}"#,
+                RequestType::Chat,
+            ),
+        ];
+
+        for (pid, (path, body, expected)) in (1000..).zip(cases) {
+            let request = format!(
+                "POST {} HTTP/1.1\r\n\
+                 Host: api.openai.com\r\n\
+                 Content-Type: application/json\r\n\
+                 \r\n\
+                 {}",
+                path, body
+            );
+            let events = decoder
+                .decode(create_raw_event(
+                    RawEventKind::SslWrite,
+                    request.as_bytes(),
+                    pid,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(events.len(), 1, "{}", path);
+            let OispEvent::AiRequest(req) = &events[0] else {
+                panic!("Expected AiRequest event for {}", path);
+            };
+            assert_eq!(req.data.request_type, Some(expected), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedding_response_emits_embedding_event() {
+        let decoder = HttpDecoder::new();
+        let request = b"POST /v1/embeddings HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"text-embedding-3-small\",\"input\":[\"a\",\"b\"]}";
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"object\":\"list\",\"model\":\"text-embedding-3-small\",\
+                         \"data\":[{\"index\":0,\"embedding\":[0.1,0.2,0.3]},\
+                         {\"index\":1,\"embedding\":[0.4,0.5,0.6]}],\
+                         \"usage\":{\"prompt_tokens\":4,\"total_tokens\":4}}";
+
+        decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 2);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        let OispEvent::AiEmbedding(embedding) = &events[1] else {
+            panic!("Expected AiEmbedding event");
+        };
+        assert_eq!(embedding.envelope.event_type, "ai.embedding");
+        assert_eq!(embedding.data.provider.as_ref().unwrap().name, "openai");
+        assert_eq!(
+            embedding.data.model.as_ref().unwrap().id,
+            "text-embedding-3-small"
+        );
+        assert_eq!(embedding.data.input_count, Some(2));
+        assert_eq!(embedding.data.dimensions, Some(3));
+        assert_eq!(embedding.data.total_tokens, Some(4));
+        assert_eq!(embedding.data.latency_ms, resp.data.latency_ms);
+
+        // Failed embeddings only get the response
+        let error = b"HTTP/1.1 400 Bad Request\r\n\
+                      Content-Type: application/json\r\n\
+                      \r\n\
+                      {\"error\":{\"message\":\"bad input\",\"type\":\"invalid_request_error\"}}";
+        decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 4321))
+            .await
+            .unwrap();
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, error, 4321))
+            .await
+            .unwrap();
+        assert!(events
+            .iter()
+            .all(|e| !matches!(e, OispEvent::AiEmbedding(_))));
+    }
+
+    #[tokio::test]
+    async fn test_decode_chunked_request() {
+        let decoder = HttpDecoder::new();
+
+        let body = r#"{"model":"gpt-4","messages":[{"role":"user","content":"Hello"}]}"#;
+        let (first, second) = body.split_at(20);
+        let head = format!(
+            "POST /v1/chat/completions HTTP/1.1\r\n\
+             Host: api.openai.com\r\n\
+             Content-Type: application/json\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             {:x}\r\n{}\r\n",
+            first.len(),
+            first
+        );
+        let tail = format!("{:x}\r\n{}\r\n0\r\n\r\n", second.len(), second);
+
+        // Headers and first chunk, then the rest in a separate write
+        let events = decoder
+            .decode(create_raw_event(
+                RawEventKind::SslWrite,
+                head.as_bytes(),
+                1234,
+            ))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+        assert_eq!(decoder.partial_requests.read().unwrap().len(), 1);
+
+        let events = decoder
+            .decode(create_raw_event(
+                RawEventKind::SslWrite,
+                tail.as_bytes(),
+                1234,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            assert_eq!(req.data.model.as_ref().unwrap().id, "gpt-4");
+            assert_eq!(req.data.messages.len(), 1);
+        } else {
+            panic!("Expected AiRequest event");
+        }
+        assert_eq!(decoder.partial_requests.read().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expect_100_continue() {
+        let decoder = HttpDecoder::new();
+        let body = r#"{"model":"gpt-4","messages":[{"role":"user","content":"Hello"}]}"#;
+        let head = format!(
+            "POST /v1/chat/completions HTTP/1.1\r\n\
+             Host: api.openai.com\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Expect: 100-continue\r\n\
+             \r\n",
+            body.len()
+        );
+        let decode = |kind, data: &[u8]| decoder.decode(create_raw_event(kind, data, 1234));
+
+        // Headers, then the interim response, then the body
+        assert!(decode(RawEventKind::SslWrite, head.as_bytes())
+            .await
+            .unwrap()
+            .is_empty());
+        let interim = decode(RawEventKind::SslRead, b"HTTP/1.1 100 Continue\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(interim.is_empty());
+        assert!(decoder.partial_responses.read().unwrap().is_empty());
+
+        let events = decode(RawEventKind::SslWrite, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        assert_eq!(req.data.messages.len(), 1);
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-1\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\
+                         \"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+        let events = decode(RawEventKind::SslRead, response).await.unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.request_id, req.data.request_id);
+        assert_eq!(resp.data.status_code, Some(200));
+
+        // Without a length, headers with `Expect` still wait for the body,
+        // and an interim response in the same read as the final one is
+        // skipped
+        let head = "POST /v1/chat/completions HTTP/1.1\r\n\
+                    Host: api.openai.com\r\n\
+                    Expect: 100-continue\r\n\
+                    \r\n";
+        assert!(decode(RawEventKind::SslWrite, head.as_bytes())
+            .await
+            .unwrap()
+            .is_empty());
+        let events = decode(RawEventKind::SslWrite, body.as_bytes())
+            .await
+            .unwrap();
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        let both = [b"HTTP/1.1 100 Continue\r\n\r\n".as_slice(), response].concat();
+        let events = decode(RawEventKind::SslRead, &both).await.unwrap();
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.request_id, req.data.request_id);
+        assert_eq!(resp.data.status_code, Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_decode_request_with_content_limits() {
+        let decoder = HttpDecoder::new().with_content_limits(ContentLimits {
+            max_content_chars: 5,
+            hash_after_redaction: false,
+            redaction: Default::default(),
+        });
+
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello world\"}]}";
+
+        let raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        let events = decoder.decode(raw).await.unwrap();
+
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        let message = &req.data.messages[0];
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hello"));
+        assert_eq!(
+            message.content_hash.as_deref(),
+            Some(crate::ai::hash_content("Hello world").as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latency_uses_capture_clock_and_clamps_skew() {
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-123\",\"model\":\"gpt-4\",\"choices\":[]}";
+
+        async fn response_after(request: &[u8], response: &[u8], delta_ns: i64) -> AiResponseEvent {
+            let decoder = HttpDecoder::new();
+            let mut raw_req = create_raw_event(RawEventKind::SslWrite, request, 1234);
+            raw_req.timestamp_ns = 5_000_000_000;
+            decoder.decode(raw_req).await.unwrap();
+
+            let mut raw_resp = create_raw_event(RawEventKind::SslRead, response, 1234);
+            raw_resp.timestamp_ns = (5_000_000_000 + delta_ns) as u64;
+            match decoder.decode(raw_resp).await.unwrap().remove(0) {
+                OispEvent::AiResponse(resp) => resp,
+                other => panic!("Expected AiResponse event, got {}", other.event_type()),
+            }
+        }
+
+        // Decoded back to back, so the wall clock alone would say ~0ms
+        let resp = response_after(request, response, 750_000_000).await;
+        assert_eq!(resp.data.latency_ms, Some(750));
+        assert!(!resp.envelope.attrs.contains_key(CLOCK_SKEW_ATTR));
+
+        let resp = response_after(request, response, -20_000_000).await;
+        assert_eq!(resp.data.latency_ms, Some(0));
+        assert_eq!(resp.envelope.attrs[CLOCK_SKEW_ATTR], 20);
+    }
+
+    #[tokio::test]
+    async fn test_decode_openai_response() {
+        let decoder = HttpDecoder::new();
+
+        // First send request
         let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
                         Host: api.openai.com\r\n\
                         Content-Type: application/json\r\n\
@@ -1539,24 +3574,1478 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_decode_anthropic_request() {
+    async fn test_orphan_response_with_attach_existing() {
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-123\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"message\":{\"role\":\"assistant\",\"content\":\"Hi!\"},\"finish_reason\":\"stop\"}]}";
+        // Connection seeded at capture start, its request never seen
+        let orphan = |data: &[u8], addr: &str, port: u16| {
+            let mut raw = create_raw_event(RawEventKind::SslRead, data, 1234);
+            raw.metadata.remote_addr = Some(addr.to_string());
+            raw.metadata.remote_port = Some(port);
+            raw
+        };
+
+        // Dropped by default
+        let events = HttpDecoder::new()
+            .decode(orphan(response, "104.18.7.192", 443))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        let decoder = HttpDecoder::new()
+            .with_attach_existing(true)
+            .with_provider_hosts(HashMap::from([(
+                "10.0.0.5:8000".to_string(),
+                "vllm".to_string(),
+            )]));
+
+        // Unknown address: the body names the provider
+        let events = decoder
+            .decode(orphan(response, "104.18.7.192", 443))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        let confidence = &resp.envelope.confidence;
+        assert_eq!(confidence.level, ConfidenceLevel::Low);
+        assert_eq!(confidence.completeness, Completeness::Partial);
+        assert!(confidence.reasons.contains(&"orphan_response".to_string()));
+        assert_eq!(
+            confidence.ai_detection_method.as_deref(),
+            Some("response_body")
+        );
+        assert_eq!(resp.data.provider.as_ref().unwrap().name, "openai");
+        assert_eq!(resp.data.status_code, Some(200));
+        assert_eq!(resp.data.choices.len(), 1);
+        assert!(!resp.data.request_id.is_empty());
+        assert!(resp.data.latency_ms.is_none());
+
+        // Mapped destination
+        let events = decoder
+            .decode(orphan(response, "10.0.0.5", 8000))
+            .await
+            .unwrap();
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.provider.as_ref().unwrap().name, "vllm");
+        assert_eq!(
+            resp.envelope.confidence.ai_detection_method.as_deref(),
+            Some("configured_host")
+        );
+        assert_eq!(resp.envelope.confidence.level, ConfidenceLevel::Low);
+
+        // Other JSON traffic is left alone
+        let other = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: application/json\r\n\
+                      \r\n\
+                      {\"ok\":true}";
+        let events = decoder
+            .decode(orphan(other, "140.82.112.3", 443))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+
+    fn openai_request() -> &'static [u8] {
+        b"POST /v1/chat/completions HTTP/1.1\r\n\
+          Host: api.openai.com\r\n\
+          Content-Type: application/json\r\n\
+          \r\n\
+          {\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}"
+    }
+
+    #[tokio::test]
+    async fn test_network_flow_on_socket_close() {
+        let decoder = HttpDecoder::new().with_network_flows(Duration::from_secs(60));
+
+        let mut connect = create_raw_event(RawEventKind::NetworkConnect, b"", 1234);
+        connect.metadata.remote_addr = Some("104.18.7.192".to_string());
+        connect.metadata.remote_port = Some(443);
+        let events = decoder.decode(connect).await.unwrap();
+        assert!(matches!(events[..], [OispEvent::NetworkConnect(_)]));
+
+        let request = create_raw_event(RawEventKind::SslWrite, openai_request(), 1234);
+        let request_len = request.data.len() as u64;
+        decoder.decode(request).await.unwrap();
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+        decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+
+        // Closing an unrelated fd is not claimed
+        let mut other = create_raw_event(RawEventKind::FileClose, b"", 1234);
+        other.metadata.fd = Some(6);
+        assert!(!decoder.can_decode(&other));
+
+        let close = create_raw_event(RawEventKind::FileClose, b"", 1234);
+        assert!(decoder.can_decode(&close));
+        let events = decoder.decode(close).await.unwrap();
+        let [OispEvent::NetworkFlow(flow)] = &events[..] else {
+            panic!("expected a network.flow event, got {:?}", events);
+        };
+        assert_eq!(flow.envelope.event_type, "network.flow");
+        assert_eq!(flow.envelope.process.as_ref().unwrap().pid, 1234);
+        assert_eq!(flow.data.bytes_sent, Some(request_len));
+        assert_eq!(flow.data.bytes_received, Some(response.len() as u64));
+        assert_eq!(flow.data.dest.port, Some(443));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_decode_failures_logged_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        /// Counts warnings from this crate
+        #[derive(Clone, Default)]
+        struct WarnCounter(Arc<AtomicUsize>);
+
+        impl<S: tracing::Subscriber> Layer<S> for WarnCounter {
+            fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+                let meta = event.metadata();
+                if *meta.level() == tracing::Level::WARN && meta.target().starts_with("oisp_decode")
+                {
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let counter = WarnCounter::default();
+        let subscriber = tracing_subscriber::registry().with(counter.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let clock = Arc::new(crate::clock::MockClock::new());
+        let metrics = oisp_core::create_metrics();
+        let decoder = HttpDecoder::new()
+            .with_metrics(metrics.clone())
+            .with_clock(clock.clone());
+        let garbled = b"HTTP/1.1 two-hundred OK\r\n\r\n";
+        for pid in 0..1000 {
+            let raw = create_raw_event(RawEventKind::SslRead, garbled, pid);
+            assert!(decoder.decode(raw).await.unwrap().is_empty());
+        }
+
+        assert_eq!(metrics.decode_failures(), 1000);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert!(metrics.to_prometheus().contains(
+            "oisp_decode_failures_total{reason=\"http_response\",provider=\"unknown\"} 1000"
+        ));
+
+        // The burst is over; the next tick past the interval reports it
+        let mono = MonoClock::default();
+        decoder.tick(&mono);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        clock.advance(crate::failures::FAILURE_LOG_INTERVAL);
+        decoder.tick(&mono);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+        decoder.flush(&mono);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+    }
+
+    /// An SslRead on an unrelated connection, just to run the periodic sweep
+    async fn trigger_cleanup(decoder: &HttpDecoder) {
+        let raw = create_raw_event(RawEventKind::SslRead, b"\x00", 9999);
+        decoder.decode(raw).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_partial_request_cleanup() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+
+        // Headers promise more body than arrives
+        let partial = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        Content-Length: 500\r\n\
+                        \r\n\
+                        {\"model\":";
+        let raw = create_raw_event(RawEventKind::SslWrite, partial, 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+        assert_eq!(decoder.partial_requests.read().unwrap().len(), 1);
+
+        // A sweep before the timeout keeps it
+        clock.advance(CLEANUP_INTERVAL + Duration::from_secs(1));
+        trigger_cleanup(&decoder).await;
+        assert_eq!(decoder.partial_requests.read().unwrap().len(), 1);
+
+        // Past the timeout, the next sweep drops it
+        clock.advance(PENDING_REQUEST_TIMEOUT);
+        trigger_cleanup(&decoder).await;
+        assert!(decoder.partial_requests.read().unwrap().is_empty());
+    }
+
+    fn pg_message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&((body.len() + 4) as u32).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    #[tokio::test]
+    async fn test_pgvector_retrieval() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+        let query = pg_message(
+            b'Q',
+            b"SELECT id FROM chunks ORDER BY embedding <-> '[0.1,0.2]' LIMIT 4\0",
+        );
+
+        let raw = create_raw_event(RawEventKind::SslWrite, &query, 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+        assert_eq!(decoder.stats().pending_retrievals, 1);
+
+        let mut response = pg_message(b'C', b"SELECT 4\0");
+        response.extend(pg_message(b'Z', b"I"));
+        let mut raw = create_raw_event(RawEventKind::SslRead, &response, 1234);
+        raw.timestamp_ns += 12_000_000;
+        let events = decoder.decode(raw).await.unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AgentRagRetrieve(event) = &events[0] else {
+            panic!("expected agent.rag_retrieve, got {:?}", events[0]);
+        };
+        let source = event.data.source.as_ref().unwrap();
+        assert_eq!(source.provider.as_deref(), Some("pgvector"));
+        assert_eq!(source.name.as_deref(), Some("chunks"));
+        assert_eq!(event.data.results_count, Some(4));
+        assert_eq!(event.data.latency_ms, Some(12));
+        assert_eq!(decoder.stats().pending_retrievals, 0);
+
+        // A query whose results never arrive is reported on timeout
+        let raw = create_raw_event(RawEventKind::SslWrite, &query, 1234);
+        decoder.decode(raw).await.unwrap();
+        clock.advance(PENDING_REQUEST_TIMEOUT + CLEANUP_INTERVAL);
+        let raw = create_raw_event(RawEventKind::SslRead, b"\x00", 9999);
+        let events = decoder.decode(raw).await.unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AgentRagRetrieve(event) = &events[0] else {
+            panic!("expected agent.rag_retrieve, got {:?}", events[0]);
+        };
+        assert_eq!(event.data.results_count, None);
+        assert_eq!(event.data.latency_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_pending_request_correlation_timeout() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+
+        let raw = create_raw_event(RawEventKind::SslWrite, openai_request(), 1234);
+        assert_eq!(decoder.decode(raw).await.unwrap().len(), 1);
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        // No sweep until the cleanup interval has passed, however stale
+        clock.advance(CLEANUP_INTERVAL);
+        trigger_cleanup(&decoder).await;
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        clock.advance(PENDING_REQUEST_TIMEOUT);
+        trigger_cleanup(&decoder).await;
+        assert_eq!(decoder.stats().pending_requests, 0);
+
+        // A late response no longer correlates to the request
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-123\",\"model\":\"gpt-4\",\"choices\":[]}";
+        let raw = create_raw_event(RawEventKind::SslRead, response, 1234);
+        let events = decoder.decode(raw).await.unwrap();
+        assert!(!events.iter().any(|e| matches!(e, OispEvent::AiResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tick_times_out_request_without_traffic() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+        let mono = MonoClock::default();
+
+        let raw = create_raw_event(RawEventKind::SslWrite, openai_request(), 1234);
+        let events = decoder.decode(raw).await.unwrap();
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        let request_id = req.data.request_id.clone();
+
+        // Nothing else is captured; only the pipeline's ticks run
+        assert!(decoder.tick(&mono).is_empty());
+        clock.advance(PENDING_REQUEST_TIMEOUT + CLEANUP_INTERVAL);
+        let events = decoder.tick(&mono);
+
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.request_id, request_id);
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Incomplete));
+        assert_eq!(decoder.stats().pending_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_reports_pending_requests_incomplete() {
         let decoder = HttpDecoder::new();
+        let mono = MonoClock::default();
 
-        let request = b"POST /v1/messages HTTP/1.1\r\n\
-                        Host: api.anthropic.com\r\n\
-                        Content-Type: application/json\r\n\
+        let raw = create_raw_event(RawEventKind::SslWrite, openai_request(), 1234);
+        decoder.decode(raw).await.unwrap();
+        assert!(decoder.tick(&mono).is_empty());
+
+        let events = decoder.flush(&mono);
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.success, Some(false));
+        assert_eq!(
+            resp.data.error.as_ref().unwrap().message.as_deref(),
+            Some("sensor stopped before the response completed")
+        );
+        assert_eq!(decoder.stats().pending_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_stream_emits_incomplete_response() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
                         \r\n\
-                        {\"model\":\"claude-3-opus-20240229\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}],\"max_tokens\":1024}";
+                        {\"model\":\"gpt-4\",\"stream\":true,\"stream_options\":{\"include_usage\":true},\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        let request_id = req.data.request_id.clone();
+
+        // The stream starts, then the connection goes quiet for good
+        let chunk = "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1}}\n\n";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             {:x}\r\n{}\r\n",
+            chunk.len(),
+            chunk
+        );
+        let events = decoder
+            .decode(create_raw_event(
+                RawEventKind::SslRead,
+                response.as_bytes(),
+                1234,
+            ))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
 
+        clock.advance(DEFAULT_STREAM_TIMEOUT + CLEANUP_INTERVAL);
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, b"\x00", 9999))
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.request_id, request_id);
+        assert_eq!(resp.data.success, Some(false));
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Incomplete));
+        assert_eq!(resp.data.model.as_ref().unwrap().id, "gpt-4");
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hel"));
+        let usage = resp.data.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, Some(9));
+        assert_eq!(usage.completion_tokens, Some(1));
+
+        // Attributed to the requesting process, not the event that swept it
+        assert_eq!(resp.envelope.process.as_ref().unwrap().pid, 1234);
+        let confidence = &resp.envelope.confidence;
+        assert_eq!(confidence.level, ConfidenceLevel::Low);
+        assert_eq!(confidence.completeness, Completeness::Partial);
+        assert!(confidence.reasons.contains(&"response_timeout".to_string()));
+
+        assert_eq!(decoder.stats().pending_requests, 0);
+        assert_eq!(decoder.stats().stream_reassemblers, 0);
+    }
+
+    /// A streaming chat completion request, and the head of its response
+    /// with one chunk
+    fn start_stream(content: &str) -> (&'static [u8], String) {
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"stream\":true,\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             {}",
+            sse_chunk(content)
+        );
+        (request, response)
+    }
+
+    /// One chunked-encoding chunk holding an SSE content delta
+    fn sse_chunk(content: &str) -> String {
+        let event = format!(
+            "data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"{}\"}},\"finish_reason\":null}}]}}\n\n",
+            content
+        );
+        format!("{:x}\r\n{}\r\n", event.len(), event)
+    }
+
+    #[tokio::test]
+    async fn test_idle_stream_closed_before_request_timeout() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new()
+            .with_clock(clock.clone())
+            .with_stream_timeout(Duration::from_secs(90));
+
+        let (request, response) = start_stream("Hel");
         let raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        assert_eq!(decoder.decode(raw).await.unwrap().len(), 1);
+        let raw = create_raw_event(RawEventKind::SslRead, response.as_bytes(), 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+
+        // Still within the idle timeout
+        clock.advance(CLEANUP_INTERVAL + Duration::from_secs(1));
+        trigger_cleanup(&decoder).await;
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        // Silent past the stream timeout, well before the request timeout
+        clock.advance(CLEANUP_INTERVAL + Duration::from_secs(1));
+        let raw = create_raw_event(RawEventKind::SslRead, b"\x00", 9999);
         let events = decoder.decode(raw).await.unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Incomplete));
+        assert_eq!(
+            resp.data.error.as_ref().unwrap().message.as_deref(),
+            Some("stream idle for 90s")
+        );
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hel"));
+        assert_eq!(decoder.stats().pending_requests, 0);
+        assert!(decoder.partial_responses.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tick_closes_idle_stream() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+        let mono = MonoClock::default();
 
+        let (request, response) = start_stream("Hel");
+        let raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        assert_eq!(decoder.decode(raw).await.unwrap().len(), 1);
+        let raw = create_raw_event(RawEventKind::SslRead, response.as_bytes(), 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+
+        // A started stream outlasts the request timeout while idle
+        clock.advance(PENDING_REQUEST_TIMEOUT + CLEANUP_INTERVAL);
+        assert!(decoder.tick(&mono).is_empty());
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        // No more traffic; a tick past the stream timeout closes it
+        clock.advance(DEFAULT_STREAM_TIMEOUT);
+        let events = decoder.tick(&mono);
         assert_eq!(events.len(), 1);
-        if let OispEvent::AiRequest(req) = &events[0] {
-            assert!(req.data.model.as_ref().unwrap().id.starts_with("claude"));
-        } else {
-            panic!("Expected AiRequest event");
-        }
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(
+            resp.data.error.as_ref().unwrap().message.as_deref(),
+            Some("stream idle for 600s")
+        );
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hel"));
+    }
+
+    #[tokio::test]
+    async fn test_active_stream_outlives_request_timeout() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new().with_clock(clock.clone());
+
+        let (request, response) = start_stream("a");
+        let raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        assert_eq!(decoder.decode(raw).await.unwrap().len(), 1);
+        let raw = create_raw_event(RawEventKind::SslRead, response.as_bytes(), 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+
+        // A chunk a minute for ten minutes, each read also running a sweep
+        for _ in 0..10 {
+            clock.advance(CLEANUP_INTERVAL + Duration::from_secs(1));
+            let chunk = sse_chunk("a");
+            let raw = create_raw_event(RawEventKind::SslRead, chunk.as_bytes(), 1234);
+            assert!(decoder.decode(raw).await.unwrap().is_empty());
+        }
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        let end = b"e\r\ndata: [DONE]\n\n\r\n0\r\n\r\n";
+        let raw = create_raw_event(RawEventKind::SslRead, end, 1234);
+        let events = decoder.decode(raw).await.unwrap();
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_ne!(resp.data.finish_reason, Some(FinishReason::Incomplete));
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if *t == "a".repeat(11)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_h2_request_and_response() {
+        use crate::http::h2_capture;
+
+        let decoder = HttpDecoder::new();
+
+        // The client writes the preface and request across two writes
+        let request = h2_capture::request();
+        let (first, second) = request.split_at(40);
+        let raw = create_raw_event(RawEventKind::SslWrite, first, 1234);
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+        let raw = create_raw_event(RawEventKind::SslWrite, second, 1234);
+        let events = decoder.decode(raw).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        assert_eq!(req.data.model.as_ref().unwrap().id, "gpt-4o");
+        assert_eq!(req.data.messages.len(), 1);
+        assert_eq!(decoder.stats().pending_requests, 1);
+
+        // Responses are read on another thread
+        let mut raw = create_raw_event(RawEventKind::SslRead, &h2_capture::response(), 1234);
+        raw.tid = Some(2);
+        let events = decoder.decode(raw).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.request_id, req.data.request_id);
+        assert_eq!(resp.data.status_code, Some(200));
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(decoder.stats().pending_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_decode_anthropic_request() {
+        let decoder = HttpDecoder::new();
+
+        let request = b"POST /v1/messages HTTP/1.1\r\n\
+                        Host: api.anthropic.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"claude-3-opus-20240229\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}],\"max_tokens\":1024}";
+
+        let raw = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        let events = decoder.decode(raw).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            assert!(req.data.model.as_ref().unwrap().id.starts_with("claude"));
+        } else {
+            panic!("Expected AiRequest event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_degrades_to_builtin_providers_without_spec_bundle() {
+        let loader =
+            SpecLoader::from_file(std::path::Path::new("/nonexistent/oisp-spec-bundle.json"));
+        assert!(loader.is_degraded());
+        let decoder = HttpDecoder::with_spec_loader(&loader);
+        assert!(decoder.provider_count() > 0);
+
+        let requests: [(&[u8], &str); 2] = [
+            (
+                b"POST /v1/chat/completions HTTP/1.1\r\n\
+                  Host: api.openai.com\r\n\
+                  Content-Type: application/json\r\n\
+                  \r\n\
+                  {\"model\":\"gpt-4o\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}",
+                "openai",
+            ),
+            (
+                b"POST /v1/messages HTTP/1.1\r\n\
+                  Host: api.anthropic.com\r\n\
+                  Content-Type: application/json\r\n\
+                  \r\n\
+                  {\"model\":\"claude-3-opus-20240229\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}],\"max_tokens\":1024}",
+                "anthropic",
+            ),
+        ];
+
+        for (pid, (request, provider)) in (1..).zip(requests) {
+            let raw = create_raw_event(RawEventKind::SslWrite, request, pid);
+            let events = decoder.decode(raw).await.unwrap();
+            let [OispEvent::AiRequest(req)] = events.as_slice() else {
+                panic!(
+                    "Expected one AiRequest event for {}: {:?}",
+                    provider, events
+                );
+            };
+            assert_eq!(req.data.provider.as_ref().unwrap().name, provider);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_detection_cached_until_bundle_swap() {
+        let decoder = HttpDecoder::new().with_openai_compatible(false);
+        let request = |host: &str| {
+            format!(
+                "POST /v1/chat/completions HTTP/1.1\r\n\
+                 Host: {}\r\n\
+                 Content-Type: application/json\r\n\
+                 \r\n\
+                 {{\"model\":\"gpt-4o\",\"messages\":[{{\"role\":\"user\",\"content\":\"Hello\"}}]}}",
+                host
+            )
+        };
+
+        for pid in 1..=3 {
+            let raw = create_raw_event(
+                RawEventKind::SslWrite,
+                request("api.openai.com").as_bytes(),
+                pid,
+            );
+            assert_eq!(decoder.decode(raw).await.unwrap().len(), 1);
+        }
+        let raw = create_raw_event(
+            RawEventKind::SslWrite,
+            request("llm.corp.example").as_bytes(),
+            4,
+        );
+        assert!(decoder.decode(raw).await.unwrap().is_empty());
+
+        let stats = decoder.stats();
+        assert_eq!(stats.provider_detections, 2);
+        assert_eq!(stats.provider_cache_hits, 2);
+        assert_eq!(stats.provider_cache_entries, 2);
+
+        // The new bundle knows the host; the cached miss must not survive the swap
+        let mut bundle = OispSpecBundle::embedded();
+        bundle
+            .domain_index
+            .insert("llm.corp.example".to_string(), "openai".to_string());
+        decoder.set_spec_bundle(Arc::new(bundle));
+        assert_eq!(decoder.stats().provider_cache_entries, 0);
+
+        let raw = create_raw_event(
+            RawEventKind::SslWrite,
+            request("llm.corp.example").as_bytes(),
+            5,
+        );
+        let events = decoder.decode(raw).await.unwrap();
+        assert!(
+            matches!(events.as_slice(), [OispEvent::AiRequest(_)]),
+            "Expected one AiRequest event: {:?}",
+            events
+        );
+        assert_eq!(decoder.stats().provider_detections, 3);
+    }
+
+    #[tokio::test]
+    async fn test_decode_ollama_native_streaming() {
+        let decoder = HttpDecoder::new();
+
+        // Native /api/chat streams by default when "stream" is omitted
+        let request = b"POST /api/chat HTTP/1.1\r\n\
+                        Host: localhost:11434\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"llama3.2\",\"messages\":[{\"role\":\"user\",\"content\":\"Why is the sky blue?\"}],\"options\":{\"temperature\":0.2,\"num_predict\":64}}";
+
+        let raw_req = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        let events = decoder.decode(raw_req).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            assert_eq!(req.data.provider.as_ref().unwrap().name, "ollama");
+            assert_eq!(req.data.model.as_ref().unwrap().id, "llama3.2");
+            assert_eq!(req.data.streaming, Some(true));
+            assert_eq!(req.data.parameters.as_ref().unwrap().max_tokens, Some(64));
+        } else {
+            panic!("Expected AiRequest event");
+        }
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/x-ndjson\r\n\
+                         \r\n\
+                         {\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"Rayleigh\"},\"done\":false}\n\
+                         {\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\" scattering.\"},\"done\":false}\n\
+                         {\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":15,\"eval_count\":4}\n";
+
+        let raw_resp = create_raw_event(RawEventKind::SslRead, response, 1234);
+        let events = decoder.decode(raw_resp).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            assert_eq!(resp.data.provider.as_ref().unwrap().name, "ollama");
+            assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+            let usage = resp.data.usage.as_ref().unwrap();
+            assert_eq!(usage.prompt_tokens, Some(15));
+            assert_eq!(usage.completion_tokens, Some(4));
+            let message = resp.data.choices[0].message.as_ref().unwrap();
+            assert!(
+                matches!(&message.content, Some(MessageContent::Text(t)) if t == "Rayleigh scattering.")
+            );
+        } else {
+            panic!("Expected AiResponse event");
+        }
+
+        assert_eq!(decoder.stats().pending_requests, 0);
+        assert_eq!(decoder.stats().ollama_reassemblers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_decode_vertex_gemini_streaming() {
+        let decoder = HttpDecoder::new();
+
+        // Regional Vertex AI endpoint; the model is in the path, not the body
+        let request = b"POST /v1/projects/acme-ml/locations/us-central1/publishers/google/models/gemini-2.0-flash:streamGenerateContent?alt=sse HTTP/1.1\r\n\
+                        Host: us-central1-aiplatform.googleapis.com\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"contents\":[{\"role\":\"user\",\"parts\":[{\"text\":\"Why is the sky blue?\"}]}],\"generationConfig\":{\"temperature\":0.4,\"maxOutputTokens\":256}}";
+
+        let raw_req = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        let events = decoder.decode(raw_req).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            let provider = req.data.provider.as_ref().unwrap();
+            assert_eq!(provider.name, "google");
+            assert_eq!(provider.project_id.as_deref(), Some("acme-ml"));
+            assert_eq!(provider.region.as_deref(), Some("us-central1"));
+            assert_eq!(req.data.model.as_ref().unwrap().id, "gemini-2.0-flash");
+            assert_eq!(req.data.streaming, Some(true));
+            assert_eq!(req.data.messages_count, Some(1));
+        } else {
+            panic!("Expected AiRequest event");
+        }
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: text/event-stream\r\n\
+                         \r\n\
+                         data: {\"candidates\": [{\"content\": {\"role\": \"model\",\"parts\": [{\"text\": \"Rayleigh\"}]}}],\"modelVersion\": \"gemini-2.0-flash-001\"}\r\n\r\n\
+                         data: {\"candidates\": [{\"content\": {\"role\": \"model\",\"parts\": [{\"text\": \" scattering.\"}]},\"finishReason\": \"STOP\"}],\"usageMetadata\": {\"promptTokenCount\": 7,\"candidatesTokenCount\": 3,\"totalTokenCount\": 10},\"modelVersion\": \"gemini-2.0-flash-001\"}\r\n\r\n";
+
+        let raw_resp = create_raw_event(RawEventKind::SslRead, response, 1234);
+        let events = decoder.decode(raw_resp).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            let provider = resp.data.provider.as_ref().unwrap();
+            assert_eq!(provider.region.as_deref(), Some("us-central1"));
+            assert_eq!(resp.data.model.as_ref().unwrap().id, "gemini-2.0-flash-001");
+            assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+            let usage = resp.data.usage.as_ref().unwrap();
+            assert_eq!(usage.prompt_tokens, Some(7));
+            assert_eq!(usage.completion_tokens, Some(3));
+            let message = resp.data.choices[0].message.as_ref().unwrap();
+            assert!(
+                matches!(&message.content, Some(MessageContent::Text(t)) if t == "Rayleigh scattering.")
+            );
+        } else {
+            panic!("Expected AiResponse event");
+        }
+
+        assert_eq!(decoder.stats().pending_requests, 0);
+        assert_eq!(decoder.stats().gemini_reassemblers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_decode_bedrock_streaming() {
+        use crate::bedrock::tests::chunk_event;
+
+        let decoder = HttpDecoder::new();
+
+        let body = br#"{"anthropic_version":"bedrock-2023-05-31","max_tokens":256,"messages":[{"role":"user","content":"Say hello"}]}"#;
+        let mut request = format!(
+            "POST /model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke-with-response-stream HTTP/1.1\r\n\
+             Host: bedrock-runtime.us-west-2.amazonaws.com\r\n\
+             Content-Type: application/json\r\n\
+             X-Amz-Date: 20240612T101500Z\r\n\
+             Authorization: AWS4-HMAC-SHA256 Credential=AKIAEXAMPLE/20240612/us-west-2/bedrock/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=0f1e2d\r\n\
+             Content-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, &request, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            let provider = req.data.provider.as_ref().unwrap();
+            assert_eq!(provider.name, "awsbedrock");
+            assert_eq!(provider.region.as_deref(), Some("us-west-2"));
+            let model = req.data.model.as_ref().unwrap();
+            assert_eq!(model.id, "anthropic.claude-3-haiku-20240307-v1:0");
+            assert_eq!(model.family.as_deref(), Some("claude-3"));
+            assert_eq!(req.data.streaming, Some(true));
+            assert_eq!(req.data.messages_count, Some(1));
+        } else {
+            panic!("Expected AiRequest event");
+        }
+
+        let chunks = [
+            serde_json::json!({"type": "message_start", "message": {"id": "msg_bdrk_01", "type": "message", "role": "assistant", "model": "claude-3-haiku-20240307", "content": [], "usage": {"input_tokens": 10, "output_tokens": 1}}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hello!"}}),
+            serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 5}}),
+            serde_json::json!({"type": "message_stop", "amazon-bedrock-invocationMetrics": {"inputTokenCount": 10, "outputTokenCount": 5, "invocationLatency": 390, "firstByteLatency": 280}}),
+        ];
+        let mut response = b"HTTP/1.1 200 OK\r\n\
+                             Content-Type: application/vnd.amazon.eventstream\r\n\
+                             Transfer-Encoding: chunked\r\n\
+                             X-Amzn-Bedrock-Content-Type: application/json\r\n\r\n"
+            .to_vec();
+        for chunk in &chunks {
+            let event = chunk_event(chunk);
+            response.extend_from_slice(format!("{:x}\r\n", event.len()).as_bytes());
+            response.extend_from_slice(&event);
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"0\r\n\r\n");
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, &response, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            assert_eq!(resp.data.provider.as_ref().unwrap().name, "awsbedrock");
+            assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+            let usage = resp.data.usage.as_ref().unwrap();
+            assert_eq!(usage.prompt_tokens, Some(10));
+            assert_eq!(usage.completion_tokens, Some(5));
+            assert_eq!(usage.total_tokens, Some(15));
+            let message = resp.data.choices[0].message.as_ref().unwrap();
+            assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hello!"));
+            // Bedrock serves Anthropic models; that is not a mismatch
+            assert_eq!(resp.envelope.confidence.level, ConfidenceLevel::High);
+        } else {
+            panic!("Expected AiResponse event");
+        }
+
+        assert_eq!(decoder.stats().pending_requests, 0);
+        assert_eq!(decoder.stats().bedrock_reassemblers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_decode_bedrock_titan_invoke() {
+        let decoder = HttpDecoder::new();
+
+        let body = br#"{"inputText":"Summarize: the meeting moved to Tuesday.","textGenerationConfig":{"maxTokenCount":128,"temperature":0.3,"topP":0.9,"stopSequences":[]}}"#;
+        let mut request = format!(
+            "POST /model/amazon.titan-text-express-v1/invoke HTTP/1.1\r\n\
+             Host: bedrock-runtime.us-east-1.amazonaws.com\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, &request, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            assert_eq!(
+                req.data.model.as_ref().unwrap().id,
+                "amazon.titan-text-express-v1"
+            );
+            assert_eq!(req.data.streaming, Some(false));
+            assert_eq!(req.data.request_type, Some(RequestType::Completion));
+            assert_eq!(req.data.parameters.as_ref().unwrap().max_tokens, Some(128));
+        } else {
+            panic!("Expected AiRequest event");
+        }
+
+        let body = br#"{"inputTextTokenCount":11,"results":[{"tokenCount":7,"outputText":"The meeting is now on Tuesday.","completionReason":"FINISH"}]}"#;
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/json\r\n\
+             X-Amzn-Bedrock-Input-Token-Count: 11\r\n\
+             X-Amzn-Bedrock-Output-Token-Count: 7\r\n\
+             Content-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, &response, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            assert_eq!(
+                resp.data.model.as_ref().unwrap().id,
+                "amazon.titan-text-express-v1"
+            );
+            assert_eq!(
+                resp.data.provider.as_ref().unwrap().region.as_deref(),
+                Some("us-east-1")
+            );
+            assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+            let usage = resp.data.usage.as_ref().unwrap();
+            assert_eq!(usage.prompt_tokens, Some(11));
+            assert_eq!(usage.completion_tokens, Some(7));
+        } else {
+            panic!("Expected AiResponse event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_gemini_generate_content() {
+        let decoder = HttpDecoder::new();
+
+        let body = br#"{"systemInstruction":{"parts":[{"text":"Answer briefly."}]},"contents":[{"role":"user","parts":[{"text":"Weather in Paris?"}]}],"tools":[{"functionDeclarations":[{"name":"get_weather","description":"Current weather"}]}]}"#;
+        let mut request = format!(
+            "POST /v1beta/models/gemini-1.5-pro:generateContent HTTP/1.1\r\n\
+             Host: generativelanguage.googleapis.com\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, &request, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiRequest(req) = &events[0] {
+            assert_eq!(req.data.model.as_ref().unwrap().id, "gemini-1.5-pro");
+            assert_eq!(req.data.streaming, Some(false));
+            assert_eq!(req.data.has_system_prompt, Some(true));
+            assert_eq!(req.data.tools[0].name, "get_weather");
+            assert!(req.data.provider.as_ref().unwrap().project_id.is_none());
+        } else {
+            panic!("Expected AiRequest event");
+        }
+
+        let body = br#"{"candidates":[{"content":{"role":"model","parts":[{"functionCall":{"name":"get_weather","args":{"city":"Paris"}}}]},"finishReason":"STOP","index":0}],"usageMetadata":{"promptTokenCount":21,"candidatesTokenCount":5,"totalTokenCount":26},"modelVersion":"gemini-1.5-pro-002","responseId":"resp_1"}"#;
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, &response, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            assert_eq!(resp.data.provider.as_ref().unwrap().name, "google");
+            assert_eq!(resp.data.provider_request_id.as_deref(), Some("resp_1"));
+            assert_eq!(resp.data.finish_reason, Some(FinishReason::ToolCalls));
+            assert_eq!(resp.data.tool_calls[0].name, "get_weather");
+            assert_eq!(resp.data.usage.as_ref().unwrap().total_tokens, Some(26));
+        } else {
+            panic!("Expected AiResponse event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_ollama_openai_compatible() {
+        let decoder = HttpDecoder::new();
+
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: 127.0.0.1:11434\r\n\
+                        Content-Type: application/json\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-oss:20b\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+
+        let raw_req = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        decoder.decode(raw_req).await.unwrap();
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion\",\"model\":\"gpt-oss:20b\",\"choices\":[{\"index\":0,\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+
+        let raw_resp = create_raw_event(RawEventKind::SslRead, response, 1234);
+        let events = decoder.decode(raw_resp).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            // Model name looks like OpenAI, but the endpoint is a local Ollama
+            assert_eq!(resp.data.provider.as_ref().unwrap().name, "ollama");
+        } else {
+            panic!("Expected AiResponse event");
+        }
+    }
+
+    /// Chat request and response as served by vLLM on a private host
+    fn vllm_exchange() -> (&'static [u8], &'static [u8]) {
+        (
+            b"POST /v1/chat/completions HTTP/1.1\r\n\
+              Host: gpu-box.internal:8000\r\n\
+              Content-Type: application/json\r\n\
+              \r\n\
+              {\"model\":\"meta-llama/Llama-3.1-8B-Instruct\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}",
+            b"HTTP/1.1 200 OK\r\n\
+              Content-Type: application/json\r\n\
+              \r\n\
+              {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion\",\"model\":\"meta-llama/Llama-3.1-8B-Instruct\",\"choices\":[{\"index\":0,\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_server_on_unknown_host() {
+        let decoder = HttpDecoder::new();
+        let (request, response) = vllm_exchange();
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        assert_eq!(
+            req.data.provider.as_ref().unwrap().name,
+            "openai-compatible"
+        );
+        assert_eq!(
+            req.data.model.as_ref().unwrap().id,
+            "meta-llama/Llama-3.1-8B-Instruct"
+        );
+        let confidence = &req.envelope.confidence;
+        assert_eq!(confidence.level, ConfidenceLevel::Medium);
+        assert_eq!(
+            confidence.ai_detection_method.as_deref(),
+            Some("request_schema")
+        );
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(
+            resp.data.provider.as_ref().unwrap().name,
+            "openai-compatible"
+        );
+        assert_eq!(
+            resp.envelope.confidence.ai_detection_method.as_deref(),
+            Some("request_schema")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_host_names_provider() {
+        let hosts = HashMap::from([("gpu-box.internal:8000".to_string(), "vllm".to_string())]);
+        let decoder = HttpDecoder::new().with_provider_hosts(hosts);
+        let (request, response) = vllm_exchange();
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        let OispEvent::AiRequest(req) = &events[0] else {
+            panic!("Expected AiRequest event");
+        };
+        assert_eq!(req.data.provider.as_ref().unwrap().name, "vllm");
+        assert_eq!(
+            req.envelope.confidence.ai_detection_method.as_deref(),
+            Some("configured_host")
+        );
+
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+        let OispEvent::AiResponse(resp) = &events[0] else {
+            panic!("Expected AiResponse event");
+        };
+        assert_eq!(resp.data.provider.as_ref().unwrap().name, "vllm");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_host_requires_openai_schema() {
+        let decoder = HttpDecoder::new();
+
+        // Embeddings match the schema
+        let embeddings = b"POST /v1/embeddings HTTP/1.1\r\n\
+                           Host: 10.0.0.7:1234\r\n\
+                           \r\n\
+                           {\"model\":\"nomic-embed-text\",\"input\":\"Hello\"}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, embeddings, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+
+        // Other JSON APIs with a model field do not
+        let other = b"POST /api/v2/jobs HTTP/1.1\r\n\
+                      Host: 10.0.0.7:1234\r\n\
+                      \r\n\
+                      {\"model\":\"report\",\"messages\":[]}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, other, 4321))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        // And nothing is classified by schema when disabled
+        let decoder = HttpDecoder::new().with_openai_compatible(false);
+        let (request, _) = vllm_exchange();
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_confidence_full_for_clean_pair() {
+        let decoder = HttpDecoder::new();
+
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+        assert_eq!(events[0].envelope().confidence.level, ConfidenceLevel::High);
+        assert_eq!(
+            events[0].envelope().confidence.completeness,
+            Completeness::Full
+        );
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+
+        let confidence = &events[0].envelope().confidence;
+        assert_eq!(confidence.level, ConfidenceLevel::High);
+        assert_eq!(confidence.completeness, Completeness::Full);
+        assert_eq!(
+            confidence.ai_detection_method.as_deref(),
+            Some("known_endpoint+body")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_confidence_downgraded_for_truncated_stream() {
+        let decoder = HttpDecoder::new();
+
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"stream\":true,\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        decoder
+            .decode(create_raw_event(RawEventKind::SslWrite, request, 1234))
+            .await
+            .unwrap();
+
+        // Chunked body ends without a finish_reason or [DONE]
+        let chunk = "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             {:x}\r\n{}\r\n0\r\n\r\n",
+            chunk.len(),
+            chunk
+        );
+        let events = decoder
+            .decode(create_raw_event(
+                RawEventKind::SslRead,
+                response.as_bytes(),
+                1234,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        let confidence = &events[0].envelope().confidence;
+        assert_eq!(confidence.completeness, Completeness::Partial);
+        assert_eq!(confidence.level, ConfidenceLevel::Medium);
+        assert!(confidence.reasons.contains(&"truncated_body".to_string()));
+        if let OispEvent::AiResponse(resp) = &events[0] {
+            let message = resp.data.choices[0].message.as_ref().unwrap();
+            assert!(matches!(&message.content, Some(MessageContent::Text(t)) if t == "Hel"));
+        } else {
+            panic!("Expected AiResponse event");
+        }
+    }
+
+    /// Decode a gzipped, chunked response whose body arrives in reads of `read_size` bytes
+    async fn decode_gzip_response(body: &[u8], read_size: usize) -> Vec<OispEvent> {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let decoder = HttpDecoder::new();
+        decoder
+            .decode(create_raw_event(
+                RawEventKind::SslWrite,
+                openai_request(),
+                1234,
+            ))
+            .await
+            .unwrap();
+
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(body).unwrap();
+        let compressed = gz.finish().unwrap();
+        let headers = b"HTTP/1.1 200 OK\r\n\
+            Content-Type: application/json\r\n\
+            Content-Encoding: gzip\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n";
+        let mut body_chunks = Vec::new();
+        for chunk in compressed.chunks(1000) {
+            body_chunks.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            body_chunks.extend_from_slice(chunk);
+            body_chunks.extend_from_slice(b"\r\n");
+        }
+        body_chunks.extend_from_slice(b"0\r\n\r\n");
+
+        // The headers must arrive whole for a response to be recognised
+        let mut events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, headers, 1234))
+            .await
+            .unwrap();
+        for read in body_chunks.chunks(read_size) {
+            events.extend(
+                decoder
+                    .decode(create_raw_event(RawEventKind::SslRead, read, 1234))
+                    .await
+                    .unwrap(),
+            );
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_response_decodes_identically() {
+        let content: String = (0..20_000).map(|i| format!("{} ", i * 7919)).collect();
+        let body = serde_json::json!({
+            "id": "chatcmpl-123",
+            "model": "gpt-4",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        })
+        .to_string();
+
+        let whole = decode_gzip_response(body.as_bytes(), usize::MAX).await;
+        let fragmented = decode_gzip_response(body.as_bytes(), 37).await;
+        assert_eq!(whole.len(), 1);
+        assert_eq!(fragmented.len(), 1);
+
+        let (OispEvent::AiResponse(whole), OispEvent::AiResponse(fragmented)) =
+            (&whole[0], &fragmented[0])
+        else {
+            panic!("Expected AiResponse events");
+        };
+        let message = fragmented.data.choices[0].message.as_ref().unwrap();
+        assert!(matches!(&message.content, Some(MessageContent::Text(t)) if *t == content));
+        // Everything but the per-request id and timing must match
+        let comparable = |data: &AiResponseData| {
+            let mut value = serde_json::to_value(data).unwrap();
+            let fields = value.as_object_mut().unwrap();
+            fields.remove("request_id");
+            fields.remove("latency_ms");
+            value
+        };
+        assert_eq!(comparable(&whole.data), comparable(&fragmented.data));
+        assert_eq!(
+            serde_json::to_value(&whole.envelope.confidence).unwrap(),
+            serde_json::to_value(&fragmented.envelope.confidence).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_confidence_downgraded_for_fallback_correlation() {
+        let decoder = HttpDecoder::new();
+
+        // Request captured without a TID, response with one
+        let request = b"POST /v1/chat/completions HTTP/1.1\r\n\
+                        Host: api.openai.com\r\n\
+                        \r\n\
+                        {\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+        let mut raw_req = create_raw_event(RawEventKind::SslWrite, request, 1234);
+        raw_req.tid = None;
+        decoder.decode(raw_req).await.unwrap();
+
+        let response = b"HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/json\r\n\
+                         \r\n\
+                         {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+        let events = decoder
+            .decode(create_raw_event(RawEventKind::SslRead, response, 1234))
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        let confidence = &events[0].envelope().confidence;
+        assert_eq!(confidence.completeness, Completeness::Partial);
+        assert_eq!(confidence.level, ConfidenceLevel::Medium);
+        assert!(confidence
+            .reasons
+            .contains(&"fallback_correlation".to_string()));
+
+        // The fallback-keyed request is cleaned up too
+        assert_eq!(decoder.stats().pending_requests, 0);
+    }
+
+    fn connect_event(pid: u32) -> RawCaptureEvent {
+        let mut raw = create_raw_event(RawEventKind::NetworkConnect, b"", pid);
+        raw.metadata.remote_addr = Some("104.18.7.192".to_string());
+        raw.metadata.remote_port = Some(443);
+        raw
+    }
+
+    #[tokio::test]
+    async fn test_sni_attributes_network_connect() {
+        let decoder = HttpDecoder::new().with_sni_extraction(true);
+
+        // Connect is held until the ClientHello arrives
+        let events = decoder.decode(connect_event(1234)).await.unwrap();
+        assert!(events.is_empty());
+
+        let hello = crate::tls::sample_client_hello();
+        let (first, rest) = hello.split_at(64);
+
+        let send = create_raw_event(RawEventKind::NetworkSend, first, 1234);
+        assert!(decoder.can_decode(&send));
+        assert!(decoder.decode(send).await.unwrap().is_empty());
+
+        let send = create_raw_event(RawEventKind::NetworkSend, rest, 1234);
+        let events = decoder.decode(send).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::NetworkConnect(connect) = &events[0] {
+            assert_eq!(connect.data.dest.domain.as_deref(), Some("api.openai.com"));
+            assert_eq!(connect.data.dest.port, Some(443));
+            let tls = connect.data.tls.as_ref().unwrap();
+            assert_eq!(tls.sni.as_deref(), Some("api.openai.com"));
+            assert_eq!(tls.version.as_deref(), Some("TLS 1.3"));
+            assert_eq!(
+                connect.envelope.attrs.get("ai_provider"),
+                Some(&serde_json::json!("openai"))
+            );
+        } else {
+            panic!("Expected NetworkConnect event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sni_non_tls_connect_emitted_without_tls() {
+        let decoder = HttpDecoder::new().with_sni_extraction(true);
+
+        decoder.decode(connect_event(1234)).await.unwrap();
+        let send = create_raw_event(RawEventKind::NetworkSend, b"GET / HTTP/1.1\r\n", 1234);
+        let events = decoder.decode(send).await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        if let OispEvent::NetworkConnect(connect) = &events[0] {
+            assert!(connect.data.tls.is_none());
+            assert!(connect.data.dest.domain.is_none());
+        } else {
+            panic!("Expected NetworkConnect event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sni_held_connect_released_on_timeout_and_flush() {
+        use crate::clock::MockClock;
+
+        let clock = Arc::new(MockClock::new());
+        let decoder = HttpDecoder::new()
+            .with_sni_extraction(true)
+            .with_clock(clock.clone());
+        let mono = MonoClock::default();
+
+        assert!(decoder
+            .decode(connect_event(1234))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(decoder.tick(&mono).is_empty());
+
+        // No capture backend reported the first bytes in time
+        clock.advance(SNI_HOLD_TIMEOUT);
+        let events = decoder.tick(&mono);
+        assert_eq!(events.len(), 1);
+        let OispEvent::NetworkConnect(connect) = &events[0] else {
+            panic!("Expected NetworkConnect event");
+        };
+        assert_eq!(connect.data.dest.ip.as_deref(), Some("104.18.7.192"));
+        assert!(connect.data.dest.domain.is_none());
+        assert!(connect.envelope.ts_mono.is_some());
+        assert!(decoder.tick(&mono).is_empty());
+
+        // Held connects are not lost at shutdown
+        decoder.decode(connect_event(5678)).await.unwrap();
+        assert!(decoder.tick(&mono).is_empty());
+        assert_eq!(decoder.flush(&mono).len(), 1);
+        assert!(decoder.flush(&mono).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_network_connect_passthrough_without_sni_extraction() {
+        let decoder = HttpDecoder::new();
+
+        let events = decoder.decode(connect_event(1234)).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!decoder.can_decode(&create_raw_event(RawEventKind::NetworkSend, b"", 1234)));
     }
 
     #[tokio::test]
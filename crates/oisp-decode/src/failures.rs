@@ -0,0 +1,250 @@
+//! Deduplicated logging of decode failures
+//!
+//! Traffic the decoder cannot handle (an unsupported format, a provider API
+//! change) usually fails the same way for every request, and a warning per
+//! request buries everything else in the log. Failures are grouped into
+//! classes by reason and provider: the first of a class is logged, repeats
+//! within the log interval are only counted, and the first failure after
+//! the interval is logged with the number suppressed since. A burst that
+//! ends has its count logged by [`FailureLog::flush_due`] once the interval
+//! passes, or by [`FailureLog::flush_all`] at shutdown. Every failure is
+//! still counted in the sensor metrics (`oisp_decode_failures_total`).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Minimum time between log lines for one failure class
+pub const FAILURE_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Failure classes tracked before the suppression state is reset
+const MAX_FAILURE_CLASSES: usize = 1024;
+
+/// Why captured traffic could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodeFailure {
+    /// A reassembled HTTP request did not parse
+    HttpRequest,
+    /// An HTTP response head did not parse
+    HttpResponse,
+    /// An HTTP/2 connection could not be followed
+    Http2,
+    /// An AI request body did not match its provider's format
+    AiRequest,
+    /// An AI response body did not match its provider's format
+    AiResponse,
+    /// A streamed AI response could not be merged
+    AiStream,
+}
+
+impl DecodeFailure {
+    /// Stable name, used as the `reason` metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HttpRequest => "http_request",
+            Self::HttpResponse => "http_response",
+            Self::Http2 => "http2",
+            Self::AiRequest => "ai_request",
+            Self::AiResponse => "ai_response",
+            Self::AiStream => "ai_stream",
+        }
+    }
+}
+
+impl fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::HttpRequest => "failed to parse HTTP request",
+            Self::HttpResponse => "failed to parse HTTP response",
+            Self::Http2 => "undecodable HTTP/2 connection",
+            Self::AiRequest => "failed to parse AI request",
+            Self::AiResponse => "failed to parse AI response",
+            Self::AiStream => "failed to parse streamed AI response",
+        })
+    }
+}
+
+/// Suppression state of one failure class
+struct FailureClass {
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+/// Rate-limits decode failure warnings per reason and provider
+pub struct FailureLog {
+    classes: Mutex<HashMap<(DecodeFailure, String), FailureClass>>,
+    interval: Duration,
+}
+
+impl FailureLog {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            classes: Mutex::new(HashMap::new()),
+            interval,
+        }
+    }
+
+    /// Log a failure unless its class was logged within the interval
+    ///
+    /// Returns whether a line was logged.
+    pub fn record(
+        &self,
+        failure: DecodeFailure,
+        provider: &str,
+        detail: &dyn fmt::Display,
+        now: Instant,
+    ) -> bool {
+        let mut classes = self.classes.lock().unwrap();
+        let key = (failure, provider.to_string());
+        let suppressed = match classes.get_mut(&key) {
+            Some(class) if now.duration_since(class.logged_at) < self.interval => {
+                class.suppressed += 1;
+                return false;
+            }
+            Some(class) => std::mem::take(&mut class.suppressed),
+            None => 0,
+        };
+
+        if classes.len() >= MAX_FAILURE_CLASSES && !classes.contains_key(&key) {
+            for ((failure, provider), class) in classes.drain() {
+                log_suppressed(failure, &provider, class.suppressed);
+            }
+        }
+        classes.insert(
+            key,
+            FailureClass {
+                logged_at: now,
+                suppressed: 0,
+            },
+        );
+        drop(classes);
+
+        if suppressed > 0 {
+            warn!(
+                reason = failure.as_str(),
+                provider,
+                suppressed,
+                "Decode failure: {} ({}); {} more since the last report",
+                failure,
+                detail,
+                suppressed
+            );
+        } else {
+            warn!(
+                reason = failure.as_str(),
+                provider, "Decode failure: {} ({})", failure, detail
+            );
+        }
+        true
+    }
+}
+
+impl FailureLog {
+    /// Log the suppressed count of classes whose interval has passed
+    /// without another failure to report it
+    ///
+    /// Returns the number of lines logged.
+    pub fn flush_due(&self, now: Instant) -> usize {
+        let mut classes = self.classes.lock().unwrap();
+        let mut logged = 0;
+        for ((failure, provider), class) in classes.iter_mut() {
+            if class.suppressed > 0 && now.duration_since(class.logged_at) >= self.interval {
+                log_suppressed(*failure, provider, std::mem::take(&mut class.suppressed));
+                class.logged_at = now;
+                logged += 1;
+            }
+        }
+        logged
+    }
+
+    /// Log every suppressed count still pending, e.g. at shutdown
+    ///
+    /// Returns the number of lines logged.
+    pub fn flush_all(&self) -> usize {
+        let mut classes = self.classes.lock().unwrap();
+        let mut logged = 0;
+        for ((failure, provider), class) in classes.iter_mut() {
+            if class.suppressed > 0 {
+                log_suppressed(*failure, provider, std::mem::take(&mut class.suppressed));
+                logged += 1;
+            }
+        }
+        logged
+    }
+}
+
+/// Summary line for failures suppressed since a class was last logged
+fn log_suppressed(failure: DecodeFailure, provider: &str, suppressed: u64) {
+    if suppressed == 0 {
+        return;
+    }
+    warn!(
+        reason = failure.as_str(),
+        provider,
+        suppressed,
+        "Decode failure: {}; {} similar failures suppressed",
+        failure,
+        suppressed
+    );
+}
+
+impl Default for FailureLog {
+    fn default() -> Self {
+        Self::new(FAILURE_LOG_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeats_suppressed_per_class() {
+        let log = FailureLog::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let record = |failure, provider, secs| {
+            log.record(
+                failure,
+                provider,
+                &"detail",
+                start + Duration::from_secs(secs),
+            )
+        };
+
+        assert!(record(DecodeFailure::AiResponse, "openai", 0));
+        assert!(!record(DecodeFailure::AiResponse, "openai", 1));
+        assert!(!record(DecodeFailure::AiResponse, "openai", 59));
+
+        // Other reasons and providers are separate classes
+        assert!(record(DecodeFailure::AiRequest, "openai", 2));
+        assert!(record(DecodeFailure::AiResponse, "anthropic", 2));
+
+        // After the interval the class is reported again
+        assert!(record(DecodeFailure::AiResponse, "openai", 60));
+        assert!(!record(DecodeFailure::AiResponse, "openai", 61));
+    }
+
+    #[test]
+    fn test_suppressed_count_logged_after_burst_ends() {
+        let log = FailureLog::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(log.record(DecodeFailure::AiResponse, "openai", &"detail", at(0)));
+        assert!(!log.record(DecodeFailure::AiResponse, "openai", &"detail", at(1)));
+        assert!(!log.record(DecodeFailure::AiResponse, "openai", &"detail", at(2)));
+        assert!(log.record(DecodeFailure::AiRequest, "openai", &"detail", at(3)));
+
+        // No further failure arrives to carry the count
+        assert_eq!(log.flush_due(at(59)), 0);
+        assert_eq!(log.flush_due(at(60)), 1);
+        assert_eq!(log.flush_due(at(200)), 0);
+
+        // The summary restarts the interval
+        assert!(!log.record(DecodeFailure::AiResponse, "openai", &"detail", at(61)));
+        assert_eq!(log.flush_all(), 1);
+        assert_eq!(log.flush_all(), 0);
+    }
+}
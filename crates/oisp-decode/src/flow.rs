@@ -0,0 +1,398 @@
+//! Per-connection network flow rollups
+//!
+//! [`FlowTracker`] groups a process's traffic to one remote address and port
+//! into a single flow, counting the plaintext bytes of every SSL write and
+//! read on it. Sockets are tied to their flow by the `NetworkConnect` that
+//! opened them (pid and fd); capture backends that report the remote host on
+//! each SSL event (macOS, Windows) need no connect. A flow closes when its
+//! last socket is closed, when its fd is reused by a new connect, or after
+//! going idle, and is then summarized as `network.flow` data.
+
+use chrono::{DateTime, Utc};
+use oisp_core::events::{Endpoint, FlowDirection, NetworkFlowData, Protocol};
+use oisp_core::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Default time without traffic before a flow is closed
+pub const DEFAULT_FLOW_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Flows tracked at once; the least recently active is closed beyond this
+const MAX_TRACKED_FLOWS: usize = 10_000;
+
+/// Longest interval between idle sweeps
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A process's connection to one remote address and port
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FlowKey {
+    pid: u32,
+    remote_addr: String,
+    remote_port: Option<u16>,
+}
+
+/// An open flow
+struct Flow {
+    metadata: RawEventMetadata,
+    sockets: usize,
+    bytes_sent: u64,
+    bytes_received: u64,
+    first_ns: u64,
+    last_ns: u64,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    last_seen: Instant,
+}
+
+/// A flow that has ended
+#[derive(Debug, Clone)]
+pub struct ClosedFlow {
+    /// Process that owned the flow
+    pub pid: u32,
+    /// Metadata of the event that opened the flow (process and endpoints)
+    pub metadata: RawEventMetadata,
+    /// Capture timestamp of the flow's last event
+    pub last_ns: u64,
+    /// Flow summary
+    pub data: NetworkFlowData,
+}
+
+/// Aggregates connects, SSL reads and SSL writes into flows
+pub struct FlowTracker {
+    flows: HashMap<FlowKey, Flow>,
+    sockets: HashMap<(u32, i32), FlowKey>,
+    idle_timeout: Duration,
+    last_sweep: Option<Instant>,
+}
+
+impl FlowTracker {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            flows: HashMap::new(),
+            sockets: HashMap::new(),
+            idle_timeout,
+            last_sweep: None,
+        }
+    }
+
+    /// Number of open flows
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Whether `fd` of `pid` is a socket belonging to an open flow
+    pub fn is_socket(&self, pid: u32, fd: i32) -> bool {
+        self.sockets.contains_key(&(pid, fd))
+    }
+
+    /// Account for a raw event, returning the flows it (or idleness) closed
+    pub fn observe(&mut self, raw: &RawCaptureEvent, now: Instant) -> Vec<ClosedFlow> {
+        let mut closed = self.sweep(now);
+        let socket = raw.metadata.fd.map(|fd| (raw.pid, fd));
+
+        match raw.kind {
+            RawEventKind::NetworkConnect => {
+                let Some(key) = Self::remote_key(raw) else {
+                    return closed;
+                };
+                if let Some(socket) = socket {
+                    // A reused fd means the previous socket was closed unseen
+                    closed.extend(self.close_socket(socket));
+                    self.sockets.insert(socket, key.clone());
+                }
+                closed.extend(self.make_room(&key));
+                let flow = self.flows.entry(key).or_insert_with(|| Flow::new(raw, now));
+                if socket.is_some() {
+                    flow.sockets += 1;
+                }
+                flow.touch(raw, now);
+            }
+            RawEventKind::SslWrite | RawEventKind::SslRead => {
+                let key = match socket.and_then(|s| self.sockets.get(&s)) {
+                    Some(key) => key.clone(),
+                    None => match Self::remote_key(raw) {
+                        Some(key) => key,
+                        None => return closed,
+                    },
+                };
+                closed.extend(self.make_room(&key));
+                let flow = self.flows.entry(key).or_insert_with(|| Flow::new(raw, now));
+                let len = raw.data.len() as u64;
+                if matches!(raw.kind, RawEventKind::SslWrite) {
+                    flow.bytes_sent += len;
+                } else {
+                    flow.bytes_received += len;
+                }
+                flow.touch(raw, now);
+            }
+            RawEventKind::FileClose => {
+                if let Some(socket) = socket {
+                    closed.extend(self.close_socket(socket));
+                }
+            }
+            _ => {}
+        }
+
+        closed
+    }
+
+    /// Close every flow idle for longer than the timeout
+    pub fn expire(&mut self, now: Instant) -> Vec<ClosedFlow> {
+        self.last_sweep = Some(now);
+        let idle: Vec<FlowKey> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| now.duration_since(flow.last_seen) >= self.idle_timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        idle.into_iter()
+            .filter_map(|key| self.close(&key))
+            .collect()
+    }
+
+    /// Expire idle flows, at most once per sweep interval
+    fn sweep(&mut self, now: Instant) -> Vec<ClosedFlow> {
+        let interval = SWEEP_INTERVAL.min(self.idle_timeout);
+        match self.last_sweep {
+            Some(last) if now.duration_since(last) < interval => Vec::new(),
+            _ => self.expire(now),
+        }
+    }
+
+    /// Close the least recently active flow if `key` would exceed the limit
+    fn make_room(&mut self, key: &FlowKey) -> Option<ClosedFlow> {
+        if self.flows.len() < MAX_TRACKED_FLOWS || self.flows.contains_key(key) {
+            return None;
+        }
+        let oldest = self
+            .flows
+            .iter()
+            .min_by_key(|(_, flow)| flow.last_seen)
+            .map(|(key, _)| key.clone())?;
+        self.close(&oldest)
+    }
+
+    /// Detach a socket, closing its flow once no sockets remain
+    fn close_socket(&mut self, socket: (u32, i32)) -> Option<ClosedFlow> {
+        let key = self.sockets.remove(&socket)?;
+        let flow = self.flows.get_mut(&key)?;
+        flow.sockets = flow.sockets.saturating_sub(1);
+        if flow.sockets == 0 {
+            self.close(&key)
+        } else {
+            None
+        }
+    }
+
+    fn close(&mut self, key: &FlowKey) -> Option<ClosedFlow> {
+        let flow = self.flows.remove(key)?;
+        self.sockets.retain(|_, k| k != key);
+        Some(flow.summarize(key))
+    }
+
+    fn remote_key(raw: &RawCaptureEvent) -> Option<FlowKey> {
+        Some(FlowKey {
+            pid: raw.pid,
+            remote_addr: raw.metadata.remote_addr.clone()?,
+            remote_port: raw.metadata.remote_port,
+        })
+    }
+}
+
+impl Default for FlowTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FLOW_IDLE_TIMEOUT)
+    }
+}
+
+impl Flow {
+    fn new(raw: &RawCaptureEvent, now: Instant) -> Self {
+        let wall = Utc::now();
+        Self {
+            metadata: raw.metadata.clone(),
+            sockets: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            first_ns: raw.timestamp_ns,
+            last_ns: raw.timestamp_ns,
+            start_time: wall,
+            end_time: wall,
+            last_seen: now,
+        }
+    }
+
+    fn touch(&mut self, raw: &RawCaptureEvent, now: Instant) {
+        self.first_ns = self.first_ns.min(raw.timestamp_ns);
+        self.last_ns = self.last_ns.max(raw.timestamp_ns);
+        self.end_time = Utc::now();
+        self.last_seen = now;
+    }
+
+    fn summarize(self, key: &FlowKey) -> ClosedFlow {
+        // Backends report either an IP or a host name as the remote address
+        let (ip, domain) = match key.remote_addr.parse::<IpAddr>() {
+            Ok(_) => (Some(key.remote_addr.clone()), None),
+            Err(_) => (None, Some(key.remote_addr.clone())),
+        };
+        let src =
+            (self.metadata.local_addr.is_some() || self.metadata.local_port.is_some()).then(|| {
+                Endpoint {
+                    ip: self.metadata.local_addr.clone(),
+                    port: self.metadata.local_port,
+                    domain: None,
+                    is_private: None,
+                    geo: None,
+                }
+            });
+
+        ClosedFlow {
+            pid: key.pid,
+            last_ns: self.last_ns,
+            data: NetworkFlowData {
+                dest: Endpoint {
+                    ip,
+                    port: key.remote_port,
+                    domain,
+                    is_private: None,
+                    geo: None,
+                },
+                src,
+                protocol: Some(Protocol::Tcp),
+                direction: Some(FlowDirection::Outbound),
+                bytes_sent: Some(self.bytes_sent),
+                bytes_received: Some(self.bytes_received),
+                packets_sent: None,
+                packets_received: None,
+                duration_ms: Some((self.last_ns - self.first_ns) / 1_000_000),
+                start_time: Some(self.start_time),
+                end_time: Some(self.end_time),
+                tls: None,
+                http: None,
+            },
+            metadata: self.metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(kind: RawEventKind, fd: Option<i32>, ms: u64, len: usize) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: ulid::Ulid::new().to_string(),
+            timestamp_ns: ms * 1_000_000,
+            kind,
+            pid: 4242,
+            tid: Some(1),
+            data: vec![b'x'; len],
+            metadata: RawEventMetadata {
+                comm: Some("python".to_string()),
+                fd,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn connect(fd: i32, ms: u64) -> RawCaptureEvent {
+        let mut raw = raw(RawEventKind::NetworkConnect, Some(fd), ms, 0);
+        raw.metadata.remote_addr = Some("104.18.7.192".to_string());
+        raw.metadata.remote_port = Some(443);
+        raw.metadata.local_addr = Some("10.0.0.5".to_string());
+        raw.metadata.local_port = Some(51234);
+        raw
+    }
+
+    #[test]
+    fn test_flow_from_connect_and_ssl_traffic() {
+        let mut tracker = FlowTracker::default();
+        let now = Instant::now();
+
+        assert!(tracker.observe(&connect(7, 1000), now).is_empty());
+        for (kind, ms, len) in [
+            (RawEventKind::SslWrite, 1010, 517),
+            (RawEventKind::SslRead, 1200, 1400),
+            (RawEventKind::SslRead, 1250, 800),
+            (RawEventKind::SslWrite, 1300, 90),
+            (RawEventKind::SslRead, 1750, 2048),
+        ] {
+            assert!(tracker
+                .observe(&raw(kind, Some(7), ms, len), now)
+                .is_empty());
+        }
+        // Traffic on other sockets of the process is not part of the flow
+        tracker.observe(&raw(RawEventKind::SslWrite, Some(9), 1300, 64), now);
+        assert!(tracker.is_socket(4242, 7));
+
+        let closed = tracker.observe(&raw(RawEventKind::FileClose, Some(7), 1800, 0), now);
+        assert_eq!(closed.len(), 1);
+        let flow = &closed[0].data;
+        assert_eq!(flow.bytes_sent, Some(517 + 90));
+        assert_eq!(flow.bytes_received, Some(1400 + 800 + 2048));
+        assert_eq!(flow.duration_ms, Some(750));
+        assert_eq!(flow.dest.ip.as_deref(), Some("104.18.7.192"));
+        assert_eq!(flow.dest.port, Some(443));
+        assert_eq!(flow.src.as_ref().unwrap().port, Some(51234));
+        assert!(flow.start_time.unwrap() <= flow.end_time.unwrap());
+        assert_eq!(closed[0].pid, 4242);
+        assert!(!tracker.is_socket(4242, 7));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_flow_closes_on_fd_reuse_and_idle() {
+        let mut tracker = FlowTracker::new(Duration::from_secs(30));
+        let now = Instant::now();
+
+        tracker.observe(&connect(7, 0), now);
+        tracker.observe(&raw(RawEventKind::SslWrite, Some(7), 5, 100), now);
+
+        // The fd is reused by a new connect to the same host: two flows
+        let mut reconnect = connect(7, 60);
+        reconnect.metadata.remote_port = Some(8443);
+        let closed = tracker.observe(&reconnect, now);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].data.bytes_sent, Some(100));
+        assert_eq!(tracker.len(), 1);
+
+        tracker.observe(
+            &raw(RawEventKind::SslRead, Some(7), 70, 10),
+            now + Duration::from_secs(10),
+        );
+        assert!(tracker.expire(now + Duration::from_secs(39)).is_empty());
+        let closed = tracker.expire(now + Duration::from_secs(40));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].data.dest.port, Some(8443));
+        assert_eq!(closed[0].data.bytes_received, Some(10));
+        assert!(!tracker.is_socket(4242, 7));
+    }
+
+    #[test]
+    fn test_flow_from_ssl_events_with_remote_host() {
+        // macOS and Windows report the remote host on every SSL event
+        let mut tracker = FlowTracker::default();
+        let now = Instant::now();
+        for (kind, len) in [(RawEventKind::SslWrite, 300), (RawEventKind::SslRead, 900)] {
+            let mut event = raw(kind, None, 0, len);
+            event.metadata.remote_addr = Some("api.openai.com".to_string());
+            tracker.observe(&event, now);
+        }
+        // Without a socket or remote host, traffic cannot be attributed
+        tracker.observe(&raw(RawEventKind::SslRead, None, 0, 50), now);
+
+        let closed = tracker.expire(now + DEFAULT_FLOW_IDLE_TIMEOUT);
+        assert_eq!(closed.len(), 1);
+        let flow = &closed[0].data;
+        assert_eq!(flow.dest.domain.as_deref(), Some("api.openai.com"));
+        assert_eq!(flow.dest.ip, None);
+        assert_eq!(
+            (flow.bytes_sent, flow.bytes_received),
+            (Some(300), Some(900))
+        );
+    }
+}
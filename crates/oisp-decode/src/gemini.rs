@@ -0,0 +1,344 @@
+//! Gemini streaming response parsing
+//!
+//! `streamGenerateContent` on the Gemini API and Vertex AI sends a sequence
+//! of `GenerateContentResponse` chunks: as Server-Sent Events with
+//! `?alt=sse`, otherwise as one JSON array written out element by element.
+
+use crate::sse::SseParser;
+use serde_json::Value;
+
+/// Incremental parser for the objects of a streamed JSON array
+///
+/// Objects are returned as soon as their closing brace arrives. Anything
+/// between them (commas, whitespace, chunked transfer encoding size lines)
+/// is skipped.
+#[derive(Default)]
+pub struct JsonArrayParser {
+    current: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    closed: bool,
+    objects: Vec<Value>,
+}
+
+impl JsonArrayParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add data to the parser
+    pub fn feed(&mut self, data: &[u8]) {
+        // Structural characters are ASCII, so scanning bytes is safe for UTF-8
+        for &byte in data {
+            if self.depth == 0 {
+                match byte {
+                    b'{' => {
+                        self.depth = 1;
+                        self.current.push(byte);
+                    }
+                    b']' => self.closed = true,
+                    _ => {}
+                }
+                continue;
+            }
+
+            self.current.push(byte);
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' => self.depth += 1,
+                b'}' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        let object = std::mem::take(&mut self.current);
+                        if let Ok(json) = serde_json::from_slice::<Value>(&object) {
+                            self.objects.push(json);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether the array's closing bracket was seen
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Take all parsed objects
+    pub fn take_objects(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.objects)
+    }
+}
+
+enum StreamFormat {
+    Sse(SseParser),
+    JsonArray(JsonArrayParser),
+}
+
+/// Reassemble a Gemini `streamGenerateContent` response
+///
+/// The format is told apart by the first chunk: SSE `data:` lines or the
+/// array's opening bracket.
+#[derive(Default)]
+pub struct GeminiStreamReassembler {
+    format: Option<StreamFormat>,
+    /// Data seen before the format could be told
+    pending: Vec<u8>,
+    complete_content: String,
+    function_calls: Vec<Value>,
+    finish_reason: Option<String>,
+    usage: Option<Value>,
+    prompt_feedback: Option<Value>,
+    model_version: Option<String>,
+    response_id: Option<String>,
+    chunks: usize,
+    complete: bool,
+}
+
+impl GeminiStreamReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed data and parse chunks
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.format.is_none() {
+            self.pending.extend_from_slice(data);
+            let sse = find(&self.pending, b"data:");
+            let array = self.pending.iter().position(|&b| b == b'[');
+            self.format = match (sse, array) {
+                (Some(s), Some(a)) if a < s => {
+                    Some(StreamFormat::JsonArray(JsonArrayParser::new()))
+                }
+                (Some(_), _) => Some(StreamFormat::Sse(SseParser::new())),
+                (None, Some(_)) => Some(StreamFormat::JsonArray(JsonArrayParser::new())),
+                (None, None) => None,
+            };
+            if self.format.is_none() {
+                return;
+            }
+            let pending = std::mem::take(&mut self.pending);
+            self.feed_format(&pending);
+        } else {
+            self.feed_format(data);
+        }
+    }
+
+    fn feed_format(&mut self, data: &[u8]) {
+        let chunks: Vec<Value> = match self.format.as_mut() {
+            Some(StreamFormat::Sse(parser)) => {
+                parser.feed(data);
+                parser
+                    .take_events()
+                    .iter()
+                    .filter_map(|e| serde_json::from_str(&e.data).ok())
+                    .collect()
+            }
+            Some(StreamFormat::JsonArray(parser)) => {
+                parser.feed(data);
+                if parser.is_closed() {
+                    self.complete = true;
+                }
+                parser.take_objects()
+            }
+            None => return,
+        };
+        for chunk in &chunks {
+            self.add_chunk(chunk);
+        }
+    }
+
+    fn add_chunk(&mut self, chunk: &Value) {
+        self.chunks += 1;
+        if let Some(version) = chunk.get("modelVersion").and_then(|m| m.as_str()) {
+            self.model_version = Some(version.to_string());
+        }
+        if let Some(id) = chunk.get("responseId").and_then(|i| i.as_str()) {
+            self.response_id = Some(id.to_string());
+        }
+        if let Some(usage) = chunk.get("usageMetadata") {
+            self.usage = Some(usage.clone());
+        }
+        if let Some(feedback) = chunk.get("promptFeedback") {
+            if feedback.get("blockReason").is_some() {
+                self.complete = true;
+            }
+            self.prompt_feedback = Some(feedback.clone());
+        }
+
+        // Streams carry a single candidate
+        let Some(candidate) = chunk.get("candidates").and_then(|c| c.get(0)) else {
+            return;
+        };
+        let parts = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array());
+        for part in parts.into_iter().flatten() {
+            if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                continue;
+            }
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                self.complete_content.push_str(text);
+            } else if part.get("functionCall").is_some() {
+                self.function_calls.push(part.clone());
+            }
+        }
+        if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+            self.complete = true;
+        }
+    }
+
+    /// Check if stream is complete: a finish reason or blocked prompt was
+    /// seen, or the JSON array was closed
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Get complete content
+    pub fn content(&self) -> &str {
+        &self.complete_content
+    }
+
+    /// Get model version reported by the stream
+    pub fn model(&self) -> Option<&str> {
+        self.model_version.as_deref()
+    }
+
+    /// Get token usage (prompt, candidates) from the latest `usageMetadata`
+    pub fn usage(&self) -> (Option<u64>, Option<u64>) {
+        let count = |field: &str| {
+            self.usage
+                .as_ref()
+                .and_then(|u| u.get(field))
+                .and_then(|v| v.as_u64())
+        };
+        (count("promptTokenCount"), count("candidatesTokenCount"))
+    }
+
+    /// Merge the stream into a single non-streaming `GenerateContentResponse`
+    ///
+    /// The result can be handed to [`crate::ai::parse_gemini_response`].
+    /// `None` if no chunk was parsed.
+    pub fn to_response(&self) -> Option<Value> {
+        if self.chunks == 0 {
+            return None;
+        }
+
+        let mut parts = Vec::new();
+        if !self.complete_content.is_empty() {
+            parts.push(serde_json::json!({ "text": self.complete_content }));
+        }
+        parts.extend(self.function_calls.iter().cloned());
+
+        let mut merged = serde_json::Map::new();
+        if !parts.is_empty() || self.finish_reason.is_some() {
+            let mut candidate = serde_json::json!({
+                "index": 0,
+                "content": { "role": "model", "parts": parts },
+            });
+            if let Some(reason) = &self.finish_reason {
+                candidate["finishReason"] = Value::String(reason.clone());
+            }
+            merged.insert("candidates".to_string(), Value::Array(vec![candidate]));
+        }
+        let fields = [
+            ("usageMetadata", self.usage.clone()),
+            ("promptFeedback", self.prompt_feedback.clone()),
+            (
+                "modelVersion",
+                self.model_version.clone().map(Value::String),
+            ),
+            ("responseId", self.response_id.clone().map(Value::String)),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                merged.insert(name.to_string(), value);
+            }
+        }
+        Some(Value::Object(merged))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from `gemini-2.0-flash:streamGenerateContent?alt=sse`
+    const SSE_STREAM: &str = concat!(
+        "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"The\"}],\"role\": \"model\"}}],",
+        "\"usageMetadata\": {\"promptTokenCount\": 9,\"totalTokenCount\": 9},",
+        "\"modelVersion\": \"gemini-2.0-flash\",\"responseId\": \"aBcDeF\"}\r\n\r\n",
+        "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \" sky is blue because of Rayleigh\"}],\"role\": \"model\"}}],",
+        "\"usageMetadata\": {\"promptTokenCount\": 9,\"totalTokenCount\": 9},",
+        "\"modelVersion\": \"gemini-2.0-flash\",\"responseId\": \"aBcDeF\"}\r\n\r\n",
+        "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \" scattering.\\n\"}],\"role\": \"model\"},",
+        "\"finishReason\": \"STOP\"}],",
+        "\"usageMetadata\": {\"promptTokenCount\": 9,\"candidatesTokenCount\": 11,\"totalTokenCount\": 20,",
+        "\"promptTokensDetails\": [{\"modality\": \"TEXT\",\"tokenCount\": 9}]},",
+        "\"modelVersion\": \"gemini-2.0-flash\",\"responseId\": \"aBcDeF\"}\r\n\r\n",
+    );
+
+    #[test]
+    fn test_gemini_sse_stream() {
+        let mut reassembler = GeminiStreamReassembler::new();
+        let (first, rest) = SSE_STREAM.as_bytes().split_at(40);
+        reassembler.feed(first);
+        assert_eq!(reassembler.content(), "");
+        reassembler.feed(rest);
+
+        assert!(reassembler.is_complete());
+        assert_eq!(
+            reassembler.content(),
+            "The sky is blue because of Rayleigh scattering.\n"
+        );
+        assert_eq!(reassembler.model(), Some("gemini-2.0-flash"));
+        assert_eq!(reassembler.usage(), (Some(9), Some(11)));
+
+        let merged = reassembler.to_response().unwrap();
+        assert_eq!(merged["candidates"][0]["finishReason"], "STOP");
+        assert_eq!(merged["responseId"], "aBcDeF");
+        assert_eq!(merged["usageMetadata"]["totalTokenCount"], 20);
+    }
+
+    #[test]
+    fn test_gemini_json_array_stream() {
+        let body = concat!(
+            "[{\"candidates\": [{\"content\": {\"role\": \"model\",\"parts\": [{\"text\": \"Checking {the} \\\"weather\\\"\"}]}}]}\r\n,",
+            "{\"candidates\": [{\"content\": {\"role\": \"model\",\"parts\": [{\"functionCall\": ",
+            "{\"name\": \"get_weather\",\"args\": {\"city\": \"Paris\"}}}]},\"finishReason\": \"STOP\"}],",
+            "\"usageMetadata\": {\"promptTokenCount\": 30,\"candidatesTokenCount\": 8,\"totalTokenCount\": 38},",
+            "\"modelVersion\": \"gemini-1.5-pro-002\"}\r\n]"
+        );
+        let mut reassembler = GeminiStreamReassembler::new();
+        for chunk in body.as_bytes().chunks(17) {
+            reassembler.feed(chunk);
+        }
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.content(), "Checking {the} \"weather\"");
+        let merged = reassembler.to_response().unwrap();
+        let parts = merged["candidates"][0]["content"]["parts"]
+            .as_array()
+            .unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1]["functionCall"]["name"], "get_weather");
+        assert_eq!(merged["modelVersion"], "gemini-1.5-pro-002");
+    }
+}
@@ -0,0 +1,266 @@
+//! Test harness replaying recorded captures through [`HttpDecoder`]
+//!
+//! Fixtures live in `fixtures/raw/` at the repository root and contain
+//! sslsniff output, one JSON object per SSL read or write. Lines starting
+//! with `#` are comments. Other crates can use this module through the
+//! `test-support` feature.
+
+use crate::HttpDecoder;
+use oisp_core::events::OispEvent;
+use oisp_core::plugins::{DecodePlugin, RawCaptureEvent};
+use std::path::{Path, PathBuf};
+
+/// Path of a fixture in `fixtures/raw/`, e.g. `fixture_path("openai-streaming")`
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../fixtures/raw")
+        .join(name)
+        .with_extension("jsonl")
+}
+
+/// Parse a recorded sslsniff capture into raw events
+pub fn load_sslsniff(path: &Path) -> std::io::Result<Vec<RawCaptureEvent>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut fragments = oisp_core::sslsniff::FragmentAssembler::new();
+    let mut events = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fragment = oisp_core::sslsniff::parse_fragment(line).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}:{}: not an sslsniff event", path.display(), n + 1),
+            )
+        })?;
+        events.extend(fragments.push(fragment));
+    }
+    Ok(events)
+}
+
+/// Feed `raw` through `decoder` in order, collecting every decoded event
+pub async fn decode_all(
+    decoder: &HttpDecoder,
+    raw: impl IntoIterator<Item = RawCaptureEvent>,
+) -> Vec<OispEvent> {
+    let mut events = Vec::new();
+    for event in raw {
+        let id = event.id.clone();
+        match decoder.decode(event).await {
+            Ok(decoded) => events.extend(decoded),
+            Err(e) => panic!("decoding raw event {} failed: {}", id, e),
+        }
+    }
+    events
+}
+
+/// Replay a named fixture through a fresh decoder
+pub async fn replay_fixture(name: &str) -> Vec<OispEvent> {
+    let path = fixture_path(name);
+    let raw =
+        load_sslsniff(&path).unwrap_or_else(|e| panic!("failed to load {}: {}", path.display(), e));
+    decode_all(&HttpDecoder::new(), raw).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{CONTEXT_LENGTH_ERROR, RATE_LIMIT_ERROR};
+    use crate::rag::{RAG_NAMESPACE_ATTR, RAG_TOP_K_ATTR, RAG_VECTOR_DIMS_ATTR};
+    use oisp_core::events::{
+        AiRequestEvent, AiResponseEvent, FinishReason, MessageContent, RequestType,
+    };
+
+    fn split(events: &[OispEvent]) -> (&AiRequestEvent, &AiResponseEvent) {
+        let requests: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                OispEvent::AiRequest(r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        let responses: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                OispEvent::AiResponse(r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(requests.len(), 1, "requests: {:#?}", events);
+        assert_eq!(responses.len(), 1, "responses: {:#?}", events);
+        assert_eq!(responses[0].data.request_id, requests[0].data.request_id);
+        (requests[0], responses[0])
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_completion() {
+        let events = replay_fixture("openai-chat-completion").await;
+        let (req, resp) = split(&events);
+
+        assert_eq!(req.data.provider.as_ref().unwrap().name, "openai");
+        assert_eq!(req.data.model.as_ref().unwrap().id, "gpt-4o-mini");
+        assert_eq!(req.data.streaming, Some(false));
+        assert_eq!(req.data.messages.len(), 2);
+
+        assert_eq!(resp.data.status_code, Some(200));
+        // Dated snapshot normalized to the canonical model
+        let model = resp.data.model.as_ref().unwrap();
+        assert_eq!(model.id, "gpt-4o-mini");
+        assert_eq!(model.family.as_deref(), Some("gpt-4o"));
+        assert_eq!(model.version.as_deref(), Some("gpt-4o-mini-2024-07-18"));
+        let usage = resp.data.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, Some(23));
+        assert_eq!(usage.completion_tokens, Some(4));
+        assert_eq!(usage.total_tokens, Some(27));
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(resp.data.latency_ms, Some(412));
+    }
+
+    #[tokio::test]
+    async fn test_openai_streaming() {
+        let events = replay_fixture("openai-streaming").await;
+        let (req, resp) = split(&events);
+
+        assert_eq!(req.data.provider.as_ref().unwrap().name, "openai");
+        assert_eq!(req.data.model.as_ref().unwrap().id, "gpt-4o");
+        assert_eq!(req.data.streaming, Some(true));
+
+        let usage = resp.data.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, Some(12));
+        assert_eq!(usage.completion_tokens, Some(7));
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(
+            matches!(&message.content, Some(MessageContent::Text(t)) if t == "One, two, three."),
+            "{:?}",
+            message.content
+        );
+        // Measured to the read that completed the stream
+        assert_eq!(resp.data.latency_ms, Some(1207));
+    }
+
+    #[tokio::test]
+    async fn test_openai_embedding() {
+        let events = replay_fixture("openai-embedding").await;
+        let (req, resp) = split(&events);
+
+        assert_eq!(req.data.provider.as_ref().unwrap().name, "openai");
+        assert_eq!(
+            req.data.model.as_ref().unwrap().id,
+            "text-embedding-3-small"
+        );
+        assert_eq!(req.data.request_type, Some(RequestType::Embedding));
+
+        assert_eq!(resp.data.status_code, Some(200));
+        let usage = resp.data.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, Some(11));
+        assert_eq!(usage.total_tokens, Some(11));
+        assert_eq!(resp.data.latency_ms, Some(87));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_gzip_response() {
+        let events = replay_fixture("anthropic-gzip").await;
+        let (req, resp) = split(&events);
+
+        assert_eq!(req.data.provider.as_ref().unwrap().name, "anthropic");
+        let model = req.data.model.as_ref().unwrap();
+        assert_eq!(model.id, "claude-3-5-sonnet");
+        assert_eq!(model.family.as_deref(), Some("claude-3.5"));
+        assert_eq!(model.version.as_deref(), Some("claude-3-5-sonnet-20241022"));
+
+        let usage = resp.data.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, Some(21));
+        assert_eq!(usage.completion_tokens, Some(10));
+        assert_eq!(resp.data.finish_reason, Some(FinishReason::Stop));
+        let message = resp.data.choices[0].message.as_ref().unwrap();
+        assert!(
+            matches!(&message.content, Some(MessageContent::Text(t)) if t == "The capital of France is Paris."),
+            "{:?}",
+            message.content
+        );
+        assert_eq!(resp.data.latency_ms, Some(1530));
+    }
+
+    #[tokio::test]
+    async fn test_openai_rate_limit_error() {
+        let events = replay_fixture("openai-rate-limit").await;
+        let (req, resp) = split(&events);
+
+        // Streaming was requested, but the error comes back as plain JSON
+        assert_eq!(req.data.streaming, Some(true));
+        assert_eq!(resp.data.status_code, Some(429));
+        assert_eq!(resp.data.success, Some(false));
+        assert_eq!(resp.data.provider.as_ref().unwrap().name, "openai");
+        let error = resp.data.error.as_ref().unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("tokens"));
+        assert_eq!(error.code.as_deref(), Some(RATE_LIMIT_ERROR));
+        assert!(error
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("Rate limit reached for gpt-4o"));
+        assert_eq!(resp.data.latency_ms, Some(143));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_context_length_error() {
+        let events = replay_fixture("anthropic-context-length").await;
+        let (_, resp) = split(&events);
+
+        assert_eq!(resp.data.status_code, Some(400));
+        assert_eq!(resp.data.success, Some(false));
+        assert_eq!(resp.data.provider.as_ref().unwrap().name, "anthropic");
+        assert_eq!(
+            resp.data.provider_request_id.as_deref(),
+            Some("req_011CSHoEeqs5C35K2UUqR7Fy")
+        );
+        let error = resp.data.error.as_ref().unwrap();
+        assert_eq!(error.error_type.as_deref(), Some("invalid_request_error"));
+        assert_eq!(error.code.as_deref(), Some(CONTEXT_LENGTH_ERROR));
+        assert_eq!(
+            error.message.as_deref(),
+            Some("prompt is too long: 210519 tokens > 200000 maximum")
+        );
+        assert_eq!(resp.data.latency_ms, Some(96));
+    }
+
+    #[tokio::test]
+    async fn test_langchain_pinecone_retrieval() {
+        let events = replay_fixture("langchain-pinecone-rag").await;
+        let (req, _) = split(&events);
+        assert_eq!(req.data.request_type, Some(RequestType::Embedding));
+
+        let retrievals: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                OispEvent::AgentRagRetrieve(r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retrievals.len(), 1, "events: {:#?}", events);
+        let retrieval = retrievals[0];
+
+        let source = retrieval.data.source.as_ref().unwrap();
+        assert_eq!(source.provider.as_deref(), Some("pinecone"));
+        assert_eq!(source.name.as_deref(), Some("docs-index"));
+        let agent = retrieval.data.agent.as_ref().unwrap();
+        assert_eq!(agent.framework.as_deref(), Some("langchain"));
+        assert_eq!(retrieval.envelope.attrs[RAG_TOP_K_ATTR], 3);
+        assert_eq!(retrieval.envelope.attrs[RAG_NAMESPACE_ATTR], "handbook");
+        assert_eq!(retrieval.envelope.attrs[RAG_VECTOR_DIMS_ATTR], 8);
+        assert_eq!(retrieval.data.latency_ms, Some(23));
+
+        assert_eq!(retrieval.data.results_count, Some(3));
+        assert_eq!(
+            retrieval.data.results[0].source.as_deref(),
+            Some("handbook-p12")
+        );
+        assert_eq!(retrieval.data.results[0].score, Some(0.8731));
+        assert!(retrieval
+            .data
+            .results
+            .iter()
+            .all(|r| r.content_preview.is_none() && r.metadata.is_none()));
+    }
+}
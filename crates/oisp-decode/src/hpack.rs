@@ -0,0 +1,429 @@
+//! HPACK header decompression (RFC 7541)
+//!
+//! A decode-only implementation used to recover HTTP/2 request and response
+//! headers from captured plaintext. Each direction of a connection keeps its
+//! own [`HpackDecoder`], since the dynamic table is per-direction state.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// Default dynamic table size (SETTINGS_HEADER_TABLE_SIZE initial value)
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+/// Per-entry overhead counted against the dynamic table size
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Errors decoding an HPACK header block
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HpackError {
+    #[error("header block ended unexpectedly")]
+    Truncated,
+
+    #[error("integer overflow")]
+    IntegerOverflow,
+
+    #[error("invalid table index {0}")]
+    InvalidIndex(usize),
+
+    #[error("invalid huffman encoding")]
+    InvalidHuffman,
+
+    #[error("header block too large")]
+    HeaderBlockTooLarge,
+}
+
+/// Stateful HPACK decoder for one direction of an HTTP/2 connection
+#[derive(Debug)]
+pub struct HpackDecoder {
+    dynamic: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HpackDecoder {
+    pub fn new() -> Self {
+        Self {
+            dynamic: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+        }
+    }
+
+    /// Decode a complete header block into an ordered list of fields
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, HpackError> {
+        let mut headers = Vec::new();
+        let mut pos = 0;
+
+        while pos < block.len() {
+            let byte = block[pos];
+
+            if byte & 0x80 != 0 {
+                // Indexed header field
+                let index = decode_integer(block, &mut pos, 7)?;
+                headers.push(self.get(index)?);
+            } else if byte & 0x40 != 0 {
+                // Literal with incremental indexing
+                let (name, value) = self.decode_literal(block, &mut pos, 6)?;
+                self.insert(name.clone(), value.clone());
+                headers.push((name, value));
+            } else if byte & 0x20 != 0 {
+                // Dynamic table size update. The SETTINGS that bound it are
+                // sent in the other direction, so any size is accepted.
+                self.max_size = decode_integer(block, &mut pos, 5)?;
+                self.evict();
+            } else {
+                // Literal without indexing / never indexed
+                headers.push(self.decode_literal(block, &mut pos, 4)?);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    fn decode_literal(
+        &self,
+        block: &[u8],
+        pos: &mut usize,
+        prefix: u8,
+    ) -> Result<(String, String), HpackError> {
+        let index = decode_integer(block, pos, prefix)?;
+        let name = if index == 0 {
+            decode_string(block, pos)?
+        } else {
+            self.get(index)?.0
+        };
+        let value = decode_string(block, pos)?;
+        Ok((name, value))
+    }
+
+    fn get(&self, index: usize) -> Result<(String, String), HpackError> {
+        if index == 0 {
+            return Err(HpackError::InvalidIndex(index));
+        }
+        if let Some((name, value)) = STATIC_TABLE.get(index - 1) {
+            return Ok((name.to_string(), value.to_string()));
+        }
+        self.dynamic
+            .get(index - STATIC_TABLE.len() - 1)
+            .cloned()
+            .ok_or(HpackError::InvalidIndex(index))
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let entry_size = name.len() + value.len() + ENTRY_OVERHEAD;
+        if entry_size > self.max_size {
+            // An oversized entry empties the table
+            self.dynamic.clear();
+            self.size = 0;
+            return;
+        }
+        self.size += entry_size;
+        self.dynamic.push_front((name, value));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            match self.dynamic.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => {
+                    self.size = 0;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Decode an HPACK integer with an N-bit prefix (RFC 7541 §5.1)
+fn decode_integer(data: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, HpackError> {
+    let mask = (1u16 << prefix) as usize - 1;
+    let first = *data.get(*pos).ok_or(HpackError::Truncated)?;
+    *pos += 1;
+
+    let mut value = first as usize & mask;
+    if value < mask {
+        return Ok(value);
+    }
+
+    let mut shift = 0u32;
+    loop {
+        let byte = *data.get(*pos).ok_or(HpackError::Truncated)?;
+        *pos += 1;
+        if shift > 28 {
+            return Err(HpackError::IntegerOverflow);
+        }
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+/// Decode an HPACK string literal (RFC 7541 §5.2)
+fn decode_string(data: &[u8], pos: &mut usize) -> Result<String, HpackError> {
+    let huffman = data.get(*pos).ok_or(HpackError::Truncated)? & 0x80 != 0;
+    let len = decode_integer(data, pos, 7)?;
+    let end = pos.checked_add(len).ok_or(HpackError::Truncated)?;
+    let raw = data.get(*pos..end).ok_or(HpackError::Truncated)?;
+    *pos = end;
+
+    let bytes = if huffman {
+        huffman_decode(raw)?
+    } else {
+        raw.to_vec()
+    };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Decode a Huffman-encoded string (RFC 7541 Appendix B)
+pub fn huffman_decode(data: &[u8]) -> Result<Vec<u8>, HpackError> {
+    let lookup = huffman_lookup();
+    let mut out = Vec::with_capacity(data.len() * 8 / 5);
+    let mut code: u32 = 0;
+    let mut len: u8 = 0;
+
+    for byte in data {
+        for bit in (0..8).rev() {
+            code = (code << 1) | ((byte >> bit) & 1) as u32;
+            len += 1;
+            if let Some(&sym) = lookup.get(&(len, code)) {
+                if sym == 256 {
+                    // EOS must not appear in the encoded data
+                    return Err(HpackError::InvalidHuffman);
+                }
+                out.push(sym as u8);
+                code = 0;
+                len = 0;
+            } else if len >= 30 {
+                return Err(HpackError::InvalidHuffman);
+            }
+        }
+    }
+
+    // Padding is at most 7 bits, all ones (the EOS prefix)
+    if len > 7 || code != (1 << len) - 1 {
+        return Err(HpackError::InvalidHuffman);
+    }
+
+    Ok(out)
+}
+
+fn huffman_lookup() -> &'static HashMap<(u8, u32), u16> {
+    static LOOKUP: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+    LOOKUP.get_or_init(|| {
+        HUFFMAN_CODES
+            .iter()
+            .enumerate()
+            .map(|(sym, &(code, len))| ((len, code), sym as u16))
+            .collect()
+    })
+}
+
+/// HPACK static table (RFC 7541 Appendix A)
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Huffman codes as (code, bit length), indexed by symbol (RFC 7541 Appendix B)
+#[rustfmt::skip]
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28),
+    (0xfffffe4, 28), (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28),
+    (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28),
+    (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28),
+    (0xffffff8, 28), (0xffffff9, 28), (0xffffffa, 28), (0xffffffb, 28),
+    (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11),
+    (0x3fa, 10), (0x3fb, 10), (0xf9, 8), (0x7fb, 11),
+    (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6),
+    (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6),
+    (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10),
+    (0x1ffa, 13), (0x21, 6), (0x5d, 7), (0x5e, 7),
+    (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7),
+    (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7),
+    (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7),
+    (0xfc, 8), (0x73, 7), (0xfd, 8), (0x1ffb, 13),
+    (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5),
+    (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6),
+    (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5),
+    (0x2b, 6), (0x76, 7), (0x2c, 6), (0x8, 5),
+    (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15),
+    (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23),
+    (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23), (0x7fffdc, 23),
+    (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23),
+    (0xffffee, 24), (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23),
+    (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24),
+    (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22),
+    (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24),
+    (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23), (0x7fffec, 23),
+    (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23),
+    (0xfffea, 20), (0x3fffe2, 22), (0x3fffe3, 22), (0x3fffe4, 22),
+    (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19),
+    (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25),
+    (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25),
+    (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26), (0x7ffffe0, 27),
+    (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26),
+    (0xffffffd, 28), (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27),
+    (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25),
+    (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26),
+    (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27), (0x7ffffea, 27),
+    (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        hex::decode(s.replace(' ', "")).unwrap()
+    }
+
+    #[test]
+    fn test_decode_integer() {
+        // RFC 7541 C.1.2: 1337 with a 5-bit prefix
+        let data = [0x1f, 0x9a, 0x0a];
+        let mut pos = 0;
+        assert_eq!(decode_integer(&data, &mut pos, 5).unwrap(), 1337);
+        assert_eq!(pos, 3);
+    }
+
+    #[test]
+    fn test_huffman_decode() {
+        // RFC 7541 C.4.1
+        let decoded = huffman_decode(&hex("f1e3 c2e5 f23a 6ba0 ab90 f4ff")).unwrap();
+        assert_eq!(decoded, b"www.example.com");
+    }
+
+    #[test]
+    fn test_decode_requests_with_huffman_and_dynamic_table() {
+        // RFC 7541 C.4.1 - C.4.2
+        let mut decoder = HpackDecoder::new();
+
+        let first = decoder
+            .decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff"))
+            .unwrap();
+        assert_eq!(
+            first,
+            vec![
+                (":method".to_string(), "GET".to_string()),
+                (":scheme".to_string(), "http".to_string()),
+                (":path".to_string(), "/".to_string()),
+                (":authority".to_string(), "www.example.com".to_string()),
+            ]
+        );
+
+        let second = decoder
+            .decode(&hex("8286 84be 5886 a8eb 1064 9cbf"))
+            .unwrap();
+        assert_eq!(
+            second[3],
+            (":authority".to_string(), "www.example.com".to_string())
+        );
+        assert_eq!(
+            second[4],
+            ("cache-control".to_string(), "no-cache".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_index() {
+        let mut decoder = HpackDecoder::new();
+        assert_eq!(
+            decoder.decode(&[0xff, 0x00]),
+            Err(HpackError::InvalidIndex(127))
+        );
+    }
+}
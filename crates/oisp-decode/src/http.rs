@@ -1,7 +1,10 @@
 //! HTTP parsing utilities
 //!
-//! Provides parsing for HTTP/1.1 requests and responses captured from SSL/TLS data.
+//! Provides parsing for HTTP/1.1 requests and responses captured from SSL/TLS data,
+//! reassembly of HTTP/2 streams into the same parsed request/response types, and
+//! streaming parsing of `multipart/form-data` request bodies.
 
+use crate::hpack::HpackDecoder;
 use std::collections::HashMap;
 
 /// Parsed HTTP request
@@ -17,6 +20,8 @@ pub struct ParsedHttpRequest {
     pub content_length: Option<usize>,
     /// Whether the request body uses chunked transfer encoding
     pub is_chunked: bool,
+    /// Parts of a `multipart/form-data` body
+    pub multipart: Option<MultipartForm>,
 }
 
 impl ParsedHttpRequest {
@@ -93,12 +98,14 @@ pub fn parse_request(data: &[u8]) -> Option<ParsedHttpRequest> {
                 None
             };
 
+            let content_type = header_map.get("content-type").cloned();
             Some(ParsedHttpRequest {
                 method: req.method?.to_string(),
                 path: req.path?.to_string(),
                 version: format!("HTTP/1.{}", req.version?),
                 host: header_map.get("host").cloned(),
-                content_type: header_map.get("content-type").cloned(),
+                multipart: parse_multipart(content_type.as_deref(), body.as_deref()),
+                content_type,
                 content_length,
                 is_chunked,
                 headers: header_map,
@@ -132,6 +139,7 @@ pub fn parse_response(data: &[u8]) -> Option<ParsedHttpResponse> {
                     ct.contains("text/event-stream")
                         || ct.contains("application/x-ndjson")
                         || ct.contains("application/stream+json")
+                        || ct.contains("application/vnd.amazon.eventstream")
                 })
                 .unwrap_or(false);
 
@@ -249,7 +257,7 @@ pub fn decode_chunked_body(data: &[u8]) -> Option<Vec<u8>> {
         }
 
         // Read chunk data
-        if pos + chunk_size > data.len() {
+        if chunk_size > data.len() - pos {
             // Incomplete chunk - just return what we have so far
             tracing::info!(
                 "decode_chunked_body: incomplete chunk, need {} bytes but only {} remaining",
@@ -282,7 +290,228 @@ pub fn decode_chunked_body(data: &[u8]) -> Option<Vec<u8>> {
     }
 }
 
+/// Check whether a chunked body has been received up to its terminal chunk
+///
+/// Walks the chunk sizes rather than searching for `0\r\n\r\n`, so chunk
+/// data that happens to contain the marker does not end the body early.
+/// Trailer fields after the last chunk are allowed.
+pub fn is_chunked_body_complete(data: &[u8]) -> bool {
+    let mut pos = 0;
+
+    loop {
+        let Some(size_end) = find_crlf(&data[pos..]) else {
+            return false;
+        };
+        let size = std::str::from_utf8(&data[pos..pos + size_end])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|hex| usize::from_str_radix(hex.trim(), 16).ok());
+        let Some(size) = size else {
+            return false;
+        };
+        pos += size_end + 2;
+
+        if size == 0 {
+            break;
+        }
+        // Chunk data plus its CRLF
+        pos = match size.checked_add(2).and_then(|len| pos.checked_add(len)) {
+            Some(end) if end <= data.len() => end,
+            _ => return false,
+        };
+    }
+
+    // Trailer section: header lines until an empty line
+    loop {
+        match find_crlf(&data[pos..]) {
+            Some(0) => return true,
+            Some(line_end) => pos += line_end + 2,
+            None => return false,
+        }
+    }
+}
+
 /// Find position of \r\n in data
+/// Decompress a complete gzip body
+pub fn gunzip(data: &[u8]) -> Option<Vec<u8>> {
+    use flate2::bufread::GzDecoder;
+    use std::io::{BufReader, Read};
+
+    let reader = BufReader::new(data);
+    let mut decoder = GzDecoder::new(reader);
+    let mut decompressed = Vec::new();
+
+    match decoder.read_to_end(&mut decompressed) {
+        Ok(_) if !decompressed.is_empty() => Some(decompressed),
+        Ok(_) => {
+            tracing::info!("Gzip decompress returned empty");
+            None
+        }
+        Err(e) => {
+            tracing::info!("Gzip decompress failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Decompress as much of a gzip body as possible, for truncated or still
+/// streaming bodies
+///
+/// Skips the gzip wrapper and inflates the raw deflate data. Handles streaming gzip with sync-flush markers (00 00 00 ff ff or 00 00 ff ff)
+pub fn gunzip_lenient(data: &[u8]) -> Option<Vec<u8>> {
+    use flate2::Decompress;
+    use flate2::FlushDecompress;
+
+    // Skip gzip header (minimum 10 bytes)
+    if data.len() <= 10 || data[0] != 0x1f || data[1] != 0x8b {
+        return None;
+    }
+
+    let mut header_end = 10;
+    let flags = data[3];
+
+    // Check for extra field (FEXTRA)
+    if flags & 0x04 != 0 && header_end + 2 <= data.len() {
+        let xlen = u16::from_le_bytes([data[header_end], data[header_end + 1]]) as usize;
+        header_end += 2 + xlen;
+    }
+    // Check for filename (FNAME)
+    if flags & 0x08 != 0 {
+        while header_end < data.len() && data[header_end] != 0 {
+            header_end += 1;
+        }
+        header_end += 1;
+    }
+    // Check for comment (FCOMMENT)
+    if flags & 0x10 != 0 {
+        while header_end < data.len() && data[header_end] != 0 {
+            header_end += 1;
+        }
+        header_end += 1;
+    }
+    // Check for header CRC (FHCRC)
+    if flags & 0x02 != 0 {
+        header_end += 2;
+    }
+
+    if header_end >= data.len() {
+        return None;
+    }
+
+    // Check for sync-flush marker at the start of deflate data
+    // Streaming gzip often starts with an empty sync-flush: 00 00 00 ff ff or 00 00 ff ff
+    let mut deflate_start = header_end;
+
+    // Pattern 1: 00 00 00 ff ff (5 bytes - empty stored block with sync flush)
+    if data.len() >= deflate_start + 5
+        && data[deflate_start] == 0x00
+        && data[deflate_start + 1] == 0x00
+        && data[deflate_start + 2] == 0x00
+        && data[deflate_start + 3] == 0xff
+        && data[deflate_start + 4] == 0xff
+    {
+        tracing::info!("Found 5-byte sync-flush marker at start, skipping");
+        deflate_start += 5;
+    }
+    // Pattern 2: 00 00 ff ff (4 bytes)
+    else if data.len() >= deflate_start + 4
+        && data[deflate_start] == 0x00
+        && data[deflate_start + 1] == 0x00
+        && data[deflate_start + 2] == 0xff
+        && data[deflate_start + 3] == 0xff
+    {
+        tracing::info!("Found 4-byte sync-flush marker at start, skipping");
+        deflate_start += 4;
+    }
+
+    // The deflate data, excluding the 8-byte gzip trailer (if present)
+    let deflate_end = if data.len() >= deflate_start + 8 {
+        data.len() - 8
+    } else {
+        data.len()
+    };
+
+    if deflate_start >= deflate_end {
+        tracing::info!("No deflate data after skipping sync-flush markers");
+        return None;
+    }
+
+    let deflate_data = &data[deflate_start..deflate_end];
+
+    tracing::info!(
+        "Trying streaming deflate on {} bytes (deflate_start={}, deflate_end={}), first 10 bytes: {:?}",
+        deflate_data.len(),
+        deflate_start,
+        deflate_end,
+        &deflate_data[..std::cmp::min(10, deflate_data.len())]
+    );
+
+    // Use low-level Decompress API which can return partial results
+    let mut decompress = Decompress::new(false); // false = raw deflate (no zlib header)
+    let mut output = vec![0u8; 10 * 1024 * 1024]; // 10MB max
+    let mut total_out = 0;
+    let mut total_in = 0;
+
+    loop {
+        let before_in = decompress.total_in() as usize;
+        let before_out = decompress.total_out() as usize;
+
+        let input = &deflate_data[total_in..];
+        let out_slice = &mut output[total_out..];
+
+        if input.is_empty() || out_slice.is_empty() {
+            break;
+        }
+
+        match decompress.decompress(input, out_slice, FlushDecompress::Sync) {
+            Ok(status) => {
+                let bytes_in = decompress.total_in() as usize - before_in;
+                let bytes_out = decompress.total_out() as usize - before_out;
+                total_in += bytes_in;
+                total_out += bytes_out;
+
+                match status {
+                    flate2::Status::Ok => {
+                        // Continue decompressing
+                        if bytes_in == 0 && bytes_out == 0 {
+                            break; // No progress
+                        }
+                    }
+                    flate2::Status::BufError => {
+                        // Need more output space or hit end of input
+                        break;
+                    }
+                    flate2::Status::StreamEnd => {
+                        // Done!
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::info!(
+                    "Streaming deflate error after {} bytes out: {}",
+                    total_out,
+                    e
+                );
+                break;
+            }
+        }
+    }
+
+    if total_out > 0 {
+        output.truncate(total_out);
+        tracing::info!(
+            "Streaming deflate succeeded: {} in -> {} out",
+            total_in,
+            total_out
+        );
+        Some(output)
+    } else {
+        tracing::info!("Streaming deflate produced no output");
+        None
+    }
+}
+
 fn find_crlf(data: &[u8]) -> Option<usize> {
     (0..data.len().saturating_sub(1)).find(|&i| data[i] == b'\r' && data[i + 1] == b'\n')
 }
@@ -314,6 +543,28 @@ pub fn is_http_response(data: &[u8]) -> bool {
     data.starts_with(b"HTTP/")
 }
 
+/// Skip interim (1xx) responses at the start of `data`
+///
+/// A server answers `Expect: 100-continue` with `100 Continue` before the
+/// final response, sometimes in the same read. `101 Switching Protocols`
+/// is final and kept.
+pub fn skip_interim_responses(mut data: &[u8]) -> &[u8] {
+    loop {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut resp = httparse::Response::new(&mut headers);
+        let header_len = match resp.parse(data) {
+            Ok(httparse::Status::Complete(header_len)) => header_len,
+            _ => return data,
+        };
+        match resp.code {
+            Some(code) if (100..200).contains(&code) && code != 101 => {
+                data = &data[header_len..];
+            }
+            _ => return data,
+        }
+    }
+}
+
 /// Extract the body from potentially incomplete HTTP data
 /// Useful for streaming responses where we may not have the full body
 pub fn extract_partial_body(data: &[u8]) -> Option<&[u8]> {
@@ -341,6 +592,656 @@ fn find_subsequence(data: &[u8], pattern: &[u8]) -> Option<usize> {
     (0..=data.len() - pattern.len()).find(|&i| &data[i..i + pattern.len()] == pattern)
 }
 
+/// Bytes of a non-file form field's value that are kept
+pub const MAX_FORM_FIELD_VALUE: usize = 4096;
+
+/// Part headers longer than this are treated as a malformed body
+const MAX_PART_HEADERS: usize = 16 * 1024;
+
+/// One part of a `multipart/form-data` body
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MultipartPart {
+    /// Form field name
+    pub name: Option<String>,
+    /// File name, present for file uploads
+    pub filename: Option<String>,
+    /// Content type of the part
+    pub content_type: Option<String>,
+    /// Size of the part's content in bytes
+    pub size: usize,
+    /// Content of a non-file part, up to [`MAX_FORM_FIELD_VALUE`] bytes
+    pub value: Option<String>,
+}
+
+impl MultipartPart {
+    /// Whether this part is a file upload
+    pub fn is_file(&self) -> bool {
+        self.filename.is_some()
+    }
+}
+
+/// Parts of a `multipart/form-data` body
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MultipartForm {
+    pub parts: Vec<MultipartPart>,
+    /// Whether the closing boundary was seen
+    pub complete: bool,
+}
+
+impl MultipartForm {
+    /// Value of the first non-file field with this name
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.parts
+            .iter()
+            .filter(|p| !p.is_file())
+            .find(|p| p.name.as_deref() == Some(name))
+            .and_then(|p| p.value.as_deref())
+    }
+
+    /// File upload parts
+    pub fn files(&self) -> impl Iterator<Item = &MultipartPart> {
+        self.parts.iter().filter(|p| p.is_file())
+    }
+}
+
+/// Boundary of a `multipart/form-data` content type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let boundary = value.trim().trim_matches('"');
+        (!boundary.is_empty()).then(|| boundary.to_string())
+    })
+}
+
+/// Parse a complete body if the content type is `multipart/form-data`
+fn parse_multipart(content_type: Option<&str>, body: Option<&[u8]>) -> Option<MultipartForm> {
+    let boundary = multipart_boundary(content_type?)?;
+    let mut parser = MultipartParser::new(&boundary);
+    parser.feed(body.unwrap_or_default());
+    Some(parser.finish())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MultipartState {
+    /// Looking for a delimiter, in the preamble or a part's content
+    Content,
+    /// After a delimiter, before the `--` or line break that follows it
+    Delimiter,
+    /// Reading a part's headers
+    Headers,
+    /// After the closing delimiter, or given up on a malformed body
+    Done,
+}
+
+/// Incremental `multipart/form-data` parser
+///
+/// Records each part's headers and size. Content is not kept, apart from
+/// a prefix of non-file fields, and only a delimiter's length of it is held
+/// back between calls to [`feed`](Self::feed), so uploads of any size are
+/// parsed in constant memory.
+#[derive(Debug)]
+pub struct MultipartParser {
+    /// `\r\n--boundary`
+    delimiter: Vec<u8>,
+    state: MultipartState,
+    pending: Vec<u8>,
+    current: Option<MultipartPart>,
+    parts: Vec<MultipartPart>,
+}
+
+impl MultipartParser {
+    pub fn new(boundary: &str) -> Self {
+        Self {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            state: MultipartState::Content,
+            // The first delimiter may open the body without a line break
+            pending: b"\r\n".to_vec(),
+            current: None,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Feed the next bytes of the body
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.state == MultipartState::Done {
+            return;
+        }
+        self.pending.extend_from_slice(data);
+
+        loop {
+            match self.state {
+                MultipartState::Content => {
+                    match find_subsequence(&self.pending, &self.delimiter) {
+                        Some(pos) => {
+                            self.consume_content(pos);
+                            self.pending.drain(..self.delimiter.len());
+                            if let Some(part) = self.current.take() {
+                                self.parts.push(part);
+                            }
+                            self.state = MultipartState::Delimiter;
+                        }
+                        None => {
+                            // Keep what could be the start of a split delimiter
+                            let keep = self.delimiter.len() - 1;
+                            if self.pending.len() > keep {
+                                self.consume_content(self.pending.len() - keep);
+                            }
+                            return;
+                        }
+                    }
+                }
+                MultipartState::Delimiter => {
+                    if self.pending.len() < 2 {
+                        return;
+                    }
+                    if self.pending.starts_with(b"--") {
+                        self.state = MultipartState::Done;
+                        self.pending.clear();
+                        return;
+                    }
+                    self.state = MultipartState::Headers;
+                }
+                MultipartState::Headers => {
+                    let Some(end) = find_subsequence(&self.pending, b"\r\n\r\n") else {
+                        if self.pending.len() > MAX_PART_HEADERS {
+                            self.state = MultipartState::Done;
+                            self.pending.clear();
+                        }
+                        return;
+                    };
+                    let headers = String::from_utf8_lossy(&self.pending[..end]).to_string();
+                    self.pending.drain(..end + 4);
+                    self.current = Some(parse_part_headers(&headers));
+                    self.state = MultipartState::Content;
+                }
+                MultipartState::Done => return,
+            }
+        }
+    }
+
+    /// Pass the first `len` pending bytes to the current part
+    fn consume_content(&mut self, len: usize) {
+        if let Some(part) = &mut self.current {
+            part.size += len;
+            if !part.is_file() {
+                let value = part.value.get_or_insert_with(String::new);
+                let room = MAX_FORM_FIELD_VALUE.saturating_sub(value.len());
+                let take = &self.pending[..len.min(room)];
+                value.push_str(&String::from_utf8_lossy(take));
+            }
+        }
+        self.pending.drain(..len);
+    }
+
+    /// Parts seen so far, including one cut short by the end of the data
+    pub fn finish(mut self) -> MultipartForm {
+        if let Some(mut part) = self.current.take() {
+            if self.state == MultipartState::Content {
+                part.size += self.pending.len();
+            }
+            self.parts.push(part);
+        }
+        MultipartForm {
+            parts: self.parts,
+            complete: self.state == MultipartState::Done,
+        }
+    }
+}
+
+/// Parse the headers of one part (the lines before its blank line)
+fn parse_part_headers(headers: &str) -> MultipartPart {
+    let mut part = MultipartPart::default();
+    for line in headers.split("\r\n") {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("content-type") {
+            part.content_type = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("content-disposition") {
+            for param in value.split(';').skip(1) {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"').to_string();
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "name" => part.name = Some(value),
+                    "filename" => part.filename = Some(value),
+                    _ => {}
+                }
+            }
+        }
+    }
+    part
+}
+
+/// HTTP/2 client connection preface
+pub const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// HTTP/2 frame header length
+const H2_FRAME_HEADER_LEN: usize = 9;
+
+const H2_FRAME_DATA: u8 = 0x0;
+const H2_FRAME_HEADERS: u8 = 0x1;
+const H2_FRAME_RST_STREAM: u8 = 0x3;
+const H2_FRAME_CONTINUATION: u8 = 0x9;
+
+const H2_FLAG_END_STREAM: u8 = 0x1;
+const H2_FLAG_END_HEADERS: u8 = 0x4;
+const H2_FLAG_PADDED: u8 = 0x8;
+const H2_FLAG_PRIORITY: u8 = 0x20;
+
+/// Largest header block buffered for a stream
+///
+/// A block cannot be skipped without losing the HPACK dynamic table, so a
+/// larger one fails the connection.
+const MAX_H2_HEADER_BLOCK: usize = 256 * 1024;
+
+/// Body bytes kept per stream; the rest is dropped and the message marked truncated
+pub const MAX_H2_BODY: usize = 10 * 1024 * 1024;
+
+/// Check if data starts with the HTTP/2 client connection preface
+pub fn is_h2_preface(data: &[u8]) -> bool {
+    data.starts_with(b"PRI * HTTP/2.0")
+}
+
+/// A request or response reconstructed from one HTTP/2 stream
+#[derive(Debug, Clone)]
+pub struct H2Message {
+    pub stream_id: u32,
+    /// Decoded header fields in wire order, including pseudo-headers
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The body passed [`MAX_H2_BODY`] and was cut off there
+    pub truncated: bool,
+}
+
+impl H2Message {
+    /// Get the first value of a header field
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Regular (non-pseudo) header fields as a map, lowercased like HTTP/1.1 parsing
+    fn header_map(&self) -> HashMap<String, String> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| !n.starts_with(':'))
+            .map(|(n, v)| (n.to_lowercase(), v.clone()))
+            .collect()
+    }
+
+    fn body(&self) -> Option<Vec<u8>> {
+        if self.body.is_empty() {
+            None
+        } else {
+            Some(self.body.clone())
+        }
+    }
+
+    /// Convert to a parsed request, if this stream carried one
+    pub fn to_request(&self) -> Option<ParsedHttpRequest> {
+        let method = self.header(":method")?.to_string();
+        let path = self.header(":path")?.to_string();
+        let header_map = self.header_map();
+        let host = self
+            .header(":authority")
+            .map(|s| s.to_string())
+            .or_else(|| header_map.get("host").cloned());
+
+        let content_type = header_map.get("content-type").cloned();
+        let body = self.body();
+        Some(ParsedHttpRequest {
+            method,
+            path,
+            version: "HTTP/2".to_string(),
+            host,
+            multipart: parse_multipart(content_type.as_deref(), body.as_deref()),
+            content_type,
+            content_length: header_map
+                .get("content-length")
+                .and_then(|v| v.parse().ok()),
+            is_chunked: false,
+            body,
+            headers: header_map,
+        })
+    }
+
+    /// Convert to a parsed response, if this stream carried one
+    ///
+    /// The stream has ended, so `content_length` is the received body length.
+    pub fn to_response(&self) -> Option<ParsedHttpResponse> {
+        let status_code: u16 = self.header(":status")?.parse().ok()?;
+        let header_map = self.header_map();
+        let content_type = header_map.get("content-type").cloned();
+
+        let is_streaming = content_type
+            .as_ref()
+            .map(|ct| {
+                ct.contains("text/event-stream")
+                    || ct.contains("application/x-ndjson")
+                    || ct.contains("application/stream+json")
+                    || ct.contains("application/vnd.amazon.eventstream")
+            })
+            .unwrap_or(false);
+
+        let is_gzipped = header_map
+            .get("content-encoding")
+            .map(|v| v.to_lowercase().contains("gzip"))
+            .unwrap_or(false);
+
+        Some(ParsedHttpResponse {
+            status_code,
+            status_text: http::StatusCode::from_u16(status_code)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+                .unwrap_or("")
+                .to_string(),
+            version: "HTTP/2".to_string(),
+            content_type,
+            content_length: Some(self.body.len()),
+            is_streaming,
+            is_chunked: false,
+            is_gzipped,
+            headers: header_map,
+            body: self.body(),
+        })
+    }
+}
+
+/// Per-stream state while frames are being collected
+#[derive(Debug, Default)]
+struct H2Stream {
+    headers: Vec<(String, String)>,
+    /// Header block fragments awaiting END_HEADERS
+    header_block: Vec<u8>,
+    body: Vec<u8>,
+    /// Body data was dropped at [`MAX_H2_BODY`]
+    truncated: bool,
+    /// END_STREAM was set on the HEADERS frame still waiting for CONTINUATION
+    end_stream_pending: bool,
+}
+
+impl H2Stream {
+    /// Append a header block fragment, failing once the block is too large
+    fn extend_header_block(&mut self, fragment: &[u8]) -> Result<(), crate::hpack::HpackError> {
+        if self.header_block.len() + fragment.len() > MAX_H2_HEADER_BLOCK {
+            return Err(crate::hpack::HpackError::HeaderBlockTooLarge);
+        }
+        self.header_block.extend_from_slice(fragment);
+        Ok(())
+    }
+
+    fn extend_body(&mut self, data: &[u8]) {
+        let room = MAX_H2_BODY - self.body.len();
+        if data.len() > room {
+            self.truncated = true;
+        }
+        self.body.extend_from_slice(&data[..data.len().min(room)]);
+    }
+}
+
+/// Reassembles HEADERS/DATA frames for one direction of an HTTP/2 connection
+///
+/// Frames are buffered across reads, header blocks are HPACK-decoded with
+/// this direction's dynamic table, and a [`H2Message`] is produced for each
+/// stream once END_STREAM is seen.
+#[derive(Debug)]
+pub struct H2FrameReassembler {
+    buffer: Vec<u8>,
+    hpack: HpackDecoder,
+    streams: HashMap<u32, H2Stream>,
+    /// Still expecting the client connection preface
+    expect_preface: bool,
+    /// Decoding failed; the HPACK state can no longer be trusted
+    failed: bool,
+}
+
+impl H2FrameReassembler {
+    /// Reassembler for the client-to-server direction (starts with the preface)
+    pub fn client() -> Self {
+        Self {
+            expect_preface: true,
+            ..Self::server()
+        }
+    }
+
+    /// Reassembler for the server-to-client direction
+    pub fn server() -> Self {
+        Self {
+            buffer: Vec::new(),
+            hpack: HpackDecoder::new(),
+            streams: HashMap::new(),
+            expect_preface: false,
+            failed: false,
+        }
+    }
+
+    /// Whether a decoding error has made the connection undecodable
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Number of streams with frames still being collected
+    pub fn open_streams(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Feed captured bytes, returning messages for streams that completed
+    pub fn feed(&mut self, data: &[u8]) -> Vec<H2Message> {
+        let mut messages = Vec::new();
+        if self.failed {
+            return messages;
+        }
+        self.buffer.extend_from_slice(data);
+
+        if self.expect_preface {
+            if self.buffer.len() < H2_PREFACE.len() {
+                return messages;
+            }
+            if !self.buffer.starts_with(H2_PREFACE) {
+                self.failed = true;
+                return messages;
+            }
+            self.buffer.drain(..H2_PREFACE.len());
+            self.expect_preface = false;
+        }
+
+        let mut pos = 0;
+        while self.buffer.len() - pos >= H2_FRAME_HEADER_LEN {
+            let header = &self.buffer[pos..pos + H2_FRAME_HEADER_LEN];
+            let length = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+            let frame_type = header[3];
+            let flags = header[4];
+            let stream_id =
+                u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff;
+
+            let frame_end = pos + H2_FRAME_HEADER_LEN + length;
+            if self.buffer.len() < frame_end {
+                break;
+            }
+            let payload = self.buffer[pos + H2_FRAME_HEADER_LEN..frame_end].to_vec();
+            pos = frame_end;
+
+            if let Err(e) = self.handle_frame(frame_type, flags, stream_id, &payload, &mut messages)
+            {
+                tracing::debug!("HTTP/2 frame decoding failed: {}", e);
+                self.failed = true;
+                break;
+            }
+        }
+        self.buffer.drain(..pos);
+
+        messages
+    }
+
+    fn handle_frame(
+        &mut self,
+        frame_type: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: &[u8],
+        messages: &mut Vec<H2Message>,
+    ) -> Result<(), crate::hpack::HpackError> {
+        match frame_type {
+            H2_FRAME_HEADERS => {
+                let Some(mut fragment) = strip_padding(payload, flags) else {
+                    return Ok(());
+                };
+                if flags & H2_FLAG_PRIORITY != 0 {
+                    fragment = fragment.get(5..).unwrap_or_default();
+                }
+                let stream = self.streams.entry(stream_id).or_default();
+                stream.extend_header_block(fragment)?;
+                stream.end_stream_pending = flags & H2_FLAG_END_STREAM != 0;
+                if flags & H2_FLAG_END_HEADERS != 0 {
+                    self.finish_headers(stream_id, messages)?;
+                }
+            }
+            H2_FRAME_CONTINUATION => {
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.extend_header_block(payload)?;
+                    if flags & H2_FLAG_END_HEADERS != 0 {
+                        self.finish_headers(stream_id, messages)?;
+                    }
+                }
+            }
+            H2_FRAME_DATA => {
+                let data = strip_padding(payload, flags).unwrap_or_default();
+                // DATA without preceding HEADERS was opened before capture began
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.extend_body(data);
+                    if flags & H2_FLAG_END_STREAM != 0 {
+                        self.complete(stream_id, messages);
+                    }
+                }
+            }
+            H2_FRAME_RST_STREAM => {
+                self.streams.remove(&stream_id);
+            }
+            // SETTINGS, PING, WINDOW_UPDATE, GOAWAY, PRIORITY, PUSH_PROMISE
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish_headers(
+        &mut self,
+        stream_id: u32,
+        messages: &mut Vec<H2Message>,
+    ) -> Result<(), crate::hpack::HpackError> {
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            return Ok(());
+        };
+        let block = std::mem::take(&mut stream.header_block);
+        // Trailers are appended after the initial headers
+        stream.headers.extend(self.hpack.decode(&block)?);
+        if stream.end_stream_pending {
+            self.complete(stream_id, messages);
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, stream_id: u32, messages: &mut Vec<H2Message>) {
+        if let Some(stream) = self.streams.remove(&stream_id) {
+            messages.push(H2Message {
+                stream_id,
+                headers: stream.headers,
+                body: stream.body,
+                truncated: stream.truncated,
+            });
+        }
+    }
+}
+
+/// Strip the pad length byte and trailing padding from a PADDED frame
+fn strip_padding(payload: &[u8], flags: u8) -> Option<&[u8]> {
+    if flags & H2_FLAG_PADDED == 0 {
+        return Some(payload);
+    }
+    let pad_len = *payload.first()? as usize;
+    payload.get(1..payload.len().checked_sub(pad_len)?)
+}
+
+/// A captured HTTP/2 chat completion POST, shared by the HTTP/2 tests
+#[cfg(test)]
+pub(crate) mod h2_capture {
+    use super::*;
+
+    pub const REQUEST_BODY: &[u8] =
+        br#"{"model":"gpt-4o","messages":[{"role":"user","content":"Hello"}]}"#;
+
+    pub const RESPONSE_BODY: &[u8] = br#"{"id":"chatcmpl-1","model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"Hi!"},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}"#;
+
+    pub fn frame(frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.push(frame_type);
+        frame.push(flags);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Client side: preface, SETTINGS, then POST on stream 1 with its header
+    /// block split across HEADERS (padded) and CONTINUATION, body in two DATA frames
+    pub fn request() -> Vec<u8> {
+        let mut first = vec![0x83, 0x87, 0x44, 0x14];
+        first.extend_from_slice(b"/v1/chat/completions");
+        // :authority: api.openai.com (Huffman)
+        first.extend_from_slice(&hex::decode("418a1d665cf596a199721e9f").unwrap());
+
+        let mut second = vec![0x5f, 0x10];
+        second.extend_from_slice(b"application/json");
+        let content_length = REQUEST_BODY.len().to_string();
+        second.extend_from_slice(&[0x0f, 0x0d, content_length.len() as u8]);
+        second.extend_from_slice(content_length.as_bytes());
+        // authorization, never indexed
+        second.extend_from_slice(&[0x1f, 0x08, 0x0e]);
+        second.extend_from_slice(b"Bearer sk-test");
+
+        let mut padded = vec![3];
+        padded.extend_from_slice(&first);
+        padded.extend_from_slice(&[0, 0, 0]);
+
+        let (body_a, body_b) = REQUEST_BODY.split_at(20);
+
+        let mut data = H2_PREFACE.to_vec();
+        data.extend(frame(0x4, 0, 0, &[0x00, 0x03, 0x00, 0x00, 0x00, 0x64]));
+        data.extend(frame(H2_FRAME_HEADERS, H2_FLAG_PADDED, 1, &padded));
+        data.extend(frame(
+            H2_FRAME_CONTINUATION,
+            H2_FLAG_END_HEADERS,
+            1,
+            &second,
+        ));
+        data.extend(frame(H2_FRAME_DATA, 0, 1, body_a));
+        data.extend(frame(H2_FRAME_DATA, H2_FLAG_END_STREAM, 1, body_b));
+        data
+    }
+
+    /// Server side: SETTINGS, then the 200 response on stream 1
+    pub fn response() -> Vec<u8> {
+        // :status 200, content-type: application/json (Huffman)
+        let block = hex::decode("885f8b1d75d0620d263d4c7441ea").unwrap();
+
+        let mut data = frame(0x4, 0, 0, &[]);
+        data.extend(frame(H2_FRAME_HEADERS, H2_FLAG_END_HEADERS, 1, &block));
+        data.extend(frame(H2_FRAME_DATA, H2_FLAG_END_STREAM, 1, RESPONSE_BODY));
+        data
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -432,6 +1333,47 @@ mod tests {
         assert_eq!(decoded, b"Hello");
     }
 
+    #[test]
+    fn test_is_chunked_body_complete() {
+        assert!(is_chunked_body_complete(b"5\r\nHello\r\n0\r\n\r\n"));
+        assert!(is_chunked_body_complete(
+            b"5\r\nHello\r\n0\r\nX-Checksum: abc\r\n\r\n"
+        ));
+
+        assert!(!is_chunked_body_complete(b""));
+        assert!(!is_chunked_body_complete(b"5\r\nHel"));
+        assert!(!is_chunked_body_complete(b"5\r\nHello\r\n"));
+        assert!(!is_chunked_body_complete(b"5\r\nHello\r\n0\r\n"));
+        // The terminator inside chunk data is not the end of the body
+        assert!(!is_chunked_body_complete(b"a\r\n0\r\n\r\nabcd"));
+    }
+
+    #[test]
+    fn test_skip_interim_responses() {
+        let final_response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let both = [b"HTTP/1.1 100 Continue\r\n\r\n".as_slice(), final_response].concat();
+        assert_eq!(skip_interim_responses(&both), final_response);
+        assert!(skip_interim_responses(b"HTTP/1.1 100 Continue\r\n\r\n").is_empty());
+
+        assert_eq!(skip_interim_responses(final_response), final_response);
+        let upgrade = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+        assert_eq!(skip_interim_responses(upgrade), upgrade);
+        // Incomplete heads are left for the caller
+        assert_eq!(
+            skip_interim_responses(b"HTTP/1.1 100 Cont"),
+            b"HTTP/1.1 100 Cont"
+        );
+    }
+
+    #[test]
+    fn test_chunk_size_overflow() {
+        // Found by the decode_chunked_body fuzz target: a chunk size near
+        // usize::MAX overflowed the end-of-chunk arithmetic
+        let data = b"0FFFFFFFFFFFFFFF\r\n\x00\r\n\r\x10";
+        assert!(!is_chunked_body_complete(data));
+        assert_eq!(decode_chunked_body(data).unwrap(), b"\x00\r\n\r\x10");
+    }
+
     #[test]
     fn test_extract_partial_body() {
         let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nHello, World!";
@@ -470,4 +1412,233 @@ mod tests {
             Some(&"99".to_string())
         );
     }
+
+    #[test]
+    fn test_is_h2_preface() {
+        assert!(is_h2_preface(H2_PREFACE));
+        assert!(!is_h2_preface(b"POST / HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_h2_reassembles_post() {
+        let mut client = H2FrameReassembler::client();
+
+        // Deliver in small reads so frames straddle read boundaries
+        let mut messages = Vec::new();
+        for chunk in h2_capture::request().chunks(7) {
+            messages.extend(client.feed(chunk));
+        }
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(client.open_streams(), 0);
+        let req = messages[0].to_request().unwrap();
+        assert_eq!(messages[0].stream_id, 1);
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/v1/chat/completions");
+        assert_eq!(req.version, "HTTP/2");
+        assert_eq!(req.host.as_deref(), Some("api.openai.com"));
+        assert_eq!(req.content_type.as_deref(), Some("application/json"));
+        assert_eq!(req.content_length, Some(h2_capture::REQUEST_BODY.len()));
+        assert_eq!(
+            req.headers.get("authorization").map(String::as_str),
+            Some("Bearer sk-test")
+        );
+        assert_eq!(req.body.as_deref(), Some(h2_capture::REQUEST_BODY));
+
+        let mut server = H2FrameReassembler::server();
+        let messages = server.feed(&h2_capture::response());
+        assert_eq!(messages.len(), 1);
+        let resp = messages[0].to_response().unwrap();
+        assert_eq!(resp.status_code, 200);
+        assert_eq!(resp.status_text, "OK");
+        assert_eq!(resp.content_type.as_deref(), Some("application/json"));
+        assert_eq!(resp.body.as_deref(), Some(h2_capture::RESPONSE_BODY));
+    }
+
+    #[test]
+    fn test_h2_multiplexed_streams_use_dynamic_table() {
+        use h2_capture::frame;
+
+        let mut client = H2FrameReassembler::client();
+        let mut data = h2_capture::request();
+        // Stream 3 refers back to :path, :authority and content-type in the
+        // dynamic table, and is interleaved with a still-open stream 5
+        data.extend(frame(
+            H2_FRAME_HEADERS,
+            H2_FLAG_END_HEADERS,
+            5,
+            &[0x83, 0x87, 0xc0, 0xbf],
+        ));
+        data.extend(frame(
+            H2_FRAME_HEADERS,
+            H2_FLAG_END_HEADERS,
+            3,
+            &[0x83, 0x87, 0xc0, 0xbf, 0xbe],
+        ));
+        data.extend(frame(H2_FRAME_DATA, 0, 5, b"{\"model\":"));
+        data.extend(frame(H2_FRAME_DATA, H2_FLAG_END_STREAM, 3, b"{}"));
+
+        let messages = client.feed(&data);
+        let ids: Vec<u32> = messages.iter().map(|m| m.stream_id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(client.open_streams(), 1);
+
+        let req = messages[1].to_request().unwrap();
+        assert_eq!(req.path, "/v1/chat/completions");
+        assert_eq!(req.host.as_deref(), Some("api.openai.com"));
+        assert_eq!(req.content_type.as_deref(), Some("application/json"));
+        assert_eq!(req.body.as_deref(), Some(b"{}".as_slice()));
+    }
+
+    #[test]
+    fn test_h2_rejects_missing_preface() {
+        let mut client = H2FrameReassembler::client();
+        assert!(client.feed(&h2_capture::response()).is_empty());
+        assert!(client.is_failed());
+    }
+
+    #[test]
+    fn test_h2_buffers_are_capped() {
+        use h2_capture::frame;
+
+        // :status 200
+        let mut server = H2FrameReassembler::server();
+        let mut data = frame(H2_FRAME_HEADERS, H2_FLAG_END_HEADERS, 1, &[0x88]);
+        let chunk = vec![b'x'; 1024 * 1024];
+        for _ in 0..MAX_H2_BODY / chunk.len() + 1 {
+            data.extend(frame(H2_FRAME_DATA, 0, 1, &chunk));
+        }
+        data.extend(frame(H2_FRAME_DATA, H2_FLAG_END_STREAM, 1, b"end"));
+
+        let messages = server.feed(&data);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].truncated);
+        assert_eq!(messages[0].body.len(), MAX_H2_BODY);
+        assert!(!server.is_failed());
+
+        // A header block that never ends fails the connection
+        let mut client = H2FrameReassembler::client();
+        let mut data = H2_PREFACE.to_vec();
+        data.extend(frame(H2_FRAME_HEADERS, 0, 1, &[0x83]));
+        let fragment = vec![0; 64 * 1024];
+        for _ in 0..MAX_H2_HEADER_BLOCK / fragment.len() {
+            data.extend(frame(H2_FRAME_CONTINUATION, 0, 1, &fragment));
+        }
+        assert!(client.feed(&data).is_empty());
+        assert!(client.is_failed());
+    }
+    fn multipart_body(boundary: &str, audio: &[u8]) -> Vec<u8> {
+        let mut body = format!(
+            "--{b}\r\n\
+             Content-Disposition: form-data; name=\"model\"\r\n\
+             \r\n\
+             whisper-1\r\n\
+             --{b}\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"meeting.mp3\"\r\n\
+             Content-Type: audio/mpeg\r\n\
+             \r\n",
+            b = boundary
+        )
+        .into_bytes();
+        body.extend_from_slice(audio);
+        body.extend_from_slice(
+            format!(
+                "\r\n--{b}\r\n\
+                 Content-Disposition: form-data; name=\"language\"\r\n\
+                 \r\n\
+                 en\r\n\
+                 --{b}--\r\n",
+                b = boundary
+            )
+            .as_bytes(),
+        );
+        body
+    }
+
+    #[test]
+    fn test_multipart_boundary() {
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=abc123").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            multipart_boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\"").as_deref(),
+            Some("a b")
+        );
+        assert_eq!(multipart_boundary("multipart/form-data"), None);
+        assert_eq!(multipart_boundary("application/json; boundary=x"), None);
+    }
+
+    #[test]
+    fn test_multipart_parsed_across_any_split() {
+        let boundary = "b4a1e";
+        // Content that looks like the start of a delimiter but is not one
+        let mut audio = b"ID3\r\n--b4a1\r\n--".to_vec();
+        audio.extend((0..3000u32).map(|i| (i % 251) as u8));
+        let body = multipart_body(boundary, &audio);
+
+        let mut expected = None;
+        for chunk_size in [1, 2, 3, 7, 16, 100, body.len()] {
+            let mut parser = MultipartParser::new(boundary);
+            for chunk in body.chunks(chunk_size) {
+                parser.feed(chunk);
+                assert!(parser.pending.len() <= MAX_PART_HEADERS + chunk_size);
+            }
+            let form = parser.finish();
+            assert!(form.complete);
+            assert_eq!(form.field("model"), Some("whisper-1"));
+            assert_eq!(form.field("language"), Some("en"));
+
+            let files: Vec<_> = form.files().collect();
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].name.as_deref(), Some("file"));
+            assert_eq!(files[0].filename.as_deref(), Some("meeting.mp3"));
+            assert_eq!(files[0].content_type.as_deref(), Some("audio/mpeg"));
+            assert_eq!(files[0].size, audio.len());
+            assert_eq!(files[0].value, None);
+
+            match &expected {
+                None => expected = Some(form),
+                Some(expected) => assert_eq!(&form, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_multipart_truncated_body() {
+        let body = multipart_body("xyz", &[0u8; 500]);
+        let mut parser = MultipartParser::new("xyz");
+        parser.feed(&body[..300]);
+        let form = parser.finish();
+        assert!(!form.complete);
+        assert_eq!(form.field("model"), Some("whisper-1"));
+        assert_eq!(form.files().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_request_multipart() {
+        let mut request = b"POST /v1/audio/transcriptions HTTP/1.1\r\n\
+            Host: api.openai.com\r\n\
+            Content-Type: multipart/form-data; boundary=xyz\r\n\
+            \r\n"
+            .to_vec();
+        request.extend(multipart_body("xyz", b"audio"));
+        let parsed = parse_request(&request).unwrap();
+        let form = parsed.multipart.unwrap();
+        assert_eq!(form.field("model"), Some("whisper-1"));
+        assert_eq!(form.files().next().unwrap().size, 5);
+
+        assert!(parse_request(openai_like_request())
+            .unwrap()
+            .multipart
+            .is_none());
+    }
+
+    fn openai_like_request() -> &'static [u8] {
+        b"POST /v1/chat/completions HTTP/1.1\r\n\
+          Host: api.openai.com\r\n\
+          Content-Type: application/json\r\n\
+          \r\n\
+          {}"
+    }
 }
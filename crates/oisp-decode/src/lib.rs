@@ -3,16 +3,31 @@
 //! This crate provides decoders that transform raw capture events into
 //! structured OISP events:
 //!
-//! - **HttpDecoder**: Decodes SSL/TLS traffic into HTTP and AI events
+//! - **HttpDecoder**: Decodes SSL/TLS traffic into HTTP and AI events, and
+//!   vector store queries into `agent.rag_retrieve`
 //! - **SystemDecoder**: Decodes process, file, and network events
+//! - **FlowTracker**: Rolls connections up into `network.flow` summaries
 
 pub mod ai;
+pub mod bedrock;
+pub mod clock;
 pub mod decoder;
+pub mod failures;
+pub mod flow;
+pub mod gemini;
+#[cfg(any(test, feature = "test-support"))]
+pub mod harness;
+pub mod hpack;
 pub mod http;
+pub mod ndjson;
+pub mod provider_cache;
+pub mod rag;
 pub mod spec_parser;
 pub mod sse;
 pub mod system;
+pub mod tls;
+pub mod tool_args;
 
 pub use decoder::HttpDecoder;
 pub use spec_parser::SpecDrivenParser;
-pub use system::SystemDecoder;
+pub use system::{FilePathFilter, SystemDecoder};
@@ -0,0 +1,237 @@
+//! Newline-delimited JSON (NDJSON) stream parsing
+//!
+//! Ollama's native API (`/api/chat`, `/api/generate`) streams one JSON
+//! object per line instead of Server-Sent Events.
+
+use serde_json::Value;
+
+/// NDJSON line parser for streaming responses
+pub struct NdjsonParser {
+    buffer: String,
+    objects: Vec<Value>,
+}
+
+impl NdjsonParser {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    /// Add data to the parser
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.push_str(&String::from_utf8_lossy(data));
+
+        while let Some(pos) = self.buffer.find('\n') {
+            let line = self.buffer[..pos].trim().to_string();
+            self.buffer = self.buffer[pos + 1..].to_string();
+            self.parse_line(&line);
+        }
+    }
+
+    /// Parse a trailing line that was not newline-terminated
+    ///
+    /// The buffer is only consumed if it holds a complete JSON object.
+    pub fn flush(&mut self) {
+        let line = self.buffer.trim();
+        if line.starts_with('{') && serde_json::from_str::<Value>(line).is_ok() {
+            let line = std::mem::take(&mut self.buffer);
+            self.parse_line(line.trim());
+        }
+    }
+
+    /// Take all parsed objects
+    pub fn take_objects(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.objects)
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        // Raw chunked transfer encoding leaves hex size lines between objects;
+        // only JSON objects are meaningful here
+        if !line.starts_with('{') {
+            return;
+        }
+
+        if let Ok(json) = serde_json::from_str::<Value>(line) {
+            if json.is_object() {
+                self.objects.push(json);
+            }
+        }
+    }
+}
+
+impl Default for NdjsonParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reassemble Ollama NDJSON streaming chunks into a complete response
+#[derive(Default)]
+pub struct OllamaStreamReassembler {
+    parser: NdjsonParser,
+    complete_content: String,
+    tool_calls: Vec<Value>,
+    final_chunk: Option<Value>,
+    last_chunk: Option<Value>,
+    model: Option<String>,
+}
+
+impl OllamaStreamReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed data and parse chunks
+    pub fn feed(&mut self, data: &[u8]) {
+        self.parser.feed(data);
+
+        // The final chunk is not always newline-terminated
+        self.parser.flush();
+
+        for chunk in self.parser.take_objects() {
+            if let Some(model) = chunk.get("model").and_then(|m| m.as_str()) {
+                self.model = Some(model.to_string());
+            }
+
+            // /api/chat streams message.content, /api/generate streams response
+            let content = chunk
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .or_else(|| chunk.get("response"))
+                .and_then(|c| c.as_str());
+            if let Some(c) = content {
+                self.complete_content.push_str(c);
+            }
+
+            if let Some(calls) = chunk
+                .get("message")
+                .and_then(|m| m.get("tool_calls"))
+                .and_then(|t| t.as_array())
+            {
+                self.tool_calls.extend(calls.iter().cloned());
+            }
+
+            if chunk.get("done").and_then(|d| d.as_bool()) == Some(true) {
+                self.final_chunk = Some(chunk);
+            } else {
+                self.last_chunk = Some(chunk);
+            }
+        }
+    }
+
+    /// Check if stream is complete (a chunk with `"done": true` was seen)
+    pub fn is_complete(&self) -> bool {
+        self.final_chunk.is_some()
+    }
+
+    /// Get complete content
+    pub fn content(&self) -> &str {
+        &self.complete_content
+    }
+
+    /// Get model id reported by the stream
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Get token usage (prompt, completion) from the final chunk
+    pub fn usage(&self) -> (Option<u64>, Option<u64>) {
+        let count = |field: &str| {
+            self.final_chunk
+                .as_ref()
+                .and_then(|c| c.get(field))
+                .and_then(|v| v.as_u64())
+        };
+        (count("prompt_eval_count"), count("eval_count"))
+    }
+
+    /// Merge the stream into a single non-streaming Ollama response body
+    ///
+    /// The result has the same shape as a `"stream": false` response, so it
+    /// can be handed to [`crate::ai::parse_ollama_response`]. If the stream
+    /// was cut off before `done`, the last chunk seen is used as the base.
+    pub fn to_response(&self) -> Option<Value> {
+        let mut merged = self
+            .final_chunk
+            .clone()
+            .or_else(|| self.last_chunk.clone())?;
+        let obj = merged.as_object_mut()?;
+
+        if obj.contains_key("message") {
+            let mut message = serde_json::json!({
+                "role": "assistant",
+                "content": self.complete_content,
+            });
+            if !self.tool_calls.is_empty() {
+                message["tool_calls"] = Value::Array(self.tool_calls.clone());
+            }
+            obj.insert("message".to_string(), message);
+        } else {
+            obj.insert(
+                "response".to_string(),
+                Value::String(self.complete_content.clone()),
+            );
+        }
+
+        Some(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndjson_parser_split_lines() {
+        let mut parser = NdjsonParser::new();
+        parser.feed(b"{\"a\":1}\n{\"b\"");
+        assert_eq!(parser.take_objects().len(), 1);
+
+        parser.feed(b":2}\n");
+        let objects = parser.take_objects();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0]["b"], 2);
+    }
+
+    #[test]
+    fn test_ollama_chat_stream() {
+        let mut reassembler = OllamaStreamReassembler::new();
+
+        let body = br#"{"model":"llama3.2","created_at":"2024-11-01T10:00:00Z","message":{"role":"assistant","content":"Hello"},"done":false}
+{"model":"llama3.2","created_at":"2024-11-01T10:00:00Z","message":{"role":"assistant","content":" there"},"done":false}
+{"model":"llama3.2","created_at":"2024-11-01T10:00:01Z","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","total_duration":5043500667,"prompt_eval_count":26,"eval_count":12}
+"#;
+        reassembler.feed(body);
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.content(), "Hello there");
+        assert_eq!(reassembler.model(), Some("llama3.2"));
+        assert_eq!(reassembler.usage(), (Some(26), Some(12)));
+
+        let merged = reassembler.to_response().unwrap();
+        assert_eq!(merged["message"]["content"], "Hello there");
+        assert_eq!(merged["done_reason"], "stop");
+    }
+
+    #[test]
+    fn test_ollama_generate_stream_chunked() {
+        let mut reassembler = OllamaStreamReassembler::new();
+
+        // Raw chunked transfer encoding framing around each line
+        reassembler
+            .feed(b"4a\r\n{\"model\":\"mistral\",\"response\":\"The sky\",\"done\":false}\n\r\n");
+        assert!(!reassembler.is_complete());
+        reassembler.feed(
+            b"5f\r\n{\"model\":\"mistral\",\"response\":\" is blue.\",\"done\":true,\"done_reason\":\"stop\",\"eval_count\":4}\n\r\n0\r\n\r\n",
+        );
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.content(), "The sky is blue.");
+        assert_eq!(
+            reassembler.to_response().unwrap()["response"],
+            "The sky is blue."
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Cache of provider detection results
+//!
+//! Detection checks the configured hosts, the spec bundle's domain index and
+//! patterns, and the built-in registry on every request, though the answer
+//! only depends on the host and on whether the body is a file upload. Results
+//! are kept in a bounded LRU keyed by both, including negative results: most
+//! decrypted traffic is not AI traffic and would otherwise be re-detected on
+//! every request.
+
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Hosts remembered by default
+pub const DEFAULT_PROVIDER_CACHE_CAPACITY: usize = 1024;
+
+/// The part of a request body that affects detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BodyShape {
+    Json,
+    Multipart,
+}
+
+/// Detection results by host and body shape; `None` for hosts that are not
+/// AI providers
+type Entries<V> = LruCache<(String, BodyShape), Option<V>>;
+
+/// Bounded LRU of detection results per host and body shape
+pub struct ProviderCache<V> {
+    /// `None` when caching is disabled (capacity 0)
+    entries: Option<Mutex<Entries<V>>>,
+    hits: AtomicU64,
+    detections: AtomicU64,
+}
+
+impl<V: Clone> ProviderCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap))),
+            hits: AtomicU64::new(0),
+            detections: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached result for the host, running `detect` on a miss
+    ///
+    /// `detect` runs without the cache locked.
+    pub fn get_or_detect(
+        &self,
+        host: &str,
+        shape: BodyShape,
+        detect: impl FnOnce() -> Option<V>,
+    ) -> Option<V> {
+        let key = (host.to_string(), shape);
+        if let Some(entries) = &self.entries {
+            if let Some(cached) = entries.lock().unwrap().get(&key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return cached.clone();
+            }
+        }
+
+        self.detections.fetch_add(1, Ordering::Relaxed);
+        let detected = detect();
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().put(key, detected.clone());
+        }
+        detected
+    }
+
+    /// Forget every result, e.g. after the provider registries change
+    pub fn clear(&self) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().clear();
+        }
+    }
+
+    /// Number of cached hosts
+    pub fn len(&self) -> usize {
+        self.entries
+            .as_ref()
+            .map_or(0, |entries| entries.lock().unwrap().len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lookups answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Lookups that ran full detection
+    pub fn detections(&self) -> u64 {
+        self.detections.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_host_evicted() {
+        let cache = ProviderCache::new(2);
+        let detect = |name: &'static str| move || Some(name);
+
+        cache.get_or_detect("a.example", BodyShape::Json, detect("a"));
+        cache.get_or_detect("b.example", BodyShape::Json, detect("b"));
+        // Touch a, so b is the least recently used
+        cache.get_or_detect("a.example", BodyShape::Json, detect("wrong"));
+        cache.get_or_detect("c.example", BodyShape::Json, detect("c"));
+        assert_eq!((cache.hits(), cache.detections()), (1, 3));
+
+        assert_eq!(
+            cache.get_or_detect("a.example", BodyShape::Json, detect("wrong")),
+            Some("a")
+        );
+        assert_eq!(
+            cache.get_or_detect("b.example", BodyShape::Json, detect("b2")),
+            Some("b2")
+        );
+        // Body shapes are cached separately
+        assert_eq!(
+            cache.get_or_detect("a.example", BodyShape::Multipart, || None),
+            None
+        );
+        assert_eq!(cache.detections(), 5);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let cache = ProviderCache::new(0);
+        cache.get_or_detect("a.example", BodyShape::Json, || Some(1));
+        cache.get_or_detect("a.example", BodyShape::Json, || Some(1));
+        assert_eq!((cache.hits(), cache.detections()), (0, 2));
+        assert!(cache.is_empty());
+    }
+}
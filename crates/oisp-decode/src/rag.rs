@@ -0,0 +1,599 @@
+//! Vector store retrieval recognition
+//!
+//! RAG pipelines (LangChain, LlamaIndex or hand-rolled) embed the question,
+//! query a vector store with the embedding and pass the matches to a chat
+//! model. The vector store query is not AI traffic, but it has a
+//! recognizable shape: a Pinecone, Qdrant, Chroma or Weaviate search
+//! endpoint, a pgvector function called through PostgREST (Supabase), or a
+//! Postgres query ordering by a pgvector distance operator. Recognized
+//! queries become `agent.rag_retrieve` events once their response is seen.
+//!
+//! Only the query's shape is kept: the store, collection, text query when
+//! the store embeds it itself, and the ids and scores of the matches.
+//! Document contents and metadata are not retained.
+
+use oisp_core::events::{RagResult, RagSource, RagSourceType};
+use regex::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+use crate::ai::hash_content;
+
+/// Matches kept per retrieval; `results_count` has the full count
+pub const MAX_RAG_RESULTS: usize = 10;
+
+/// Attribute holding the number of matches the query asked for
+pub const RAG_TOP_K_ATTR: &str = "rag.top_k";
+
+/// Attribute holding the namespace (Pinecone) the query searched
+pub const RAG_NAMESPACE_ATTR: &str = "rag.namespace";
+
+/// Attribute holding the dimensions of the query vector, when one was sent
+pub const RAG_VECTOR_DIMS_ATTR: &str = "rag.vector_dims";
+
+/// Attribute set when the query filtered on metadata
+pub const RAG_FILTERED_ATTR: &str = "rag.filtered";
+
+/// A recognized vector store query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VectorQuery {
+    /// Vector store: pinecone, qdrant, chroma, weaviate, pgvector
+    pub store: &'static str,
+    /// Index, collection, class, table or function searched
+    pub collection: Option<String>,
+    pub namespace: Option<String>,
+    /// Text query, for stores that embed it themselves
+    pub query: Option<String>,
+    pub top_k: Option<usize>,
+    pub vector_dims: Option<usize>,
+    pub filtered: bool,
+    /// Framework named by the client (`source_tag=langchain` in Pinecone's
+    /// user agent)
+    pub framework: Option<String>,
+}
+
+impl VectorQuery {
+    /// Event source for this query
+    pub fn source(&self) -> RagSource {
+        RagSource {
+            source_type: Some(RagSourceType::VectorDb),
+            name: self.collection.clone(),
+            provider: Some(self.store.to_string()),
+        }
+    }
+
+    /// Hash of the text query, for correlating repeated questions
+    pub fn query_hash(&self) -> Option<String> {
+        self.query.as_deref().map(hash_content)
+    }
+
+    /// Attributes describing the query beyond the event's fields
+    pub fn attrs(&self) -> Vec<(&'static str, Value)> {
+        let mut attrs = Vec::new();
+        if let Some(top_k) = self.top_k {
+            attrs.push((RAG_TOP_K_ATTR, top_k.into()));
+        }
+        if let Some(namespace) = &self.namespace {
+            attrs.push((RAG_NAMESPACE_ATTR, namespace.clone().into()));
+        }
+        if let Some(dims) = self.vector_dims {
+            attrs.push((RAG_VECTOR_DIMS_ATTR, dims.into()));
+        }
+        if self.filtered {
+            attrs.push((RAG_FILTERED_ATTR, true.into()));
+        }
+        attrs
+    }
+}
+
+/// Matches returned by a vector store query
+#[derive(Debug, Clone, Default)]
+pub struct Retrieved {
+    pub count: usize,
+    /// Up to [`MAX_RAG_RESULTS`] matches, with id and score
+    pub results: Vec<RagResult>,
+}
+
+static CHROMA_QUERY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^/api/v[12]/(?:tenants/[^/]+/databases/[^/]+/)?collections/([^/]+)/query$")
+        .unwrap()
+});
+static QDRANT_SEARCH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^/collections/([^/]+)/points/(?:search|query)$").unwrap());
+static PINECONE_SEARCH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^/records/namespaces/([^/]+)/search$").unwrap());
+static POSTGREST_RPC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^/rest/v1/rpc/([A-Za-z0-9_]+)$").unwrap());
+
+static GRAPHQL_CLASS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"Get\s*\{\s*([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+static GRAPHQL_SEARCH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(nearText|nearVector|nearObject|hybrid|bm25)\s*:").unwrap());
+static GRAPHQL_TEXT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:concepts\s*:\s*\[\s*|query\s*:\s*)"((?:[^"\\]|\\.)*)""#).unwrap()
+});
+static GRAPHQL_LIMIT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\blimit\s*:\s*(\d+)").unwrap());
+
+static SQL_FROM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bfrom\s+([A-Za-z0-9_."]+)"#).unwrap());
+static SQL_LIMIT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\blimit\s+(\d+)").unwrap());
+
+/// pgvector distance operators: L2, cosine, inner product, L1
+const PGVECTOR_OPERATORS: &[&str] = &["<->", "<=>", "<#>", "<+>"];
+
+/// Recognize a vector store query from an HTTP request
+pub fn parse_http_query(
+    host: &str,
+    path: &str,
+    user_agent: Option<&str>,
+    body: &Value,
+) -> Option<VectorQuery> {
+    let path = path.split('?').next().unwrap_or(path);
+    let host = host.split(':').next().unwrap_or(host);
+
+    let mut query = if host.ends_with(".pinecone.io") {
+        parse_pinecone(host, path, body)
+    } else if let Some(caps) = QDRANT_SEARCH.captures(path) {
+        Some(parse_qdrant(&caps[1], body))
+    } else if let Some(caps) = CHROMA_QUERY.captures(path) {
+        Some(parse_chroma(&caps[1], body))
+    } else if path == "/v1/graphql" {
+        parse_weaviate(body)
+    } else if let Some(caps) = POSTGREST_RPC.captures(path) {
+        parse_postgrest(&caps[1], body)
+    } else {
+        None
+    }?;
+
+    query.framework = user_agent.and_then(source_tag);
+    Some(query)
+}
+
+/// Recognize a pgvector similarity search in a Postgres frontend message
+///
+/// Handles simple (`Q`) and extended (`P`, parse) queries; the SQL must
+/// order or filter by one of pgvector's distance operators.
+pub fn parse_postgres_query(data: &[u8]) -> Option<VectorQuery> {
+    let (&tag, rest) = data.split_first()?;
+    let body = rest.get(4..)?;
+    let sql = match tag {
+        b'Q' => nul_terminated(body)?,
+        b'P' => {
+            let name_end = body.iter().position(|&b| b == 0)?;
+            nul_terminated(&body[name_end + 1..])?
+        }
+        _ => return None,
+    };
+
+    let lower = sql.to_ascii_lowercase();
+    if !lower.trim_start().starts_with("select") && !lower.trim_start().starts_with("with") {
+        return None;
+    }
+    if !PGVECTOR_OPERATORS.iter().any(|op| sql.contains(op)) {
+        return None;
+    }
+
+    Some(VectorQuery {
+        store: "pgvector",
+        collection: SQL_FROM
+            .captures(sql)
+            .map(|caps| caps[1].trim_matches('"').to_string()),
+        top_k: SQL_LIMIT
+            .captures(sql)
+            .and_then(|caps| caps[1].parse().ok()),
+        filtered: lower.contains(" where "),
+        ..Default::default()
+    })
+}
+
+/// Matches in a vector store's HTTP response
+pub fn parse_http_results(store: &str, body: &Value) -> Option<Retrieved> {
+    let matches: Vec<(Option<String>, Option<f64>)> = match store {
+        "pinecone" => {
+            if let Some(matches) = body.get("matches").and_then(Value::as_array) {
+                matches
+                    .iter()
+                    .map(|m| (id_of(m.get("id")), m.get("score").and_then(Value::as_f64)))
+                    .collect()
+            } else {
+                body.pointer("/result/hits")?
+                    .as_array()?
+                    .iter()
+                    .map(|m| (id_of(m.get("_id")), m.get("_score").and_then(Value::as_f64)))
+                    .collect()
+            }
+        }
+        "qdrant" => {
+            let result = body.get("result")?;
+            result
+                .as_array()
+                .or_else(|| result.get("points").and_then(Value::as_array))?
+                .iter()
+                .map(|m| (id_of(m.get("id")), m.get("score").and_then(Value::as_f64)))
+                .collect()
+        }
+        "chroma" => {
+            // One list per query embedding; LangChain sends a single query
+            let ids = body.pointer("/ids/0")?.as_array()?;
+            let distances = body.pointer("/distances/0").and_then(Value::as_array);
+            ids.iter()
+                .enumerate()
+                .map(|(i, id)| {
+                    let distance = distances.and_then(|d| d.get(i)).and_then(Value::as_f64);
+                    (id_of(Some(id)), distance)
+                })
+                .collect()
+        }
+        "weaviate" => {
+            let classes = body.pointer("/data/Get")?.as_object()?;
+            classes
+                .values()
+                .filter_map(Value::as_array)
+                .flatten()
+                .map(|m| {
+                    let additional = m.get("_additional");
+                    let score = additional.and_then(|a| {
+                        ["certainty", "score", "distance"]
+                            .iter()
+                            .find_map(|k| a.get(*k).and_then(score_of))
+                    });
+                    (id_of(additional.and_then(|a| a.get("id"))), score)
+                })
+                .collect()
+        }
+        "pgvector" => body
+            .as_array()?
+            .iter()
+            .map(|row| {
+                let score = ["similarity", "score", "distance"]
+                    .iter()
+                    .find_map(|k| row.get(*k).and_then(Value::as_f64));
+                (id_of(row.get("id")), score)
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Some(Retrieved {
+        count: matches.len(),
+        results: matches
+            .into_iter()
+            .take(MAX_RAG_RESULTS)
+            .map(|(source, score)| RagResult {
+                source,
+                score,
+                content_preview: None,
+                content_hash: None,
+                metadata: None,
+            })
+            .collect(),
+    })
+}
+
+/// Rows returned to a pgvector query, from the `SELECT n` CommandComplete
+/// in Postgres backend messages
+///
+/// Reads need not start on a message boundary, so the message is found by
+/// its tag, length and command text rather than by walking from the start.
+pub fn parse_postgres_results(data: &[u8]) -> Option<Retrieved> {
+    const COMMAND: &[u8] = b"SELECT ";
+    data.windows(COMMAND.len())
+        .enumerate()
+        .filter(|(pos, window)| *window == COMMAND && *pos >= 5 && data[pos - 5] == b'C')
+        .find_map(|(pos, _)| {
+            let len = u32::from_be_bytes(data[pos - 4..pos].try_into().ok()?) as usize;
+            let command = nul_terminated(&data[pos..])?;
+            if len != command.len() + 5 {
+                return None;
+            }
+            let count = command.strip_prefix("SELECT ")?.parse().ok()?;
+            Some(Retrieved {
+                count,
+                results: Vec::new(),
+            })
+        })
+}
+
+fn parse_pinecone(host: &str, path: &str, body: &Value) -> Option<VectorQuery> {
+    // Index hosts are `<index>-<project>.svc.<environment>.pinecone.io`
+    let label = host.split('.').next()?;
+    let index = label
+        .rsplit_once('-')
+        .map(|(index, _)| index)
+        .unwrap_or(label);
+
+    if path == "/query" {
+        return Some(VectorQuery {
+            store: "pinecone",
+            collection: Some(index.to_string()),
+            namespace: body
+                .get("namespace")
+                .and_then(Value::as_str)
+                .filter(|ns| !ns.is_empty())
+                .map(String::from),
+            top_k: usize_of(body.get("topK")),
+            vector_dims: body.get("vector").and_then(Value::as_array).map(Vec::len),
+            filtered: body.get("filter").is_some_and(|f| !f.is_null()),
+            ..Default::default()
+        });
+    }
+
+    // Integrated inference: Pinecone embeds the text itself
+    let caps = PINECONE_SEARCH.captures(path)?;
+    let search = body.get("query")?;
+    Some(VectorQuery {
+        store: "pinecone",
+        collection: Some(index.to_string()),
+        namespace: Some(caps[1].to_string()),
+        query: search
+            .pointer("/inputs/text")
+            .and_then(Value::as_str)
+            .map(String::from),
+        top_k: usize_of(search.get("top_k")),
+        vector_dims: search
+            .pointer("/vector/values")
+            .and_then(Value::as_array)
+            .map(Vec::len),
+        filtered: search.get("filter").is_some_and(|f| !f.is_null()),
+        ..Default::default()
+    })
+}
+
+fn parse_qdrant(collection: &str, body: &Value) -> VectorQuery {
+    // `vector` may be a plain list or a named vector `{name, vector}`
+    let vector = body
+        .get("vector")
+        .map(|v| v.get("vector").unwrap_or(v))
+        .or_else(|| body.get("query"));
+    VectorQuery {
+        store: "qdrant",
+        collection: Some(collection.to_string()),
+        top_k: usize_of(body.get("limit")),
+        vector_dims: vector.and_then(Value::as_array).map(Vec::len),
+        filtered: body.get("filter").is_some_and(|f| !f.is_null()),
+        ..Default::default()
+    }
+}
+
+fn parse_chroma(collection: &str, body: &Value) -> VectorQuery {
+    VectorQuery {
+        store: "chroma",
+        collection: Some(collection.to_string()),
+        query: body
+            .pointer("/query_texts/0")
+            .and_then(Value::as_str)
+            .map(String::from),
+        top_k: usize_of(body.get("n_results")),
+        vector_dims: body
+            .pointer("/query_embeddings/0")
+            .and_then(Value::as_array)
+            .map(Vec::len),
+        filtered: ["where", "where_document"]
+            .iter()
+            .any(|k| body.get(*k).is_some_and(|f| !f.is_null())),
+        ..Default::default()
+    }
+}
+
+fn parse_weaviate(body: &Value) -> Option<VectorQuery> {
+    let graphql = body.get("query")?.as_str()?;
+    if !GRAPHQL_SEARCH.is_match(graphql) {
+        return None;
+    }
+    let class = GRAPHQL_CLASS.captures(graphql)?;
+    Some(VectorQuery {
+        store: "weaviate",
+        collection: Some(class[1].to_string()),
+        query: GRAPHQL_TEXT
+            .captures(graphql)
+            .map(|caps| caps[1].replace("\\\"", "\"")),
+        top_k: GRAPHQL_LIMIT
+            .captures(graphql)
+            .and_then(|caps| caps[1].parse().ok()),
+        filtered: graphql.contains("where:"),
+        ..Default::default()
+    })
+}
+
+fn parse_postgrest(function: &str, body: &Value) -> Option<VectorQuery> {
+    // pgvector search functions (Supabase's `match_documents` convention)
+    // take the query embedding as an argument
+    let args = body.as_object()?;
+    let embedding = args
+        .iter()
+        .find(|(name, value)| name.contains("embedding") && value.is_array())?;
+    Some(VectorQuery {
+        store: "pgvector",
+        collection: Some(function.to_string()),
+        top_k: ["match_count", "k", "limit", "top_k"]
+            .iter()
+            .find_map(|k| usize_of(args.get(*k))),
+        vector_dims: embedding.1.as_array().map(Vec::len),
+        filtered: args.get("filter").is_some_and(|f| !f.is_null()),
+        ..Default::default()
+    })
+}
+
+/// Framework from a `source_tag=<name>` user agent suffix
+fn source_tag(user_agent: &str) -> Option<String> {
+    let tag = user_agent.split("source_tag=").nth(1)?;
+    let tag: String = tag
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect();
+    (!tag.is_empty()).then_some(tag)
+}
+
+fn nul_terminated(data: &[u8]) -> Option<&str> {
+    let end = data.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&data[..end]).ok()
+}
+
+fn usize_of(value: Option<&Value>) -> Option<usize> {
+    value?.as_u64().map(|n| n as usize)
+}
+
+fn id_of(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Weaviate returns scores as numbers or, for hybrid/bm25, strings
+fn score_of(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pinecone_query() {
+        let query = parse_http_query(
+            "docs-index-a1b2c3d.svc.us-east1-gcp.pinecone.io",
+            "/query",
+            Some("python-client-5.0.1 (urllib3:2.2.1); source_tag=langchain"),
+            &json!({
+                "vector": [0.1, 0.2, 0.3],
+                "topK": 4,
+                "namespace": "handbook",
+                "includeMetadata": true
+            }),
+        )
+        .unwrap();
+        assert_eq!(query.store, "pinecone");
+        assert_eq!(query.collection.as_deref(), Some("docs-index"));
+        assert_eq!(query.namespace.as_deref(), Some("handbook"));
+        assert_eq!(query.top_k, Some(4));
+        assert_eq!(query.vector_dims, Some(3));
+        assert_eq!(query.framework.as_deref(), Some("langchain"));
+        assert!(!query.filtered);
+
+        let retrieved = parse_http_results(
+            "pinecone",
+            &json!({"matches": [
+                {"id": "doc-1", "score": 0.91, "metadata": {"text": "secret"}},
+                {"id": "doc-7", "score": 0.84}
+            ], "namespace": "handbook"}),
+        )
+        .unwrap();
+        assert_eq!(retrieved.count, 2);
+        assert_eq!(retrieved.results[0].source.as_deref(), Some("doc-1"));
+        assert_eq!(retrieved.results[0].score, Some(0.91));
+        assert!(retrieved.results[0].metadata.is_none());
+    }
+
+    #[test]
+    fn test_self_hosted_stores() {
+        let qdrant = parse_http_query(
+            "qdrant.internal:6333",
+            "/collections/articles/points/search",
+            None,
+            &json!({"vector": {"name": "text", "vector": [0.1, 0.2]}, "limit": 5, "filter": {"must": []}}),
+        )
+        .unwrap();
+        assert_eq!(qdrant.store, "qdrant");
+        assert_eq!(qdrant.collection.as_deref(), Some("articles"));
+        assert_eq!((qdrant.top_k, qdrant.vector_dims), (Some(5), Some(2)));
+        assert!(qdrant.filtered);
+        let retrieved = parse_http_results(
+            "qdrant",
+            &json!({"result": [{"id": 42, "score": 0.7}], "status": "ok"}),
+        )
+        .unwrap();
+        assert_eq!(retrieved.results[0].source.as_deref(), Some("42"));
+
+        let chroma = parse_http_query(
+            "localhost:8000",
+            "/api/v1/collections/5f1c/query",
+            None,
+            &json!({"query_texts": ["refund policy"], "n_results": 3}),
+        )
+        .unwrap();
+        assert_eq!(chroma.store, "chroma");
+        assert_eq!(chroma.query.as_deref(), Some("refund policy"));
+        assert!(chroma.query_hash().unwrap().starts_with("sha256:"));
+
+        let weaviate = parse_http_query(
+            "weaviate.internal",
+            "/v1/graphql",
+            None,
+            &json!({"query": "{ Get { Article(nearText: {concepts: [\"refund policy\"]}, limit: 2) { title _additional { id distance } } } }"}),
+        )
+        .unwrap();
+        assert_eq!(weaviate.collection.as_deref(), Some("Article"));
+        assert_eq!(weaviate.query.as_deref(), Some("refund policy"));
+        assert_eq!(weaviate.top_k, Some(2));
+        let retrieved = parse_http_results(
+            "weaviate",
+            &json!({"data": {"Get": {"Article": [
+                {"title": "Refunds", "_additional": {"id": "a1", "distance": 0.12}}
+            ]}}}),
+        )
+        .unwrap();
+        assert_eq!(retrieved.results[0].score, Some(0.12));
+
+        let supabase = parse_http_query(
+            "abc.supabase.co",
+            "/rest/v1/rpc/match_documents",
+            None,
+            &json!({"query_embedding": [0.1, 0.2, 0.3, 0.4], "match_count": 8}),
+        )
+        .unwrap();
+        assert_eq!(supabase.store, "pgvector");
+        assert_eq!(supabase.collection.as_deref(), Some("match_documents"));
+        assert_eq!(supabase.top_k, Some(8));
+
+        // GraphQL that is not a search, and an RPC without an embedding
+        assert!(parse_http_query(
+            "weaviate.internal",
+            "/v1/graphql",
+            None,
+            &json!({"query": "{ Get { Article { title } } }"})
+        )
+        .is_none());
+        assert!(parse_http_query(
+            "abc.supabase.co",
+            "/rest/v1/rpc/signup",
+            None,
+            &json!({"email": "x"})
+        )
+        .is_none());
+    }
+
+    fn pg_message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&((body.len() + 4) as u32).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    #[test]
+    fn test_pgvector_wire_query() {
+        let sql = b"SELECT id, content FROM documents ORDER BY embedding <=> $1 LIMIT 5\0";
+        let mut parse = b"\0".to_vec();
+        parse.extend_from_slice(sql);
+        parse.extend_from_slice(&[0, 0]);
+        let query = parse_postgres_query(&pg_message(b'P', &parse)).unwrap();
+        assert_eq!(query.store, "pgvector");
+        assert_eq!(query.collection.as_deref(), Some("documents"));
+        assert_eq!(query.top_k, Some(5));
+
+        assert!(parse_postgres_query(&pg_message(b'Q', b"SELECT 1\0")).is_none());
+        assert!(parse_postgres_query(b"GET / HTTP/1.1\r\n").is_none());
+
+        let mut response = pg_message(b'1', b"");
+        response.extend(pg_message(b'D', b"\0\x02\0\0\0\x011\0\0\0\x01a"));
+        response.extend(pg_message(b'C', b"SELECT 5\0"));
+        response.extend(pg_message(b'Z', b"I"));
+        assert_eq!(parse_postgres_results(&response).unwrap().count, 5);
+        assert!(parse_postgres_results(&pg_message(b'D', b"\0\0")).is_none());
+    }
+}
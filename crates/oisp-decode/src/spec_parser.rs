@@ -4,10 +4,11 @@
 //! AI requests and responses dynamically. This allows adding new providers
 //! without code changes.
 
+use crate::tool_args::tool_call;
 use oisp_core::events::{
     AgentContext, AiRequestData, AiResponseData, Choice, ConversationContext, FinishReason,
     Message, MessageContent, MessageRole, ModelInfo, ModelParameters, ProviderInfo, RequestType,
-    ThinkingBlock, ThinkingMode, ToolArguments, ToolCall, ToolDefinition, ToolType, Usage,
+    ThinkingBlock, ThinkingMode, ToolCall, ToolDefinition, ToolType, Usage,
 };
 use oisp_core::spec::{DynamicProviderRegistry, EndpointRules, ExtractionRuleSet};
 use serde_json::Value;
@@ -367,6 +368,7 @@ fn parse_single_message(msg: &Value) -> Message {
         content_length: content_str.map(|s| s.len()),
         has_images: detect_images(msg),
         image_count: count_images(msg),
+        images: Vec::new(),
         tool_call_id: msg
             .get("tool_call_id")
             .and_then(|t| t.as_str())
@@ -581,6 +583,7 @@ fn extract_choices(body: &Value) -> Vec<Choice> {
                 content_length: Some(text_content.len()),
                 has_images: None,
                 image_count: None,
+                images: Vec::new(),
                 tool_call_id: None,
                 name: None,
             }),
@@ -612,16 +615,9 @@ fn extract_tool_calls(body: &Value) -> Vec<ToolCall> {
                 let arguments = tc
                     .get("function")
                     .and_then(|f| f.get("arguments"))
-                    .and_then(|a| a.as_str())
-                    .map(|s| ToolArguments::String(s.to_string()));
-
-                Some(ToolCall {
-                    id,
-                    name: name.to_string(),
-                    tool_type: Some(ToolType::Function),
-                    arguments,
-                    arguments_hash: None,
-                })
+                    .filter(|a| a.is_string());
+
+                Some(tool_call(id, name, arguments))
             })
             .collect();
     }
@@ -637,15 +633,7 @@ fn extract_tool_calls(body: &Value) -> Vec<ToolCall> {
 
                 let id = block.get("id").and_then(|i| i.as_str()).map(String::from);
                 let name = block.get("name").and_then(|n| n.as_str())?;
-                let input = block.get("input");
-
-                Some(ToolCall {
-                    id,
-                    name: name.to_string(),
-                    tool_type: Some(ToolType::Function),
-                    arguments: input.map(|i| ToolArguments::String(i.to_string())),
-                    arguments_hash: None,
-                })
+                Some(tool_call(id, name, block.get("input")))
             })
             .collect();
     }
@@ -114,6 +114,8 @@ pub struct StreamReassembler {
     complete_content: String,
     #[allow(dead_code)]
     tool_calls: Vec<Value>,
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -131,6 +133,8 @@ impl StreamReassembler {
             chunks: Vec::new(),
             complete_content: String::new(),
             tool_calls: Vec::new(),
+            input_tokens: None,
+            output_tokens: None,
         }
     }
 
@@ -145,6 +149,19 @@ impl StreamReassembler {
 
             // Try to parse as OpenAI-style streaming response
             if let Ok(json) = serde_json::from_str::<Value>(&event.data) {
+                // OpenAI Responses API streams typed `response.*` events
+                if let Some(event_type) = json.get("type").and_then(|t| t.as_str()) {
+                    if event_type.starts_with("response.") {
+                        self.feed_responses_event(event_type, &json);
+                        continue;
+                    }
+                }
+
+                if let Some(usage) = json.get("usage").filter(|u| !u.is_null()) {
+                    self.input_tokens = usage.get("prompt_tokens").and_then(|t| t.as_u64());
+                    self.output_tokens = usage.get("completion_tokens").and_then(|t| t.as_u64());
+                }
+
                 if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
                     for choice in choices {
                         let index =
@@ -182,11 +199,84 @@ impl StreamReassembler {
         }
     }
 
+    /// Handle a single OpenAI Responses API stream event
+    fn feed_responses_event(&mut self, event_type: &str, json: &Value) {
+        match event_type {
+            "response.output_text.delta" => {
+                if let Some(delta) = json.get("delta").and_then(|d| d.as_str()) {
+                    self.complete_content.push_str(delta);
+                    self.chunks.push(StreamChunk {
+                        index: json
+                            .get("output_index")
+                            .and_then(|i| i.as_u64())
+                            .unwrap_or(0) as usize,
+                        content: Some(delta.to_string()),
+                        tool_calls: None,
+                        finish_reason: None,
+                    });
+                }
+            }
+            "response.output_item.done" => {
+                if let Some(item) = json.get("item") {
+                    if item.get("type").and_then(|t| t.as_str()) == Some("function_call") {
+                        self.chunks.push(StreamChunk {
+                            index: json
+                                .get("output_index")
+                                .and_then(|i| i.as_u64())
+                                .unwrap_or(0) as usize,
+                            content: None,
+                            tool_calls: Some(vec![item.clone()]),
+                            finish_reason: None,
+                        });
+                    }
+                }
+            }
+            "response.completed" | "response.incomplete" | "response.failed" => {
+                let response = json.get("response");
+
+                if let Some(usage) = response.and_then(|r| r.get("usage")) {
+                    self.input_tokens = usage.get("input_tokens").and_then(|t| t.as_u64());
+                    self.output_tokens = usage.get("output_tokens").and_then(|t| t.as_u64());
+                }
+
+                let has_tool_calls = self.chunks.iter().any(|c| c.tool_calls.is_some());
+                let finish_reason = match event_type {
+                    "response.completed" if has_tool_calls => "tool_calls",
+                    "response.completed" => "stop",
+                    "response.incomplete" => {
+                        match response
+                            .and_then(|r| r.get("incomplete_details"))
+                            .and_then(|d| d.get("reason"))
+                            .and_then(|r| r.as_str())
+                        {
+                            Some("content_filter") => "content_filter",
+                            _ => "length",
+                        }
+                    }
+                    _ => "error",
+                };
+
+                self.chunks.push(StreamChunk {
+                    index: 0,
+                    content: None,
+                    tool_calls: None,
+                    finish_reason: Some(finish_reason.to_string()),
+                });
+            }
+            _ => {}
+        }
+    }
+
     /// Check if stream is complete
     pub fn is_complete(&self) -> bool {
         self.parser.is_done() || self.chunks.iter().any(|c| c.finish_reason.is_some())
     }
 
+    /// Get token usage (input, output) if the stream reported it
+    pub fn usage(&self) -> (Option<u64>, Option<u64>) {
+        (self.input_tokens, self.output_tokens)
+    }
+
     /// Get complete content
     pub fn content(&self) -> &str {
         &self.complete_content
@@ -438,6 +528,60 @@ mod tests {
         assert_eq!(reassembler.finish_reason(), Some("stop"));
     }
 
+    #[test]
+    fn test_stream_reassembler_responses_api() {
+        let mut reassembler = StreamReassembler::new();
+
+        let created = br#"event: response.created
+data: {"type":"response.created","response":{"id":"resp_123","object":"response","status":"in_progress"}}
+
+"#;
+        let delta1 = br#"event: response.output_text.delta
+data: {"type":"response.output_text.delta","output_index":0,"content_index":0,"delta":"Hello"}
+
+"#;
+        let delta2 = br#"event: response.output_text.delta
+data: {"type":"response.output_text.delta","output_index":0,"content_index":0,"delta":"!"}
+
+"#;
+        let completed = br#"event: response.completed
+data: {"type":"response.completed","response":{"id":"resp_123","object":"response","status":"completed","usage":{"input_tokens":12,"output_tokens":3,"total_tokens":15}}}
+
+"#;
+
+        reassembler.feed(created);
+        reassembler.feed(delta1);
+        assert!(!reassembler.is_complete());
+        reassembler.feed(delta2);
+        reassembler.feed(completed);
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.content(), "Hello!");
+        assert_eq!(reassembler.finish_reason(), Some("stop"));
+        assert_eq!(reassembler.usage(), (Some(12), Some(3)));
+    }
+
+    #[test]
+    fn test_stream_reassembler_responses_api_function_call() {
+        let mut reassembler = StreamReassembler::new();
+
+        let item_done = br#"event: response.output_item.done
+data: {"type":"response.output_item.done","output_index":0,"item":{"type":"function_call","call_id":"call_1","name":"get_weather","arguments":"{\"city\":\"Paris\"}"}}
+
+"#;
+        let completed = br#"event: response.completed
+data: {"type":"response.completed","response":{"status":"completed"}}
+
+"#;
+
+        reassembler.feed(item_done);
+        reassembler.feed(completed);
+
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.finish_reason(), Some("tool_calls"));
+        assert!(reassembler.chunks().iter().any(|c| c.tool_calls.is_some()));
+    }
+
     #[test]
     fn test_anthropic_stream_reassembler() {
         let mut reassembler = AnthropicStreamReassembler::new();
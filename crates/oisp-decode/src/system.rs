@@ -3,6 +3,7 @@
 //! This decoder handles non-HTTP events that come from eBPF tracepoints.
 
 use async_trait::async_trait;
+use glob::{MatchOptions, Pattern, PatternError};
 use oisp_core::events::envelope::{Actor, EventEnvelope, ProcessInfo};
 use oisp_core::events::file::{FileAccess, FileOpenData, FileOpenEvent as OispFileOpenEvent};
 use oisp_core::events::network::{
@@ -19,12 +20,96 @@ use oisp_core::plugins::{
 use std::any::Any;
 use tracing::debug;
 
+/// Kernel and device pseudo-filesystems, never reported whatever the
+/// configured lists say
+pub const BUILTIN_FILE_PATH_EXCLUDE: &[&str] = &["/proc/**", "/sys/**", "/dev/**"];
+
+/// `*` stops at `/`, `**` spans directories
+const PATH_MATCH: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Which `file.open` paths are reported, by glob
+///
+/// A path matching any exclude pattern is dropped, even if it also matches
+/// an include pattern. Otherwise it is reported if the include list is
+/// empty or it matches one of the include patterns. A leading `~/` expands
+/// to `$HOME` of the sensor's user.
+#[derive(Debug, Clone)]
+pub struct FilePathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl FilePathFilter {
+    /// Filter with these include and exclude patterns, plus
+    /// [`BUILTIN_FILE_PATH_EXCLUDE`]
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, PatternError> {
+        let compile = |patterns: &[String]| -> Result<Vec<Pattern>, PatternError> {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(&expand_home(p)))
+                .collect()
+        };
+        let mut exclude = compile(exclude)?;
+        for pattern in BUILTIN_FILE_PATH_EXCLUDE {
+            exclude.push(Pattern::new(pattern)?);
+        }
+        Ok(Self {
+            include: compile(include)?,
+            exclude,
+        })
+    }
+
+    /// Whether a `file.open` of `path` is reported
+    pub fn allows(&self, path: &str) -> bool {
+        if self
+            .exclude
+            .iter()
+            .any(|p| p.matches_with(path, PATH_MATCH))
+        {
+            return false;
+        }
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| p.matches_with(path, PATH_MATCH))
+    }
+}
+
+impl Default for FilePathFilter {
+    /// Only the built-in excludes
+    fn default() -> Self {
+        Self::new(&[], &[]).expect("built-in patterns are valid")
+    }
+}
+
+fn expand_home(pattern: &str) -> String {
+    match (pattern.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => pattern.to_string(),
+    }
+}
+
 /// System event decoder for process, file, and network events
-pub struct SystemDecoder;
+pub struct SystemDecoder {
+    file_paths: FilePathFilter,
+}
 
 impl SystemDecoder {
     pub fn new() -> Self {
-        Self
+        Self {
+            file_paths: FilePathFilter::default(),
+        }
+    }
+
+    /// Report only `file.open` events whose path `filter` allows
+    pub fn with_file_path_filter(mut self, filter: FilePathFilter) -> Self {
+        self.file_paths = filter;
+        self
     }
 }
 
@@ -105,6 +190,7 @@ impl SystemDecoder {
 
         // Set timestamp from raw event
         envelope.ts = timestamp_from_ns(raw.timestamp_ns);
+        envelope.ts_mono = Some(raw.timestamp_ns);
 
         // Set process info
         envelope.process = Some(ProcessInfo {
@@ -151,6 +237,7 @@ impl SystemDecoder {
         let mut envelope = EventEnvelope::new("process.exit");
 
         envelope.ts = timestamp_from_ns(raw.timestamp_ns);
+        envelope.ts_mono = Some(raw.timestamp_ns);
 
         // Set process info
         envelope.process = Some(ProcessInfo {
@@ -191,9 +278,15 @@ impl SystemDecoder {
     }
 
     fn decode_file_open(&self, raw: &RawCaptureEvent) -> Option<OispEvent> {
+        let path = raw.metadata.path.clone().unwrap_or_default();
+        if !self.file_paths.allows(&path) {
+            return None;
+        }
+
         let mut envelope = EventEnvelope::new("file.open");
 
         envelope.ts = timestamp_from_ns(raw.timestamp_ns);
+        envelope.ts_mono = Some(raw.timestamp_ns);
 
         // Set process info
         envelope.process = Some(ProcessInfo {
@@ -215,7 +308,6 @@ impl SystemDecoder {
             });
         }
 
-        let path = raw.metadata.path.clone().unwrap_or_default();
         let flags = raw
             .metadata
             .extra
@@ -265,6 +357,7 @@ impl SystemDecoder {
         let mut envelope = EventEnvelope::new("network.connect");
 
         envelope.ts = timestamp_from_ns(raw.timestamp_ns);
+        envelope.ts_mono = Some(raw.timestamp_ns);
 
         // Set process info
         envelope.process = Some(ProcessInfo {
@@ -416,6 +509,69 @@ mod tests {
         }
     }
 
+    fn file_open(path: &str) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: "test-open".to_string(),
+            timestamp_ns: 1234567890,
+            kind: RawEventKind::FileOpen,
+            pid: 1234,
+            tid: Some(1234),
+            data: Vec::new(),
+            metadata: RawEventMetadata {
+                path: Some(path.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_file_path_filter_precedence() {
+        let filter = FilePathFilter::new(
+            &patterns(&["/home/*/projects/**"]),
+            &patterns(&["**/node_modules/**", "**/*.pyc"]),
+        )
+        .unwrap();
+
+        assert!(filter.allows("/home/dev/projects/agent/main.py"));
+        // Outside the include list
+        assert!(!filter.allows("/home/dev/.bashrc"));
+        assert!(!filter.allows("/etc/passwd"));
+        // Exclude wins over include
+        assert!(!filter.allows("/home/dev/projects/web/node_modules/react/index.js"));
+        assert!(!filter.allows("/home/dev/projects/agent/__pycache__/main.cpython-312.pyc"));
+        // `*` does not cross directories
+        assert!(!filter.allows("/home/dev/x/projects/main.py"));
+
+        // No include list: everything not excluded
+        let filter = FilePathFilter::new(&[], &patterns(&["/tmp/**"])).unwrap();
+        assert!(filter.allows("/etc/hosts"));
+        assert!(!filter.allows("/tmp/scratch.txt"));
+
+        assert!(FilePathFilter::new(&patterns(&["/home/[dev"]), &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_noise_paths_filtered() {
+        // Even an include list that covers them does not bring them back
+        let filters = [
+            FilePathFilter::default(),
+            FilePathFilter::new(&patterns(&["/**"]), &[]).unwrap(),
+        ];
+        for filter in filters {
+            let decoder = SystemDecoder::new().with_file_path_filter(filter);
+            for path in ["/proc/self/status", "/sys/fs/cgroup/cpu.max", "/dev/null"] {
+                let events = decoder.decode(file_open(path)).await.unwrap();
+                assert!(events.is_empty(), "{} was reported", path);
+            }
+            let events = decoder.decode(file_open("/etc/hosts")).await.unwrap();
+            assert_eq!(events.len(), 1);
+        }
+    }
+
     #[tokio::test]
     async fn test_decode_network_connect() {
         let decoder = SystemDecoder::new();
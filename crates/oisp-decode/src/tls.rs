@@ -0,0 +1,318 @@
+//! TLS ClientHello parsing
+//!
+//! Extracts the Server Name Indication (SNI) and ALPN protocols from the
+//! first outbound TLS record(s) on a connection. This lets us attribute the
+//! destination of connections whose payloads we cannot decrypt (e.g.
+//! processes using rustls or BoringSSL, which the SSL uprobes don't cover).
+
+/// TLS record content type for handshake messages
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+
+/// Handshake message type for ClientHello
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+
+/// Extension types
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// Largest ClientHello we are willing to buffer across records
+pub const MAX_CLIENT_HELLO_LEN: usize = 16 * 1024;
+
+/// Fields extracted from a ClientHello
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHelloInfo {
+    /// Server Name Indication host name
+    pub sni: Option<String>,
+    /// Offered ALPN protocols, in client preference order
+    pub alpn: Vec<String>,
+    /// Highest offered TLS version (e.g. "TLS 1.3")
+    pub version: Option<String>,
+}
+
+/// Result of parsing the start of an outbound TLS stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientHelloParse {
+    /// A complete ClientHello was parsed
+    Complete(ClientHelloInfo),
+    /// Looks like a ClientHello, but more bytes are needed
+    Incomplete,
+    /// Data is not a TLS ClientHello
+    NotClientHello,
+}
+
+/// Parse a ClientHello from the first outbound bytes of a connection
+///
+/// The handshake message may be fragmented across several TLS records;
+/// `data` should contain every byte seen so far on the connection.
+pub fn parse_client_hello(data: &[u8]) -> ClientHelloParse {
+    // Reassemble the handshake payload from consecutive handshake records
+    let mut handshake = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        if data.len() < pos + 5 {
+            return ClientHelloParse::Incomplete;
+        }
+
+        let record = &data[pos..];
+        // Record header: type(1) version(2) length(2); version major is always 3
+        if record[0] != CONTENT_TYPE_HANDSHAKE || record[1] != 0x03 {
+            return ClientHelloParse::NotClientHello;
+        }
+
+        let record_len = u16::from_be_bytes([record[3], record[4]]) as usize;
+        let available = (record.len() - 5).min(record_len);
+        handshake.extend_from_slice(&record[5..5 + available]);
+
+        if handshake
+            .first()
+            .is_some_and(|t| *t != HANDSHAKE_CLIENT_HELLO)
+        {
+            return ClientHelloParse::NotClientHello;
+        }
+
+        if handshake.len() >= 4 {
+            let msg_len =
+                u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+            if msg_len + 4 > MAX_CLIENT_HELLO_LEN {
+                return ClientHelloParse::NotClientHello;
+            }
+            if handshake.len() >= msg_len + 4 {
+                return match parse_client_hello_body(&handshake[4..4 + msg_len]) {
+                    Some(info) => ClientHelloParse::Complete(info),
+                    None => ClientHelloParse::NotClientHello,
+                };
+            }
+        }
+
+        if available < record_len {
+            // Current record is still arriving
+            return ClientHelloParse::Incomplete;
+        }
+        pos += 5 + record_len;
+    }
+}
+
+/// Convenience wrapper returning only the SNI host name
+pub fn extract_sni(data: &[u8]) -> Option<String> {
+    match parse_client_hello(data) {
+        ClientHelloParse::Complete(info) => info.sni,
+        _ => None,
+    }
+}
+
+fn parse_client_hello_body(body: &[u8]) -> Option<ClientHelloInfo> {
+    let mut reader = Reader::new(body);
+
+    let legacy_version = reader.u16()?;
+    reader.skip(32)?; // random
+    let session_id_len = reader.u8()? as usize;
+    reader.skip(session_id_len)?;
+    let cipher_suites_len = reader.u16()? as usize;
+    reader.skip(cipher_suites_len)?;
+    let compression_len = reader.u8()? as usize;
+    reader.skip(compression_len)?;
+
+    let mut info = ClientHelloInfo {
+        version: version_name(legacy_version),
+        ..Default::default()
+    };
+
+    // Extensions are optional in very old ClientHellos
+    if reader.remaining() == 0 {
+        return Some(info);
+    }
+
+    let extensions_len = reader.u16()? as usize;
+    let mut extensions = Reader::new(reader.take(extensions_len)?);
+
+    while extensions.remaining() >= 4 {
+        let ext_type = extensions.u16()?;
+        let ext_len = extensions.u16()? as usize;
+        let mut ext = Reader::new(extensions.take(ext_len)?);
+
+        match ext_type {
+            EXT_SERVER_NAME => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while list.remaining() >= 3 {
+                    let name_type = list.u8()?;
+                    let name_len = list.u16()? as usize;
+                    let name = list.take(name_len)?;
+                    // 0 = host_name, the only type defined
+                    if name_type == 0 && info.sni.is_none() {
+                        info.sni = std::str::from_utf8(name).ok().map(|s| s.to_lowercase());
+                    }
+                }
+            }
+            EXT_ALPN => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while list.remaining() >= 1 {
+                    let proto_len = list.u8()? as usize;
+                    let proto = list.take(proto_len)?;
+                    info.alpn.push(String::from_utf8_lossy(proto).to_string());
+                }
+            }
+            EXT_SUPPORTED_VERSIONS => {
+                // TLS 1.3 advertises its real version here; legacy_version stays 1.2
+                let list_len = ext.u8()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                let mut highest = None;
+                while list.remaining() >= 2 {
+                    let v = list.u16()?;
+                    // Skip GREASE values (0x?a?a)
+                    if v & 0x0f0f == 0x0a0a {
+                        continue;
+                    }
+                    if (0x0300..=0x0304).contains(&v) {
+                        highest = highest.max(Some(v));
+                    }
+                }
+                if let Some(v) = highest {
+                    info.version = version_name(v);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(info)
+}
+
+fn version_name(version: u16) -> Option<String> {
+    let name = match version {
+        0x0300 => "SSL 3.0",
+        0x0301 => "TLS 1.0",
+        0x0302 => "TLS 1.1",
+        0x0303 => "TLS 1.2",
+        0x0304 => "TLS 1.3",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Minimal big-endian byte reader
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.remaining() < n {
+            return None;
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Some(slice)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
+/// ClientHello for api.openai.com captured from OpenSSL 3 (TLS 1.3, ALPN h2/http1.1)
+#[cfg(test)]
+const CLIENT_HELLO_HEX: &[&str] = &[
+    "1603010200010001fc03036cb46cd041974927dfc67797bcdb1cb171ac1e53cf4afa554ed921d5575d9cb020ca4e4843",
+    "2305283d28ef29073cd35fd37de693ff00afb65576f8b3daf9a775ae0024130213031301c02cc030c02bc02fcca9cca8",
+    "c024c028c023c027009f009e006b006700ff0100018f00000013001100000e6170692e6f70656e61692e636f6d000b00",
+    "0403000102000a00160014001d0017001e0019001801000101010201030104002300000010000e000c02683208687474",
+    "702f312e310016000000170000000d002a0028040305030603080708080809080a080b08040805080604010501060103",
+    "0303010302040205020602002b00050403040303002d00020101003300260024001d0020f72b75257541a174587a6938",
+    "a3062c05112212eef7f3b31c26ba4a53afed3660001500cd000000000000000000000000000000000000000000000000",
+    "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    "00000000000000000000000000000000000000000000000000000000000000000000000000",
+];
+
+/// Captured ClientHello bytes, shared with decoder tests
+#[cfg(test)]
+pub(crate) fn sample_client_hello() -> Vec<u8> {
+    hex::decode(CLIENT_HELLO_HEX.concat()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello() -> Vec<u8> {
+        sample_client_hello()
+    }
+
+    #[test]
+    fn test_parse_captured_client_hello() {
+        let info = match parse_client_hello(&client_hello()) {
+            ClientHelloParse::Complete(info) => info,
+            other => panic!("expected complete ClientHello, got {:?}", other),
+        };
+
+        assert_eq!(info.sni.as_deref(), Some("api.openai.com"));
+        assert_eq!(info.alpn, vec!["h2".to_string(), "http/1.1".to_string()]);
+        assert_eq!(info.version.as_deref(), Some("TLS 1.3"));
+    }
+
+    #[test]
+    fn test_partial_client_hello_needs_more() {
+        let data = client_hello();
+        assert_eq!(parse_client_hello(&data[..3]), ClientHelloParse::Incomplete);
+        assert_eq!(
+            parse_client_hello(&data[..200]),
+            ClientHelloParse::Incomplete
+        );
+    }
+
+    #[test]
+    fn test_client_hello_fragmented_across_records() {
+        let data = client_hello();
+        let handshake = &data[5..];
+        let (first, second) = handshake.split_at(100);
+
+        // Re-frame the handshake message as two TLS records
+        let mut fragmented = Vec::new();
+        for fragment in [first, second] {
+            fragmented.extend_from_slice(&[0x16, 0x03, 0x01]);
+            fragmented.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            fragmented.extend_from_slice(fragment);
+        }
+
+        assert_eq!(
+            parse_client_hello(&fragmented[..110]),
+            ClientHelloParse::Incomplete
+        );
+        assert_eq!(extract_sni(&fragmented).as_deref(), Some("api.openai.com"));
+    }
+
+    #[test]
+    fn test_non_tls_data() {
+        assert_eq!(
+            parse_client_hello(b"POST /v1/chat/completions HTTP/1.1\r\n"),
+            ClientHelloParse::NotClientHello
+        );
+        // Application data record
+        assert_eq!(
+            parse_client_hello(&[0x17, 0x03, 0x03, 0x00, 0x01, 0x00]),
+            ClientHelloParse::NotClientHello
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Bounded extraction of tool-call arguments
+//!
+//! Agent tool calls can carry megabytes of arguments (whole files, patches,
+//! search results). Arguments up to [`MAX_FULL_ARGUMENTS`] bytes are copied
+//! onto the event as before. Larger ones are streamed through a sink that
+//! counts, hashes and keeps only the first [`ARGUMENTS_PREVIEW`] bytes, so
+//! neither a second copy nor a serialized string of the whole arguments is
+//! ever built.
+
+use oisp_core::events::{ToolArguments, ToolCall, ToolType};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+
+/// Arguments larger than this (in bytes of JSON) are summarized
+pub const MAX_FULL_ARGUMENTS: usize = 64 * 1024;
+
+/// Bytes of summarized arguments kept as a preview
+pub const ARGUMENTS_PREVIEW: usize = 1024;
+
+/// Counts and hashes everything written, keeping a bounded prefix
+struct ArgumentsSink {
+    size: usize,
+    hasher: Sha256,
+    preview: Vec<u8>,
+}
+
+impl ArgumentsSink {
+    fn new() -> Self {
+        Self {
+            size: 0,
+            hasher: Sha256::new(),
+            preview: Vec::with_capacity(ARGUMENTS_PREVIEW),
+        }
+    }
+
+    /// Preview cut back to the last whole UTF-8 character
+    fn preview(mut self) -> String {
+        let valid = match std::str::from_utf8(&self.preview) {
+            Ok(_) => self.preview.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        self.preview.truncate(valid);
+        String::from_utf8(self.preview).unwrap_or_default()
+    }
+}
+
+impl Write for ArgumentsSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.size += buf.len();
+        self.hasher.update(buf);
+        let room = ARGUMENTS_PREVIEW - self.preview.len();
+        self.preview.extend_from_slice(&buf[..room.min(buf.len())]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Build a function tool call from its id, name and arguments
+///
+/// String arguments (OpenAI's JSON-encoded `arguments`) are kept as-is;
+/// anything else (Anthropic's `input`, Ollama's `arguments`) is kept as its
+/// JSON text. Oversized arguments become a preview plus `arguments_size`
+/// and an `arguments_hash` of the full text.
+pub fn tool_call(id: Option<String>, name: &str, arguments: Option<&Value>) -> ToolCall {
+    let mut call = ToolCall {
+        id,
+        name: name.to_string(),
+        tool_type: Some(ToolType::Function),
+        arguments: None,
+        arguments_hash: None,
+        arguments_size: None,
+    };
+    let Some(arguments) = arguments else {
+        return call;
+    };
+
+    let mut sink = ArgumentsSink::new();
+    let written = match arguments {
+        Value::String(s) => sink.write_all(s.as_bytes()),
+        other => serde_json::to_writer(&mut sink, other).map_err(io::Error::from),
+    };
+    if written.is_err() {
+        return call;
+    }
+
+    if sink.size <= MAX_FULL_ARGUMENTS {
+        call.arguments = Some(ToolArguments::String(match arguments {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }));
+        return call;
+    }
+
+    call.arguments_size = Some(sink.size);
+    call.arguments_hash = Some(format!(
+        "sha256:{}",
+        hex::encode(&std::mem::take(&mut sink.hasher).finalize()[..8])
+    ));
+    call.arguments = Some(ToolArguments::String(sink.preview()));
+    call
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn arguments_text(call: &ToolCall) -> &str {
+        match &call.arguments {
+            Some(ToolArguments::String(s)) => s,
+            other => panic!("unexpected arguments: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_small_arguments_kept_whole() {
+        let encoded = json!(r#"{"location": "San Francisco"}"#);
+        let call = tool_call(Some("call_1".into()), "get_weather", Some(&encoded));
+        assert_eq!(arguments_text(&call), r#"{"location": "San Francisco"}"#);
+        assert_eq!(call.arguments_size, None);
+        assert_eq!(call.arguments_hash, None);
+
+        let input = json!({"path": "src/main.rs", "lines": [1, 2]});
+        let call = tool_call(None, "read_file", Some(&input));
+        assert_eq!(arguments_text(&call), input.to_string());
+    }
+
+    #[test]
+    fn test_large_arguments_summarized() {
+        // A multi-megabyte patch with non-ASCII text straddling the preview cut
+        let content = "é".repeat(3 * 1024 * 1024);
+        let input = json!({"path": "big.txt", "content": content});
+        let full = input.to_string();
+
+        let call = tool_call(Some("toolu_1".into()), "write_file", Some(&input));
+        let preview = arguments_text(&call);
+        assert!(preview.len() <= ARGUMENTS_PREVIEW);
+        assert!(preview.len() > ARGUMENTS_PREVIEW - 4);
+        assert!(full.starts_with(preview));
+        assert_eq!(call.arguments_size, Some(full.len()));
+
+        let mut hasher = Sha256::new();
+        hasher.update(full.as_bytes());
+        let expected = format!("sha256:{}", hex::encode(&hasher.finalize()[..8]));
+        assert_eq!(call.arguments_hash, Some(expected));
+
+        // JSON-encoded string arguments are measured without re-encoding
+        let encoded = Value::String(full.clone());
+        let call = tool_call(None, "write_file", Some(&encoded));
+        assert_eq!(call.arguments_size, Some(full.len()));
+        assert!(arguments_text(&call).len() <= ARGUMENTS_PREVIEW);
+    }
+}
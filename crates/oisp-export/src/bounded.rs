@@ -0,0 +1,222 @@
+//! Per-exporter concurrency limit
+//!
+//! Exporters that send over the network can be called from several tasks at
+//! once (live pipeline, replay, backfill). [`BoundedExporter`] caps how many
+//! of those sends run at the same time; callers past the limit wait for a
+//! slot, so a slow destination pushes back on its producers instead of
+//! piling up requests.
+
+use async_trait::async_trait;
+use oisp_core::events::OispEvent;
+use oisp_core::plugins::{ExportPlugin, Plugin, PluginConfig, PluginInfo, PluginResult};
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Exporter that limits the number of concurrent calls into another
+pub struct BoundedExporter {
+    inner: Box<dyn ExportPlugin>,
+    permits: Semaphore,
+    in_flight: Arc<AtomicU64>,
+}
+
+/// Holds one slot; released (and the gauge lowered) on drop
+struct InFlight<'a> {
+    _permit: SemaphorePermit<'a>,
+    gauge: &'a AtomicU64,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.gauge.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl BoundedExporter {
+    /// Allow at most `max_in_flight` concurrent sends, reporting the current
+    /// count in `in_flight` (see `MetricsCollector::export_in_flight_gauge`)
+    pub fn new(
+        inner: Box<dyn ExportPlugin>,
+        max_in_flight: usize,
+        in_flight: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            inner,
+            permits: Semaphore::new(max_in_flight.max(1)),
+            in_flight,
+        }
+    }
+
+    /// Sends currently in progress
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    async fn acquire(&self) -> InFlight<'_> {
+        // The semaphore is never closed
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("export semaphore closed");
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            _permit: permit,
+            gauge: &self.in_flight,
+        }
+    }
+}
+
+impl PluginInfo for BoundedExporter {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn version(&self) -> &str {
+        self.inner.version()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+}
+
+impl Plugin for BoundedExporter {
+    fn init(&mut self, config: &PluginConfig) -> PluginResult<()> {
+        self.inner.init(config)
+    }
+
+    fn shutdown(&mut self) -> PluginResult<()> {
+        self.inner.shutdown()
+    }
+
+    // Downcasts see through to the wrapped exporter
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self.inner.as_any_mut()
+    }
+}
+
+#[async_trait]
+impl ExportPlugin for BoundedExporter {
+    async fn export(&self, event: &OispEvent) -> PluginResult<()> {
+        let _slot = self.acquire().await;
+        self.inner.export(event).await
+    }
+
+    async fn export_batch(&self, events: &[OispEvent]) -> PluginResult<()> {
+        let _slot = self.acquire().await;
+        self.inner.export_batch(events).await
+    }
+
+    async fn flush(&self) -> PluginResult<()> {
+        let _slot = self.acquire().await;
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::events::{EventEnvelope, ProcessExecData, ProcessExecEvent};
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    /// Exporter that takes a while per event and records peak concurrency
+    #[derive(Default)]
+    struct SlowExporter {
+        active: AtomicUsize,
+        peak: Arc<AtomicUsize>,
+        exported: Arc<AtomicUsize>,
+    }
+
+    impl PluginInfo for SlowExporter {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn version(&self) -> &str {
+            "0.0.0"
+        }
+
+        fn description(&self) -> &str {
+            "Test exporter"
+        }
+    }
+
+    impl Plugin for SlowExporter {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[async_trait]
+    impl ExportPlugin for SlowExporter {
+        async fn export(&self, _event: &OispEvent) -> PluginResult<()> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            self.exported.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn event() -> OispEvent {
+        OispEvent::ProcessExec(ProcessExecEvent {
+            envelope: EventEnvelope::new("process.exec"),
+            data: ProcessExecData {
+                exe: "/usr/bin/python3".to_string(),
+                args: vec![],
+                cwd: None,
+                env: Default::default(),
+                interpreter: None,
+                script_path: None,
+                is_shell: None,
+                is_script: None,
+                is_interactive: None,
+                binary_hash: None,
+                code_signature: None,
+            },
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrency_never_exceeds_limit() {
+        let slow = SlowExporter::default();
+        let peak = slow.peak.clone();
+        let exported = slow.exported.clone();
+        let gauge = Arc::new(AtomicU64::new(0));
+        let exporter = Arc::new(BoundedExporter::new(Box::new(slow), 3, gauge.clone()));
+
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let exporter = exporter.clone();
+                let gauge = gauge.clone();
+                tokio::spawn(async move {
+                    exporter.export(&event()).await.unwrap();
+                    assert!(gauge.load(Ordering::Relaxed) <= 3);
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(exported.load(Ordering::SeqCst), 100);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(exporter.in_flight(), 0);
+    }
+}
@@ -1,16 +1,37 @@
 //! JSONL file exporter
+//!
+//! Events from several capture sources can reach the exporter slightly out
+//! of timestamp order. With a reorder window configured, each event is held
+//! until its `ts` is at least that far in the past and written sorted by
+//! `ts` (then `ts_mono`), so lines are monotonic as long as no event arrives
+//! later than the window. Every event is delayed by up to the window.
+//!
+//! Lines reach the file after every event with `flush_each`. Otherwise they
+//! are buffered and written once `flush_bytes` are pending, every
+//! `flush_interval`, on `flush()` (which the pipeline calls on SIGUSR1 when
+//! `flush_on_signal` is set) and on shutdown.
 
 use async_trait::async_trait;
-use oisp_core::events::OispEvent;
+use chrono::{DateTime, Utc};
+use oisp_core::events::{OispEvent, SchemaTransform};
 use oisp_core::plugins::{
     ExportPlugin, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
 };
 use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
-use std::sync::Mutex;
-use tracing::{info, warn};
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Default cap on events held for reordering
+pub const DEFAULT_REORDER_MAX_EVENTS: usize = 10_000;
+
+/// Default size of the write buffer
+pub const DEFAULT_FLUSH_BYTES: usize = 8 * 1024;
 
 /// JSONL exporter configuration
 #[derive(Debug, Clone)]
@@ -26,6 +47,23 @@ pub struct JsonlExporterConfig {
 
     /// Flush after each write
     pub flush_each: bool,
+
+    /// Without `flush_each`, flush buffered lines this often (None = only
+    /// when the buffer fills, on `flush()` and on shutdown)
+    pub flush_interval: Option<Duration>,
+
+    /// Without `flush_each`, flush once this many bytes are buffered
+    pub flush_bytes: usize,
+
+    /// Hold events this long and write them sorted by timestamp (None = write
+    /// immediately in arrival order)
+    pub reorder_window: Option<Duration>,
+
+    /// Most events held for reordering; the oldest are written early beyond this
+    pub reorder_max_events: usize,
+
+    /// Write events in this older schema version (None = current schema)
+    pub schema: Option<SchemaTransform>,
 }
 
 impl Default for JsonlExporterConfig {
@@ -35,14 +73,146 @@ impl Default for JsonlExporterConfig {
             append: true,
             pretty: false,
             flush_each: true,
+            flush_interval: None,
+            flush_bytes: DEFAULT_FLUSH_BYTES,
+            reorder_window: None,
+            reorder_max_events: DEFAULT_REORDER_MAX_EVENTS,
+            schema: None,
         }
     }
 }
 
+type SharedWriter = Arc<Mutex<BufWriter<File>>>;
+
+/// When `write_lines` flushes
+#[derive(Debug, Clone, Copy)]
+struct FlushPolicy {
+    each: bool,
+    bytes: usize,
+}
+
+impl FlushPolicy {
+    fn new(config: &JsonlExporterConfig) -> Self {
+        Self {
+            each: config.flush_each,
+            bytes: config.flush_bytes.max(1),
+        }
+    }
+
+    /// Never flushes on its own; the caller flushes
+    fn none() -> Self {
+        Self {
+            each: false,
+            bytes: usize::MAX,
+        }
+    }
+}
+
+fn open_writer(config: &JsonlExporterConfig) -> std::io::Result<BufWriter<File>> {
+    let file = if config.append {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?
+    } else {
+        File::create(&config.path)?
+    };
+    Ok(BufWriter::with_capacity(config.flush_bytes.max(1), file))
+}
+
+/// Serialized event waiting in the reorder buffer
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct PendingLine {
+    ts: DateTime<Utc>,
+    ts_mono: u64,
+    /// Arrival order, so equal timestamps keep their order
+    seq: u64,
+    line: String,
+}
+
+/// Events held back until their timestamp leaves the reorder window
+struct ReorderBuffer {
+    window: chrono::Duration,
+    max_events: usize,
+    pending: BinaryHeap<Reverse<PendingLine>>,
+    seq: u64,
+}
+
+impl ReorderBuffer {
+    fn new(window: Duration, max_events: usize) -> Self {
+        Self {
+            window: chrono::Duration::from_std(window).unwrap_or(chrono::Duration::MAX),
+            max_events: max_events.max(1),
+            pending: BinaryHeap::new(),
+            seq: 0,
+        }
+    }
+
+    fn push(&mut self, event: &OispEvent, line: String) {
+        let envelope = event.envelope();
+        self.seq += 1;
+        self.pending.push(Reverse(PendingLine {
+            ts: envelope.ts,
+            ts_mono: envelope.ts_mono.unwrap_or(0),
+            seq: self.seq,
+            line,
+        }));
+    }
+
+    /// Lines whose timestamp is older than `now` minus the window, oldest
+    /// first, plus the oldest beyond `max_events`
+    fn drain_ready(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let cutoff = now.checked_sub_signed(self.window);
+        let mut ready = Vec::new();
+        while let Some(Reverse(next)) = self.pending.peek() {
+            let expired = cutoff.is_some_and(|cutoff| next.ts <= cutoff);
+            if !expired && self.pending.len() <= self.max_events {
+                break;
+            }
+            if let Some(Reverse(next)) = self.pending.pop() {
+                ready.push(next.line);
+            }
+        }
+        ready
+    }
+
+    fn drain_all(&mut self) -> Vec<String> {
+        let mut lines = Vec::with_capacity(self.pending.len());
+        while let Some(Reverse(next)) = self.pending.pop() {
+            lines.push(next.line);
+        }
+        lines
+    }
+}
+
+fn write_lines(
+    writer: &Mutex<BufWriter<File>>,
+    lines: &[String],
+    flush: FlushPolicy,
+) -> PluginResult<()> {
+    let mut w = writer
+        .lock()
+        .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+    for line in lines {
+        writeln!(w, "{}", line)?;
+    }
+    // The buffer writes itself out when a line doesn't fit; this covers
+    // it filling exactly
+    if flush.each || w.buffer().len() >= flush.bytes {
+        w.flush()?;
+    }
+    Ok(())
+}
+
 /// JSONL file exporter
 pub struct JsonlExporter {
     config: JsonlExporterConfig,
-    writer: Option<Mutex<BufWriter<File>>>,
+    writer: Option<SharedWriter>,
+    reorder: Option<Arc<Mutex<ReorderBuffer>>>,
+    /// Starts the task that writes held events once they leave the window
+    drain_task: Once,
+    /// Starts the task that flushes every `flush_interval`
+    flush_task: Once,
     events_written: std::sync::atomic::AtomicU64,
 }
 
@@ -50,19 +220,10 @@ impl JsonlExporter {
     pub fn new(config: JsonlExporterConfig) -> Self {
         // Eagerly create the file on construction
         // This ensures the file exists even if init() is never called
-        let writer = if config.append {
-            OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&config.path)
-        } else {
-            File::create(&config.path)
-        };
-
-        let writer = match writer {
-            Ok(file) => {
+        let writer = match open_writer(&config) {
+            Ok(writer) => {
                 info!("JSONL exporter writing to: {:?}", config.path);
-                Some(Mutex::new(BufWriter::new(file)))
+                Some(Arc::new(Mutex::new(writer)))
             }
             Err(e) => {
                 warn!(
@@ -73,29 +234,101 @@ impl JsonlExporter {
             }
         };
 
+        let reorder = config.reorder_window.map(|window| {
+            Arc::new(Mutex::new(ReorderBuffer::new(
+                window,
+                config.reorder_max_events,
+            )))
+        });
+
         Self {
             config,
             writer,
+            reorder,
+            drain_task: Once::new(),
+            flush_task: Once::new(),
             events_written: std::sync::atomic::AtomicU64::new(0),
         }
     }
 
     fn ensure_writer(&mut self) -> PluginResult<()> {
         if self.writer.is_none() {
-            let file = if self.config.append {
-                OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(&self.config.path)?
-            } else {
-                File::create(&self.config.path)?
-            };
-
-            self.writer = Some(Mutex::new(BufWriter::new(file)));
+            self.writer = Some(Arc::new(Mutex::new(open_writer(&self.config)?)));
             info!("JSONL exporter writing to: {:?}", self.config.path);
         }
         Ok(())
     }
+
+    /// Periodically write held events whose window has passed, so they are
+    /// not stuck behind a quiet period; stops when the exporter is dropped
+    fn spawn_drain_task(&self, window: Duration) {
+        let (Some(writer), Some(reorder)) = (&self.writer, &self.reorder) else {
+            return;
+        };
+        let writer: Weak<Mutex<BufWriter<File>>> = Arc::downgrade(writer);
+        let reorder = Arc::downgrade(reorder);
+        let flush = FlushPolicy::new(&self.config);
+        let period = (window / 2).max(Duration::from_millis(10));
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let (Some(writer), Some(reorder)) = (writer.upgrade(), reorder.upgrade()) else {
+                    break;
+                };
+                let ready = match reorder.lock() {
+                    Ok(mut buffer) => buffer.drain_ready(Utc::now()),
+                    Err(_) => break,
+                };
+                if !ready.is_empty() {
+                    if let Err(e) = write_lines(&writer, &ready, flush) {
+                        debug!("Failed to write reordered JSONL events: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Flush buffered lines every `period`; stops when the exporter is
+    /// dropped
+    fn spawn_flush_task(&self, period: Duration) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let writer: Weak<Mutex<BufWriter<File>>> = Arc::downgrade(writer);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period.max(Duration::from_millis(1)));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(writer) = writer.upgrade() else {
+                    break;
+                };
+                let Ok(mut w) = writer.lock() else {
+                    break;
+                };
+                if !w.buffer().is_empty() {
+                    if let Err(e) = w.flush() {
+                        debug!("Failed to flush JSONL events: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Write every held event, regardless of the window
+    fn drain_reorder_buffer(&self) -> PluginResult<()> {
+        let (Some(writer), Some(reorder)) = (&self.writer, &self.reorder) else {
+            return Ok(());
+        };
+        let lines = reorder
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?
+            .drain_all();
+        write_lines(writer, &lines, FlushPolicy::none())
+    }
 }
 
 impl PluginInfo for JsonlExporter {
@@ -123,12 +356,16 @@ impl Plugin for JsonlExporter {
         if let Some(pretty) = config.get::<bool>("pretty") {
             self.config.pretty = pretty;
         }
+        if let Some(version) = config.get::<String>("schema_version") {
+            self.config.schema = Some(SchemaTransform::new(&version)?);
+        }
 
         self.ensure_writer()?;
         Ok(())
     }
 
     fn shutdown(&mut self) -> PluginResult<()> {
+        let _ = self.drain_reorder_buffer();
         if let Some(writer) = &self.writer {
             if let Ok(mut w) = writer.lock() {
                 let _ = w.flush();
@@ -150,31 +387,43 @@ impl Plugin for JsonlExporter {
 #[async_trait]
 impl ExportPlugin for JsonlExporter {
     async fn export(&self, event: &OispEvent) -> PluginResult<()> {
-        let json = if self.config.pretty {
-            serde_json::to_string_pretty(event)?
-        } else {
-            serde_json::to_string(event)?
+        let json = match (&self.config.schema, self.config.pretty) {
+            (Some(schema), true) => serde_json::to_string_pretty(&schema.apply(event)?)?,
+            (Some(schema), false) => serde_json::to_string(&schema.apply(event)?)?,
+            (None, true) => serde_json::to_string_pretty(event)?,
+            (None, false) => serde_json::to_string(event)?,
         };
 
-        if let Some(writer) = &self.writer {
-            let mut w = writer
-                .lock()
-                .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
-
-            writeln!(w, "{}", json)?;
+        let Some(writer) = &self.writer else {
+            return Ok(());
+        };
+        self.events_written
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if let (false, Some(period)) = (self.config.flush_each, self.config.flush_interval) {
+            self.flush_task.call_once(|| self.spawn_flush_task(period));
+        }
+        let flush = FlushPolicy::new(&self.config);
 
-            if self.config.flush_each {
-                w.flush()?;
-            }
+        let (Some(reorder), Some(window)) = (&self.reorder, self.config.reorder_window) else {
+            return write_lines(writer, &[json], flush);
+        };
 
-            self.events_written
-                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.drain_task.call_once(|| self.spawn_drain_task(window));
+        // Lock order is buffer then writer, as in the drain task, so lines
+        // leave the buffer and reach the file in the same order
+        let mut buffer = reorder
+            .lock()
+            .map_err(|e| PluginError::OperationFailed(format!("Lock poisoned: {}", e)))?;
+        buffer.push(event, json);
+        let ready = buffer.drain_ready(Utc::now());
+        if ready.is_empty() {
+            return Ok(());
         }
-
-        Ok(())
+        write_lines(writer, &ready, flush)
     }
 
     async fn flush(&self) -> PluginResult<()> {
+        self.drain_reorder_buffer()?;
         if let Some(writer) = &self.writer {
             let mut w = writer
                 .lock()
@@ -184,3 +433,199 @@ impl ExportPlugin for JsonlExporter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::pipeline::{Pipeline, PipelineConfig};
+    use oisp_core::replay::{EventReplay, ReplayConfig};
+
+    const FIXTURE: &str = r#"{"oisp_version":"0.1","event_id":"evt-1","event_type":"ai.request","ts":"2024-01-01T12:00:00Z","source":{"collector":"test"},"confidence":{"level":"high","completeness":"full"},"data":{"request_id":"req-1","request_type":"chat","provider":{"name":"openai"},"model":{"id":"gpt-4o"},"messages":[{"role":"user","content":"Hello"}]}}
+{"oisp_version":"0.1","event_id":"evt-2","event_type":"ai.response","ts":"2024-01-01T12:00:01Z","source":{"collector":"test"},"confidence":{"level":"high","completeness":"full"},"data":{"request_id":"req-1","success":true,"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}}
+{"oisp_version":"0.1","event_id":"evt-3","event_type":"process.exit","ts":"2024-01-01T12:00:02Z","process":{"pid":42,"name":"python"},"source":{"collector":"test"},"confidence":{"level":"high","completeness":"full"},"data":{"exit_code":0}}"#;
+
+    fn parse_lines(content: &str) -> Vec<serde_json::Value> {
+        content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    fn exit_event(id: usize, ts: DateTime<Utc>) -> OispEvent {
+        let line = format!(
+            r#"{{"oisp_version":"0.1","event_id":"evt-{}","event_type":"process.exit","ts":"{}","process":{{"pid":42}},"source":{{"collector":"test"}},"confidence":{{"level":"high","completeness":"full"}},"data":{{"exit_code":0}}}}"#,
+            id,
+            ts.to_rfc3339()
+        );
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay_into_jsonl_matches_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.jsonl");
+        let output = dir.path().join("output.jsonl");
+        std::fs::write(&input, FIXTURE).unwrap();
+
+        let mut pipeline = Pipeline::new(PipelineConfig::default());
+        pipeline.add_export(Box::new(JsonlExporter::new(JsonlExporterConfig {
+            path: output.clone(),
+            append: false,
+            pretty: false,
+            flush_each: false,
+            ..Default::default()
+        })));
+
+        let replay = EventReplay::new(ReplayConfig {
+            input_file: input,
+            speed_multiplier: 0.0,
+            loop_playback: false,
+        });
+        let count = replay.run_through(&pipeline).await.unwrap();
+        assert_eq!(count, 3);
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(parse_lines(&written), parse_lines(FIXTURE));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_window_sorts_by_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output.jsonl");
+        let exporter = JsonlExporter::new(JsonlExporterConfig {
+            path: output.clone(),
+            append: false,
+            reorder_window: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+
+        let now = Utc::now();
+        let offsets_ms = [300, 100, 500, 200, 400, 0];
+        for (i, offset) in offsets_ms.iter().enumerate() {
+            let event = exit_event(i, now + chrono::Duration::milliseconds(*offset));
+            exporter.export(&event).await.unwrap();
+        }
+
+        // Nothing has left the window yet
+        assert!(std::fs::read_to_string(&output).unwrap().is_empty());
+
+        exporter.flush().await.unwrap();
+        let written = parse_lines(&std::fs::read_to_string(&output).unwrap());
+        let ts: Vec<DateTime<Utc>> = written
+            .iter()
+            .map(|e| e["ts"].as_str().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(ts.len(), offsets_ms.len());
+        assert!(ts.windows(2).all(|w| w[0] <= w[1]), "{:?}", ts);
+    }
+
+    fn line_count(path: &std::path::Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn test_buffered_flush_on_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output.jsonl");
+        let now = Utc::now();
+        let line_len = serde_json::to_string(&exit_event(0, now)).unwrap().len() + 1;
+        let exporter = JsonlExporter::new(JsonlExporterConfig {
+            path: output.clone(),
+            append: false,
+            flush_each: false,
+            flush_bytes: 3 * line_len,
+            ..Default::default()
+        });
+
+        for i in 0..2 {
+            exporter.export(&exit_event(i, now)).await.unwrap();
+        }
+        assert_eq!(line_count(&output), 0);
+
+        // The third line fills the buffer
+        exporter.export(&exit_event(2, now)).await.unwrap();
+        assert_eq!(line_count(&output), 3);
+
+        exporter.export(&exit_event(3, now)).await.unwrap();
+        assert_eq!(line_count(&output), 3);
+        exporter.flush().await.unwrap();
+        assert_eq!(line_count(&output), 4);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_flush_on_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output.jsonl");
+        let exporter = JsonlExporter::new(JsonlExporterConfig {
+            path: output.clone(),
+            append: false,
+            flush_each: false,
+            flush_interval: Some(Duration::from_millis(50)),
+            flush_bytes: 1024 * 1024,
+            ..Default::default()
+        });
+
+        exporter.export(&exit_event(0, Utc::now())).await.unwrap();
+        assert_eq!(line_count(&output), 0);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while line_count(&output) == 0 {
+            assert!(tokio::time::Instant::now() < deadline, "never flushed");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(line_count(&output), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_flush_on_sigusr1() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output.jsonl");
+        let mut pipeline = Pipeline::new(PipelineConfig {
+            flush_on_signal: true,
+            ..Default::default()
+        });
+        pipeline.add_export(Box::new(JsonlExporter::new(JsonlExporterConfig {
+            path: output.clone(),
+            append: false,
+            flush_each: false,
+            flush_bytes: 1024 * 1024,
+            ..Default::default()
+        })));
+        pipeline.start().await.unwrap();
+
+        pipeline.process_event(exit_event(0, Utc::now())).await;
+        assert_eq!(line_count(&output), 0);
+
+        // SAFETY: raise only sends a signal to this process, which the
+        // pipeline handles
+        assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while line_count(&output) == 0 {
+            assert!(tokio::time::Instant::now() < deadline, "never flushed");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(line_count(&output), 1);
+        assert!(pipeline.is_running().await);
+
+        pipeline.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_reorder_buffer_bounded() {
+        let mut buffer = ReorderBuffer::new(Duration::from_secs(60), 2);
+        let now = Utc::now();
+        for (i, offset) in [30, 10, 20].iter().enumerate() {
+            let event = exit_event(i, now + chrono::Duration::milliseconds(*offset));
+            buffer.push(&event, format!("line-{}", i));
+        }
+
+        // Over the cap, the oldest is released before its window passes
+        assert_eq!(buffer.drain_ready(now), vec!["line-1".to_string()]);
+        assert_eq!(
+            buffer.drain_all(),
+            vec!["line-2".to_string(), "line-0".to_string()]
+        );
+    }
+}
@@ -4,7 +4,8 @@
 //! Supports SASL authentication, TLS, and batching.
 
 use async_trait::async_trait;
-use oisp_core::events::OispEvent;
+use oisp_core::events::schema::event_to_string;
+use oisp_core::events::{OispEvent, SchemaTransform};
 use oisp_core::plugins::{
     ExportPlugin, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
 };
@@ -99,6 +100,9 @@ pub struct KafkaExporterConfig {
 
     /// Client ID for Kafka
     pub client_id: String,
+
+    /// Publish events in this older schema version (None = current schema)
+    pub schema: Option<SchemaTransform>,
 }
 
 /// Kafka compression codec
@@ -145,6 +149,7 @@ impl Default for KafkaExporterConfig {
             request_timeout_ms: 10000,
             key_by_event_id: true,
             client_id: "oisp-sensor".to_string(),
+            schema: None,
         }
     }
 }
@@ -323,6 +328,9 @@ impl Plugin for KafkaExporter {
         if let Some(key_by_event_id) = config.get::<bool>("key_by_event_id") {
             self.config.key_by_event_id = key_by_event_id;
         }
+        if let Some(version) = config.get::<String>("schema_version") {
+            self.config.schema = Some(SchemaTransform::new(&version)?);
+        }
 
         self.init_producer()?;
 
@@ -361,7 +369,11 @@ impl ExportPlugin for KafkaExporter {
         })?;
 
         // Serialize event to JSON
-        let payload = serde_json::to_string(event)?;
+        let payload = event_to_string(event, self.config.schema.as_ref())?;
+        let oisp_version = match &self.config.schema {
+            Some(schema) => schema.target().to_string(),
+            None => event.envelope().oisp_version.clone(),
+        };
         let key = self.message_key(event);
         let event_type = event.event_type();
         let timestamp = event.envelope().ts.timestamp_millis();
@@ -379,7 +391,7 @@ impl ExportPlugin for KafkaExporter {
                     })
                     .insert(rdkafka::message::Header {
                         key: "oisp_version",
-                        value: Some(event.envelope().oisp_version.as_bytes()),
+                        value: Some(oisp_version.as_bytes()),
                     }),
             );
 
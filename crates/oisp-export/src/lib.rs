@@ -8,6 +8,10 @@
 //! - **Kafka** (optional): Publishes events to Apache Kafka topics
 //! - **Webhook** (optional): POSTs events to HTTP endpoints
 //!
+//! Any exporter can be wrapped in a [`RedactingExporter`] to apply its own
+//! redaction profile, and in a [`BoundedExporter`] to cap its concurrent
+//! sends.
+//!
 //! ## Feature Flags
 //!
 //! - `jsonl` - JSONL file export (default)
@@ -16,7 +20,9 @@
 //! - `kafka` - Apache Kafka export
 //! - `webhook` - HTTP webhook export
 
+pub mod bounded;
 pub mod jsonl;
+pub mod redacting;
 pub mod websocket;
 
 #[cfg(feature = "otlp")]
@@ -29,7 +35,9 @@ pub mod kafka;
 pub mod webhook;
 
 // Re-exports
+pub use bounded::BoundedExporter;
 pub use jsonl::{JsonlExporter, JsonlExporterConfig};
+pub use redacting::RedactingExporter;
 pub use websocket::{WebSocketExporter, WebSocketExporterConfig};
 
 #[cfg(feature = "otlp")]
@@ -2,9 +2,14 @@
 //!
 //! Exports OISP events as OpenTelemetry logs using OTLP.
 //! Supports both gRPC and HTTP transports.
+//!
+//! Each event becomes one LogRecord: severity from the event type and
+//! outcome, the event JSON as body, GenAI/process/host attributes, and the
+//! trace and span ids of the event's trace context (when it has one) so
+//! backends can correlate the record with traces.
 
 use async_trait::async_trait;
-use oisp_core::events::OispEvent;
+use oisp_core::events::{EventEnvelope, OispEvent};
 use oisp_core::plugins::{
     ExportPlugin, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
 };
@@ -16,6 +21,7 @@ use tracing::{debug, info, warn};
 use opentelemetry::logs::{
     AnyValue, LogRecord as OtelLogRecord, Logger, LoggerProvider as _, Severity,
 };
+use opentelemetry::trace::{SpanId, TraceFlags, TraceId};
 use opentelemetry::{Key, KeyValue};
 use opentelemetry_otlp::{
     LogExporter, Protocol, WithExportConfig, WithHttpConfig, WithTonicConfig,
@@ -581,6 +587,35 @@ impl OtlpExporter {
         attrs
     }
 
+    /// Fill a log record with everything exported for an event
+    fn fill_log_record<R: OtelLogRecord>(&self, record: &mut R, event: &OispEvent) {
+        let envelope = event.envelope();
+
+        // Set timestamp
+        let timestamp = std::time::SystemTime::UNIX_EPOCH
+            + std::time::Duration::from_nanos(envelope.ts.timestamp_nanos_opt().unwrap_or(0) as u64);
+        record.set_timestamp(timestamp);
+        record.set_observed_timestamp(std::time::SystemTime::now());
+
+        // Set severity
+        record.set_severity_number(self.event_severity(event));
+        record.set_severity_text(event.event_type());
+
+        // Set body as JSON of the event
+        let body = serde_json::to_string(event).unwrap_or_default();
+        record.set_body(opentelemetry::logs::AnyValue::String(body.into()));
+
+        // Set attributes
+        for (key, value) in self.event_to_attributes(event) {
+            record.add_attribute(key, value);
+        }
+
+        // Correlate with the trace the event belongs to
+        if let Some((trace_id, span_id, trace_flags)) = trace_context(envelope) {
+            record.set_trace_context(trace_id, span_id, trace_flags);
+        }
+    }
+
     /// Get severity level based on event type
     fn event_severity(&self, event: &OispEvent) -> Severity {
         match event {
@@ -605,6 +640,19 @@ impl OtlpExporter {
     }
 }
 
+/// Trace and span ids of an envelope's W3C trace context
+///
+/// Ids that are malformed or all zeros are left off the record.
+fn trace_context(envelope: &EventEnvelope) -> Option<(TraceId, SpanId, Option<TraceFlags>)> {
+    let ctx = envelope.trace_context.as_ref()?;
+    let trace_id = TraceId::from_hex(&ctx.trace_id).ok()?;
+    let span_id = SpanId::from_hex(&ctx.span_id).ok()?;
+    if trace_id == TraceId::INVALID || span_id == SpanId::INVALID {
+        return None;
+    }
+    Some((trace_id, span_id, ctx.trace_flags.map(TraceFlags::new)))
+}
+
 impl PluginInfo for OtlpExporter {
     fn name(&self) -> &str {
         "otlp-exporter"
@@ -693,36 +741,15 @@ impl ExportPlugin for OtlpExporter {
         })?;
 
         let logger = provider.logger("oisp-sensor");
-        let envelope = event.envelope();
 
-        // Create log record
+        // Create and emit the log record
         let mut record = logger.create_log_record();
-
-        // Set timestamp
-        let timestamp = std::time::SystemTime::UNIX_EPOCH
-            + std::time::Duration::from_nanos(envelope.ts.timestamp_nanos_opt().unwrap_or(0) as u64);
-        record.set_timestamp(timestamp);
-        record.set_observed_timestamp(std::time::SystemTime::now());
-
-        // Set severity
-        record.set_severity_number(self.event_severity(event));
-        record.set_severity_text(event.event_type());
-
-        // Set body as JSON of the event
-        let body = serde_json::to_string(event).unwrap_or_default();
-        record.set_body(opentelemetry::logs::AnyValue::String(body.into()));
-
-        // Set attributes
-        for (key, value) in self.event_to_attributes(event) {
-            record.add_attribute(key, value);
-        }
-
-        // Emit the log record
+        self.fill_log_record(&mut record, event);
         logger.emit(record);
 
         self.events_exported
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        debug!("Exported event {} to OTLP", envelope.event_id);
+        debug!("Exported event {} to OTLP", event.envelope().event_id);
 
         Ok(())
     }
@@ -752,6 +779,9 @@ impl ExportPlugin for OtlpExporter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use oisp_core::events::{
+        AiResponseData, AiResponseEvent, ErrorInfo, ModelInfo, ProviderInfo, TraceContext, Usage,
+    };
 
     #[test]
     fn test_default_config() {
@@ -766,4 +796,123 @@ mod tests {
     fn test_transport_variants() {
         assert_eq!(OtlpTransport::default(), OtlpTransport::Grpc);
     }
+
+    #[test]
+    fn test_errored_response_log_record() {
+        let mut envelope = EventEnvelope::new("ai.response");
+        envelope.trace_context = Some(TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            span_id: "00f067aa0ba902b7".to_string(),
+            trace_flags: Some(1),
+        });
+        let event = OispEvent::AiResponse(AiResponseEvent {
+            envelope,
+            data: AiResponseData {
+                request_id: "req-123".to_string(),
+                provider_request_id: None,
+                provider: Some(ProviderInfo {
+                    name: "openai".to_string(),
+                    endpoint: None,
+                    region: None,
+                    organization_id: None,
+                    project_id: None,
+                }),
+                model: Some(ModelInfo {
+                    id: "gpt-4o".to_string(),
+                    name: None,
+                    family: None,
+                    version: None,
+                    capabilities: None,
+                    context_window: None,
+                    max_output_tokens: None,
+                }),
+                status_code: Some(429),
+                success: Some(false),
+                error: Some(ErrorInfo {
+                    error_type: Some("rate_limit_error".to_string()),
+                    message: Some("Rate limit reached".to_string()),
+                    code: None,
+                }),
+                choices: vec![],
+                tool_calls: vec![],
+                tool_calls_count: None,
+                usage: Some(Usage {
+                    prompt_tokens: Some(120),
+                    completion_tokens: Some(0),
+                    ..Default::default()
+                }),
+                latency_ms: Some(85),
+                time_to_first_token_ms: None,
+                was_cached: None,
+                finish_reason: None,
+                thinking: None,
+            },
+        });
+
+        let exporter = OtlpExporter::new(OtlpExporterConfig::default());
+        let logger = LoggerProvider::builder().build().logger("test");
+        let mut record = logger.create_log_record();
+        exporter.fill_log_record(&mut record, &event);
+
+        assert_eq!(record.severity_number, Some(Severity::Error));
+        assert_eq!(record.severity_text, Some("ai.response"));
+
+        let attr = |key: &str| {
+            record
+                .attributes_iter()
+                .find(|(k, _)| k.as_str() == key)
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(
+            attr(semconv::GEN_AI_SYSTEM),
+            Some(AnyValue::String("openai".into()))
+        );
+        assert_eq!(
+            attr(semconv::GEN_AI_RESPONSE_MODEL),
+            Some(AnyValue::String("gpt-4o".into()))
+        );
+        assert_eq!(
+            attr(semconv::GEN_AI_USAGE_INPUT_TOKENS),
+            Some(AnyValue::Int(120))
+        );
+        assert_eq!(
+            attr(semconv::GEN_AI_USAGE_OUTPUT_TOKENS),
+            Some(AnyValue::Int(0))
+        );
+        assert_eq!(attr(semconv::OISP_SUCCESS), Some(AnyValue::Boolean(false)));
+        assert_eq!(attr(semconv::OISP_STATUS_CODE), Some(AnyValue::Int(429)));
+
+        let trace = record.trace_context.as_ref().unwrap();
+        assert_eq!(
+            trace.trace_id,
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(trace.span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+        assert_eq!(trace.trace_flags, Some(TraceFlags::SAMPLED));
+
+        match record.body {
+            Some(AnyValue::String(ref body)) => assert!(body.as_str().contains("rate_limit_error")),
+            ref other => panic!("unexpected body {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_trace_context_rejects_invalid_ids() {
+        let mut envelope = EventEnvelope::new("ai.request");
+        assert!(trace_context(&envelope).is_none());
+
+        envelope.trace_context = Some(TraceContext {
+            trace_id: "00000000000000000000000000000000".to_string(),
+            span_id: "00f067aa0ba902b7".to_string(),
+            trace_flags: None,
+        });
+        assert!(trace_context(&envelope).is_none());
+
+        envelope.trace_context = Some(TraceContext {
+            trace_id: "not-hex".to_string(),
+            span_id: "00f067aa0ba902b7".to_string(),
+            trace_flags: None,
+        });
+        assert!(trace_context(&envelope).is_none());
+    }
 }
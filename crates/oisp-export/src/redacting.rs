@@ -0,0 +1,106 @@
+//! Per-exporter redaction
+//!
+//! The action stage redacts events once for the whole pipeline. When
+//! exporters need different redaction (full content to a local file, safe
+//! to the cloud), the action stage keeps the richest form any exporter needs
+//! and [`RedactingExporter`] redacts a copy for each exporter that wants
+//! less. The shared event is never modified.
+
+use async_trait::async_trait;
+use oisp_core::actions::RedactionPlugin;
+use oisp_core::events::OispEvent;
+use oisp_core::plugins::{ExportPlugin, Plugin, PluginConfig, PluginInfo, PluginResult};
+use oisp_core::redaction::RedactionConfig;
+use std::any::Any;
+
+/// Exporter that redacts events before passing them to another
+pub struct RedactingExporter {
+    inner: Box<dyn ExportPlugin>,
+    redaction: RedactionPlugin,
+}
+
+impl RedactingExporter {
+    pub fn new(inner: Box<dyn ExportPlugin>, redaction: RedactionConfig) -> Self {
+        Self {
+            inner,
+            redaction: RedactionPlugin::new(redaction),
+        }
+    }
+
+    /// Wrap `inner` if `redaction` redacts more than `upstream`, the
+    /// redaction events already went through in the action stage
+    pub fn wrap(
+        inner: Box<dyn ExportPlugin>,
+        redaction: RedactionConfig,
+        upstream: &RedactionConfig,
+    ) -> Box<dyn ExportPlugin> {
+        if upstream.mode.keeps_more_than(redaction.mode) {
+            Box::new(Self::new(inner, redaction))
+        } else {
+            inner
+        }
+    }
+}
+
+impl PluginInfo for RedactingExporter {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn version(&self) -> &str {
+        self.inner.version()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+}
+
+impl Plugin for RedactingExporter {
+    fn init(&mut self, config: &PluginConfig) -> PluginResult<()> {
+        self.inner.init(config)
+    }
+
+    fn shutdown(&mut self) -> PluginResult<()> {
+        self.inner.shutdown()
+    }
+
+    // Downcasts see through to the wrapped exporter
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self.inner.as_any_mut()
+    }
+}
+
+#[async_trait]
+impl ExportPlugin for RedactingExporter {
+    async fn export(&self, event: &OispEvent) -> PluginResult<()> {
+        match self.redaction.redacted(event) {
+            Some(redacted) => self.inner.export(&redacted).await,
+            None => self.inner.export(event).await,
+        }
+    }
+
+    async fn export_batch(&self, events: &[OispEvent]) -> PluginResult<()> {
+        let redacted: Vec<OispEvent> = events
+            .iter()
+            .map(|event| {
+                self.redaction
+                    .redacted(event)
+                    .unwrap_or_else(|| event.clone())
+            })
+            .collect();
+        self.inner.export_batch(&redacted).await
+    }
+
+    async fn flush(&self) -> PluginResult<()> {
+        self.inner.flush().await
+    }
+}
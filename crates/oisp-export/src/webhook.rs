@@ -4,7 +4,8 @@
 //! Supports batching, retries with exponential backoff, and various authentication methods.
 
 use async_trait::async_trait;
-use oisp_core::events::OispEvent;
+use oisp_core::events::schema::{event_to_string, events_to_string};
+use oisp_core::events::{OispEvent, SchemaTransform};
 use oisp_core::plugins::{
     ExportPlugin, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
 };
@@ -101,6 +102,9 @@ pub struct WebhookExporterConfig {
 
     /// Dead letter queue file path (for failed events)
     pub dlq_path: Option<String>,
+
+    /// Send events in this older schema version (None = current schema)
+    pub schema: Option<SchemaTransform>,
 }
 
 impl Default for WebhookExporterConfig {
@@ -122,6 +126,7 @@ impl Default for WebhookExporterConfig {
             user_agent: format!("oisp-sensor/{}", env!("CARGO_PKG_VERSION")),
             content_type: "application/json".to_string(),
             dlq_path: None,
+            schema: None,
         }
     }
 }
@@ -303,7 +308,7 @@ impl WebhookExporter {
         let events: Vec<_> = buffer.drain(..).collect();
         drop(buffer); // Release lock before sending
 
-        let payload = serde_json::to_string(&events)?;
+        let payload = events_to_string(&events, self.config.schema.as_ref())?;
         let count = events.len();
 
         self.send_with_retry(&payload).await?;
@@ -408,6 +413,9 @@ impl Plugin for WebhookExporter {
         if let Some(dlq_path) = config.get::<String>("dlq_path") {
             self.config.dlq_path = Some(dlq_path);
         }
+        if let Some(version) = config.get::<String>("schema_version") {
+            self.config.schema = Some(SchemaTransform::new(&version)?);
+        }
 
         // Parse auth config
         if let Some(api_key) = config.get::<String>("api_key") {
@@ -472,7 +480,7 @@ impl ExportPlugin for WebhookExporter {
             }
         } else {
             // Send immediately
-            let payload = serde_json::to_string(event)?;
+            let payload = event_to_string(event, self.config.schema.as_ref())?;
             self.send_with_retry(&payload).await?;
             self.events_exported.fetch_add(1, Ordering::Relaxed);
             debug!("Exported event {} to webhook", event.envelope().event_id);
@@ -484,7 +492,7 @@ impl ExportPlugin for WebhookExporter {
     async fn export_batch(&self, events: &[OispEvent]) -> PluginResult<()> {
         if self.config.batch_mode {
             // Send as a single batch
-            let payload = serde_json::to_string(events)?;
+            let payload = events_to_string(events, self.config.schema.as_ref())?;
             self.send_with_retry(&payload).await?;
             self.events_exported
                 .fetch_add(events.len() as u64, Ordering::Relaxed);
@@ -0,0 +1,379 @@
+//! Backfill of captured events to Oximy Cloud
+//!
+//! Streams a JSONL capture through the exporter's batch path. After each
+//! batch the cloud acknowledges, the number of the last line it contained is
+//! written to a checkpoint file, so an interrupted run continues after the
+//! last delivered line instead of sending the file again.
+
+use crate::error::{OximyError, OximyResult};
+use crate::exporter::OximyExporter;
+use oisp_core::actions::RedactionPlugin;
+use oisp_core::events::OispEvent;
+use oisp_core::redaction::RedactionConfig;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Backfill settings
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    /// Events per batch
+    pub batch_size: usize,
+
+    /// Upper bound on events sent per second (0 for no limit)
+    pub max_events_per_sec: u32,
+
+    /// Where progress is recorded
+    pub checkpoint_path: PathBuf,
+
+    /// Ignore an existing checkpoint and start from the first line
+    pub restart: bool,
+
+    /// Redaction applied to each event before it is sent (unset = sent as
+    /// read), for captures recorded with more content than the cloud gets
+    pub redaction: Option<RedactionConfig>,
+}
+
+impl BackfillConfig {
+    /// Defaults for an input file, checkpointing next to it
+    pub fn for_input(input: &Path) -> Self {
+        Self {
+            batch_size: 100,
+            max_events_per_sec: 500,
+            checkpoint_path: default_checkpoint_path(input),
+            restart: false,
+            redaction: None,
+        }
+    }
+}
+
+/// Checkpoint file used when none is given: `<input>.checkpoint`
+pub fn default_checkpoint_path(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+/// Outcome of a backfill run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackfillReport {
+    /// Line the run started after (0 when starting from the beginning)
+    pub resumed_from: u64,
+
+    /// Last line delivered, as recorded in the checkpoint
+    pub last_line: u64,
+
+    /// Events delivered in this run
+    pub events_sent: u64,
+
+    /// Batches delivered in this run
+    pub batches_sent: u64,
+
+    /// Lines that were not valid events and were skipped
+    pub malformed_lines: u64,
+}
+
+/// Send the events of a JSONL file to Oximy Cloud
+///
+/// Blank lines are ignored and lines that don't parse as events are skipped
+/// and counted. A batch that fails to send stops the run with the error; the
+/// checkpoint still covers every batch delivered before it.
+pub async fn backfill(
+    exporter: &OximyExporter,
+    input: &Path,
+    config: &BackfillConfig,
+) -> OximyResult<BackfillReport> {
+    if config.batch_size == 0 {
+        return Err(OximyError::Config("batch size must be at least 1".into()));
+    }
+
+    let resumed_from = if config.restart {
+        0
+    } else {
+        read_checkpoint(&config.checkpoint_path)?
+    };
+    if resumed_from > 0 {
+        info!("Resuming backfill after line {}", resumed_from);
+    }
+
+    let mut report = BackfillReport {
+        resumed_from,
+        last_line: resumed_from,
+        ..Default::default()
+    };
+    let reader = BufReader::new(File::open(input)?);
+    let redaction = config.redaction.clone().map(RedactionPlugin::new);
+    let started = Instant::now();
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut batch_end = resumed_from;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index as u64 + 1;
+        let line = line?;
+        if line_number <= resumed_from {
+            continue;
+        }
+        batch_end = line_number;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<OispEvent>(&line) {
+            Ok(event) => batch.push(match &redaction {
+                Some(redaction) => redaction.redacted(&event).unwrap_or(event),
+                None => event,
+            }),
+            Err(e) => {
+                debug!("Skipping malformed line {}: {}", line_number, e);
+                report.malformed_lines += 1;
+                continue;
+            }
+        }
+
+        if batch.len() >= config.batch_size {
+            send(exporter, &mut batch, batch_end, config, &mut report).await?;
+            throttle(started, report.events_sent, config.max_events_per_sec).await;
+        }
+    }
+
+    if !batch.is_empty() {
+        send(exporter, &mut batch, batch_end, config, &mut report).await?;
+    }
+    // Trailing blank or malformed lines need no delivery; record them as
+    // done so a re-run has nothing left to do
+    if batch_end > report.last_line {
+        write_checkpoint(&config.checkpoint_path, batch_end)?;
+        report.last_line = batch_end;
+    }
+
+    if report.malformed_lines > 0 {
+        warn!(
+            "Skipped {} malformed line(s) in {}",
+            report.malformed_lines,
+            input.display()
+        );
+    }
+    Ok(report)
+}
+
+async fn send(
+    exporter: &OximyExporter,
+    batch: &mut Vec<OispEvent>,
+    last_line: u64,
+    config: &BackfillConfig,
+    report: &mut BackfillReport,
+) -> OximyResult<()> {
+    let events = std::mem::take(batch);
+    let count = events.len() as u64;
+    exporter.send_now(events).await?;
+
+    write_checkpoint(&config.checkpoint_path, last_line)?;
+    report.last_line = last_line;
+    report.events_sent += count;
+    report.batches_sent += 1;
+    debug!("Backfilled {} events through line {}", count, last_line);
+    Ok(())
+}
+
+/// Sleep until the events sent so far fit within the rate limit
+async fn throttle(started: Instant, events_sent: u64, max_events_per_sec: u32) {
+    if max_events_per_sec == 0 {
+        return;
+    }
+    let due = started + Duration::from_secs_f64(events_sent as f64 / max_events_per_sec as f64);
+    tokio::time::sleep_until(due).await;
+}
+
+/// Last delivered line recorded in the checkpoint, 0 if there is none
+fn read_checkpoint(path: &Path) -> OximyResult<u64> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => contents.trim().parse().map_err(|_| {
+            OximyError::Config(format!("invalid backfill checkpoint {}", path.display()))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_checkpoint(path: &Path, line: u64) -> OximyResult<()> {
+    // Write then rename, so an interrupted write never leaves a bad checkpoint
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, format!("{}\n", line))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::CloudClient;
+    use crate::config::OximyConfig;
+    use crate::exporter::OximyExporterConfig;
+    use crate::types::Credentials;
+    use chrono::Utc;
+    use oisp_core::events::{EventEnvelope, ProcessExitEvent};
+    use std::sync::Arc;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn event_line(pid: u32) -> String {
+        let event = OispEvent::ProcessExit(ProcessExitEvent {
+            envelope: EventEnvelope::new("process.exit"),
+            data: serde_json::from_value(serde_json::json!({"exit_code": pid})).unwrap(),
+        });
+        serde_json::to_string(&event).unwrap()
+    }
+
+    async fn exporter(server: &MockServer) -> OximyExporter {
+        let client = Arc::new(CloudClient::new(OximyConfig {
+            api_endpoint: server.uri(),
+            ..Default::default()
+        }));
+        client
+            .set_credentials(Credentials {
+                device_id: "dev_123".to_string(),
+                device_token: "tok".to_string(),
+                token_expires_at: Utc::now() + chrono::Duration::days(1),
+                organization_id: "org_123".to_string(),
+                workspace_id: None,
+                api_endpoint: server.uri(),
+                stream_endpoint: "wss://stream.oximy.com".to_string(),
+                created_at: Utc::now(),
+            })
+            .await;
+        OximyExporter::new(
+            client,
+            OximyExporterConfig {
+                offline_queue_enabled: false,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    async fn delivered_events(server: &MockServer) -> usize {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| {
+                let body: serde_json::Value = r.body_json().unwrap();
+                body["events"].as_array().unwrap().len()
+            })
+            .sum()
+    }
+
+    fn accept() -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_json(serde_json::json!({"received": 0, "batch_id": "b"}))
+    }
+
+    #[tokio::test]
+    async fn test_backfill_delivers_valid_events_and_checkpoints() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/events/batch"))
+            .respond_with(accept())
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("events.jsonl");
+        let lines = [
+            event_line(1),
+            "{not json".to_string(),
+            event_line(2),
+            String::new(),
+            event_line(3),
+            r#"{"event_type": "ai.telepathy"}"#.to_string(),
+            event_line(4),
+            event_line(5),
+        ];
+        std::fs::write(&input, lines.join("\n")).unwrap();
+
+        let config = BackfillConfig {
+            batch_size: 2,
+            max_events_per_sec: 0,
+            ..BackfillConfig::for_input(&input)
+        };
+        let exporter = exporter(&server).await;
+        let report = backfill(&exporter, &input, &config).await.unwrap();
+
+        assert_eq!(
+            report,
+            BackfillReport {
+                resumed_from: 0,
+                last_line: 8,
+                events_sent: 5,
+                batches_sent: 3,
+                malformed_lines: 2,
+            }
+        );
+        assert_eq!(delivered_events(&server).await, 5);
+        assert_eq!(read_checkpoint(&config.checkpoint_path).unwrap(), 8);
+
+        // Appended events are all a re-run sends
+        let mut lines = lines.to_vec();
+        lines.push(event_line(6));
+        std::fs::write(&input, lines.join("\n")).unwrap();
+        let report = backfill(&exporter, &input, &config).await.unwrap();
+        assert_eq!((report.resumed_from, report.events_sent), (8, 1));
+        assert_eq!(delivered_events(&server).await, 6);
+        assert_eq!(read_checkpoint(&config.checkpoint_path).unwrap(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_failure_keeps_checkpoint_of_acked_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/events/batch"))
+            .respond_with(accept())
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/events/batch"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("events.jsonl");
+        let lines: Vec<String> = (1..=5).map(event_line).collect();
+        std::fs::write(&input, lines.join("\n")).unwrap();
+
+        let config = BackfillConfig {
+            batch_size: 2,
+            max_events_per_sec: 0,
+            ..BackfillConfig::for_input(&input)
+        };
+        let exporter = exporter(&server).await;
+        assert!(backfill(&exporter, &input, &config).await.is_err());
+        assert_eq!(read_checkpoint(&config.checkpoint_path).unwrap(), 2);
+
+        let restarted = BackfillConfig {
+            restart: true,
+            ..config.clone()
+        };
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/events/batch"))
+            .respond_with(accept())
+            .mount(&server)
+            .await;
+        let report = backfill(&exporter, &input, &restarted).await.unwrap();
+        assert_eq!((report.resumed_from, report.events_sent), (0, 5));
+    }
+
+    #[test]
+    fn test_default_checkpoint_path() {
+        assert_eq!(
+            default_checkpoint_path(Path::new("/tmp/events.jsonl")),
+            PathBuf::from("/tmp/events.jsonl.checkpoint")
+        );
+    }
+}
@@ -1,13 +1,15 @@
 //! HTTP client for Oximy REST API
 //!
-//! Handles all REST API calls to api.oximy.com
+//! Handles all REST API calls to api.oximy.com, or to a self-hosted
+//! endpoint that may use a private CA (`ca_bundle_path`).
 
+use crate::config::OximyConfig;
 use crate::error::{OximyError, OximyResult};
 use crate::types::{
     ApiError, DeviceInfo, HeartbeatRequest, HeartbeatResponse, RegistrationResponse, SensorStats,
     SensorStatus,
 };
-use reqwest::{Client, StatusCode};
+use reqwest::{Certificate, Client, ClientBuilder, StatusCode};
 use std::time::Duration;
 use tracing::{debug, error, warn};
 
@@ -20,10 +22,7 @@ pub struct HttpClient {
 impl HttpClient {
     /// Create a new HTTP client
     pub fn new(base_url: &str, timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .user_agent(format!("oisp-sensor/{}", env!("CARGO_PKG_VERSION")))
-            .gzip(true)
+        let client = Self::builder(timeout)
             .build()
             .expect("Failed to create HTTP client");
 
@@ -33,6 +32,57 @@ impl HttpClient {
         }
     }
 
+    /// Create a client for the configured API endpoint, trusting the CA
+    /// certificates in `ca_bundle_path` in addition to the public roots
+    pub fn from_config(config: &OximyConfig) -> OximyResult<Self> {
+        let mut builder = Self::builder(config.connect_timeout());
+
+        if let Some(path) = &config.ca_bundle_path {
+            let pem = std::fs::read(path).map_err(|e| {
+                OximyError::Config(format!("Cannot read CA bundle {}: {}", path, e))
+            })?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .map_err(|e| OximyError::Config(format!("Invalid CA bundle {}: {}", path, e)))?;
+            if certs.is_empty() {
+                return Err(OximyError::Config(format!(
+                    "CA bundle {} contains no certificates",
+                    path
+                )));
+            }
+            debug!("Trusting {} CA certificate(s) from {}", certs.len(), path);
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if config.accept_invalid_hostnames {
+            warn!(
+                "TLS HOSTNAME VERIFICATION IS DISABLED for {}: any certificate from a trusted CA \
+                 is accepted regardless of its name. Only use this for internal deployments.",
+                config.api_endpoint
+            );
+            builder = builder.danger_accept_invalid_hostnames(true);
+        }
+
+        let client = builder.build()?;
+        Ok(Self {
+            client,
+            base_url: config.api_endpoint.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn builder(timeout: Duration) -> ClientBuilder {
+        Client::builder()
+            .timeout(timeout)
+            .user_agent(format!("oisp-sensor/{}", env!("CARGO_PKG_VERSION")))
+            .gzip(true)
+    }
+
+    /// API endpoint this client sends to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     /// Register device with API key
     pub async fn register_device(
         &self,
@@ -143,6 +193,34 @@ impl HttpClient {
         self.handle_response(response).await
     }
 
+    /// Check that the API endpoint is reachable
+    ///
+    /// Unauthenticated; any HTTP response counts as reachable and its
+    /// status code is returned. Only transport failures are errors.
+    pub async fn ping(&self) -> OximyResult<u16> {
+        let url = format!("{}/v1/health", self.base_url);
+
+        debug!("Pinging {}", url);
+
+        let response = self.client.get(&url).send().await?;
+        Ok(response.status().as_u16())
+    }
+
+    /// Check that the API endpoint is reachable before enrolling
+    ///
+    /// Like [`HttpClient::ping`], but a transport failure becomes
+    /// [`OximyError::Unreachable`] with the underlying cause (e.g. an
+    /// untrusted certificate) spelled out.
+    pub async fn check_reachable(&self) -> OximyResult<u16> {
+        self.ping().await.map_err(|e| match e {
+            OximyError::Network(e) => OximyError::Unreachable {
+                endpoint: self.base_url.clone(),
+                reason: describe_transport_error(&e),
+            },
+            other => other,
+        })
+    }
+
     /// Generic response handler
     async fn handle_response<T: serde::de::DeserializeOwned>(
         &self,
@@ -208,6 +286,25 @@ impl HttpClient {
     }
 }
 
+/// Flatten a transport error and its causes into one line, with a hint when
+/// the server's certificate was rejected
+fn describe_transport_error(e: &reqwest::Error) -> String {
+    let mut reason = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        reason.push_str(": ");
+        reason.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    if reason.contains("certificate") {
+        reason.push_str(
+            " (for a self-hosted endpoint with a private CA, set ca_bundle_path; \
+             for a certificate issued to another name, accept_invalid_hostnames)",
+        );
+    }
+    reason
+}
+
 /// Batch request payload
 #[derive(Debug, serde::Serialize)]
 struct BatchRequest {
@@ -228,6 +325,8 @@ pub struct BatchResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::self_hosted;
+    use std::path::Path;
 
     #[test]
     fn test_http_client_new() {
@@ -240,4 +339,67 @@ mod tests {
         let client = HttpClient::new("https://api.oximy.com/", Duration::from_secs(10));
         assert_eq!(client.base_url, "https://api.oximy.com");
     }
+
+    fn client_for(endpoint: &str, ca_bundle_path: Option<&Path>, any_hostname: bool) -> HttpClient {
+        HttpClient::from_config(&OximyConfig {
+            api_endpoint: endpoint.to_string(),
+            ca_bundle_path: ca_bundle_path.map(|p| p.display().to_string()),
+            accept_invalid_hostnames: any_hostname,
+            connect_timeout_ms: 5000,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_private_ca_trusted_from_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = self_hosted::start(dir.path(), "localhost").await;
+
+        let client = client_for(&server.endpoint, Some(&server.ca_path), false);
+        assert_eq!(client.check_reachable().await.unwrap(), 200);
+
+        // Without the bundle the private CA is unknown
+        let client = client_for(&server.endpoint, None, false);
+        let err = client.check_reachable().await.unwrap_err();
+        assert!(matches!(err, OximyError::Unreachable { .. }), "{}", err);
+        assert!(err.to_string().contains("ca_bundle_path"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_accept_invalid_hostnames() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = self_hosted::start(dir.path(), "oximy.corp.internal").await;
+
+        let client = client_for(&server.endpoint, Some(&server.ca_path), false);
+        assert!(client.check_reachable().await.is_err());
+
+        let client = client_for(&server.endpoint, Some(&server.ca_path), true);
+        assert_eq!(client.check_reachable().await.unwrap(), 200);
+
+        // The chain is still verified
+        let client = client_for(&server.endpoint, None, true);
+        assert!(client.check_reachable().await.is_err());
+    }
+
+    #[test]
+    fn test_bad_ca_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let config = OximyConfig {
+            ca_bundle_path: Some(missing.display().to_string()),
+            ..Default::default()
+        };
+        let err = HttpClient::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("missing.pem"), "{}", err);
+
+        let empty = dir.path().join("empty.pem");
+        std::fs::write(&empty, "").unwrap();
+        let config = OximyConfig {
+            ca_bundle_path: Some(empty.display().to_string()),
+            ..Default::default()
+        };
+        let err = HttpClient::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("no certificates"), "{}", err);
+    }
 }
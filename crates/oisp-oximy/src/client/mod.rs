@@ -3,36 +3,77 @@
 //! Central client that manages connections to Oximy Cloud.
 
 mod http;
+#[cfg(test)]
+pub(crate) mod self_hosted;
 
 pub use http::HttpClient;
 
 use crate::config::OximyConfig;
+use crate::enrollment::{CredentialStore, FileCredentialStore};
 use crate::error::{OximyError, OximyResult};
 use crate::types::Credentials;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{error, info, warn};
 
 /// Cloud client for Oximy platform
 ///
 /// Manages HTTP and WebSocket connections to the Oximy cloud.
+///
+/// Requests made through [`CloudClient::with_auth`] rotate the device token
+/// once it is within `token_refresh_window_secs` of expiry. Refreshes are
+/// serialized, so concurrent requests trigger a single rotation.
 pub struct CloudClient {
     config: OximyConfig,
     http: HttpClient,
     credentials: Arc<RwLock<Option<Credentials>>>,
+    store: parking_lot::RwLock<Arc<dyn CredentialStore>>,
+    refresh_lock: Mutex<()>,
 }
 
 impl CloudClient {
     /// Create a new cloud client
+    ///
+    /// A CA bundle that cannot be loaded is logged and ignored, leaving only
+    /// the public roots trusted; use [`CloudClient::try_new`] to fail instead.
     pub fn new(config: OximyConfig) -> Self {
-        let http = HttpClient::new(&config.api_endpoint, config.connect_timeout());
+        let http = HttpClient::from_config(&config).unwrap_or_else(|e| {
+            error!("{}; trusting only the public CA roots", e);
+            HttpClient::new(&config.api_endpoint, config.connect_timeout())
+        });
+        Self::with_http(config, http)
+    }
+
+    /// Create a new cloud client, failing if the configured CA bundle
+    /// cannot be loaded
+    pub fn try_new(config: OximyConfig) -> OximyResult<Self> {
+        let http = HttpClient::from_config(&config)?;
+        Ok(Self::with_http(config, http))
+    }
+
+    fn with_http(config: OximyConfig, http: HttpClient) -> Self {
+        let store: Arc<dyn CredentialStore> = Arc::new(FileCredentialStore::from_config(&config));
 
         Self {
             config,
             http,
             credentials: Arc::new(RwLock::new(None)),
+            store: parking_lot::RwLock::new(store),
+            refresh_lock: Mutex::new(()),
         }
     }
 
+    /// Credential store that refreshed tokens are persisted to
+    pub fn credential_store(&self) -> Arc<dyn CredentialStore> {
+        self.store.read().clone()
+    }
+
+    /// Replace the credential store that refreshed tokens are persisted to
+    pub fn set_credential_store(&self, store: Arc<dyn CredentialStore>) {
+        *self.store.write() = store;
+    }
+
     /// Get HTTP client reference
     pub fn http(&self) -> &HttpClient {
         &self.http
@@ -96,6 +137,93 @@ impl CloudClient {
             None => Err(OximyError::NotEnrolled),
         }
     }
+
+    /// Run an authenticated request, refreshing the token around it
+    ///
+    /// The token is rotated first if it is within the refresh window of
+    /// expiry. If the request is rejected with an auth error, the token is
+    /// rotated and the request retried once.
+    pub async fn with_auth<T, F, Fut>(&self, request: F) -> OximyResult<T>
+    where
+        F: Fn(String, String) -> Fut,
+        Fut: Future<Output = OximyResult<T>>,
+    {
+        if let Err(e) = self.refresh_if_needed().await {
+            warn!("Token refresh failed: {}", e);
+        }
+
+        let (device_id, token) = self.ensure_authenticated().await?;
+        match request(device_id, token.clone()).await {
+            Err(OximyError::TokenExpired | OximyError::Auth(_)) => {
+                self.refresh(Some(&token)).await?;
+                let (device_id, token) = self.ensure_authenticated().await?;
+                request(device_id, token).await
+            }
+            result => result,
+        }
+    }
+
+    /// Rotate the device token if it is within the refresh window of expiry
+    ///
+    /// Returns whether a rotation happened.
+    pub async fn refresh_if_needed(&self) -> OximyResult<bool> {
+        if !self.needs_refresh().await {
+            return Ok(false);
+        }
+        self.refresh(None).await
+    }
+
+    async fn needs_refresh(&self) -> bool {
+        let window = chrono::Duration::from_std(self.config.token_refresh_window())
+            .unwrap_or(chrono::Duration::MAX);
+        let guard = self.credentials.read().await;
+        guard.as_ref().is_some_and(|c| c.expires_soon(window))
+    }
+
+    /// Rotate the device token
+    ///
+    /// With `stale_token`, rotates only if that token is still current;
+    /// otherwise only if the token is within the refresh window. Either way
+    /// the check is repeated under the refresh lock, so a task that waited
+    /// on another's rotation does not rotate again.
+    async fn refresh(&self, stale_token: Option<&str>) -> OximyResult<bool> {
+        let _refreshing = self.refresh_lock.lock().await;
+
+        let current = self.credentials().await.ok_or(OximyError::NotEnrolled)?;
+        let needed = match stale_token {
+            Some(stale) => current.device_token == stale,
+            None => self.needs_refresh().await,
+        };
+        if !needed {
+            return Ok(false);
+        }
+
+        let response = self
+            .http
+            .rotate_token(&current.device_id, &current.device_token)
+            .await?;
+        let mut refreshed = Credentials::from_registration(
+            response,
+            &current.api_endpoint,
+            &current.stream_endpoint,
+        );
+        refreshed.created_at = current.created_at;
+
+        // Hold the write lock across the save so readers never see a token
+        // that differs from the stored one. The old token may already be
+        // revoked, so a failed save still updates the in-memory copy.
+        let mut guard = self.credentials.write().await;
+        if let Err(e) = self.credential_store().save(&refreshed) {
+            warn!("Failed to persist rotated device token: {}", e);
+        }
+        info!(
+            "Rotated device token for {}, expires {}",
+            refreshed.device_id, refreshed.token_expires_at
+        );
+        *guard = Some(refreshed);
+
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -135,4 +263,159 @@ mod tests {
         assert!(client.has_valid_credentials().await);
         assert_eq!(client.device_id().await, Some("dev_123".to_string()));
     }
+
+    mod refresh {
+        use super::*;
+        use crate::enrollment::MemoryCredentialStore;
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        const ROTATE_PATH: &str = "/v1/devices/dev_123/rotate-token";
+
+        fn credentials(token: &str, expires_in: chrono::Duration) -> Credentials {
+            Credentials {
+                device_id: "dev_123".to_string(),
+                device_token: token.to_string(),
+                token_expires_at: Utc::now() + expires_in,
+                organization_id: "org_123".to_string(),
+                workspace_id: None,
+                api_endpoint: "https://api.oximy.com".to_string(),
+                stream_endpoint: "wss://stream.oximy.com".to_string(),
+                created_at: Utc::now() - chrono::Duration::days(30),
+            }
+        }
+
+        fn rotated() -> ResponseTemplate {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "device": {
+                    "id": "dev_123",
+                    "organization_id": "org_123",
+                    "workspace_id": null,
+                    "name": "test",
+                    "status": "active"
+                },
+                "credentials": {
+                    "device_token": "tok_new",
+                    "expires_at": Utc::now() + chrono::Duration::hours(24)
+                }
+            }))
+        }
+
+        async fn client_with(
+            server: &MockServer,
+            creds: Credentials,
+        ) -> (CloudClient, Arc<MemoryCredentialStore>) {
+            let config = OximyConfig {
+                api_endpoint: server.uri(),
+                token_refresh_window_secs: 3600,
+                ..Default::default()
+            };
+            let client = CloudClient::new(config);
+            let store = Arc::new(MemoryCredentialStore::new());
+            client.set_credential_store(store.clone());
+            client.set_credentials(creds).await;
+            (client, store)
+        }
+
+        #[tokio::test]
+        async fn test_refresh_near_expiry() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path(ROTATE_PATH))
+                .and(header("Authorization", "Bearer tok_old"))
+                .respond_with(rotated())
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let old = credentials("tok_old", chrono::Duration::minutes(10));
+            let created_at = old.created_at;
+            let (client, store) = client_with(&server, old).await;
+
+            let token = client
+                .with_auth(|_, token| async move { Ok(token) })
+                .await
+                .unwrap();
+            assert_eq!(token, "tok_new");
+
+            let current = client.credentials().await.unwrap();
+            assert_eq!(current.device_token, "tok_new");
+            assert_eq!(current.created_at, created_at);
+            let stored = store.load().unwrap().unwrap();
+            assert_eq!(stored.device_token, "tok_new");
+        }
+
+        #[tokio::test]
+        async fn test_no_refresh_outside_window() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path(ROTATE_PATH))
+                .respond_with(rotated())
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            let (client, store) =
+                client_with(&server, credentials("tok_old", chrono::Duration::hours(5))).await;
+
+            assert!(!client.refresh_if_needed().await.unwrap());
+            let token = client
+                .with_auth(|_, token| async move { Ok(token) })
+                .await
+                .unwrap();
+            assert_eq!(token, "tok_old");
+            assert!(store.load().unwrap().is_none());
+        }
+
+        #[tokio::test]
+        async fn test_concurrent_requests_single_refresh() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path(ROTATE_PATH))
+                .respond_with(rotated().set_delay(std::time::Duration::from_millis(50)))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let (client, _store) = client_with(
+                &server,
+                credentials("tok_old", chrono::Duration::minutes(10)),
+            )
+            .await;
+
+            let requests = (0..16).map(|_| client.with_auth(|_, token| async move { Ok(token) }));
+            let tokens = futures_util::future::join_all(requests).await;
+
+            for token in tokens {
+                assert_eq!(token.unwrap(), "tok_new");
+            }
+        }
+
+        #[tokio::test]
+        async fn test_retry_after_auth_failure() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path(ROTATE_PATH))
+                .respond_with(rotated())
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            // Token looks valid locally but the server has revoked it
+            let (client, _store) =
+                client_with(&server, credentials("tok_old", chrono::Duration::hours(5))).await;
+
+            let token = client
+                .with_auth(|_, token| async move {
+                    if token == "tok_old" {
+                        Err(OximyError::Auth("token revoked".to_string()))
+                    } else {
+                        Ok(token)
+                    }
+                })
+                .await
+                .unwrap();
+            assert_eq!(token, "tok_new");
+        }
+    }
 }
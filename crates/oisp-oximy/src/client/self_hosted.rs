@@ -0,0 +1,143 @@
+//! Mock self-hosted Oximy endpoint for tests
+//!
+//! Serves HTTPS with a certificate issued by a freshly generated private CA,
+//! like an internal deployment would. Answers `/v1/health` and
+//! `/v1/devices/enroll`; everything else is a 404.
+
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::ServerConfig;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+pub(crate) struct SelfHosted {
+    /// `https://127.0.0.1:<port>`
+    pub endpoint: String,
+    /// PEM file holding the private CA certificate
+    pub ca_path: PathBuf,
+}
+
+/// Start a server whose certificate is issued to `hostname` and signed by a
+/// private CA written to `dir/ca.pem`
+///
+/// The certificate also covers 127.0.0.1 when `hostname` is `localhost`.
+pub(crate) async fn start(dir: &Path, hostname: &str) -> SelfHosted {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let ca_path = dir.join("ca.pem");
+    std::fs::write(&ca_path, ca_cert.pem()).unwrap();
+
+    let mut names = vec![hostname.to_string()];
+    if hostname == "localhost" {
+        names.push("127.0.0.1".to_string());
+    }
+    let key = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(names)
+        .unwrap()
+        .signed_by(&key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(cert.der().to_vec())],
+                PrivateKeyDer::from(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            )
+            .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!(
+        "https://127.0.0.1:{}",
+        listener.local_addr().unwrap().port()
+    );
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                // Handshake failures are what the rejecting tests expect
+                if let Ok(mut tls) = acceptor.accept(stream).await {
+                    let path = read_request(&mut tls).await;
+                    let (status, body) = respond(&path);
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = tls.write_all(response.as_bytes()).await;
+                    let _ = tls.shutdown().await;
+                }
+            });
+        }
+    });
+
+    SelfHosted { endpoint, ca_path }
+}
+
+/// Read one request, body included, and return its path
+async fn read_request<S: AsyncReadExt + Unpin>(stream: &mut S) -> String {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return String::new(),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length: usize = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    head.split_whitespace().nth(1).unwrap_or("").to_string()
+}
+
+fn respond(path: &str) -> (&'static str, String) {
+    match path {
+        "/v1/health" => ("200 OK", r#"{"status":"ok"}"#.to_string()),
+        "/v1/devices/enroll" => (
+            "201 Created",
+            serde_json::json!({
+                "device": {
+                    "id": "dev_selfhosted",
+                    "organization_id": "org_internal",
+                    "workspace_id": null,
+                    "name": "sensor",
+                    "status": "active"
+                },
+                "credentials": {
+                    "device_token": "tok_selfhosted",
+                    "expires_at": chrono::Utc::now() + chrono::Duration::days(30)
+                }
+            })
+            .to_string(),
+        ),
+        _ => (
+            "404 Not Found",
+            r#"{"code":"not_found","message":"Not found"}"#.to_string(),
+        ),
+    }
+}
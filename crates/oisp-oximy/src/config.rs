@@ -54,6 +54,21 @@ pub struct OximyConfig {
 
     /// Credential storage path (for file-based storage)
     pub credential_path: Option<String>,
+
+    /// Restrict an existing credential file readable by others to 0600
+    /// instead of refusing to load it
+    pub fix_credential_permissions: bool,
+
+    /// Rotate the device token when it is within this many seconds of expiry
+    pub token_refresh_window_secs: u64,
+
+    /// PEM file of CA certificates trusted for the API endpoint, in addition
+    /// to the public roots (for self-hosted deployments with a private CA)
+    pub ca_bundle_path: Option<String>,
+
+    /// Accept certificates whose name does not match the endpoint host.
+    /// The chain is still verified; only for internal deployments.
+    pub accept_invalid_hostnames: bool,
 }
 
 impl Default for OximyConfig {
@@ -74,6 +89,10 @@ impl Default for OximyConfig {
             reconnect_enabled: true,
             reconnect_max_delay_ms: 30000,
             credential_path: None,
+            fix_credential_permissions: false,
+            token_refresh_window_secs: 3600,
+            ca_bundle_path: None,
+            accept_invalid_hostnames: false,
         }
     }
 }
@@ -98,6 +117,23 @@ impl OximyConfig {
         if let Ok(val) = std::env::var("OISP_OXIMY_STREAM_ENDPOINT") {
             config.stream_endpoint = val;
         }
+        if let Ok(val) = std::env::var("OISP_OXIMY_CREDENTIAL_PATH") {
+            config.credential_path = Some(val);
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_FIX_CREDENTIAL_PERMISSIONS") {
+            config.fix_credential_permissions = val.parse().unwrap_or(false);
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_TOKEN_REFRESH_WINDOW_SECS") {
+            if let Ok(secs) = val.parse() {
+                config.token_refresh_window_secs = secs;
+            }
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_CA_BUNDLE") {
+            config.ca_bundle_path = Some(val);
+        }
+        if let Ok(val) = std::env::var("OISP_OXIMY_ACCEPT_INVALID_HOSTNAMES") {
+            config.accept_invalid_hostnames = val.parse().unwrap_or(false);
+        }
 
         config
     }
@@ -111,6 +147,8 @@ impl OximyConfig {
             device_id: basic.device_id.clone(),
             batch_size: basic.batch_size,
             flush_interval_ms: basic.flush_interval_ms,
+            ca_bundle_path: basic.ca_bundle_path.clone(),
+            accept_invalid_hostnames: basic.accept_invalid_hostnames,
             ..Default::default()
         }
     }
@@ -130,6 +168,11 @@ impl OximyConfig {
         Duration::from_millis(self.connect_timeout_ms)
     }
 
+    /// Get token refresh window as Duration
+    pub fn token_refresh_window(&self) -> Duration {
+        Duration::from_secs(self.token_refresh_window_secs)
+    }
+
     /// Get max reconnect delay as Duration
     pub fn reconnect_max_delay(&self) -> Duration {
         Duration::from_millis(self.reconnect_max_delay_ms)
@@ -156,5 +199,6 @@ mod tests {
         assert_eq!(config.flush_interval(), Duration::from_millis(5000));
         assert_eq!(config.heartbeat_interval(), Duration::from_secs(30));
         assert_eq!(config.connect_timeout(), Duration::from_secs(10));
+        assert_eq!(config.token_refresh_window(), Duration::from_secs(3600));
     }
 }
@@ -2,12 +2,22 @@
 //!
 //! Provides secure storage for device credentials.
 
+use crate::config::OximyConfig;
 use crate::error::{OximyError, OximyResult};
 use crate::types::Credentials;
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
 use tracing::{debug, warn};
 
+/// Credential file mode (owner read/write only)
+#[cfg(unix)]
+const CREDENTIAL_FILE_MODE: u32 = 0o600;
+
+/// Mode for directories created to hold the credential file
+#[cfg(unix)]
+const CREDENTIAL_DIR_MODE: u32 = 0o700;
+
 /// Trait for credential storage backends
 pub trait CredentialStore: Send + Sync {
     /// Save credentials
@@ -29,14 +39,43 @@ pub trait CredentialStore: Send + Sync {
 ///
 /// Stores credentials as JSON in a file. This is a simple implementation
 /// for development/testing. Production should use OS keychain.
+///
+/// On Unix the file is created `0600` and new parent directories `0700`;
+/// an existing file readable by group or others is refused unless
+/// permission fixing is enabled. On Windows the file's ACL is restricted to
+/// the current user after writing.
 pub struct FileCredentialStore {
     path: PathBuf,
+    fix_permissions: bool,
 }
 
 impl FileCredentialStore {
     /// Create with custom path
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            fix_permissions: false,
+        }
+    }
+
+    /// Create from config (`credential_path`, `fix_credential_permissions`)
+    pub fn from_config(config: &OximyConfig) -> Self {
+        let store = match &config.credential_path {
+            Some(path) => Self::new(PathBuf::from(path)),
+            None => Self::default(),
+        };
+        store.with_fix_permissions(config.fix_credential_permissions)
+    }
+
+    /// Restrict an overly permissive existing file instead of failing
+    pub fn with_fix_permissions(mut self, fix: bool) -> Self {
+        self.fix_permissions = fix;
+        self
+    }
+
+    /// File the credentials are stored in
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
     }
 
     /// Get default credential path
@@ -56,12 +95,108 @@ impl FileCredentialStore {
     }
 
     /// Ensure parent directory exists
+    ///
+    /// Directories created here are owner-only. Existing directories are
+    /// left alone (the parent may be shared, e.g. `/var/lib`), but a
+    /// permissive one is reported.
     fn ensure_dir(&self) -> OximyResult<()> {
-        if let Some(parent) = self.path.parent() {
-            fs::create_dir_all(parent)?;
+        let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+            return Ok(());
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+            if parent.exists() {
+                let mode = fs::metadata(parent)?.permissions().mode() & 0o777;
+                if mode & 0o077 != 0 {
+                    warn!(
+                        "Credential directory {:?} has permissions {:04o}; 0700 is recommended",
+                        parent, mode
+                    );
+                }
+            } else {
+                fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(CREDENTIAL_DIR_MODE)
+                    .create(parent)?;
+            }
         }
+
+        #[cfg(not(unix))]
+        fs::create_dir_all(parent)?;
+
         Ok(())
     }
+
+    /// Refuse (or fix) an existing credential file readable by others
+    #[cfg(unix)]
+    fn check_permissions(&self) -> OximyResult<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(&self.path)?.permissions().mode() & 0o777;
+        if mode & 0o077 == 0 {
+            return Ok(());
+        }
+
+        if self.fix_permissions {
+            warn!(
+                "Credential file {:?} had permissions {:04o}; restricting to {:04o}",
+                self.path, mode, CREDENTIAL_FILE_MODE
+            );
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(CREDENTIAL_FILE_MODE))?;
+            Ok(())
+        } else {
+            Err(OximyError::CredentialStore(format!(
+                "credential file {} has permissions {:04o} (expected {:04o}); \
+                 run `chmod 600` on it or set fix_credential_permissions",
+                self.path.display(),
+                mode,
+                CREDENTIAL_FILE_MODE
+            )))
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn check_permissions(&self) -> OximyResult<()> {
+        Ok(())
+    }
+
+    /// Create a new file readable only by the current user
+    fn create_private_file(path: &std::path::Path) -> OximyResult<fs::File> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(CREDENTIAL_FILE_MODE);
+        }
+
+        Ok(options.open(path)?)
+    }
+
+    /// Restrict the file's ACL to the current user (inheritance removed)
+    #[cfg(windows)]
+    fn restrict_acl(&self) {
+        let Ok(user) = std::env::var("USERNAME") else {
+            warn!("USERNAME not set; credential file ACL left unchanged");
+            return;
+        };
+
+        let status = std::process::Command::new("icacls")
+            .arg(&self.path)
+            .args(["/inheritance:r", "/grant:r"])
+            .arg(format!("{}:F", user))
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+
+        if !status.map(|s| s.success()).unwrap_or(false) {
+            warn!("Failed to restrict credential file ACL on {:?}", self.path);
+        }
+    }
 }
 
 impl Default for FileCredentialStore {
@@ -76,18 +211,22 @@ impl CredentialStore for FileCredentialStore {
 
         let json = serde_json::to_string_pretty(credentials)?;
 
-        // Write atomically using temp file
+        // Write atomically using a temp file that is private from creation,
+        // so the token is never briefly readable by others
         let temp_path = self.path.with_extension("tmp");
-        fs::write(&temp_path, &json)?;
+        match fs::remove_file(&temp_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut file = Self::create_private_file(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
         fs::rename(&temp_path, &self.path)?;
 
-        // Set restrictive permissions on Unix
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let perms = fs::Permissions::from_mode(0o600);
-            fs::set_permissions(&self.path, perms)?;
-        }
+        #[cfg(windows)]
+        self.restrict_acl();
 
         debug!("Saved credentials to {:?}", self.path);
         Ok(())
@@ -98,6 +237,8 @@ impl CredentialStore for FileCredentialStore {
             return Ok(None);
         }
 
+        self.check_permissions()?;
+
         let json = fs::read_to_string(&self.path)?;
         let credentials: Credentials = serde_json::from_str(&json)?;
 
@@ -322,6 +463,64 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[cfg(unix)]
+    fn mode(path: &std::path::Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_store_writes_owner_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("oisp-sensor");
+        let path = dir.join("credentials.json");
+        let store = FileCredentialStore::new(path.clone());
+
+        store.save(&test_credentials()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&dir), 0o700);
+
+        // Overwriting keeps the mode
+        store.save(&test_credentials()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_store_permissive_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("creds.json");
+        fs::write(&path, serde_json::to_string(&test_credentials()).unwrap()).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        // Refused by default, and the file is left untouched
+        let err = FileCredentialStore::new(path.clone()).load().unwrap_err();
+        assert!(err.to_string().contains("0644"));
+        assert_eq!(mode(&path), 0o644);
+
+        // Fixed when enabled
+        let store = FileCredentialStore::new(path.clone()).with_fix_permissions(true);
+        assert!(store.load().unwrap().is_some());
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn test_file_store_from_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("creds.json");
+        let config = OximyConfig {
+            credential_path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let store = FileCredentialStore::from_config(&config);
+        store.save(&test_credentials()).unwrap();
+        assert!(path.exists());
+    }
+
     #[test]
     fn test_memory_store() {
         let store = MemoryCredentialStore::new();
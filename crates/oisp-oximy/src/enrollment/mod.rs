@@ -3,8 +3,12 @@
 //! Handles device registration and credential management.
 
 mod credentials;
+mod status;
 
+#[cfg(test)]
+pub(crate) use credentials::MemoryCredentialStore;
 pub use credentials::{CredentialStore, FileCredentialStore};
+pub use status::{humanize_expiry, EnrollmentState, EnrollmentStatus};
 
 use crate::client::CloudClient;
 use crate::config::OximyConfig;
@@ -16,18 +20,24 @@ use tracing::{debug, info, warn};
 /// Device enrollor - handles registration flow
 pub struct Enrollor {
     client: Arc<CloudClient>,
-    store: Box<dyn CredentialStore>,
+    store: Arc<dyn CredentialStore>,
 }
 
 impl Enrollor {
-    /// Create new enrollor with file-based credential storage
+    /// Create new enrollor using the client's credential store
+    /// (file-based unless replaced)
     pub fn new(client: Arc<CloudClient>) -> Self {
-        let store = Box::new(FileCredentialStore::default());
+        let store = client.credential_store();
         Self { client, store }
     }
 
     /// Create enrollor with custom credential store
+    ///
+    /// The store is also installed on the client, so rotated tokens are
+    /// persisted to it.
     pub fn with_store(client: Arc<CloudClient>, store: Box<dyn CredentialStore>) -> Self {
+        let store: Arc<dyn CredentialStore> = Arc::from(store);
+        client.set_credential_store(store.clone());
         Self { client, store }
     }
 
@@ -40,6 +50,8 @@ impl Enrollor {
             return Err(OximyError::InvalidApiKey);
         }
 
+        self.check_endpoint().await?;
+
         // Collect device info
         let info = DeviceInfo::default();
         debug!("Device info: {:?}", info);
@@ -73,6 +85,8 @@ impl Enrollor {
             return Err(OximyError::InvalidEnrollmentToken);
         }
 
+        self.check_endpoint().await?;
+
         // Collect device info
         let info = DeviceInfo::default();
         debug!("Device info: {:?}", info);
@@ -97,6 +111,18 @@ impl Enrollor {
         Ok(credentials)
     }
 
+    /// Fail early with [`OximyError::Unreachable`] when the API endpoint
+    /// cannot be reached, e.g. a self-hosted endpoint behind an untrusted CA
+    async fn check_endpoint(&self) -> OximyResult<()> {
+        let status = self.client.http().check_reachable().await?;
+        debug!(
+            "Endpoint {} reachable (health: {})",
+            self.client.http().base_url(),
+            status
+        );
+        Ok(())
+    }
+
     /// Load stored credentials
     pub fn load_credentials(&self) -> OximyResult<Option<Credentials>> {
         match self.store.load() {
@@ -125,14 +151,35 @@ impl Enrollor {
         if let Some(creds) = self.load_credentials()? {
             if !creds.is_expired() {
                 self.client.set_credentials(creds).await;
+                if let Err(e) = self.client.refresh_if_needed().await {
+                    warn!("Token refresh failed: {}", e);
+                }
                 return Ok(true);
             }
-            // Could try token rotation here
             warn!("Credentials expired, need re-enrollment");
         }
         Ok(false)
     }
 
+    /// Report enrollment status from stored credentials
+    ///
+    /// Valid credentials are loaded into the client, as in [`Self::initialize`].
+    /// Does not contact the cloud.
+    pub async fn status(&self) -> OximyResult<EnrollmentStatus> {
+        let creds = self.load_credentials()?;
+        if let Some(creds) = creds.as_ref().filter(|c| !c.is_expired()) {
+            self.client.set_credentials(creds.clone()).await;
+        }
+
+        let config = self.client.config();
+        Ok(EnrollmentStatus::new(
+            creds.as_ref(),
+            &config.api_endpoint,
+            &config.stream_endpoint,
+            self.client.has_valid_credentials().await,
+        ))
+    }
+
     /// Check if device is enrolled
     pub async fn is_enrolled(&self) -> bool {
         self.client.has_valid_credentials().await
@@ -151,7 +198,7 @@ impl Enrollor {
 
 /// Convenience function to enroll device with config
 pub async fn enroll_device(config: &OximyConfig) -> OximyResult<Credentials> {
-    let client = Arc::new(CloudClient::new(config.clone()));
+    let client = Arc::new(CloudClient::try_new(config.clone())?);
     let enrollor = Enrollor::new(client);
 
     // Try to load existing credentials first
@@ -176,6 +223,146 @@ pub async fn enroll_device(config: &OximyConfig) -> OximyResult<Credentials> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn credentials(expires_in: chrono::Duration) -> Credentials {
+        Credentials {
+            device_id: "dev_123".to_string(),
+            device_token: "tok_secret".to_string(),
+            token_expires_at: Utc::now() + expires_in,
+            organization_id: "org_123".to_string(),
+            workspace_id: Some("ws_123".to_string()),
+            api_endpoint: "https://api.oximy.com".to_string(),
+            stream_endpoint: "wss://stream.oximy.com".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn enrollor_with(api_endpoint: &str, stored: Option<Credentials>) -> Enrollor {
+        let config = OximyConfig {
+            api_endpoint: api_endpoint.to_string(),
+            ..Default::default()
+        };
+        let store = MemoryCredentialStore::new();
+        if let Some(creds) = stored {
+            store.save(&creds).unwrap();
+        }
+        Enrollor::with_store(Arc::new(CloudClient::new(config)), Box::new(store))
+    }
+
+    #[tokio::test]
+    async fn test_status_enrolled() {
+        let enrollor = enrollor_with(
+            "https://api.oximy.com",
+            Some(credentials(chrono::Duration::hours(5))),
+        );
+        let status = enrollor.status().await.unwrap();
+
+        assert_eq!(status.state, EnrollmentState::Enrolled);
+        assert_eq!(status.device_id.as_deref(), Some("dev_123"));
+        assert_eq!(status.organization_id.as_deref(), Some("org_123"));
+        assert_eq!(status.workspace_id.as_deref(), Some("ws_123"));
+        assert_eq!(status.api_endpoint, "https://api.oximy.com");
+        assert!(status.has_valid_credentials);
+        assert!(enrollor.is_enrolled().await);
+
+        let expiry = status.expiry_description(Utc::now()).unwrap();
+        assert!(expiry.starts_with("expires in 4h"), "{}", expiry);
+
+        let json = serde_json::to_string(&status).unwrap();
+        assert!(!json.contains("tok_secret"));
+    }
+
+    #[tokio::test]
+    async fn test_status_expired() {
+        let enrollor = enrollor_with(
+            "https://api.oximy.com",
+            Some(credentials(-chrono::Duration::hours(2))),
+        );
+        let status = enrollor.status().await.unwrap();
+
+        assert_eq!(status.state, EnrollmentState::Expired);
+        assert_eq!(status.device_id.as_deref(), Some("dev_123"));
+        assert!(!status.has_valid_credentials);
+        assert!(!enrollor.is_enrolled().await);
+        assert!(status
+            .expiry_description(Utc::now())
+            .unwrap()
+            .starts_with("expired"));
+    }
+
+    #[tokio::test]
+    async fn test_status_not_enrolled() {
+        let enrollor = enrollor_with("https://api.oximy.com", None);
+        let status = enrollor.status().await.unwrap();
+
+        assert_eq!(status.state, EnrollmentState::NotEnrolled);
+        assert!(status.device_id.is_none());
+        assert!(status.token_expires_at.is_none());
+        assert!(status.expiry_description(Utc::now()).is_none());
+        assert!(!status.has_valid_credentials);
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/health"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let enrollor = enrollor_with(&server.uri(), None);
+        assert_eq!(enrollor.client.http().ping().await.unwrap(), 200);
+
+        // Unreachable endpoint is a transport error, not a status code
+        let enrollor = enrollor_with("http://127.0.0.1:1", None);
+        assert!(enrollor.client.http().ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enroll_with_self_hosted_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = crate::client::self_hosted::start(dir.path(), "localhost").await;
+        let config = OximyConfig {
+            api_endpoint: server.endpoint.clone(),
+            stream_endpoint: server.endpoint.replace("https", "wss"),
+            ca_bundle_path: Some(server.ca_path.display().to_string()),
+            ..Default::default()
+        };
+        let client = Arc::new(CloudClient::try_new(config).unwrap());
+        let enrollor = Enrollor::with_store(client, Box::new(MemoryCredentialStore::new()));
+
+        let creds = enrollor.enroll_with_token("enroll_abc").await.unwrap();
+        assert_eq!(creds.device_id, "dev_selfhosted");
+        assert_eq!(creds.api_endpoint, server.endpoint);
+        assert!(enrollor.is_enrolled().await);
+    }
+
+    #[tokio::test]
+    async fn test_enroll_unreachable_endpoint() {
+        let enrollor = enrollor_with("http://127.0.0.1:1", None);
+        let err = enrollor.enroll_with_token("enroll_abc").await.unwrap_err();
+        assert!(matches!(err, OximyError::Unreachable { .. }), "{}", err);
+        assert!(err.to_string().contains("http://127.0.0.1:1"), "{}", err);
+
+        // A self-hosted endpoint whose private CA is not configured
+        let dir = tempfile::tempdir().unwrap();
+        let server = crate::client::self_hosted::start(dir.path(), "localhost").await;
+        let enrollor = enrollor_with(&server.endpoint, None);
+        let err = enrollor
+            .register_with_api_key("oxm_live_x")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OximyError::Unreachable { .. }), "{}", err);
+        assert!(err.to_string().contains("ca_bundle_path"), "{}", err);
+    }
+
     #[test]
     fn test_invalid_api_key() {
         // This is a sync check, doesn't need tokio
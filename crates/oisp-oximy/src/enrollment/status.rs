@@ -0,0 +1,133 @@
+//! Enrollment status reporting
+//!
+//! Summarizes the locally stored enrollment for `oisp-sensor oximy status`.
+//! Nothing here includes the device token.
+
+use crate::types::Credentials;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Enrollment state of this device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrollmentState {
+    /// Credentials are stored and the token has not expired
+    Enrolled,
+    /// Credentials are stored but the token has expired
+    Expired,
+    /// No credentials are stored
+    NotEnrolled,
+}
+
+impl std::fmt::Display for EnrollmentState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnrollmentState::Enrolled => write!(f, "enrolled"),
+            EnrollmentState::Expired => write!(f, "expired"),
+            EnrollmentState::NotEnrolled => write!(f, "not enrolled"),
+        }
+    }
+}
+
+/// Snapshot of the device's enrollment, safe to print
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrollmentStatus {
+    /// Overall state
+    pub state: EnrollmentState,
+
+    /// Device ID, if enrolled
+    pub device_id: Option<String>,
+
+    /// Organization ID, if enrolled
+    pub organization_id: Option<String>,
+
+    /// Workspace ID, if the enrollment is scoped to one
+    pub workspace_id: Option<String>,
+
+    /// Device token expiry, if enrolled
+    pub token_expires_at: Option<DateTime<Utc>>,
+
+    /// Configured REST API endpoint
+    pub api_endpoint: String,
+
+    /// Configured streaming endpoint
+    pub stream_endpoint: String,
+
+    /// Whether the client currently holds valid credentials
+    pub has_valid_credentials: bool,
+}
+
+impl EnrollmentStatus {
+    /// Build a status from (optionally) stored credentials
+    pub(crate) fn new(
+        credentials: Option<&Credentials>,
+        api_endpoint: &str,
+        stream_endpoint: &str,
+        has_valid_credentials: bool,
+    ) -> Self {
+        let state = match credentials {
+            Some(creds) if creds.is_expired() => EnrollmentState::Expired,
+            Some(_) => EnrollmentState::Enrolled,
+            None => EnrollmentState::NotEnrolled,
+        };
+
+        Self {
+            state,
+            device_id: credentials.map(|c| c.device_id.clone()),
+            organization_id: credentials.map(|c| c.organization_id.clone()),
+            workspace_id: credentials.and_then(|c| c.workspace_id.clone()),
+            token_expires_at: credentials.map(|c| c.token_expires_at),
+            api_endpoint: api_endpoint.to_string(),
+            stream_endpoint: stream_endpoint.to_string(),
+            has_valid_credentials,
+        }
+    }
+
+    /// Human-readable token expiry relative to `now`, if enrolled
+    pub fn expiry_description(&self, now: DateTime<Utc>) -> Option<String> {
+        self.token_expires_at.map(|at| humanize_expiry(at, now))
+    }
+}
+
+/// Describe an expiry relative to `now`, e.g. "expires in 5h" or "expired 2d ago"
+pub fn humanize_expiry(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = expires_at - now;
+    if delta > chrono::Duration::zero() {
+        format!("expires in {}", humanize_duration(delta))
+    } else {
+        format!("expired {} ago", humanize_duration(-delta))
+    }
+}
+
+fn humanize_duration(d: chrono::Duration) -> String {
+    if d.num_days() >= 2 {
+        format!("{}d", d.num_days())
+    } else if d.num_hours() >= 1 {
+        format!("{}h", d.num_hours())
+    } else if d.num_minutes() >= 1 {
+        format!("{}m", d.num_minutes())
+    } else {
+        format!("{}s", d.num_seconds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_humanize_expiry() {
+        let now = Utc::now();
+        let hours = |h| now + chrono::Duration::hours(h);
+
+        assert_eq!(humanize_expiry(hours(5), now), "expires in 5h");
+        assert_eq!(humanize_expiry(hours(47), now), "expires in 47h");
+        assert_eq!(humanize_expiry(hours(72), now), "expires in 3d");
+        assert_eq!(humanize_expiry(hours(-2), now), "expired 2h ago");
+        assert_eq!(
+            humanize_expiry(now + chrono::Duration::minutes(30), now),
+            "expires in 30m"
+        );
+        assert_eq!(humanize_expiry(now, now), "expired 0s ago");
+    }
+}
@@ -65,6 +65,14 @@ pub enum OximyError {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
+    /// API endpoint could not be reached (DNS, connect or TLS failure)
+    #[error("Oximy endpoint {endpoint} is unreachable: {reason}")]
+    Unreachable { endpoint: String, reason: String },
+
+    /// Server command could not be applied
+    #[error("Command failed: {0}")]
+    Command(String),
+
     /// Connection closed
     #[error("Connection closed")]
     ConnectionClosed,
@@ -79,7 +87,10 @@ impl OximyError {
     pub fn is_network_error(&self) -> bool {
         matches!(
             self,
-            OximyError::Network(_) | OximyError::WebSocket(_) | OximyError::ConnectionClosed
+            OximyError::Network(_)
+                | OximyError::WebSocket(_)
+                | OximyError::Unreachable { .. }
+                | OximyError::ConnectionClosed
         )
     }
 
@@ -89,6 +100,7 @@ impl OximyError {
             self,
             OximyError::Network(_)
                 | OximyError::WebSocket(_)
+                | OximyError::Unreachable { .. }
                 | OximyError::RateLimited(_)
                 | OximyError::Server { .. }
                 | OximyError::ConnectionClosed
@@ -3,15 +3,16 @@
 //! Implements the `ExportPlugin` trait to send events to Oximy Cloud.
 
 use crate::client::CloudClient;
-use crate::error::OximyResult;
+use crate::error::{OximyError, OximyResult};
 use crate::offline_queue::OfflineQueue;
+use crate::types::ConnectionState;
 use async_trait::async_trait;
 use oisp_core::events::OispEvent;
 use oisp_core::plugins::{
     ExportPlugin, Plugin, PluginConfig, PluginError, PluginInfo, PluginResult,
 };
 use std::any::Any;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
@@ -61,23 +62,16 @@ pub struct OximyExporter {
     last_flush: Mutex<Instant>,
 
     // Stats
-    events_exported: AtomicU64,
-    events_failed: AtomicU64,
-    events_queued: AtomicU64,
-    batches_sent: AtomicU64,
+    counters: ExporterCounters,
 }
 
 impl OximyExporter {
     /// Create a new Oximy exporter
     pub fn new(client: Arc<CloudClient>, config: OximyExporterConfig) -> OximyResult<Self> {
         let offline_queue = if config.offline_queue_enabled {
-            let path = config.offline_queue_path.clone().unwrap_or_else(|| {
-                dirs::data_dir()
-                    .unwrap_or_else(|| std::path::PathBuf::from("/var/lib/oisp-sensor"))
-                    .join("offline_queue.db")
-                    .to_string_lossy()
-                    .to_string()
-            });
+            let path = offline_queue_path(config.offline_queue_path.as_deref())
+                .to_string_lossy()
+                .to_string();
 
             Some(OfflineQueue::new(&path, config.offline_queue_max_events)?)
         } else {
@@ -90,10 +84,7 @@ impl OximyExporter {
             buffer: Mutex::new(Vec::new()),
             offline_queue,
             last_flush: Mutex::new(Instant::now()),
-            events_exported: AtomicU64::new(0),
-            events_failed: AtomicU64::new(0),
-            events_queued: AtomicU64::new(0),
-            batches_sent: AtomicU64::new(0),
+            counters: ExporterCounters::default(),
         })
     }
 
@@ -104,12 +95,31 @@ impl OximyExporter {
 
     /// Get export statistics
     pub fn stats(&self) -> ExporterStats {
-        ExporterStats {
-            events_exported: self.events_exported.load(Ordering::Relaxed),
-            events_failed: self.events_failed.load(Ordering::Relaxed),
-            events_queued: self.events_queued.load(Ordering::Relaxed),
-            batches_sent: self.batches_sent.load(Ordering::Relaxed),
-        }
+        self.snapshot_stats()
+    }
+
+    /// Point-in-time copy of the export statistics
+    pub fn snapshot_stats(&self) -> ExporterStats {
+        self.counters.snapshot()
+    }
+
+    /// Zero the counters, returning what they held
+    ///
+    /// The returned stats are the delta since the previous reset, for
+    /// periodic reporting. Each counter is swapped atomically, so an
+    /// increment racing the reset lands in exactly one period.
+    /// `events_queued` is the current queue depth, not a counter, and is
+    /// left as is.
+    pub fn reset_stats(&self) -> ExporterStats {
+        self.counters.reset()
+    }
+
+    /// Send events to the cloud right away, bypassing the buffer
+    ///
+    /// Returns an error unless the cloud acknowledged the batch. On a network
+    /// error the events also go to the offline queue, if enabled.
+    pub async fn send_now(&self, events: Vec<OispEvent>) -> OximyResult<()> {
+        self.send_batch(events).await
     }
 
     /// Check if flush is needed based on time
@@ -124,35 +134,44 @@ impl OximyExporter {
             return Ok(());
         }
 
-        let (device_id, token) = self.client.ensure_authenticated().await?;
         let count = events.len();
 
         debug!("Sending batch of {} events to cloud", count);
 
-        match self
+        let http = self.client.http();
+        let batch = &events;
+        let result = self
             .client
-            .http()
-            .send_events(&device_id, &token, &events)
-            .await
-        {
+            .with_auth(|device_id, token| async move {
+                http.send_events(&device_id, &token, batch).await
+            })
+            .await;
+
+        match result {
             Ok(response) => {
-                self.events_exported
+                self.counters
+                    .set_connection_state(ConnectionState::Connected);
+                self.counters
+                    .events_exported
                     .fetch_add(count as u64, Ordering::Relaxed);
-                self.batches_sent.fetch_add(1, Ordering::Relaxed);
+                self.counters.batches_sent.fetch_add(1, Ordering::Relaxed);
                 debug!(
                     "Batch sent successfully: {} events, batch_id={}",
                     response.received, response.batch_id
                 );
                 Ok(())
             }
+            Err(e @ (OximyError::NotEnrolled | OximyError::TokenExpired)) => Err(e),
             Err(e) if e.is_network_error() => {
+                self.counters.set_connection_state(ConnectionState::Offline);
                 warn!("Network error sending batch, queueing for retry: {}", e);
                 self.queue_for_retry(events).await?;
                 Err(e)
             }
             Err(e) => {
                 error!("Failed to send batch: {}", e);
-                self.events_failed
+                self.counters
+                    .events_failed
                     .fetch_add(count as u64, Ordering::Relaxed);
                 Err(e)
             }
@@ -164,13 +183,15 @@ impl OximyExporter {
         if let Some(queue) = &self.offline_queue {
             let count = events.len();
             queue.enqueue(&events)?;
-            self.events_queued
+            self.counters
+                .events_queued
                 .fetch_add(count as u64, Ordering::Relaxed);
             debug!("Queued {} events for retry", count);
         } else {
             // No offline queue, events are lost
             let count = events.len();
-            self.events_failed
+            self.counters
+                .events_failed
                 .fetch_add(count as u64, Ordering::Relaxed);
             warn!("No offline queue, {} events lost", count);
         }
@@ -201,7 +222,8 @@ impl OximyExporter {
             match self.send_batch(batch.clone()).await {
                 Ok(_) => {
                     total_sent += batch.len();
-                    self.events_queued
+                    self.counters
+                        .events_queued
                         .fetch_sub(batch.len() as u64, Ordering::Relaxed);
                 }
                 Err(e) if e.is_network_error() => {
@@ -357,6 +379,67 @@ pub struct ExporterStats {
 
     /// Total batches sent
     pub batches_sent: u64,
+
+    /// Outcome of the most recent send
+    pub connection_state: ConnectionState,
+}
+
+/// Live export counters behind [`ExporterStats`]
+#[derive(Debug, Default)]
+struct ExporterCounters {
+    events_exported: AtomicU64,
+    events_failed: AtomicU64,
+    events_queued: AtomicU64,
+    batches_sent: AtomicU64,
+    connection_state: AtomicU8,
+}
+
+impl ExporterCounters {
+    fn set_connection_state(&self, state: ConnectionState) {
+        self.connection_state.store(state as u8, Ordering::Relaxed);
+    }
+
+    fn connection_state(&self) -> ConnectionState {
+        match self.connection_state.load(Ordering::Relaxed) {
+            s if s == ConnectionState::Connected as u8 => ConnectionState::Connected,
+            s if s == ConnectionState::Offline as u8 => ConnectionState::Offline,
+            _ => ConnectionState::Unknown,
+        }
+    }
+
+    fn snapshot(&self) -> ExporterStats {
+        ExporterStats {
+            events_exported: self.events_exported.load(Ordering::Relaxed),
+            events_failed: self.events_failed.load(Ordering::Relaxed),
+            events_queued: self.events_queued.load(Ordering::Relaxed),
+            batches_sent: self.batches_sent.load(Ordering::Relaxed),
+            connection_state: self.connection_state(),
+        }
+    }
+
+    fn reset(&self) -> ExporterStats {
+        ExporterStats {
+            events_exported: self.events_exported.swap(0, Ordering::Relaxed),
+            events_failed: self.events_failed.swap(0, Ordering::Relaxed),
+            events_queued: self.events_queued.load(Ordering::Relaxed),
+            batches_sent: self.batches_sent.swap(0, Ordering::Relaxed),
+            connection_state: self.connection_state(),
+        }
+    }
+}
+
+/// Offline queue database at `configured`, or the default location
+pub fn offline_queue_path(configured: Option<&str>) -> std::path::PathBuf {
+    configured
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(default_offline_queue_path)
+}
+
+/// Offline queue database used when `offline_queue_path` is not set
+pub fn default_offline_queue_path() -> std::path::PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("/var/lib/oisp-sensor"))
+        .join("offline_queue.db")
 }
 
 // Helper for data directory
@@ -423,4 +506,162 @@ mod tests {
         assert_eq!(stats.events_queued, 0);
         assert_eq!(stats.batches_sent, 0);
     }
+
+    #[test]
+    fn test_stats_snapshot_and_reset_deltas() {
+        let counters = ExporterCounters::default();
+        counters.events_exported.fetch_add(250, Ordering::Relaxed);
+        counters.batches_sent.fetch_add(3, Ordering::Relaxed);
+        counters.events_failed.fetch_add(4, Ordering::Relaxed);
+        counters.events_queued.fetch_add(40, Ordering::Relaxed);
+
+        // Snapshots don't change the counters
+        let snapshot = counters.snapshot();
+        assert_eq!(snapshot.events_exported, 250);
+        assert_eq!(counters.snapshot().events_exported, 250);
+
+        let delta = counters.reset();
+        assert_eq!(
+            (
+                delta.events_exported,
+                delta.batches_sent,
+                delta.events_failed
+            ),
+            (250, 3, 4)
+        );
+        let after = counters.snapshot();
+        assert_eq!((after.events_exported, after.batches_sent), (0, 0));
+        // Queue depth is a level, not a counter
+        assert_eq!(after.events_queued, 40);
+
+        assert_eq!(after.connection_state, ConnectionState::Unknown);
+        counters.set_connection_state(ConnectionState::Offline);
+        assert_eq!(counters.reset().connection_state, ConnectionState::Offline);
+
+        counters.events_exported.fetch_add(100, Ordering::Relaxed);
+        assert_eq!(counters.reset().events_exported, 100);
+    }
+
+    #[test]
+    fn test_stats_reset_loses_no_increments() {
+        let counters = std::sync::Arc::new(ExporterCounters::default());
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let counters = counters.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        counters.events_exported.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+
+        let mut reported = 0;
+        while writers.iter().any(|w| !w.is_finished()) {
+            reported += counters.reset().events_exported;
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        reported += counters.reset().events_exported;
+        assert_eq!(reported, 40_000);
+    }
+
+    #[tokio::test]
+    async fn test_per_exporter_redaction_from_same_event() {
+        use crate::config::OximyConfig;
+        use crate::types::Credentials;
+        use chrono::Utc;
+        use oisp_core::events::{AiRequestEvent, EventEnvelope};
+        use oisp_core::redaction::{RedactionConfig, RedactionMode};
+        use oisp_export::{JsonlExporter, JsonlExporterConfig, RedactingExporter};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        const SECRET: &str = "sk-abcdefghijklmnopqrstuvwxyz0123";
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/events/batch"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"received": 1, "batch_id": "b"})),
+            )
+            .mount(&server)
+            .await;
+        let client = Arc::new(CloudClient::new(OximyConfig {
+            api_endpoint: server.uri(),
+            ..Default::default()
+        }));
+        client
+            .set_credentials(Credentials {
+                device_id: "dev_123".to_string(),
+                device_token: "tok".to_string(),
+                token_expires_at: Utc::now() + chrono::Duration::days(1),
+                organization_id: "org_123".to_string(),
+                workspace_id: None,
+                api_endpoint: server.uri(),
+                stream_endpoint: "wss://stream.oximy.com".to_string(),
+                created_at: Utc::now(),
+            })
+            .await;
+        let oximy = OximyExporter::new(
+            client,
+            OximyExporterConfig {
+                offline_queue_enabled: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // The action stage ran in full mode for the JSONL file; the cloud
+        // gets safe mode
+        let full = RedactionConfig {
+            mode: RedactionMode::Full,
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("events.jsonl");
+        let jsonl = RedactingExporter::wrap(
+            Box::new(JsonlExporter::new(JsonlExporterConfig {
+                path: output.clone(),
+                append: false,
+                ..Default::default()
+            })),
+            full.clone(),
+            &full,
+        );
+        let oximy = RedactingExporter::wrap(Box::new(oximy), RedactionConfig::default(), &full);
+
+        let event = OispEvent::AiRequest(AiRequestEvent {
+            envelope: EventEnvelope::new("ai.request"),
+            data: serde_json::from_value(serde_json::json!({
+                "request_id": "req-1",
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o"},
+                "messages": [{"role": "user", "content": format!("my key is {}", SECRET)}],
+            }))
+            .unwrap(),
+        });
+        let original = serde_json::to_string(&event).unwrap();
+        for exporter in [&jsonl, &oximy] {
+            exporter.export(&event).await.unwrap();
+            exporter.flush().await.unwrap();
+        }
+
+        // The shared event is untouched
+        assert_eq!(serde_json::to_string(&event).unwrap(), original);
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.contains(SECRET));
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        let content = body["events"][0]["data"]["messages"][0]["content"]
+            .as_str()
+            .unwrap();
+        assert!(!content.contains(SECRET), "{}", content);
+        assert!(content.starts_with("my key is "));
+    }
 }
@@ -5,7 +5,12 @@
 
 use crate::client::CloudClient;
 use crate::error::OximyResult;
-use crate::types::{HeartbeatResponse, SensorStats, SensorStatus, ServerCommand};
+use crate::exporter::{ExporterStats, OximyExporter};
+use crate::log_level::{self, LogLevelControl};
+use crate::types::{
+    HeartbeatResponse, SensorStats, SensorStatus, ServerCommand, StageLatencyStats,
+};
+use oisp_core::metrics::{read_process_metrics_with_prev, ProcessMetrics, SharedMetrics};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -44,6 +49,7 @@ pub struct HeartbeatService {
     config: HeartbeatConfig,
     stats_provider: Arc<dyn StatsProvider>,
     command_handler: Option<CommandHandler>,
+    log_level: Option<LogLevelControl>,
 
     // State
     last_heartbeat: RwLock<Option<Instant>>,
@@ -86,6 +92,111 @@ impl StatsProvider for DefaultStatsProvider {
             policy_version: None,
             memory_mb: 0,
             cpu_percent: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn get_status(&self) -> SensorStatus {
+        SensorStatus::Active
+    }
+}
+
+/// Source of the exporter's queue depth and connection state
+type ExportStatsSource = Box<dyn Fn() -> ExporterStats + Send + Sync>;
+
+/// Stats provider backed by the running pipeline's metrics
+///
+/// Counts and stage latencies come from the pipeline's metrics collector;
+/// offline queue depth and connection state come from the Oximy exporter,
+/// when one is attached.
+pub struct PipelineStatsProvider {
+    metrics: SharedMetrics,
+    export_stats: Option<ExportStatsSource>,
+    policy_version: parking_lot::RwLock<Option<String>>,
+    /// Previous sample of this process, for CPU usage between heartbeats
+    process: parking_lot::Mutex<Option<ProcessMetrics>>,
+}
+
+impl PipelineStatsProvider {
+    pub fn new(metrics: SharedMetrics) -> Self {
+        Self {
+            metrics,
+            export_stats: None,
+            policy_version: parking_lot::RwLock::new(None),
+            process: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Report the queue depth and connection state of an exporter
+    pub fn with_exporter(self, exporter: Arc<OximyExporter>) -> Self {
+        self.with_export_stats(move || exporter.snapshot_stats())
+    }
+
+    /// Report queue depth and connection state from a custom source
+    pub fn with_export_stats(
+        mut self,
+        source: impl Fn() -> ExporterStats + Send + Sync + 'static,
+    ) -> Self {
+        self.export_stats = Some(Box::new(source));
+        self
+    }
+
+    /// Set the policy version reported in heartbeats
+    pub fn set_policy_version(&self, version: Option<String>) {
+        *self.policy_version.write() = version;
+    }
+
+    /// Memory (MB) and CPU (%) of this process
+    fn process_usage(&self) -> (u32, f32) {
+        let mut prev = self.process.lock();
+        match read_process_metrics_with_prev(std::process::id(), prev.take()) {
+            Some(sample) => {
+                let usage = (
+                    (sample.memory_rss_bytes / (1024 * 1024)) as u32,
+                    sample.cpu_percent as f32,
+                );
+                *prev = Some(sample);
+                usage
+            }
+            None => (0, 0.0),
+        }
+    }
+}
+
+impl StatsProvider for PipelineStatsProvider {
+    fn get_stats(&self) -> SensorStats {
+        let pipeline = &self.metrics.pipeline;
+        let export = self
+            .export_stats
+            .as_ref()
+            .map(|source| source())
+            .unwrap_or_default();
+        let (memory_mb, cpu_percent) = self.process_usage();
+
+        SensorStats {
+            sensor_version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: self.metrics.uptime_seconds(),
+            events_captured: self.metrics.events_captured(),
+            events_exported: pipeline.events_exported.load(Ordering::Relaxed),
+            events_queued: export.events_queued,
+            policy_version: self.policy_version.read().clone(),
+            memory_mb,
+            cpu_percent,
+            events_decoded: pipeline.events_processed.load(Ordering::Relaxed),
+            events_dropped: pipeline.events_dropped.load(Ordering::Relaxed),
+            decode_failures: self.metrics.decode_failures(),
+            stage_latency: self
+                .metrics
+                .stages
+                .snapshot()
+                .into_iter()
+                .map(|s| StageLatencyStats {
+                    stage: s.stage.as_str().to_string(),
+                    p50_us: s.p50_us,
+                    p99_us: s.p99_us,
+                })
+                .collect(),
+            connection_state: export.connection_state,
         }
     }
 
@@ -102,6 +213,7 @@ impl HeartbeatService {
             config: HeartbeatConfig::default(),
             stats_provider,
             command_handler: None,
+            log_level: log_level::installed().cloned(),
             last_heartbeat: RwLock::new(None),
             last_response: RwLock::new(None),
             consecutive_failures: AtomicU64::new(0),
@@ -121,6 +233,7 @@ impl HeartbeatService {
             config,
             stats_provider,
             command_handler: None,
+            log_level: log_level::installed().cloned(),
             last_heartbeat: RwLock::new(None),
             last_response: RwLock::new(None),
             consecutive_failures: AtomicU64::new(0),
@@ -134,19 +247,26 @@ impl HeartbeatService {
         self.command_handler = Some(handler);
     }
 
+    /// Set the control `set_log_level` commands are applied to
+    ///
+    /// Defaults to the process-wide one from [`log_level::install`].
+    pub fn set_log_level_control(&mut self, control: LogLevelControl) {
+        self.log_level = Some(control);
+    }
+
     /// Send a single heartbeat
     pub async fn send_heartbeat(&self) -> OximyResult<HeartbeatResponse> {
-        let (device_id, token) = self.client.ensure_authenticated().await?;
-
-        let status = self.stats_provider.get_status();
-        let stats = self.stats_provider.get_stats();
-
-        debug!("Sending heartbeat for device {}", device_id);
-
+        let http = self.client.http();
         let response = self
             .client
-            .http()
-            .heartbeat(&device_id, &token, status, stats)
+            .with_auth(|device_id, token| async move {
+                let status = self.stats_provider.get_status();
+                let stats = self.stats_provider.get_stats();
+
+                debug!("Sending heartbeat for device {}", device_id);
+
+                http.heartbeat(&device_id, &token, status, stats).await
+            })
             .await?;
 
         // Update state
@@ -180,27 +300,38 @@ impl HeartbeatService {
         for cmd in commands {
             info!("Received server command: {:?}", cmd);
 
-            if let Some(ref handler) = self.command_handler {
-                handler(cmd.clone());
-            } else {
-                match cmd {
-                    ServerCommand::RotateToken => {
-                        warn!("Token rotation requested but no handler configured");
-                    }
-                    ServerCommand::FetchPolicies => {
-                        debug!("Policy fetch requested");
-                    }
-                    ServerCommand::Restart => {
-                        warn!("Restart requested - not implemented");
-                    }
-                    ServerCommand::Update { version } => {
-                        info!("Update to version {} requested", version);
-                    }
+            match (cmd, &self.command_handler) {
+                // Applied here, a handler has no access to the subscriber
+                (ServerCommand::SetLogLevel { level, ttl_secs }, _) => {
+                    self.set_log_level(level, *ttl_secs);
+                }
+                (_, Some(handler)) => handler(cmd.clone()),
+                (ServerCommand::RotateToken, None) => {
+                    warn!("Token rotation requested but no handler configured");
+                }
+                (ServerCommand::FetchPolicies, None) => {
+                    debug!("Policy fetch requested");
+                }
+                (ServerCommand::Restart, None) => {
+                    warn!("Restart requested - not implemented");
+                }
+                (ServerCommand::Update { version }, None) => {
+                    info!("Update to version {} requested", version);
                 }
             }
         }
     }
 
+    fn set_log_level(&self, level: &str, ttl_secs: Option<u64>) {
+        let Some(ref control) = self.log_level else {
+            warn!("Log level change requested but logging is not reloadable");
+            return;
+        };
+        if let Err(e) = control.set(level, ttl_secs.map(Duration::from_secs)) {
+            warn!("Failed to set log level: {}", e);
+        }
+    }
+
     /// Start background heartbeat task
     pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
         let interval = self.config.interval;
@@ -283,6 +414,9 @@ pub struct HeartbeatStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ConnectionState;
+    use oisp_core::metrics::PipelineStage;
+    use oisp_core::plugins::RawEventKind;
 
     #[test]
     fn test_heartbeat_config_default() {
@@ -304,6 +438,70 @@ mod tests {
         assert_eq!(status, SensorStatus::Active);
     }
 
+    #[test]
+    fn test_pipeline_stats_provider_reflects_metrics() {
+        let metrics = oisp_core::metrics::create_metrics();
+        metrics.record_raw_event(&RawEventKind::SslWrite, 512);
+        metrics.record_raw_event(&RawEventKind::SslRead, 2048);
+        metrics.record_raw_event(&RawEventKind::ProcessExec, 0);
+        metrics
+            .pipeline
+            .events_processed
+            .fetch_add(2, Ordering::Relaxed);
+        metrics
+            .pipeline
+            .events_exported
+            .fetch_add(1, Ordering::Relaxed);
+        metrics
+            .pipeline
+            .events_dropped
+            .fetch_add(4, Ordering::Relaxed);
+        metrics.record_decode_failure("truncated_body", "openai");
+        metrics
+            .stages
+            .record(PipelineStage::Decode, Duration::from_micros(250));
+
+        let provider = PipelineStatsProvider::new(metrics).with_export_stats(|| ExporterStats {
+            events_queued: 40,
+            connection_state: ConnectionState::Offline,
+            ..Default::default()
+        });
+        provider.set_policy_version(Some("v7".to_string()));
+
+        let stats = provider.get_stats();
+        assert_eq!(stats.events_captured, 3);
+        assert_eq!(stats.events_decoded, 2);
+        assert_eq!(stats.events_exported, 1);
+        assert_eq!(stats.events_dropped, 4);
+        assert_eq!(stats.decode_failures, 1);
+        assert_eq!(stats.events_queued, 40);
+        assert_eq!(stats.connection_state, ConnectionState::Offline);
+        assert_eq!(stats.policy_version.as_deref(), Some("v7"));
+        assert_eq!(
+            stats.stage_latency,
+            vec![StageLatencyStats {
+                stage: "decode".to_string(),
+                p50_us: 250,
+                p99_us: 250,
+            }]
+        );
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["connection_state"], "offline");
+        assert_eq!(json["stage_latency"][0]["p99_us"], 250);
+    }
+
+    #[test]
+    fn test_pipeline_stats_provider_without_exporter() {
+        let provider = PipelineStatsProvider::new(oisp_core::metrics::create_metrics());
+        let stats = provider.get_stats();
+        assert_eq!(stats.events_captured, 0);
+        assert_eq!(stats.events_queued, 0);
+        assert_eq!(stats.connection_state, ConnectionState::Unknown);
+        assert!(stats.stage_latency.is_empty());
+        assert_eq!(provider.get_status(), SensorStatus::Active);
+    }
+
     #[test]
     fn test_heartbeat_stats_default() {
         let stats = HeartbeatStats::default();
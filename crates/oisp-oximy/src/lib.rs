@@ -9,6 +9,7 @@
 //! - **Offline Queue** - Buffer events when disconnected for later retry
 //! - **Policy Sync** - Receive and apply cloud-managed policies
 //! - **Heartbeat/Telemetry** - Report sensor health and stats
+//! - **Remote Log Level** - Adjust log verbosity from the cloud
 //!
 //! ## Quick Start
 //!
@@ -40,30 +41,41 @@
 //! }
 //! ```
 
+pub mod backfill;
 pub mod client;
 pub mod config;
 pub mod enrollment;
 pub mod error;
 pub mod exporter;
 pub mod heartbeat;
+pub mod log_level;
 pub mod offline_queue;
 pub mod policy_sync;
 pub mod types;
 
 // Re-exports for convenience
+pub use backfill::{backfill, BackfillConfig, BackfillReport};
 pub use client::{CloudClient, HttpClient};
 pub use config::OximyConfig;
-pub use enrollment::{enroll_device, CredentialStore, Enrollor, FileCredentialStore};
+pub use enrollment::{
+    enroll_device, humanize_expiry, CredentialStore, EnrollmentState, EnrollmentStatus, Enrollor,
+    FileCredentialStore,
+};
 pub use error::{OximyError, OximyResult};
-pub use exporter::{ExporterStats, OximyExporter, OximyExporterConfig};
+pub use exporter::{
+    default_offline_queue_path, offline_queue_path, ExporterStats, OximyExporter,
+    OximyExporterConfig,
+};
 pub use heartbeat::{
-    DefaultStatsProvider, HeartbeatConfig, HeartbeatService, HeartbeatStats, StatsProvider,
+    DefaultStatsProvider, HeartbeatConfig, HeartbeatService, HeartbeatStats, PipelineStatsProvider,
+    StatsProvider,
 };
+pub use log_level::LogLevelControl;
 pub use offline_queue::{OfflineQueue, QueueStats};
 pub use policy_sync::{CloudPolicy, LocalPolicy, PolicyDocument, PolicySync};
 pub use types::{
-    Credentials, DeviceInfo, HeartbeatResponse, RegistrationResponse, SensorStats, SensorStatus,
-    ServerCommand,
+    ConnectionState, Credentials, DeviceInfo, HeartbeatResponse, RegistrationResponse, SensorStats,
+    SensorStatus, ServerCommand, StageLatencyStats,
 };
 
 /// Crate version
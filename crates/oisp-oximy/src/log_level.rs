@@ -0,0 +1,209 @@
+//! Remote log-level control
+//!
+//! The sensor's `tracing` subscriber filters through a reloadable
+//! [`LevelFilter`]. [`LogLevelControl`] wraps its handle so a
+//! `set_log_level` server command can raise or lower verbosity at runtime,
+//! optionally only for a while: a change with a TTL is reverted to the
+//! level set without one (the configured level at startup) when the TTL
+//! runs out, unless a newer change came in first.
+
+use crate::error::{OximyError, OximyResult};
+use parking_lot::Mutex;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::info;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::reload;
+
+type ReloadFn = dyn Fn(LevelFilter) -> Result<(), reload::Error> + Send + Sync;
+
+struct LevelState {
+    /// Level to revert to when a temporary change expires
+    base: LevelFilter,
+    current: LevelFilter,
+    /// Bumped on every change, so a stale TTL doesn't revert a newer one
+    generation: u64,
+}
+
+/// Runtime control over the subscriber's max level
+#[derive(Clone)]
+pub struct LogLevelControl {
+    reload: Arc<ReloadFn>,
+    state: Arc<Mutex<LevelState>>,
+}
+
+impl LogLevelControl {
+    /// Wrap the handle of a `reload::Layer<LevelFilter, _>`
+    pub fn new<S: 'static>(handle: reload::Handle<LevelFilter, S>) -> Self {
+        let level = handle.clone_current().unwrap_or(LevelFilter::WARN);
+        Self {
+            reload: Arc::new(move |level| handle.reload(level)),
+            state: Arc::new(Mutex::new(LevelState {
+                base: level,
+                current: level,
+                generation: 0,
+            })),
+        }
+    }
+
+    /// Current max level
+    pub fn current(&self) -> LevelFilter {
+        self.state.lock().current
+    }
+
+    /// Set the max level (`trace`, `debug`, `info`, `warn`, `error` or `off`)
+    ///
+    /// With a TTL the previous base level is restored once it expires; this
+    /// spawns a task, so it must be called from within a tokio runtime.
+    pub fn set(&self, level: &str, ttl: Option<Duration>) -> OximyResult<()> {
+        let level: LevelFilter = level
+            .trim()
+            .parse()
+            .map_err(|_| OximyError::Command(format!("invalid log level: {}", level)))?;
+
+        let generation = {
+            let mut state = self.state.lock();
+            (self.reload)(level).map_err(|e| OximyError::Command(e.to_string()))?;
+            state.current = level;
+            state.generation += 1;
+            if ttl.is_none() {
+                state.base = level;
+            }
+            state.generation
+        };
+
+        match ttl {
+            Some(ttl) => {
+                info!("Log level set to {} for {}s", level, ttl.as_secs());
+                let control = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(ttl).await;
+                    control.revert(generation);
+                });
+            }
+            None => info!("Log level set to {}", level),
+        }
+        Ok(())
+    }
+
+    /// Restore the base level if nothing changed since `generation`
+    fn revert(&self, generation: u64) {
+        let mut state = self.state.lock();
+        if state.generation != generation || state.current == state.base {
+            return;
+        }
+        if (self.reload)(state.base).is_ok() {
+            state.current = state.base;
+            drop(state);
+            info!("Log level change expired, restored {}", self.current());
+        }
+    }
+}
+
+static INSTALLED: OnceLock<LogLevelControl> = OnceLock::new();
+
+/// Make `control` the process-wide control picked up by heartbeat services
+///
+/// Returns `false` if one was already installed.
+pub fn install(control: LogLevelControl) -> bool {
+    INSTALLED.set(control).is_ok()
+}
+
+/// The process-wide control, if the subscriber was set up with one
+pub fn installed() -> Option<&'static LogLevelControl> {
+    INSTALLED.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ServerCommand;
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::{Layer, Registry};
+
+    /// Records the level of every event that gets through the filter
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: Subscriber> Layer<S> for Captured {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.lock().push(*event.metadata().level());
+        }
+    }
+
+    impl Captured {
+        fn take(&self) -> Vec<Level> {
+            std::mem::take(&mut *self.0.lock())
+        }
+    }
+
+    fn log_all() {
+        tracing::trace!("trace");
+        tracing::debug!("debug");
+        tracing::info!("info");
+        tracing::warn!("warn");
+    }
+
+    #[tokio::test]
+    async fn test_set_log_level_changes_filtering() {
+        let (filter, handle) = reload::Layer::new(LevelFilter::WARN);
+        let captured = Captured::default();
+        let subscriber = Registry::default().with(filter).with(captured.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let control = LogLevelControl::new(handle);
+
+        log_all();
+        assert_eq!(captured.take(), vec![Level::WARN]);
+
+        let command: ServerCommand =
+            serde_json::from_str(r#"{"type": "set_log_level", "level": "debug"}"#).unwrap();
+        let ServerCommand::SetLogLevel { level, ttl_secs } = command else {
+            panic!("unexpected command: {:?}", command);
+        };
+        control
+            .set(&level, ttl_secs.map(Duration::from_secs))
+            .unwrap();
+        captured.take();
+        log_all();
+        assert_eq!(
+            captured.take(),
+            vec![Level::DEBUG, Level::INFO, Level::WARN]
+        );
+        assert_eq!(control.current(), LevelFilter::DEBUG);
+
+        // A temporary change reverts to the last permanent level
+        control
+            .set("TRACE", Some(Duration::from_millis(50)))
+            .unwrap();
+        captured.take();
+        log_all();
+        assert_eq!(captured.take().len(), 4);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(control.current(), LevelFilter::DEBUG);
+        captured.take();
+        log_all();
+        assert_eq!(
+            captured.take(),
+            vec![Level::DEBUG, Level::INFO, Level::WARN]
+        );
+
+        assert!(control.set("verbose", None).is_err());
+        assert_eq!(control.current(), LevelFilter::DEBUG);
+    }
+
+    #[tokio::test]
+    async fn test_newer_change_outlives_earlier_ttl() {
+        let (filter, handle) = reload::Layer::new(LevelFilter::WARN);
+        let _guard = tracing::subscriber::set_default(Registry::default().with(filter));
+        let control = LogLevelControl::new(handle);
+
+        control
+            .set("debug", Some(Duration::from_millis(50)))
+            .unwrap();
+        control.set("info", Some(Duration::from_secs(60))).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(control.current(), LevelFilter::INFO);
+    }
+}
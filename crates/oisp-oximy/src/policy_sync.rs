@@ -108,21 +108,27 @@ impl PolicySync {
 
     /// Fetch policies from cloud
     pub async fn fetch_policies(&self) -> OximyResult<PolicyDocument> {
-        let (device_id, token) = self.client.ensure_authenticated().await?;
-
         debug!("Fetching policies from cloud");
 
-        let url = format!(
-            "{}/v1/devices/{}/policies",
-            self.client.config().api_endpoint,
-            device_id
-        );
-
-        let response = reqwest::Client::new()
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .timeout(Duration::from_secs(30))
-            .send()
+        let api_endpoint = &self.client.config().api_endpoint;
+        let response = self
+            .client
+            .with_auth(|device_id, token| async move {
+                let url = format!("{}/v1/devices/{}/policies", api_endpoint, device_id);
+
+                let response = reqwest::Client::new()
+                    .get(&url)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .timeout(Duration::from_secs(30))
+                    .send()
+                    .await?;
+
+                if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                    let message = response.text().await.unwrap_or_default();
+                    return Err(OximyError::Auth(message));
+                }
+                Ok(response)
+            })
             .await?;
 
         if !response.status().is_success() {
@@ -223,6 +223,50 @@ pub struct SensorStats {
 
     /// CPU usage percentage
     pub cpu_percent: f32,
+
+    /// Total events decoded from captured traffic
+    #[serde(default)]
+    pub events_decoded: u64,
+
+    /// Raw events dropped because the pipeline buffer was full
+    #[serde(default)]
+    pub events_dropped: u64,
+
+    /// Captured exchanges that failed to decode
+    #[serde(default)]
+    pub decode_failures: u64,
+
+    /// Recent latency of each pipeline stage
+    #[serde(default)]
+    pub stage_latency: Vec<StageLatencyStats>,
+
+    /// Whether the last batch reached the cloud
+    #[serde(default)]
+    pub connection_state: ConnectionState,
+}
+
+/// Latency percentiles of one pipeline stage, in microseconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageLatencyStats {
+    /// Stage name (decode, enrich, action, export)
+    pub stage: String,
+    pub p50_us: u64,
+    pub p99_us: u64,
+}
+
+/// Connectivity to Oximy Cloud, as seen by the exporter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Nothing has been sent yet
+    #[default]
+    Unknown,
+
+    /// The last batch was delivered
+    Connected,
+
+    /// The last batch hit a network error and was queued for retry
+    Offline,
 }
 
 /// Commands from server
@@ -240,6 +284,13 @@ pub enum ServerCommand {
 
     /// Update sensor
     Update { version: String },
+
+    /// Change the log level, reverting after `ttl_secs` if given
+    SetLogLevel {
+        level: String,
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    },
 }
 
 /// API error response
@@ -15,6 +15,9 @@
 #[cfg(windows)]
 mod windows_main;
 
+#[cfg(any(windows, test))]
+mod wire;
+
 #[cfg(windows)]
 fn main() {
     if let Err(e) = windows_main::run() {
@@ -0,0 +1,252 @@
+//! Exporting the MITM CA and trusting it on the system
+//!
+//! Decrypted interception only works once the CA created by
+//! [`CertificateAuthority`] is a trusted root. This module exports the CA as
+//! PEM (browsers, Python, Node and most other tools) and DER `.cer` (the
+//! Windows certificate store), and adds it to or checks it against the Root
+//! store using `certutil`.
+
+use anyhow::{Context, Result};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::tls_mitm::CertificateAuthority;
+
+/// File name of the exported PEM certificate
+const PEM_FILE: &str = "oisp-ca.pem";
+
+/// File name of the exported DER certificate
+const DER_FILE: &str = "oisp-ca.cer";
+
+/// Paths of an exported CA certificate
+#[derive(Debug, Clone)]
+pub struct CaExport {
+    pub pem_path: PathBuf,
+    pub der_path: PathBuf,
+    /// SHA-1 thumbprint, as Windows displays it
+    pub thumbprint: String,
+}
+
+/// Where the CA is trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustStatus {
+    /// In the machine Root store (all users)
+    Machine,
+    /// In the current user's Root store only
+    User,
+    NotTrusted,
+}
+
+/// Why adding the CA to the Root store failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallFailure {
+    /// The machine store needs an elevated prompt
+    AccessDenied,
+    /// The user declined the confirmation dialog
+    Cancelled,
+    Other(String),
+}
+
+impl std::fmt::Display for InstallFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallFailure::AccessDenied => write!(
+                f,
+                "access denied: adding a machine-wide root requires Administrator privileges"
+            ),
+            InstallFailure::Cancelled => write!(f, "cancelled by the user"),
+            InstallFailure::Other(output) => write!(f, "certutil failed: {}", output),
+        }
+    }
+}
+
+/// Decode the first certificate of a PEM document to DER
+pub fn pem_to_der(pem: &str) -> Result<Vec<u8>> {
+    let cert = rustls_pemfile::certs(&mut pem.as_bytes())
+        .next()
+        .context("No certificate found in PEM")?
+        .context("Invalid PEM certificate")?;
+    Ok(cert.to_vec())
+}
+
+/// SHA-1 thumbprint of a DER certificate, uppercase hex without separators
+pub fn thumbprint(der: &[u8]) -> String {
+    Sha1::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Write the CA certificate to `dir` as PEM and DER
+pub fn export_ca(ca: &CertificateAuthority, dir: &Path) -> Result<CaExport> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let pem = ca.ca_cert_pem();
+    let der = pem_to_der(pem)?;
+
+    let pem_path = dir.join(PEM_FILE);
+    let der_path = dir.join(DER_FILE);
+    fs::write(&pem_path, pem).with_context(|| format!("Failed to write {:?}", pem_path))?;
+    fs::write(&der_path, &der).with_context(|| format!("Failed to write {:?}", der_path))?;
+
+    Ok(CaExport {
+        pem_path,
+        der_path,
+        thumbprint: thumbprint(&der),
+    })
+}
+
+/// Whether `certutil -store` output lists a certificate with `thumbprint`
+///
+/// certutil prints hashes as `Cert Hash(sha1): ...`, with or without spaces
+/// between bytes depending on the Windows version.
+pub fn store_listing_contains(output: &str, thumbprint: &str) -> bool {
+    output.lines().any(|line| {
+        let Some((label, hash)) = line.split_once(':') else {
+            return false;
+        };
+        label.trim().eq_ignore_ascii_case("Cert Hash(sha1)")
+            && hash
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .eq_ignore_ascii_case(thumbprint)
+    })
+}
+
+/// Classify the output of a failed `certutil -addstore`
+pub fn classify_install_failure(output: &str) -> InstallFailure {
+    let lower = output.to_ascii_lowercase();
+    if lower.contains("0x80070005") || lower.contains("access is denied") {
+        InstallFailure::AccessDenied
+    } else if lower.contains("0x800704c7") || lower.contains("canceled by the user") {
+        InstallFailure::Cancelled
+    } else {
+        InstallFailure::Other(output.trim().to_string())
+    }
+}
+
+/// Check whether the CA with `thumbprint` is a trusted root
+pub fn check_trust(thumbprint: &str) -> Result<TrustStatus> {
+    if store_listing_contains(&certutil(&["-store", "Root", thumbprint])?.1, thumbprint) {
+        return Ok(TrustStatus::Machine);
+    }
+    if store_listing_contains(
+        &certutil(&["-user", "-store", "Root", thumbprint])?.1,
+        thumbprint,
+    ) {
+        return Ok(TrustStatus::User);
+    }
+    Ok(TrustStatus::NotTrusted)
+}
+
+/// Add an exported CA to the machine Root store
+pub fn install_ca(export: &CaExport) -> std::result::Result<(), InstallFailure> {
+    let der_path = export.der_path.to_string_lossy();
+    match certutil(&["-addstore", "-f", "Root", &der_path]) {
+        Ok((true, _)) => Ok(()),
+        Ok((false, output)) => Err(classify_install_failure(&output)),
+        Err(e) => Err(InstallFailure::Other(e.to_string())),
+    }
+}
+
+/// Run certutil, returning whether it succeeded and its combined output
+fn certutil(args: &[&str]) -> Result<(bool, String)> {
+    let output = Command::new("certutil")
+        .args(args)
+        .output()
+        .context("Failed to run certutil")?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_export_pem_and_der_match() {
+        let ca_dir = temp_dir().join("oisp-test-ca-export");
+        let _ = fs::remove_dir_all(&ca_dir);
+        let ca = CertificateAuthority::new_or_load(&ca_dir).unwrap();
+
+        let export = export_ca(&ca, &ca_dir.join("export")).unwrap();
+        let pem = fs::read_to_string(&export.pem_path).unwrap();
+        let der = fs::read(&export.der_path).unwrap();
+
+        assert!(pem.starts_with("-----BEGIN CERTIFICATE-----"));
+        // DER is an ASN.1 SEQUENCE, and is exactly the PEM body decoded
+        assert_eq!(der[0], 0x30);
+        let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(body)
+                .unwrap(),
+            der
+        );
+        assert_eq!(export.thumbprint, thumbprint(&der));
+        assert_eq!(export.thumbprint.len(), 40);
+
+        let _ = fs::remove_dir_all(&ca_dir);
+    }
+
+    #[test]
+    fn test_thumbprint_format() {
+        assert_eq!(
+            thumbprint(b"abc"),
+            "A9993E364706816ABA3E25717850C26C9CD0D89D"
+        );
+        assert!(pem_to_der("not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_store_listing_parsing() {
+        let thumbprint = "A9993E364706816ABA3E25717850C26C9CD0D89D";
+        let listing = "Root \"Trusted Root Certification Authorities\"\r\n\
+            ================ Certificate 0 ================\r\n\
+            Serial Number: 1a2b\r\n\
+            Issuer: CN=OISP Sensor CA, O=OISP, C=US\r\n\
+            Cert Hash(sha1): a9 99 3e 36 47 06 81 6a ba 3e 25 71 78 50 c2 6c 9c d0 d8 9d\r\n\
+            CertUtil: -store command completed successfully.\r\n";
+        assert!(store_listing_contains(listing, thumbprint));
+        assert!(store_listing_contains(
+            "Cert Hash(sha1): a9993e364706816aba3e25717850c26c9cd0d89d",
+            thumbprint
+        ));
+        assert!(!store_listing_contains(
+            "Cert Hash(sha1): 0000000000000000000000000000000000000000",
+            thumbprint
+        ));
+        // Not found: certutil reports an error rather than listing nothing
+        assert!(!store_listing_contains(
+            "CertUtil: -store command FAILED: 0x80090011 (-2146893807 NTE_NOT_FOUND)",
+            thumbprint
+        ));
+    }
+
+    #[test]
+    fn test_install_failure_classified() {
+        assert_eq!(
+            classify_install_failure(
+                "CertUtil: -addstore command FAILED: 0x80070005 (WIN32: 5 ERROR_ACCESS_DENIED)\r\n\
+                 CertUtil: Access is denied."
+            ),
+            InstallFailure::AccessDenied
+        );
+        assert_eq!(
+            classify_install_failure(
+                "CertUtil: -addstore command FAILED: 0x800704c7 (WIN32: 1223 ERROR_CANCELLED)\r\n\
+                 CertUtil: The operation was canceled by the user."
+            ),
+            InstallFailure::Cancelled
+        );
+        assert!(matches!(
+            classify_install_failure("CertUtil: something else"),
+            InstallFailure::Other(_)
+        ));
+    }
+}
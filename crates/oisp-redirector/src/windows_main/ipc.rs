@@ -1,9 +1,12 @@
 //! Named Pipe IPC client for communicating with oisp-sensor
 //!
 //! The redirector sends captured events to the main sensor process
-//! via Windows Named Pipes. Events are sent as newline-delimited JSON.
+//! via Windows Named Pipes. Each connection starts with a hello offering
+//! binary frames (see [`crate::wire`]); events are sent as frames if the
+//! sensor accepts, and as newline-delimited JSON otherwise.
 
 use anyhow::{Context, Result};
+use oisp_core::wire::Encoding;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
@@ -12,10 +15,16 @@ use tracing::info;
 use tracing::{debug, warn};
 
 use super::connection::ConnectionInfo;
+use crate::wire::SslData;
 
 /// Default named pipe path
 pub const DEFAULT_PIPE_PATH: &str = r"\\.\pipe\oisp-capture";
 
+/// How long to wait for the sensor's reply to the hello before sending
+/// JSON, as to a sensor that predates the handshake
+#[cfg(target_os = "windows")]
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Event sent over IPC
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpcEvent {
@@ -150,6 +159,9 @@ pub struct IpcClient {
     /// Statistics
     stats: Arc<IpcClientStats>,
 
+    /// Encoding the sensor accepted for this connection
+    encoding: Encoding,
+
     /// Pipe handle (Windows only)
     #[cfg(target_os = "windows")]
     pipe_handle: Option<windows::Win32::Foundation::HANDLE>,
@@ -170,6 +182,7 @@ impl IpcClient {
             pipe_path: pipe_path.to_string(),
             connected: Arc::new(AtomicBool::new(false)),
             stats: Arc::new(IpcClientStats::default()),
+            encoding: Encoding::Json,
             pipe_handle: None,
         };
 
@@ -188,6 +201,7 @@ impl IpcClient {
             pipe_path: pipe_path.to_string(),
             connected: Arc::new(AtomicBool::new(false)),
             stats: Arc::new(IpcClientStats::default()),
+            encoding: Encoding::Json,
         })
     }
 
@@ -197,7 +211,7 @@ impl IpcClient {
         use std::ffi::OsStr;
         use std::os::windows::ffi::OsStrExt;
         use windows::core::PCWSTR;
-        use windows::Win32::Foundation::{GENERIC_WRITE, INVALID_HANDLE_VALUE};
+        use windows::Win32::Foundation::{GENERIC_READ, GENERIC_WRITE, INVALID_HANDLE_VALUE};
         use windows::Win32::Storage::FileSystem::{
             CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_NONE, OPEN_EXISTING,
         };
@@ -208,11 +222,11 @@ impl IpcClient {
             .chain(std::iter::once(0))
             .collect();
 
-        // Open the pipe for writing
+        // Open the pipe for writing, and reading the handshake reply
         let handle = unsafe {
             CreateFileW(
                 PCWSTR::from_raw(pipe_path_wide.as_ptr()),
-                GENERIC_WRITE.0,
+                GENERIC_READ.0 | GENERIC_WRITE.0,
                 FILE_SHARE_NONE,
                 None,
                 OPEN_EXISTING,
@@ -226,6 +240,8 @@ impl IpcClient {
                 info!("Connected to named pipe: {}", self.pipe_path);
                 self.pipe_handle = Some(h);
                 self.connected.store(true, Ordering::SeqCst);
+                self.encoding = self.handshake(h);
+                info!("Sending events as {:?}", self.encoding);
                 Ok(())
             }
             Ok(_) => {
@@ -241,8 +257,68 @@ impl IpcClient {
         }
     }
 
+    /// Offer binary frames and read the sensor's choice
+    ///
+    /// Falls back to JSON when the sensor doesn't answer within
+    /// [`HANDSHAKE_TIMEOUT`]; a sensor without the handshake logs the hello
+    /// as a malformed event and reads JSON.
+    #[cfg(target_os = "windows")]
+    fn handshake(&self, handle: windows::Win32::Foundation::HANDLE) -> Encoding {
+        use std::time::Instant;
+        use windows::Win32::Storage::FileSystem::{ReadFile, WriteFile};
+        use windows::Win32::System::Pipes::PeekNamedPipe;
+
+        let hello = crate::wire::hello_line();
+        let mut bytes_written = 0u32;
+        if let Err(e) = unsafe {
+            WriteFile(
+                handle,
+                Some(hello.as_slice()),
+                Some(&mut bytes_written),
+                None,
+            )
+        } {
+            warn!("Failed to send handshake: {}", e);
+            return Encoding::Json;
+        }
+
+        // ReadFile would block forever on a sensor that never replies
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let mut available = 0u32;
+            let peeked =
+                unsafe { PeekNamedPipe(handle, None, 0, None, Some(&mut available), None) };
+            if peeked.is_err() {
+                return Encoding::Json;
+            }
+            if available > 0 {
+                break;
+            }
+            if Instant::now() >= deadline {
+                debug!("No handshake reply from the sensor");
+                return Encoding::Json;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut reply = vec![0u8; 1024];
+        let mut bytes_read = 0u32;
+        match unsafe { ReadFile(handle, Some(&mut reply), Some(&mut bytes_read), None) } {
+            Ok(()) => crate::wire::negotiated_encoding(&reply[..bytes_read as usize]),
+            Err(e) => {
+                warn!("Failed to read handshake reply: {}", e);
+                Encoding::Json
+            }
+        }
+    }
+
     /// Send a connection event
     pub async fn send_connection_event(&mut self, conn: &ConnectionInfo) -> Result<()> {
+        // Binary frames carry capture events only; the sensor ignores
+        // connection and status lines anyway
+        if !self.ensure_connected()? || self.encoding == Encoding::Binary {
+            return Ok(());
+        }
         let event = IpcEvent {
             event_type: "connection".to_string(),
             timestamp_ns: Self::current_time_ns(),
@@ -274,6 +350,27 @@ impl IpcClient {
     ) -> Result<()> {
         use base64::prelude::*;
 
+        if !self.ensure_connected()? {
+            debug!("IPC not connected, event dropped: {:?}", direction);
+            return Ok(());
+        }
+        let timestamp_ns = Self::current_time_ns();
+        if self.encoding == Encoding::Binary {
+            let ssl = SslData {
+                id,
+                direction,
+                pid,
+                remote_host,
+                remote_port,
+                data,
+                process_name,
+                exe_path,
+            };
+            let mut frame = Vec::new();
+            ssl.encode(timestamp_ns, &mut frame);
+            return self.send_bytes(&frame, direction).await;
+        }
+
         let event = IpcEvent {
             event_type: if direction == "read" {
                 "ssl_read"
@@ -281,7 +378,7 @@ impl IpcClient {
                 "ssl_write"
             }
             .to_string(),
-            timestamp_ns: Self::current_time_ns(),
+            timestamp_ns,
             data: IpcEventData::SslData(SslDataEvent {
                 id: id.to_string(),
                 direction: direction.to_string(),
@@ -302,6 +399,9 @@ impl IpcClient {
 
     /// Send a status/heartbeat event
     pub async fn send_status(&mut self, packets: u64, connections: u64) -> Result<()> {
+        if !self.ensure_connected()? || self.encoding == Encoding::Binary {
+            return Ok(());
+        }
         let event = IpcEvent {
             event_type: "status".to_string(),
             timestamp_ns: Self::current_time_ns(),
@@ -315,26 +415,40 @@ impl IpcClient {
         self.send_event(&event).await
     }
 
-    /// Send an event over the pipe (Windows)
-    #[cfg(target_os = "windows")]
+    /// Send an event over the pipe as a JSON line
     async fn send_event(&mut self, event: &IpcEvent) -> Result<()> {
-        use windows::Win32::Storage::FileSystem::WriteFile;
+        // Serialize event to JSON with newline
+        let mut json = serde_json::to_string(event).context("Failed to serialize event")?;
+        json.push('\n');
+
+        self.send_bytes(json.as_bytes(), &event.event_type).await
+    }
 
-        // Try to connect if not connected
+    /// Reconnect if the pipe was lost; false if the sensor isn't there
+    ///
+    /// Runs before an event is encoded, since reconnecting renegotiates
+    /// the encoding.
+    #[cfg(target_os = "windows")]
+    fn ensure_connected(&mut self) -> Result<bool> {
         if !self.connected.load(Ordering::SeqCst) {
             self.try_connect()?;
             if !self.connected.load(Ordering::SeqCst) {
-                debug!("IPC not connected, event dropped: {:?}", event.event_type);
-                return Ok(());
+                return Ok(false);
             }
             self.stats.reconnects.fetch_add(1, Ordering::Relaxed);
         }
+        Ok(true)
+    }
 
-        // Serialize event to JSON with newline
-        let mut json = serde_json::to_string(event).context("Failed to serialize event")?;
-        json.push('\n');
+    #[cfg(not(target_os = "windows"))]
+    fn ensure_connected(&mut self) -> Result<bool> {
+        Ok(true)
+    }
 
-        let bytes = json.as_bytes();
+    /// Write an encoded event to the pipe (Windows)
+    #[cfg(target_os = "windows")]
+    async fn send_bytes(&mut self, bytes: &[u8], what: &str) -> Result<()> {
+        use windows::Win32::Storage::FileSystem::WriteFile;
 
         // Write to pipe
         if let Some(handle) = self.pipe_handle {
@@ -352,7 +466,7 @@ impl IpcClient {
                 }
                 Err(e) => {
                     self.stats.send_errors.fetch_add(1, Ordering::Relaxed);
-                    warn!("Failed to write to pipe: {}", e);
+                    warn!("Failed to write {} to pipe: {}", what, e);
                     // Mark as disconnected for reconnection
                     self.connected.store(false, Ordering::SeqCst);
                     self.pipe_handle = None;
@@ -365,10 +479,10 @@ impl IpcClient {
         }
     }
 
-    /// Send an event over the pipe (non-Windows stub)
+    /// Write an encoded event to the pipe (non-Windows stub)
     #[cfg(not(target_os = "windows"))]
-    async fn send_event(&mut self, event: &IpcEvent) -> Result<()> {
-        debug!("IPC stub - would send event: {:?}", event.event_type);
+    async fn send_bytes(&mut self, bytes: &[u8], what: &str) -> Result<()> {
+        debug!("IPC stub - would send {} bytes: {:?}", bytes.len(), what);
         Ok(())
     }
 
@@ -11,6 +11,7 @@ use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 
 mod ai_filter;
+mod ca_trust;
 mod connection;
 mod ipc;
 mod packet_rewrite;
@@ -24,6 +25,7 @@ use ipc::IpcClient;
 use packet_rewrite::rewrite_ipv4_dst;
 use proxy::TransparentProxy;
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
 use tls_mitm::{get_ca_dir, CertificateAuthority, TlsMitmHandler};
 use windivert_capture::WinDivertCapture;
 
@@ -36,6 +38,9 @@ pub struct RedirectorConfig {
     /// Local proxy port for redirected traffic
     pub proxy_port: u16,
 
+    /// Maximum connections the proxy handles at once
+    pub max_connections: usize,
+
     /// Filter expression for WinDivert (which ports to intercept)
     pub filter_ports: Vec<u16>,
 
@@ -50,6 +55,20 @@ pub struct RedirectorConfig {
 
     /// Whether to log packet details
     pub verbose: bool,
+
+    /// CA management command to run instead of capturing
+    pub ca_command: Option<CaCommand>,
+}
+
+/// One-shot commands for the TLS MITM certificate authority
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaCommand {
+    /// Write the CA certificate to a directory as PEM and DER
+    Export(PathBuf),
+    /// Add the CA to the machine Trusted Root store
+    Install,
+    /// Report whether the CA is already trusted
+    Check,
 }
 
 impl Default for RedirectorConfig {
@@ -57,12 +76,14 @@ impl Default for RedirectorConfig {
         Self {
             pipe_path: r"\\.\pipe\oisp-capture".to_string(),
             proxy_port: 8443,
+            max_connections: proxy::DEFAULT_MAX_CONNECTIONS,
             // Default: HTTPS ports commonly used by AI APIs
             filter_ports: vec![443],
             capture_only: true, // Start with capture-only mode
             tls_mitm: false,    // TLS MITM disabled by default
             ai_filter: true,    // AI filtering enabled by default
             verbose: false,
+            ca_command: None,
         }
     }
 }
@@ -75,6 +96,14 @@ pub fn run() -> Result<()> {
     info!("OISP Redirector starting...");
     info!("Version: {}", env!("CARGO_PKG_VERSION"));
 
+    // Parse command line arguments
+    let config = parse_args()?;
+
+    // CA commands don't capture; installing asks for elevation itself
+    if let Some(command) = &config.ca_command {
+        return run_ca_command(command);
+    }
+
     // Check if running as Administrator (Windows only)
     #[cfg(windows)]
     {
@@ -92,9 +121,6 @@ pub fn run() -> Result<()> {
         return Err(anyhow::anyhow!("Windows required"));
     }
 
-    // Parse command line arguments
-    let config = parse_args()?;
-
     // Create shutdown signal
     let running = Arc::new(AtomicBool::new(true));
     let running_clone = running.clone();
@@ -161,6 +187,10 @@ fn is_elevated() -> Result<bool> {
 /// Parse command line arguments
 fn parse_args() -> Result<RedirectorConfig> {
     let args: Vec<String> = std::env::args().collect();
+    parse_args_from(&args)
+}
+
+fn parse_args_from(args: &[String]) -> Result<RedirectorConfig> {
     let mut config = RedirectorConfig::default();
 
     let mut i = 1;
@@ -191,12 +221,34 @@ fn parse_args() -> Result<RedirectorConfig> {
                     config.proxy_port = args[i].parse().context("Invalid proxy port")?;
                 }
             }
+            "--max-connections" => {
+                i += 1;
+                if i < args.len() {
+                    config.max_connections = args[i].parse().context("Invalid max connections")?;
+                    if config.max_connections == 0 {
+                        return Err(anyhow::anyhow!("--max-connections must be at least 1"));
+                    }
+                }
+            }
             "--pipe" => {
                 i += 1;
                 if i < args.len() {
                     config.pipe_path = args[i].clone();
                 }
             }
+            "--export-ca" => {
+                i += 1;
+                let dir = args
+                    .get(i)
+                    .context("--export-ca requires an output directory")?;
+                config.ca_command = Some(CaCommand::Export(PathBuf::from(dir)));
+            }
+            "--install-ca" => {
+                config.ca_command = Some(CaCommand::Install);
+            }
+            "--check-ca" => {
+                config.ca_command = Some(CaCommand::Check);
+            }
             "--help" | "-h" => {
                 print_help();
                 std::process::exit(0);
@@ -225,9 +277,17 @@ fn print_help() {
     println!("  --no-ai-filter        Disable AI endpoint filtering");
     println!("  -v, --verbose         Enable verbose packet logging");
     println!("  -p, --proxy-port      Local proxy port (default: 8443)");
+    println!("  --max-connections     Max concurrent proxy connections (default: 512)");
     println!("  --pipe                Named pipe path (default: \\\\.\\pipe\\oisp-capture)");
     println!("  -h, --help            Show this help message");
     println!();
+    println!("CA Commands:");
+    println!("  --export-ca <DIR>     Write the CA as oisp-ca.pem and oisp-ca.cer (DER) to DIR");
+    println!(
+        "  --install-ca          Add the CA to the machine Trusted Root store (Administrator)"
+    );
+    println!("  --check-ca            Report whether the CA is trusted");
+    println!();
     println!("AI Filtering:");
     println!("  By default, only traffic to known AI API endpoints is intercepted.");
     println!("  Supported providers: OpenAI, Anthropic, Google, Azure OpenAI, AWS Bedrock,");
@@ -236,8 +296,69 @@ fn print_help() {
     println!("TLS MITM Mode:");
     println!("  When using --tls-mitm, OISP will create a CA certificate that must be");
     println!("  trusted by your system. The CA will be stored in %LOCALAPPDATA%\\OISP\\");
+    println!("  Use --install-ca to trust it, or --export-ca to import it elsewhere.");
     println!();
-    println!("This application requires Administrator privileges.");
+    println!("This application requires Administrator privileges, except for");
+    println!("--export-ca and --check-ca.");
+}
+
+/// Run a CA management command
+fn run_ca_command(command: &CaCommand) -> Result<()> {
+    let ca_dir = get_ca_dir();
+    let ca = CertificateAuthority::new_or_load(&ca_dir)?;
+
+    match command {
+        CaCommand::Export(dir) => {
+            let export = ca_trust::export_ca(&ca, dir)?;
+            println!("Exported OISP CA certificate:");
+            println!("  PEM: {}", export.pem_path.display());
+            println!("  DER: {}", export.der_path.display());
+            println!("  Thumbprint (SHA-1): {}", export.thumbprint);
+        }
+        CaCommand::Check => {
+            let der = ca_trust::pem_to_der(ca.ca_cert_pem())?;
+            let thumbprint = ca_trust::thumbprint(&der);
+            println!("OISP CA thumbprint (SHA-1): {}", thumbprint);
+            match ca_trust::check_trust(&thumbprint)? {
+                ca_trust::TrustStatus::Machine => {
+                    println!("Trusted: machine Trusted Root store (all users)");
+                }
+                ca_trust::TrustStatus::User => {
+                    println!("Trusted: current user's Trusted Root store only");
+                    println!("Services running as other accounts will not trust it.");
+                }
+                ca_trust::TrustStatus::NotTrusted => {
+                    println!("Not trusted. Run --install-ca as Administrator to trust it.");
+                    return Err(anyhow::anyhow!("CA is not trusted"));
+                }
+            }
+        }
+        CaCommand::Install => {
+            let export = ca_trust::export_ca(&ca, &ca_dir)?;
+            println!("Adding the OISP CA to the machine Trusted Root store.");
+            println!("Every program on this machine will then accept certificates it");
+            println!("issues, which lets the redirector decrypt intercepted HTTPS traffic.");
+            println!("Thumbprint (SHA-1): {}", export.thumbprint);
+            println!(
+                "To remove it later: certutil -delstore Root {}",
+                export.thumbprint
+            );
+            match ca_trust::install_ca(&export) {
+                Ok(()) => println!("CA installed."),
+                Err(ca_trust::InstallFailure::AccessDenied) => {
+                    error!("Administrator privileges are required to trust the CA machine-wide.");
+                    error!("Re-run --install-ca from an elevated prompt, or trust it for the");
+                    error!(
+                        "current user only: certutil -user -addstore Root \"{}\"",
+                        export.der_path.display()
+                    );
+                    return Err(anyhow::anyhow!("Administrator privileges required"));
+                }
+                Err(e) => return Err(anyhow::anyhow!("Failed to install CA: {}", e)),
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Run the main capture loop
@@ -247,6 +368,7 @@ async fn run_capture(config: RedirectorConfig, running: Arc<AtomicBool>) -> Resu
     info!("  TLS MITM: {}", config.tls_mitm);
     info!("  AI filter: {}", config.ai_filter);
     info!("  Proxy port: {}", config.proxy_port);
+    info!("  Max connections: {}", config.max_connections);
     info!("  Filter ports: {:?}", config.filter_ports);
     info!("  Pipe path: {}", config.pipe_path);
 
@@ -320,7 +442,8 @@ async fn run_capture(config: RedirectorConfig, running: Arc<AtomicBool>) -> Resu
 
     // Start transparent proxy if not in capture-only mode
     let proxy = if !config.capture_only {
-        let proxy = TransparentProxy::new(config.proxy_port);
+        let proxy =
+            TransparentProxy::new(config.proxy_port).with_max_connections(config.max_connections);
 
         // Set up data callback to send captured data to IPC
         // This will be used in Phase 4 for TLS-terminated traffic
@@ -428,8 +551,9 @@ async fn run_capture(config: RedirectorConfig, running: Arc<AtomicBool>) -> Resu
                         .as_ref()
                         .map(|(p, _)| {
                             format!(
-                                ", proxy: {} conns ({} bytes)",
+                                ", proxy: {} conns, {} rejected ({} bytes)",
                                 p.stats().connections_accepted.load(Ordering::Relaxed),
+                                p.stats().connections_rejected.load(Ordering::Relaxed),
                                 p.stats().bytes_forwarded.load(Ordering::Relaxed)
                             )
                         })
@@ -543,5 +667,33 @@ mod tests {
         assert_eq!(config.proxy_port, 8443);
         assert!(config.capture_only);
         assert_eq!(config.filter_ports, vec![443]);
+        assert_eq!(config.ca_command, None);
+    }
+
+    #[test]
+    fn test_parse_ca_commands() {
+        let args = |list: &[&str]| -> Vec<String> {
+            std::iter::once("oisp-redirector.exe")
+                .chain(list.iter().copied())
+                .map(String::from)
+                .collect()
+        };
+
+        let config = parse_args_from(&args(&["--export-ca", r"C:\certs"])).unwrap();
+        assert_eq!(
+            config.ca_command,
+            Some(CaCommand::Export(PathBuf::from(r"C:\certs")))
+        );
+        assert_eq!(
+            parse_args_from(&args(&["--install-ca"]))
+                .unwrap()
+                .ca_command,
+            Some(CaCommand::Install)
+        );
+        assert_eq!(
+            parse_args_from(&args(&["--check-ca"])).unwrap().ca_command,
+            Some(CaCommand::Check)
+        );
+        assert!(parse_args_from(&args(&["--export-ca"])).is_err());
     }
 }
@@ -5,24 +5,40 @@
 //! 2. Preserves the original destination using SO_ORIGINAL_DST
 //! 3. Forwards traffic to the original destination
 //! 4. Captures plaintext data before TLS (for Phase 4 TLS MITM)
+//!
+//! Concurrent connections are capped; connections beyond the cap are closed
+//! immediately and counted, so a connection flood can't exhaust sockets or
+//! memory.
 
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use tracing::{debug, error, info, warn};
 
 /// Default proxy port
 pub const DEFAULT_PROXY_PORT: u16 = 8443;
 
+/// Default cap on concurrently proxied connections
+pub const DEFAULT_MAX_CONNECTIONS: usize = 512;
+
+/// NAT entries whose connection never reached the proxy are dropped after this
+const NAT_ENTRY_TTL: Duration = Duration::from_secs(60);
+
+/// How often stale NAT entries are swept
+const NAT_CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Proxy statistics
 pub struct ProxyStats {
     pub connections_accepted: AtomicU64,
     pub connections_active: AtomicU64,
+    /// Connections closed on accept because the proxy was at its limit
+    pub connections_rejected: AtomicU64,
     pub bytes_forwarded: AtomicU64,
     pub errors: AtomicU64,
 }
@@ -32,12 +48,45 @@ impl Default for ProxyStats {
         Self {
             connections_accepted: AtomicU64::new(0),
             connections_active: AtomicU64::new(0),
+            connections_rejected: AtomicU64::new(0),
             bytes_forwarded: AtomicU64::new(0),
             errors: AtomicU64::new(0),
         }
     }
 }
 
+/// Bounds the number of connections proxied at once
+pub struct ConnectionLimiter {
+    permits: Arc<Semaphore>,
+    max_connections: usize,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_connections: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_connections)),
+            max_connections,
+        }
+    }
+
+    /// Admit a connection, or count it as rejected when at the limit
+    ///
+    /// The connection holds its slot until the permit is dropped.
+    pub fn try_admit(&self, stats: &ProxyStats) -> Option<OwnedSemaphorePermit> {
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                stats.connections_rejected.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+}
+
 /// Original destination info for a redirected connection
 #[derive(Debug, Clone)]
 pub struct OriginalDestination {
@@ -51,9 +100,9 @@ pub struct OriginalDestination {
 
 /// Connection NAT table - maps local proxy connections to original destinations
 pub struct NatTable {
-    /// Maps (src_port) -> original destination
+    /// Maps (src_port) -> (original destination, when it was added)
     /// When WinDivert redirects a connection to our proxy, we store the original dest
-    entries: RwLock<HashMap<u16, OriginalDestination>>,
+    entries: RwLock<HashMap<u16, (OriginalDestination, Instant)>>,
 }
 
 impl NatTable {
@@ -70,22 +119,38 @@ impl NatTable {
             "NAT: Adding entry for port {} -> {:?}",
             src_port, original.dest_addr
         );
-        entries.insert(src_port, original);
+        entries.insert(src_port, (original, Instant::now()));
     }
 
     /// Get and remove a NAT entry
     pub async fn get_entry(&self, src_port: u16) -> Option<OriginalDestination> {
         let mut entries = self.entries.write().await;
-        entries.remove(&src_port)
+        entries.remove(&src_port).map(|(original, _)| original)
     }
 
     /// Cleanup old entries (called periodically)
+    ///
+    /// An entry is normally removed when its connection is accepted; one
+    /// whose connection never arrives (e.g. the SYN was dropped) would
+    /// otherwise stay forever.
     pub async fn cleanup(&self) {
-        // For now, just log the count
-        let entries = self.entries.read().await;
-        if !entries.is_empty() {
-            debug!("NAT table has {} entries", entries.len());
+        self.remove_older_than(NAT_ENTRY_TTL).await;
+    }
+
+    /// Remove entries added more than `ttl` ago, returning how many
+    async fn remove_older_than(&self, ttl: Duration) -> usize {
+        let mut entries = self.entries.write().await;
+        let before = entries.len();
+        entries.retain(|_, (_, added)| added.elapsed() < ttl);
+        let removed = before - entries.len();
+        if removed > 0 {
+            debug!(
+                "NAT: Removed {} stale entries, {} remain",
+                removed,
+                entries.len()
+            );
         }
+        removed
     }
 }
 
@@ -108,6 +173,8 @@ pub struct TransparentProxy {
     stats: Arc<ProxyStats>,
     /// NAT table
     nat_table: Arc<NatTable>,
+    /// Cap on concurrent connections
+    limiter: Arc<ConnectionLimiter>,
     /// Data capture callback (called with data, is_outbound, original_dest)
     data_callback: Option<DataCallback>,
 }
@@ -120,10 +187,17 @@ impl TransparentProxy {
             running: Arc::new(AtomicBool::new(false)),
             stats: Arc::new(ProxyStats::default()),
             nat_table: Arc::new(NatTable::new()),
+            limiter: Arc::new(ConnectionLimiter::new(DEFAULT_MAX_CONNECTIONS)),
             data_callback: None,
         }
     }
 
+    /// Set the maximum number of concurrently proxied connections
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.limiter = Arc::new(ConnectionLimiter::new(max_connections));
+        self
+    }
+
     /// Set the data capture callback
     pub fn set_data_callback(&mut self, callback: DataCallback) {
         self.data_callback = Some(callback);
@@ -161,19 +235,29 @@ impl TransparentProxy {
             .context(format!("Failed to bind proxy to {}", addr))?;
 
         self.running.store(true, Ordering::SeqCst);
-        info!("Transparent proxy listening on {}", addr);
+        info!(
+            "Transparent proxy listening on {} (max {} connections)",
+            addr,
+            self.limiter.max_connections()
+        );
 
         let running = self.running.clone();
         let stats = self.stats.clone();
         let nat_table = self.nat_table.clone();
+        let limiter = self.limiter.clone();
         let data_callback = self.data_callback.clone();
 
         let handle = tokio::spawn(async move {
+            let mut nat_cleanup = tokio::time::interval(NAT_CLEANUP_INTERVAL);
             while running.load(Ordering::SeqCst) {
                 tokio::select! {
                     result = listener.accept() => {
                         match result {
                             Ok((stream, peer_addr)) => {
+                                let Some(permit) = limiter.try_admit(&stats) else {
+                                    reject_connection(stream, peer_addr, &nat_table).await;
+                                    continue;
+                                };
                                 stats.connections_accepted.fetch_add(1, Ordering::Relaxed);
                                 stats.connections_active.fetch_add(1, Ordering::Relaxed);
 
@@ -193,6 +277,7 @@ impl TransparentProxy {
                                         stats.errors.fetch_add(1, Ordering::Relaxed);
                                     }
                                     stats.connections_active.fetch_sub(1, Ordering::Relaxed);
+                                    drop(permit);
                                 });
                             }
                             Err(e) => {
@@ -201,6 +286,9 @@ impl TransparentProxy {
                             }
                         }
                     }
+                    _ = nat_cleanup.tick() => {
+                        nat_table.cleanup().await;
+                    }
                     _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
                         // Check running flag
                     }
@@ -213,6 +301,19 @@ impl TransparentProxy {
     }
 }
 
+/// Close a connection the proxy has no room for
+///
+/// Its NAT entry is removed too, since nothing else will claim it.
+async fn reject_connection(stream: TcpStream, peer_addr: SocketAddr, nat_table: &NatTable) {
+    let original = nat_table.get_entry(peer_addr.port()).await;
+    warn!(
+        "Connection limit reached, rejecting {} to {:?}",
+        peer_addr,
+        original.map(|o| o.dest_addr)
+    );
+    drop(stream);
+}
+
 /// Handle a single proxied connection
 async fn handle_connection(
     mut client: TcpStream,
@@ -328,4 +429,65 @@ mod tests {
         // Entry should be removed after get
         assert!(nat.get_entry(12345).await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_nat_cleanup_removes_stale_entries() {
+        let nat = NatTable::new();
+        let original = OriginalDestination {
+            dest_addr: "93.184.216.34:443".parse().unwrap(),
+            pid: None,
+            process_name: None,
+        };
+        nat.add_entry(1, original.clone()).await;
+        nat.add_entry(2, original).await;
+
+        assert_eq!(nat.remove_older_than(Duration::from_secs(60)).await, 0);
+        assert_eq!(nat.remove_older_than(Duration::ZERO).await, 2);
+        assert!(nat.get_entry(1).await.is_none());
+    }
+
+    #[test]
+    fn test_limiter_bounds_connections_and_counts_rejections() {
+        let stats = ProxyStats::default();
+        let limiter = ConnectionLimiter::new(2);
+
+        let first = limiter.try_admit(&stats).unwrap();
+        let _second = limiter.try_admit(&stats).unwrap();
+        assert!(limiter.try_admit(&stats).is_none());
+        assert!(limiter.try_admit(&stats).is_none());
+        assert_eq!(stats.connections_rejected.load(Ordering::Relaxed), 2);
+
+        // A closed connection frees its slot
+        drop(first);
+        assert!(limiter.try_admit(&stats).is_some());
+        assert_eq!(stats.connections_rejected.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_connection_clears_nat_entry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, peer_addr) = listener.accept().await.unwrap();
+
+        let nat = NatTable::new();
+        nat.add_entry(
+            peer_addr.port(),
+            OriginalDestination {
+                dest_addr: "93.184.216.34:443".parse().unwrap(),
+                pid: None,
+                process_name: None,
+            },
+        )
+        .await;
+
+        reject_connection(stream, peer_addr, &nat).await;
+        assert!(nat.get_entry(peer_addr.port()).await.is_none());
+
+        // The client sees the connection closed
+        let mut client = client;
+        let mut buf = [0u8; 1];
+        assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+    }
 }
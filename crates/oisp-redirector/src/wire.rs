@@ -0,0 +1,168 @@
+//! Event encoding for the sensor's named pipe
+//!
+//! The redirector opens each pipe connection with a hello offering binary
+//! frames (see `oisp_core::wire`). If the sensor picks binary, SSL data is
+//! sent as frames; otherwise, or if the sensor never answers, it is sent as
+//! the JSON lines the pipe server has always read.
+
+use oisp_core::plugins::{RawCaptureEvent, RawEventKind, RawEventMetadata};
+use oisp_core::wire::{encode_event, Encoding, Hello, HelloAck};
+
+/// Hello line that starts every pipe connection
+pub fn hello_line() -> Vec<u8> {
+    Hello::new(vec![Encoding::Binary, Encoding::Json]).to_line()
+}
+
+/// Encoding the sensor picked in its reply to the hello; JSON for anything
+/// that isn't an ack
+pub fn negotiated_encoding(reply: &[u8]) -> Encoding {
+    let reply = String::from_utf8_lossy(reply);
+    let line = reply.lines().next().unwrap_or_default();
+    HelloAck::parse(line)
+        .map(|ack| ack.encoding)
+        .unwrap_or(Encoding::Json)
+}
+
+/// Decrypted traffic of one connection, as the proxy reports it
+pub struct SslData<'a> {
+    pub id: &'a str,
+    /// "read" or "write"
+    pub direction: &'a str,
+    pub pid: u32,
+    pub remote_host: &'a str,
+    pub remote_port: u16,
+    pub data: &'a [u8],
+    pub process_name: &'a str,
+    pub exe_path: &'a str,
+}
+
+impl SslData<'_> {
+    /// The event the sensor builds from this data's JSON line
+    pub fn to_raw_event(&self, timestamp_ns: u64) -> RawCaptureEvent {
+        RawCaptureEvent {
+            id: self.id.to_string(),
+            timestamp_ns,
+            kind: if self.direction == "read" {
+                RawEventKind::SslRead
+            } else {
+                RawEventKind::SslWrite
+            },
+            pid: self.pid,
+            tid: None,
+            data: self.data.to_vec(),
+            metadata: RawEventMetadata {
+                comm: Some(self.process_name.to_string()),
+                exe: Some(self.exe_path.to_string()),
+                uid: Some(0), // Windows doesn't have Unix UIDs
+                remote_addr: Some(self.remote_host.to_string()),
+                remote_port: Some(self.remote_port),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Append this data as a binary frame
+    pub fn encode(&self, timestamp_ns: u64, out: &mut Vec<u8>) {
+        encode_event(&self.to_raw_event(timestamp_ns), out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::wire::{WireDecoder, WireMessage};
+
+    fn ssl_data() -> SslData<'static> {
+        SslData {
+            id: "conn-1-0",
+            direction: "write",
+            pid: 4242,
+            remote_host: "api.openai.com",
+            remote_port: 443,
+            data: b"POST /v1/chat/completions HTTP/1.1\r\n\r\n\x00\xff",
+            process_name: "python.exe",
+            exe_path: r"C:\Python311\python.exe",
+        }
+    }
+
+    #[test]
+    fn test_frames_decode_in_pipe_server() {
+        // What the redirector writes, fed to the pipe server's decoder
+        let mut decoder = WireDecoder::new();
+        decoder.feed(&hello_line());
+        let Some(WireMessage::Hello(ack)) = decoder.next_message().unwrap() else {
+            panic!("expected hello");
+        };
+        assert_eq!(negotiated_encoding(&ack.to_line()), Encoding::Binary);
+
+        let mut bytes = Vec::new();
+        ssl_data().encode(1_703_680_000_000_000_000, &mut bytes);
+        let read = SslData {
+            direction: "read",
+            data: b"HTTP/1.1 200 OK\r\n\r\n",
+            ..ssl_data()
+        };
+        read.encode(1_703_680_000_500_000_000, &mut bytes);
+        decoder.feed(&bytes);
+
+        let expected = [
+            ssl_data().to_raw_event(1_703_680_000_000_000_000),
+            read.to_raw_event(1_703_680_000_500_000_000),
+        ];
+        for expected in expected {
+            let Some(WireMessage::Event(event)) = decoder.next_message().unwrap() else {
+                panic!("expected event");
+            };
+            assert_eq!(format!("{:?}", event), format!("{:?}", expected));
+        }
+        assert!(decoder.next_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_frame_matches_json_line() {
+        use base64::prelude::*;
+        use oisp_capture_windows::pipe_server::RedirectorEvent;
+
+        // The JSON line the IPC client sends for the same data
+        let data = ssl_data();
+        let line = serde_json::json!({
+            "type": "ssl_write",
+            "timestamp_ns": 1_703_680_000_000_000_000u64,
+            "data": {
+                "id": data.id,
+                "direction": data.direction,
+                "pid": data.pid,
+                "remote_host": data.remote_host,
+                "remote_port": data.remote_port,
+                "data": BASE64_STANDARD.encode(data.data),
+                "metadata": {
+                    "comm": data.process_name,
+                    "exe": data.exe_path,
+                    "uid": 0
+                }
+            }
+        });
+        let from_json = serde_json::from_value::<RedirectorEvent>(line)
+            .unwrap()
+            .into_raw_event()
+            .unwrap();
+
+        let mut frame = Vec::new();
+        data.encode(1_703_680_000_000_000_000, &mut frame);
+        let from_frame = oisp_core::wire::decode_event(&frame[4..]).unwrap();
+        assert_eq!(format!("{:?}", from_frame), format!("{:?}", from_json));
+    }
+
+    #[test]
+    fn test_falls_back_to_json_without_ack() {
+        assert_eq!(negotiated_encoding(b""), Encoding::Json);
+        assert_eq!(
+            negotiated_encoding(b"{\"oisp_hello_ack\":{\"version\":1,\"encoding\":\"json\"}}\n"),
+            Encoding::Json
+        );
+        assert_eq!(
+            negotiated_encoding(b"{\"oisp_hello_ack\":{\"version\":1,\"encoding\":\"binary\"}}\n"),
+            Encoding::Binary
+        );
+    }
+}
@@ -2,25 +2,52 @@
 //!
 //! Zero-instrumentation sensor for AI activity monitoring and control.
 
-use clap::{Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use oisp_capture::{TestGenerator, TestGeneratorConfig};
 #[cfg(target_os = "linux")]
 use oisp_capture_ebpf::{EbpfCapture, EbpfCaptureConfig};
 #[cfg(target_os = "macos")]
 use oisp_capture_macos::{MacOSCapture, MacOSCaptureConfig};
-use oisp_core::config::{ConfigLoader, SensorConfig};
-use oisp_core::enrichers::{AppEnricher, HostEnricher, ProcessTreeEnricher};
-use oisp_core::pipeline::{Pipeline, PipelineConfig};
+use oisp_core::config::{
+    BudgetSettings, ConfigLoader, CorrelationSettings, DeadLetterConfig, ExportSettings,
+    KubernetesSettings, LatencySettings, ProviderSettings, RedactionSettings, SensorConfig,
+    SharedConfig,
+};
+use oisp_core::dead_letter::DeadLetterSink;
+use oisp_core::enrichers::{
+    AppEnricher, HostEnricher, KubernetesEnricher, PodSource, ProcessTreeEnricher,
+    ResourceEnricher, SourceEnricher,
+};
+use oisp_core::events::SchemaTransform;
+use oisp_core::monotonic::TsMonoSource;
+use oisp_core::pipeline::{ChannelPolicy, EventIds, Pipeline, PipelineConfig, SchemaValidation};
+use oisp_core::plugins::ExportPlugin;
+use oisp_core::redaction::{least_redacting, RedactionConfig};
 use oisp_core::replay::{EventReplay, ReplayConfig};
-use oisp_core::RedactionPlugin;
+use oisp_core::watchdog::WatchdogConfig;
+use oisp_core::{
+    AgentStepPlugin, BudgetAlertPlugin, DynamicProviderRegistry, EventLimitPlugin, LatencyPlugin,
+    RedactionPlugin, SessionPlugin,
+};
 use oisp_core::{AppRegistry, LiveRegistry};
-use oisp_decode::{HttpDecoder, SystemDecoder};
+use oisp_decode::ai::ContentLimits;
+use oisp_decode::{FilePathFilter, HttpDecoder, SystemDecoder};
 use oisp_export::jsonl::{JsonlExporter, JsonlExporterConfig};
 use oisp_export::websocket::{WebSocketExporter, WebSocketExporterConfig};
+use oisp_export::{BoundedExporter, RedactingExporter};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{error, info, warn, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{debug, error, info, warn, Level};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+mod diagnostics;
+mod style;
+#[cfg(target_os = "linux")]
+mod tls_detect;
 
 #[derive(Parser)]
 #[command(name = "oisp-sensor")]
@@ -40,6 +67,14 @@ struct Cli {
     #[arg(short, long, global = true, env = "OISP_CONFIG")]
     config: Option<PathBuf>,
 
+    /// Emit logs as JSON lines, including pipeline span fields (event_id, trace_id)
+    #[arg(long, global = true)]
+    log_json: bool,
+
+    /// Never color output (also set by NO_COLOR; color is only used on a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -47,60 +82,7 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Record AI activity (requires elevated privileges on some platforms)
-    Record {
-        /// Output file for JSONL events
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        /// Start web UI
-        #[arg(long, default_value = "true")]
-        web: bool,
-
-        /// Web UI port
-        #[arg(long, default_value = "7777")]
-        port: u16,
-
-        /// Start TUI
-        #[arg(long)]
-        tui: bool,
-
-        /// Filter by process name
-        #[arg(short, long)]
-        process: Option<Vec<String>>,
-
-        /// Filter by PID
-        #[arg(long)]
-        pid: Option<Vec<u32>>,
-
-        /// Redaction mode (safe, full, minimal)
-        #[arg(long, default_value = "safe")]
-        redaction: String,
-
-        /// Disable SSL/TLS capture
-        #[arg(long)]
-        no_ssl: bool,
-
-        /// Disable process capture
-        #[arg(long)]
-        no_process: bool,
-
-        /// Disable file capture
-        #[arg(long)]
-        no_file: bool,
-
-        /// Disable network capture
-        #[arg(long)]
-        no_network: bool,
-
-        /// Path to eBPF bytecode file (Linux only, auto-detected if not specified)
-        #[arg(long)]
-        ebpf_path: Option<PathBuf>,
-
-        /// Path to libssl.so OR binary with embedded SSL (e.g., node) for SSL interception
-        /// (auto-detected if not specified)
-        #[arg(long)]
-        libssl_path: Option<PathBuf>,
-    },
+    Record(RecordArgs),
 
     /// Show captured events
     Show {
@@ -119,6 +101,21 @@ enum Commands {
         /// Number of events to show
         #[arg(short, long, default_value = "50")]
         num: usize,
+
+        /// table (compact columns), pretty (indented JSON), json (one JSON
+        /// array on one line), or ndjson (one JSON object per line)
+        #[arg(long, default_value = "pretty", value_parser = ["table", "pretty", "json", "ndjson"])]
+        output_format: String,
+
+        /// Instead of the events, show what this redaction mode (safe, full,
+        /// minimal) would remove from each AI event's content
+        #[arg(long, value_name = "MODE", value_parser = ["safe", "full", "minimal"])]
+        redact_preview: Option<String>,
+
+        /// Extra regex to redact in the preview, on top of the config
+        /// file's custom patterns (repeatable)
+        #[arg(long, value_name = "REGEX", requires = "redact_preview")]
+        redact_pattern: Vec<String>,
     },
 
     /// Analyze recorded events
@@ -130,18 +127,69 @@ enum Commands {
         /// Analysis type (inventory, traces, costs)
         #[arg(short = 't', long, default_value = "inventory")]
         analysis_type: String,
+
+        /// Print structured JSON instead of a table (inventory only)
+        #[arg(long)]
+        json: bool,
+
+        /// Write structured JSON to this file (inventory only)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Show sensor status and capabilities
     Status,
 
     /// Check system compatibility and requirements
-    Check,
+    Check {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Manage the sensor daemon
     #[command(subcommand)]
     Daemon(DaemonCommands),
 
+    /// Inspect the Oximy Cloud connector
+    #[command(subcommand)]
+    Oximy(OximyCommands),
+
+    /// Inspect the AI providers and models this sensor detects
+    #[command(subcommand)]
+    Providers(ProvidersCommands),
+
+    /// Collect diagnostics for a support request
+    #[command(subcommand)]
+    Diagnostics(DiagnosticsCommands),
+
+    /// Delete local sensor state (refuses while the daemon is running)
+    ///
+    /// Paths come from the config file and environment. The config file,
+    /// policies and the cached spec bundle are kept.
+    Reset {
+        /// Delete the stored Oximy device credentials
+        #[arg(long)]
+        credentials: bool,
+
+        /// Delete the Oximy offline event queue
+        #[arg(long)]
+        queue: bool,
+
+        /// Delete the JSONL event output, the dead-letter file, their web
+        /// history cursors and backfill checkpoints, and daemon logs
+        #[arg(long)]
+        logs: bool,
+
+        /// Delete all of the above
+        #[arg(long)]
+        all: bool,
+
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
     /// Self-test sensor capabilities
     Test,
 
@@ -200,6 +248,9 @@ enum Commands {
         /// Redaction mode (safe, full, minimal)
         #[arg(long, default_value = "full")]
         redaction: String,
+
+        #[command(flatten)]
+        limits: RunLimits,
     },
 
     /// Replay recorded events from a JSONL file (for development without live capture)
@@ -227,6 +278,14 @@ enum Commands {
         /// Start TUI instead of web
         #[arg(long)]
         tui: bool,
+
+        /// Re-export events to a destination instead of serving the UI
+        #[arg(long, value_parser = ["jsonl", "oximy", "otlp", "kafka"])]
+        to: Option<String>,
+
+        /// Output file for --to jsonl (defaults to export.jsonl.path)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
@@ -269,12 +328,155 @@ enum DaemonCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum DiagnosticsCommands {
+    /// Write a zip of sanitized config, system check, recent logs, capture
+    /// stats, spec bundle version and platform info
+    ///
+    /// Credentials are redacted and captured event content is never included.
+    Bundle {
+        /// Zip file to write
+        #[arg(short, long, default_value = "oisp-diagnostics.zip")]
+        output: PathBuf,
+
+        /// Journal lines to include when the sensor runs under systemd
+        #[arg(long, default_value = "2000")]
+        log_lines: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum OximyCommands {
+    /// Show enrollment status (never prints the device token)
+    Status {
+        /// Also check that the API endpoint is reachable
+        #[arg(long)]
+        ping: bool,
+    },
+
+    /// Send the events of a JSONL capture to Oximy Cloud
+    ///
+    /// Progress is checkpointed after every delivered batch, so re-running
+    /// the same command continues where the last run stopped.
+    Backfill {
+        /// JSONL file of events, e.g. from the jsonl exporter
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Events per batch
+        #[arg(long, default_value = "100")]
+        batch_size: usize,
+
+        /// Maximum events sent per second (0 for no limit)
+        #[arg(long, default_value = "500")]
+        rate: u32,
+
+        /// Checkpoint file [default: <input>.checkpoint]
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+
+        /// Ignore the checkpoint and send the whole file again
+        #[arg(long)]
+        restart: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProvidersCommands {
+    /// List providers from the spec bundle with their detection domains
+    List {
+        /// Only providers whose ID, name or a domain contains this text
+        /// (case-insensitive), or models containing it
+        filter: Option<String>,
+
+        /// Also list each provider's models
+        #[arg(long)]
+        models: bool,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Arguments of `oisp-sensor record`
+#[derive(clap::Args)]
+struct RecordArgs {
+    /// Output file for JSONL events
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Start web UI
+    #[arg(long, default_value = "true")]
+    web: bool,
+
+    /// Web UI port
+    #[arg(long, default_value = "7777")]
+    port: u16,
+
+    /// Start TUI
+    #[arg(long)]
+    tui: bool,
+
+    /// Filter by process name
+    #[arg(short, long)]
+    process: Option<Vec<String>>,
+
+    /// Filter by PID
+    #[arg(long)]
+    pid: Option<Vec<u32>>,
+
+    /// Redaction mode (safe, full, minimal)
+    #[arg(long, default_value = "safe")]
+    redaction: String,
+
+    /// Disable SSL/TLS capture
+    #[arg(long)]
+    no_ssl: bool,
+
+    /// Disable process capture
+    #[arg(long)]
+    no_process: bool,
+
+    /// Disable file capture
+    #[arg(long)]
+    no_file: bool,
+
+    /// Disable network capture
+    #[arg(long)]
+    no_network: bool,
+
+    /// Path to eBPF bytecode file (Linux only, auto-detected if not specified)
+    #[arg(long)]
+    ebpf_path: Option<PathBuf>,
+
+    /// Path to libssl.so OR binary with embedded SSL (e.g., node) for SSL interception
+    /// (auto-detected if not specified)
+    #[arg(long)]
+    libssl_path: Option<PathBuf>,
+
+    /// Report responses on connections opened before the sensor started,
+    /// whose requests were never captured (low confidence)
+    #[arg(long)]
+    attach_existing: bool,
+
+    #[command(flatten)]
+    limits: RunLimits,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    // Matches are kept to tell flags the user gave from clap defaults
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    style::init(cli.no_color);
 
     // Load configuration file
     let sensor_config = load_config(cli.config.clone());
+    if sensor_config.sensor.air_gapped {
+        oisp_core::set_air_gapped(true);
+    }
 
     // Setup logging - CLI verbose flag takes precedence, then config, then default
     let log_level = if cli.verbose > 0 {
@@ -294,65 +496,96 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(log_level)
+    // The level is reloadable so Oximy Cloud can change it at runtime
+    let (level_filter, level_handle) = reload::Layer::new(LevelFilter::from_level(log_level));
+    let registry = Registry::default().with(level_filter);
+
+    // Logs go to stderr so they never mix with command output such as JSON
+    let fmt_layer = fmt::layer()
+        .with_writer(std::io::stderr)
         .with_target(false)
         .with_thread_ids(false)
         .with_file(false)
-        .with_line_number(false)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber)?;
+        .with_line_number(false);
+
+    if cli.log_json {
+        let fmt_layer = fmt_layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true);
+        tracing::subscriber::set_global_default(registry.with(fmt_layer))?;
+    } else {
+        tracing::subscriber::set_global_default(registry.with(fmt_layer))?;
+    }
+    oisp_oximy::log_level::install(oisp_oximy::LogLevelControl::new(level_handle));
 
     match cli.command {
-        Commands::Record {
-            output,
-            web,
-            port,
-            tui,
-            process,
-            pid,
-            redaction,
-            no_ssl,
-            no_process,
-            no_file,
-            no_network,
-            ebpf_path,
-            libssl_path,
-        } => {
+        Commands::Record(args) => {
             // Merge CLI args with config file settings
             // CLI args take precedence over config file
-            let merged_config = merge_record_config(
-                &sensor_config,
-                output,
-                web,
-                port,
-                tui,
-                process,
-                pid,
-                redaction,
-                no_ssl,
-                no_process,
-                no_file,
-                no_network,
-                ebpf_path,
-                libssl_path,
+            let record_matches = matches
+                .subcommand_matches("record")
+                .expect("record subcommand matched");
+            let merged_config = merge_record_config(&sensor_config, args, record_matches);
+            // Kept for SIGHUP reloads
+            let shared_config = SharedConfig::new(sensor_config.clone());
+            shared_config.set_config_path(
+                ConfigLoader::new()
+                    .with_cli_path(cli.config.clone())
+                    .find_config_file(),
             );
-            record_command(merged_config).await
+            let reload = ConfigReload {
+                config: shared_config,
+                cli_log_level: cli.verbose > 0,
+            };
+            record_command(merged_config, reload).await
         }
         Commands::Show {
             input,
             event_type,
             follow,
             num,
-        } => show_command(&input, event_type, follow, num).await,
+            output_format,
+            redact_preview,
+            redact_pattern,
+        } => {
+            let redaction = redact_preview.map(|mode| {
+                let mut settings = RedactionSettings {
+                    mode,
+                    ..sensor_config.redaction.clone()
+                };
+                settings.custom_patterns.extend(redact_pattern);
+                RedactionPlugin::new(settings.to_redaction_config())
+            });
+            show_command(&input, event_type, follow, num, &output_format, redaction).await
+        }
         Commands::Analyze {
             input,
             analysis_type,
-        } => analyze_command(&input, &analysis_type).await,
+            json,
+            output,
+        } => analyze_command(&input, &analysis_type, json, output.as_deref()).await,
         Commands::Status => status_command().await,
-        Commands::Check => check_command().await,
+        Commands::Check { json } => check_command(json).await,
         Commands::Daemon(daemon_cmd) => daemon_command(daemon_cmd).await,
+        Commands::Oximy(oximy_cmd) => oximy_command(oximy_cmd, &sensor_config).await,
+        Commands::Providers(providers_cmd) => providers_command(providers_cmd),
+        Commands::Diagnostics(diagnostics_cmd) => {
+            diagnostics_command(diagnostics_cmd, &sensor_config, cli.config.clone()).await
+        }
+        Commands::Reset {
+            credentials,
+            queue,
+            logs,
+            all,
+            yes,
+        } => reset_command(
+            &sensor_config,
+            credentials || all,
+            queue || all,
+            logs || all,
+            yes,
+        ),
         Commands::Test => test_command().await,
         Commands::Diagnose { pid, maps, network } => diagnose_command(pid, maps, network).await,
         Commands::SslInfo { detailed, usage } => ssl_info_command(detailed, usage).await,
@@ -364,15 +597,18 @@ async fn main() -> anyhow::Result<()> {
             interval,
             count,
             redaction,
+            limits,
         } => {
             demo_command(DemoConfig {
                 output,
                 web,
+                host: sensor_config.web.host.clone(),
                 port,
                 tui,
                 interval_ms: interval,
                 event_count: count,
                 redaction_mode: redaction,
+                limits,
             })
             .await
         }
@@ -383,14 +619,21 @@ async fn main() -> anyhow::Result<()> {
             web,
             port,
             tui,
+            to,
+            output,
         } => {
             replay_command(ReplayCommandConfig {
                 input,
                 speed,
                 loop_playback,
                 web,
+                host: sensor_config.web.host.clone(),
                 port,
                 tui,
+                to,
+                output,
+                export: sensor_config.export.clone(),
+                redaction: sensor_config.redaction.clone(),
             })
             .await
         }
@@ -476,6 +719,11 @@ async fn load_app_registry() -> Arc<AppRegistry> {
         }
     }
 
+    if oisp_core::air_gapped() {
+        info!("Air-gapped: using bundled app registry without GitHub refresh");
+        return Arc::new(LiveRegistry::new_bundled_only().clone_registry().await);
+    }
+
     // Use hybrid approach: bundled + GitHub refresh
     info!("Using hybrid app registry (bundled + GitHub refresh)");
     let live_registry = LiveRegistry::new_with_refresh().await;
@@ -493,25 +741,40 @@ async fn load_app_registry() -> Arc<AppRegistry> {
     Arc::new(registry)
 }
 
+/// Whether the user gave `id` on the command line or in its environment
+/// variable, as opposed to clap filling in the default
+fn user_provided(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
 /// Merge CLI arguments with config file settings
 /// CLI arguments take precedence when explicitly provided
-#[allow(clippy::too_many_arguments)]
 fn merge_record_config(
     config: &SensorConfig,
-    output: Option<PathBuf>,
-    web: bool,
-    port: u16,
-    tui: bool,
-    process: Option<Vec<String>>,
-    pid: Option<Vec<u32>>,
-    redaction: String,
-    no_ssl: bool,
-    no_process: bool,
-    no_file: bool,
-    no_network: bool,
-    ebpf_path: Option<PathBuf>,
-    libssl_path: Option<PathBuf>,
+    args: RecordArgs,
+    matches: &ArgMatches,
 ) -> RecordConfig {
+    let RecordArgs {
+        output,
+        web,
+        port,
+        tui,
+        process,
+        pid,
+        redaction,
+        no_ssl,
+        no_process,
+        no_file,
+        no_network,
+        ebpf_path,
+        libssl_path,
+        attach_existing,
+        limits,
+    } = args;
+
     // For boolean flags, CLI explicit disables take precedence
     // Otherwise use config file value
     let ssl = if no_ssl { false } else { config.capture.ssl };
@@ -545,58 +808,312 @@ fn merge_record_config(
         }
     });
 
-    // Web/port - CLI args have defaults, so they're always "set"
-    // Use CLI values but fall back to config if CLI has defaults
-    let web_enabled = if !web { false } else { config.web.enabled };
-    let web_port = if port != 7777 { port } else { config.web.port };
-
-    // Redaction mode - CLI has default "safe", use it unless different
-    let redaction_mode = if redaction != "safe" {
+    // Web, port and redaction have CLI defaults; those only apply when the
+    // user gave the flag, otherwise the config file wins
+    let web_enabled = if user_provided(matches, "web") {
+        web
+    } else {
+        config.web.enabled
+    };
+    let web_port = if user_provided(matches, "port") {
+        port
+    } else {
+        config.web.port
+    };
+    let redaction_mode = if user_provided(matches, "redaction") {
         redaction
     } else {
         config.redaction.mode.clone()
     };
 
-    RecordConfig {
+    let redaction_settings = RedactionSettings {
+        mode: redaction_mode,
+        ..config.redaction.clone()
+    };
+    let redaction = redaction_settings.to_redaction_config();
+    let content_limits =
+        config
+            .redaction
+            .max_content_chars
+            .map(|max_content_chars| ContentLimits {
+                max_content_chars,
+                hash_after_redaction: config.redaction.hash_after_redaction,
+                redaction: redaction.clone(),
+            });
+
+    let web_tls = config.web.tls.as_ref().map(|tls| oisp_web::TlsConfig {
+        cert_path: PathBuf::from(&tls.cert_path),
+        key_path: PathBuf::from(&tls.key_path),
+    });
+    let web_auth = config.web.auth.as_ref().map(|auth| oisp_web::AuthConfig {
+        bearer_token: auth.token.clone(),
+        basic: auth.username.clone().zip(auth.password.clone()),
+    });
+
+    let merged = RecordConfig {
         output,
         web: web_enabled,
+        web_host: config.web.host.clone(),
         port: web_port,
+        web_tls,
+        web_auth,
+        web_cors_origins: config.web.cors_origins.clone(),
+        instance_id: config
+            .sensor
+            .instance_id
+            .clone()
+            .unwrap_or_else(|| ulid::Ulid::new().to_string()),
+        source_labels: config.source_labels.clone(),
+        kubernetes: config.kubernetes.clone(),
+        process_resources: config
+            .process_resources
+            .enabled
+            .then(|| std::time::Duration::from_secs(config.process_resources.sample_interval_secs)),
+        budget: config.budget.clone(),
+        latency: config.latency.clone(),
+        providers: config.providers.clone(),
+        correlation: config.correlation.clone(),
+        reorder_window: (config.export.jsonl.reorder_window_ms > 0)
+            .then(|| std::time::Duration::from_millis(config.export.jsonl.reorder_window_ms)),
+        reorder_max_events: config.export.jsonl.reorder_max_events,
+        jsonl_flush_each: config.export.jsonl.flush_each,
+        jsonl_flush_interval: (config.export.jsonl.flush_interval_ms > 0)
+            .then(|| std::time::Duration::from_millis(config.export.jsonl.flush_interval_ms)),
+        jsonl_flush_bytes: config.export.jsonl.flush_bytes,
+        flush_on_signal: config.export.jsonl.flush_on_signal,
+        schema_version: config.export.jsonl.schema_version.clone(),
         tui,
         process_filter,
         pid_filter,
-        redaction_mode,
+        redaction,
+        jsonl_redaction: redaction_settings.for_exporter(config.export.jsonl.redaction.as_deref()),
+        websocket_redaction: redaction_settings
+            .for_exporter(config.export.websocket.redaction.as_deref()),
         ssl,
         process: process_enabled,
         file,
         network,
+        sni_extraction: config.capture.sni_extraction,
+        network_flows: config
+            .capture
+            .network_flows
+            .then(|| std::time::Duration::from_secs(config.capture.network_flow_idle_timeout_secs)),
+        stream_timeout: std::time::Duration::from_secs(config.capture.stream_timeout_secs),
+        attach_existing: attach_existing || config.capture.attach_existing,
+        ringbuf_size: config.capture.ringbuf_size,
+        channel_policy: config.capture.channel_policy,
+        file_path_include: config.capture.file_path_include.clone(),
+        file_path_exclude: config.capture.file_path_exclude.clone(),
+        watchdog: (config.capture.stall_timeout_secs > 0).then(|| WatchdogConfig {
+            stall_timeout: std::time::Duration::from_secs(config.capture.stall_timeout_secs),
+            max_restarts: config.capture.max_capture_restarts,
+        }),
+        schema_validation: config.sensor.schema_validation,
+        event_ids: config.sensor.event_ids,
+        ts_mono: config.sensor.ts_mono,
+        dead_letter: config
+            .export
+            .dead_letter
+            .enabled
+            .then(|| config.export.dead_letter.clone()),
+        content_limits,
         ebpf_path,
         libssl_path,
-    }
+        limits,
+    };
+
+    debug!(
+        output = ?merged.output,
+        web = merged.web,
+        port = merged.port,
+        tui = merged.tui,
+        redaction = %redaction_settings.mode,
+        process_filter = ?merged.process_filter,
+        pid_filter = ?merged.pid_filter,
+        ssl = merged.ssl,
+        process = merged.process,
+        file = merged.file,
+        network = merged.network,
+        ebpf_path = ?merged.ebpf_path,
+        libssl_path = ?merged.libssl_path,
+        "Effective record configuration"
+    );
+    merged
 }
 
 #[allow(dead_code)]
 struct RecordConfig {
     output: Option<PathBuf>,
     web: bool,
+    web_host: String,
     port: u16,
+    web_tls: Option<oisp_web::TlsConfig>,
+    web_auth: Option<oisp_web::AuthConfig>,
+    web_cors_origins: Vec<String>,
+    instance_id: String,
+    source_labels: HashMap<String, String>,
+    kubernetes: KubernetesSettings,
+    /// Sample interval for process resource usage (None = disabled)
+    process_resources: Option<std::time::Duration>,
+    budget: BudgetSettings,
+    latency: LatencySettings,
+    providers: ProviderSettings,
+    correlation: CorrelationSettings,
+    reorder_window: Option<std::time::Duration>,
+    reorder_max_events: usize,
+    jsonl_flush_each: bool,
+    /// Flush period of the buffered JSONL output (None = no timer)
+    jsonl_flush_interval: Option<std::time::Duration>,
+    jsonl_flush_bytes: usize,
+    /// Flush exporters on SIGUSR1
+    flush_on_signal: bool,
+    /// Target schema for the JSONL output (None = current)
+    schema_version: Option<String>,
     tui: bool,
     process_filter: Vec<String>,
     pid_filter: Vec<u32>,
-    redaction_mode: String,
+    redaction: RedactionConfig,
+    /// Redaction of each exporter, `redaction` unless it sets its own mode
+    jsonl_redaction: RedactionConfig,
+    websocket_redaction: RedactionConfig,
     ssl: bool,
     process: bool,
     file: bool,
     network: bool,
+    sni_extraction: bool,
+    /// Idle timeout for network flow rollups (None = disabled)
+    network_flows: Option<std::time::Duration>,
+    /// Time without a chunk before a streamed response is closed
+    stream_timeout: std::time::Duration,
+    /// Decode responses whose request was never captured
+    attach_existing: bool,
+    /// eBPF ring buffer size in bytes (None = sslsniff default)
+    ringbuf_size: Option<usize>,
+    channel_policy: ChannelPolicy,
+    /// Globs of file.open paths to report and to drop
+    file_path_include: Vec<String>,
+    file_path_exclude: Vec<String>,
+    /// Restarts of stalled capture sources (None = disabled)
+    watchdog: Option<WatchdogConfig>,
+    schema_validation: SchemaValidation,
+    event_ids: EventIds,
+    ts_mono: TsMonoSource,
+    /// Retries and dead-letter file for events every exporter rejects
+    /// (None = one attempt, failed events are dropped)
+    dead_letter: Option<DeadLetterConfig>,
+    content_limits: Option<ContentLimits>,
     ebpf_path: Option<PathBuf>,
     libssl_path: Option<PathBuf>,
+    limits: RunLimits,
+}
+
+/// Kubernetes enricher for the Downward API environment and configured pod list
+fn kubernetes_enricher(settings: &KubernetesSettings) -> KubernetesEnricher {
+    let enricher = KubernetesEnricher::from_env();
+    let refresh = std::time::Duration::from_secs(settings.refresh_interval_secs);
+    let source = match (&settings.kubelet_url, &settings.pods_file) {
+        (Some(url), _) => Some(PodSource::Kubelet(url.clone())),
+        (None, Some(path)) => Some(PodSource::File(PathBuf::from(path))),
+        (None, None) => None,
+    };
+    match source {
+        Some(source) => {
+            info!("Kubernetes enrichment enabled, pod list from {:?}", source);
+            enricher.with_pod_source(source, refresh)
+        }
+        None => {
+            info!("Kubernetes enrichment enabled (Downward API only)");
+            enricher
+        }
+    }
 }
 
-async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
+/// Auto-stop thresholds for `record` and `demo`
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+struct RunLimits {
+    /// Stop after this many decoded events have passed the action stage,
+    /// whether or not an exporter then accepts them
+    #[arg(long, value_name = "N", conflicts_with = "tui")]
+    max_events: Option<u64>,
+
+    /// Stop after running for this many seconds
+    #[arg(long, value_name = "SECONDS", conflicts_with = "tui")]
+    max_duration: Option<u64>,
+}
+
+impl RunLimits {
+    /// Add the event limit to `pipeline`, returning a notifier for when it is hit
+    ///
+    /// Must be called after all other actions so events they drop don't count.
+    fn install(&self, pipeline: &mut Pipeline) -> Option<Arc<tokio::sync::Notify>> {
+        let limit = EventLimitPlugin::new(self.max_events?);
+        let reached = limit.reached();
+        pipeline.add_action(Box::new(limit));
+        Some(reached)
+    }
+
+    /// Wait until a limit is hit or a shutdown signal arrives
+    async fn wait(
+        &self,
+        reached: Option<Arc<tokio::sync::Notify>>,
+        config: Option<&ConfigReload>,
+    ) -> anyhow::Result<()> {
+        let events = async {
+            match &reached {
+                Some(notify) => notify.notified().await,
+                None => std::future::pending().await,
+            }
+        };
+        let duration = async {
+            match self.max_duration {
+                Some(secs) => tokio::time::sleep(std::time::Duration::from_secs(secs)).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = wait_for_shutdown_signal(config) => result,
+            _ = events => {
+                info!("Reached --max-events {}, shutting down", self.max_events.unwrap_or_default());
+                Ok(())
+            }
+            _ = duration => {
+                info!("Reached --max-duration {}s, shutting down", self.max_duration.unwrap_or_default());
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn record_command(config: RecordConfig, reload: ConfigReload) -> anyhow::Result<()> {
     info!("Starting OISP Sensor...");
 
     // Create pipeline
-    let pipeline_config = PipelineConfig::default();
+    let mut pipeline_config = PipelineConfig {
+        channel_policy: config.channel_policy,
+        schema_validation: config.schema_validation,
+        event_ids: config.event_ids,
+        ts_mono: config.ts_mono,
+        watchdog: config.watchdog,
+        flush_on_signal: config.flush_on_signal,
+        ..Default::default()
+    };
+    if let Some(dead_letter) = &config.dead_letter {
+        pipeline_config.export_attempts = dead_letter.max_attempts;
+        pipeline_config.export_retry_delay =
+            std::time::Duration::from_millis(dead_letter.retry_delay_ms);
+    }
     let mut pipeline = Pipeline::new(pipeline_config);
+    if let Some(dead_letter) = &config.dead_letter {
+        let sink = DeadLetterSink::open(&dead_letter.path).map_err(|e| {
+            anyhow::anyhow!("cannot open dead-letter file {}: {}", dead_letter.path, e)
+        })?;
+        info!(
+            "Events no exporter accepts after {} attempts go to {}",
+            dead_letter.max_attempts, dead_letter.path
+        );
+        pipeline.set_dead_letter(sink);
+    }
 
     // Add eBPF capture on Linux
     #[cfg(target_os = "linux")]
@@ -614,6 +1131,9 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
                 comm_filter: config.process_filter.clone(),
                 pid_filter: config.pid_filter.first().copied(),
                 ebpf_bytecode_path: config.ebpf_path.map(|p| p.to_string_lossy().to_string()),
+                ringbuf_size: config.ringbuf_size,
+                // Only the web API attaches processes at runtime
+                runtime_attach: config.web,
             };
 
             let ebpf_capture = EbpfCapture::with_config(ebpf_config);
@@ -632,6 +1152,7 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
                 network: config.network,
                 use_system_extension: true,
                 socket_path: "/tmp/oisp.sock".to_string(),
+                ..Default::default()
             };
 
             let macos_capture = MacOSCapture::with_config(macos_config);
@@ -647,34 +1168,129 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
         let _ = (&config.ebpf_path, &config.libssl_path); // Suppress unused warnings
     }
 
+    // Holding connects for a ClientHello that never arrives would only
+    // delay them
+    let sni_extraction = config.sni_extraction && pipeline.captures_report_network_send().await;
+    if config.sni_extraction && !sni_extraction {
+        warn!(
+            "Ignoring capture.sni_extraction: no capture backend in use reports the first bytes of connections"
+        );
+    }
+
     // Add decoders
-    pipeline.add_decode(Box::new(HttpDecoder::new()));
-    pipeline.add_decode(Box::new(SystemDecoder::new()));
+    let mut http_decoder = HttpDecoder::new()
+        .with_sni_extraction(sni_extraction)
+        .with_openai_compatible(config.providers.openai_compatible)
+        .with_provider_hosts(config.providers.hosts.clone())
+        .with_provider_cache_capacity(config.providers.detection_cache_size)
+        .with_stream_timeout(config.stream_timeout)
+        .with_attach_existing(config.attach_existing)
+        .with_metrics(pipeline.metrics());
+    if let Some(idle_timeout) = config.network_flows {
+        http_decoder = http_decoder.with_network_flows(idle_timeout);
+    }
+    if let Some(limits) = config.content_limits.clone() {
+        http_decoder = http_decoder.with_content_limits(limits);
+    }
+    pipeline.add_decode(Box::new(http_decoder));
+    let file_paths = FilePathFilter::new(&config.file_path_include, &config.file_path_exclude)
+        .map_err(|e| anyhow::anyhow!("Invalid file path pattern: {}", e))?;
+    pipeline.add_decode(Box::new(
+        SystemDecoder::new().with_file_path_filter(file_paths),
+    ));
 
     // Add enrichers
     pipeline.add_enrich(Box::new(HostEnricher::new()));
     pipeline.add_enrich(Box::new(ProcessTreeEnricher::new()));
+    info!("Sensor instance id: {}", config.instance_id);
+    pipeline.add_enrich(Box::new(SourceEnricher::new(
+        config.instance_id.clone(),
+        config.source_labels.clone(),
+    )));
+    if config.kubernetes.enabled {
+        pipeline.add_enrich(Box::new(kubernetes_enricher(&config.kubernetes)));
+    }
+    if let Some(interval) = config.process_resources {
+        info!(
+            "Process resource enrichment enabled, sampled every {:?}",
+            interval
+        );
+        pipeline.add_enrich(Box::new(ResourceEnricher::new(interval)));
+    }
 
     // Add app enricher with hybrid registry (bundled + GitHub refresh)
     let app_registry = load_app_registry().await;
     pipeline.add_enrich(Box::new(AppEnricher::new(app_registry)));
 
-    // Add redaction
-    let redaction = match config.redaction_mode.as_str() {
-        "full" => RedactionPlugin::full_capture(),
-        "minimal" => RedactionPlugin::minimal(),
-        _ => RedactionPlugin::safe_mode(),
-    };
-    pipeline.add_action(Box::new(redaction));
+    // Add redaction. Exporters can set their own mode: the action stage
+    // keeps the richest form any of them needs, and the others redact
+    // their own copy further
+    let mut redaction_profiles = vec![&config.redaction, &config.websocket_redaction];
+    if config.output.is_some() {
+        redaction_profiles.push(&config.jsonl_redaction);
+    }
+    let action_redaction = least_redacting(redaction_profiles)
+        .unwrap_or(&config.redaction)
+        .clone();
+    pipeline.add_action(Box::new(RedactionPlugin::new(action_redaction.clone())));
+    if config.budget.enabled {
+        info!(
+            "Budget alerts enabled: ${:.2} per {}s ({:?})",
+            config.budget.threshold_usd, config.budget.window_secs, config.budget.scope
+        );
+        let registry = Arc::new(DynamicProviderRegistry::new(oisp_core::global_spec_bundle()));
+        pipeline.add_action(Box::new(BudgetAlertPlugin::new(
+            config.budget.to_budget_config(),
+            registry,
+        )));
+    }
+    if config.latency.enabled {
+        info!("Latency tagging enabled");
+        pipeline.add_action(Box::new(LatencyPlugin::new(
+            config.latency.to_latency_config(),
+        )));
+    }
+    if config.correlation.sessions {
+        info!(
+            "Agent sessions enabled: idle timeout {}ms",
+            config.correlation.session_idle_timeout_ms
+        );
+        let registry = Arc::new(DynamicProviderRegistry::new(oisp_core::global_spec_bundle()));
+        pipeline.add_action(Box::new(SessionPlugin::new(
+            config.correlation.to_session_config(),
+            registry,
+        )));
+    }
+    if config.correlation.agent_steps {
+        info!("Agent steps enabled");
+        pipeline.add_action(Box::new(AgentStepPlugin::new(
+            config.correlation.to_agent_step_config(),
+        )));
+    }
+    let limit_reached = config.limits.install(&mut pipeline);
 
     // Add exporters
-    if let Some(output_path) = config.output {
-        pipeline.add_export(Box::new(JsonlExporter::new(JsonlExporterConfig {
+    if let Some(output_path) = config.output.clone() {
+        let jsonl_exporter = JsonlExporter::new(JsonlExporterConfig {
             path: output_path,
             append: true,
             pretty: false,
-            flush_each: true,
-        })));
+            flush_each: config.jsonl_flush_each,
+            flush_interval: config.jsonl_flush_interval,
+            flush_bytes: config.jsonl_flush_bytes,
+            reorder_window: config.reorder_window,
+            reorder_max_events: config.reorder_max_events,
+            schema: config
+                .schema_version
+                .as_deref()
+                .map(SchemaTransform::new)
+                .transpose()?,
+        });
+        pipeline.add_export(RedactingExporter::wrap(
+            Box::new(jsonl_exporter),
+            config.jsonl_redaction,
+            &action_redaction,
+        ));
     }
 
     let ws_exporter = WebSocketExporter::new(WebSocketExporterConfig {
@@ -682,7 +1298,11 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
         host: "127.0.0.1".to_string(),
         buffer_size: 1000,
     });
-    pipeline.add_export(Box::new(ws_exporter));
+    pipeline.add_export(RedactingExporter::wrap(
+        Box::new(ws_exporter),
+        config.websocket_redaction,
+        &action_redaction,
+    ));
 
     // Enable traces
     pipeline.enable_traces();
@@ -695,19 +1315,34 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
     pipeline.start().await?;
 
     info!("Pipeline started");
+    print_capture_errors(&pipeline);
 
     // Start web UI if requested
     if config.web {
         let web_config = oisp_web::WebConfig {
-            host: "0.0.0.0".to_string(),
+            host: config.web_host.clone(),
             port: config.port,
+            tls: config.web_tls.clone(),
+            auth: config.web_auth.clone(),
+            cors_origins: config.web_cors_origins.clone(),
+            history: config.output.clone(),
         };
 
         let event_tx = pipeline.event_sender();
         let tb = trace_builder.clone();
+        let metrics = pipeline.metrics();
+        let process_targets = pipeline.process_targets().await;
 
         tokio::spawn(async move {
-            if let Err(e) = oisp_web::start_server(web_config, event_tx, tb).await {
+            if let Err(e) = oisp_web::start_server_with_metrics(
+                web_config,
+                event_tx,
+                tb,
+                Some(metrics),
+                process_targets,
+            )
+            .await
+            {
                 error!("Web server error: {}", e);
             }
         });
@@ -725,27 +1360,246 @@ async fn record_command(config: RecordConfig) -> anyhow::Result<()> {
     if config.tui {
         oisp_tui::run(event_rx).await?;
     } else {
-        // Wait for Ctrl+C
-        tokio::signal::ctrl_c().await?;
+        config.limits.wait(limit_reached, Some(&reload)).await?;
     }
 
-    // Cleanup
+    // Cleanup (stopping the pipeline flushes all exporters)
     pipeline.stop().await?;
     info!("Sensor stopped");
 
     Ok(())
 }
 
+/// Print capture errors reported while starting the pipeline
+fn print_capture_errors(pipeline: &Pipeline) {
+    let errors = pipeline.capture_errors();
+    if errors.is_empty() {
+        return;
+    }
+
+    eprintln!();
+    eprintln!("  Capture errors:");
+    for err in &errors {
+        eprintln!("    [{}] {:?}: {}", err.plugin, err.kind, err.message);
+    }
+    eprintln!();
+}
+
+/// Config file re-read on SIGHUP
+///
+/// Only the log level is applied to the running sensor; capture, pipeline,
+/// redaction, exporters and the web server keep the settings they started
+/// with until restart.
+struct ConfigReload {
+    config: SharedConfig,
+    /// `-v` was given, which takes precedence over the file's log level
+    cli_log_level: bool,
+}
+
+impl ConfigReload {
+    /// Re-read the config file and apply what can change at runtime
+    fn reload(&self) {
+        let before = self.config.get();
+        match self.config.reload() {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("No configuration file to reload");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to reload configuration: {}", e);
+                return;
+            }
+        }
+        let after = self.config.get();
+
+        let log_level = &after.sensor.log_level;
+        if log_level != &before.sensor.log_level {
+            if self.cli_log_level {
+                info!("Keeping the log level set with -v over sensor.log_level");
+            } else if let Some(control) = oisp_oximy::log_level::installed() {
+                if let Err(e) = control.set(log_level, None) {
+                    warn!("Cannot apply sensor.log_level {:?}: {}", log_level, e);
+                }
+            }
+        }
+
+        if restart_needed(&before, &after) {
+            warn!("Configuration changes other than sensor.log_level take effect after a restart");
+        } else {
+            info!("Configuration reloaded");
+        }
+    }
+}
+
+/// Whether `after` differs from `before` in anything but the log level
+fn restart_needed(before: &SensorConfig, after: &SensorConfig) -> bool {
+    let mut after = after.clone();
+    after.sensor.log_level = before.sensor.log_level.clone();
+    serde_json::to_value(before).ok() != serde_json::to_value(&after).ok()
+}
+
+/// Wait until the sensor should shut down
+///
+/// Returns on SIGINT or SIGTERM (e.g. `systemctl stop`) so the caller can
+/// stop the pipeline and flush exporters. SIGHUP reloads `config` if given.
+#[cfg(unix)]
+async fn wait_for_shutdown_signal(config: Option<&ConfigReload>) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+
+    loop {
+        tokio::select! {
+            _ = sigint.recv() => {
+                info!("Received SIGINT, shutting down");
+                return Ok(());
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM, shutting down");
+                return Ok(());
+            }
+            _ = sighup.recv() => {
+                let Some(config) = config else {
+                    debug!("Received SIGHUP, nothing to reload");
+                    continue;
+                };
+                info!("Received SIGHUP, reloading configuration");
+                config.reload();
+            }
+        }
+    }
+}
+
+/// Wait until the sensor should shut down (Ctrl+C only on this platform)
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal(_config: Option<&ConfigReload>) -> anyhow::Result<()> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Columns of `show --output-format table`, with their maximum widths
+const SHOW_TABLE_COLUMNS: [(&str, usize); 6] = [
+    ("TS", 23),
+    ("TYPE", 24),
+    ("PROVIDER", 12),
+    ("MODEL", 28),
+    ("PID", 7),
+    ("PROCESS", 20),
+];
+
+/// Cells of an event's `show` table row, "-" where a value is missing
+fn show_table_row(event: &serde_json::Value) -> Vec<String> {
+    let text = |pointer: &str| {
+        event
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let ts = text("/ts").map(|ts| match chrono::DateTime::parse_from_rfc3339(&ts) {
+        Ok(t) => t.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        Err(_) => ts,
+    });
+
+    [
+        ts,
+        text("/event_type"),
+        text("/data/provider/name"),
+        text("/data/model/id"),
+        event.pointer("/process/pid").map(|pid| pid.to_string()),
+        text("/process/name"),
+    ]
+    .into_iter()
+    .map(|cell| cell.unwrap_or_else(|| "-".to_string()))
+    .collect()
+}
+
+/// Original (`-`, matches marked `[[type:...]]`) and redacted (`+`) text of
+/// each content field redaction would change in an event
+fn format_redaction_preview(
+    event: &serde_json::Value,
+    redactions: &[oisp_core::redaction::FieldRedaction],
+    config: &RedactionConfig,
+) -> String {
+    use oisp_core::redaction::{find_matches, mark_matches};
+
+    let row = show_table_row(event);
+    let event_id = event
+        .get("event_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("-");
+    let mut out = format!("{} {} ({} {})", row[1], event_id, row[2], row[3]);
+    if redactions.is_empty() {
+        out.push_str("\n  nothing redacted");
+    }
+    for field in redactions {
+        let findings: Vec<String> = field
+            .result
+            .findings
+            .iter()
+            .map(|f| format!("{} x{}", f.finding_type, f.count))
+            .collect();
+        let marked = mark_matches(
+            &field.original,
+            &find_matches(&field.original, Some(&field.field), config),
+        );
+        out.push_str(&format!(
+            "\n  {}  [{}]\n    - {}\n    + {}",
+            field.path,
+            findings.join(", "),
+            marked,
+            field.result.content
+        ));
+    }
+    out
+}
+
+/// Join cells into a line, truncating and padding each to its column width
+fn format_show_table_row(cells: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (cell, &width) in cells.iter().zip(widths) {
+        let cell = if cell.chars().count() > width {
+            let mut cut: String = cell.chars().take(width.saturating_sub(1)).collect();
+            cut.push('…');
+            cut
+        } else {
+            cell.clone()
+        };
+        line.push_str(&format!("{:<width$}  ", cell, width = width));
+    }
+    line.trim_end().to_string()
+}
+
 async fn show_command(
     input: &PathBuf,
     event_type: Option<String>,
     follow: bool,
     num: usize,
+    output_format: &str,
+    redact_preview: Option<RedactionPlugin>,
 ) -> anyhow::Result<()> {
     use std::fs::File;
     use std::io::{BufRead, BufReader, Seek, SeekFrom};
     use std::time::Duration;
 
+    if follow && output_format == "json" && redact_preview.is_none() {
+        anyhow::bail!("--output-format json prints one array at the end; use ndjson with --follow");
+    }
+
+    let headers: Vec<String> = SHOW_TABLE_COLUMNS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let max_widths: Vec<usize> = SHOW_TABLE_COLUMNS.iter().map(|(_, w)| *w).collect();
+    // Rows are printed as they arrive when following, so columns can't be
+    // sized to fit and use their maximum widths
+    if follow && output_format == "table" && redact_preview.is_none() {
+        println!("{}", format_show_table_row(&headers, &max_widths));
+    }
+    let mut collected = Vec::new();
+
     let mut file = File::open(input)?;
 
     // If following, start from the end of the file
@@ -800,8 +1654,36 @@ async fn show_command(
             }
         }
 
-        // Pretty print
-        println!("{}", serde_json::to_string_pretty(&event)?);
+        if let Some(plugin) = &redact_preview {
+            let is_ai = event
+                .get("event_type")
+                .and_then(|v| v.as_str())
+                .is_some_and(|t| t.starts_with("ai."));
+            if !is_ai {
+                continue;
+            }
+            println!(
+                "{}",
+                format_redaction_preview(&event, &plugin.preview(&event), plugin.config())
+            );
+            count += 1;
+            if !follow && count >= num {
+                break;
+            }
+            continue;
+        }
+
+        match output_format {
+            "ndjson" => println!("{}", serde_json::to_string(&event)?),
+            "table" if follow => {
+                println!(
+                    "{}",
+                    format_show_table_row(&show_table_row(&event), &max_widths)
+                )
+            }
+            "json" | "table" => collected.push(event),
+            _ => println!("{}", serde_json::to_string_pretty(&event)?),
+        }
 
         count += 1;
         if !follow && count >= num {
@@ -809,11 +1691,41 @@ async fn show_command(
         }
     }
 
+    match output_format {
+        _ if redact_preview.is_some() => {}
+        "json" => println!("{}", serde_json::to_string(&collected)?),
+        "table" if !follow => {
+            let rows: Vec<Vec<String>> = collected.iter().map(show_table_row).collect();
+            let widths: Vec<usize> = max_widths
+                .iter()
+                .enumerate()
+                .map(|(i, max)| {
+                    rows.iter()
+                        .chain(std::iter::once(&headers))
+                        .map(|row| row[i].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                        .min(*max)
+                })
+                .collect();
+            println!("{}", format_show_table_row(&headers, &widths));
+            for row in &rows {
+                println!("{}", format_show_table_row(row, &widths));
+            }
+        }
+        _ => {}
+    }
+
     Ok(())
 }
 
-async fn analyze_command(input: &PathBuf, analysis_type: &str) -> anyhow::Result<()> {
-    use std::collections::HashMap;
+async fn analyze_command(
+    input: &PathBuf,
+    analysis_type: &str,
+    json: bool,
+    output: Option<&std::path::Path>,
+) -> anyhow::Result<()> {
+    use oisp_core::{DynamicProviderRegistry, Inventory};
     use std::fs::File;
     use std::io::{BufRead, BufReader};
 
@@ -832,54 +1744,52 @@ async fn analyze_command(input: &PathBuf, analysis_type: &str) -> anyhow::Result
 
     match analysis_type {
         "inventory" => {
-            let mut providers: HashMap<String, u64> = HashMap::new();
-            let mut models: HashMap<String, u64> = HashMap::new();
-            let mut apps: HashMap<String, u64> = HashMap::new();
-
-            for event in &events {
-                if event.get("event_type").and_then(|v| v.as_str()) == Some("ai.request") {
-                    if let Some(data) = event.get("data") {
-                        if let Some(provider) = data
-                            .get("provider")
-                            .and_then(|p| p.get("name"))
-                            .and_then(|n| n.as_str())
-                        {
-                            *providers.entry(provider.to_string()).or_default() += 1;
-                        }
-                        if let Some(model) = data
-                            .get("model")
-                            .and_then(|m| m.get("id"))
-                            .and_then(|i| i.as_str())
-                        {
-                            *models.entry(model.to_string()).or_default() += 1;
-                        }
-                    }
-                    if let Some(proc) = event
-                        .get("process")
-                        .and_then(|p| p.get("name"))
-                        .and_then(|n| n.as_str())
-                    {
-                        *apps.entry(proc.to_string()).or_default() += 1;
+            let registry = DynamicProviderRegistry::new(oisp_core::global_spec_bundle());
+            let inventory = Inventory::from_events(&events, &registry);
+
+            if json || output.is_some() {
+                let rendered = serde_json::to_string_pretty(&inventory)?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(path, rendered + "\n")?;
+                        println!("Inventory written to {}", path.display());
                     }
+                    None => println!("{}", rendered),
                 }
+                return Ok(());
             }
 
             println!("\n=== AI Inventory ===\n");
 
             println!("Providers:");
-            for (name, count) in providers.iter() {
-                println!("  {:<20} {:>6} requests", name, count);
+            for entry in &inventory.providers {
+                println!("  {:<20} {:>6} requests", entry.name, entry.requests);
             }
 
             println!("\nModels:");
-            for (name, count) in models.iter() {
-                println!("  {:<30} {:>6} requests", name, count);
+            for entry in &inventory.models {
+                let cost = entry
+                    .estimated_cost_usd
+                    .map(|c| format!("${:.4}", c))
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "  {:<30} {:>6} requests {:>10} in {:>10} out {:>10}",
+                    entry.model, entry.requests, entry.input_tokens, entry.output_tokens, cost
+                );
             }
 
             println!("\nApplications:");
-            for (name, count) in apps.iter() {
-                println!("  {:<20} {:>6} requests", name, count);
+            for entry in &inventory.apps {
+                println!("  {:<20} {:>6} requests", entry.name, entry.requests);
             }
+
+            println!(
+                "\nTotal: {} requests, {} input / {} output tokens, ~${:.4}",
+                inventory.totals.requests,
+                inventory.totals.input_tokens,
+                inventory.totals.output_tokens,
+                inventory.totals.estimated_cost_usd
+            );
         }
         "traces" => {
             println!("Trace analysis not yet implemented");
@@ -897,7 +1807,10 @@ async fn analyze_command(input: &PathBuf, analysis_type: &str) -> anyhow::Result
 
 async fn status_command() -> anyhow::Result<()> {
     println!();
-    println!("OISP Sensor v{}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "{}",
+        style::heading(format!("OISP Sensor v{}", env!("CARGO_PKG_VERSION")))
+    );
     println!();
 
     // Platform
@@ -911,19 +1824,19 @@ async fn status_command() -> anyhow::Result<()> {
     #[cfg(target_os = "linux")]
     {
         println!();
-        println!("Linux Capabilities:");
+        println!("{}", style::heading("Linux Capabilities:"));
 
         // Check if running as root
         let uid = unsafe { libc::getuid() };
-        println!("  Running as root: {}", uid == 0);
+        println!("  Running as root: {}", style::flag(uid == 0));
 
         // Check for eBPF support
         let ebpf_supported = std::path::Path::new("/sys/fs/bpf").exists();
-        println!("  eBPF supported: {}", ebpf_supported);
+        println!("  eBPF supported: {}", style::flag(ebpf_supported));
 
         // Check for BTF
         let btf_available = std::path::Path::new("/sys/kernel/btf/vmlinux").exists();
-        println!("  BTF available: {}", btf_available);
+        println!("  BTF available: {}", style::flag(btf_available));
 
         // Check kernel version
         if let Ok(release) = std::fs::read_to_string("/proc/sys/kernel/osrelease") {
@@ -934,7 +1847,7 @@ async fn status_command() -> anyhow::Result<()> {
     #[cfg(target_os = "macos")]
     {
         println!();
-        println!("macOS Capabilities:");
+        println!("{}", style::heading("macOS Capabilities:"));
         println!("  System Extension: Not installed");
         println!("  Full Disk Access: Unknown");
     }
@@ -942,7 +1855,7 @@ async fn status_command() -> anyhow::Result<()> {
     #[cfg(target_os = "windows")]
     {
         println!();
-        println!("Windows Capabilities:");
+        println!("{}", style::heading("Windows Capabilities:"));
         println!("  Running as Administrator: Unknown");
         println!("  ETW access: Unknown");
     }
@@ -955,11 +1868,13 @@ async fn status_command() -> anyhow::Result<()> {
 struct DemoConfig {
     output: Option<PathBuf>,
     web: bool,
+    host: String,
     port: u16,
     tui: bool,
     interval_ms: u64,
     event_count: u64,
     redaction_mode: String,
+    limits: RunLimits,
 }
 
 /// Demo mode - generates fake events to test the pipeline and UI
@@ -994,7 +1909,9 @@ async fn demo_command(config: DemoConfig) -> anyhow::Result<()> {
     pipeline.add_capture(Box::new(test_generator));
 
     // Add decoders
-    pipeline.add_decode(Box::new(HttpDecoder::new()));
+    pipeline.add_decode(Box::new(
+        HttpDecoder::new().with_metrics(pipeline.metrics()),
+    ));
     pipeline.add_decode(Box::new(SystemDecoder::new()));
 
     // Add enrichers
@@ -1012,14 +1929,16 @@ async fn demo_command(config: DemoConfig) -> anyhow::Result<()> {
         _ => RedactionPlugin::safe_mode(),
     };
     pipeline.add_action(Box::new(redaction));
+    let limit_reached = config.limits.install(&mut pipeline);
 
     // Add exporters
-    if let Some(output_path) = config.output {
+    if let Some(output_path) = config.output.clone() {
         pipeline.add_export(Box::new(JsonlExporter::new(JsonlExporterConfig {
             path: output_path.clone(),
             append: true,
             pretty: false,
             flush_each: true,
+            ..Default::default()
         })));
         println!("  Output: {}", output_path.display());
     }
@@ -1042,19 +1961,28 @@ async fn demo_command(config: DemoConfig) -> anyhow::Result<()> {
     pipeline.start().await?;
 
     info!("Demo pipeline started");
+    print_capture_errors(&pipeline);
 
     // Start web UI if requested
     if config.web {
         let web_config = oisp_web::WebConfig {
-            host: "0.0.0.0".to_string(),
+            host: config.host.clone(),
             port: config.port,
+            tls: None,
+            auth: None,
+            cors_origins: Vec::new(),
+            history: config.output.clone(),
         };
 
         let event_tx = pipeline.event_sender();
         let tb = trace_builder.clone();
+        let metrics = pipeline.metrics();
 
         tokio::spawn(async move {
-            if let Err(e) = oisp_web::start_server(web_config, event_tx, tb).await {
+            if let Err(e) =
+                oisp_web::start_server_with_metrics(web_config, event_tx, tb, Some(metrics), None)
+                    .await
+            {
                 error!("Web server error: {}", e);
             }
         });
@@ -1070,8 +1998,7 @@ async fn demo_command(config: DemoConfig) -> anyhow::Result<()> {
     if config.tui {
         oisp_tui::run(event_rx).await?;
     } else {
-        // Wait for Ctrl+C
-        tokio::signal::ctrl_c().await?;
+        config.limits.wait(limit_reached, None).await?;
     }
 
     // Cleanup
@@ -1086,8 +2013,13 @@ struct ReplayCommandConfig {
     speed: f64,
     loop_playback: bool,
     web: bool,
+    host: String,
     port: u16,
     tui: bool,
+    to: Option<String>,
+    output: Option<PathBuf>,
+    export: ExportSettings,
+    redaction: RedactionSettings,
 }
 
 /// Replay mode - replays recorded events from a JSONL file
@@ -1124,6 +2056,10 @@ async fn replay_command(config: ReplayCommandConfig) -> anyhow::Result<()> {
     println!("  Found {} events to replay", event_count);
     println!();
 
+    if let Some(to) = &config.to {
+        return replay_to_exporter(&config, to).await;
+    }
+
     info!("Starting OISP Sensor in replay mode...");
 
     // Create broadcast channel for events (same as pipeline uses)
@@ -1144,8 +2080,12 @@ async fn replay_command(config: ReplayCommandConfig) -> anyhow::Result<()> {
     // Start web UI if requested
     if config.web {
         let web_config = oisp_web::WebConfig {
-            host: "0.0.0.0".to_string(),
+            host: config.host.clone(),
             port: config.port,
+            tls: None,
+            auth: None,
+            cors_origins: Vec::new(),
+            history: None,
         };
 
         let event_tx_clone = event_tx.clone();
@@ -1196,8 +2136,154 @@ async fn replay_command(config: ReplayCommandConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Replay events through a pipeline with a single exporter
+///
+/// Events are already decoded, so capture and decode are skipped and each
+/// event goes straight to the export stage.
+async fn replay_to_exporter(config: &ReplayCommandConfig, to: &str) -> anyhow::Result<()> {
+    let mut exporter = replay_exporter(to, config.output.as_deref(), &config.export).await?;
+    // Recorded events are already redacted as recorded; only an exporter's
+    // own mode redacts them further
+    if let Some(mode) = config.export.redaction_mode(to) {
+        exporter = Box::new(RedactingExporter::new(
+            exporter,
+            config.redaction.for_exporter(Some(mode)),
+        ));
+    }
+    println!("  Exporting to: {}", to);
+    println!();
+
+    let mut pipeline = Pipeline::new(PipelineConfig::default());
+    if let Some(limit) = config.export.max_in_flight(to) {
+        let gauge = pipeline.metrics().export_in_flight_gauge(exporter.name());
+        exporter = Box::new(BoundedExporter::new(exporter, limit, gauge));
+    }
+    pipeline.add_export(exporter);
+
+    let replay = EventReplay::new(ReplayConfig {
+        input_file: config.input.clone(),
+        speed_multiplier: config.speed,
+        loop_playback: config.loop_playback,
+    });
+    let stop_handle = replay.stop_handle();
+
+    tokio::select! {
+        result = replay.run_through(&pipeline) => {
+            let count = result?;
+            info!("Replay finished: {} events", count);
+            println!("  Replayed {} events", count);
+        }
+        _ = tokio::signal::ctrl_c() => {
+            info!("Ctrl+C received, stopping replay...");
+            stop_handle.store(false, std::sync::atomic::Ordering::Relaxed);
+            pipeline.flush().await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the exporter for `replay --to`
+async fn replay_exporter(
+    to: &str,
+    output: Option<&std::path::Path>,
+    export: &ExportSettings,
+) -> anyhow::Result<Box<dyn ExportPlugin>> {
+    match to {
+        "jsonl" => {
+            let path = output
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(&export.jsonl.path));
+            Ok(Box::new(JsonlExporter::new(JsonlExporterConfig {
+                path,
+                append: export.jsonl.append,
+                pretty: export.jsonl.pretty,
+                flush_each: false,
+                schema: export
+                    .jsonl
+                    .schema_version
+                    .as_deref()
+                    .map(SchemaTransform::new)
+                    .transpose()?,
+                ..Default::default()
+            })))
+        }
+        "oximy" => {
+            let oximy_config = oisp_oximy::OximyConfig::from_export_config(&export.oximy);
+            let credentials = oisp_oximy::enroll_device(&oximy_config).await?;
+            let client = Arc::new(oisp_oximy::CloudClient::try_new(oximy_config)?);
+            client.set_credentials(credentials).await;
+            Ok(Box::new(oisp_oximy::OximyExporter::new(
+                client,
+                oisp_oximy::OximyExporterConfig {
+                    offline_queue_path: export.oximy.offline_queue_path.clone(),
+                    ..Default::default()
+                },
+            )?))
+        }
+        #[cfg(feature = "otlp")]
+        "otlp" => {
+            use oisp_core::plugins::{Plugin, PluginConfig};
+            use oisp_export::{OtlpExporter, OtlpExporterConfig};
+
+            let mut plugin_config = PluginConfig::new();
+            plugin_config.set("endpoint", &export.otlp.endpoint);
+            plugin_config.set("transport", &export.otlp.protocol);
+            plugin_config.set("compression", export.otlp.compression);
+            plugin_config.set("batch_size", export.otlp.batch_size);
+            plugin_config.set("headers", &export.otlp.headers);
+            if let Some(api_key) = &export.otlp.api_key {
+                plugin_config.set("api_key", api_key);
+            }
+            if let Some(token) = &export.otlp.bearer_token {
+                plugin_config.set("bearer_token", token);
+            }
+
+            let mut exporter = OtlpExporter::new(OtlpExporterConfig::default());
+            exporter.init(&plugin_config)?;
+            Ok(Box::new(exporter))
+        }
+        #[cfg(feature = "kafka")]
+        "kafka" => {
+            use oisp_core::plugins::{Plugin, PluginConfig};
+            use oisp_export::{KafkaExporter, KafkaExporterConfig};
+
+            let mut plugin_config = PluginConfig::new();
+            plugin_config.set("bootstrap_servers", &export.kafka.brokers);
+            plugin_config.set("topic", &export.kafka.topic);
+            plugin_config.set("tls", export.kafka.tls);
+            plugin_config.set("compression", &export.kafka.compression);
+            plugin_config.set("batch_size", export.kafka.batch_size);
+            plugin_config.set("linger_ms", export.kafka.linger_ms);
+            plugin_config.set("key_by_event_id", export.kafka.key_mode == "event_id");
+            if let Some(version) = &export.kafka.schema_version {
+                plugin_config.set("schema_version", version);
+            }
+            if let Some(mechanism) = &export.kafka.sasl_mechanism {
+                plugin_config.set("sasl_mechanism", mechanism);
+            }
+            if let Some(username) = &export.kafka.sasl_username {
+                plugin_config.set("sasl_username", username);
+            }
+            if let Some(password) = &export.kafka.sasl_password {
+                plugin_config.set("sasl_password", password);
+            }
+
+            let mut exporter = KafkaExporter::new(KafkaExporterConfig::default());
+            exporter.init(&plugin_config)?;
+            Ok(Box::new(exporter))
+        }
+        other => anyhow::bail!(
+            "Exporter '{}' is not available in this build (rebuild with --features {})",
+            other,
+            other
+        ),
+    }
+}
+
 async fn test_command() -> anyhow::Result<()> {
     println!("Running sensor self-test...\n");
+    let mut failures = 0;
 
     // Test 1: Event creation
     print!("  Creating test events... ");
@@ -1217,6 +2303,9 @@ async fn test_command() -> anyhow::Result<()> {
         oisp_core::redaction::redact("My API key is sk-proj-abc123def456ghi789jkl0", &config);
     let passed = result.content.contains("[API_KEY_REDACTED]");
     println!("{}", if passed { "OK" } else { "FAILED" });
+    if !passed {
+        failures += 1;
+    }
 
     // Test 4: JSON serialization
     print!("  Testing JSON serialization... ");
@@ -1225,6 +2314,27 @@ async fn test_command() -> anyhow::Result<()> {
     let _: oisp_core::events::envelope::EventEnvelope = serde_json::from_str(&json)?;
     println!("OK");
 
+    // Test 5: eBPF load (the step most likely to fail on a new host)
+    print!("  Loading eBPF programs... ");
+    #[cfg(target_os = "linux")]
+    {
+        let mut capture = EbpfCapture::with_config(EbpfCaptureConfig::default());
+        match capture.check_load() {
+            Ok(()) => println!("OK"),
+            Err(message) => {
+                println!("FAILED");
+                println!("    {}", message);
+                failures += 1;
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    println!("SKIPPED (Linux only)");
+
+    if failures > 0 {
+        anyhow::bail!("{} self-test(s) failed", failures);
+    }
+
     println!("\nAll tests passed!\n");
 
     Ok(())
@@ -1273,147 +2383,286 @@ const EDGE_CASE_PATHS: &[(&str, &str)] = &[
     ("~/anaconda3/bin/python", "Anaconda (bundles own OpenSSL)"),
 ];
 
-async fn check_command() -> anyhow::Result<()> {
-    println!();
-    println!("OISP Sensor System Check");
-    println!("========================");
-    println!();
+/// Result of one `check` item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn tag(self) -> String {
+        match self {
+            CheckStatus::Ok => style::ok("[OK]"),
+            CheckStatus::Warn => style::warn("[WARN]"),
+            CheckStatus::Fail => style::fail("[FAIL]"),
+        }
+    }
+}
 
-    #[allow(unused_assignments)]
-    let mut all_ok = true;
-    let mut warnings = Vec::new();
+#[derive(Debug, Clone, serde::Serialize)]
+struct CheckItem {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    /// Shown after the status tag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
 
-    // Platform info
-    println!(
-        "Platform: {} {} ({})",
-        std::env::consts::OS,
-        std::env::consts::ARCH,
-        if cfg!(target_os = "linux") {
-            "supported"
-        } else {
-            "limited support"
+impl CheckItem {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            note: None,
         }
-    );
+    }
 
-    // Distribution info
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(distro_info) = detect_linux_distribution() {
-            println!("Distribution: {} {}", distro_info.0, distro_info.1);
+    fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    fn render(&self) -> String {
+        let label = format!("{}:", self.name);
+        let mut line = format!("{:<19}{} {}", label, self.detail, self.status.tag());
+        if let Some(note) = &self.note {
+            line.push_str(&format!(" ({})", note));
         }
+        line
     }
+}
 
-    println!();
+/// Everything `check` finds, printed as text or with `--json`
+#[derive(Debug, Clone, serde::Serialize)]
+struct CheckReport {
+    os: &'static str,
+    arch: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distribution: Option<String>,
+    /// Platform requirements (Linux only)
+    checks: Vec<CheckItem>,
+    ssl_libraries: Vec<String>,
+    spec_bundle: CheckItem,
+    config_file: Option<PathBuf>,
+    warnings: Vec<String>,
+    ready: bool,
+}
+
+fn run_checks() -> CheckReport {
+    #[allow(unused_mut)]
+    let mut checks = Vec::new();
+    let mut warnings = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    let distribution = detect_linux_distribution()
+        .ok()
+        .map(|(name, version)| format!("{} {}", name, version));
+    #[cfg(not(target_os = "linux"))]
+    let distribution = None;
+
+    #[cfg(target_os = "linux")]
+    let ssl_libraries: Vec<String> = SSL_LIBRARY_PATHS
+        .iter()
+        .filter(|path| std::path::Path::new(path).exists())
+        .map(|path| path.to_string())
+        .collect();
+    #[cfg(not(target_os = "linux"))]
+    let ssl_libraries = Vec::new();
 
-    // Linux-specific checks
     #[cfg(target_os = "linux")]
     {
         // Check 1: Kernel version
-        print!("Kernel Version:    ");
-        match check_kernel_version() {
-            Ok((major, minor, patch, release)) => {
+        checks.push(match check_kernel_version() {
+            Ok((major, minor, patch, _release)) => {
+                let version = format!("{}.{}.{}", major, minor, patch);
                 if major >= 5 {
-                    println!("{}.{}.{} [OK]", major, minor, patch);
+                    CheckItem::new("Kernel Version", CheckStatus::Ok, version)
                 } else if major == 4 && minor >= 18 {
-                    println!("{}.{}.{} [OK] (minimum supported)", major, minor, patch);
+                    CheckItem::new("Kernel Version", CheckStatus::Ok, version)
+                        .with_note("minimum supported")
                 } else {
-                    println!("{}.{}.{} [FAIL] (requires >= 4.18)", major, minor, patch);
-                    all_ok = false;
+                    CheckItem::new("Kernel Version", CheckStatus::Fail, version)
+                        .with_note("requires >= 4.18")
                 }
-                let _ = release; // Used for debug if needed
             }
             Err(e) => {
-                println!("Unknown [WARN] ({})", e);
                 warnings.push("Could not determine kernel version".to_string());
+                CheckItem::new("Kernel Version", CheckStatus::Warn, "Unknown")
+                    .with_note(e.to_string())
             }
-        }
+        });
 
         // Check 2: BTF availability
-        print!("BTF Support:       ");
-        let btf_path = std::path::Path::new("/sys/kernel/btf/vmlinux");
-        if btf_path.exists() {
-            println!("/sys/kernel/btf/vmlinux [OK]");
+        if std::path::Path::new("/sys/kernel/btf/vmlinux").exists() {
+            checks.push(CheckItem::new(
+                "BTF Support",
+                CheckStatus::Ok,
+                "/sys/kernel/btf/vmlinux",
+            ));
         } else {
-            println!("Not found [WARN]");
+            checks.push(CheckItem::new(
+                "BTF Support",
+                CheckStatus::Warn,
+                "Not found",
+            ));
             warnings.push(
                 "BTF not found - may need CONFIG_DEBUG_INFO_BTF=y or kernel headers".to_string(),
             );
         }
 
         // Check 3: eBPF filesystem
-        print!("eBPF Filesystem:   ");
-        let bpf_path = std::path::Path::new("/sys/fs/bpf");
-        if bpf_path.exists() {
-            println!("/sys/fs/bpf [OK]");
+        if std::path::Path::new("/sys/fs/bpf").exists() {
+            checks.push(CheckItem::new(
+                "eBPF Filesystem",
+                CheckStatus::Ok,
+                "/sys/fs/bpf",
+            ));
         } else {
-            println!("Not found [FAIL]");
-            all_ok = false;
+            checks.push(CheckItem::new(
+                "eBPF Filesystem",
+                CheckStatus::Fail,
+                "Not found",
+            ));
         }
 
         // Check 4: Running as root or capabilities
-        print!("Permissions:       ");
         let uid = unsafe { libc::getuid() };
-        if uid == 0 {
-            println!("root [OK]");
+        checks.push(if uid == 0 {
+            CheckItem::new("Permissions", CheckStatus::Ok, "root")
         } else {
-            // Check for capabilities
-            if let Ok(caps) = check_capabilities() {
-                if caps {
-                    println!("CAP_BPF+CAP_PERFMON set [OK]");
-                } else {
-                    println!("No capabilities [WARN]");
+            match check_capabilities() {
+                Ok(true) => {
+                    CheckItem::new("Permissions", CheckStatus::Ok, "CAP_BPF+CAP_PERFMON set")
+                }
+                Ok(false) => {
                     warnings.push(
                         "Not running as root and no capabilities set - run with sudo or set capabilities"
                             .to_string(),
                     );
+                    CheckItem::new("Permissions", CheckStatus::Warn, "No capabilities")
+                }
+                Err(_) => {
+                    warnings.push(
+                        "Not running as root - SSL capture requires root or CAP_BPF+CAP_PERFMON"
+                            .to_string(),
+                    );
+                    CheckItem::new("Permissions", CheckStatus::Warn, "No")
                 }
-            } else {
-                println!("No [WARN]");
-                warnings.push(
-                    "Not running as root - SSL capture requires root or CAP_BPF+CAP_PERFMON"
-                        .to_string(),
-                );
             }
-        }
+        });
 
         // Check 5: Systemd availability
-        print!("Systemd:           ");
         if std::process::Command::new("systemctl")
             .arg("--version")
             .output()
             .is_ok()
         {
-            println!("Available [OK]");
+            checks.push(CheckItem::new("Systemd", CheckStatus::Ok, "Available"));
         } else {
-            println!("Not found [WARN]");
+            checks.push(CheckItem::new("Systemd", CheckStatus::Warn, "Not found"));
             warnings.push("systemd not available - use manual process management".to_string());
         }
 
         // Check 6: SSL Libraries
+        if ssl_libraries.is_empty() {
+            warnings.push("No system OpenSSL found - SSL capture may not work".to_string());
+        }
+    }
+
+    let spec_bundle = match load_spec_bundle_info() {
+        Ok((version, providers, models)) => CheckItem::new(
+            "Spec Bundle",
+            CheckStatus::Ok,
+            format!("v{} ({} providers, {} models)", version, providers, models),
+        ),
+        Err(e) => {
+            warnings.push("Spec bundle could not be loaded".to_string());
+            CheckItem::new(
+                "Spec Bundle",
+                CheckStatus::Warn,
+                format!("Error loading: {}", e),
+            )
+        }
+    };
+
+    // Full SSL capture is only available on Linux
+    let ready = cfg!(target_os = "linux") && checks.iter().all(|c| c.status != CheckStatus::Fail);
+
+    CheckReport {
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        distribution,
+        checks,
+        ssl_libraries,
+        spec_bundle,
+        config_file: ConfigLoader::new().find_config_file(),
+        warnings,
+        ready,
+    }
+}
+
+async fn check_command(json: bool) -> anyhow::Result<()> {
+    let report = run_checks();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!();
+    println!("{}", style::heading("OISP Sensor System Check"));
+    println!("========================");
+    println!();
+
+    // Platform info
+    println!(
+        "Platform: {} {} ({})",
+        report.os,
+        report.arch,
+        if cfg!(target_os = "linux") {
+            "supported"
+        } else {
+            "limited support"
+        }
+    );
+    if let Some(distribution) = &report.distribution {
+        println!("Distribution: {}", distribution);
+    }
+    println!();
+
+    // Linux-specific checks
+    #[cfg(target_os = "linux")]
+    {
+        for check in &report.checks {
+            println!("{}", check.render());
+        }
+
         println!();
-        println!("SSL Libraries:");
-        let mut found_ssl = false;
-        for path in SSL_LIBRARY_PATHS {
-            if std::path::Path::new(path).exists() {
-                println!("  {} [FOUND]", path);
-                found_ssl = true;
-            }
+        println!("{}", style::heading("SSL Libraries:"));
+        for path in &report.ssl_libraries {
+            println!("  {} {}", path, style::ok("[FOUND]"));
         }
-        if !found_ssl {
-            println!("  No system SSL libraries found [WARN]");
-            warnings.push("No system OpenSSL found - SSL capture may not work".to_string());
+        if report.ssl_libraries.is_empty() {
+            println!("  No system SSL libraries found {}", style::warn("[WARN]"));
         }
 
-        // Check 7: Edge cases notice
+        // Edge cases notice
         println!();
-        println!("Edge Cases (require binary_paths config):");
+        println!(
+            "{}",
+            style::heading("Edge Cases (require binary_paths config):")
+        );
         for (pattern, desc) in EDGE_CASE_PATHS {
             println!("  {} - {}", pattern, desc);
         }
 
         // Note about unsupported TLS
         println!();
-        println!("Unsupported TLS Libraries:");
+        println!("{}", style::heading("Unsupported TLS Libraries:"));
         println!("  Go crypto/tls, rustls, BoringSSL, GnuTLS, NSS");
         println!("  Run 'oisp-sensor ssl-info' for detailed TLS library information.");
     }
@@ -1423,56 +2672,47 @@ async fn check_command() -> anyhow::Result<()> {
     {
         println!("Note: Full SSL capture is only available on Linux.");
         println!("      This platform has limited functionality.");
-        all_ok = false;
     }
 
-    // Check spec bundle
     println!();
-    print!("Spec Bundle:       ");
-    match load_spec_bundle_info() {
-        Ok((version, providers, models)) => {
-            println!(
-                "v{} ({} providers, {} models) [OK]",
-                version, providers, models
-            );
-        }
-        Err(e) => {
-            println!("Error loading: {} [WARN]", e);
-            warnings.push("Spec bundle could not be loaded".to_string());
-        }
-    }
+    println!("{}", report.spec_bundle.render());
 
-    // Check config file
     println!();
-    print!("Config File:       ");
-    let loader = ConfigLoader::new();
-    if let Some(path) = loader.find_config_file() {
-        println!("{} [FOUND]", path.display());
-    } else {
-        println!("Not found (using defaults) [OK]");
+    match &report.config_file {
+        Some(path) => println!(
+            "{:<19}{} {}",
+            "Config File:",
+            path.display(),
+            style::ok("[FOUND]")
+        ),
+        None => println!(
+            "{:<19}Not found (using defaults) {}",
+            "Config File:",
+            style::ok("[OK]")
+        ),
     }
 
     // Summary
     println!();
     println!("========================");
-    if all_ok && warnings.is_empty() {
-        println!("Result: READY");
+    if report.ready && report.warnings.is_empty() {
+        println!("Result: {}", style::ok("READY"));
         println!();
         println!("Run 'sudo oisp-sensor record' to start capturing.");
-    } else if all_ok {
-        println!("Result: READY (with warnings)");
+    } else if report.ready {
+        println!("Result: {}", style::warn("READY (with warnings)"));
         println!();
         println!("Warnings:");
-        for w in &warnings {
+        for w in &report.warnings {
             println!("  - {}", w);
         }
         println!();
         println!("Run 'sudo oisp-sensor record' to start capturing.");
     } else {
-        println!("Result: NOT READY");
+        println!("Result: {}", style::fail("NOT READY"));
         println!();
         println!("Issues:");
-        for w in &warnings {
+        for w in &report.warnings {
             println!("  - {}", w);
         }
         println!();
@@ -1549,10 +2789,133 @@ fn load_spec_bundle_info() -> anyhow::Result<(String, usize, usize)> {
     Ok((bundle.version, providers, models))
 }
 
+async fn diagnostics_command(
+    cmd: DiagnosticsCommands,
+    sensor_config: &SensorConfig,
+    config_path: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    match cmd {
+        DiagnosticsCommands::Bundle { output, log_lines } => {
+            let check = serde_json::to_value(run_checks())?;
+            let contents = diagnostics::BundleContents {
+                config: sensor_config.clone(),
+                config_file: ConfigLoader::new()
+                    .with_cli_path(config_path)
+                    .find_config_file(),
+                check,
+                stats: diagnostics::fetch_stats(&sensor_config.web).await,
+                logs: sensor_logs(log_lines),
+            };
+            let files = diagnostics::write_bundle(&output, &contents)?;
+
+            println!("Wrote {}", output.display());
+            for file in &files {
+                println!("  {}", file);
+            }
+            println!();
+            println!("Credentials are redacted and captured events are not included.");
+            println!("Review the bundle before sharing it.");
+            Ok(())
+        }
+    }
+}
+
+/// Recent sensor logs: the systemd journal, or log files in the log directory
+fn sensor_logs(lines: usize) -> Vec<(String, String)> {
+    #[cfg(target_os = "linux")]
+    {
+        if std::path::Path::new("/run/systemd/system").exists()
+            && std::path::Path::new("/etc/systemd/system/oisp-sensor.service").exists()
+        {
+            let output = std::process::Command::new("journalctl")
+                .args(["-u", "oisp-sensor", "--no-pager", "-o", "short-iso", "-n"])
+                .arg(lines.to_string())
+                .output();
+            if let Ok(output) = output {
+                if output.status.success() {
+                    let text = String::from_utf8_lossy(&output.stdout).into_owned();
+                    return vec![("journal.log".to_string(), text)];
+                }
+            }
+        }
+    }
+    let _ = lines;
+    diagnostics::collect_log_files(std::path::Path::new(LOG_DIR))
+}
+
+fn providers_command(cmd: ProvidersCommands) -> anyhow::Result<()> {
+    match cmd {
+        ProvidersCommands::List {
+            filter,
+            models,
+            json,
+        } => providers_list(filter.as_deref(), models, json),
+    }
+}
+
+/// Print the providers of the loaded spec bundle
+fn providers_list(filter: Option<&str>, models: bool, json: bool) -> anyhow::Result<()> {
+    let bundle = oisp_core::global_spec_bundle();
+    let providers = bundle.list_providers(filter);
+
+    if json {
+        let mut providers = serde_json::to_value(&providers)?;
+        if !models {
+            for provider in providers.as_array_mut().into_iter().flatten() {
+                if let Some(fields) = provider.as_object_mut() {
+                    fields.remove("models");
+                }
+            }
+        }
+        let output = serde_json::json!({
+            "version": bundle.version,
+            "bundle_version": bundle.bundle_version,
+            "generated_at": bundle.generated_at,
+            "providers": providers,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!(
+        "Spec bundle v{} (format {}, generated {})",
+        bundle.version, bundle.bundle_version, bundle.generated_at
+    );
+    println!();
+    if providers.is_empty() {
+        match filter {
+            Some(filter) => println!("No providers match \"{}\"", filter),
+            None => println!("The spec bundle lists no providers"),
+        }
+        return Ok(());
+    }
+
+    println!("{:<20} {:<24} DOMAINS", "PROVIDER", "NAME");
+    for provider in &providers {
+        let domains = if provider.domains.is_empty() {
+            "-".to_string()
+        } else {
+            provider.domains.join(", ")
+        };
+        println!(
+            "{:<20} {:<24} {}",
+            provider.id, provider.display_name, domains
+        );
+        if models {
+            for model in &provider.models {
+                println!("    {}", model);
+            }
+        }
+    }
+    println!();
+    println!("{} providers", providers.len());
+    Ok(())
+}
+
 /// Diagnose SSL capture capability for a specific process
 async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyhow::Result<()> {
     println!();
-    println!("OISP Sensor Process Diagnosis");
+    println!("{}", style::heading("OISP Sensor Process Diagnosis"));
     println!("==============================");
     println!();
     println!("Target PID: {}", pid);
@@ -1567,12 +2930,12 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
 
         // Check if process exists
         if !Path::new(&proc_path).exists() {
-            println!("ERROR: Process {} does not exist", pid);
+            println!("{} Process {} does not exist", style::fail("ERROR:"), pid);
             return Ok(());
         }
 
         // Basic process info
-        println!("Process Information:");
+        println!("{}", style::heading("Process Information:"));
         println!("--------------------");
 
         // Executable path
@@ -1610,7 +2973,7 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
 
         // SSL Library Detection
         println!();
-        println!("SSL Libraries Loaded:");
+        println!("{}", style::heading("SSL Libraries Loaded:"));
         println!("----------------------");
 
         if let Ok(maps) = fs::read_to_string(format!("{}/maps", proc_path)) {
@@ -1636,15 +2999,10 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
             }
 
             if ssl_libs.is_empty() {
-                println!("  No libssl.so loaded [WARN]");
-                println!();
-                println!("  This process may:");
-                println!("    - Not use SSL/TLS");
-                println!("    - Use a statically linked SSL library");
-                println!("    - Use a non-OpenSSL TLS implementation (rustls, BoringSSL, etc.)");
+                println!("  No libssl.so loaded {}", style::warn("[WARN]"));
             } else {
                 for lib in &ssl_libs {
-                    println!("  {} [OK]", lib);
+                    println!("  {} {}", lib, style::ok("[OK]"));
                     // Try to get version
                     if let Ok(output) = std::process::Command::new("strings")
                         .args([lib, "-a"])
@@ -1672,7 +3030,7 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
             // Full memory maps if requested
             if show_maps {
                 println!();
-                println!("Full Memory Maps (libraries only):");
+                println!("{}", style::heading("Full Memory Maps (libraries only):"));
                 println!("-----------------------------------");
                 for line in maps.lines() {
                     if line.contains(".so") && line.contains('/') {
@@ -1691,7 +3049,7 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
         // Network connections if requested
         if show_network {
             println!();
-            println!("Network Connections:");
+            println!("{}", style::heading("Network Connections:"));
             println!("--------------------");
 
             // Count file descriptors that are sockets
@@ -1722,38 +3080,28 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
 
         // Capture recommendation
         println!();
-        println!("Capture Recommendation:");
+        println!("{}", style::heading("Capture Recommendation:"));
         println!("-----------------------");
 
         if let Ok(maps) = fs::read_to_string(format!("{}/maps", proc_path)) {
-            let has_system_ssl = maps.lines().any(|l| {
-                l.contains("libssl.so")
-                    && (l.contains("/usr/lib")
-                        || l.contains("/lib/x86_64")
-                        || l.contains("/lib/aarch64"))
-            });
-
-            if has_system_ssl {
-                println!("  This process uses system OpenSSL.");
-                println!("  SSL capture should work with default configuration.");
-                println!();
-                println!("  Command: sudo oisp-sensor record --pid {}", pid);
-            } else if maps.lines().any(|l| l.contains("libssl")) {
-                println!("  This process uses a non-system OpenSSL.");
-                println!("  You may need to configure binary_paths in your config.");
-                println!();
-                println!("  Add to config.yaml:");
-                println!("    capture:");
-                println!("      ssl_binary_paths:");
-                println!("        - /path/to/custom/libssl.so");
+            // Statically linked TLS only shows up in the executable itself
+            let exe_path = fs::read_link(format!("{}/exe", proc_path))
+                .ok()
+                .map(|p| p.to_string_lossy().into_owned());
+            let exe = fs::read(format!("{}/exe", proc_path)).unwrap_or_default();
+            let tls = tls_detect::TlsLibrary::detect(&maps, exe_path.as_deref(), &exe);
+
+            println!("  TLS library: {}", tls.name());
+            if tls.capture_supported() {
+                println!("  SSL capture: {}", style::ok("supported"));
             } else {
-                println!("  This process does not appear to use OpenSSL.");
-                println!("  SSL capture may not work for this process.");
-                println!();
-                println!("  Possible reasons:");
-                println!("    - Process uses static SSL (NVM Node.js, some Python builds)");
-                println!("    - Process uses alternative TLS (rustls, BoringSSL, GnuTLS)");
-                println!("    - Process hasn't loaded SSL libraries yet");
+                println!("  SSL capture: not supported {}", style::warn("[WARN]"));
+            }
+            println!();
+            println!("  {}", tls.explanation());
+            println!();
+            for line in tls.alternative(pid) {
+                println!("  {}", line);
             }
         }
     }
@@ -1771,18 +3119,17 @@ async fn diagnose_command(pid: u32, show_maps: bool, show_network: bool) -> anyh
 /// Show SSL library information on the system
 async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()> {
     println!();
-    println!("OISP Sensor SSL Library Information");
+    println!("{}", style::heading("OISP Sensor SSL Library Information"));
     println!("====================================");
     println!();
 
     #[cfg(target_os = "linux")]
     {
-        use std::collections::HashMap;
         use std::fs;
         use std::process::Command;
 
         // Find all SSL libraries
-        println!("System SSL Libraries:");
+        println!("{}", style::heading("System SSL Libraries:"));
         println!("---------------------");
 
         let mut found_libs: Vec<(String, Option<String>)> = Vec::new();
@@ -1838,7 +3185,7 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
         }
 
         if found_libs.is_empty() {
-            println!("  No SSL libraries found [WARN]");
+            println!("  No SSL libraries found {}", style::warn("[WARN]"));
         } else {
             for (path, version) in &found_libs {
                 println!("  {}", path);
@@ -1875,7 +3222,7 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
         // Show process usage
         if show_usage {
             println!();
-            println!("Processes Using SSL Libraries:");
+            println!("{}", style::heading("Processes Using SSL Libraries:"));
             println!("------------------------------");
 
             let mut lib_users: HashMap<String, Vec<(u32, String)>> = HashMap::new();
@@ -1934,7 +3281,7 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
 
         // Check for alternative TLS libraries (mostly unsupported)
         println!();
-        println!("Alternative TLS Libraries:");
+        println!("{}", style::heading("Alternative TLS Libraries:"));
         println!("--------------------------");
 
         let alt_tls_libs: &[(&str, &str, &str)] = &[
@@ -1956,7 +3303,12 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
         for (path, name, status) in alt_tls_libs {
             if std::path::Path::new(path).exists() {
                 found_alt = true;
-                println!("  {} at {} [{}]", name, path, status);
+                println!(
+                    "  {} at {} {}",
+                    name,
+                    path,
+                    style::fail(format!("[{}]", status))
+                );
             }
         }
 
@@ -1966,7 +3318,10 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
 
         // Unsupported TLS implementations info
         println!();
-        println!("Known Unsupported TLS Implementations:");
+        println!(
+            "{}",
+            style::heading("Known Unsupported TLS Implementations:")
+        );
         println!("--------------------------------------");
         println!("  • BoringSSL   - Used by: Chrome, gRPC, some Go apps");
         println!("  • GnuTLS      - Used by: wget, some GNOME apps");
@@ -1979,7 +3334,7 @@ async fn ssl_info_command(detailed: bool, show_usage: bool) -> anyhow::Result<()
 
         // Edge cases reminder
         println!();
-        println!("Edge Cases (may not use system SSL):");
+        println!("{}", style::heading("Edge Cases (may not use system SSL):"));
         println!("-------------------------------------");
         for (pattern, desc) in EDGE_CASE_PATHS {
             println!("  {} - {}", pattern, desc);
@@ -2469,3 +3824,387 @@ fn format_uptime(start_jiffies: u64) -> String {
         format!("{}d {}h", uptime_secs / 86400, (uptime_secs % 86400) / 3600)
     }
 }
+
+/// Why local state cannot be reset right now, if the daemon is running
+fn running_daemon() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        if std::path::Path::new("/run/systemd/system").exists()
+            && std::path::Path::new("/etc/systemd/system/oisp-sensor.service").exists()
+        {
+            let active = std::process::Command::new("systemctl")
+                .args(["is-active", "--quiet", "oisp-sensor"])
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if active {
+                return Some("the oisp-sensor systemd service is active".to_string());
+            }
+        }
+    }
+
+    read_pid_file()
+        .filter(|pid| is_process_running(*pid))
+        .map(|pid| format!("the daemon is running (PID: {})", pid))
+}
+
+/// Remove a file, returning whether it existed
+fn remove_if_exists(path: &std::path::Path) -> std::io::Result<bool> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn reset_command(
+    sensor_config: &SensorConfig,
+    credentials: bool,
+    queue: bool,
+    logs: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    use oisp_oximy::{CredentialStore, FileCredentialStore, OximyConfig};
+    use std::io::{BufRead, Write};
+
+    if !(credentials || queue || logs) {
+        anyhow::bail!("Nothing to reset: pass --credentials, --queue, --logs or --all");
+    }
+    if let Some(reason) = running_daemon() {
+        anyhow::bail!(
+            "Refusing to reset while {}. Stop it first with 'oisp-sensor daemon stop'.",
+            reason
+        );
+    }
+
+    let mut oximy_config = OximyConfig::from_export_config(&sensor_config.export.oximy);
+    if let Ok(path) = std::env::var("OISP_OXIMY_CREDENTIAL_PATH") {
+        oximy_config.credential_path = Some(path);
+    }
+    let store = FileCredentialStore::from_config(&oximy_config);
+
+    // The queue is SQLite, so its journal files go with it
+    let queue_db =
+        oisp_oximy::offline_queue_path(sensor_config.export.oximy.offline_queue_path.as_deref());
+    let queue_files: Vec<PathBuf> = ["", "-wal", "-shm", "-journal"]
+        .iter()
+        .map(|suffix| {
+            let mut name = queue_db.clone().into_os_string();
+            name.push(suffix);
+            PathBuf::from(name)
+        })
+        .collect();
+
+    let mut log_files: Vec<PathBuf> = Vec::new();
+    for path in [
+        PathBuf::from(&sensor_config.export.jsonl.path),
+        PathBuf::from(LOG_DIR).join("events.jsonl"),
+        PathBuf::from(&sensor_config.export.dead_letter.path),
+    ] {
+        if log_files.contains(&path) {
+            continue;
+        }
+        // Web history cursor and `oximy backfill` checkpoint
+        let sidecars: Vec<PathBuf> = [".cursor", ".checkpoint"]
+            .iter()
+            .map(|suffix| {
+                let mut name = path.clone().into_os_string();
+                name.push(suffix);
+                PathBuf::from(name)
+            })
+            .filter(|sidecar| sidecar.exists())
+            .collect();
+        log_files.push(path);
+        log_files.extend(sidecars);
+    }
+    if let Ok(entries) = std::fs::read_dir(LOG_DIR) {
+        log_files.extend(
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log")),
+        );
+    }
+
+    println!("This will permanently delete:");
+    if credentials {
+        println!("  credentials  {}", store.path().display());
+    }
+    if queue {
+        println!("  queue        {}", queue_db.display());
+    }
+    if logs {
+        for path in &log_files {
+            println!("  logs         {}", path.display());
+        }
+    }
+
+    if !yes {
+        print!("Continue? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    if credentials {
+        store.delete()?;
+        println!("Deleted credentials");
+    }
+    if queue {
+        for path in &queue_files {
+            remove_if_exists(path)?;
+        }
+        println!("Deleted offline queue");
+    }
+    if logs {
+        for path in &log_files {
+            if remove_if_exists(path)? {
+                println!("Deleted {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn oximy_command(cmd: OximyCommands, sensor_config: &SensorConfig) -> anyhow::Result<()> {
+    match cmd {
+        OximyCommands::Status { ping } => oximy_status(sensor_config, ping).await,
+        OximyCommands::Backfill {
+            input,
+            batch_size,
+            rate,
+            checkpoint,
+            restart,
+        } => {
+            let mut config = oisp_oximy::BackfillConfig::for_input(&input);
+            config.batch_size = batch_size;
+            config.max_events_per_sec = rate;
+            config.restart = restart;
+            config.redaction = sensor_config
+                .export
+                .oximy
+                .redaction
+                .as_deref()
+                .map(|mode| sensor_config.redaction.for_exporter(Some(mode)));
+            if let Some(checkpoint) = checkpoint {
+                config.checkpoint_path = checkpoint;
+            }
+            oximy_backfill(sensor_config, &input, config).await
+        }
+    }
+}
+
+async fn oximy_backfill(
+    sensor_config: &SensorConfig,
+    input: &std::path::Path,
+    config: oisp_oximy::BackfillConfig,
+) -> anyhow::Result<()> {
+    use oisp_oximy::{CloudClient, OximyConfig, OximyExporter, OximyExporterConfig};
+
+    let mut oximy_config = OximyConfig::from_export_config(&sensor_config.export.oximy);
+    if let Ok(path) = std::env::var("OISP_OXIMY_CREDENTIAL_PATH") {
+        oximy_config.credential_path = Some(path);
+    }
+    let credentials = oisp_oximy::enroll_device(&oximy_config).await?;
+    let client = Arc::new(CloudClient::try_new(oximy_config)?);
+    client.set_credentials(credentials).await;
+
+    // Undelivered events stay in the input file; queueing them offline too
+    // would send them twice once the run is resumed
+    let exporter = OximyExporter::new(
+        client,
+        OximyExporterConfig {
+            offline_queue_enabled: false,
+            ..Default::default()
+        },
+    )?;
+
+    let report = oisp_oximy::backfill(&exporter, input, &config)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "backfill stopped: {} (re-run to continue from the checkpoint in {})",
+                e,
+                config.checkpoint_path.display()
+            )
+        })?;
+
+    if report.resumed_from > 0 {
+        println!("Resumed after line {}", report.resumed_from);
+    }
+    println!(
+        "Sent {} events in {} batches (through line {})",
+        report.events_sent, report.batches_sent, report.last_line
+    );
+    if report.malformed_lines > 0 {
+        println!("Skipped {} malformed lines", report.malformed_lines);
+    }
+    Ok(())
+}
+
+async fn oximy_status(sensor_config: &SensorConfig, ping: bool) -> anyhow::Result<()> {
+    use oisp_oximy::{CloudClient, EnrollmentState, Enrollor, OximyConfig};
+
+    let mut config = OximyConfig::from_export_config(&sensor_config.export.oximy);
+    if let Ok(path) = std::env::var("OISP_OXIMY_CREDENTIAL_PATH") {
+        config.credential_path = Some(path);
+    }
+
+    let client = Arc::new(CloudClient::try_new(config)?);
+    let enrollor = Enrollor::new(client.clone());
+    let status = enrollor.status().await?;
+
+    println!();
+    println!("Oximy Cloud Connector");
+    println!("=====================");
+    println!();
+    println!("State:             {}", status.state);
+
+    if status.state != EnrollmentState::NotEnrolled {
+        println!(
+            "Device ID:         {}",
+            status.device_id.as_deref().unwrap_or("-")
+        );
+        println!(
+            "Organization:      {}",
+            status.organization_id.as_deref().unwrap_or("-")
+        );
+        println!(
+            "Workspace:         {}",
+            status.workspace_id.as_deref().unwrap_or("(none)")
+        );
+        if let Some(expires_at) = status.token_expires_at {
+            println!(
+                "Token expires:     {} ({})",
+                expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                oisp_oximy::humanize_expiry(expires_at, chrono::Utc::now())
+            );
+        }
+    }
+
+    println!("API endpoint:      {}", status.api_endpoint);
+    println!("Stream endpoint:   {}", status.stream_endpoint);
+    println!(
+        "Valid credentials: {}",
+        if status.has_valid_credentials {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+
+    if ping {
+        match client.http().ping().await {
+            Ok(code) => println!("Reachable:         yes (HTTP {})", code),
+            Err(e) => println!("Reachable:         no ({})", e),
+        }
+    }
+
+    match status.state {
+        EnrollmentState::Enrolled => {}
+        EnrollmentState::Expired => {
+            println!();
+            println!("Device token has expired; re-enroll with an API key or enrollment token.");
+        }
+        EnrollmentState::NotEnrolled => {
+            println!();
+            println!("Device is not enrolled with Oximy Cloud.");
+        }
+    }
+
+    println!();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::redaction::RedactionMode;
+
+    /// Parse `oisp-sensor record <args>` and merge it with `config`
+    fn merge(config: &SensorConfig, args: &[&str]) -> RecordConfig {
+        let matches = Cli::command()
+            .try_get_matches_from(["oisp-sensor", "record"].iter().chain(args))
+            .unwrap();
+        let Commands::Record(record) = Cli::from_arg_matches(&matches).unwrap().command else {
+            panic!("expected record");
+        };
+        merge_record_config(
+            config,
+            record,
+            matches.subcommand_matches("record").unwrap(),
+        )
+    }
+
+    fn config() -> SensorConfig {
+        let mut config = SensorConfig::default();
+        config.web.enabled = false;
+        config.web.port = 9000;
+        config.redaction.mode = "minimal".to_string();
+        config
+    }
+
+    #[test]
+    fn test_defaulted_flags_fall_back_to_config() {
+        let merged = merge(&config(), &[]);
+        assert!(!merged.web);
+        assert_eq!(merged.port, 9000);
+        assert_eq!(merged.redaction.mode, RedactionMode::Minimal);
+    }
+
+    #[test]
+    fn test_flags_equal_to_default_still_override_config() {
+        let merged = merge(
+            &config(),
+            &["--port", "7777", "--web", "--redaction", "safe"],
+        );
+        assert!(merged.web);
+        assert_eq!(merged.port, 7777);
+        assert_eq!(merged.redaction.mode, RedactionMode::Safe);
+    }
+
+    #[test]
+    fn test_cli_filters_and_disables_override_config() {
+        let mut config = config();
+        config.capture.process_filter = vec!["node".to_string()];
+        config.capture.ssl = true;
+
+        let merged = merge(
+            &config,
+            &[
+                "--process",
+                "python",
+                "--no-ssl",
+                "--port",
+                "8080",
+                "--attach-existing",
+            ],
+        );
+        assert_eq!(merged.process_filter, vec!["python".to_string()]);
+        assert!(!merged.ssl);
+        assert_eq!(merged.port, 8080);
+        assert!(merged.attach_existing);
+
+        let merged = merge(&config, &[]);
+        assert_eq!(merged.process_filter, vec!["node".to_string()]);
+        assert!(merged.ssl);
+        assert!(!merged.attach_existing);
+    }
+
+    #[test]
+    fn test_reload_restart_needed_beyond_log_level() {
+        let before = config();
+        let mut after = before.clone();
+        after.sensor.log_level = "debug".to_string();
+        assert!(!restart_needed(&before, &after));
+
+        after.redaction.mode = "full".to_string();
+        assert!(restart_needed(&before, &after));
+    }
+}
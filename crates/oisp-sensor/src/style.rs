@@ -0,0 +1,96 @@
+//! Terminal styling for command output
+//!
+//! `status`, `check`, `ssl-info` and `diagnose` color their markers and
+//! headings through these helpers. Color is used only when stdout is a
+//! terminal, `NO_COLOR` is unset or empty (<https://no-color.org>) and
+//! `--no-color` was not given; otherwise the helpers return the text
+//! unchanged, so piped output never contains escape codes.
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const RED: &str = "31";
+const BOLD: &str = "1";
+
+/// Decide once, at startup, whether output is colored
+pub fn init(no_color_flag: bool) {
+    let enabled = color_enabled(
+        no_color_flag,
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::io::stdout().is_terminal(),
+    );
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn color_enabled(no_color_flag: bool, no_color_env: Option<&OsStr>, stdout_tty: bool) -> bool {
+    !no_color_flag && no_color_env.is_none_or(OsStr::is_empty) && stdout_tty
+}
+
+fn paint(enabled: bool, code: &str, text: impl Display) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn styled(code: &str, text: impl Display) -> String {
+    paint(ENABLED.load(Ordering::Relaxed), code, text)
+}
+
+/// A passed check, e.g. `[OK]`
+pub fn ok(text: impl Display) -> String {
+    styled(GREEN, text)
+}
+
+/// Something that works with caveats, e.g. `[WARN]`
+pub fn warn(text: impl Display) -> String {
+    styled(YELLOW, text)
+}
+
+/// A failed check, e.g. `[FAIL]`
+pub fn fail(text: impl Display) -> String {
+    styled(RED, text)
+}
+
+/// A title or section heading
+pub fn heading(text: impl Display) -> String {
+    styled(BOLD, text)
+}
+
+/// A yes/no capability, green when true
+pub fn flag(value: bool) -> String {
+    if value {
+        ok(value)
+    } else {
+        warn(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_enabled() {
+        assert!(color_enabled(false, None, true));
+        // An empty NO_COLOR does not count as set
+        assert!(color_enabled(false, Some(OsStr::new("")), true));
+
+        assert!(!color_enabled(false, None, false));
+        assert!(!color_enabled(false, Some(OsStr::new("1")), true));
+        assert!(!color_enabled(true, None, true));
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint(true, GREEN, "[OK]"), "\x1b[32m[OK]\x1b[0m");
+        assert_eq!(paint(false, GREEN, "[OK]"), "[OK]");
+    }
+}
@@ -0,0 +1,398 @@
+//! TLS library detection for `oisp-sensor diagnose`
+//!
+//! SSL capture attaches uprobes to OpenSSL's `SSL_read`/`SSL_write`, so it
+//! only sees processes that map a libssl or carry OpenSSL's symbols in their
+//! executable. Everything else is identified from the library paths in
+//! `/proc/<pid>/maps` and, for TLS compiled into the executable, from marker
+//! strings in the binary, so the diagnosis can say why capture won't work
+//! instead of just that OpenSSL is missing.
+
+use std::path::Path;
+
+/// Section and build-info markers present in every Go binary
+const GO_MARKERS: &[&[u8]] = &[b"\xff Go buildinf:", b".gopclntab", b".go.buildinfo"];
+
+/// Source paths and version strings BoringSSL leaves in the binaries it is
+/// linked into (Chrome, Electron, gRPC)
+const BORINGSSL_MARKERS: &[&[u8]] = &[b"boringssl", b"BoringSSL"];
+
+/// Paths the Rust standard library leaves in every Rust binary
+const RUST_MARKERS: &[&[u8]] = &[b"/rustc/", b"RUST_BACKTRACE"];
+
+/// HTTP client crates that bring TLS along when no TLS library is mapped
+const RUST_HTTP_MARKERS: &[&[u8]] = &[b"reqwest", b"hyper"];
+
+/// TLS implementation a process uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsLibrary {
+    /// OpenSSL from a system library directory
+    SystemOpenSsl { path: String },
+    /// OpenSSL shipped outside the system directories (Conda, pyenv, vendored)
+    BundledOpenSsl { path: String },
+    /// OpenSSL linked into the executable with its symbols (NVM Node.js)
+    StaticOpenSsl { exe: String },
+    /// BoringSSL, compiled into the executable or a loaded module
+    BoringSsl { path: Option<String> },
+    /// GnuTLS shared library
+    GnuTls { path: String },
+    /// Mozilla NSS shared library
+    Nss { path: String },
+    /// Go's crypto/tls, compiled into the executable
+    GoCryptoTls,
+    /// rustls, compiled into a Rust executable
+    Rustls,
+    /// libcrypto without libssl: OpenSSL primitives but no OpenSSL TLS
+    CryptoOnly { path: String },
+    /// No TLS library found
+    None,
+}
+
+impl TlsLibrary {
+    /// Identify the TLS library from a maps file and the executable's contents
+    ///
+    /// `exe` may be empty when the executable cannot be read; detection then
+    /// relies on the maps alone.
+    pub fn detect(maps: &str, exe_path: Option<&str>, exe: &[u8]) -> Self {
+        let libs = mapped_paths(maps);
+        let find = |matches: fn(&str) -> bool| {
+            libs.iter()
+                .find(|path| matches(file_name(path)))
+                .map(|path| path.to_string())
+        };
+
+        if let Some(path) = find(is_openssl) {
+            return if is_system_path(&path) {
+                Self::SystemOpenSsl { path }
+            } else {
+                Self::BundledOpenSsl { path }
+            };
+        }
+        if let Some(path) = find(is_boringssl) {
+            return Self::BoringSsl { path: Some(path) };
+        }
+        if contains_any(exe, BORINGSSL_MARKERS) {
+            return Self::BoringSsl { path: None };
+        }
+        if contains(exe, b"SSL_write") && contains(exe, b"OpenSSL ") {
+            if let Some(exe) = exe_path {
+                return Self::StaticOpenSsl {
+                    exe: exe.to_string(),
+                };
+            }
+        }
+        if let Some(path) = find(is_gnutls) {
+            return Self::GnuTls { path };
+        }
+        if let Some(path) = find(is_nss) {
+            return Self::Nss { path };
+        }
+        if contains_any(exe, GO_MARKERS) {
+            return Self::GoCryptoTls;
+        }
+        // With no TLS library mapped, a Rust HTTP client means TLS is
+        // compiled in, and in Rust that is rustls
+        if contains(exe, b"rustls")
+            || (contains_any(exe, RUST_MARKERS) && contains_any(exe, RUST_HTTP_MARKERS))
+        {
+            return Self::Rustls;
+        }
+        if let Some(path) = find(is_libcrypto) {
+            return Self::CryptoOnly { path };
+        }
+        Self::None
+    }
+
+    /// Human-readable name of the library
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SystemOpenSsl { .. } => "OpenSSL (system)",
+            Self::BundledOpenSsl { .. } => "OpenSSL (bundled)",
+            Self::StaticOpenSsl { .. } => "OpenSSL (statically linked)",
+            Self::BoringSsl { .. } => "BoringSSL",
+            Self::GnuTls { .. } => "GnuTLS",
+            Self::Nss { .. } => "NSS",
+            Self::GoCryptoTls => "Go crypto/tls",
+            Self::Rustls => "rustls",
+            Self::CryptoOnly { .. } => "libcrypto only",
+            Self::None => "none",
+        }
+    }
+
+    /// Whether SSL capture can see this process's plaintext
+    pub fn capture_supported(&self) -> bool {
+        matches!(
+            self,
+            Self::SystemOpenSsl { .. } | Self::BundledOpenSsl { .. } | Self::StaticOpenSsl { .. }
+        )
+    }
+
+    /// Why capture does or doesn't work for this library
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::SystemOpenSsl { .. } => {
+                "SSL capture hooks the system libssl, so it works with the default configuration."
+            }
+            Self::BundledOpenSsl { .. } => {
+                "This libssl is outside the system library directories, so the sensor \
+                 does not attach to it unless it is listed in ssl_binary_paths."
+            }
+            Self::StaticOpenSsl { .. } => {
+                "OpenSSL is linked into the executable. SSL capture can attach to \
+                 the executable's own SSL_read/SSL_write once it is listed in ssl_binary_paths."
+            }
+            Self::BoringSsl { .. } => {
+                "BoringSSL is compiled into the application and its symbols are stripped, \
+                 so there is no SSL_read/SSL_write for SSL capture to attach to."
+            }
+            Self::GnuTls { .. } => {
+                "GnuTLS uses gnutls_record_send/recv rather than OpenSSL's API; \
+                 SSL capture only attaches to OpenSSL."
+            }
+            Self::Nss { .. } => {
+                "NSS (libssl3/libnspr4) uses PR_Read/PR_Write rather than OpenSSL's API; \
+                 SSL capture only attaches to OpenSSL."
+            }
+            Self::GoCryptoTls => {
+                "Go implements TLS in its standard library and links it statically, \
+                 so no TLS library is loaded for SSL capture to attach to."
+            }
+            Self::Rustls => {
+                "rustls is compiled into the Rust binary and has no stable exported \
+                 symbols, so there is nothing for SSL capture to attach to."
+            }
+            Self::CryptoOnly { .. } => {
+                "libcrypto is loaded without libssl: the process uses OpenSSL for \
+                 cryptography but not for TLS connections."
+            }
+            Self::None => {
+                "No TLS library is loaded. The process may not use TLS, or has not \
+                 loaded its TLS library yet."
+            }
+        }
+    }
+
+    /// What to do instead, one line per step
+    pub fn alternative(&self, pid: u32) -> Vec<String> {
+        let metadata_only = "Enable capture.network_flows to record which addresses it \
+                             contacts (no request content)."
+            .to_string();
+        match self {
+            Self::SystemOpenSsl { .. } => {
+                vec![format!("sudo oisp-sensor record --pid {}", pid)]
+            }
+            Self::BundledOpenSsl { path } => vec![
+                "Add the library to capture.ssl_binary_paths:".to_string(),
+                format!("  ssl_binary_paths = [\"{}\"]", path),
+            ],
+            Self::StaticOpenSsl { exe } => vec![
+                "Add the executable to capture.ssl_binary_paths:".to_string(),
+                format!("  ssl_binary_paths = [\"{}\"]", exe),
+            ],
+            Self::BoringSsl { .. } => vec![
+                "Route the application through an HTTPS proxy (HTTPS_PROXY or \
+                 --proxy-server) to see request content."
+                    .to_string(),
+                metadata_only,
+            ],
+            Self::GnuTls { .. } | Self::Nss { .. } => vec![
+                "Use an OpenSSL-based client for AI traffic where possible.".to_string(),
+                metadata_only,
+            ],
+            Self::GoCryptoTls | Self::Rustls => vec![
+                "Set HTTPS_PROXY to route the application through an HTTPS proxy to \
+                 see request content."
+                    .to_string(),
+                metadata_only,
+            ],
+            Self::CryptoOnly { .. } | Self::None => vec![
+                "Run diagnose again once the process has made an HTTPS request.".to_string(),
+                metadata_only,
+            ],
+        }
+    }
+}
+
+/// Distinct file paths mapped into the process, in order of first appearance
+fn mapped_paths(maps: &str) -> Vec<&str> {
+    let mut paths: Vec<&str> = Vec::new();
+    for line in maps.lines() {
+        // address perms offset dev inode pathname; the pathname may hold spaces
+        let mut rest = line;
+        for _ in 0..5 {
+            rest = rest.trim_start();
+            rest = match rest.find(char::is_whitespace) {
+                Some(end) => &rest[end..],
+                None => "",
+            };
+        }
+        let path = rest.trim().trim_end_matches(" (deleted)");
+        if path.starts_with('/') && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// OpenSSL's libssl (`libssl.so.3`), not NSS's `libssl3.so`
+fn is_openssl(name: &str) -> bool {
+    name.starts_with("libssl.so") || name.starts_with("libssl-")
+}
+
+fn is_libcrypto(name: &str) -> bool {
+    name.starts_with("libcrypto.so") || name.starts_with("libcrypto-")
+}
+
+/// BoringSSL as a shared library, or gRPC's Python module that embeds it
+fn is_boringssl(name: &str) -> bool {
+    name.contains("boringssl") || name.starts_with("cygrpc.")
+}
+
+fn is_gnutls(name: &str) -> bool {
+    name.starts_with("libgnutls")
+}
+
+fn is_nss(name: &str) -> bool {
+    name.starts_with("libnss3") || name.starts_with("libssl3.so") || name.starts_with("libnspr4")
+}
+
+fn is_system_path(path: &str) -> bool {
+    ["/usr/lib/", "/usr/lib64/", "/lib/", "/lib64/"]
+        .iter()
+        .any(|dir| path.starts_with(dir))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn contains_any(haystack: &[u8], needles: &[&[u8]]) -> bool {
+    needles.iter().any(|needle| contains(haystack, needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maps(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../fixtures/maps")
+            .join(format!("{}.maps", name));
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+    }
+
+    #[test]
+    fn test_openssl() {
+        let detected = TlsLibrary::detect(&maps("openssl-system"), None, b"");
+        assert_eq!(
+            detected,
+            TlsLibrary::SystemOpenSsl {
+                path: "/usr/lib/x86_64-linux-gnu/libssl.so.3".to_string()
+            }
+        );
+        assert!(detected.capture_supported());
+
+        let detected = TlsLibrary::detect(&maps("openssl-bundled"), None, b"");
+        assert_eq!(
+            detected,
+            TlsLibrary::BundledOpenSsl {
+                path: "/opt/conda/lib/libssl.so.3".to_string()
+            }
+        );
+        assert!(detected.alternative(1)[1].contains("/opt/conda/lib/libssl.so.3"));
+
+        let exe = "/home/dev/.nvm/versions/node/v20.11.0/bin/node";
+        let detected = TlsLibrary::detect(
+            &maps("openssl-static"),
+            Some(exe),
+            b"\0SSL_write\0OpenSSL 3.0.13+quic 30 Jan 2024\0",
+        );
+        assert_eq!(
+            detected,
+            TlsLibrary::StaticOpenSsl {
+                exe: exe.to_string()
+            }
+        );
+        assert!(detected.capture_supported());
+    }
+
+    #[test]
+    fn test_boringssl() {
+        // Electron maps NSS for certificate handling, but TLS is BoringSSL
+        let exe = b"\0../../third_party/boringssl/src/ssl/ssl_lib.cc\0";
+        let detected = TlsLibrary::detect(&maps("boringssl"), None, exe);
+        assert_eq!(detected, TlsLibrary::BoringSsl { path: None });
+        assert!(!detected.capture_supported());
+
+        let detected = TlsLibrary::detect(&maps("grpc"), None, b"");
+        assert!(
+            matches!(detected, TlsLibrary::BoringSsl { path: Some(p) } if p.ends_with("cygrpc.cpython-311-x86_64-linux-gnu.so"))
+        );
+    }
+
+    #[test]
+    fn test_gnutls_and_nss() {
+        let detected = TlsLibrary::detect(&maps("gnutls"), None, b"");
+        assert_eq!(
+            detected,
+            TlsLibrary::GnuTls {
+                path: "/usr/lib/x86_64-linux-gnu/libgnutls.so.30.34.3".to_string()
+            }
+        );
+
+        // libssl3.so is NSS, not OpenSSL
+        let detected = TlsLibrary::detect(&maps("nss"), None, b"");
+        assert_eq!(
+            detected,
+            TlsLibrary::Nss {
+                path: "/usr/lib/firefox/libnss3.so".to_string()
+            }
+        );
+        assert!(!detected.capture_supported());
+    }
+
+    #[test]
+    fn test_go() {
+        let exe = b"\x7fELF\0\xff Go buildinf:\x08\x02\0crypto/tls.(*Conn).Write\0";
+        let detected = TlsLibrary::detect(&maps("go"), Some("/usr/local/bin/kubectl"), exe);
+        assert_eq!(detected, TlsLibrary::GoCryptoTls);
+        assert!(!detected.capture_supported());
+    }
+
+    #[test]
+    fn test_rustls() {
+        let maps = maps("rustls");
+        let exe = b"\x7fELF\0/rustc/abc/library/core\0hyper-util-0.1.10/src/client\0";
+        assert_eq!(TlsLibrary::detect(&maps, None, exe), TlsLibrary::Rustls);
+        assert_eq!(
+            TlsLibrary::detect(&maps, None, b"rustls-0.23.12/src/conn.rs"),
+            TlsLibrary::Rustls
+        );
+
+        // Without a hint there is nothing to go on
+        assert_eq!(TlsLibrary::detect(&maps, None, b""), TlsLibrary::None);
+    }
+
+    #[test]
+    fn test_no_tls() {
+        let detected = TlsLibrary::detect(&maps("no-tls"), None, b"\x7fELF\0");
+        assert_eq!(detected, TlsLibrary::None);
+        assert!(!detected.capture_supported());
+    }
+
+    #[test]
+    fn test_mapped_paths_keep_spaces() {
+        let maps = maps("boringssl");
+        let paths = mapped_paths(&maps);
+        assert_eq!(paths[0], "/opt/Code Editor/code-editor");
+        assert!(!paths.iter().any(|p| p.starts_with('[')));
+    }
+}
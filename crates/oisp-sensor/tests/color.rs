@@ -0,0 +1,50 @@
+//! Color handling of `status`, `check`, `ssl-info` and `diagnose`
+//!
+//! Test stdout is a pipe, so none of these may emit escape codes, with or
+//! without `NO_COLOR` and `--no-color`.
+
+use std::process::Command;
+
+fn stdout_of(args: &[&str], no_color_env: Option<&str>) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_oisp-sensor"));
+    command.args(args).env_remove("NO_COLOR");
+    if let Some(value) = no_color_env {
+        command.env("NO_COLOR", value);
+    }
+    let output = command.output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn commands() -> Vec<Vec<String>> {
+    let pid = std::process::id().to_string();
+    vec![
+        vec!["status".into()],
+        vec!["check".into()],
+        vec!["ssl-info".into()],
+        vec!["diagnose".into(), "--pid".into(), pid],
+    ]
+}
+
+#[test]
+fn test_no_escape_codes_when_stdout_is_not_a_terminal() {
+    for args in commands() {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let stdout = stdout_of(&args, None);
+        assert!(!stdout.trim().is_empty(), "{:?} printed nothing", args);
+        assert!(!stdout.contains('\x1b'), "{:?}: {:?}", args, stdout);
+    }
+}
+
+#[test]
+fn test_no_escape_codes_with_no_color() {
+    for args in commands() {
+        let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let stdout = stdout_of(&args, Some("1"));
+        assert!(!stdout.contains('\x1b'), "{:?}: {:?}", args, stdout);
+
+        args.push("--no-color");
+        let stdout = stdout_of(&args, None);
+        assert!(!stdout.trim().is_empty(), "{:?} printed nothing", args);
+        assert!(!stdout.contains('\x1b'), "{:?}: {:?}", args, stdout);
+    }
+}
@@ -0,0 +1,59 @@
+//! `oisp-sensor providers list`
+
+use std::process::Command;
+
+fn providers_list(args: &[&str]) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+        .args(["providers", "list"])
+        .args(args)
+        // No cached bundle, so the embedded one is listed
+        .env("XDG_CACHE_HOME", dir.path())
+        .env("OISP_CONFIG", dir.path().join("missing.toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_json_lists_openai_domains() {
+    let stdout = providers_list(&["--json"]);
+    let listing: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert!(!listing["version"].as_str().unwrap().is_empty());
+
+    let providers = listing["providers"].as_array().unwrap();
+    let openai = providers.iter().find(|p| p["id"] == "openai").unwrap();
+    let domains: Vec<&str> = openai["domains"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d.as_str().unwrap())
+        .collect();
+    assert!(domains.contains(&"api.openai.com"), "{:?}", domains);
+    assert!(openai.get("models").is_none());
+}
+
+#[test]
+fn test_filter_and_models() {
+    let stdout = providers_list(&["--json", "--models", "openai"]);
+    let listing: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let providers = listing["providers"].as_array().unwrap();
+    assert!(providers.iter().all(|p| p["id"] != "anthropic"));
+    let openai = providers.iter().find(|p| p["id"] == "openai").unwrap();
+    assert!(openai["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|m| m == "gpt-4o"));
+
+    let table = providers_list(&["openai"]);
+    let row = table
+        .lines()
+        .find(|line| line.starts_with("openai "))
+        .unwrap();
+    assert!(row.contains("api.openai.com"), "{}", row);
+
+    let table = providers_list(&["no-such-provider"]);
+    assert!(table.contains("No providers match \"no-such-provider\""));
+}
@@ -0,0 +1,96 @@
+//! `oisp-sensor show --redact-preview`
+
+use std::path::Path;
+use std::process::Command;
+
+const API_KEY: &str = "sk-proj-abc123def456ghi789jkl012";
+
+fn write_events(path: &Path) {
+    let events = [
+        serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": "01J0000000000000000000000A",
+            "event_type": "ai.request",
+            "ts": "2025-01-15T10:30:00.123Z",
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {
+                "request_id": "req",
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o"},
+                "messages": [{
+                    "role": "user",
+                    "content": format!("use {} and mail jane@example.com, ticket T-4821", API_KEY)
+                }]
+            }
+        }),
+        serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": "01J0000000000000000000000B",
+            "event_type": "process.exec",
+            "ts": "2025-01-15T10:30:01Z",
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {"exe": "/usr/bin/curl", "args": ["jane@example.com"]}
+        }),
+    ];
+    let lines: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    std::fs::write(path, lines.join("\n") + "\n").unwrap();
+}
+
+fn preview(input: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+        .args(["show", "--input"])
+        .arg(input)
+        .args(args)
+        .env("OISP_CONFIG", input.with_file_name("missing.toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_safe_preview_marks_api_key_and_email() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("events.jsonl");
+    write_events(&input);
+
+    let stdout = preview(&input, &["--redact-preview", "safe"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "ai.request 01J0000000000000000000000A (openai gpt-4o)",
+            "  /data/messages/0/content  [api_key x1, email x1]",
+            &format!(
+                "    - use [[api_key:{}]] and mail [[email:jane@example.com]], ticket T-4821",
+                API_KEY
+            ),
+            "    + use [API_KEY_REDACTED] and mail [EMAIL_REDACTED], ticket T-4821",
+        ]
+    );
+}
+
+#[test]
+fn test_preview_custom_pattern_and_full_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("events.jsonl");
+    write_events(&input);
+
+    let stdout = preview(
+        &input,
+        &["--redact-preview", "safe", "--redact-pattern", r"T-\d{4}"],
+    );
+    assert!(stdout.contains("[[custom:T-4821]]"), "{}", stdout);
+    assert!(stdout.contains("ticket [CUSTOM_REDACTED]"), "{}", stdout);
+
+    let stdout = preview(&input, &["--redact-preview", "full"]);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "ai.request 01J0000000000000000000000A (openai gpt-4o)",
+            "  nothing redacted",
+        ]
+    );
+}
@@ -0,0 +1,137 @@
+//! `oisp-sensor reset` removes only the state it is asked to
+
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Local state laid out under a temporary home
+struct State {
+    dir: tempfile::TempDir,
+}
+
+impl State {
+    fn new() -> Self {
+        let state = Self {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        for path in state.all_files() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "{}").unwrap();
+        }
+        state
+    }
+
+    fn credentials(&self) -> PathBuf {
+        self.dir
+            .path()
+            .join("config")
+            .join("oisp-sensor")
+            .join("credentials.json")
+    }
+
+    /// The configured queue, not the one in the data directory
+    fn queue(&self) -> Vec<PathBuf> {
+        let queue = self.dir.path().join("queue");
+        vec![queue.join("events.db"), queue.join("events.db-wal")]
+    }
+
+    /// Event output, dead letters and the state kept next to them
+    fn events(&self) -> Vec<PathBuf> {
+        let root = self.dir.path();
+        vec![
+            root.join("events.jsonl"),
+            root.join("events.jsonl.cursor"),
+            root.join("events.jsonl.checkpoint"),
+            root.join("dead-letter.jsonl"),
+        ]
+    }
+
+    fn all_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.credentials()];
+        files.extend(self.queue());
+        files.extend(self.events());
+        files
+    }
+
+    fn reset(&self, args: &[&str]) -> Output {
+        let root = self.dir.path();
+        Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+            .arg("reset")
+            .args(args)
+            .env("OISP_CONFIG", root.join("missing.toml"))
+            .env("HOME", root)
+            .env("XDG_CONFIG_HOME", root.join("config"))
+            .env("XDG_DATA_HOME", root.join("data"))
+            .env("OISP_JSONL_PATH", root.join("events.jsonl"))
+            .env("OISP_DEAD_LETTER_PATH", root.join("dead-letter.jsonl"))
+            .env(
+                "OISP_OXIMY_OFFLINE_QUEUE_PATH",
+                root.join("queue").join("events.db"),
+            )
+            .env_remove("OISP_OXIMY_CREDENTIAL_PATH")
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    }
+}
+
+#[test]
+fn test_credentials_flag_removes_only_credentials() {
+    let state = State::new();
+    let output = state.reset(&["--credentials", "--yes"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert!(!state.credentials().exists());
+    assert!(state.queue().iter().all(|p| p.exists()));
+    assert!(state.events().iter().all(|p| p.exists()));
+}
+
+#[test]
+fn test_queue_flag_removes_only_queue() {
+    let state = State::new();
+    let output = state.reset(&["--queue", "--yes"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert!(state.credentials().exists());
+    assert!(state.queue().iter().all(|p| !p.exists()));
+    assert!(state.events().iter().all(|p| p.exists()));
+}
+
+#[test]
+fn test_logs_flag_removes_only_logs() {
+    let state = State::new();
+    let output = state.reset(&["--logs", "--yes"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert!(state.credentials().exists());
+    assert!(state.queue().iter().all(|p| p.exists()));
+    assert!(state.events().iter().all(|p| !p.exists()));
+}
+
+#[test]
+fn test_all_flag_removes_everything() {
+    let state = State::new();
+    let output = state.reset(&["--all", "--yes"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert!(state.all_files().iter().all(|p| !p.exists()));
+}
+
+#[test]
+fn test_declined_prompt_keeps_state() {
+    let state = State::new();
+    // stdin is empty, so the confirmation is not given
+    let output = state.reset(&["--all"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Aborted."));
+
+    assert!(state.all_files().iter().all(|p| p.exists()));
+}
+
+#[test]
+fn test_no_target_is_an_error() {
+    let state = State::new();
+    let output = state.reset(&["--yes"]);
+    assert!(!output.status.success());
+
+    assert!(state.all_files().iter().all(|p| p.exists()));
+}
@@ -0,0 +1,78 @@
+//! Auto-stop tests for `--max-events` and `--max-duration`
+
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn spawn_demo(dir: &Path, output: &Path, args: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+        .args(["demo", "--interval", "20", "--port"])
+        .arg(free_port().to_string())
+        .arg("--output")
+        .arg(output)
+        .args(args)
+        .env("OISP_CONFIG", dir.join("missing.toml"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> ExitStatus {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("sensor did not stop on its own");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Exported events other than the raw capture records
+fn decoded_events(output: &Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("complete JSON"))
+        .filter(|event| event["event_type"] != "capture.raw")
+        .collect()
+}
+
+#[test]
+fn test_max_events_stops_after_count() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("events.jsonl");
+
+    let mut child = spawn_demo(dir.path(), &output, &["--max-events", "5"]);
+    let status = wait_for_exit(&mut child, Duration::from_secs(30));
+
+    assert!(status.success(), "unexpected exit status: {:?}", status);
+    assert_eq!(decoded_events(&output).len(), 5);
+}
+
+#[test]
+fn test_max_duration_stops_after_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("events.jsonl");
+
+    let started = Instant::now();
+    let mut child = spawn_demo(dir.path(), &output, &["--max-duration", "1"]);
+    let status = wait_for_exit(&mut child, Duration::from_secs(30));
+
+    assert!(status.success(), "unexpected exit status: {:?}", status);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert!(!decoded_events(&output).is_empty());
+}
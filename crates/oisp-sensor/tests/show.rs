@@ -0,0 +1,120 @@
+//! `oisp-sensor show --output-format`
+
+use std::path::Path;
+use std::process::Command;
+
+fn write_events(path: &Path) {
+    let events = [
+        serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": "01J0000000000000000000000A",
+            "event_type": "ai.request",
+            "ts": "2025-01-15T10:30:00.123Z",
+            "process": {"pid": 4242, "name": "python"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {
+                "request_id": "req",
+                "provider": {"name": "openai"},
+                "model": {"id": "gpt-4o-mini-2024-07-18-with-a-very-long-suffix"}
+            }
+        }),
+        serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": "01J0000000000000000000000B",
+            "event_type": "process.exec",
+            "ts": "2025-01-15T10:30:01Z",
+            "process": {"pid": 7, "name": "curl"},
+            "source": {"collector": "test"},
+            "confidence": {"level": "high", "completeness": "full"},
+            "data": {"exe": "/usr/bin/curl"}
+        }),
+    ];
+    let lines: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    std::fs::write(path, lines.join("\n") + "\n").unwrap();
+}
+
+fn show(input: &Path, format: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+        .args(["show", "--output-format", format, "--input"])
+        .arg(input)
+        .env("OISP_CONFIG", input.with_file_name("missing.toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_table_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("events.jsonl");
+    write_events(&input);
+
+    let stdout = show(&input, "table");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+
+    let header: Vec<&str> = lines[0].split_whitespace().collect();
+    assert_eq!(
+        header,
+        ["TS", "TYPE", "PROVIDER", "MODEL", "PID", "PROCESS"]
+    );
+
+    // The timestamp holds one space, so it spans two whitespace-separated fields
+    let row: Vec<&str> = lines[1].split_whitespace().collect();
+    assert_eq!(row.len(), 7);
+    assert_eq!(&row[..3], ["2025-01-15", "10:30:00.123", "ai.request"]);
+    assert_eq!(row[3], "openai");
+    assert!(row[4].starts_with("gpt-4o-mini") && row[4].ends_with('…'));
+    assert_eq!(row[4].chars().count(), 28);
+    assert_eq!(&row[5..], ["4242", "python"]);
+
+    let row: Vec<&str> = lines[2].split_whitespace().collect();
+    assert_eq!(
+        row,
+        [
+            "2025-01-15",
+            "10:30:01.000",
+            "process.exec",
+            "-",
+            "-",
+            "7",
+            "curl"
+        ]
+    );
+
+    // Columns line up across rows (in characters; the ellipsis is multibyte)
+    let column = lines[0].find("PID").unwrap();
+    let at = |line: &str, len: usize| -> String { line.chars().skip(column).take(len).collect() };
+    assert_eq!(at(lines[1], 4), "4242");
+    assert_eq!(at(lines[2], 1), "7");
+}
+
+#[test]
+fn test_ndjson_one_object_per_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("events.jsonl");
+    write_events(&input);
+
+    let stdout = show(&input, "ndjson");
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("one JSON object per line"))
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e.is_object()));
+    assert_eq!(events[1]["event_type"], "process.exec");
+}
+
+#[test]
+fn test_json_single_array() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("events.jsonl");
+    write_events(&input);
+
+    let stdout = show(&input, "json");
+    assert_eq!(stdout.lines().count(), 1);
+    let events: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(events.as_array().unwrap().len(), 2);
+}
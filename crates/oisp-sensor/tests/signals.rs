@@ -0,0 +1,67 @@
+//! Signal handling tests for the sensor binary
+
+#![cfg(target_os = "linux")]
+
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn test_sigterm_stops_cleanly_and_flushes_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("events.jsonl");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oisp-sensor"))
+        .args(["demo", "--interval", "50", "--port"])
+        .arg(free_port().to_string())
+        .arg("--output")
+        .arg(&output)
+        .env("OISP_CONFIG", dir.path().join("missing.toml"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Wait for the pipeline to export some events
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while std::fs::read_to_string(&output)
+        .map(|c| c.lines().count() < 3)
+        .unwrap_or(true)
+    {
+        assert!(Instant::now() < deadline, "no events written");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("sensor did not exit after SIGTERM");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    // A clean exit (not killed by the signal) means the pipeline was stopped
+    assert!(status.success(), "unexpected exit status: {:?}", status);
+
+    let content = std::fs::read_to_string(&output).unwrap();
+    assert!(content.ends_with('\n'));
+    for line in content.lines() {
+        serde_json::from_str::<serde_json::Value>(line).expect("every line is complete JSON");
+    }
+}
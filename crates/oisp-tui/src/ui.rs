@@ -224,7 +224,7 @@ fn draw_inventory(frame: &mut Frame, area: Rect, app: &App) {
 
     // Apps - sorted by request count descending
     let mut sorted_apps: Vec<_> = app.apps.values().collect();
-    sorted_apps.sort_by(|a, b| b.request_count.cmp(&a.request_count));
+    sorted_apps.sort_by_key(|a| std::cmp::Reverse(a.request_count));
 
     let app_items: Vec<ListItem> = sorted_apps
         .iter()
@@ -285,7 +285,7 @@ fn draw_inventory(frame: &mut Frame, area: Rect, app: &App) {
     // Web Apps - sorted by request count descending
     if !app.web_apps.is_empty() {
         let mut sorted_web_apps: Vec<_> = app.web_apps.values().collect();
-        sorted_web_apps.sort_by(|a, b| b.request_count.cmp(&a.request_count));
+        sorted_web_apps.sort_by_key(|a| std::cmp::Reverse(a.request_count));
 
         let web_app_items: Vec<ListItem> = sorted_web_apps
             .iter()
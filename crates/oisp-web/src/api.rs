@@ -1,18 +1,118 @@
 //! REST API handlers
 
+use crate::history::{HistoryCursor, HistoryError};
 use crate::web_event::{WebEvent, WebEventsResponse};
+use crate::ws::Subscription;
 use crate::AppState;
-use axum::{extract::State, Json};
-use serde::Serialize;
-use std::collections::HashMap;
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use oisp_core::events::OispEvent;
+use oisp_core::metrics::ROLLUP_INTERVAL;
+use oisp_core::plugins::{
+    CaptureError, CapturePluginStats, ProcessTargetControl, ProcessTargetError, ProcessTargets,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize)]
 pub struct EventsResponse {
     pub events: Vec<serde_json::Value>,
     pub total: usize,
+    /// Pass as `before` to get older events from the JSONL history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Cursor from a previous response; pages into the JSONL history
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+    /// Only events of this type, e.g. `ai.request`
+    pub event_type: Option<String>,
+    /// Only events of this process
+    pub pid: Option<u32>,
+    /// Only AI events of this provider (case-insensitive)
+    pub provider: Option<String>,
+    /// Sort by `ts`, newest first by default
+    pub order: Option<EventOrder>,
+}
+
+/// Order of `/api/events` results by `ts`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Events returned by default and at most
+const DEFAULT_EVENTS_LIMIT: usize = 100;
+const MAX_EVENTS_LIMIT: usize = 1000;
+
+impl EventsQuery {
+    /// Number of events to return, if the parameters are valid
+    fn validate(&self) -> Result<usize, String> {
+        let limit = self.limit.unwrap_or(DEFAULT_EVENTS_LIMIT);
+        if limit == 0 || limit > MAX_EVENTS_LIMIT {
+            return Err(format!(
+                "limit must be between 1 and {}, got {}",
+                MAX_EVENTS_LIMIT, limit
+            ));
+        }
+        if self.event_type.as_deref() == Some("") {
+            return Err("event_type must not be empty".to_string());
+        }
+        if self.provider.as_deref() == Some("") {
+            return Err("provider must not be empty".to_string());
+        }
+        let filtered = self.event_type.is_some()
+            || self.pid.is_some()
+            || self.provider.is_some()
+            || self.order.is_some();
+        if self.before.is_some() && filtered {
+            return Err(
+                "event_type, pid, provider and order apply to in-memory events and cannot be combined with before"
+                    .to_string(),
+            );
+        }
+        Ok(limit)
+    }
+
+    /// The filter parameters; unset ones match everything
+    fn filter(&self) -> Subscription {
+        Subscription {
+            event_types: self.event_type.iter().cloned().collect(),
+            pids: self.pid.into_iter().collect(),
+            providers: self.provider.iter().cloned().collect(),
+        }
+    }
+}
+
+fn bad_request(message: impl Into<String>) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "error": message.into() })),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeriesQuery {
+    /// How far back to go, e.g. `90s`, `5m` or `1h`
+    pub window: Option<String>,
+}
+
+/// Series window when none is given
+const DEFAULT_SERIES_WINDOW: Duration = Duration::from_secs(300);
+
 #[derive(Serialize)]
 pub struct TracesResponse {
     pub traces: Vec<TraceInfo>,
@@ -60,20 +160,133 @@ pub struct StatsResponse {
     pub ai_events: u64,
     pub active_traces: usize,
     pub uptime_seconds: u64,
+    /// Recent errors reported by capture plugins
+    pub capture_errors: Vec<CaptureError>,
 }
 
-pub async fn get_events(State(state): State<Arc<AppState>>) -> Json<EventsResponse> {
-    let events = state.events.read().await;
-    let event_values: Vec<serde_json::Value> = events
+/// Per-plugin capture statistics
+#[derive(Serialize)]
+pub struct CaptureStatsResponse {
+    pub plugins: Vec<CapturePluginStats>,
+}
+
+/// Process forest reconstructed from observed events
+#[derive(Debug, Serialize)]
+pub struct ProcessTreeResponse {
+    pub processes: Vec<ProcessTreeNode>,
+    /// Number of real (non-synthetic) processes in the tree
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessTreeNode {
+    pub pid: u32,
+    pub ppid: Option<u32>,
+    pub name: String,
+    pub exe: Option<String>,
+    pub ai_request_count: u64,
+    /// True for the synthetic root that holds orphaned processes
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub synthetic: bool,
+    pub children: Vec<ProcessTreeNode>,
+}
+
+/// PID of the synthetic root for processes whose parent was never seen
+pub const ORPHAN_ROOT_PID: u32 = 0;
+
+/// Most recent events from memory, or with `before`, older events from the
+/// JSONL history
+///
+/// In-memory events can be filtered by `event_type`, `pid` and `provider`;
+/// the newest `limit` matches are returned, sorted by `ts` in `order`.
+pub async fn get_events(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<EventsQuery>, QueryRejection>,
+) -> Response {
+    let query = match query {
+        Ok(Query(query)) => query,
+        Err(rejection) => return bad_request(rejection.body_text()),
+    };
+    let limit = match query.validate() {
+        Ok(limit) => limit,
+        Err(message) => return bad_request(message),
+    };
+
+    let Some(before) = query.before.clone() else {
+        let events = state.events.read().await;
+        let filter = query.filter();
+        let mut matching: Vec<&Arc<OispEvent>> =
+            events.iter().filter(|e| filter.matches(e)).collect();
+        // Stable, so events with the same `ts` stay newest first
+        matching.sort_by_key(|e| std::cmp::Reverse(e.envelope().ts));
+        let total = matching.len();
+        matching.truncate(limit);
+        if query.order.unwrap_or_default() == EventOrder::Asc {
+            matching.reverse();
+        }
+
+        let event_values: Vec<serde_json::Value> = matching
+            .into_iter()
+            .filter_map(|e| serde_json::to_value(e.as_ref()).ok())
+            .collect();
+        let cursor = state
+            .history
+            .as_ref()
+            .map(|history| history.tail().to_string());
+        return Json(EventsResponse {
+            total,
+            events: event_values,
+            cursor,
+        })
+        .into_response();
+    };
+
+    let Some(history) = state.history.clone() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Event history is not available without a JSONL output file"
+            })),
+        )
+            .into_response();
+    };
+    let before = match before.parse::<HistoryCursor>() {
+        Ok(cursor) => cursor,
+        Err(e) => return bad_request(e.to_string()),
+    };
+
+    // Events still in memory were already served from there
+    let in_memory: HashSet<String> = state
+        .events
+        .read()
+        .await
         .iter()
-        .take(100)
-        .filter_map(|e| serde_json::to_value(e.as_ref()).ok())
+        .map(|e| e.envelope().event_id.clone())
         .collect();
-
-    Json(EventsResponse {
-        total: events.len(),
-        events: event_values,
-    })
+    let page =
+        tokio::task::spawn_blocking(move || history.page(Some(before), limit, &in_memory)).await;
+
+    match page {
+        Ok(Ok(page)) => Json(EventsResponse {
+            total: page.events.len(),
+            events: page.events,
+            cursor: page.next.map(|cursor| cursor.to_string()),
+        })
+        .into_response(),
+        Ok(Err(e)) => {
+            let status = match e {
+                HistoryError::InvalidCursor(_) => StatusCode::BAD_REQUEST,
+                HistoryError::Rotated => StatusCode::GONE,
+                HistoryError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
 }
 
 /// Get events in WebEvent format (optimized for frontend)
@@ -185,15 +398,221 @@ pub async fn get_stats(State(state): State<Arc<AppState>>) -> Json<StatsResponse
         .as_ref()
         .map(|m| m.uptime_seconds())
         .unwrap_or(0);
+    let capture_errors = state
+        .metrics
+        .as_ref()
+        .map(|m| m.capture_errors())
+        .unwrap_or_default();
 
     Json(StatsResponse {
         total_events: events.len() as u64,
         ai_events,
         active_traces: builder.active_traces().len(),
         uptime_seconds,
+        capture_errors,
     })
 }
 
+pub async fn get_capture_stats(State(state): State<Arc<AppState>>) -> Json<CaptureStatsResponse> {
+    let plugins = match &state.metrics {
+        Some(metrics) => metrics.capture_plugin_stats().await,
+        None => Vec::new(),
+    };
+    Json(CaptureStatsResponse { plugins })
+}
+
+/// Body of capture attach and detach requests
+#[derive(Debug, Deserialize)]
+pub struct CaptureTargetRequest {
+    pub pid: u32,
+}
+
+/// Processes the capture is tracing
+pub async fn get_capture_targets(State(state): State<Arc<AppState>>) -> Response {
+    capture_targets_response(&state, |control| Ok(control.targets())).await
+}
+
+/// Start capturing SSL traffic of a running process
+pub async fn attach_capture(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CaptureTargetRequest>,
+) -> Response {
+    capture_targets_response(&state, move |control| control.attach(request.pid)).await
+}
+
+/// Stop capturing a process previously attached
+///
+/// Fails with `409 Conflict` unless capture is filtered by process (the
+/// sensor was started with a pid filter); otherwise every process stays
+/// captured.
+pub async fn detach_capture(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CaptureTargetRequest>,
+) -> Response {
+    capture_targets_response(&state, move |control| control.detach(request.pid)).await
+}
+
+/// Apply `change` to the capture's process targets
+///
+/// Runs on the blocking pool: an attach writes to sslsniff's stdin and
+/// waits for its result.
+async fn capture_targets_response(
+    state: &AppState,
+    change: impl FnOnce(&dyn ProcessTargetControl) -> Result<ProcessTargets, ProcessTargetError>
+        + Send
+        + 'static,
+) -> Response {
+    let Some(control) = state.process_targets.clone() else {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({
+                "error": "Runtime process attach is not supported by the active capture"
+            })),
+        )
+            .into_response();
+    };
+    let changed = match tokio::task::spawn_blocking(move || change(control.as_ref())).await {
+        Ok(changed) => changed,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+    match changed {
+        Ok(targets) => Json(targets).into_response(),
+        Err(e) => {
+            let status = match e {
+                ProcessTargetError::NoSuchProcess(_) | ProcessTargetError::NotAttached(_) => {
+                    StatusCode::NOT_FOUND
+                }
+                ProcessTargetError::NoTlsLibrary(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                ProcessTargetError::NotRunning => StatusCode::SERVICE_UNAVAILABLE,
+                ProcessTargetError::NotFiltered => StatusCode::CONFLICT,
+                ProcessTargetError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        }
+    }
+}
+
+pub async fn get_process_tree(State(state): State<Arc<AppState>>) -> Json<ProcessTreeResponse> {
+    let events = state.events.read().await;
+    Json(build_process_tree(&events))
+}
+
+/// Build the process forest from events' process context
+///
+/// Processes without a parent are roots. Processes whose parent was never
+/// seen are attached under a synthetic root (pid 0) so the UI can show them
+/// grouped rather than as a long flat list.
+pub fn build_process_tree(events: &[Arc<OispEvent>]) -> ProcessTreeResponse {
+    struct Entry {
+        ppid: Option<u32>,
+        name: Option<String>,
+        exe: Option<String>,
+        ai_request_count: u64,
+    }
+
+    let mut entries: BTreeMap<u32, Entry> = BTreeMap::new();
+    for event in events {
+        let Some(proc) = &event.envelope().process else {
+            continue;
+        };
+        let entry = entries.entry(proc.pid).or_insert_with(|| Entry {
+            ppid: None,
+            name: None,
+            exe: None,
+            ai_request_count: 0,
+        });
+        entry.ppid = entry.ppid.or(proc.ppid.filter(|&ppid| ppid != proc.pid));
+        entry.name = entry.name.take().or_else(|| proc.name.clone());
+        entry.exe = entry.exe.take().or_else(|| proc.exe.clone());
+        if matches!(event.as_ref(), OispEvent::AiRequest(_)) {
+            entry.ai_request_count += 1;
+        }
+    }
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut roots = Vec::new();
+    let mut orphans = Vec::new();
+    for (&pid, entry) in &entries {
+        match entry.ppid {
+            None => roots.push(pid),
+            Some(ppid) if entries.contains_key(&ppid) => {
+                children.entry(ppid).or_default().push(pid)
+            }
+            Some(_) => orphans.push(pid),
+        }
+    }
+
+    fn build(
+        pid: u32,
+        entries: &BTreeMap<u32, Entry>,
+        children: &HashMap<u32, Vec<u32>>,
+        visited: &mut HashSet<u32>,
+    ) -> Option<ProcessTreeNode> {
+        if !visited.insert(pid) {
+            return None;
+        }
+        let entry = &entries[&pid];
+        Some(ProcessTreeNode {
+            pid,
+            ppid: entry.ppid,
+            name: entry.name.clone().unwrap_or_else(|| "unknown".to_string()),
+            exe: entry.exe.clone(),
+            ai_request_count: entry.ai_request_count,
+            synthetic: false,
+            children: children
+                .get(&pid)
+                .into_iter()
+                .flatten()
+                .filter_map(|&child| build(child, entries, children, visited))
+                .collect(),
+        })
+    }
+
+    let mut visited = HashSet::new();
+    let mut processes: Vec<ProcessTreeNode> = roots
+        .into_iter()
+        .filter_map(|pid| build(pid, &entries, &children, &mut visited))
+        .collect();
+
+    let mut orphan_nodes: Vec<ProcessTreeNode> = orphans
+        .into_iter()
+        .filter_map(|pid| build(pid, &entries, &children, &mut visited))
+        .collect();
+
+    // Parent cycles (e.g. from PID reuse) have no root; surface them as orphans
+    let unreached: Vec<u32> = entries
+        .keys()
+        .copied()
+        .filter(|pid| !visited.contains(pid))
+        .collect();
+    for pid in unreached {
+        orphan_nodes.extend(build(pid, &entries, &children, &mut visited));
+    }
+
+    if !orphan_nodes.is_empty() {
+        processes.push(ProcessTreeNode {
+            pid: ORPHAN_ROOT_PID,
+            ppid: None,
+            name: "(orphaned)".to_string(),
+            exe: None,
+            ai_request_count: 0,
+            synthetic: true,
+            children: orphan_nodes,
+        });
+    }
+
+    ProcessTreeResponse {
+        processes,
+        total: entries.len(),
+    }
+}
+
 /// Get detailed metrics in JSON format
 pub async fn get_metrics(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     if let Some(metrics) = &state.metrics {
@@ -206,6 +625,72 @@ pub async fn get_metrics(State(state): State<Arc<AppState>>) -> Json<serde_json:
     }
 }
 
+/// Set the metric counters back to zero, returning the values they had
+///
+/// Prometheus sees this as a counter reset, which `rate()` tolerates.
+pub async fn reset_metrics(State(state): State<Arc<AppState>>) -> Response {
+    let Some(metrics) = &state.metrics else {
+        return metrics_unavailable();
+    };
+    Json(serde_json::json!({
+        "reset_at": chrono::Utc::now(),
+        "counters": metrics.reset(),
+    }))
+    .into_response()
+}
+
+/// Counter increments per rollup interval over a recent window
+pub async fn get_metrics_series(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SeriesQuery>,
+) -> Response {
+    let Some(metrics) = &state.metrics else {
+        return metrics_unavailable();
+    };
+    let window = match query.window.as_deref().map(parse_window).transpose() {
+        Ok(window) => window.unwrap_or(DEFAULT_SERIES_WINDOW),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": e })),
+            )
+                .into_response()
+        }
+    };
+    Json(serde_json::json!({
+        "interval_secs": ROLLUP_INTERVAL.as_secs(),
+        "window_secs": window.as_secs(),
+        "buckets": metrics.series(window),
+    }))
+    .into_response()
+}
+
+fn metrics_unavailable() -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": "Metrics collector not initialized" })),
+    )
+        .into_response()
+}
+
+/// Parse a window such as `90s`, `5m` or `1h`; a bare number is seconds
+fn parse_window(window: &str) -> Result<Duration, String> {
+    let window = window.trim();
+    let (number, unit_secs) = match window.char_indices().last() {
+        Some((i, 's')) => (&window[..i], 1),
+        Some((i, 'm')) => (&window[..i], 60),
+        Some((i, 'h')) => (&window[..i], 3600),
+        _ => (window, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit_secs))
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid window: {:?} (expected e.g. 90s, 5m or 1h)", window))
+}
+
 /// Get metrics in Prometheus format
 pub async fn get_metrics_prometheus(
     State(state): State<Arc<AppState>>,
@@ -275,3 +760,104 @@ pub async fn get_process_metrics(
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oisp_core::events::{AiRequestEvent, EventEnvelope, ProcessExecEvent, ProcessInfo};
+
+    fn envelope(event_type: &str, pid: u32, ppid: Option<u32>, name: &str) -> EventEnvelope {
+        let mut envelope = EventEnvelope::new(event_type);
+        envelope.process = Some(ProcessInfo {
+            pid,
+            ppid,
+            name: Some(name.to_string()),
+            exe: Some(format!("/usr/bin/{}", name)),
+            ..Default::default()
+        });
+        envelope
+    }
+
+    fn exec(pid: u32, ppid: Option<u32>, name: &str) -> Arc<OispEvent> {
+        Arc::new(OispEvent::ProcessExec(ProcessExecEvent {
+            envelope: envelope("process.exec", pid, ppid, name),
+            data: serde_json::from_value(serde_json::json!({"exe": name})).unwrap(),
+        }))
+    }
+
+    fn ai_request(pid: u32, ppid: Option<u32>, name: &str) -> Arc<OispEvent> {
+        Arc::new(OispEvent::AiRequest(AiRequestEvent {
+            envelope: envelope("ai.request", pid, ppid, name),
+            data: serde_json::from_value(serde_json::json!({"request_id": "req"})).unwrap(),
+        }))
+    }
+
+    #[test]
+    fn test_process_tree_nesting_and_counts() {
+        let events = vec![
+            exec(100, None, "launchd"),
+            exec(200, Some(100), "zsh"),
+            exec(300, Some(200), "python"),
+            ai_request(300, Some(200), "python"),
+            ai_request(300, Some(200), "python"),
+            ai_request(400, Some(200), "node"),
+            // Parent 999 was never observed
+            ai_request(500, Some(999), "cursor"),
+            exec(600, Some(500), "cursor-helper"),
+        ];
+
+        let tree = build_process_tree(&events);
+        assert_eq!(tree.total, 6);
+        assert_eq!(tree.processes.len(), 2);
+
+        let root = &tree.processes[0];
+        assert_eq!(root.pid, 100);
+        assert_eq!(root.children.len(), 1);
+        let shell = &root.children[0];
+        assert_eq!(shell.name, "zsh");
+        assert_eq!(
+            shell.children.iter().map(|c| c.pid).collect::<Vec<_>>(),
+            vec![300, 400]
+        );
+        assert_eq!(shell.children[0].ai_request_count, 2);
+        assert_eq!(shell.children[0].exe.as_deref(), Some("/usr/bin/python"));
+        assert_eq!(shell.children[1].ai_request_count, 1);
+
+        let orphans = &tree.processes[1];
+        assert!(orphans.synthetic);
+        assert_eq!(orphans.pid, ORPHAN_ROOT_PID);
+        assert_eq!(orphans.children.len(), 1);
+        assert_eq!(orphans.children[0].pid, 500);
+        assert_eq!(orphans.children[0].ai_request_count, 1);
+        assert_eq!(orphans.children[0].children[0].name, "cursor-helper");
+    }
+
+    #[test]
+    fn test_process_tree_parent_cycle_is_orphaned() {
+        let events = vec![exec(10, Some(20), "a"), exec(20, Some(10), "b")];
+
+        let tree = build_process_tree(&events);
+        assert_eq!(tree.processes.len(), 1);
+        let orphans = &tree.processes[0];
+        assert!(orphans.synthetic);
+        assert_eq!(orphans.children.len(), 1);
+        assert_eq!(orphans.children[0].children.len(), 1);
+
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["processes"][0]["synthetic"], true);
+        assert!(json["processes"][0]["children"][0]
+            .get("synthetic")
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_window("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_window("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_window("45"), Ok(Duration::from_secs(45)));
+        assert!(parse_window("0m").is_err());
+        assert!(parse_window("5d").is_err());
+        assert!(parse_window("").is_err());
+    }
+}
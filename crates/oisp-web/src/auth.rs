@@ -0,0 +1,266 @@
+//! Optional authentication for the API and WebSocket
+//!
+//! When [`WebConfig::auth`](crate::WebConfig) is set, `/api/*` (except
+//! `/api/health`) and `/ws` require either a static bearer token or HTTP
+//! basic-auth credentials. Failures get `401` with a `WWW-Authenticate`
+//! challenge, which also makes browsers prompt for basic-auth credentials.
+//! Browsers cannot set headers on WebSocket upgrades, so `/ws` alone also
+//! accepts the bearer token as a `token` query parameter.
+//!
+//! Routes that change sensor state (capture attach and detach, metrics
+//! reset) are additionally guarded by [`require_control`].
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use base64::Engine;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Accepted credentials; a request passes if it matches either
+#[derive(Clone, Default)]
+pub struct AuthConfig {
+    /// Static token accepted as `Authorization: Bearer <token>`
+    pub bearer_token: Option<String>,
+    /// Username and password accepted as `Authorization: Basic ...`
+    pub basic: Option<(String, String)>,
+}
+
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("bearer_token", &self.bearer_token.as_ref().map(|_| "***"))
+            .field("basic", &self.basic.as_ref().map(|(user, _)| (user, "***")))
+            .finish()
+    }
+}
+
+impl AuthConfig {
+    /// Whether any credentials are configured
+    pub fn is_enabled(&self) -> bool {
+        self.bearer_token.is_some() || self.basic.is_some()
+    }
+
+    /// Check the request's `Authorization` header, or the bearer token
+    /// passed as `query_token` on routes that accept one
+    fn authorize(&self, headers: &HeaderMap, query_token: Option<&str>) -> bool {
+        let authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+
+        if let Some(expected) = &self.bearer_token {
+            let presented = authorization
+                .and_then(|v| strip_scheme(v, "Bearer"))
+                .or(query_token);
+            if presented.is_some_and(|token| constant_time_eq(token, expected)) {
+                return true;
+            }
+        }
+
+        if let Some((user, password)) = &self.basic {
+            let decoded = authorization
+                .and_then(|v| strip_scheme(v, "Basic"))
+                .and_then(|b64| base64::engine::general_purpose::STANDARD.decode(b64).ok())
+                .and_then(|raw| String::from_utf8(raw).ok());
+            if let Some((u, p)) = decoded.as_deref().and_then(|d| d.split_once(':')) {
+                // Evaluate both so timing does not reveal which one matched
+                let user_ok = constant_time_eq(u, user);
+                let password_ok = constant_time_eq(p, password);
+                if user_ok && password_ok {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn challenge(&self) -> HeaderValue {
+        let value = if self.basic.is_some() {
+            r#"Basic realm="oisp-sensor", charset="UTF-8""#
+        } else {
+            r#"Bearer realm="oisp-sensor""#
+        };
+        HeaderValue::from_static(value)
+    }
+}
+
+fn strip_scheme<'a>(value: &'a str, scheme: &str) -> Option<&'a str> {
+    let (s, rest) = value.split_once(' ')?;
+    s.eq_ignore_ascii_case(scheme).then(|| rest.trim())
+}
+
+/// Path of the WebSocket upgrade, the only route taking `?token=`
+const WS_PATH: &str = "/ws";
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// The percent-decoded `token` query parameter
+fn query_token(uri: &Uri) -> Option<String> {
+    Query::<TokenQuery>::try_from_uri(uri).ok()?.0.token
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Middleware rejecting requests without valid credentials
+pub async fn require_auth(
+    State(auth): State<Arc<AuthConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Tokens in other URLs would end up in access logs and Referer headers
+    let token = (request.uri().path() == WS_PATH)
+        .then(|| query_token(request.uri()))
+        .flatten();
+    if auth.authorize(request.headers(), token.as_deref()) {
+        return next.run(request).await;
+    }
+
+    debug!(
+        "Rejected unauthenticated request to {}",
+        request.uri().path()
+    );
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, auth.challenge());
+    response
+}
+
+/// Middleware for routes that change sensor state
+///
+/// They need `Content-Type: application/json`, which a cross-site form or
+/// script cannot send without a CORS preflight, and the preflight only
+/// allows `GET`. Credentials, when configured, are checked by
+/// [`require_auth`]; without them the server may still listen on every
+/// interface, so the request must also come from loopback.
+pub async fn require_control(
+    State(auth_enabled): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !is_json(request.headers()) {
+        return control_rejected(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected Content-Type: application/json",
+        );
+    }
+
+    if !auth_enabled {
+        let (mut parts, body) = request.into_parts();
+        let peer = ConnectInfo::<SocketAddr>::from_request_parts(&mut parts, &())
+            .await
+            .ok();
+        if !peer.is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback()) {
+            debug!(
+                "Rejected {} from {:?}: not loopback and no credentials configured",
+                parts.uri.path(),
+                peer.map(|ConnectInfo(addr)| addr)
+            );
+            return control_rejected(
+                StatusCode::FORBIDDEN,
+                "Only allowed from localhost unless web authentication is configured",
+            );
+        }
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+
+    next.run(request).await
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn control_rejected(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = AuthConfig {
+            bearer_token: Some("s3cret".to_string()),
+            basic: Some(("admin".to_string(), "pa:ss".to_string())),
+        };
+        let basic = |creds: &str| {
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(creds)
+            )
+        };
+
+        assert!(auth.authorize(&headers("Bearer s3cret"), None));
+        assert!(auth.authorize(&headers("bearer s3cret"), None));
+        assert!(auth.authorize(&headers(&basic("admin:pa:ss")), None));
+        assert!(auth.authorize(&HeaderMap::new(), Some("s3cret")));
+
+        assert!(!auth.authorize(&HeaderMap::new(), None));
+        assert!(!auth.authorize(&headers("Bearer s3cre"), None));
+        assert!(!auth.authorize(&headers(&basic("admin:wrong")), None));
+        assert!(!auth.authorize(&headers("Basic not-base64!"), None));
+        assert!(!auth.authorize(&HeaderMap::new(), Some("nope")));
+    }
+
+    #[test]
+    fn test_query_token_percent_decoded() {
+        let token = |uri: &str| query_token(&uri.parse().unwrap());
+        assert_eq!(token("/ws?a=1&token=s3cret").as_deref(), Some("s3cret"));
+        assert_eq!(token("/ws?token=a%2Fb%3Dc%26d").as_deref(), Some("a/b=c&d"));
+        assert_eq!(token("/ws?a=1"), None);
+        assert_eq!(token("/ws"), None);
+    }
+
+    #[test]
+    fn test_is_json() {
+        let content_type = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, value.parse().unwrap());
+            headers
+        };
+        assert!(is_json(&content_type("application/json")));
+        assert!(is_json(&content_type("Application/JSON; charset=utf-8")));
+        assert!(!is_json(&content_type("text/plain")));
+        assert!(!is_json(&content_type("application/x-www-form-urlencoded")));
+        assert!(!is_json(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let auth = AuthConfig {
+            bearer_token: Some("s3cret".to_string()),
+            basic: Some(("admin".to_string(), "hunter2".to_string())),
+        };
+        let debug = format!("{:?}", auth);
+        assert!(!debug.contains("s3cret"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("admin"));
+    }
+}
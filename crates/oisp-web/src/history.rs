@@ -0,0 +1,415 @@
+//! Historical events from the JSONL export
+//!
+//! The API keeps only the most recent events in memory. When the sensor also
+//! writes a JSONL file, [`JsonlHistory`] pages further into the past by
+//! reading that file backwards from a cursor.
+//!
+//! A background poll follows the file as it grows. Only complete lines up to
+//! the polled offset are served, so a line still being written is never
+//! returned half-parsed. The offset is persisted next to the file, so a
+//! restarted sensor resumes where it was instead of reading the whole file
+//! again. When the file is rotated (replaced, or truncated) the offset starts
+//! over on the new file and cursors into the old one are rejected.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Most bytes read from the file by one poll
+const MAX_POLL_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Most bytes scanned for one page of history
+const MAX_PAGE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+
+    #[error("The event file was rotated since this cursor was issued")]
+    Rotated,
+
+    #[error("Failed to read event history: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Position in one generation of the event file
+///
+/// Shown to API clients as `<file id>-<offset>`, e.g. `1a2b3c-40960`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryCursor {
+    /// Identity of the file, so cursors do not carry over a rotation
+    pub file_id: u64,
+    /// Byte offset of the start of a line
+    pub offset: u64,
+}
+
+impl fmt::Display for HistoryCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}-{}", self.file_id, self.offset)
+    }
+}
+
+impl FromStr for HistoryCursor {
+    type Err = HistoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HistoryError::InvalidCursor(s.to_string());
+        let (file_id, offset) = s.split_once('-').ok_or_else(invalid)?;
+        Ok(Self {
+            file_id: u64::from_str_radix(file_id, 16).map_err(|_| invalid())?,
+            offset: offset.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One page of historical events, newest first
+#[derive(Debug)]
+pub struct HistoryPage {
+    pub events: Vec<serde_json::Value>,
+    /// Cursor for the next (older) page; `None` at the start of the file
+    pub next: Option<HistoryCursor>,
+}
+
+/// Reader of past events from a JSONL event file
+pub struct JsonlHistory {
+    path: PathBuf,
+    state_path: PathBuf,
+    /// End of the complete lines read so far
+    tail: Mutex<HistoryCursor>,
+}
+
+impl JsonlHistory {
+    /// History of the JSONL file at `path`, resuming from the offset saved
+    /// at `<path>.cursor` when it still applies
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut state_path = path.clone().into_os_string();
+        state_path.push(".cursor");
+        Self::with_state_path(path, PathBuf::from(state_path))
+    }
+
+    /// History of `path` with its offset saved at `state_path`
+    pub fn with_state_path(path: impl Into<PathBuf>, state_path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let state_path = state_path.into();
+        let tail = std::fs::read(&state_path)
+            .ok()
+            .and_then(|state| serde_json::from_slice::<HistoryCursor>(&state).ok())
+            .filter(|saved| {
+                std::fs::metadata(&path)
+                    .is_ok_and(|m| file_id(&m) == saved.file_id && m.len() >= saved.offset)
+            });
+        if let Some(saved) = &tail {
+            debug!(
+                "Resuming event history of {} at offset {}",
+                path.display(),
+                saved.offset
+            );
+        }
+
+        Self {
+            path,
+            state_path,
+            tail: Mutex::new(tail.unwrap_or(HistoryCursor {
+                file_id: 0,
+                offset: 0,
+            })),
+        }
+    }
+
+    /// The end of the history read so far, i.e. the cursor of the newest page
+    pub fn tail(&self) -> HistoryCursor {
+        *self.tail.lock().unwrap()
+    }
+
+    /// Read lines appended since the last poll and advance the tail
+    ///
+    /// Returns the number of complete lines read. A replaced or truncated
+    /// file restarts the history at its beginning.
+    pub fn poll(&self) -> Result<usize, HistoryError> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            // Not written yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let metadata = file.metadata()?;
+        let id = file_id(&metadata);
+
+        let mut tail = self.tail.lock().unwrap();
+        if tail.file_id != id || metadata.len() < tail.offset {
+            if tail.offset > 0 {
+                info!(
+                    "Event file {} was rotated, restarting its history",
+                    self.path.display()
+                );
+            }
+            *tail = HistoryCursor {
+                file_id: id,
+                offset: 0,
+            };
+        }
+        if metadata.len() == tail.offset {
+            return Ok(0);
+        }
+
+        let len = (metadata.len() - tail.offset).min(MAX_POLL_BYTES);
+        let mut buf = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(tail.offset))?;
+        file.take(len).read_to_end(&mut buf)?;
+
+        // Stop after the last complete line
+        let Some(end) = buf.iter().rposition(|b| *b == b'\n') else {
+            return Ok(0);
+        };
+        let lines = buf[..=end].iter().filter(|b| **b == b'\n').count();
+        tail.offset += end as u64 + 1;
+
+        let state = serde_json::to_vec(&*tail).expect("cursor serializes");
+        if let Err(e) = write_atomic(&self.state_path, &state) {
+            warn!(
+                "Failed to save event history offset to {}: {}",
+                self.state_path.display(),
+                e
+            );
+        }
+        Ok(lines)
+    }
+
+    /// Up to `limit` events that start before `before` (default: the tail),
+    /// newest first, leaving out events whose `event_id` is in `skip`
+    pub fn page(
+        &self,
+        before: Option<HistoryCursor>,
+        limit: usize,
+        skip: &HashSet<String>,
+    ) -> Result<HistoryPage, HistoryError> {
+        let tail = self.tail();
+        let before = match before {
+            Some(cursor) if cursor.file_id != tail.file_id => return Err(HistoryError::Rotated),
+            Some(cursor) => cursor.offset.min(tail.offset),
+            None => tail.offset,
+        };
+        if before == 0 || limit == 0 {
+            return Ok(HistoryPage {
+                events: Vec::new(),
+                next: (before > 0).then_some(HistoryCursor {
+                    file_id: tail.file_id,
+                    offset: before,
+                }),
+            });
+        }
+
+        let start = before.saturating_sub(MAX_PAGE_BYTES);
+        let mut buf = Vec::with_capacity((before - start) as usize);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.take(before - start).read_to_end(&mut buf)?;
+
+        // Unless at the start of the file, the window begins mid-line
+        let first_line = if start == 0 {
+            0
+        } else {
+            match buf.iter().position(|b| *b == b'\n') {
+                Some(newline) => newline + 1,
+                // One line longer than the window; skip it
+                None => buf.len(),
+            }
+        };
+
+        let mut events = Vec::new();
+        let mut next = start + first_line as u64;
+        let mut line_end = buf.len();
+        while line_end > first_line && events.len() < limit {
+            // `line_end` is just past the line's newline
+            let body_end = line_end - 1;
+            let line_start = buf[first_line..body_end]
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(first_line, |newline| first_line + newline + 1);
+            let line = &buf[line_start..body_end];
+            line_end = line_start;
+            next = start + line_start as u64;
+
+            match serde_json::from_slice::<serde_json::Value>(line) {
+                Ok(event) => {
+                    let seen = event
+                        .get("event_id")
+                        .and_then(|id| id.as_str())
+                        .is_some_and(|id| skip.contains(id));
+                    if !seen {
+                        events.push(event);
+                    }
+                }
+                Err(e) if !line.is_empty() => {
+                    debug!("Skipping unparsable event history line: {}", e);
+                }
+                Err(_) => {}
+            }
+        }
+
+        Ok(HistoryPage {
+            events,
+            next: (next > 0).then_some(HistoryCursor {
+                file_id: tail.file_id,
+                offset: next,
+            }),
+        })
+    }
+}
+
+/// Identity of a file that changes when it is replaced
+#[cfg(unix)]
+fn file_id(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+/// Identity of a file that changes when it is replaced
+///
+/// Without inodes a replaced file is only noticed once it is shorter than
+/// the offset already read.
+#[cfg(not(unix))]
+fn file_id(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn event(n: usize) -> String {
+        format!(
+            "{{\"event_id\":\"evt-{}\",\"event_type\":\"ai.request\"}}\n",
+            n
+        )
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn ids(page: &HistoryPage) -> Vec<&str> {
+        page.events
+            .iter()
+            .map(|e| e["event_id"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = HistoryCursor {
+            file_id: 0x1a2b,
+            offset: 4096,
+        };
+        assert_eq!(cursor.to_string(), "1a2b-4096");
+        assert_eq!("1a2b-4096".parse::<HistoryCursor>().unwrap(), cursor);
+        assert!("4096".parse::<HistoryCursor>().is_err());
+        assert!("xyz-1".parse::<HistoryCursor>().is_err());
+    }
+
+    #[test]
+    fn test_poll_advances_and_persists_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let history = JsonlHistory::open(&path);
+        assert_eq!(history.poll().unwrap(), 0);
+
+        append(&path, &(event(1) + &event(2)));
+        // Still being written
+        append(&path, "{\"event_id\":\"evt-3\"");
+        assert_eq!(history.poll().unwrap(), 2);
+        let after_two = history.tail();
+        assert_eq!(after_two.offset, (event(1).len() + event(2).len()) as u64);
+        assert_eq!(history.poll().unwrap(), 0);
+
+        append(&path, ",\"event_type\":\"ai.request\"}\n");
+        assert_eq!(history.poll().unwrap(), 1);
+        let tail = history.tail();
+        assert_eq!(tail.offset, std::fs::metadata(&path).unwrap().len());
+
+        // A restart resumes from the saved offset
+        let reopened = JsonlHistory::open(&path);
+        assert_eq!(reopened.tail(), tail);
+        assert_eq!(reopened.poll().unwrap(), 0);
+        let page = reopened.page(None, 10, &HashSet::new()).unwrap();
+        assert_eq!(ids(&page), ["evt-3", "evt-2", "evt-1"]);
+    }
+
+    #[test]
+    fn test_pages_walk_backwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        append(&path, &(1..=5).map(event).collect::<String>());
+        append(&path, "not json\n");
+        let history = JsonlHistory::open(&path);
+        history.poll().unwrap();
+
+        // Events already in memory are left out
+        let skip = HashSet::from(["evt-5".to_string()]);
+        let page = history.page(None, 2, &skip).unwrap();
+        assert_eq!(ids(&page), ["evt-4", "evt-3"]);
+
+        let next = page.next.unwrap();
+        assert_eq!(next.offset, (event(1).len() + event(2).len()) as u64);
+        let page = history.page(Some(next), 2, &skip).unwrap();
+        assert_eq!(ids(&page), ["evt-2", "evt-1"]);
+        assert_eq!(page.next, None);
+
+        // Events appended after the last poll are not served yet
+        append(&path, &event(6));
+        let page = history.page(None, 1, &HashSet::new()).unwrap();
+        assert_eq!(ids(&page), ["evt-5"]);
+    }
+
+    #[test]
+    fn test_rotation_restarts_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        append(&path, &(1..=3).map(event).collect::<String>());
+        let history = JsonlHistory::open(&path);
+        history.poll().unwrap();
+        let old = history.page(None, 1, &HashSet::new()).unwrap().next;
+
+        // Truncated in place (copytruncate)
+        std::fs::write(&path, event(10)).unwrap();
+        assert_eq!(history.poll().unwrap(), 1);
+        let page = history.page(None, 10, &HashSet::new()).unwrap();
+        assert_eq!(ids(&page), ["evt-10"]);
+
+        // Renamed away and recreated
+        std::fs::rename(&path, dir.path().join("events.jsonl.1")).unwrap();
+        append(&path, &(event(20) + &event(21)));
+        assert_eq!(history.poll().unwrap(), 2);
+        let page = history.page(None, 10, &HashSet::new()).unwrap();
+        assert_eq!(ids(&page), ["evt-21", "evt-20"]);
+
+        if cfg!(unix) {
+            assert!(matches!(
+                history.page(old, 10, &HashSet::new()),
+                Err(HistoryError::Rotated)
+            ));
+        }
+    }
+}
@@ -3,26 +3,38 @@
 //! Serves the React frontend (embedded) and provides REST/WebSocket APIs.
 
 mod api;
+pub mod auth;
+pub mod history;
+pub mod tls;
 pub mod web_event;
 mod ws;
 
+pub use auth::AuthConfig;
+pub use history::JsonlHistory;
+pub use tls::TlsConfig;
 pub use web_event::{WebEvent, WebEventType, WebEventsResponse};
 
 use axum::{
     body::Body,
-    http::{header, StatusCode},
-    response::{IntoResponse, Json, Response},
-    routing::get,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{Json, Response},
+    routing::{get, post},
+    serve::ListenerExt,
     Router,
 };
 use oisp_core::events::OispEvent;
 use oisp_core::metrics::SharedMetrics;
+use oisp_core::plugins::SharedProcessTargets;
 use oisp_core::trace::TraceBuilder;
 use rust_embed::RustEmbed;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{broadcast, RwLock};
-use tower_http::cors::{Any, CorsLayer};
-use tracing::{debug, info};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{debug, info, warn};
 
 /// Embedded frontend assets (built from frontend/ directory)
 #[derive(RustEmbed)]
@@ -30,19 +42,59 @@ use tracing::{debug, info};
 #[prefix = ""]
 struct FrontendAssets;
 
+/// Looks up a frontend file by path
+type AssetLookup = fn(&str) -> Option<rust_embed::EmbeddedFile>;
+
+/// Served instead of the UI by builds without `frontend/out`
+const NO_FRONTEND_PAGE: &str = r#"<!doctype html>
+<html>
+<head><meta charset="utf-8"><title>OISP Sensor</title></head>
+<body style="font-family: sans-serif; max-width: 40em; margin: 3em auto">
+<h1>OISP Sensor</h1>
+<p>This build of the sensor does not include the web UI. The sensor is
+running, and its API is available:</p>
+<ul>
+<li><a href="/api/health">/api/health</a> &mdash; health check</li>
+<li><a href="/api/events">/api/events</a> &mdash; recent events</li>
+<li><a href="/api/web-events">/api/web-events</a> &mdash; events in web UI format</li>
+<li><a href="/api/traces">/api/traces</a> &mdash; agent traces</li>
+<li><a href="/api/stats">/api/stats</a> &mdash; statistics</li>
+<li><a href="/metrics">/metrics</a> &mdash; Prometheus metrics</li>
+<li><code>/ws</code> &mdash; WebSocket stream of live events</li>
+</ul>
+<p>To include the UI, build the frontend before the sensor:
+<code>cd frontend &amp;&amp; npm install &amp;&amp; npm run build</code>, then
+<code>cargo build --release</code>.</p>
+</body>
+</html>
+"#;
+
 /// Web server configuration
 #[derive(Debug, Clone)]
 pub struct WebConfig {
     pub host: String,
     pub port: u16,
+    /// Serve HTTPS and `wss://` with this certificate; plain HTTP when unset
+    pub tls: Option<TlsConfig>,
+    /// Require credentials for `/api/*` (except health) and `/ws`
+    pub auth: Option<AuthConfig>,
+    /// Origins allowed to call the API cross-origin (`"*"` allows any);
+    /// when empty, only localhost origins are allowed
+    pub cors_origins: Vec<String>,
+    /// JSONL event file to page into for events older than those in memory
+    pub history: Option<PathBuf>,
 }
 
 impl Default for WebConfig {
     fn default() -> Self {
         Self {
-            // Use 0.0.0.0 for Docker compatibility
-            host: "0.0.0.0".to_string(),
+            // Containers set 0.0.0.0 (OISP_WEB_HOST) to publish the port
+            host: "127.0.0.1".to_string(),
             port: 7777,
+            tls: None,
+            auth: None,
+            cors_origins: Vec::new(),
+            history: None,
         }
     }
 }
@@ -50,12 +102,19 @@ impl Default for WebConfig {
 /// Maximum events to keep in memory for API access
 const MAX_EVENTS: usize = 1000;
 
+/// How often the JSONL history is checked for new lines
+const HISTORY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Shared application state
 pub struct AppState {
     pub event_tx: broadcast::Sender<Arc<OispEvent>>,
     pub trace_builder: Arc<RwLock<TraceBuilder>>,
     pub events: Arc<RwLock<Vec<Arc<OispEvent>>>>,
     pub metrics: Option<SharedMetrics>,
+    /// Runtime process attach/detach, when the capture supports it
+    pub process_targets: Option<SharedProcessTargets>,
+    /// Older events from the JSONL output, when there is one
+    pub history: Option<Arc<JsonlHistory>>,
 }
 
 /// Start the web server
@@ -64,15 +123,16 @@ pub async fn start_server(
     event_tx: broadcast::Sender<Arc<OispEvent>>,
     trace_builder: Arc<RwLock<TraceBuilder>>,
 ) -> anyhow::Result<()> {
-    start_server_with_metrics(config, event_tx, trace_builder, None).await
+    start_server_with_metrics(config, event_tx, trace_builder, None, None).await
 }
 
-/// Start the web server with optional metrics collector
+/// Start the web server with optional metrics collector and capture control
 pub async fn start_server_with_metrics(
     config: WebConfig,
     event_tx: broadcast::Sender<Arc<OispEvent>>,
     trace_builder: Arc<RwLock<TraceBuilder>>,
     metrics: Option<SharedMetrics>,
+    process_targets: Option<SharedProcessTargets>,
 ) -> anyhow::Result<()> {
     let events = Arc::new(RwLock::new(Vec::new()));
 
@@ -101,53 +161,171 @@ pub async fn start_server_with_metrics(
         }
     });
 
+    let history = config.history.as_ref().map(|path| {
+        let history = Arc::new(JsonlHistory::open(path));
+        let poller = history.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HISTORY_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let history = poller.clone();
+                match tokio::task::spawn_blocking(move || history.poll()).await {
+                    Ok(Err(e)) => debug!("Failed to follow event history: {}", e),
+                    Err(_) => break,
+                    Ok(Ok(_)) => {}
+                }
+            }
+        });
+        history
+    });
+
     let state = Arc::new(AppState {
         event_tx,
         trace_builder,
         events,
         metrics,
+        process_targets,
+        history,
     });
+    if config.auth.as_ref().is_some_and(AuthConfig::is_enabled) {
+        info!("Web API authentication enabled");
+    }
+    let app = router(state, &config);
+
+    let addr = format!("{}:{}", config.host, config.port);
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    info!("Web UI available at {}://{}", scheme, addr);
+    info!("  - React frontend at /");
+    info!("  - API at /api/*");
+    info!("  - WebSocket at /ws");
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // The peer address decides whether state-changing routes are allowed
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    match config.tls {
+        Some(tls_config) => {
+            let cert = Arc::new(tls::ReloadableCert::load(tls_config)?);
+            tls::reload_on_sighup(cert.clone());
+            // Tapped so the peer address is available as connect info
+            let listener = tls::TlsListener::new(listener, tls::acceptor(cert)?)?.tap_io(|_| {});
+            axum::serve(listener, app).await?;
+        }
+        None => axum::serve(listener, app).await?,
+    }
 
-    let app = Router::new()
-        // API routes
+    Ok(())
+}
+
+/// Build the application router
+///
+/// With `auth` set, the API and WebSocket routes require credentials while
+/// the health check, Prometheus metrics and frontend assets stay open.
+/// Routes that change sensor state also require a JSON body, and without
+/// `auth` a loopback peer (see [`auth::require_control`]).
+fn router(state: Arc<AppState>, config: &WebConfig) -> Router {
+    router_with_assets(state, config, FrontendAssets::get)
+}
+
+fn router_with_assets(state: Arc<AppState>, config: &WebConfig, assets: AssetLookup) -> Router {
+    if assets("index.html").is_none() {
+        warn!("Web UI not included in this build; serving API only");
+    }
+
+    let cors = cors_layer(&config.cors_origins);
+    let auth = config.auth.clone().filter(AuthConfig::is_enabled);
+
+    let mut protected = Router::new()
         .route("/api/events", get(api::get_events))
         .route("/api/web-events", get(api::get_web_events))
         .route("/api/traces", get(api::get_traces))
         .route("/api/inventory", get(api::get_inventory))
+        .route("/api/process-tree", get(api::get_process_tree))
         .route("/api/stats", get(api::get_stats))
+        .route("/api/capture-stats", get(api::get_capture_stats))
         .route("/api/metrics", get(api::get_metrics))
         .route("/api/metrics/processes", get(api::get_process_metrics))
+        .route("/api/metrics/series", get(api::get_metrics_series))
+        .route("/api/capture/targets", get(api::get_capture_targets))
+        .route("/ws", get(ws::ws_handler));
+    // Routes changing sensor state
+    let control = Router::new()
+        .route("/api/metrics/reset", post(api::reset_metrics))
+        .route("/api/capture/attach", post(api::attach_capture))
+        .route("/api/capture/detach", post(api::detach_capture))
+        .route_layer(middleware::from_fn_with_state(
+            auth.is_some(),
+            auth::require_control,
+        ));
+    protected = protected.merge(control);
+    if let Some(auth) = auth {
+        protected = protected.route_layer(middleware::from_fn_with_state(
+            Arc::new(auth),
+            auth::require_auth,
+        ));
+    }
+
+    Router::new()
+        .merge(protected)
         .route("/metrics", get(api::get_metrics_prometheus))
         .route("/api/health", get(health_check))
-        .route("/ws", get(ws::ws_handler))
         // Frontend routes - serve React app for all paths
-        .fallback(serve_frontend)
+        .fallback(move |uri: axum::http::Uri| async move { serve_frontend(assets, uri) })
         .layer(cors)
-        .with_state(state);
+        .with_state(state)
+}
 
-    let addr = format!("{}:{}", config.host, config.port);
-    info!("Web UI available at http://{}", addr);
-    info!("  - React frontend at /");
-    info!("  - API at /api/*");
-    info!("  - WebSocket at /ws");
+/// Build the CORS layer for the configured origin allowlist
+///
+/// Only `GET` and the headers needed for authentication are allowed, so
+/// other sites can read the API but not attach or detach capture. Wildcard
+/// origins must be opted into with `"*"`; by default only pages served from
+/// localhost may call the API.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        warn!("CORS allows any origin; any website can call the web API");
+        AllowOrigin::any()
+    } else if origins.is_empty() {
+        AllowOrigin::predicate(|origin, _| is_localhost_origin(origin))
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|o| {
+            let origin = o.trim_end_matches('/');
+            HeaderValue::from_str(origin)
+                .inspect_err(|_| warn!("Ignoring invalid CORS origin: {}", o))
+                .ok()
+        }))
+    };
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+}
 
-    Ok(())
+fn is_localhost_origin(origin: &HeaderValue) -> bool {
+    let Some(rest) = origin.to_str().ok().and_then(|o| {
+        o.strip_prefix("http://")
+            .or_else(|| o.strip_prefix("https://"))
+    }) else {
+        return false;
+    };
+    let host = match rest.strip_prefix('[') {
+        // IPv6 literal, e.g. [::1]:7777
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => rest.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
 }
 
 /// Serve embedded frontend files
-async fn serve_frontend(uri: axum::http::Uri) -> impl IntoResponse {
+fn serve_frontend(assets: AssetLookup, uri: axum::http::Uri) -> Response {
     let path = uri.path().trim_start_matches('/');
 
     // Try exact path first
-    if let Some(content) = FrontendAssets::get(path) {
+    if let Some(content) = assets(path) {
         let mime = mime_guess::from_path(path).first_or_octet_stream();
         return Response::builder()
             .status(StatusCode::OK)
@@ -163,7 +341,7 @@ async fn serve_frontend(uri: axum::http::Uri) -> impl IntoResponse {
         format!("{}/index.html", path)
     };
 
-    if let Some(content) = FrontendAssets::get(&index_path) {
+    if let Some(content) = assets(&index_path) {
         return Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, "text/html")
@@ -172,7 +350,7 @@ async fn serve_frontend(uri: axum::http::Uri) -> impl IntoResponse {
     }
 
     // For SPA routing: serve root index.html for any unmatched route
-    if let Some(content) = FrontendAssets::get("index.html") {
+    if let Some(content) = assets("index.html") {
         return Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, "text/html")
@@ -180,13 +358,11 @@ async fn serve_frontend(uri: axum::http::Uri) -> impl IntoResponse {
             .unwrap();
     }
 
-    // Fallback to 404
+    // Built without the frontend: point at the API instead of a bare 404
     Response::builder()
-        .status(StatusCode::NOT_FOUND)
-        .header(header::CONTENT_TYPE, "text/html")
-        .body(Body::from(
-            "<html><body><h1>404 Not Found</h1></body></html>",
-        ))
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(NO_FRONTEND_PAGE))
         .unwrap()
 }
 
@@ -198,3 +374,566 @@ async fn health_check() -> Json<serde_json::Value> {
         "version": env!("CARGO_PKG_VERSION")
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::connect_info::MockConnectInfo;
+    use oisp_core::plugins::{ProcessTargetError, ProcessTargets};
+    use rustls::pki_types::ServerName;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tower::ServiceExt;
+
+    fn test_state() -> Arc<AppState> {
+        let (event_tx, _) = broadcast::channel(16);
+        Arc::new(AppState {
+            event_tx,
+            trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            metrics: None,
+            process_targets: None,
+            history: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tls_serves_health() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tls_config, cert_pem) = tls::test_cert::write(dir.path());
+
+        let cert = Arc::new(tls::ReloadableCert::load(tls_config).unwrap());
+        let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = tls::TlsListener::new(tcp, tls::acceptor(cert).unwrap()).unwrap();
+        let addr = axum::serve::Listener::local_addr(&listener).unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router(test_state(), &WebConfig::default())).await
+        });
+
+        // Client trusting only the self-signed certificate
+        let mut roots = rustls::RootCertStore::empty();
+        for der in rustls_pemfile::certs(&mut cert_pem.as_bytes()) {
+            roots.add(der.unwrap()).unwrap();
+        }
+        let client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(server_name, tcp).await.unwrap();
+
+        stream
+            .write_all(b"GET /api/health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("\"status\":\"healthy\""));
+    }
+
+    async fn get_status(app: &Router, uri: &str, authorization: Option<&str>) -> Response {
+        let mut request = axum::http::Request::get(uri);
+        if let Some(value) = authorization {
+            request = request.header(header::AUTHORIZATION, value);
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_auth_protects_api() {
+        let app = router(
+            test_state(),
+            &WebConfig {
+                auth: Some(AuthConfig {
+                    bearer_token: Some("s3cret".to_string()),
+                    basic: Some(("admin".to_string(), "hunter2".to_string())),
+                }),
+                ..Default::default()
+            },
+        );
+
+        for uri in ["/api/events", "/api/stats", "/ws"] {
+            let response = get_status(&app, uri, None).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            let challenge = response.headers()[header::WWW_AUTHENTICATE]
+                .to_str()
+                .unwrap();
+            assert!(challenge.starts_with("Basic realm="), "{}", challenge);
+
+            let response = get_status(&app, uri, Some("Bearer wrong")).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+
+        let response = get_status(&app, "/api/events", Some("Bearer s3cret")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_status(&app, "/api/stats", Some("Basic YWRtaW46aHVudGVyMg==")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Health stays open for probes
+        let response = get_status(&app, "/api/health", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only the WebSocket upgrade takes the token in the URL
+        let response = get_status(&app, "/api/events?token=s3cret", None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get_status(&app, "/ws?token=s3cret", None).await;
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_no_auth_by_default() {
+        let app = router(test_state(), &WebConfig::default());
+        let response = get_status(&app, "/api/events", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = get_status(app, uri, None).await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_events_history_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let lines: String = (1..=3)
+            .map(|n| format!("{{\"event_id\":\"evt-{}\"}}\n", n))
+            .collect();
+        std::fs::write(&path, lines).unwrap();
+        let history = Arc::new(JsonlHistory::open(&path));
+        history.poll().unwrap();
+
+        let (event_tx, _) = broadcast::channel(16);
+        let state = Arc::new(AppState {
+            event_tx,
+            trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            metrics: None,
+            process_targets: None,
+            history: Some(history),
+        });
+        let app = router(state, &WebConfig::default());
+
+        let (status, body) = get_json(&app, "/api/events").await;
+        assert_eq!(status, StatusCode::OK);
+        let cursor = body["cursor"].as_str().unwrap().to_string();
+
+        let (status, body) =
+            get_json(&app, &format!("/api/events?before={}&limit=2", cursor)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["events"][0]["event_id"], "evt-3");
+        assert_eq!(body["events"][1]["event_id"], "evt-2");
+        let cursor = body["cursor"].as_str().unwrap().to_string();
+
+        let (_, body) = get_json(&app, &format!("/api/events?before={}", cursor)).await;
+        assert_eq!(body["events"].as_array().unwrap().len(), 1);
+        assert!(body.get("cursor").is_none());
+
+        let (status, _) = get_json(&app, "/api/events?before=nonsense").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Without a JSONL output there is no history
+        let app = router(test_state(), &WebConfig::default());
+        let (status, _) = get_json(&app, "/api/events?before=1-0").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    fn event(id: usize, event_type: &str, pid: u32, provider: Option<&str>) -> Arc<OispEvent> {
+        let data = match provider {
+            Some(name) => serde_json::json!({
+                "request_id": format!("req-{}", id),
+                "provider": { "name": name },
+            }),
+            None => serde_json::json!({ "exe": "/usr/bin/curl" }),
+        };
+        let event = serde_json::json!({
+            "oisp_version": "0.1",
+            "event_id": format!("evt-{}", id),
+            "event_type": event_type,
+            "ts": format!("2024-01-01T12:00:{:02}Z", id),
+            "process": { "pid": pid },
+            "source": { "collector": "test" },
+            "confidence": { "level": "high", "completeness": "full" },
+            "data": data,
+        });
+        Arc::new(serde_json::from_value(event).unwrap())
+    }
+
+    fn event_ids(body: &serde_json::Value) -> Vec<&str> {
+        body["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["event_id"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_events_query_filters() {
+        let state = test_state();
+        {
+            // Newest first, as the event buffer holds them
+            let mut events = state.events.write().await;
+            for n in (0..6).rev() {
+                let event = match n % 3 {
+                    0 => event(n, "process.exec", 10, None),
+                    1 => event(n, "ai.request", 20, Some("openai")),
+                    _ => event(n, "ai.request", 30, Some("anthropic")),
+                };
+                events.push(event);
+            }
+        }
+        let app = router(state, &WebConfig::default());
+
+        let (status, body) = get_json(&app, "/api/events").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            event_ids(&body),
+            ["evt-5", "evt-4", "evt-3", "evt-2", "evt-1", "evt-0"]
+        );
+
+        let (_, body) = get_json(&app, "/api/events?event_type=ai.request").await;
+        assert_eq!(event_ids(&body), ["evt-5", "evt-4", "evt-2", "evt-1"]);
+        assert_eq!(body["total"], 4);
+
+        let (_, body) = get_json(&app, "/api/events?provider=OpenAI&order=asc").await;
+        assert_eq!(event_ids(&body), ["evt-1", "evt-4"]);
+
+        let (_, body) = get_json(&app, "/api/events?pid=10&limit=1").await;
+        assert_eq!(event_ids(&body), ["evt-3"]);
+        assert_eq!(body["total"], 2);
+
+        // The newest matches, in ascending order
+        let (_, body) = get_json(&app, "/api/events?event_type=ai.request&limit=2&order=asc").await;
+        assert_eq!(event_ids(&body), ["evt-4", "evt-5"]);
+
+        let (_, body) = get_json(&app, "/api/events?event_type=file.open").await;
+        assert!(event_ids(&body).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_events_query_rejects_invalid_parameters() {
+        let app = router(test_state(), &WebConfig::default());
+
+        let (status, _) = get_json(&app, "/api/events?limit=1000").await;
+        assert_eq!(status, StatusCode::OK);
+
+        for (uri, message) in [
+            ("/api/events?limit=1001", "limit must be between 1 and 1000"),
+            ("/api/events?limit=0", "limit must be between 1 and 1000"),
+            ("/api/events?limit=many", "limit"),
+            ("/api/events?order=newest", "order"),
+            ("/api/events?pid=-1", "pid"),
+            ("/api/events?event_type=", "event_type"),
+            (
+                "/api/events?before=1-0&pid=10",
+                "cannot be combined with before",
+            ),
+        ] {
+            let (status, body) = get_json(&app, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            let error = body["error"].as_str().unwrap();
+            assert!(error.contains(message), "{}: {}", uri, error);
+        }
+    }
+
+    /// Accepts pids 1-99, as if each had OpenSSL loaded
+    #[derive(Default)]
+    struct FakeTargets(std::sync::Mutex<Vec<u32>>);
+
+    impl oisp_core::plugins::ProcessTargetControl for FakeTargets {
+        fn attach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError> {
+            if pid >= 100 {
+                return Err(ProcessTargetError::NoTlsLibrary(pid));
+            }
+            self.0.lock().unwrap().push(pid);
+            Ok(self.targets())
+        }
+
+        fn detach(&self, pid: u32) -> Result<ProcessTargets, ProcessTargetError> {
+            let mut pids = self.0.lock().unwrap();
+            let index = pids
+                .iter()
+                .position(|p| *p == pid)
+                .ok_or(ProcessTargetError::NotAttached(pid))?;
+            pids.remove(index);
+            drop(pids);
+            Ok(self.targets())
+        }
+
+        fn targets(&self) -> ProcessTargets {
+            ProcessTargets {
+                filtered: true,
+                pids: self.0.lock().unwrap().clone(),
+                ..Default::default()
+            }
+        }
+    }
+
+    /// The router as seen by a client at `peer`
+    fn from_peer(app: Router, peer: [u8; 4]) -> Router {
+        app.layer(MockConnectInfo(SocketAddr::from((peer, 40000))))
+    }
+
+    async fn post_pid(app: &Router, uri: &str, pid: u32) -> (StatusCode, serde_json::Value) {
+        let request = axum::http::Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!("{{\"pid\":{}}}", pid)))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        // A 401 has no body
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_capture_attach_detach() {
+        let (event_tx, _) = broadcast::channel(16);
+        let state = Arc::new(AppState {
+            event_tx,
+            trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            metrics: None,
+            process_targets: Some(Arc::new(FakeTargets::default())),
+            history: None,
+        });
+        let app = from_peer(router(state, &WebConfig::default()), [127, 0, 0, 1]);
+
+        let (status, body) = post_pid(&app, "/api/capture/attach", 42).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({"filtered": true, "pids": [42]}));
+
+        let (status, body) = post_pid(&app, "/api/capture/attach", 4242).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("OpenSSL"));
+
+        let (status, body) = post_pid(&app, "/api/capture/detach", 42).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pids"], serde_json::json!([]));
+
+        let (status, _) = post_pid(&app, "/api/capture/detach", 42).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Without a capture that supports it
+        let app = from_peer(router(test_state(), &WebConfig::default()), [127, 0, 0, 1]);
+        let (status, _) = post_pid(&app, "/api/capture/attach", 42).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[tokio::test]
+    async fn test_control_routes_guarded() {
+        let state = || {
+            let (event_tx, _) = broadcast::channel(16);
+            Arc::new(AppState {
+                event_tx,
+                trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+                events: Arc::new(RwLock::new(Vec::new())),
+                metrics: None,
+                process_targets: Some(Arc::new(FakeTargets::default())),
+                history: None,
+            })
+        };
+
+        // Without credentials, only loopback peers
+        let remote = from_peer(router(state(), &WebConfig::default()), [192, 168, 1, 20]);
+        let (status, _) = post_pid(&remote, "/api/capture/attach", 42).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let unknown_peer = router(state(), &WebConfig::default());
+        let (status, _) = post_pid(&unknown_peer, "/api/capture/attach", 42).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // A cross-site form post cannot send JSON
+        let local = from_peer(router(state(), &WebConfig::default()), [127, 0, 0, 1]);
+        let form = axum::http::Request::post("/api/capture/attach")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("{\"pid\":42}"))
+            .unwrap();
+        let response = local.clone().oneshot(form).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        // With credentials configured, any peer that presents them
+        let app = from_peer(
+            router(
+                state(),
+                &WebConfig {
+                    auth: Some(AuthConfig {
+                        bearer_token: Some("s3cret".to_string()),
+                        basic: None,
+                    }),
+                    ..Default::default()
+                },
+            ),
+            [192, 168, 1, 20],
+        );
+        let (status, _) = post_pid(&app, "/api/capture/attach", 42).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = axum::http::Request::post("/api/capture/attach")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, "Bearer s3cret")
+            .body(Body::from("{\"pid\":42}"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reset_and_series() {
+        let metrics = oisp_core::metrics::create_metrics();
+        let (event_tx, _) = broadcast::channel(16);
+        let state = Arc::new(AppState {
+            event_tx,
+            trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            metrics: Some(metrics.clone()),
+            process_targets: None,
+            history: None,
+        });
+        let app = from_peer(router(state, &WebConfig::default()), [127, 0, 0, 1]);
+
+        metrics
+            .pipeline
+            .events_exported
+            .fetch_add(7, std::sync::atomic::Ordering::Relaxed);
+        metrics.rollup();
+
+        let (status, body) = get_json(&app, "/api/metrics/series?window=5m").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["window_secs"], 300);
+        assert_eq!(
+            body["buckets"][0]["counters"]["pipeline.events_exported"],
+            7
+        );
+
+        // Without auth or a JSON body, e.g. a cross-site form post
+        let request = axum::http::Request::post("/api/metrics/reset")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let (_, body) = get_json(&app, "/api/metrics").await;
+        assert_eq!(body["pipeline"]["events_exported"], 7);
+
+        let request = axum::http::Request::post("/api/metrics/reset")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["counters"]["pipeline.events_exported"], 7);
+
+        let (_, body) = get_json(&app, "/api/metrics").await;
+        assert_eq!(body["pipeline"]["events_exported"], 0);
+
+        let (status, _) = get_json(&app, "/api/metrics/series?window=soon").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Without a metrics collector
+        let app = router(test_state(), &WebConfig::default());
+        let (status, _) = get_json(&app, "/api/metrics/series").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// `Access-Control-Allow-Origin` returned for a preflight from `origin`
+    async fn preflight(app: &Router, origin: &str) -> Option<String> {
+        let request = axum::http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/events")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowlist() {
+        let app = router(
+            test_state(),
+            &WebConfig {
+                cors_origins: vec!["https://dashboard.example.com/".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            preflight(&app, "https://dashboard.example.com")
+                .await
+                .as_deref(),
+            Some("https://dashboard.example.com")
+        );
+        assert_eq!(preflight(&app, "https://evil.example.com").await, None);
+        assert_eq!(preflight(&app, "http://localhost:3000").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_placeholder_without_frontend() {
+        let app = router_with_assets(test_state(), &WebConfig::default(), |_| None);
+
+        for uri in ["/", "/traces"] {
+            let response = get_status(&app, uri, None).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body.contains("does not include the web UI"), "{}", body);
+            assert!(body.contains(r#"href="/api/health""#));
+            assert!(body.contains("/ws"));
+        }
+
+        // The API itself is unaffected
+        let response = get_status(&app, "/api/health", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_cors_defaults_to_localhost() {
+        let app = router(test_state(), &WebConfig::default());
+        for origin in [
+            "http://localhost:3000",
+            "http://127.0.0.1:7777",
+            "https://[::1]:7777",
+        ] {
+            assert_eq!(preflight(&app, origin).await.as_deref(), Some(origin));
+        }
+        for origin in ["https://evil.example.com", "http://localhost.evil.com"] {
+            assert_eq!(preflight(&app, origin).await, None, "{}", origin);
+        }
+
+        let any = router(
+            test_state(),
+            &WebConfig {
+                cors_origins: vec!["*".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            preflight(&any, "https://evil.example.com").await.as_deref(),
+            Some("*")
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! HTTPS/WSS support for the web server
+//!
+//! When [`WebConfig::tls`](crate::WebConfig) is set, connections are
+//! terminated with rustls before reaching axum, so the API and the `/ws`
+//! WebSocket are served as `https://` and `wss://`. The certificate is read
+//! from PEM files and re-read on SIGHUP without restarting the server.
+
+use axum::serve::Listener;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+/// Handshakes slower than this are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS certificate configuration
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: PathBuf,
+}
+
+/// Certificate resolver whose certificate can be swapped at runtime
+#[derive(Debug)]
+pub struct ReloadableCert {
+    config: TlsConfig,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCert {
+    /// Load the certificate and key named in `config`
+    pub fn load(config: TlsConfig) -> anyhow::Result<Self> {
+        let key = load_certified_key(&config)?;
+        Ok(Self {
+            config,
+            current: RwLock::new(Arc::new(key)),
+        })
+    }
+
+    /// Re-read the certificate and key; the old pair stays in use on error
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let key = load_certified_key(&self.config)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        Ok(())
+    }
+
+    /// Certificate currently being served
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn load_certified_key(config: &TlsConfig) -> anyhow::Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut open(&config.cert_path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            anyhow::anyhow!("Invalid certificate {}: {}", config.cert_path.display(), e)
+        })?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", config.cert_path.display());
+    }
+
+    let key = rustls_pemfile::private_key(&mut open(&config.key_path)?)
+        .map_err(|e| anyhow::anyhow!("Invalid private key {}: {}", config.key_path.display(), e))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", config.key_path.display()))?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| anyhow::anyhow!("Unsupported private key: {}", e))?;
+
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+fn open(path: &Path) -> anyhow::Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+/// Build a TLS acceptor serving the resolver's certificate
+pub fn acceptor(cert: Arc<ReloadableCert>) -> anyhow::Result<TlsAcceptor> {
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_cert_resolver(cert);
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Reload the certificate whenever the process receives SIGHUP
+#[cfg(unix)]
+pub fn reload_on_sighup(cert: Arc<ReloadableCert>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Cannot watch SIGHUP for TLS certificate reload: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match cert.reload() {
+                Ok(()) => info!("Reloaded web TLS certificate"),
+                Err(e) => warn!(
+                    "Failed to reload web TLS certificate, keeping current: {}",
+                    e
+                ),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_cert: Arc<ReloadableCert>) {}
+
+/// Listener that yields connections after a completed TLS handshake
+///
+/// Handshakes run in their own tasks so a slow client cannot hold up
+/// accepting others.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Start accepting TLS connections on `listener`
+    pub fn new(listener: TcpListener, acceptor: TlsAcceptor) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        debug!("Accept error: {}", e);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        continue;
+                    }
+                };
+                if tx.is_closed() {
+                    break;
+                }
+
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, addr)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(conn) => conn,
+            // The accept task only stops once we are dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_cert {
+    use super::TlsConfig;
+    use std::path::Path;
+
+    /// Write a self-signed certificate for `localhost` into `dir`
+    pub fn write(dir: &Path) -> (TlsConfig, String) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_pem = cert.cert.pem();
+        let config = TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        std::fs::write(&config.cert_path, &cert_pem).unwrap();
+        std::fs::write(&config.key_path, cert.key_pair.serialize_pem()).unwrap();
+        (config, cert_pem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_swaps_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, _) = test_cert::write(dir.path());
+        let cert = ReloadableCert::load(config.clone()).unwrap();
+        let before = cert.current().cert[0].clone();
+
+        test_cert::write(dir.path());
+        cert.reload().unwrap();
+        assert_ne!(cert.current().cert[0], before);
+
+        // A broken file leaves the current certificate in place
+        std::fs::write(&config.key_path, "not a key").unwrap();
+        let current = cert.current().cert[0].clone();
+        assert!(cert.reload().is_err());
+        assert_eq!(cert.current().cert[0], current);
+    }
+
+    #[test]
+    fn test_load_missing_files() {
+        let err = ReloadableCert::load(TlsConfig {
+            cert_path: "/nonexistent/cert.pem".into(),
+            key_path: "/nonexistent/key.pem".into(),
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/cert.pem"));
+    }
+}
@@ -1,6 +1,18 @@
 //! WebSocket handler for real-time updates
 //!
 //! Sends events in WebEvent format for easy frontend consumption.
+//!
+//! A client that only wants some events sends a subscription, and the server
+//! then forwards matching events only:
+//!
+//! ```json
+//! {"subscribe": {"event_types": ["ai.request"], "pids": [4242], "providers": ["openai"]}}
+//! ```
+//!
+//! Each list that is present and non-empty must match; `{"subscribe": {}}`
+//! goes back to all events. A new subscription replaces the previous one and
+//! is acknowledged with `{"subscribed": {...}}`; an invalid message gets
+//! `{"error": "..."}` and leaves the subscription unchanged.
 
 use crate::web_event::WebEvent;
 use crate::AppState;
@@ -11,9 +23,70 @@ use axum::{
     },
     response::Response,
 };
+use oisp_core::events::OispEvent;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::{debug, error};
 
+/// Events a client wants; empty lists match everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Subscription {
+    /// Event types, e.g. `ai.request`
+    pub event_types: Vec<String>,
+
+    /// Process IDs
+    pub pids: Vec<u32>,
+
+    /// AI providers, e.g. `openai` (case-insensitive)
+    pub providers: Vec<String>,
+}
+
+impl Subscription {
+    /// Whether `event` should be sent to the client
+    pub fn matches(&self, event: &OispEvent) -> bool {
+        if !self.event_types.is_empty() && !self.event_types.iter().any(|t| t == event.event_type())
+        {
+            return false;
+        }
+
+        if !self.pids.is_empty() {
+            let pid = event.envelope().process.as_ref().map(|p| p.pid);
+            if !pid.is_some_and(|pid| self.pids.contains(&pid)) {
+                return false;
+            }
+        }
+
+        if !self.providers.is_empty() {
+            let provider = event_provider(event);
+            if !provider
+                .is_some_and(|name| self.providers.iter().any(|p| p.eq_ignore_ascii_case(name)))
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Provider name of AI events that carry one
+fn event_provider(event: &OispEvent) -> Option<&str> {
+    let provider = match event {
+        OispEvent::AiRequest(e) => e.data.provider.as_ref(),
+        OispEvent::AiResponse(e) => e.data.provider.as_ref(),
+        OispEvent::AiEmbedding(e) => e.data.provider.as_ref(),
+        _ => None,
+    };
+    provider.map(|p| p.name.as_str())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientMessage {
+    subscribe: Subscription,
+}
+
 pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
     ws.on_upgrade(|socket| handle_socket(socket, state))
 }
@@ -22,12 +95,16 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
     debug!("WebSocket client connected");
 
     let mut rx = state.event_tx.subscribe();
+    let mut subscription = Subscription::default();
 
     loop {
         tokio::select! {
             result = rx.recv() => {
                 match result {
                     Ok(event) => {
+                        if !subscription.matches(&event) {
+                            continue;
+                        }
                         // Convert to WebEvent format for frontend
                         let web_event = WebEvent::from_oisp_event(event.as_ref());
                         if let Ok(json) = serde_json::to_string(&web_event) {
@@ -46,7 +123,24 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
                 match msg {
                     Some(Ok(Message::Close(_))) | None => break,
                     Some(Ok(Message::Ping(data))) => {
-                        if socket.send(Message::Pong(data)).await.is_err() {
+                        let sent = socket.send(Message::Pong(data)).await;
+                        if sent.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Text(text))) => {
+                        let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                            Ok(message) => {
+                                debug!(subscription = ?message.subscribe, "WebSocket subscription updated");
+                                subscription = message.subscribe;
+                                serde_json::json!({ "subscribed": subscription })
+                            }
+                            Err(e) => {
+                                debug!("Invalid WebSocket message: {}", e);
+                                serde_json::json!({ "error": e.to_string() })
+                            }
+                        };
+                        if socket.send(Message::Text(reply.to_string().into())).await.is_err() {
                             break;
                         }
                     }
@@ -58,3 +152,143 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
 
     debug!("WebSocket client disconnected");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{router, WebConfig};
+    use futures_util::{SinkExt, StreamExt};
+    use oisp_core::events::{AiRequestEvent, EventEnvelope, ProcessExecEvent, ProcessInfo};
+    use oisp_core::trace::TraceBuilder;
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+    use tokio::sync::{broadcast, RwLock};
+    use tokio_tungstenite::tungstenite::Message as ClientMsg;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn envelope(event_type: &str, pid: u32) -> EventEnvelope {
+        let mut envelope = EventEnvelope::new(event_type);
+        envelope.process = Some(ProcessInfo {
+            pid,
+            ..Default::default()
+        });
+        envelope
+    }
+
+    fn exec(pid: u32) -> Arc<OispEvent> {
+        Arc::new(OispEvent::ProcessExec(ProcessExecEvent {
+            envelope: envelope("process.exec", pid),
+            data: serde_json::from_value(serde_json::json!({"exe": "/usr/bin/curl"})).unwrap(),
+        }))
+    }
+
+    fn ai_request(pid: u32, provider: &str) -> Arc<OispEvent> {
+        Arc::new(OispEvent::AiRequest(AiRequestEvent {
+            envelope: envelope("ai.request", pid),
+            data: serde_json::from_value(serde_json::json!({
+                "request_id": format!("req-{}", pid),
+                "provider": {"name": provider}
+            }))
+            .unwrap(),
+        }))
+    }
+
+    async fn serve() -> (broadcast::Sender<Arc<OispEvent>>, Client) {
+        let (event_tx, _) = broadcast::channel(16);
+        let state = Arc::new(AppState {
+            event_tx: event_tx.clone(),
+            trace_builder: Arc::new(RwLock::new(TraceBuilder::new())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            metrics: None,
+            process_targets: None,
+            history: None,
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            async move { axum::serve(listener, router(state, &WebConfig::default())).await },
+        );
+
+        let (client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+            .await
+            .unwrap();
+        (event_tx, client)
+    }
+
+    async fn next_json(client: &mut Client) -> serde_json::Value {
+        let msg = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no message from server")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    async fn subscribe(client: &mut Client, filter: serde_json::Value) -> serde_json::Value {
+        let message = serde_json::json!({ "subscribe": filter }).to_string();
+        client.send(ClientMsg::Text(message)).await.unwrap();
+        next_json(client).await
+    }
+
+    #[test]
+    fn test_subscription_matches() {
+        let all = Subscription::default();
+        assert!(all.matches(&exec(1)));
+
+        let filter = Subscription {
+            event_types: vec!["ai.request".to_string()],
+            pids: vec![10, 20],
+            providers: vec!["OpenAI".to_string()],
+        };
+        assert!(filter.matches(&ai_request(10, "openai")));
+        assert!(!filter.matches(&ai_request(30, "openai")));
+        assert!(!filter.matches(&ai_request(10, "anthropic")));
+        assert!(!filter.matches(&exec(10)));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_filters_events() {
+        let (event_tx, mut client) = serve().await;
+
+        let ack = subscribe(
+            &mut client,
+            serde_json::json!({"event_types": ["ai.request"]}),
+        )
+        .await;
+        assert_eq!(ack["subscribed"]["event_types"][0], "ai.request");
+
+        event_tx.send(exec(1)).unwrap();
+        event_tx.send(ai_request(2, "openai")).unwrap();
+        let event = next_json(&mut client).await;
+        assert_eq!(event["type"], "ai_prompt");
+        assert_eq!(event["pid"], 2);
+
+        // Updated mid-connection: only anthropic requests now
+        subscribe(&mut client, serde_json::json!({"providers": ["anthropic"]})).await;
+        event_tx.send(ai_request(3, "openai")).unwrap();
+        event_tx.send(exec(4)).unwrap();
+        event_tx.send(ai_request(5, "anthropic")).unwrap();
+        let event = next_json(&mut client).await;
+        assert_eq!(event["pid"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_subscription_is_rejected() {
+        let (event_tx, mut client) = serve().await;
+
+        subscribe(&mut client, serde_json::json!({"pids": [7]})).await;
+        client
+            .send(ClientMsg::Text(r#"{"subscribe": {"pid": 8}}"#.to_string()))
+            .await
+            .unwrap();
+        let reply = next_json(&mut client).await;
+        assert!(reply["error"].as_str().unwrap().contains("pid"));
+
+        // The previous subscription still applies
+        event_tx.send(exec(8)).unwrap();
+        event_tx.send(exec(7)).unwrap();
+        assert_eq!(next_json(&mut client).await["pid"], 7);
+    }
+}
@@ -0,0 +1,66 @@
+'use client';
+
+import { useState, useEffect, useCallback } from 'react';
+
+const API_BASE = typeof window !== 'undefined'
+  ? `${window.location.protocol}//${window.location.host}`
+  : 'http://localhost:7777';
+
+export interface ProcessTreeNode {
+  pid: number;
+  ppid: number | null;
+  name: string;
+  exe: string | null;
+  ai_request_count: number;
+  /** Set on the synthetic root holding processes whose parent was never seen */
+  synthetic?: boolean;
+  children: ProcessTreeNode[];
+}
+
+export interface ProcessTreeResponse {
+  processes: ProcessTreeNode[];
+  total: number;
+}
+
+interface UseProcessTreeReturn {
+  tree: ProcessTreeNode[];
+  total: number;
+  loading: boolean;
+  error: string | null;
+  refresh: () => Promise<void>;
+}
+
+export function useProcessTree(refreshInterval = 3000): UseProcessTreeReturn {
+  const [tree, setTree] = useState<ProcessTreeNode[]>([]);
+  const [total, setTotal] = useState(0);
+  const [loading, setLoading] = useState(true);
+  const [error, setError] = useState<string | null>(null);
+
+  const refresh = useCallback(async () => {
+    try {
+      setError(null);
+
+      const response = await fetch(`${API_BASE}/api/process-tree`);
+      if (!response.ok) {
+        throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+      }
+
+      const data: ProcessTreeResponse = await response.json();
+      setTree(data.processes);
+      setTotal(data.total);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to fetch process tree');
+    } finally {
+      setLoading(false);
+    }
+  }, []);
+
+  useEffect(() => {
+    refresh();
+
+    const interval = setInterval(refresh, refreshInterval);
+    return () => clearInterval(interval);
+  }, [refresh, refreshInterval]);
+
+  return { tree, total, loading, error, refresh };
+}
@@ -6,11 +6,19 @@ const API_BASE = typeof window !== 'undefined'
   ? `${window.location.protocol}//${window.location.host}`
   : 'http://localhost:7777';
 
+export interface CaptureError {
+  plugin: string;
+  kind: 'start' | 'permission_denied' | 'attach_failed' | 'io' | 'stopped' | 'other';
+  message: string;
+  timestamp: string;
+}
+
 export interface Stats {
   total_events: number;
   ai_events: number;
   active_traces: number;
   uptime_seconds: number;
+  capture_errors: CaptureError[];
 }
 
 interface UseStatsReturn {
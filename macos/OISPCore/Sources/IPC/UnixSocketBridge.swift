@@ -2,10 +2,20 @@
 // OISPCore
 //
 // Unix domain socket client for sending events to oisp-sensor (Rust)
+//
+// Each connection starts with a hello offering binary frames (see
+// crates/oisp-core/src/wire.rs). Events are sent as frames if the sensor
+// accepts, and as JSON lines otherwise.
 
 import Foundation
 import Network
 
+/// Event encoding negotiated with the sensor
+public enum WireEncoding: String, Sendable {
+    case json
+    case binary
+}
+
 /// Protocol for emitting raw capture events
 public protocol EventEmitter: Sendable {
     func emit(_ event: RawCaptureEvent) async throws
@@ -36,6 +46,16 @@ public actor UnixSocketBridge: EventEmitter {
     /// Maximum number of pending events to buffer during disconnection
     private let maxPendingEvents = 10000
 
+    /// Encoding for this connection; nil until the handshake completes
+    private var encoding: WireEncoding?
+
+    /// Handshake protocol version (oisp-core `wire::PROTOCOL_VERSION`)
+    static let protocolVersion = 1
+
+    /// How long to wait for the hello reply before sending JSON, as to a
+    /// sensor that predates the handshake
+    static let handshakeTimeout: TimeInterval = 2.0
+
     // MARK: - Statistics
 
     private var eventsSent: UInt64 = 0
@@ -57,7 +77,7 @@ public actor UnixSocketBridge: EventEmitter {
     // MARK: - Connection Management
 
     public var isConnected: Bool {
-        connectionState == .ready
+        connectionState == .ready && encoding != nil
     }
 
     public func connect() async throws {
@@ -88,9 +108,9 @@ public actor UnixSocketBridge: EventEmitter {
         // Start connection
         conn.start(queue: .global(qos: .userInteractive))
 
-        // Wait for connection to be ready
+        // Wait for connection to be ready and the handshake to finish
         for _ in 0..<100 { // 10 second timeout
-            if connectionState == .ready {
+            if isConnected {
                 return
             }
             if case .failed = connectionState {
@@ -108,17 +128,22 @@ public actor UnixSocketBridge: EventEmitter {
         switch state {
         case .ready:
             reconnectAttempts = 0
+            if let conn = connection {
+                encoding = await negotiateEncoding(on: conn)
+            }
             // Send any pending events
             await flushPendingEvents()
 
         case .failed(let error):
             connection = nil
+            encoding = nil
             if shouldReconnect {
                 await attemptReconnect(error: error)
             }
 
         case .cancelled:
             connection = nil
+            encoding = nil
 
         default:
             break
@@ -147,23 +172,92 @@ public actor UnixSocketBridge: EventEmitter {
         connection = nil
     }
 
+    // MARK: - Handshake
+
+    /// Offer binary frames and read the sensor's choice
+    ///
+    /// Any failure, or no reply within `handshakeTimeout`, falls back to
+    /// JSON; a sensor without the handshake logs the hello as a malformed
+    /// event and reads JSON.
+    private func negotiateEncoding(on conn: NWConnection) async -> WireEncoding {
+        let hello = "{\"oisp_hello\":{\"version\":\(Self.protocolVersion),\"encodings\":[\"binary\",\"json\"]}}\n"
+        do {
+            try await send(Data(hello.utf8), on: conn)
+        } catch {
+            return .json
+        }
+
+        let reply: Data? = await withCheckedContinuation { continuation in
+            let once = ResumeOnce(continuation)
+            DispatchQueue.global().asyncAfter(deadline: .now() + Self.handshakeTimeout) {
+                once.resume(nil)
+            }
+            Self.receiveLine(on: conn, buffer: Data()) { once.resume($0) }
+        }
+
+        struct HelloAckLine: Decodable {
+            struct Ack: Decodable {
+                let version: Int
+                let encoding: String
+            }
+            let oisp_hello_ack: Ack
+        }
+        guard let reply,
+              let ack = try? JSONDecoder().decode(HelloAckLine.self, from: reply),
+              let encoding = WireEncoding(rawValue: ack.oisp_hello_ack.encoding) else {
+            return .json
+        }
+        return encoding
+    }
+
+    /// Read up to the first newline; nil if the connection ends first
+    private static func receiveLine(
+        on conn: NWConnection,
+        buffer: Data,
+        completion: @escaping @Sendable (Data?) -> Void
+    ) {
+        conn.receive(minimumIncompleteLength: 1, maximumLength: 4096) { data, _, _, error in
+            guard error == nil, let data, !data.isEmpty else {
+                completion(nil)
+                return
+            }
+            var buffer = buffer
+            buffer.append(data)
+            if let newline = buffer.firstIndex(of: 0x0A) {
+                completion(buffer[buffer.startIndex..<newline])
+            } else {
+                receiveLine(on: conn, buffer: buffer, completion: completion)
+            }
+        }
+    }
+
     // MARK: - Event Emission
 
     public func emit(_ event: RawCaptureEvent) async throws {
         // If not connected, buffer the event
-        guard let conn = connection, connectionState == .ready else {
+        guard let conn = connection, connectionState == .ready, let encoding else {
             await bufferEvent(event)
             return
         }
 
-        // Serialize to JSON
-        let jsonData = try event.toJSON()
+        let data: Data
+        switch encoding {
+        case .binary:
+            data = event.toBinaryFrame()
+        case .json:
+            // JSON with a newline delimiter
+            var json = try event.toJSON()
+            json.append(contentsOf: [0x0A])
+            data = json
+        }
+
+        try await send(data, on: conn)
 
-        // Add newline delimiter
-        var data = jsonData
-        data.append(contentsOf: [0x0A]) // newline
+        eventsSent += 1
+        bytesTransferred += UInt64(data.count)
+    }
 
-        // Send data
+    private func send(_ data: Data, on conn: NWConnection) async throws {
         try await withCheckedThrowingContinuation { (continuation: CheckedContinuation<Void, Error>) in
             conn.send(content: data, completion: .contentProcessed { error in
                 if let error = error {
@@ -173,9 +267,6 @@ public actor UnixSocketBridge: EventEmitter {
                 }
             })
         }
-
-        eventsSent += 1
-        bytesTransferred += UInt64(data.count)
     }
 
     private func bufferEvent(_ event: RawCaptureEvent) async {
@@ -217,6 +308,25 @@ public actor UnixSocketBridge: EventEmitter {
     }
 }
 
+/// Resumes a continuation once, for whichever of a reply and a timeout
+/// comes first
+private final class ResumeOnce: @unchecked Sendable {
+    private let lock = NSLock()
+    private var continuation: CheckedContinuation<Data?, Never>?
+
+    init(_ continuation: CheckedContinuation<Data?, Never>) {
+        self.continuation = continuation
+    }
+
+    func resume(_ value: Data?) {
+        lock.lock()
+        let continuation = self.continuation
+        self.continuation = nil
+        lock.unlock()
+        continuation?.resume(returning: value)
+    }
+}
+
 // MARK: - Errors
 
 public enum UnixSocketError: Error, LocalizedError {
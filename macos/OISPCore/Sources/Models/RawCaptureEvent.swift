@@ -136,6 +136,78 @@ public struct RawCaptureEvent: Codable, Sendable {
         let data = try toJSON()
         return String(data: data, encoding: .utf8) ?? ""
     }
+
+    /// Serialize as a length-prefixed binary frame, for a connection that
+    /// negotiated binary encoding
+    ///
+    /// The layout is documented in crates/oisp-core/src/wire.rs: integers
+    /// are little-endian, strings and the payload are prefixed with a
+    /// `UInt32` length, and `flags` marks which optional fields follow.
+    public func toBinaryFrame() -> Data {
+        var flags: UInt16 = Self.flagUid | Self.flagComm | Self.flagExe
+        if tid != nil { flags |= Self.flagTid }
+        if metadata.ppid != nil { flags |= Self.flagPpid }
+        if metadata.fd != nil { flags |= Self.flagFd }
+        if remotePort != nil { flags |= Self.flagRemotePort }
+        if remoteHost != nil { flags |= Self.flagRemoteAddr }
+        if metadata.bundleId != nil { flags |= Self.flagBundleId }
+
+        var body = Data()
+        body.append(kind.wireCode)
+        body.appendLittleEndian(flags)
+        body.appendLittleEndian(timestampNs)
+        body.appendLittleEndian(pid)
+        if let tid { body.appendLittleEndian(tid) }
+        if let ppid = metadata.ppid { body.appendLittleEndian(ppid) }
+        body.appendLittleEndian(metadata.uid)
+        if let fd = metadata.fd { body.appendLittleEndian(fd) }
+        if let remotePort { body.appendLittleEndian(remotePort) }
+
+        body.appendPrefixed(Data(id.utf8))
+        body.appendPrefixed(Data(metadata.comm.utf8))
+        body.appendPrefixed(Data(metadata.exe.utf8))
+        if let remoteHost { body.appendPrefixed(Data(remoteHost.utf8)) }
+        if let bundleId = metadata.bundleId { body.appendPrefixed(Data(bundleId.utf8)) }
+        body.appendPrefixed(Data(base64Encoded: data) ?? Data())
+
+        var frame = Data()
+        frame.appendLittleEndian(UInt32(body.count))
+        frame.append(body)
+        return frame
+    }
+
+    // Presence flags for optional frame fields (oisp-core `wire::FLAG_*`)
+    static let flagTid: UInt16 = 1 << 0
+    static let flagPpid: UInt16 = 1 << 1
+    static let flagUid: UInt16 = 1 << 2
+    static let flagFd: UInt16 = 1 << 3
+    static let flagRemotePort: UInt16 = 1 << 4
+    static let flagComm: UInt16 = 1 << 6
+    static let flagExe: UInt16 = 1 << 7
+    static let flagRemoteAddr: UInt16 = 1 << 9
+    static let flagBundleId: UInt16 = 1 << 11
+}
+
+extension RawEventKind {
+    /// Kind code in a binary frame (oisp-core `wire::kind_code`)
+    var wireCode: UInt8 {
+        switch self {
+        case .sslWrite: return 0
+        case .sslRead: return 1
+        }
+    }
+}
+
+extension Data {
+    mutating func appendLittleEndian<T: FixedWidthInteger>(_ value: T) {
+        withUnsafeBytes(of: value.littleEndian) { append(contentsOf: $0) }
+    }
+
+    /// Append `bytes` after their `UInt32` length
+    mutating func appendPrefixed(_ bytes: Data) {
+        appendLittleEndian(UInt32(bytes.count))
+        append(bytes)
+    }
 }
 
 // MARK: - Convenience Extensions
@@ -55,6 +55,28 @@ final class OISPCoreTests: XCTestCase {
         XCTAssertTrue(jsonString.contains("\"comm\":\"curl\""))
     }
 
+    func testRawCaptureEventBinaryFrame() throws {
+        let metadata = RawEventMetadata(comm: "curl", exe: "/usr/bin/curl", uid: 501)
+        let event = RawCaptureEvent(
+            kind: .sslRead,
+            pid: 1234,
+            data: "HTTP/1.1 200 OK".data(using: .utf8)!,
+            metadata: metadata,
+            remoteHost: "api.openai.com",
+            remotePort: 443
+        )
+
+        let frame = [UInt8](event.toBinaryFrame())
+        let length = frame[0..<4].enumerated().reduce(0) { $0 | Int($1.element) << (8 * $1.offset) }
+        XCTAssertEqual(length, frame.count - 4)
+        XCTAssertEqual(frame[4], 1) // SslRead
+        // uid, remote_port, comm, exe and remote_addr present
+        XCTAssertEqual(frame[5], 0xD4)
+        XCTAssertEqual(frame[6], 0x02)
+        // The payload is sent as raw bytes at the end of the frame
+        XCTAssertEqual(Data(frame.suffix(15)), "HTTP/1.1 200 OK".data(using: .utf8)!)
+    }
+
     // MARK: - AIEndpointFilter Tests
 
     func testAIEndpointFilterExactMatch() {